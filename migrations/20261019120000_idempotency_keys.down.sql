@@ -0,0 +1,2 @@
+DROP index idx__idempotency_keys__expires_at;
+DROP TABLE idempotency_keys;
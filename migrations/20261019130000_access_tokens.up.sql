@@ -0,0 +1,21 @@
+CREATE TABLE access_tokens (
+    id UUID,
+    user_id UUID NOT NULL,
+    name varchar(128) NOT NULL,
+    -- hex sha256 of the token
+    token_hash TEXT NOT NULL,
+    -- space separated
+    scopes TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    expires_at TIMESTAMPTZ,
+    last_used_at TIMESTAMPTZ,
+
+    PRIMARY KEY(id),
+    CONSTRAINT fk__user_id__users__id
+        FOREIGN KEY(user_id)
+            REFERENCES users(id)
+            ON DELETE CASCADE
+);
+
+create unique index idx__access_tokens__token_hash on access_tokens using btree (token_hash);
+create index idx__access_tokens__user_id on access_tokens using btree (user_id);
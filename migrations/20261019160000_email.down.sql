@@ -0,0 +1,5 @@
+DROP index idx__email_tokens__user_id;
+DROP TABLE email_tokens;
+DROP index idx__users__verified_email;
+ALTER TABLE users DROP COLUMN email_verified;
+ALTER TABLE users DROP COLUMN email;
@@ -0,0 +1,18 @@
+CREATE TABLE user_identities (
+    id UUID,
+    user_id UUID NOT NULL,
+    -- issuer and subject of OpenID Connect ID token
+    issuer TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    email varchar(254),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+
+    PRIMARY KEY(id),
+    CONSTRAINT fk__user_id__users__id
+        FOREIGN KEY(user_id)
+            REFERENCES users(id)
+            ON DELETE CASCADE
+);
+
+create unique index idx__user_identities__issuer__subject on user_identities using btree (issuer, subject);
+create index idx__user_identities__user_id on user_identities using btree (user_id);
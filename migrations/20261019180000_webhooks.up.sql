@@ -0,0 +1,46 @@
+CREATE TABLE webhooks (
+    id UUID,
+    user_id UUID NOT NULL,
+    url varchar(2048) NOT NULL,
+    -- key of HMAC-SHA256 signature, must be readable for signing
+    secret varchar(128) NOT NULL,
+    -- space separated
+    events TEXT NOT NULL,
+    enabled BOOLEAN NOT NULL DEFAULT true,
+    consecutive_failures INTEGER NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+
+    PRIMARY KEY(id),
+    CONSTRAINT fk__user_id__users__id
+        FOREIGN KEY(user_id)
+            REFERENCES users(id)
+            ON DELETE CASCADE
+);
+
+create index idx__webhooks__user_id on webhooks using btree (user_id);
+
+CREATE TABLE webhook_deliveries (
+    id UUID,
+    webhook_id UUID NOT NULL,
+    event varchar(32) NOT NULL,
+    -- request body, json
+    payload TEXT NOT NULL,
+    -- pending, succeeded, failed
+    status varchar(16) NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_status_code INTEGER,
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    last_attempt_at TIMESTAMPTZ,
+    -- set while pending
+    next_attempt_at TIMESTAMPTZ,
+
+    PRIMARY KEY(id),
+    CONSTRAINT fk__webhook_id__webhooks__id
+        FOREIGN KEY(webhook_id)
+            REFERENCES webhooks(id)
+            ON DELETE CASCADE
+);
+
+create index idx__webhook_deliveries__webhook_id__created_at on webhook_deliveries using btree (webhook_id, created_at);
+create index idx__webhook_deliveries__next_attempt_at on webhook_deliveries using btree (next_attempt_at) where status = 'pending';
@@ -0,0 +1,15 @@
+CREATE TABLE calendar_feeds (
+    user_id UUID,
+    -- hex sha256 of the token
+    token_hash TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    last_used_at TIMESTAMPTZ,
+
+    PRIMARY KEY(user_id),
+    CONSTRAINT fk__user_id__users__id
+        FOREIGN KEY(user_id)
+            REFERENCES users(id)
+            ON DELETE CASCADE
+);
+
+create unique index idx__calendar_feeds__token_hash on calendar_feeds using btree (token_hash);
@@ -0,0 +1,4 @@
+ALTER TABLE tasks DROP COLUMN rrule;
+ALTER TABLE tasks DROP COLUMN priority;
+ALTER TABLE tasks DROP COLUMN completed_at;
+ALTER TABLE tasks DROP COLUMN due_at;
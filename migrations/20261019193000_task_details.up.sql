@@ -0,0 +1,7 @@
+-- time the task is due and time it was completed, open task has no completion time
+ALTER TABLE tasks ADD COLUMN due_at TIMESTAMPTZ;
+ALTER TABLE tasks ADD COLUMN completed_at TIMESTAMPTZ;
+-- 1 is the highest and 9 is the lowest, like PRIORITY of iCalendar
+ALTER TABLE tasks ADD COLUMN priority INTEGER;
+-- recurrence rule of iCalendar (RFC 5545 3.3.10), e.g. FREQ=WEEKLY;BYDAY=MO
+ALTER TABLE tasks ADD COLUMN rrule TEXT;
@@ -0,0 +1,15 @@
+DROP TRIGGER todo_lists__name_seq ON todo_lists;
+DROP TRIGGER tasks__tombstone ON tasks;
+DROP TRIGGER tasks__change_seq ON tasks;
+DROP FUNCTION todo_lists__count_rename;
+DROP FUNCTION tasks__count_delete;
+DROP FUNCTION tasks__count_change;
+DROP FUNCTION next_change_seq;
+DROP index idx__task_tombstones__todo_list_id;
+DROP TABLE task_tombstones;
+ALTER TABLE tasks DROP COLUMN moved_at;
+ALTER TABLE tasks DROP COLUMN updated_at;
+ALTER TABLE tasks DROP COLUMN change_seq;
+ALTER TABLE tasks DROP COLUMN created_seq;
+ALTER TABLE todo_lists DROP COLUMN name_seq;
+ALTER TABLE todo_lists DROP COLUMN change_seq;
@@ -0,0 +1,87 @@
+-- change sequence of the list: counter of task changes, name change is counted too
+ALTER TABLE todo_lists ADD COLUMN change_seq BIGINT NOT NULL DEFAULT 0;
+-- sequence number of the last name change
+ALTER TABLE todo_lists ADD COLUMN name_seq BIGINT NOT NULL DEFAULT 0;
+
+ALTER TABLE tasks ADD COLUMN created_seq BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE tasks ADD COLUMN change_seq BIGINT NOT NULL DEFAULT 0;
+-- time of the last description change and the last move, used to resolve conflicts of sync
+ALTER TABLE tasks ADD COLUMN updated_at TIMESTAMPTZ NOT NULL DEFAULT now();
+ALTER TABLE tasks ADD COLUMN moved_at TIMESTAMPTZ NOT NULL DEFAULT now();
+
+-- existing data is the first change
+UPDATE todo_lists SET change_seq = 1, name_seq = 1;
+UPDATE tasks SET created_seq = 1, change_seq = 1;
+
+CREATE TABLE task_tombstones (
+    task_id UUID,
+    todo_list_id UUID NOT NULL,
+    change_seq BIGINT NOT NULL,
+    deleted_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+
+    PRIMARY KEY(task_id),
+    CONSTRAINT fk__todo_list_id__todo_lists__id
+        FOREIGN KEY(todo_list_id)
+            REFERENCES todo_lists(id)
+            ON DELETE CASCADE
+);
+
+create index idx__task_tombstones__todo_list_id on task_tombstones using btree (todo_list_id, change_seq);
+
+CREATE FUNCTION next_change_seq(list_id UUID) RETURNS BIGINT AS $$
+    UPDATE todo_lists
+    SET change_seq = change_seq + 1
+    WHERE id = list_id
+    RETURNING change_seq;
+$$ LANGUAGE SQL;
+
+CREATE FUNCTION tasks__count_change() RETURNS TRIGGER AS $$
+BEGIN
+    NEW.change_seq = next_change_seq(NEW.todo_list_id);
+
+    IF TG_OP = 'INSERT' THEN
+        NEW.created_seq = NEW.change_seq;
+        -- task with id chosen by client (CalDAV) could be deleted before
+        DELETE FROM task_tombstones WHERE task_id = NEW.id;
+    END IF;
+
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE FUNCTION tasks__count_delete() RETURNS TRIGGER AS $$
+DECLARE
+    seq BIGINT;
+BEGIN
+    seq = next_change_seq(OLD.todo_list_id);
+
+    -- list is deleted too, tombstone is not needed
+    IF seq IS NOT NULL THEN
+        INSERT INTO task_tombstones (task_id, todo_list_id, change_seq)
+        VALUES (OLD.id, OLD.todo_list_id, seq);
+    END IF;
+
+    RETURN OLD;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE FUNCTION todo_lists__count_rename() RETURNS TRIGGER AS $$
+BEGIN
+    NEW.change_seq = OLD.change_seq + 1;
+    NEW.name_seq = NEW.change_seq;
+
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER tasks__change_seq
+    BEFORE INSERT OR UPDATE OF description, "order", due_at, completed_at, priority, rrule ON tasks
+    FOR EACH ROW EXECUTE FUNCTION tasks__count_change();
+
+CREATE TRIGGER tasks__tombstone
+    AFTER DELETE ON tasks
+    FOR EACH ROW EXECUTE FUNCTION tasks__count_delete();
+
+CREATE TRIGGER todo_lists__name_seq
+    BEFORE UPDATE OF name ON todo_lists
+    FOR EACH ROW EXECUTE FUNCTION todo_lists__count_rename();
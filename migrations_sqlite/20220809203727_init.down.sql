@@ -0,0 +1,4 @@
+DROP TABLE tasks;
+DROP index idx__user_id;
+DROP TABLE todo_lists;
+DROP TABLE users;
\ No newline at end of file
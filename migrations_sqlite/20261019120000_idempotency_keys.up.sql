@@ -0,0 +1,14 @@
+CREATE TABLE idempotency_keys (
+    scope TEXT NOT NULL,
+    key TEXT NOT NULL,
+    fingerprint TEXT NOT NULL,
+    status_code INT,
+    content_type TEXT,
+    body BLOB,
+    -- unix time in seconds
+    expires_at INTEGER NOT NULL,
+
+    PRIMARY KEY(scope, key)
+);
+
+create index idx__idempotency_keys__expires_at on idempotency_keys (expires_at);
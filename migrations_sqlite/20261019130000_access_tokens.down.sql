@@ -0,0 +1,3 @@
+DROP index idx__access_tokens__user_id;
+DROP index idx__access_tokens__token_hash;
+DROP TABLE access_tokens;
@@ -0,0 +1,22 @@
+CREATE TABLE access_tokens (
+    id BLOB,
+    user_id BLOB NOT NULL,
+    name varchar(128) NOT NULL,
+    -- hex sha256 of the token
+    token_hash TEXT NOT NULL,
+    -- space separated
+    scopes TEXT NOT NULL,
+    -- unix time in seconds
+    created_at INTEGER NOT NULL,
+    expires_at INTEGER,
+    last_used_at INTEGER,
+
+    PRIMARY KEY(id),
+    CONSTRAINT fk__user_id__users__id
+        FOREIGN KEY(user_id)
+            REFERENCES users(id)
+            ON DELETE CASCADE
+);
+
+create unique index idx__access_tokens__token_hash on access_tokens (token_hash);
+create index idx__access_tokens__user_id on access_tokens (user_id);
@@ -0,0 +1,25 @@
+CREATE TABLE totp_secrets (
+    user_id BLOB,
+    secret BLOB NOT NULL,
+    enabled BOOLEAN NOT NULL DEFAULT 0,
+    -- last accepted time step, codes of the same or earlier steps are rejected
+    last_used_step INTEGER,
+
+    PRIMARY KEY(user_id),
+    CONSTRAINT fk__user_id__users__id
+        FOREIGN KEY(user_id)
+            REFERENCES users(id)
+            ON DELETE CASCADE
+);
+
+CREATE TABLE recovery_codes (
+    user_id BLOB NOT NULL,
+    -- hex sha256 of normalized code
+    code_hash TEXT NOT NULL,
+
+    PRIMARY KEY(user_id, code_hash),
+    CONSTRAINT fk__user_id__users__id
+        FOREIGN KEY(user_id)
+            REFERENCES users(id)
+            ON DELETE CASCADE
+);
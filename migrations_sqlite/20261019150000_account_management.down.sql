@@ -0,0 +1,2 @@
+DROP index idx__users__login;
+ALTER TABLE users DROP COLUMN session_version;
@@ -0,0 +1,4 @@
+-- incremented on password change, login tokens with other version are rejected
+ALTER TABLE users ADD COLUMN session_version INT NOT NULL DEFAULT 0;
+
+create unique index idx__users__login on users (login);
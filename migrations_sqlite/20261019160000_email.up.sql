@@ -0,0 +1,24 @@
+ALTER TABLE users ADD COLUMN email varchar(254);
+ALTER TABLE users ADD COLUMN email_verified BOOLEAN NOT NULL DEFAULT 0;
+
+-- unverified email can be set by several users, only owner can verify it
+create unique index idx__users__verified_email on users (email) WHERE email_verified;
+
+CREATE TABLE email_tokens (
+    -- hex sha256 of the token
+    token_hash TEXT NOT NULL,
+    user_id BLOB NOT NULL,
+    -- verify_email or reset_password
+    purpose TEXT NOT NULL,
+    email varchar(254) NOT NULL,
+    -- unix time in seconds
+    expires_at INTEGER NOT NULL,
+
+    PRIMARY KEY(token_hash),
+    CONSTRAINT fk__user_id__users__id
+        FOREIGN KEY(user_id)
+            REFERENCES users(id)
+            ON DELETE CASCADE
+);
+
+create index idx__email_tokens__user_id on email_tokens (user_id);
@@ -0,0 +1,3 @@
+DROP index idx__user_identities__user_id;
+DROP index idx__user_identities__issuer__subject;
+DROP TABLE user_identities;
@@ -0,0 +1,19 @@
+CREATE TABLE user_identities (
+    id BLOB,
+    user_id BLOB NOT NULL,
+    -- issuer and subject of OpenID Connect ID token
+    issuer TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    email varchar(254),
+    -- unix time in seconds
+    created_at INTEGER NOT NULL,
+
+    PRIMARY KEY(id),
+    CONSTRAINT fk__user_id__users__id
+        FOREIGN KEY(user_id)
+            REFERENCES users(id)
+            ON DELETE CASCADE
+);
+
+create unique index idx__user_identities__issuer__subject on user_identities (issuer, subject);
+create index idx__user_identities__user_id on user_identities (user_id);
@@ -0,0 +1,5 @@
+DROP index idx__webhook_deliveries__next_attempt_at;
+DROP index idx__webhook_deliveries__webhook_id__created_at;
+DROP TABLE webhook_deliveries;
+DROP index idx__webhooks__user_id;
+DROP TABLE webhooks;
@@ -0,0 +1,2 @@
+DROP index idx__calendar_feeds__token_hash;
+DROP TABLE calendar_feeds;
@@ -0,0 +1,16 @@
+CREATE TABLE calendar_feeds (
+    user_id BLOB,
+    -- hex sha256 of the token
+    token_hash TEXT NOT NULL,
+    -- unix time in seconds
+    created_at INTEGER NOT NULL,
+    last_used_at INTEGER,
+
+    PRIMARY KEY(user_id),
+    CONSTRAINT fk__user_id__users__id
+        FOREIGN KEY(user_id)
+            REFERENCES users(id)
+            ON DELETE CASCADE
+);
+
+create unique index idx__calendar_feeds__token_hash on calendar_feeds (token_hash);
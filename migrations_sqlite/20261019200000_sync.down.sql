@@ -0,0 +1,12 @@
+DROP TRIGGER todo_lists__name_seq;
+DROP TRIGGER tasks__tombstone;
+DROP TRIGGER tasks__change_seq;
+DROP TRIGGER tasks__created_seq;
+DROP index idx__task_tombstones__todo_list_id;
+DROP TABLE task_tombstones;
+ALTER TABLE tasks DROP COLUMN moved_at;
+ALTER TABLE tasks DROP COLUMN updated_at;
+ALTER TABLE tasks DROP COLUMN change_seq;
+ALTER TABLE tasks DROP COLUMN created_seq;
+ALTER TABLE todo_lists DROP COLUMN name_seq;
+ALTER TABLE todo_lists DROP COLUMN change_seq;
@@ -0,0 +1,66 @@
+-- change sequence of the list: counter of task changes, name change is counted too
+ALTER TABLE todo_lists ADD COLUMN change_seq INTEGER NOT NULL DEFAULT 0;
+-- sequence number of the last name change
+ALTER TABLE todo_lists ADD COLUMN name_seq INTEGER NOT NULL DEFAULT 0;
+
+ALTER TABLE tasks ADD COLUMN created_seq INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE tasks ADD COLUMN change_seq INTEGER NOT NULL DEFAULT 0;
+-- unix time in microseconds of the last description change and the last move, sync compares them with client time of mutations
+ALTER TABLE tasks ADD COLUMN updated_at INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE tasks ADD COLUMN moved_at INTEGER NOT NULL DEFAULT 0;
+
+-- existing data is the first change
+UPDATE todo_lists SET change_seq = 1, name_seq = 1;
+UPDATE tasks SET created_seq = 1, change_seq = 1, updated_at = unixepoch() * 1000000, moved_at = unixepoch() * 1000000;
+
+CREATE TABLE task_tombstones (
+    task_id BLOB,
+    todo_list_id BLOB NOT NULL,
+    change_seq INTEGER NOT NULL,
+    -- unix time in seconds
+    deleted_at INTEGER NOT NULL,
+
+    PRIMARY KEY(task_id),
+    CONSTRAINT fk__todo_list_id__todo_lists__id
+        FOREIGN KEY(todo_list_id)
+            REFERENCES todo_lists(id)
+            ON DELETE CASCADE
+);
+
+create index idx__task_tombstones__todo_list_id on task_tombstones (todo_list_id, change_seq);
+
+-- sqlite triggers can't change the new row, sequence numbers are set by update after insert
+
+CREATE TRIGGER tasks__created_seq AFTER INSERT ON tasks
+BEGIN
+    UPDATE todo_lists SET change_seq = change_seq + 1 WHERE id = NEW.todo_list_id;
+    UPDATE tasks
+    SET created_seq = (SELECT change_seq FROM todo_lists WHERE id = NEW.todo_list_id),
+        change_seq = (SELECT change_seq FROM todo_lists WHERE id = NEW.todo_list_id)
+    WHERE id = NEW.id;
+    -- task with id chosen by client (CalDAV) could be deleted before
+    DELETE FROM task_tombstones WHERE task_id = NEW.id;
+END;
+
+CREATE TRIGGER tasks__change_seq AFTER UPDATE OF description, "order", due_at, completed_at, priority, rrule ON tasks
+BEGIN
+    UPDATE todo_lists SET change_seq = change_seq + 1 WHERE id = NEW.todo_list_id;
+    UPDATE tasks
+    SET change_seq = (SELECT change_seq FROM todo_lists WHERE id = NEW.todo_list_id)
+    WHERE id = NEW.id;
+END;
+
+-- list is deleted too, when it's not found, tombstone is not needed
+CREATE TRIGGER tasks__tombstone AFTER DELETE ON tasks
+BEGIN
+    UPDATE todo_lists SET change_seq = change_seq + 1 WHERE id = OLD.todo_list_id;
+    INSERT INTO task_tombstones (task_id, todo_list_id, change_seq, deleted_at)
+    SELECT OLD.id, id, change_seq, unixepoch()
+    FROM todo_lists
+    WHERE id = OLD.todo_list_id;
+END;
+
+CREATE TRIGGER todo_lists__name_seq AFTER UPDATE OF name ON todo_lists
+BEGIN
+    UPDATE todo_lists SET change_seq = change_seq + 1, name_seq = change_seq + 1 WHERE id = NEW.id;
+END;
@@ -0,0 +1,1318 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{
+        Duration,
+        Instant
+    }
+};
+
+use async_trait::async_trait;
+use chrono::{
+    DateTime,
+    Utc
+};
+use uuid::Uuid;
+
+use super::{
+    internal_error,
+    AccessTokenRepository,
+    EmailTokenRepository,
+    IdentityRepository,
+    TwoFactorRepository,
+    IdempotencyRepository,
+    Repositories,
+    SyncTransaction,
+    TaskRepository,
+    TodoListRepository,
+    UserRepository,
+    WebhookRepository,
+    CalendarFeedRepository
+};
+use crate::models::{
+    ServiceError,
+    NewUser,
+    NewTodoList,
+    UpdateTodoList,
+    FullTodoListInfo,
+    FullTaskInfo,
+    TaskRange,
+    TaskDetails,
+    TaskContent,
+    IdempotencyState,
+    IdempotentResponse,
+    NewAccessToken,
+    AccessTokenInfo,
+    AccessTokenOwner,
+    TotpSecret,
+    UserEmail,
+    EmailTokenPurpose,
+    EmailTokenOwner,
+    NewIdentity,
+    UserIdentity,
+    NewWebhook,
+    UpdateWebhook,
+    WebhookInfo,
+    WebhookEvent,
+    WebhookDelivery,
+    PendingDelivery,
+    DeliveryAttempt,
+    DeliveryStatus,
+    CalendarFeedInfo,
+    DeletedTodoList,
+    RestoredTodoList,
+    TaskChanges,
+    TaskTombstone,
+    ConditionalWrite,
+    Outbox,
+    MovedTask
+};
+
+struct UserRecord {
+    id: Uuid,
+    login: String,
+    password: String,
+    session_version: i32,
+    email: Option<UserEmail>,
+}
+
+#[derive(Clone)]
+struct TodoListRecord {
+    id: Uuid,
+    user_id: Uuid,
+    name: String,
+    change_seq: i64,
+    name_seq: i64,
+}
+
+#[derive(Clone)]
+struct TaskRecord {
+    id: Uuid,
+    todo_list_id: Uuid,
+    description: String,
+    order: i32,
+    details: TaskDetails,
+    created_seq: i64,
+    change_seq: i64,
+    updated_at: DateTime<Utc>,
+    moved_at: DateTime<Utc>,
+}
+
+#[derive(Clone)]
+struct TombstoneRecord {
+    todo_list_id: Uuid,
+    change_seq: i64,
+    tombstone: TaskTombstone,
+}
+
+/// Lists and tasks before change, the change is counted by comparing with them
+struct Snapshot {
+    /// list id -> name
+    lists: HashMap<Uuid, String>,
+    /// task id -> (list id, description, order, details)
+    tasks: HashMap<Uuid, (Uuid, String, i32, TaskDetails)>,
+}
+
+struct IdempotencyRecord {
+    fingerprint: String,
+    response: Option<IdempotentResponse>,
+    expires_at: Instant,
+}
+
+struct AccessTokenRecord {
+    user_id: Uuid,
+    token_hash: String,
+    info: AccessTokenInfo,
+}
+
+struct EmailTokenRecord {
+    token_hash: String,
+    purpose: EmailTokenPurpose,
+    owner: EmailTokenOwner,
+    expires_at: DateTime<Utc>,
+}
+
+struct IdentityRecord {
+    user_id: Uuid,
+    identity: UserIdentity,
+}
+
+struct WebhookRecord {
+    user_id: Uuid,
+    secret: String,
+    info: WebhookInfo,
+}
+
+struct WebhookDeliveryRecord {
+    webhook_id: Uuid,
+    payload: String,
+    delivery: WebhookDelivery,
+}
+
+struct CalendarFeedRecord {
+    token_hash: String,
+    info: CalendarFeedInfo,
+}
+
+struct TotpRecord {
+    secret: Vec<u8>,
+    enabled: bool,
+    last_used_step: Option<i64>,
+    recovery_code_hashes: Vec<String>,
+}
+
+impl From<&TodoListRecord> for FullTodoListInfo {
+    fn from(list: &TodoListRecord) -> Self {
+        Self { id: list.id, user_id: list.user_id, name: list.name.clone() }
+    }
+}
+
+impl TodoListRecord {
+    fn new(id: Uuid, user_id: Uuid, name: String) -> Self {
+        Self { id, user_id, name, change_seq: 0, name_seq: 0 }
+    }
+}
+
+impl TaskRecord {
+    /// Sequence numbers are set, when change is counted
+    fn new(id: Uuid, todo_list_id: Uuid, description: String, order: i32, changed_at: DateTime<Utc>) -> Self {
+        Self { id, todo_list_id, description, order, details: TaskDetails::default(), created_seq: 0, change_seq: 0, updated_at: changed_at, moved_at: changed_at }
+    }
+}
+
+impl From<&TaskRecord> for FullTaskInfo {
+    fn from(task: &TaskRecord) -> Self {
+        let TaskDetails { due_at, completed_at, priority, rrule } = task.details.clone();
+        Self { id: task.id, todo_list_id: task.todo_list_id, description: task.description.clone(), order: task.order, due_at, completed_at, priority, rrule }
+    }
+}
+
+#[derive(Default)]
+struct Data {
+    users: Vec<UserRecord>,
+    lists: Vec<TodoListRecord>,
+    tasks: Vec<TaskRecord>,
+    tombstones: Vec<TombstoneRecord>,
+    /// (scope, key) -> record
+    idempotency: HashMap<(String, String), IdempotencyRecord>,
+    access_tokens: Vec<AccessTokenRecord>,
+    /// user id -> record
+    totp: HashMap<Uuid, TotpRecord>,
+    email_tokens: Vec<EmailTokenRecord>,
+    identities: Vec<IdentityRecord>,
+    webhooks: Vec<WebhookRecord>,
+    webhook_deliveries: Vec<WebhookDeliveryRecord>,
+    /// user id -> record
+    calendar_feeds: HashMap<Uuid, CalendarFeedRecord>,
+}
+
+/// Storage without database, behaves like postgres storage. Used in tests and with `memory://` database url
+#[derive(Default)]
+pub struct MemoryStorage {
+    data: Mutex<Data>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn with_data<T>(&self, f: impl FnOnce(&mut Data) -> Result<T, ServiceError>) -> Result<T, ServiceError> {
+        let mut data = self.data.lock().map_err(internal_error)?;
+        f(&mut data)
+    }
+
+    /// Like `with_data`, but changes of lists and tasks are counted like triggers of sql storages do
+    fn with_tracked_data<T>(&self, f: impl FnOnce(&mut Data) -> Result<T, ServiceError>) -> Result<T, ServiceError> {
+        let mut data = self.data.lock().map_err(internal_error)?;
+        let before = data.snapshot();
+        let result = f(&mut data);
+        data.count_changes(before);
+        result
+    }
+}
+
+impl Data {
+    fn list_tasks(&mut self, todo_list_id: Uuid) -> impl Iterator<Item = &mut TaskRecord> {
+        self.tasks.iter_mut().filter(move |task| task.todo_list_id == todo_list_id)
+    }
+
+    fn offset_range(&mut self, todo_list_id: Uuid, bottom: i32, top: i32, offset: i32) {
+        self.list_tasks(todo_list_id)
+            .filter(|task| task.order >= bottom && task.order <= top)
+            .for_each(|task| task.order += offset);
+    }
+
+    /// Same as `move_task_in` of sql storages
+    fn move_task(&mut self, todo_list_id: Uuid, task_id: Uuid, new_order: i32, moved_at: DateTime<Utc>, conditional: bool) -> ConditionalWrite {
+        let old_order = match self.list_tasks(todo_list_id).find(|task| task.id == task_id) {
+            Some(task) if conditional && task.moved_at > moved_at => return ConditionalWrite::Outdated(FullTaskInfo::from(&*task)),
+            Some(task) => task.order,
+            None => return ConditionalWrite::NotFound,
+        };
+        let new_order = new_order.clamp(1, self.list_tasks(todo_list_id).count() as i32);
+
+        // same range offset as postgres storage
+        if old_order > new_order {
+            self.offset_range(todo_list_id, new_order, old_order - 1, 1);
+        } else if old_order < new_order {
+            self.offset_range(todo_list_id, old_order + 1, new_order, -1);
+        }
+
+        self.list_tasks(todo_list_id)
+            .find(|task| task.id == task_id)
+            .map(|task| {
+                if task.order != new_order {
+                    task.order = new_order;
+                    task.moved_at = moved_at;
+                }
+                ConditionalWrite::Applied(FullTaskInfo::from(&*task))
+            })
+            .unwrap_or(ConditionalWrite::NotFound)
+    }
+
+    fn delete_task(&mut self, todo_list_id: Uuid, task_id: Uuid) -> Option<FullTaskInfo> {
+        let index = self.tasks.iter().position(|task| task.todo_list_id == todo_list_id && task.id == task_id)?;
+
+        let task = self.tasks.remove(index);
+        self.offset_range(todo_list_id, task.order, i32::MAX, -1);
+
+        Some(FullTaskInfo::from(&task))
+    }
+
+    /// Changes task by `change` and adds `task.updated` event
+    fn update_task(&mut self, todo_list_id: Uuid, task_id: Uuid, outbox: &mut Outbox, change: impl FnOnce(&mut TaskRecord)) -> Result<Option<FullTaskInfo>, ServiceError> {
+        let task = match self.list_tasks(todo_list_id).find(|task| task.id == task_id) {
+            Some(task) => {
+                change(task);
+                task.updated_at = Utc::now();
+                FullTaskInfo::from(&*task)
+            },
+            None => return Ok(None),
+        };
+
+        outbox.add(WebhookEvent::TaskUpdated, &task)?;
+        self.insert_outbox(outbox);
+        Ok(Some(task))
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            lists: self.lists.iter().map(|list| (list.id, list.name.clone())).collect(),
+            tasks: self.tasks.iter().map(|task| (task.id, (task.todo_list_id, task.description.clone(), task.order, task.details.clone()))).collect(),
+        }
+    }
+
+    /// Increments sequence of the list for every changed, inserted and deleted task and for rename.
+    /// Deleted task of existing list gets tombstone
+    fn count_changes(&mut self, before: Snapshot) {
+        fn next_change_seq(lists: &mut [TodoListRecord], todo_list_id: Uuid) -> Option<i64> {
+            let list = lists.iter_mut().find(|list| list.id == todo_list_id)?;
+            list.change_seq += 1;
+            Some(list.change_seq)
+        }
+
+        let now = Utc::now();
+        for (task_id, (todo_list_id, _, _, _)) in &before.tasks {
+            if self.tasks.iter().any(|task| task.id == *task_id) {
+                continue;
+            }
+
+            if let Some(change_seq) = next_change_seq(&mut self.lists, *todo_list_id) {
+                self.tombstones.push(TombstoneRecord { todo_list_id: *todo_list_id, change_seq, tombstone: TaskTombstone { id: *task_id, deleted_at: now } });
+            }
+        }
+
+        for task in &mut self.tasks {
+            match before.tasks.get(&task.id) {
+                None => {
+                    task.created_seq = next_change_seq(&mut self.lists, task.todo_list_id).unwrap_or_default();
+                    task.change_seq = task.created_seq;
+                    self.tombstones.retain(|tombstone| tombstone.tombstone.id != task.id);
+                },
+                Some((_, description, order, details)) if *description != task.description || *order != task.order || *details != task.details => {
+                    task.change_seq = next_change_seq(&mut self.lists, task.todo_list_id).unwrap_or_default();
+                },
+                Some(_) => {},
+            }
+        }
+
+        for list in &mut self.lists {
+            if before.lists.get(&list.id).is_some_and(|name| *name != list.name) {
+                list.change_seq += 1;
+                list.name_seq = list.change_seq;
+            }
+        }
+
+        // ON DELETE CASCADE
+        let lists = &self.lists;
+        self.tombstones.retain(|tombstone| lists.iter().any(|list| list.id == tombstone.todo_list_id));
+    }
+
+    /// Pending delivery of every event for every enabled webhook of the user subscribed to it
+    fn insert_outbox(&mut self, outbox: &Outbox) {
+        let now = Utc::now();
+
+        for (event, payloads) in outbox.payloads() {
+            let webhook_ids: Vec<Uuid> = self.webhooks.iter()
+                .filter(|webhook| webhook.user_id == outbox.user_id() && webhook.info.enabled && webhook.info.events.contains(&event))
+                .map(|webhook| webhook.info.id)
+                .collect();
+
+            for payload in &payloads {
+                for webhook_id in &webhook_ids {
+                    self.webhook_deliveries.push(WebhookDeliveryRecord {
+                        webhook_id: *webhook_id,
+                        payload: payload.clone(),
+                        delivery: WebhookDelivery {
+                            id: Uuid::new_v4(),
+                            event,
+                            status: DeliveryStatus::Pending,
+                            attempts: 0,
+                            last_status_code: None,
+                            last_error: None,
+                            created_at: now,
+                            last_attempt_at: None,
+                            next_attempt_at: Some(now),
+                        },
+                    });
+                }
+            }
+        }
+    }
+
+    /// `false` if the identity is linked, like unique index of issuer and subject
+    fn insert_identity(&mut self, user_id: Uuid, identity: &NewIdentity) -> bool {
+        if self.identities.iter().any(|record| record.identity.issuer == identity.issuer && record.identity.subject == identity.subject) {
+            return false;
+        }
+
+        self.identities.push(IdentityRecord {
+            user_id,
+            identity: UserIdentity {
+                id: Uuid::new_v4(),
+                issuer: identity.issuer.clone(),
+                subject: identity.subject.clone(),
+                email: identity.email.clone(),
+                created_at: Utc::now(),
+            },
+        });
+        true
+    }
+}
+
+#[async_trait]
+impl UserRepository for MemoryStorage {
+    async fn insert_user(&self, user: &NewUser) -> Result<Uuid, ServiceError> {
+        self.with_data(|data| {
+            let id = Uuid::new_v4();
+            data.users.push(UserRecord { id, login: user.login.clone(), password: user.password.clone(), session_version: 0, email: None });
+            Ok(id)
+        })
+    }
+
+    async fn select_user_id(&self, login: &str, password: &str) -> Result<Option<Uuid>, ServiceError> {
+        self.with_data(|data| {
+            Ok(data.users.iter().find(|user| user.login == login && user.password == password).map(|user| user.id))
+        })
+    }
+
+    async fn is_user_exist(&self, login: &str) -> Result<bool, ServiceError> {
+        self.with_data(|data| Ok(data.users.iter().any(|user| user.login == login)))
+    }
+
+    async fn select_user_login(&self, user_id: Uuid) -> Result<Option<String>, ServiceError> {
+        self.with_data(|data| Ok(data.users.iter().find(|user| user.id == user_id).map(|user| user.login.clone())))
+    }
+
+    async fn select_session_version(&self, user_id: Uuid) -> Result<Option<i32>, ServiceError> {
+        self.with_data(|data| Ok(data.users.iter().find(|user| user.id == user_id).map(|user| user.session_version)))
+    }
+
+    async fn update_user_password(&self, user_id: Uuid, old_password: &str, new_password: &str) -> Result<Option<i32>, ServiceError> {
+        self.with_data(|data| {
+            Ok(data.users.iter_mut()
+                .find(|user| user.id == user_id && user.password == old_password)
+                .map(|user| {
+                    user.password = new_password.to_string();
+                    user.session_version += 1;
+                    user.session_version
+                }))
+        })
+    }
+
+    async fn update_user_login(&self, user_id: Uuid, login: &str) -> Result<bool, ServiceError> {
+        self.with_data(|data| {
+            // unique index of login
+            if data.users.iter().any(|user| user.login == login && user.id != user_id) {
+                return Ok(false);
+            }
+
+            data.users.iter_mut()
+                .filter(|user| user.id == user_id)
+                .for_each(|user| user.login = login.to_string());
+            Ok(true)
+        })
+    }
+
+    async fn delete_user(&self, user_id: Uuid, password: &str) -> Result<bool, ServiceError> {
+        self.with_tracked_data(|data| {
+            if !data.users.iter().any(|user| user.id == user_id && user.password == password) {
+                return Ok(false);
+            }
+
+            data.users.retain(|user| user.id != user_id);
+
+            // ON DELETE CASCADE
+            let lists: Vec<Uuid> = data.lists.iter().filter(|list| list.user_id == user_id).map(|list| list.id).collect();
+            data.lists.retain(|list| list.user_id != user_id);
+            data.tasks.retain(|task| !lists.contains(&task.todo_list_id));
+            data.access_tokens.retain(|token| token.user_id != user_id);
+            data.totp.remove(&user_id);
+            data.email_tokens.retain(|token| token.owner.user_id != user_id);
+            data.identities.retain(|identity| identity.user_id != user_id);
+            let webhooks: Vec<Uuid> = data.webhooks.iter().filter(|webhook| webhook.user_id == user_id).map(|webhook| webhook.info.id).collect();
+            data.webhooks.retain(|webhook| webhook.user_id != user_id);
+            data.webhook_deliveries.retain(|delivery| !webhooks.contains(&delivery.webhook_id));
+            data.calendar_feeds.remove(&user_id);
+
+            let scope = user_id.to_string();
+            data.idempotency.retain(|(key_scope, _), _| *key_scope != scope);
+
+            Ok(true)
+        })
+    }
+
+    async fn select_user_email(&self, user_id: Uuid) -> Result<Option<UserEmail>, ServiceError> {
+        self.with_data(|data| Ok(data.users.iter().find(|user| user.id == user_id).and_then(|user| user.email.clone())))
+    }
+
+    async fn update_user_email(&self, user_id: Uuid, email: &str) -> Result<(), ServiceError> {
+        self.with_data(|data| {
+            data.users.iter_mut()
+                .filter(|user| user.id == user_id)
+                .for_each(|user| user.email = Some(UserEmail { email: email.to_string(), verified: false }));
+            Ok(())
+        })
+    }
+
+    async fn verify_user_email(&self, user_id: Uuid, email: &str) -> Result<bool, ServiceError> {
+        self.with_data(|data| {
+            // unique index of verified emails
+            if data.users.iter().any(|user| user.id != user_id && user.email == Some(UserEmail { email: email.to_string(), verified: true })) {
+                return Ok(false);
+            }
+
+            Ok(data.users.iter_mut()
+                .find(|user| user.id == user_id)
+                .and_then(|user| user.email.as_mut())
+                .filter(|user_email| user_email.email == email)
+                .map(|user_email| user_email.verified = true)
+                .is_some())
+        })
+    }
+
+    async fn select_user_id_by_email(&self, email: &str) -> Result<Option<Uuid>, ServiceError> {
+        self.with_data(|data| {
+            Ok(data.users.iter()
+                .find(|user| user.email == Some(UserEmail { email: email.to_string(), verified: true }))
+                .map(|user| user.id))
+        })
+    }
+
+    async fn reset_user_password(&self, user_id: Uuid, new_password: &str) -> Result<(), ServiceError> {
+        self.with_data(|data| {
+            data.users.iter_mut()
+                .filter(|user| user.id == user_id)
+                .for_each(|user| {
+                    user.password = new_password.to_string();
+                    user.session_version += 1;
+                });
+            Ok(())
+        })
+    }
+}
+
+#[async_trait]
+impl TodoListRepository for MemoryStorage {
+    async fn insert_todo_list(&self, user_id: Uuid, todo_list: &NewTodoList, outbox: &mut Outbox) -> Result<Uuid, ServiceError> {
+        self.with_tracked_data(|data| {
+            if data.lists.iter().any(|list| list.user_id == user_id) {
+                return Err(internal_error("duplicate key value violates unique constraint \"idx__user_id\""));
+            }
+
+            let list = TodoListRecord::new(Uuid::new_v4(), user_id, todo_list.name.clone());
+            outbox.add(WebhookEvent::ListCreated, &FullTodoListInfo::from(&list))?;
+
+            let id = list.id;
+            data.lists.push(list);
+            data.insert_outbox(outbox);
+            Ok(id)
+        })
+    }
+
+    async fn select_todo_list_id(&self, user_id: Uuid) -> Result<Option<Uuid>, ServiceError> {
+        self.with_data(|data| Ok(data.lists.iter().find(|list| list.user_id == user_id).map(|list| list.id)))
+    }
+
+    async fn delete_todo_list(&self, user_id: Uuid, outbox: &mut Outbox) -> Result<Option<DeletedTodoList>, ServiceError> {
+        self.with_tracked_data(|data| {
+            let todo_list_id = match data.lists.iter().find(|list| list.user_id == user_id) {
+                Some(list) => list.id,
+                None => return Ok(None),
+            };
+
+            let count = data.tasks.len();
+            data.tasks.retain(|task| task.todo_list_id != todo_list_id);
+            data.lists.retain(|list| list.id != todo_list_id);
+
+            let deleted = DeletedTodoList { id: todo_list_id, deleted_tasks: (count - data.tasks.len()) as i64 };
+            outbox.add(WebhookEvent::ListDeleted, &deleted)?;
+            data.insert_outbox(outbox);
+            Ok(Some(deleted))
+        })
+    }
+
+    async fn update_todo_list(&self, todo_list_id: Uuid, update_list: &UpdateTodoList, outbox: &mut Outbox) -> Result<(), ServiceError> {
+        self.with_tracked_data(|data| {
+            if let Some(list) = data.lists.iter_mut().find(|list| list.id == todo_list_id) {
+                list.name = update_list.name.clone();
+                outbox.add(WebhookEvent::ListUpdated, &FullTodoListInfo::from(&*list))?;
+            }
+            data.insert_outbox(outbox);
+            Ok(())
+        })
+    }
+
+    async fn select_todo_list(&self, user_id: Uuid) -> Result<Option<FullTodoListInfo>, ServiceError> {
+        self.with_data(|data| Ok(data.lists.iter().find(|list| list.user_id == user_id).map(FullTodoListInfo::from)))
+    }
+
+    async fn restore_todo_list(&self, user_id: Uuid, name: String, tasks: Vec<TaskContent>, replace: bool, outbox: &mut Outbox) -> Result<Option<RestoredTodoList>, ServiceError> {
+        self.with_tracked_data(|data| {
+            let mut replaced = None;
+
+            if let Some(current) = data.lists.iter().find(|list| list.user_id == user_id).map(|list| list.id) {
+                if !replace {
+                    return Ok(None);
+                }
+
+                let count = data.tasks.len();
+                data.tasks.retain(|task| task.todo_list_id != current);
+                data.lists.retain(|list| list.id != current);
+                replaced = Some(DeletedTodoList { id: current, deleted_tasks: (count - data.tasks.len()) as i64 });
+            }
+
+            let list = FullTodoListInfo { id: Uuid::new_v4(), user_id, name };
+            data.lists.push(TodoListRecord::new(list.id, user_id, list.name.clone()));
+
+            let tasks: Vec<FullTaskInfo> = tasks.into_iter()
+                .enumerate()
+                .map(|(index, TaskContent { description, details })| {
+                    let mut record = TaskRecord::new(Uuid::new_v4(), list.id, description, index as i32 + 1, Utc::now());
+                    record.details = details;
+                    let task = FullTaskInfo::from(&record);
+                    data.tasks.push(record);
+                    task
+                })
+                .collect();
+
+            if let Some(replaced) = &replaced {
+                outbox.add(WebhookEvent::ListDeleted, replaced)?;
+            }
+            outbox.add(WebhookEvent::ListCreated, &list)?;
+            for task in &tasks {
+                outbox.add(WebhookEvent::TaskCreated, task)?;
+            }
+            data.insert_outbox(outbox);
+
+            Ok(Some(RestoredTodoList { list, tasks, replaced }))
+        })
+    }
+}
+
+#[async_trait]
+impl TaskRepository for MemoryStorage {
+    async fn select_task_count(&self, todo_list_id: Uuid) -> Result<i64, ServiceError> {
+        self.with_tracked_data(|data| Ok(data.list_tasks(todo_list_id).count() as i64))
+    }
+
+    async fn insert_task_to_end(&self, todo_list_id: Uuid, description: String, outbox: &mut Outbox) -> Result<Uuid, ServiceError> {
+        self.with_tracked_data(|data| {
+            let order = data.list_tasks(todo_list_id).count() as i32 + 1;
+            let record = TaskRecord::new(Uuid::new_v4(), todo_list_id, description, order, Utc::now());
+            outbox.add(WebhookEvent::TaskCreated, &FullTaskInfo::from(&record))?;
+
+            let id = record.id;
+            data.tasks.push(record);
+            data.insert_outbox(outbox);
+            Ok(id)
+        })
+    }
+
+    async fn insert_tasks_to_end(&self, todo_list_id: Uuid, tasks: Vec<TaskContent>, outbox: &mut Outbox) -> Result<Vec<FullTaskInfo>, ServiceError> {
+        self.with_tracked_data(|data| {
+            let count = data.list_tasks(todo_list_id).count() as i32;
+            let mut inserted = Vec::with_capacity(tasks.len());
+
+            for (index, TaskContent { description, details }) in tasks.into_iter().enumerate() {
+                let mut record = TaskRecord::new(Uuid::new_v4(), todo_list_id, description, count + 1 + index as i32, Utc::now());
+                record.details = details;
+                let task = FullTaskInfo::from(&record);
+                outbox.add(WebhookEvent::TaskCreated, &task)?;
+                inserted.push(task);
+                data.tasks.push(record);
+            }
+
+            data.insert_outbox(outbox);
+            Ok(inserted)
+        })
+    }
+
+    async fn insert_task_with_id(&self, todo_list_id: Uuid, task_id: Uuid, description: String, details: TaskDetails, outbox: &mut Outbox) -> Result<Option<FullTaskInfo>, ServiceError> {
+        self.with_tracked_data(|data| {
+            if data.tasks.iter().any(|task| task.id == task_id) {
+                return Ok(None);
+            }
+
+            let order = data.list_tasks(todo_list_id).count() as i32 + 1;
+            let mut record = TaskRecord::new(task_id, todo_list_id, description, order, Utc::now());
+            record.details = details;
+
+            let task = FullTaskInfo::from(&record);
+            outbox.add(WebhookEvent::TaskCreated, &task)?;
+            data.tasks.push(record);
+            data.insert_outbox(outbox);
+            Ok(Some(task))
+        })
+    }
+
+    async fn select_task(&self, todo_list_id: Uuid, task_id: Uuid) -> Result<Option<FullTaskInfo>, ServiceError> {
+        self.with_data(|data| {
+            Ok(data.list_tasks(todo_list_id).find(|task| task.id == task_id).map(|task| FullTaskInfo::from(&*task)))
+        })
+    }
+
+    async fn insert_task(&self, todo_list_id: Uuid, description: String, order: i32, outbox: &mut Outbox) -> Result<Uuid, ServiceError> {
+        self.with_tracked_data(|data| {
+            let order = order.clamp(1, data.list_tasks(todo_list_id).count() as i32 + 1);
+            let record = TaskRecord::new(Uuid::new_v4(), todo_list_id, description, order, Utc::now());
+            outbox.add(WebhookEvent::TaskCreated, &FullTaskInfo::from(&record))?;
+
+            let id = record.id;
+            data.offset_range(todo_list_id, order, i32::MAX, 1);
+            data.tasks.push(record);
+            data.insert_outbox(outbox);
+            Ok(id)
+        })
+    }
+
+    async fn select_tasks(&self, todo_list_id: Uuid) -> Result<Vec<FullTaskInfo>, ServiceError> {
+        self.with_data(|data| Ok(data.list_tasks(todo_list_id).map(|task| FullTaskInfo::from(&*task)).collect()))
+    }
+
+    async fn select_tasks_by_ids(&self, todo_list_id: Uuid, ids: &[Uuid]) -> Result<Vec<FullTaskInfo>, ServiceError> {
+        self.with_data(|data| Ok(data.list_tasks(todo_list_id).filter(|task| ids.contains(&task.id)).map(|task| FullTaskInfo::from(&*task)).collect()))
+    }
+
+    async fn select_tasks_range(&self, todo_list_id: Uuid, range: TaskRange) -> Result<Vec<FullTaskInfo>, ServiceError> {
+        self.with_data(|data| {
+            let mut tasks: Vec<FullTaskInfo> = data.list_tasks(todo_list_id).map(|task| FullTaskInfo::from(&*task)).collect();
+            tasks.sort_by_key(|task| task.order);
+
+            Ok(tasks.into_iter().skip(range.offset as usize).take(range.count as usize).collect())
+        })
+    }
+
+    async fn delete_task(&self, todo_list_id: Uuid, task_id: Uuid, outbox: &mut Outbox) -> Result<Option<FullTaskInfo>, ServiceError> {
+        self.with_tracked_data(|data| {
+            let task = data.delete_task(todo_list_id, task_id);
+
+            if let Some(task) = &task {
+                outbox.add(WebhookEvent::TaskDeleted, task)?;
+                data.insert_outbox(outbox);
+            }
+            Ok(task)
+        })
+    }
+
+    async fn update_task(&self, todo_list_id: Uuid, task_id: Uuid, description: String, outbox: &mut Outbox) -> Result<Option<FullTaskInfo>, ServiceError> {
+        self.with_tracked_data(|data| {
+            data.update_task(todo_list_id, task_id, outbox, |task| task.description = description)
+        })
+    }
+
+    async fn update_task_details(&self, todo_list_id: Uuid, task_id: Uuid, details: TaskDetails, outbox: &mut Outbox) -> Result<Option<FullTaskInfo>, ServiceError> {
+        self.with_tracked_data(|data| {
+            data.update_task(todo_list_id, task_id, outbox, |task| task.details = details)
+        })
+    }
+
+    async fn replace_task(&self, todo_list_id: Uuid, task_id: Uuid, description: String, details: TaskDetails, outbox: &mut Outbox) -> Result<Option<FullTaskInfo>, ServiceError> {
+        self.with_tracked_data(|data| {
+            data.update_task(todo_list_id, task_id, outbox, |task| {
+                task.description = description;
+                task.details = details;
+            })
+        })
+    }
+
+    async fn move_task(&self, todo_list_id: Uuid, task_id: Uuid, new_order: i32, outbox: &mut Outbox) -> Result<Option<FullTaskInfo>, ServiceError> {
+        self.with_tracked_data(|data| {
+            let previous_order = match data.list_tasks(todo_list_id).find(|task| task.id == task_id) {
+                Some(task) => task.order,
+                None => return Ok(None),
+            };
+
+            let task = match data.move_task(todo_list_id, task_id, new_order, Utc::now(), false) {
+                ConditionalWrite::Applied(task) | ConditionalWrite::Outdated(task) => task,
+                ConditionalWrite::NotFound => return Ok(None),
+            };
+
+            if task.order != previous_order {
+                outbox.add(WebhookEvent::TaskMoved, &MovedTask { task: &task, previous_order })?;
+                data.insert_outbox(outbox);
+            }
+            Ok(Some(task))
+        })
+    }
+
+    async fn select_task_changes(&self, todo_list_id: Uuid, since: i64) -> Result<Option<TaskChanges>, ServiceError> {
+        self.with_data(|data| {
+            let list = match data.lists.iter().find(|list| list.id == todo_list_id) {
+                Some(list) => list,
+                None => return Ok(None),
+            };
+
+            let mut changes = TaskChanges { seq: list.change_seq, name_seq: list.name_seq, created: Vec::new(), updated: Vec::new(), deleted: Vec::new() };
+
+            let mut tasks: Vec<&TaskRecord> = data.tasks.iter().filter(|task| task.todo_list_id == todo_list_id && task.change_seq > since).collect();
+            tasks.sort_by_key(|task| task.order);
+            for task in tasks {
+                if task.created_seq > since {
+                    changes.created.push(FullTaskInfo::from(task));
+                } else {
+                    changes.updated.push(FullTaskInfo::from(task));
+                }
+            }
+
+            let mut tombstones: Vec<&TombstoneRecord> = data.tombstones.iter().filter(|tombstone| tombstone.todo_list_id == todo_list_id && tombstone.change_seq > since).collect();
+            tombstones.sort_by_key(|tombstone| tombstone.change_seq);
+            changes.deleted = tombstones.into_iter()
+                .map(|tombstone| TaskTombstone { id: tombstone.tombstone.id, deleted_at: tombstone.tombstone.deleted_at })
+                .collect();
+
+            Ok(Some(changes))
+        })
+    }
+
+    async fn begin_sync_transaction(&self, todo_list_id: Uuid) -> Result<Box<dyn SyncTransaction + '_>, ServiceError> {
+        let rollback = self.with_data(|data| Ok((data.lists.clone(), data.tasks.clone(), data.tombstones.clone())))?;
+        Ok(Box::new(MemorySyncTransaction { storage: self, todo_list_id, rollback: Some(rollback) }))
+    }
+}
+
+/// Writes are applied at once, lists and tasks are restored if transaction is dropped without commit.
+/// Writes of other requests made meanwhile are lost then, it's enough for tests
+struct MemorySyncTransaction<'a> {
+    storage: &'a MemoryStorage,
+    todo_list_id: Uuid,
+    rollback: Option<(Vec<TodoListRecord>, Vec<TaskRecord>, Vec<TombstoneRecord>)>,
+}
+
+impl Drop for MemorySyncTransaction<'_> {
+    fn drop(&mut self) {
+        if let Some((lists, tasks, tombstones)) = self.rollback.take() {
+            let _ = self.storage.with_data(|data| {
+                data.lists = lists;
+                data.tasks = tasks;
+                data.tombstones = tombstones;
+                Ok(())
+            });
+        }
+    }
+}
+
+#[async_trait]
+impl SyncTransaction for MemorySyncTransaction<'_> {
+    async fn select_task_count(&mut self) -> Result<i64, ServiceError> {
+        self.storage.select_task_count(self.todo_list_id).await
+    }
+
+    async fn select_task(&mut self, task_id: Uuid) -> Result<Option<FullTaskInfo>, ServiceError> {
+        self.storage.select_task(self.todo_list_id, task_id).await
+    }
+
+    async fn is_task_deleted(&mut self, task_id: Uuid) -> Result<bool, ServiceError> {
+        let todo_list_id = self.todo_list_id;
+        self.storage.with_data(|data| Ok(data.tombstones.iter().any(|tombstone| tombstone.todo_list_id == todo_list_id && tombstone.tombstone.id == task_id)))
+    }
+
+    async fn insert_task_at(&mut self, task_id: Uuid, description: String, order: i32, changed_at: DateTime<Utc>) -> Result<bool, ServiceError> {
+        let todo_list_id = self.todo_list_id;
+        self.storage.with_tracked_data(|data| {
+            if data.tasks.iter().any(|task| task.id == task_id) {
+                return Ok(false);
+            }
+
+            let order = order.clamp(1, data.list_tasks(todo_list_id).count() as i32 + 1);
+            data.offset_range(todo_list_id, order, i32::MAX, 1);
+            data.tasks.push(TaskRecord::new(task_id, todo_list_id, description, order, changed_at));
+            Ok(true)
+        })
+    }
+
+    async fn update_task_at(&mut self, task_id: Uuid, description: String, changed_at: DateTime<Utc>) -> Result<ConditionalWrite, ServiceError> {
+        let todo_list_id = self.todo_list_id;
+        self.storage.with_tracked_data(|data| {
+            Ok(match data.list_tasks(todo_list_id).find(|task| task.id == task_id) {
+                // the same time is not older, the later written change wins
+                Some(task) if task.updated_at > changed_at => ConditionalWrite::Outdated(FullTaskInfo::from(&*task)),
+                Some(task) => {
+                    task.description = description;
+                    task.updated_at = changed_at;
+                    ConditionalWrite::Applied(FullTaskInfo::from(&*task))
+                },
+                None => ConditionalWrite::NotFound,
+            })
+        })
+    }
+
+    async fn delete_task(&mut self, task_id: Uuid) -> Result<Option<FullTaskInfo>, ServiceError> {
+        let todo_list_id = self.todo_list_id;
+        self.storage.with_tracked_data(|data| Ok(data.delete_task(todo_list_id, task_id)))
+    }
+
+    async fn move_task_at(&mut self, task_id: Uuid, new_order: i32, changed_at: DateTime<Utc>) -> Result<ConditionalWrite, ServiceError> {
+        let todo_list_id = self.todo_list_id;
+        self.storage.with_tracked_data(|data| Ok(data.move_task(todo_list_id, task_id, new_order, changed_at, true)))
+    }
+
+    async fn commit(mut self: Box<Self>, outbox: &Outbox) -> Result<(), ServiceError> {
+        self.storage.with_data(|data| {
+            data.insert_outbox(outbox);
+            Ok(())
+        })?;
+        self.rollback = None;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl IdempotencyRepository for MemoryStorage {
+    async fn start_idempotent_request(&self, scope: &str, key: &str, fingerprint: &str, ttl: Duration) -> Result<IdempotencyState, ServiceError> {
+        self.with_data(|data| {
+            let now = Instant::now();
+            data.idempotency.retain(|_, record| record.expires_at > now);
+
+            let record_key = (scope.to_string(), key.to_string());
+
+            if let Some(record) = data.idempotency.get(&record_key) {
+                return Ok(match &record.response {
+                    _ if record.fingerprint != fingerprint => IdempotencyState::Mismatch,
+                    Some(response) => IdempotencyState::Completed(response.clone()),
+                    None => IdempotencyState::InProgress,
+                });
+            }
+
+            data.idempotency.insert(record_key, IdempotencyRecord { fingerprint: fingerprint.to_string(), response: None, expires_at: now + ttl });
+            Ok(IdempotencyState::Started)
+        })
+    }
+
+    async fn complete_idempotent_request(&self, scope: &str, key: &str, response: &IdempotentResponse) -> Result<(), ServiceError> {
+        self.with_data(|data| {
+            if let Some(record) = data.idempotency.get_mut(&(scope.to_string(), key.to_string())) {
+                record.response = Some(response.clone());
+            }
+            Ok(())
+        })
+    }
+
+    async fn cancel_idempotent_request(&self, scope: &str, key: &str) -> Result<(), ServiceError> {
+        self.with_data(|data| {
+            data.idempotency.remove(&(scope.to_string(), key.to_string()));
+            Ok(())
+        })
+    }
+}
+
+#[async_trait]
+impl AccessTokenRepository for MemoryStorage {
+    async fn insert_access_token(&self, user_id: Uuid, token_hash: &str, token: &NewAccessToken, expires_at: Option<DateTime<Utc>>) -> Result<AccessTokenInfo, ServiceError> {
+        self.with_data(|data| {
+            let info = AccessTokenInfo {
+                id: Uuid::new_v4(),
+                name: token.name.clone(),
+                scopes: token.scopes.clone(),
+                created_at: Utc::now(),
+                expires_at,
+                last_used_at: None,
+            };
+
+            data.access_tokens.push(AccessTokenRecord { user_id, token_hash: token_hash.to_string(), info: info.clone() });
+            Ok(info)
+        })
+    }
+
+    async fn select_access_tokens(&self, user_id: Uuid) -> Result<Vec<AccessTokenInfo>, ServiceError> {
+        self.with_data(|data| {
+            Ok(data.access_tokens.iter()
+                .filter(|token| token.user_id == user_id)
+                .map(|token| token.info.clone())
+                .collect())
+        })
+    }
+
+    async fn delete_access_token(&self, user_id: Uuid, token_id: Uuid) -> Result<bool, ServiceError> {
+        self.with_data(|data| {
+            let count = data.access_tokens.len();
+            data.access_tokens.retain(|token| !(token.user_id == user_id && token.info.id == token_id));
+            Ok(data.access_tokens.len() < count)
+        })
+    }
+
+    async fn use_access_token(&self, token_hash: &str) -> Result<Option<AccessTokenOwner>, ServiceError> {
+        self.with_data(|data| {
+            let now = Utc::now();
+
+            Ok(data.access_tokens.iter_mut()
+                .find(|token| token.token_hash == token_hash && token.info.expires_at.is_none_or(|expires_at| expires_at > now))
+                .map(|token| {
+                    token.info.last_used_at = Some(now);
+                    AccessTokenOwner { user_id: token.user_id, scopes: token.info.scopes.clone() }
+                }))
+        })
+    }
+}
+
+#[async_trait]
+impl TwoFactorRepository for MemoryStorage {
+    async fn upsert_totp_secret(&self, user_id: Uuid, secret: &[u8]) -> Result<(), ServiceError> {
+        self.with_data(|data| {
+            data.totp.insert(user_id, TotpRecord { secret: secret.to_vec(), enabled: false, last_used_step: None, recovery_code_hashes: Vec::new() });
+            Ok(())
+        })
+    }
+
+    async fn select_totp_secret(&self, user_id: Uuid) -> Result<Option<TotpSecret>, ServiceError> {
+        self.with_data(|data| {
+            Ok(data.totp.get(&user_id).map(|record| TotpSecret { secret: record.secret.clone(), enabled: record.enabled }))
+        })
+    }
+
+    async fn enable_totp(&self, user_id: Uuid, recovery_code_hashes: &[String]) -> Result<(), ServiceError> {
+        self.with_data(|data| {
+            if let Some(record) = data.totp.get_mut(&user_id) {
+                record.enabled = true;
+                record.recovery_code_hashes = recovery_code_hashes.to_vec();
+            }
+            Ok(())
+        })
+    }
+
+    async fn use_totp_step(&self, user_id: Uuid, step: i64) -> Result<bool, ServiceError> {
+        self.with_data(|data| {
+            match data.totp.get_mut(&user_id) {
+                Some(record) if record.last_used_step.is_none_or(|last_used_step| last_used_step < step) => {
+                    record.last_used_step = Some(step);
+                    Ok(true)
+                },
+                _ => Ok(false),
+            }
+        })
+    }
+
+    async fn use_recovery_code(&self, user_id: Uuid, code_hash: &str) -> Result<bool, ServiceError> {
+        self.with_data(|data| {
+            let record = match data.totp.get_mut(&user_id) {
+                Some(record) => record,
+                None => return Ok(false),
+            };
+
+            let count = record.recovery_code_hashes.len();
+            record.recovery_code_hashes.retain(|hash| hash != code_hash);
+            Ok(record.recovery_code_hashes.len() < count)
+        })
+    }
+
+    async fn delete_totp(&self, user_id: Uuid) -> Result<(), ServiceError> {
+        self.with_data(|data| {
+            data.totp.remove(&user_id);
+            Ok(())
+        })
+    }
+}
+
+#[async_trait]
+impl EmailTokenRepository for MemoryStorage {
+    async fn insert_email_token(&self, user_id: Uuid, token_hash: &str, purpose: EmailTokenPurpose, email: &str, expires_at: DateTime<Utc>) -> Result<(), ServiceError> {
+        self.with_data(|data| {
+            data.email_tokens.push(EmailTokenRecord {
+                token_hash: token_hash.to_string(),
+                purpose,
+                owner: EmailTokenOwner { user_id, email: email.to_string() },
+                expires_at,
+            });
+            Ok(())
+        })
+    }
+
+    async fn use_email_token(&self, token_hash: &str, purpose: EmailTokenPurpose) -> Result<Option<EmailTokenOwner>, ServiceError> {
+        self.with_data(|data| {
+            let now = Utc::now();
+            data.email_tokens.retain(|token| token.expires_at > now);
+
+            let owner = data.email_tokens.iter()
+                .find(|token| token.token_hash == token_hash && token.purpose == purpose)
+                .map(|token| token.owner.clone());
+
+            if let Some(owner) = &owner {
+                data.email_tokens.retain(|token| !(token.owner.user_id == owner.user_id && token.purpose == purpose));
+            }
+
+            Ok(owner)
+        })
+    }
+}
+
+#[async_trait]
+impl IdentityRepository for MemoryStorage {
+    async fn select_identity_user_id(&self, issuer: &str, subject: &str) -> Result<Option<Uuid>, ServiceError> {
+        self.with_data(|data| {
+            Ok(data.identities.iter()
+                .find(|record| record.identity.issuer == issuer && record.identity.subject == subject)
+                .map(|record| record.user_id))
+        })
+    }
+
+    async fn insert_identity_user(&self, user: &NewUser, identity: &NewIdentity) -> Result<Option<Uuid>, ServiceError> {
+        self.with_data(|data| {
+            if data.users.iter().any(|record| record.login == user.login) {
+                return Ok(None);
+            }
+
+            let id = Uuid::new_v4();
+            if !data.insert_identity(id, identity) {
+                return Ok(None);
+            }
+
+            data.users.push(UserRecord { id, login: user.login.clone(), password: user.password.clone(), session_version: 0, email: None });
+            Ok(Some(id))
+        })
+    }
+
+    async fn insert_identity(&self, user_id: Uuid, identity: &NewIdentity) -> Result<bool, ServiceError> {
+        self.with_data(|data| Ok(data.insert_identity(user_id, identity)))
+    }
+
+    async fn select_identities(&self, user_id: Uuid) -> Result<Vec<UserIdentity>, ServiceError> {
+        self.with_data(|data| {
+            Ok(data.identities.iter()
+                .filter(|record| record.user_id == user_id)
+                .map(|record| record.identity.clone())
+                .collect())
+        })
+    }
+}
+
+#[async_trait]
+impl WebhookRepository for MemoryStorage {
+    async fn insert_webhook(&self, user_id: Uuid, webhook: &NewWebhook, secret: &str) -> Result<WebhookInfo, ServiceError> {
+        self.with_data(|data| {
+            let info = WebhookInfo {
+                id: Uuid::new_v4(),
+                url: webhook.url.clone(),
+                events: webhook.events.clone(),
+                enabled: true,
+                consecutive_failures: 0,
+                created_at: Utc::now(),
+            };
+
+            data.webhooks.push(WebhookRecord { user_id, secret: secret.to_string(), info: info.clone() });
+            Ok(info)
+        })
+    }
+
+    async fn select_webhooks(&self, user_id: Uuid) -> Result<Vec<WebhookInfo>, ServiceError> {
+        self.with_data(|data| {
+            Ok(data.webhooks.iter()
+                .filter(|webhook| webhook.user_id == user_id)
+                .map(|webhook| webhook.info.clone())
+                .collect())
+        })
+    }
+
+    async fn update_webhook(&self, user_id: Uuid, webhook_id: Uuid, update: &UpdateWebhook) -> Result<Option<WebhookInfo>, ServiceError> {
+        self.with_data(|data| {
+            Ok(data.webhooks.iter_mut()
+                .find(|webhook| webhook.user_id == user_id && webhook.info.id == webhook_id)
+                .map(|webhook| {
+                    if let Some(url) = &update.url {
+                        webhook.info.url = url.clone();
+                    }
+                    if let Some(secret) = &update.secret {
+                        webhook.secret = secret.clone();
+                    }
+                    if let Some(events) = &update.events {
+                        webhook.info.events = events.clone();
+                    }
+                    if let Some(enabled) = update.enabled {
+                        webhook.info.enabled = enabled;
+                        if enabled {
+                            webhook.info.consecutive_failures = 0;
+                        }
+                    }
+                    webhook.info.clone()
+                }))
+        })
+    }
+
+    async fn delete_webhook(&self, user_id: Uuid, webhook_id: Uuid) -> Result<bool, ServiceError> {
+        self.with_data(|data| {
+            let count = data.webhooks.len();
+            data.webhooks.retain(|webhook| !(webhook.user_id == user_id && webhook.info.id == webhook_id));
+
+            if data.webhooks.len() == count {
+                return Ok(false);
+            }
+
+            // ON DELETE CASCADE
+            data.webhook_deliveries.retain(|delivery| delivery.webhook_id != webhook_id);
+            Ok(true)
+        })
+    }
+
+    async fn select_webhook_deliveries(&self, user_id: Uuid, webhook_id: Uuid, limit: i64) -> Result<Option<Vec<WebhookDelivery>>, ServiceError> {
+        self.with_data(|data| {
+            if !data.webhooks.iter().any(|webhook| webhook.user_id == user_id && webhook.info.id == webhook_id) {
+                return Ok(None);
+            }
+
+            // newest first
+            Ok(Some(data.webhook_deliveries.iter()
+                .rev()
+                .filter(|delivery| delivery.webhook_id == webhook_id)
+                .take(limit.max(0) as usize)
+                .map(|delivery| delivery.delivery.clone())
+                .collect()))
+        })
+    }
+
+    async fn claim_webhook_deliveries(&self, lease: Duration, limit: i64) -> Result<Vec<PendingDelivery>, ServiceError> {
+        self.with_data(|data| {
+            let now = Utc::now();
+            let lease_until = now + chrono::Duration::from_std(lease).map_err(internal_error)?;
+
+            let mut due: Vec<&mut WebhookDeliveryRecord> = data.webhook_deliveries.iter_mut()
+                .filter(|delivery| delivery.delivery.status == DeliveryStatus::Pending && delivery.delivery.next_attempt_at.is_some_and(|next_attempt_at| next_attempt_at <= now))
+                .collect();
+            due.sort_by_key(|delivery| delivery.delivery.next_attempt_at);
+
+            let mut claimed = Vec::new();
+            for delivery in due {
+                if claimed.len() as i64 >= limit {
+                    break;
+                }
+
+                let webhook = match data.webhooks.iter().find(|webhook| webhook.info.id == delivery.webhook_id && webhook.info.enabled) {
+                    Some(webhook) => webhook,
+                    None => continue,
+                };
+
+                delivery.delivery.next_attempt_at = Some(lease_until);
+                claimed.push(PendingDelivery {
+                    id: delivery.delivery.id,
+                    webhook_id: delivery.webhook_id,
+                    url: webhook.info.url.clone(),
+                    secret: webhook.secret.clone(),
+                    event: delivery.delivery.event,
+                    payload: delivery.payload.clone(),
+                    attempts: delivery.delivery.attempts,
+                });
+            }
+
+            Ok(claimed)
+        })
+    }
+
+    async fn record_webhook_attempt(&self, delivery_id: Uuid, attempt: &DeliveryAttempt, disable_after: i32) -> Result<bool, ServiceError> {
+        self.with_data(|data| {
+            let record = match data.webhook_deliveries.iter_mut().find(|delivery| delivery.delivery.id == delivery_id) {
+                Some(record) => record,
+                None => return Ok(false),
+            };
+            let webhook_id = record.webhook_id;
+            let delivery = &mut record.delivery;
+
+            delivery.status = match (attempt.succeeded, attempt.retry_at) {
+                (true, _) => DeliveryStatus::Succeeded,
+                (false, Some(_)) => DeliveryStatus::Pending,
+                (false, None) => DeliveryStatus::Failed,
+            };
+            delivery.attempts += 1;
+            delivery.last_status_code = attempt.status_code;
+            delivery.last_error = attempt.error.clone();
+            delivery.last_attempt_at = Some(Utc::now());
+            delivery.next_attempt_at = attempt.retry_at.filter(|_| !attempt.succeeded);
+
+            let webhook = match data.webhooks.iter_mut().find(|webhook| webhook.info.id == webhook_id) {
+                Some(webhook) => &mut webhook.info,
+                None => return Ok(false),
+            };
+
+            if attempt.succeeded {
+                webhook.consecutive_failures = 0;
+                return Ok(false);
+            }
+
+            webhook.consecutive_failures += 1;
+            webhook.enabled = webhook.enabled && webhook.consecutive_failures < disable_after;
+
+            Ok(!webhook.enabled && webhook.consecutive_failures == disable_after)
+        })
+    }
+
+    async fn delete_webhook_deliveries_before(&self, before: DateTime<Utc>) -> Result<u64, ServiceError> {
+        self.with_data(|data| {
+            let count = data.webhook_deliveries.len();
+            data.webhook_deliveries.retain(|delivery| delivery.delivery.status == DeliveryStatus::Pending || delivery.delivery.created_at >= before);
+            Ok((count - data.webhook_deliveries.len()) as u64)
+        })
+    }
+}
+
+#[async_trait]
+impl CalendarFeedRepository for MemoryStorage {
+    async fn upsert_calendar_feed(&self, user_id: Uuid, token_hash: &str) -> Result<CalendarFeedInfo, ServiceError> {
+        self.with_data(|data| {
+            let info = CalendarFeedInfo { created_at: Utc::now(), last_used_at: None };
+            data.calendar_feeds.insert(user_id, CalendarFeedRecord { token_hash: token_hash.to_string(), info: info.clone() });
+            Ok(info)
+        })
+    }
+
+    async fn select_calendar_feed(&self, user_id: Uuid) -> Result<Option<CalendarFeedInfo>, ServiceError> {
+        self.with_data(|data| Ok(data.calendar_feeds.get(&user_id).map(|feed| feed.info.clone())))
+    }
+
+    async fn delete_calendar_feed(&self, user_id: Uuid) -> Result<bool, ServiceError> {
+        self.with_data(|data| Ok(data.calendar_feeds.remove(&user_id).is_some()))
+    }
+
+    async fn use_calendar_feed(&self, token_hash: &str) -> Result<Option<Uuid>, ServiceError> {
+        self.with_data(|data| {
+            Ok(data.calendar_feeds.iter_mut()
+                .find(|(_, feed)| feed.token_hash == token_hash)
+                .map(|(user_id, feed)| {
+                    feed.info.last_used_at = Some(Utc::now());
+                    *user_id
+                }))
+        })
+    }
+}
+
+#[async_trait]
+impl Repositories for MemoryStorage {
+    async fn close(&self) {}
+}
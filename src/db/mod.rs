@@ -1,3 +1,365 @@
-pub mod user;
-pub mod list;
-pub mod task;
\ No newline at end of file
+use std::{
+    sync::Arc,
+    time::Duration
+};
+
+use async_trait::async_trait;
+use chrono::{
+    DateTime,
+    Utc
+};
+use uuid::Uuid;
+
+use crate::models::{
+    ServiceError,
+    StatusCode,
+    NewUser,
+    NewTodoList,
+    UpdateTodoList,
+    FullTodoListInfo,
+    FullTaskInfo,
+    TaskRange,
+    TaskDetails,
+    TaskContent,
+    IdempotencyState,
+    IdempotentResponse,
+    NewAccessToken,
+    AccessTokenInfo,
+    AccessTokenOwner,
+    TotpSecret,
+    UserEmail,
+    EmailTokenPurpose,
+    EmailTokenOwner,
+    NewIdentity,
+    UserIdentity,
+    NewWebhook,
+    UpdateWebhook,
+    WebhookInfo,
+    WebhookDelivery,
+    PendingDelivery,
+    DeliveryAttempt,
+    CalendarFeedInfo,
+    DeletedTodoList,
+    RestoredTodoList,
+    TaskChanges,
+    ConditionalWrite,
+    Outbox
+};
+use crate::utils::events::ChangeEvents;
+
+pub mod postgres;
+pub mod memory;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+#[async_trait]
+pub trait UserRepository: Send + Sync {
+    async fn insert_user(&self, user: &NewUser) -> Result<Uuid, ServiceError>;
+
+    async fn select_user_id(&self, login: &str, password: &str) -> Result<Option<Uuid>, ServiceError>;
+
+    async fn is_user_exist(&self, login: &str) -> Result<bool, ServiceError>;
+
+    async fn select_user_login(&self, user_id: Uuid) -> Result<Option<String>, ServiceError>;
+
+    /// Version of login tokens of the user, `None` if user does not exist
+    async fn select_session_version(&self, user_id: Uuid) -> Result<Option<i32>, ServiceError>;
+
+    /// Sets new password and increments session version, if `old_password` matches.
+    /// Returns new session version, `None` if old password is wrong
+    async fn update_user_password(&self, user_id: Uuid, old_password: &str, new_password: &str) -> Result<Option<i32>, ServiceError>;
+
+    /// Returns `false` if login is taken by other user
+    async fn update_user_login(&self, user_id: Uuid, login: &str) -> Result<bool, ServiceError>;
+
+    /// Deletes user with lists, tasks, tokens and stored idempotent responses, if password matches.
+    /// Returns `false` if password is wrong
+    async fn delete_user(&self, user_id: Uuid, password: &str) -> Result<bool, ServiceError>;
+
+    /// `None` if user has no email
+    async fn select_user_email(&self, user_id: Uuid) -> Result<Option<UserEmail>, ServiceError>;
+
+    /// Sets not verified email
+    async fn update_user_email(&self, user_id: Uuid, email: &str) -> Result<(), ServiceError>;
+
+    /// Marks email as verified if it is still the email of the user.
+    /// Returns `false` if email was changed or is verified by other user
+    async fn verify_user_email(&self, user_id: Uuid, email: &str) -> Result<bool, ServiceError>;
+
+    /// User with the verified email
+    async fn select_user_id_by_email(&self, email: &str) -> Result<Option<Uuid>, ServiceError>;
+
+    /// Sets new password and increments session version
+    async fn reset_user_password(&self, user_id: Uuid, new_password: &str) -> Result<(), ServiceError>;
+}
+
+/// Writes add their events to `outbox` and store its webhook deliveries in the same transaction
+#[async_trait]
+pub trait TodoListRepository: Send + Sync {
+    async fn insert_todo_list(&self, user_id: Uuid, todo_list: &NewTodoList, outbox: &mut Outbox) -> Result<Uuid, ServiceError>;
+
+    async fn select_todo_list_id(&self, user_id: Uuid) -> Result<Option<Uuid>, ServiceError>;
+
+    /// Deletes list of the user with its tasks, `None` if user has no list
+    async fn delete_todo_list(&self, user_id: Uuid, outbox: &mut Outbox) -> Result<Option<DeletedTodoList>, ServiceError>;
+
+    async fn update_todo_list(&self, todo_list_id: Uuid, update_list: &UpdateTodoList, outbox: &mut Outbox) -> Result<(), ServiceError>;
+
+    async fn select_todo_list(&self, user_id: Uuid) -> Result<Option<FullTodoListInfo>, ServiceError>;
+
+    /// Creates list with tasks in the given order, all or nothing. List of the user is deleted
+    /// with its tasks if `replace`, otherwise `None` is returned for user with list
+    async fn restore_todo_list(&self, user_id: Uuid, name: String, tasks: Vec<TaskContent>, replace: bool, outbox: &mut Outbox) -> Result<Option<RestoredTodoList>, ServiceError>;
+}
+
+/// Tasks of the list have continuous order starting from 1, every method keeps this invariant,
+/// also when called concurrently for the same list. Writes add their events to `outbox` like writes of lists
+#[async_trait]
+pub trait TaskRepository: Send + Sync {
+    async fn select_task_count(&self, todo_list_id: Uuid) -> Result<i64, ServiceError>;
+
+    async fn insert_task_to_end(&self, todo_list_id: Uuid, description: String, outbox: &mut Outbox) -> Result<Uuid, ServiceError>;
+
+    /// Inserts tasks to the end in the given order, all or none of them (import). Returns inserted tasks
+    async fn insert_tasks_to_end(&self, todo_list_id: Uuid, tasks: Vec<TaskContent>, outbox: &mut Outbox) -> Result<Vec<FullTaskInfo>, ServiceError>;
+
+    /// Inserts task with id chosen by client (CalDAV resource name) to the end.
+    /// Returns `None` if task with the id already exists, also in list of another user
+    async fn insert_task_with_id(&self, todo_list_id: Uuid, task_id: Uuid, description: String, details: TaskDetails, outbox: &mut Outbox) -> Result<Option<FullTaskInfo>, ServiceError>;
+
+    async fn select_task(&self, todo_list_id: Uuid, task_id: Uuid) -> Result<Option<FullTaskInfo>, ServiceError>;
+
+    /// Inserts task with `order` (clamped to the list bounds), tasks with the same or greater order are moved down
+    async fn insert_task(&self, todo_list_id: Uuid, description: String, order: i32, outbox: &mut Outbox) -> Result<Uuid, ServiceError>;
+
+    async fn select_tasks(&self, todo_list_id: Uuid) -> Result<Vec<FullTaskInfo>, ServiceError>;
+
+    /// Tasks of the list with the ids in one query, missing ids are skipped
+    async fn select_tasks_by_ids(&self, todo_list_id: Uuid, ids: &[Uuid]) -> Result<Vec<FullTaskInfo>, ServiceError>;
+
+    async fn select_tasks_range(&self, todo_list_id: Uuid, range: TaskRange) -> Result<Vec<FullTaskInfo>, ServiceError>;
+
+    async fn delete_task(&self, todo_list_id: Uuid, task_id: Uuid, outbox: &mut Outbox) -> Result<Option<FullTaskInfo>, ServiceError>;
+
+    async fn update_task(&self, todo_list_id: Uuid, task_id: Uuid, description: String, outbox: &mut Outbox) -> Result<Option<FullTaskInfo>, ServiceError>;
+
+    /// Replaces due time, completion, priority and recurrence of the task
+    async fn update_task_details(&self, todo_list_id: Uuid, task_id: Uuid, details: TaskDetails, outbox: &mut Outbox) -> Result<Option<FullTaskInfo>, ServiceError>;
+
+    /// Replaces description and details of the task by one change (CalDAV resource)
+    async fn replace_task(&self, todo_list_id: Uuid, task_id: Uuid, description: String, details: TaskDetails, outbox: &mut Outbox) -> Result<Option<FullTaskInfo>, ServiceError>;
+
+    /// Moves task to `new_order` (clamped to the list bounds), tasks between are shifted.
+    /// Current order is read in the same transaction, so concurrent moves keep orders continuous.
+    /// Returns `None` if task does not exist, e.g. was deleted by concurrent request
+    async fn move_task(&self, todo_list_id: Uuid, task_id: Uuid, new_order: i32, outbox: &mut Outbox) -> Result<Option<FullTaskInfo>, ServiceError>;
+
+    /// Changes of the list after the sequence number (sync). Every change of task increments sequence of its list.
+    /// Returns `None` if list does not exist
+    async fn select_task_changes(&self, todo_list_id: Uuid, since: i64) -> Result<Option<TaskChanges>, ServiceError>;
+
+    /// Starts transaction of sync writes to the list, order changes of the list by other requests wait for its end
+    async fn begin_sync_transaction(&self, todo_list_id: Uuid) -> Result<Box<dyn SyncTransaction + '_>, ServiceError>;
+}
+
+/// Writes of sync mutations to one list, all or none of them are saved. Reads see the writes made before.
+/// Dropped transaction is rolled back
+#[async_trait]
+pub trait SyncTransaction: Send {
+    async fn select_task_count(&mut self) -> Result<i64, ServiceError>;
+
+    async fn select_task(&mut self, task_id: Uuid) -> Result<Option<FullTaskInfo>, ServiceError>;
+
+    /// `true` if the list has tombstone of the task
+    async fn is_task_deleted(&mut self, task_id: Uuid) -> Result<bool, ServiceError>;
+
+    /// Inserts task with id chosen by client with `order` (clamped to the list bounds), `changed_at` is time of its
+    /// last change and move. Returns `false` if task with the id already exists, also in list of another user
+    async fn insert_task_at(&mut self, task_id: Uuid, description: String, order: i32, changed_at: DateTime<Utc>) -> Result<bool, ServiceError>;
+
+    /// Updates description, unless it was changed after `changed_at`. Change at the same time is applied,
+    /// so the later written change wins
+    async fn update_task_at(&mut self, task_id: Uuid, description: String, changed_at: DateTime<Utc>) -> Result<ConditionalWrite, ServiceError>;
+
+    async fn delete_task(&mut self, task_id: Uuid) -> Result<Option<FullTaskInfo>, ServiceError>;
+
+    /// Moves task like `TaskRepository::move_task`, unless it was moved after `changed_at`
+    async fn move_task_at(&mut self, task_id: Uuid, new_order: i32, changed_at: DateTime<Utc>) -> Result<ConditionalWrite, ServiceError>;
+
+    /// Commits the writes with webhook deliveries of the events in `outbox`
+    async fn commit(self: Box<Self>, outbox: &Outbox) -> Result<(), ServiceError>;
+}
+
+/// Keys of `Idempotency-Key` header, unique in scope (user)
+#[async_trait]
+pub trait IdempotencyRepository: Send + Sync {
+    /// Reserves key for request with `fingerprint` for `ttl`, or returns state of the request which reserved it.
+    /// Expired keys are removed
+    async fn start_idempotent_request(&self, scope: &str, key: &str, fingerprint: &str, ttl: Duration) -> Result<IdempotencyState, ServiceError>;
+
+    async fn complete_idempotent_request(&self, scope: &str, key: &str, response: &IdempotentResponse) -> Result<(), ServiceError>;
+
+    /// Releases key, so the request can be retried
+    async fn cancel_idempotent_request(&self, scope: &str, key: &str) -> Result<(), ServiceError>;
+}
+
+/// Personal access tokens of users, only hash of the token is stored
+#[async_trait]
+pub trait AccessTokenRepository: Send + Sync {
+    async fn insert_access_token(&self, user_id: Uuid, token_hash: &str, token: &NewAccessToken, expires_at: Option<DateTime<Utc>>) -> Result<AccessTokenInfo, ServiceError>;
+
+    async fn select_access_tokens(&self, user_id: Uuid) -> Result<Vec<AccessTokenInfo>, ServiceError>;
+
+    /// Returns `false` if user has no token with the id
+    async fn delete_access_token(&self, user_id: Uuid, token_id: Uuid) -> Result<bool, ServiceError>;
+
+    /// Owner of not expired token with the hash, last use time of the token is updated
+    async fn use_access_token(&self, token_hash: &str) -> Result<Option<AccessTokenOwner>, ServiceError>;
+}
+
+/// TOTP secrets and recovery codes of users, recovery codes are stored as hashes
+#[async_trait]
+pub trait TwoFactorRepository: Send + Sync {
+    /// Sets new not enabled secret, previous secret and recovery codes are removed
+    async fn upsert_totp_secret(&self, user_id: Uuid, secret: &[u8]) -> Result<(), ServiceError>;
+
+    async fn select_totp_secret(&self, user_id: Uuid) -> Result<Option<TotpSecret>, ServiceError>;
+
+    /// Enables 2FA with new recovery codes
+    async fn enable_totp(&self, user_id: Uuid, recovery_code_hashes: &[String]) -> Result<(), ServiceError>;
+
+    /// Accepts code of the time step once: `false` if the same or later step was already used
+    async fn use_totp_step(&self, user_id: Uuid, step: i64) -> Result<bool, ServiceError>;
+
+    /// Removes recovery code, `false` if user has no such code
+    async fn use_recovery_code(&self, user_id: Uuid, code_hash: &str) -> Result<bool, ServiceError>;
+
+    async fn delete_totp(&self, user_id: Uuid) -> Result<(), ServiceError>;
+}
+
+/// Single-use tokens sent by mail, only hash of the token is stored
+#[async_trait]
+pub trait EmailTokenRepository: Send + Sync {
+    async fn insert_email_token(&self, user_id: Uuid, token_hash: &str, purpose: EmailTokenPurpose, email: &str, expires_at: DateTime<Utc>) -> Result<(), ServiceError>;
+
+    /// Removes not expired token with the hash and other tokens of the user with the same purpose.
+    /// Expired tokens are removed
+    async fn use_email_token(&self, token_hash: &str, purpose: EmailTokenPurpose) -> Result<Option<EmailTokenOwner>, ServiceError>;
+}
+
+/// External identities of OpenID Connect providers, identity is linked to one user
+#[async_trait]
+pub trait IdentityRepository: Send + Sync {
+    async fn select_identity_user_id(&self, issuer: &str, subject: &str) -> Result<Option<Uuid>, ServiceError>;
+
+    /// Creates user with the identity in one transaction.
+    /// Returns `None` if the identity was linked or the login was taken meanwhile
+    async fn insert_identity_user(&self, user: &NewUser, identity: &NewIdentity) -> Result<Option<Uuid>, ServiceError>;
+
+    /// Returns `false` if the identity is already linked
+    async fn insert_identity(&self, user_id: Uuid, identity: &NewIdentity) -> Result<bool, ServiceError>;
+
+    async fn select_identities(&self, user_id: Uuid) -> Result<Vec<UserIdentity>, ServiceError>;
+}
+
+/// Webhooks of users and outbox of their deliveries. Delivery is pending until it succeeds or its attempts run out
+#[async_trait]
+pub trait WebhookRepository: Send + Sync {
+    async fn insert_webhook(&self, user_id: Uuid, webhook: &NewWebhook, secret: &str) -> Result<WebhookInfo, ServiceError>;
+
+    async fn select_webhooks(&self, user_id: Uuid) -> Result<Vec<WebhookInfo>, ServiceError>;
+
+    /// Enabling resets failures of the webhook. Returns `None` if user has no webhook with the id
+    async fn update_webhook(&self, user_id: Uuid, webhook_id: Uuid, update: &UpdateWebhook) -> Result<Option<WebhookInfo>, ServiceError>;
+
+    /// Deletes webhook with its deliveries, returns `false` if user has no webhook with the id
+    async fn delete_webhook(&self, user_id: Uuid, webhook_id: Uuid) -> Result<bool, ServiceError>;
+
+    /// Latest deliveries of the webhook, `None` if user has no webhook with the id
+    async fn select_webhook_deliveries(&self, user_id: Uuid, webhook_id: Uuid, limit: i64) -> Result<Option<Vec<WebhookDelivery>>, ServiceError>;
+
+    /// Pending deliveries of enabled webhooks, which are due. Their next attempt is postponed by `lease`,
+    /// so they are not claimed by other dispatchers, and are retried if dispatcher stops before recording the attempt
+    async fn claim_webhook_deliveries(&self, lease: Duration, limit: i64) -> Result<Vec<PendingDelivery>, ServiceError>;
+
+    /// Records attempt of claimed delivery. Webhook is disabled after `disable_after` failed attempts in a row.
+    /// Returns `true` if the webhook was disabled by this attempt
+    async fn record_webhook_attempt(&self, delivery_id: Uuid, attempt: &DeliveryAttempt, disable_after: i32) -> Result<bool, ServiceError>;
+
+    /// Removes succeeded and failed deliveries created before the time, returns count
+    async fn delete_webhook_deliveries_before(&self, before: DateTime<Utc>) -> Result<u64, ServiceError>;
+}
+
+/// Calendar feeds of users, feed is read by secret token in url and only hash of the token is stored
+#[async_trait]
+pub trait CalendarFeedRepository: Send + Sync {
+    /// Creates feed of the user or replaces its token, old token stops working
+    async fn upsert_calendar_feed(&self, user_id: Uuid, token_hash: &str) -> Result<CalendarFeedInfo, ServiceError>;
+
+    async fn select_calendar_feed(&self, user_id: Uuid) -> Result<Option<CalendarFeedInfo>, ServiceError>;
+
+    async fn delete_calendar_feed(&self, user_id: Uuid) -> Result<bool, ServiceError>;
+
+    /// Owner of the feed token, marks the feed as used
+    async fn use_calendar_feed(&self, token_hash: &str) -> Result<Option<Uuid>, ServiceError>;
+}
+
+/// Storage backend, which implements all repositories
+#[async_trait]
+pub trait Repositories: UserRepository + TodoListRepository + TaskRepository + IdempotencyRepository + AccessTokenRepository + TwoFactorRepository + EmailTokenRepository + IdentityRepository + WebhookRepository + CalendarFeedRepository {
+    async fn close(&self);
+}
+
+/// Repositories of one backend, handlers get them as `web::Data<dyn ...Repository>`
+#[derive(Clone)]
+pub struct Storage {
+    pub users: Arc<dyn UserRepository>,
+    pub lists: Arc<dyn TodoListRepository>,
+    pub tasks: Arc<dyn TaskRepository>,
+    pub idempotency: Arc<dyn IdempotencyRepository>,
+    pub access_tokens: Arc<dyn AccessTokenRepository>,
+    pub two_factor: Arc<dyn TwoFactorRepository>,
+    pub email_tokens: Arc<dyn EmailTokenRepository>,
+    pub identities: Arc<dyn IdentityRepository>,
+    pub webhooks: Arc<dyn WebhookRepository>,
+    pub calendar_feeds: Arc<dyn CalendarFeedRepository>,
+    /// Change notifications of the stored lists and tasks
+    pub events: ChangeEvents,
+    backend: Arc<dyn Repositories>,
+}
+
+impl Storage {
+    pub fn new<T: Repositories + 'static>(backend: T) -> Self {
+        let backend = Arc::new(backend);
+
+        Self {
+            users: backend.clone(),
+            lists: backend.clone(),
+            tasks: backend.clone(),
+            idempotency: backend.clone(),
+            access_tokens: backend.clone(),
+            two_factor: backend.clone(),
+            email_tokens: backend.clone(),
+            identities: backend.clone(),
+            webhooks: backend.clone(),
+            calendar_feeds: backend.clone(),
+            events: ChangeEvents::new(),
+            backend,
+        }
+    }
+
+    pub async fn close(&self) {
+        self.backend.close().await
+    }
+}
+
+/// Unique constraint violation of postgres (`23505`) or sqlite (`2067`)
+pub(crate) fn is_unique_violation(e: &sqlx::Error) -> bool {
+    matches!(e.as_database_error().and_then(|e| e.code()).as_deref(), Some("23505") | Some("2067"))
+}
+
+/// Logs database error with the request scoped logger and converts it to `ServiceError`
+pub fn internal_error(e: impl std::fmt::Display) -> ServiceError {
+    slog_scope::error!("Database error: {e}");
+    ServiceError { status_code: StatusCode::InternalError, detail: Some(e.to_string()) }
+}
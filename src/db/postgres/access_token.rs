@@ -0,0 +1,138 @@
+use async_trait::async_trait;
+use chrono::{
+    DateTime,
+    Utc
+};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::PgStorage;
+use crate::db::{
+    internal_error,
+    AccessTokenRepository
+};
+use crate::utils::telemetry::{
+    traced,
+    TracedQuery
+};
+use crate::models::{
+    ServiceError,
+    NewAccessToken,
+    AccessTokenInfo,
+    AccessTokenOwner,
+    scopes_from_string,
+    scopes_to_string
+};
+
+pub async fn insert_access_token(user_id: Uuid, token_hash: &str, token: &NewAccessToken, expires_at: Option<DateTime<Utc>>, db_pool: &PgPool) -> Result<AccessTokenInfo, ServiceError> {
+    traced("db.insert_access_token", async move {
+        let id = Uuid::new_v4();
+
+        let created_at = sqlx::query_scalar!(
+                "INSERT INTO access_tokens (id, user_id, name, token_hash, scopes, expires_at)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                RETURNING created_at",
+                id,
+                user_id,
+                token.name,
+                token_hash,
+                scopes_to_string(&token.scopes),
+                expires_at
+            )
+            .fetch_one(db_pool)
+            .traced_query("INSERT", "access_tokens")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(AccessTokenInfo {
+            id,
+            name: token.name.clone(),
+            scopes: token.scopes.clone(),
+            created_at,
+            expires_at,
+            last_used_at: None,
+        })
+    }).await
+}
+
+pub async fn select_access_tokens(user_id: Uuid, db_pool: &PgPool) -> Result<Vec<AccessTokenInfo>, ServiceError> {
+    traced("db.select_access_tokens", async move {
+        let result = sqlx::query!(
+                "SELECT id, name, scopes, created_at, expires_at, last_used_at
+                FROM access_tokens
+                WHERE user_id = $1
+                ORDER BY created_at",
+                user_id
+            )
+            .fetch_all(db_pool)
+            .traced_query("SELECT", "access_tokens")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(result.into_iter()
+            .map(|r| AccessTokenInfo {
+                id: r.id,
+                name: r.name,
+                scopes: scopes_from_string(&r.scopes),
+                created_at: r.created_at,
+                expires_at: r.expires_at,
+                last_used_at: r.last_used_at,
+            })
+            .collect())
+    }).await
+}
+
+pub async fn delete_access_token(user_id: Uuid, token_id: Uuid, db_pool: &PgPool) -> Result<bool, ServiceError> {
+    traced("db.delete_access_token", async move {
+        let deleted = sqlx::query!(
+                "DELETE FROM access_tokens
+                WHERE id = $1 AND user_id = $2",
+                token_id,
+                user_id
+            )
+            .execute(db_pool)
+            .traced_query("DELETE", "access_tokens")
+            .await
+            .map_err(internal_error)?
+            .rows_affected();
+
+        Ok(deleted > 0)
+    }).await
+}
+
+pub async fn use_access_token(token_hash: &str, db_pool: &PgPool) -> Result<Option<AccessTokenOwner>, ServiceError> {
+    traced("db.use_access_token", async move {
+        let result = sqlx::query!(
+                "UPDATE access_tokens
+                SET last_used_at = now()
+                WHERE token_hash = $1 AND (expires_at IS NULL OR expires_at > now())
+                RETURNING user_id, scopes",
+                token_hash
+            )
+            .fetch_optional(db_pool)
+            .traced_query("UPDATE", "access_tokens")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(result.map(|r| AccessTokenOwner { user_id: r.user_id, scopes: scopes_from_string(&r.scopes) }))
+    }).await
+}
+
+#[async_trait]
+impl AccessTokenRepository for PgStorage {
+    async fn insert_access_token(&self, user_id: Uuid, token_hash: &str, token: &NewAccessToken, expires_at: Option<DateTime<Utc>>) -> Result<AccessTokenInfo, ServiceError> {
+        insert_access_token(user_id, token_hash, token, expires_at, &self.pool).await
+    }
+
+    async fn select_access_tokens(&self, user_id: Uuid) -> Result<Vec<AccessTokenInfo>, ServiceError> {
+        select_access_tokens(user_id, &self.pool).await
+    }
+
+    async fn delete_access_token(&self, user_id: Uuid, token_id: Uuid) -> Result<bool, ServiceError> {
+        delete_access_token(user_id, token_id, &self.pool).await
+    }
+
+    async fn use_access_token(&self, token_hash: &str) -> Result<Option<AccessTokenOwner>, ServiceError> {
+        use_access_token(token_hash, &self.pool).await
+    }
+}
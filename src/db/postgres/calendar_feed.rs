@@ -0,0 +1,106 @@
+use async_trait::async_trait;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::PgStorage;
+use crate::db::{
+    internal_error,
+    CalendarFeedRepository
+};
+use crate::utils::telemetry::{
+    traced,
+    TracedQuery
+};
+use crate::models::{
+    ServiceError,
+    CalendarFeedInfo
+};
+
+pub async fn upsert_calendar_feed(user_id: Uuid, token_hash: &str, db_pool: &PgPool) -> Result<CalendarFeedInfo, ServiceError> {
+    traced("db.upsert_calendar_feed", async move {
+        let created_at = sqlx::query_scalar!(
+                "INSERT INTO calendar_feeds (user_id, token_hash)
+                VALUES ($1, $2)
+                ON CONFLICT (user_id) DO UPDATE
+                SET token_hash = EXCLUDED.token_hash, created_at = now(), last_used_at = NULL
+                RETURNING created_at",
+                user_id,
+                token_hash
+            )
+            .fetch_one(db_pool)
+            .traced_query("INSERT", "calendar_feeds")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(CalendarFeedInfo { created_at, last_used_at: None })
+    }).await
+}
+
+pub async fn select_calendar_feed(user_id: Uuid, db_pool: &PgPool) -> Result<Option<CalendarFeedInfo>, ServiceError> {
+    traced("db.select_calendar_feed", async move {
+        let result = sqlx::query!(
+                "SELECT created_at, last_used_at
+                FROM calendar_feeds
+                WHERE user_id = $1",
+                user_id
+            )
+            .fetch_optional(db_pool)
+            .traced_query("SELECT", "calendar_feeds")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(result.map(|r| CalendarFeedInfo { created_at: r.created_at, last_used_at: r.last_used_at }))
+    }).await
+}
+
+pub async fn delete_calendar_feed(user_id: Uuid, db_pool: &PgPool) -> Result<bool, ServiceError> {
+    traced("db.delete_calendar_feed", async move {
+        let deleted = sqlx::query!(
+                "DELETE FROM calendar_feeds
+                WHERE user_id = $1",
+                user_id
+            )
+            .execute(db_pool)
+            .traced_query("DELETE", "calendar_feeds")
+            .await
+            .map_err(internal_error)?
+            .rows_affected();
+
+        Ok(deleted > 0)
+    }).await
+}
+
+pub async fn use_calendar_feed(token_hash: &str, db_pool: &PgPool) -> Result<Option<Uuid>, ServiceError> {
+    traced("db.use_calendar_feed", async move {
+        sqlx::query_scalar!(
+                "UPDATE calendar_feeds
+                SET last_used_at = now()
+                WHERE token_hash = $1
+                RETURNING user_id",
+                token_hash
+            )
+            .fetch_optional(db_pool)
+            .traced_query("UPDATE", "calendar_feeds")
+            .await
+            .map_err(internal_error)
+    }).await
+}
+
+#[async_trait]
+impl CalendarFeedRepository for PgStorage {
+    async fn upsert_calendar_feed(&self, user_id: Uuid, token_hash: &str) -> Result<CalendarFeedInfo, ServiceError> {
+        upsert_calendar_feed(user_id, token_hash, &self.pool).await
+    }
+
+    async fn select_calendar_feed(&self, user_id: Uuid) -> Result<Option<CalendarFeedInfo>, ServiceError> {
+        select_calendar_feed(user_id, &self.pool).await
+    }
+
+    async fn delete_calendar_feed(&self, user_id: Uuid) -> Result<bool, ServiceError> {
+        delete_calendar_feed(user_id, &self.pool).await
+    }
+
+    async fn use_calendar_feed(&self, token_hash: &str) -> Result<Option<Uuid>, ServiceError> {
+        use_calendar_feed(token_hash, &self.pool).await
+    }
+}
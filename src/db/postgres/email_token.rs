@@ -0,0 +1,95 @@
+use async_trait::async_trait;
+use chrono::{
+    DateTime,
+    Utc
+};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::PgStorage;
+use crate::db::{
+    internal_error,
+    EmailTokenRepository
+};
+use crate::utils::telemetry::{
+    traced,
+    TracedQuery
+};
+use crate::models::{
+    ServiceError,
+    EmailTokenPurpose,
+    EmailTokenOwner
+};
+
+pub async fn insert_email_token(user_id: Uuid, token_hash: &str, purpose: EmailTokenPurpose, email: &str, expires_at: DateTime<Utc>, db_pool: &PgPool) -> Result<(), ServiceError> {
+    traced("db.insert_email_token", async move {
+        sqlx::query!(
+                "INSERT INTO email_tokens (token_hash, user_id, purpose, email, expires_at)
+                VALUES ($1, $2, $3, $4, $5)",
+                token_hash,
+                user_id,
+                purpose.as_str(),
+                email,
+                expires_at
+            ).execute(db_pool)
+            .traced_query("INSERT", "email_tokens")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(())
+    }).await
+}
+
+pub async fn use_email_token(token_hash: &str, purpose: EmailTokenPurpose, db_pool: &PgPool) -> Result<Option<EmailTokenOwner>, ServiceError> {
+    traced("db.use_email_token", async move {
+        let mut transaction = db_pool.begin().await.map_err(internal_error)?;
+
+        sqlx::query!(
+                "DELETE FROM email_tokens
+                WHERE expires_at <= now()"
+            ).execute(&mut transaction)
+            .traced_query("DELETE", "email_tokens")
+            .await
+            .map_err(internal_error)?;
+
+        let owner = sqlx::query!(
+                "DELETE FROM email_tokens
+                WHERE token_hash = $1 AND purpose = $2
+                RETURNING user_id, email",
+                token_hash,
+                purpose.as_str()
+            )
+            .fetch_optional(&mut transaction)
+            .traced_query("DELETE", "email_tokens")
+            .await
+            .map_err(internal_error)?
+            .map(|r| EmailTokenOwner { user_id: r.user_id, email: r.email });
+
+        if let Some(owner) = &owner {
+            sqlx::query!(
+                    "DELETE FROM email_tokens
+                    WHERE user_id = $1 AND purpose = $2",
+                    owner.user_id,
+                    purpose.as_str()
+                ).execute(&mut transaction)
+                .traced_query("DELETE", "email_tokens")
+                .await
+                .map_err(internal_error)?;
+        }
+
+        transaction.commit().await.map_err(internal_error)?;
+
+        Ok(owner)
+    }).await
+}
+
+#[async_trait]
+impl EmailTokenRepository for PgStorage {
+    async fn insert_email_token(&self, user_id: Uuid, token_hash: &str, purpose: EmailTokenPurpose, email: &str, expires_at: DateTime<Utc>) -> Result<(), ServiceError> {
+        insert_email_token(user_id, token_hash, purpose, email, expires_at, &self.pool).await
+    }
+
+    async fn use_email_token(&self, token_hash: &str, purpose: EmailTokenPurpose) -> Result<Option<EmailTokenOwner>, ServiceError> {
+        use_email_token(token_hash, purpose, &self.pool).await
+    }
+}
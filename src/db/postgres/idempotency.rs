@@ -0,0 +1,130 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use sqlx::PgPool;
+
+use super::PgStorage;
+use crate::db::{
+    internal_error,
+    IdempotencyRepository
+};
+use crate::utils::telemetry::{
+    traced,
+    TracedQuery
+};
+use crate::models::{
+    ServiceError,
+    IdempotencyState,
+    IdempotentResponse
+};
+
+pub async fn start_idempotent_request(scope: &str, key: &str, fingerprint: &str, ttl: Duration, db_pool: &PgPool) -> Result<IdempotencyState, ServiceError> {
+    traced("db.start_idempotent_request", async move {
+        sqlx::query!(
+                "DELETE FROM idempotency_keys
+                WHERE expires_at < now()"
+            )
+            .execute(db_pool)
+            .traced_query("DELETE", "idempotency_keys")
+            .await
+            .map_err(internal_error)?;
+
+        let inserted = sqlx::query!(
+                "INSERT INTO idempotency_keys (scope, key, fingerprint, expires_at)
+                VALUES ($1, $2, $3, now() + make_interval(secs => $4))
+                ON CONFLICT (scope, key) DO NOTHING",
+                scope,
+                key,
+                fingerprint,
+                ttl.as_secs_f64()
+            )
+            .execute(db_pool)
+            .traced_query("INSERT", "idempotency_keys")
+            .await
+            .map_err(internal_error)?
+            .rows_affected();
+
+        if inserted > 0 {
+            return Ok(IdempotencyState::Started);
+        }
+
+        let record = sqlx::query!(
+                "SELECT fingerprint, status_code, content_type, body
+                FROM idempotency_keys
+                WHERE scope = $1 AND key = $2",
+                scope,
+                key
+            )
+            .fetch_optional(db_pool)
+            .traced_query("SELECT", "idempotency_keys")
+            .await
+            .map_err(internal_error)?;
+
+        // record can expire between insert and select, it is handled as concurrent request
+        Ok(match record {
+            Some(record) if record.fingerprint != fingerprint => IdempotencyState::Mismatch,
+            Some(record) => match record.status_code {
+                Some(status_code) => IdempotencyState::Completed(IdempotentResponse {
+                    status_code: status_code as u16,
+                    content_type: record.content_type,
+                    body: record.body.unwrap_or_default(),
+                }),
+                None => IdempotencyState::InProgress,
+            },
+            None => IdempotencyState::InProgress,
+        })
+    }).await
+}
+
+pub async fn complete_idempotent_request(scope: &str, key: &str, response: &IdempotentResponse, db_pool: &PgPool) -> Result<(), ServiceError> {
+    traced("db.complete_idempotent_request", async move {
+        sqlx::query!(
+                "UPDATE idempotency_keys
+                SET status_code = $1, content_type = $2, body = $3
+                WHERE scope = $4 AND key = $5",
+                response.status_code as i32,
+                response.content_type,
+                response.body,
+                scope,
+                key
+            )
+            .execute(db_pool)
+            .traced_query("UPDATE", "idempotency_keys")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(())
+    }).await
+}
+
+pub async fn cancel_idempotent_request(scope: &str, key: &str, db_pool: &PgPool) -> Result<(), ServiceError> {
+    traced("db.cancel_idempotent_request", async move {
+        sqlx::query!(
+                "DELETE FROM idempotency_keys
+                WHERE scope = $1 AND key = $2",
+                scope,
+                key
+            )
+            .execute(db_pool)
+            .traced_query("DELETE", "idempotency_keys")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(())
+    }).await
+}
+
+#[async_trait]
+impl IdempotencyRepository for PgStorage {
+    async fn start_idempotent_request(&self, scope: &str, key: &str, fingerprint: &str, ttl: Duration) -> Result<IdempotencyState, ServiceError> {
+        start_idempotent_request(scope, key, fingerprint, ttl, &self.pool).await
+    }
+
+    async fn complete_idempotent_request(&self, scope: &str, key: &str, response: &IdempotentResponse) -> Result<(), ServiceError> {
+        complete_idempotent_request(scope, key, response, &self.pool).await
+    }
+
+    async fn cancel_idempotent_request(&self, scope: &str, key: &str) -> Result<(), ServiceError> {
+        cancel_idempotent_request(scope, key, &self.pool).await
+    }
+}
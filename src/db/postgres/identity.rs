@@ -0,0 +1,147 @@
+use async_trait::async_trait;
+use sqlx::{
+    PgPool,
+    Postgres,
+    Transaction
+};
+use uuid::Uuid;
+
+use super::PgStorage;
+use crate::db::{
+    internal_error,
+    is_unique_violation,
+    IdentityRepository
+};
+use crate::utils::telemetry::{
+    traced,
+    TracedQuery
+};
+use crate::models::{
+    ServiceError,
+    NewUser,
+    NewIdentity,
+    UserIdentity
+};
+
+pub async fn select_identity_user_id(issuer: &str, subject: &str, db_pool: &PgPool) -> Result<Option<Uuid>, ServiceError> {
+    traced("db.select_identity_user_id", async move {
+        let result = sqlx::query!(
+                "SELECT user_id
+                FROM user_identities
+                WHERE issuer = $1 AND subject = $2",
+                issuer,
+                subject
+            )
+            .fetch_optional(db_pool)
+            .traced_query("SELECT", "user_identities")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(result.map(|r| r.user_id))
+    }).await
+}
+
+pub async fn insert_identity_user(user: &NewUser, identity: &NewIdentity, db_pool: &PgPool) -> Result<Option<Uuid>, ServiceError> {
+    traced("db.insert_identity_user", async move {
+        let id = Uuid::new_v4();
+        let mut transaction = db_pool.begin().await.map_err(internal_error)?;
+
+        let result = sqlx::query!(
+                "INSERT INTO users (id, login, password)
+                VALUES ($1, $2, $3)",
+                id,
+                user.login,
+                user.password
+            ).execute(&mut transaction)
+            .traced_query("INSERT", "users")
+            .await;
+
+        match result {
+            Ok(_) => (),
+            Err(e) if is_unique_violation(&e) => return Ok(None),
+            Err(e) => return Err(internal_error(e)),
+        }
+
+        if !insert_identity_on(id, identity, &mut transaction).await? {
+            return Ok(None);
+        }
+
+        transaction.commit().await.map_err(internal_error)?;
+
+        Ok(Some(id))
+    }).await
+}
+
+pub async fn insert_identity(user_id: Uuid, identity: &NewIdentity, db_pool: &PgPool) -> Result<bool, ServiceError> {
+    traced("db.insert_identity", async move {
+        let mut transaction = db_pool.begin().await.map_err(internal_error)?;
+
+        if !insert_identity_on(user_id, identity, &mut transaction).await? {
+            return Ok(false);
+        }
+
+        transaction.commit().await.map_err(internal_error)?;
+
+        Ok(true)
+    }).await
+}
+
+/// `false` on unique violation, transaction can't be used after it
+async fn insert_identity_on(user_id: Uuid, identity: &NewIdentity, transaction: &mut Transaction<'_, Postgres>) -> Result<bool, ServiceError> {
+    let result = sqlx::query!(
+            "INSERT INTO user_identities (id, user_id, issuer, subject, email)
+            VALUES ($1, $2, $3, $4, $5)",
+            Uuid::new_v4(),
+            user_id,
+            identity.issuer,
+            identity.subject,
+            identity.email
+        ).execute(transaction)
+        .traced_query("INSERT", "user_identities")
+        .await;
+
+    match result {
+        Ok(_) => Ok(true),
+        Err(e) if is_unique_violation(&e) => Ok(false),
+        Err(e) => Err(internal_error(e)),
+    }
+}
+
+pub async fn select_identities(user_id: Uuid, db_pool: &PgPool) -> Result<Vec<UserIdentity>, ServiceError> {
+    traced("db.select_identities", async move {
+        let result = sqlx::query!(
+                "SELECT id, issuer, subject, email, created_at
+                FROM user_identities
+                WHERE user_id = $1
+                ORDER BY created_at",
+                user_id
+            )
+            .fetch_all(db_pool)
+            .traced_query("SELECT", "user_identities")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(result.into_iter()
+            .map(|r| UserIdentity { id: r.id, issuer: r.issuer, subject: r.subject, email: r.email, created_at: r.created_at })
+            .collect())
+    }).await
+}
+
+#[async_trait]
+impl IdentityRepository for PgStorage {
+    async fn select_identity_user_id(&self, issuer: &str, subject: &str) -> Result<Option<Uuid>, ServiceError> {
+        select_identity_user_id(issuer, subject, &self.pool).await
+    }
+
+    async fn insert_identity_user(&self, user: &NewUser, identity: &NewIdentity) -> Result<Option<Uuid>, ServiceError> {
+        insert_identity_user(user, identity, &self.pool).await
+    }
+
+    async fn insert_identity(&self, user_id: Uuid, identity: &NewIdentity) -> Result<bool, ServiceError> {
+        insert_identity(user_id, identity, &self.pool).await
+    }
+
+    async fn select_identities(&self, user_id: Uuid) -> Result<Vec<UserIdentity>, ServiceError> {
+        select_identities(user_id, &self.pool).await
+    }
+}
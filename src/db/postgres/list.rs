@@ -0,0 +1,287 @@
+use async_trait::async_trait;
+use sqlx::{
+    PgConnection,
+    PgPool
+};
+use uuid::Uuid;
+
+use super::{
+    webhook::insert_outbox,
+    PgStorage
+};
+use crate::db::{
+    internal_error,
+    TodoListRepository
+};
+use crate::utils::telemetry::{
+    traced,
+    TracedQuery
+};
+use crate::models::{
+    ServiceError,
+    UpdateTodoList,
+    NewTodoList,
+    FullTaskInfo,
+    FullTodoListInfo,
+    DeletedTodoList,
+    RestoredTodoList,
+    TaskContent,
+    TaskDetails,
+    WebhookEvent,
+    Outbox
+};
+
+pub async fn insert_todo_list(user_id: Uuid, todo_list: &NewTodoList, outbox: &mut Outbox, db_pool: &PgPool) -> Result<Uuid, ServiceError> {
+    traced("db.insert_todo_list", async move {
+        let id = uuid::Uuid::new_v4();
+
+        let mut transaction = db_pool.begin().await.map_err(internal_error)?;
+
+        sqlx::query!(
+                "INSERT INTO todo_lists
+                VALUES ($1, $2, $3)",
+                id,
+                user_id,
+                todo_list.name
+            ).execute(&mut transaction)
+            .traced_query("INSERT", "todo_lists")
+            .await
+            .map_err(internal_error)?;
+
+        outbox.add(WebhookEvent::ListCreated, &FullTodoListInfo { id, user_id, name: todo_list.name.clone() })?;
+        insert_outbox(outbox, &mut transaction).await?;
+
+        transaction.commit().await.map_err(internal_error)?;
+
+        Ok(id)
+    }).await
+}
+
+pub async fn select_todo_list_id(user_id: Uuid, db_pool: &PgPool) -> Result<Option<Uuid>, ServiceError> {
+    traced("db.select_todo_list_id", async move {
+        let result = sqlx::query!(
+                "SELECT id
+                FROM todo_lists
+                WHERE user_id = $1
+                LIMIT 1",
+                user_id
+            )
+            .fetch_all(db_pool)
+            .traced_query("SELECT", "todo_lists")
+            .await
+            .map_err(internal_error)?;
+
+            Ok(result.first().map(|r| r.id))
+    }).await
+}
+
+pub async fn delete_todo_list(user_id: Uuid, outbox: &mut Outbox, db_pool: &PgPool) -> Result<Option<DeletedTodoList>, ServiceError> {
+    traced("db.delete_todo_list", async move {
+        let mut transaction = db_pool.begin().await.map_err(internal_error)?;
+
+        let todo_list_id = match lock_user_todo_list(user_id, &mut transaction).await? {
+            Some(todo_list_id) => todo_list_id,
+            None => return Ok(None),
+        };
+
+        let deleted = delete_todo_list_in(todo_list_id, &mut transaction).await?;
+
+        outbox.add(WebhookEvent::ListDeleted, &deleted)?;
+        insert_outbox(outbox, &mut transaction).await?;
+
+        transaction.commit().await.map_err(internal_error)?;
+
+        Ok(Some(deleted))
+    }).await
+}
+
+pub async fn update_todo_list(todo_list_id: Uuid, update_list: &UpdateTodoList, outbox: &mut Outbox, db_pool: &PgPool) -> Result<(), ServiceError> {
+    traced("db.update_todo_list", async move {
+        let mut transaction = db_pool.begin().await.map_err(internal_error)?;
+
+        let updated = sqlx::query_as!(
+                FullTodoListInfo,
+                "UPDATE todo_lists
+                SET name = $1
+                WHERE id = $2
+                RETURNING id, user_id, name",
+                update_list.name,
+                todo_list_id
+            )
+            .fetch_optional(&mut transaction)
+            .traced_query("UPDATE", "todo_lists")
+            .await
+            .map_err(internal_error)?;
+
+        if let Some(updated) = updated {
+            outbox.add(WebhookEvent::ListUpdated, &updated)?;
+            insert_outbox(outbox, &mut transaction).await?;
+        }
+
+        transaction.commit().await.map_err(internal_error)?;
+
+        Ok(())
+    }).await
+}
+
+pub async fn select_todo_list(user_id: Uuid, db_pool: &PgPool) -> Result<Option<FullTodoListInfo>, ServiceError> {
+    traced("db.select_todo_list", async move {
+        let result = sqlx::query_as!(
+                FullTodoListInfo,
+                "SELECT id, user_id, name
+                FROM todo_lists
+                WHERE user_id = $1",
+                user_id
+            )
+            .fetch_all(db_pool)
+            .traced_query("SELECT", "todo_lists")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(result.into_iter().nth(0))
+    }).await
+}
+
+pub async fn restore_todo_list(user_id: Uuid, name: String, tasks: Vec<TaskContent>, replace: bool, outbox: &mut Outbox, db_pool: &PgPool) -> Result<Option<RestoredTodoList>, ServiceError> {
+    traced("db.restore_todo_list", async move {
+        let mut transaction = db_pool.begin().await.map_err(internal_error)?;
+
+        let mut replaced = None;
+        if let Some(current) = lock_user_todo_list(user_id, &mut transaction).await? {
+            if !replace {
+                return Ok(None);
+            }
+
+            replaced = Some(delete_todo_list_in(current, &mut transaction).await?);
+        }
+
+        let id = uuid::Uuid::new_v4();
+
+        // list created concurrently is the same as list, which existed before
+        let inserted = sqlx::query!(
+                "INSERT INTO todo_lists
+                VALUES ($1, $2, $3)
+                ON CONFLICT (user_id) DO NOTHING",
+                id,
+                user_id,
+                name
+            )
+            .execute(&mut transaction)
+            .traced_query("INSERT", "todo_lists")
+            .await
+            .map_err(internal_error)?
+            .rows_affected();
+
+        if inserted == 0 {
+            return Ok(None);
+        }
+
+        let mut restored = Vec::with_capacity(tasks.len());
+        for (index, TaskContent { description, details }) in tasks.into_iter().enumerate() {
+            let task_id = uuid::Uuid::new_v4();
+            let order = index as i32 + 1;
+            let TaskDetails { due_at, completed_at, priority, rrule } = details;
+
+            sqlx::query!(
+                    "INSERT INTO tasks (id, todo_list_id, description, \"order\", due_at, completed_at, priority, rrule)
+                    VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+                    task_id,
+                    id,
+                    description,
+                    order,
+                    due_at,
+                    completed_at,
+                    priority,
+                    rrule
+                )
+                .execute(&mut transaction)
+                .traced_query("INSERT", "tasks")
+                .await
+                .map_err(internal_error)?;
+
+            restored.push(FullTaskInfo { id: task_id, todo_list_id: id, description, order, due_at, completed_at, priority, rrule });
+        }
+
+        let list = FullTodoListInfo { id, user_id, name };
+
+        if let Some(replaced) = &replaced {
+            outbox.add(WebhookEvent::ListDeleted, replaced)?;
+        }
+        outbox.add(WebhookEvent::ListCreated, &list)?;
+        for task in &restored {
+            outbox.add(WebhookEvent::TaskCreated, task)?;
+        }
+        insert_outbox(outbox, &mut transaction).await?;
+
+        transaction.commit().await.map_err(internal_error)?;
+
+        Ok(Some(RestoredTodoList { list, tasks: restored, replaced }))
+    }).await
+}
+
+/// Id of the list of the user, the list is locked until the end of transaction
+async fn lock_user_todo_list(user_id: Uuid, connection: &mut PgConnection) -> Result<Option<Uuid>, ServiceError> {
+    sqlx::query_scalar!(
+            "SELECT id
+            FROM todo_lists
+            WHERE user_id = $1
+            FOR UPDATE",
+            user_id
+        )
+        .fetch_optional(connection)
+        .traced_query("SELECT", "todo_lists")
+        .await
+        .map_err(internal_error)
+}
+
+async fn delete_todo_list_in(todo_list_id: Uuid, connection: &mut PgConnection) -> Result<DeletedTodoList, ServiceError> {
+    let deleted_tasks = sqlx::query!(
+            "DELETE FROM tasks
+            WHERE todo_list_id = $1",
+            todo_list_id
+        )
+        .execute(&mut *connection)
+        .traced_query("DELETE", "tasks")
+        .await
+        .map_err(internal_error)?
+        .rows_affected();
+
+    sqlx::query!(
+            "DELETE FROM todo_lists
+            WHERE id = $1",
+            todo_list_id
+        )
+        .execute(&mut *connection)
+        .traced_query("DELETE", "todo_lists")
+        .await
+        .map_err(internal_error)?;
+
+    Ok(DeletedTodoList { id: todo_list_id, deleted_tasks: deleted_tasks as i64 })
+}
+
+#[async_trait]
+impl TodoListRepository for PgStorage {
+    async fn insert_todo_list(&self, user_id: Uuid, todo_list: &NewTodoList, outbox: &mut Outbox) -> Result<Uuid, ServiceError> {
+        insert_todo_list(user_id, todo_list, outbox, &self.pool).await
+    }
+
+    async fn select_todo_list_id(&self, user_id: Uuid) -> Result<Option<Uuid>, ServiceError> {
+        select_todo_list_id(user_id, &self.pool).await
+    }
+
+    async fn delete_todo_list(&self, user_id: Uuid, outbox: &mut Outbox) -> Result<Option<DeletedTodoList>, ServiceError> {
+        delete_todo_list(user_id, outbox, &self.pool).await
+    }
+
+    async fn update_todo_list(&self, todo_list_id: Uuid, update_list: &UpdateTodoList, outbox: &mut Outbox) -> Result<(), ServiceError> {
+        update_todo_list(todo_list_id, update_list, outbox, &self.pool).await
+    }
+
+    async fn select_todo_list(&self, user_id: Uuid) -> Result<Option<FullTodoListInfo>, ServiceError> {
+        select_todo_list(user_id, &self.pool).await
+    }
+
+    async fn restore_todo_list(&self, user_id: Uuid, name: String, tasks: Vec<TaskContent>, replace: bool, outbox: &mut Outbox) -> Result<Option<RestoredTodoList>, ServiceError> {
+        restore_todo_list(user_id, name, tasks, replace, outbox, &self.pool).await
+    }
+}
@@ -0,0 +1,34 @@
+use async_trait::async_trait;
+use sqlx::PgPool;
+
+use super::Repositories;
+
+pub mod user;
+pub mod list;
+pub mod task;
+pub mod idempotency;
+pub mod access_token;
+pub mod two_factor;
+pub mod email_token;
+pub mod identity;
+pub mod webhook;
+pub mod calendar_feed;
+
+/// Postgres storage, queries are checked at compile time (see `sqlx-data.json` for offline build)
+#[derive(Clone)]
+pub struct PgStorage {
+    pool: PgPool,
+}
+
+impl PgStorage {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl Repositories for PgStorage {
+    async fn close(&self) {
+        self.pool.close().await
+    }
+}
@@ -0,0 +1,869 @@
+use async_trait::async_trait;
+use chrono::{
+    DateTime,
+    Utc
+};
+use sqlx::{
+    PgConnection,
+    PgPool,
+    Postgres,
+    Transaction
+};
+use uuid::Uuid;
+
+use super::{
+    webhook::insert_outbox,
+    PgStorage
+};
+use crate::db::{
+    internal_error,
+    SyncTransaction,
+    TaskRepository
+};
+use crate::utils::telemetry::{
+    traced,
+    TracedQuery
+};
+use crate::models::{
+    ServiceError,
+    FullTaskInfo,
+    TaskRange,
+    TaskDetails,
+    TaskContent,
+    TaskChanges,
+    TaskTombstone,
+    ConditionalWrite,
+    WebhookEvent,
+    MovedTask,
+    Outbox
+};
+
+pub async fn select_task_count(todo_list_id: Uuid, db_pool: &PgPool) -> Result<i64, ServiceError> {
+    traced("db.select_task_count", async move {
+        let result = sqlx::query!(
+                "SELECT COUNT(*) as count
+                FROM tasks
+                WHERE todo_list_id = $1",
+                todo_list_id
+            )
+            .fetch_one(db_pool)
+            .traced_query("SELECT", "tasks")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(result.count.unwrap())
+    }).await
+}
+
+pub async fn insert_task_to_end(todo_list_id: Uuid, description: String, outbox: &mut Outbox, db_pool: &PgPool) -> Result<Uuid, ServiceError> {
+    traced("db.insert_task_to_end", async move {
+        let id = uuid::Uuid::new_v4();
+
+        let mut transaction = db_pool.begin().await.map_err(internal_error)?;
+        lock_todo_list(todo_list_id, &mut transaction).await?;
+
+        let task_count = task_count(todo_list_id, &mut transaction).await?;
+        let task_order = (task_count + 1) as i32;
+
+        sqlx::query!(
+                "INSERT INTO tasks
+                VALUES ($1, $2, $3, $4)",
+                id,
+                todo_list_id,
+                description,
+                task_order
+            ).execute(&mut transaction)
+            .traced_query("INSERT", "tasks")
+            .await
+            .map_err(internal_error)?;
+
+        outbox.add(WebhookEvent::TaskCreated, &FullTaskInfo { id, todo_list_id, description, order: task_order, ..Default::default() })?;
+        insert_outbox(outbox, &mut transaction).await?;
+
+        transaction.commit().await.map_err(internal_error)?;
+
+        Ok(id)
+    }).await
+}
+
+pub async fn insert_tasks_to_end(todo_list_id: Uuid, tasks: Vec<TaskContent>, outbox: &mut Outbox, db_pool: &PgPool) -> Result<Vec<FullTaskInfo>, ServiceError> {
+    traced("db.insert_tasks_to_end", async move {
+        let mut inserted = Vec::with_capacity(tasks.len());
+
+        let mut transaction = db_pool.begin().await.map_err(internal_error)?;
+        lock_todo_list(todo_list_id, &mut transaction).await?;
+
+        let task_count = task_count(todo_list_id, &mut transaction).await?;
+
+        for (index, TaskContent { description, details }) in tasks.into_iter().enumerate() {
+            let id = uuid::Uuid::new_v4();
+            let task_order = (task_count + 1) as i32 + index as i32;
+            let TaskDetails { due_at, completed_at, priority, rrule } = details;
+
+            sqlx::query!(
+                    "INSERT INTO tasks (id, todo_list_id, description, \"order\", due_at, completed_at, priority, rrule)
+                    VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+                    id,
+                    todo_list_id,
+                    description,
+                    task_order,
+                    due_at,
+                    completed_at,
+                    priority,
+                    rrule
+                ).execute(&mut transaction)
+                .traced_query("INSERT", "tasks")
+                .await
+                .map_err(internal_error)?;
+
+            let task = FullTaskInfo { id, todo_list_id, description, order: task_order, due_at, completed_at, priority, rrule };
+            outbox.add(WebhookEvent::TaskCreated, &task)?;
+            inserted.push(task);
+        }
+
+        insert_outbox(outbox, &mut transaction).await?;
+
+        transaction.commit().await.map_err(internal_error)?;
+
+        Ok(inserted)
+    }).await
+}
+
+pub async fn insert_task_with_id(todo_list_id: Uuid, task_id: Uuid, description: String, details: TaskDetails, outbox: &mut Outbox, db_pool: &PgPool) -> Result<Option<FullTaskInfo>, ServiceError> {
+    traced("db.insert_task_with_id", async move {
+        let mut transaction = db_pool.begin().await.map_err(internal_error)?;
+        lock_todo_list(todo_list_id, &mut transaction).await?;
+
+        let task_count = task_count(todo_list_id, &mut transaction).await?;
+        let task_order = (task_count + 1) as i32;
+
+        let inserted = sqlx::query_as!(
+                FullTaskInfo,
+                "INSERT INTO tasks (id, todo_list_id, description, \"order\", due_at, completed_at, priority, rrule)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                ON CONFLICT (id) DO NOTHING
+                RETURNING id, todo_list_id, description, \"order\", due_at, completed_at, priority, rrule",
+                task_id,
+                todo_list_id,
+                description,
+                task_order,
+                details.due_at,
+                details.completed_at,
+                details.priority,
+                details.rrule
+            ).fetch_optional(&mut transaction)
+            .traced_query("INSERT", "tasks")
+            .await
+            .map_err(internal_error)?;
+
+        insert_task_event(WebhookEvent::TaskCreated, inserted.as_ref(), outbox, &mut transaction).await?;
+
+        transaction.commit().await.map_err(internal_error)?;
+
+        Ok(inserted)
+    }).await
+}
+
+pub async fn select_task(todo_list_id: Uuid, task_id: Uuid, db_pool: &PgPool) -> Result<Option<FullTaskInfo>, ServiceError> {
+    traced("db.select_task", async move {
+        let mut connection = db_pool.acquire().await.map_err(internal_error)?;
+        select_task_in(todo_list_id, task_id, &mut connection).await
+    }).await
+}
+
+async fn select_task_in(todo_list_id: Uuid, task_id: Uuid, connection: &mut PgConnection) -> Result<Option<FullTaskInfo>, ServiceError> {
+    sqlx::query_as!(
+            FullTaskInfo,
+            "SELECT id, todo_list_id, description, \"order\", due_at, completed_at, priority, rrule
+            FROM tasks
+            WHERE todo_list_id = $1 AND id = $2",
+            todo_list_id,
+            task_id
+        )
+        .fetch_optional(connection)
+        .traced_query("SELECT", "tasks")
+        .await
+        .map_err(internal_error)
+}
+
+pub async fn insert_task(todo_list_id: Uuid, description: String, order: i32, outbox: &mut Outbox, db_pool: &PgPool) -> Result<Uuid, ServiceError> {
+    traced("db.insert_task", async move {
+        let id = uuid::Uuid::new_v4();
+
+        let mut transaction = db_pool.begin().await.map_err(internal_error)?;
+        lock_todo_list(todo_list_id, &mut transaction).await?;
+
+        // order could be computed from outdated list
+        let task_count = task_count(todo_list_id, &mut transaction).await?;
+        let order = order.clamp(1, task_count as i32 + 1);
+
+        offset_add_or_remove_space(todo_list_id, order, 1, &mut transaction).await?;
+
+        sqlx::query!(
+                "INSERT INTO tasks
+                VALUES ($1, $2, $3, $4);",
+                id,
+                todo_list_id,
+                description,
+                order
+            ).execute(&mut transaction)
+            .traced_query("INSERT", "tasks")
+            .await
+            .map_err(internal_error)?;
+
+        outbox.add(WebhookEvent::TaskCreated, &FullTaskInfo { id, todo_list_id, description, order, ..Default::default() })?;
+        insert_outbox(outbox, &mut transaction).await?;
+
+        transaction.commit().await.map_err(internal_error)?;
+
+        Ok(id)
+    }).await
+}
+
+pub async fn select_tasks(todo_list_id: Uuid, db_pool: &PgPool) -> Result<Vec<FullTaskInfo>, ServiceError> {
+    traced("db.select_tasks", async move {
+        let result = sqlx::query_as!(
+                FullTaskInfo,
+                "SELECT id, todo_list_id, description, \"order\", due_at, completed_at, priority, rrule
+                FROM tasks
+                WHERE todo_list_id = $1",
+                todo_list_id
+            )
+            .fetch_all(db_pool)
+            .traced_query("SELECT", "tasks")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(result)
+    }).await
+}
+
+pub async fn select_tasks_by_ids(todo_list_id: Uuid, ids: &[Uuid], db_pool: &PgPool) -> Result<Vec<FullTaskInfo>, ServiceError> {
+    traced("db.select_tasks_by_ids", async move {
+        let result = sqlx::query_as!(
+                FullTaskInfo,
+                "SELECT id, todo_list_id, description, \"order\", due_at, completed_at, priority, rrule
+                FROM tasks
+                WHERE todo_list_id = $1 AND id = ANY($2)",
+                todo_list_id,
+                ids
+            )
+            .fetch_all(db_pool)
+            .traced_query("SELECT", "tasks")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(result)
+    }).await
+}
+
+pub async fn select_tasks_range(todo_list_id: Uuid, range: TaskRange, db_pool: &PgPool) -> Result<Vec<FullTaskInfo>, ServiceError> {
+    traced("db.select_tasks_range", async move {
+        let result = sqlx::query_as!(
+                FullTaskInfo,
+                "SELECT id, todo_list_id, description, \"order\", due_at, completed_at, priority, rrule
+                FROM tasks
+                WHERE todo_list_id = $1
+                ORDER BY \"order\"
+                LIMIT $2 OFFSET $3",
+                todo_list_id,
+                range.count as i64,
+                range.offset as i64
+            )
+            .fetch_all(db_pool)
+            .traced_query("SELECT", "tasks")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(result)
+    }).await
+}
+
+pub async fn delete_task(todo_list_id: Uuid, task_id: Uuid, outbox: &mut Outbox, db_pool: &PgPool) -> Result<Option<FullTaskInfo>, ServiceError> {
+    traced("db.delete_task", async move {
+        let mut transaction = db_pool.begin().await.map_err(internal_error)?;
+        lock_todo_list(todo_list_id, &mut transaction).await?;
+
+        let result = delete_task_in(todo_list_id, task_id, &mut transaction).await?;
+        insert_task_event(WebhookEvent::TaskDeleted, result.as_ref(), outbox, &mut transaction).await?;
+
+        transaction.commit().await.map_err(internal_error)?;
+    
+        Ok(result)
+    }).await
+}
+
+/// Deletes task in transaction, which locked the list
+async fn delete_task_in(todo_list_id: Uuid, task_id: Uuid, connection: &mut PgConnection) -> Result<Option<FullTaskInfo>, ServiceError> {
+    let result = sqlx::query_as!(
+            FullTaskInfo,
+            "WITH deleted AS (DELETE FROM tasks
+            WHERE todo_list_id = $1 AND id = $2 RETURNING id, todo_list_id, description, \"order\", due_at, completed_at, priority, rrule)
+
+            SELECT * FROM deleted",
+            todo_list_id,
+            task_id
+        )
+        .fetch_optional(&mut *connection)
+        .traced_query("DELETE", "tasks")
+        .await
+        .map_err(internal_error)?;
+
+    if let Some(task) = &result {
+        offset_add_or_remove_space(todo_list_id, task.order, -1, &mut *connection).await?;
+    }
+
+    Ok(result)
+}
+
+pub async fn update_task(todo_list_id: Uuid, task_id: Uuid, description: String, outbox: &mut Outbox, db_pool: &PgPool) -> Result<Option<FullTaskInfo>, ServiceError> {
+    traced("db.update_task", async move {
+        let mut transaction = db_pool.begin().await.map_err(internal_error)?;
+
+        let result = sqlx::query_as!(
+                FullTaskInfo,
+                "WITH update AS (UPDATE tasks
+                SET description = $1, updated_at = now()
+                WHERE todo_list_id = $2 AND id = $3 RETURNING id, todo_list_id, description, \"order\", due_at, completed_at, priority, rrule)
+                SELECT * FROM update",
+                description,
+                todo_list_id,
+                task_id
+            ).fetch_optional(&mut transaction)
+            .traced_query("UPDATE", "tasks")
+            .await
+            .map_err(internal_error)?;
+
+        insert_task_event(WebhookEvent::TaskUpdated, result.as_ref(), outbox, &mut transaction).await?;
+
+        transaction.commit().await.map_err(internal_error)?;
+
+        Ok(result)
+    }).await
+}
+
+pub async fn update_task_details(todo_list_id: Uuid, task_id: Uuid, details: TaskDetails, outbox: &mut Outbox, db_pool: &PgPool) -> Result<Option<FullTaskInfo>, ServiceError> {
+    traced("db.update_task_details", async move {
+        let mut transaction = db_pool.begin().await.map_err(internal_error)?;
+
+        let result = sqlx::query_as!(
+                FullTaskInfo,
+                "WITH update AS (UPDATE tasks
+                SET due_at = $1, completed_at = $2, priority = $3, rrule = $4, updated_at = now()
+                WHERE todo_list_id = $5 AND id = $6 RETURNING id, todo_list_id, description, \"order\", due_at, completed_at, priority, rrule)
+                SELECT * FROM update",
+                details.due_at,
+                details.completed_at,
+                details.priority,
+                details.rrule,
+                todo_list_id,
+                task_id
+            ).fetch_optional(&mut transaction)
+            .traced_query("UPDATE", "tasks")
+            .await
+            .map_err(internal_error)?;
+
+        insert_task_event(WebhookEvent::TaskUpdated, result.as_ref(), outbox, &mut transaction).await?;
+
+        transaction.commit().await.map_err(internal_error)?;
+
+        Ok(result)
+    }).await
+}
+
+pub async fn replace_task(todo_list_id: Uuid, task_id: Uuid, description: String, details: TaskDetails, outbox: &mut Outbox, db_pool: &PgPool) -> Result<Option<FullTaskInfo>, ServiceError> {
+    traced("db.replace_task", async move {
+        let mut transaction = db_pool.begin().await.map_err(internal_error)?;
+
+        let result = sqlx::query_as!(
+                FullTaskInfo,
+                "UPDATE tasks
+                SET description = $1, due_at = $2, completed_at = $3, priority = $4, rrule = $5, updated_at = now()
+                WHERE todo_list_id = $6 AND id = $7
+                RETURNING id, todo_list_id, description, \"order\", due_at, completed_at, priority, rrule",
+                description,
+                details.due_at,
+                details.completed_at,
+                details.priority,
+                details.rrule,
+                todo_list_id,
+                task_id
+            ).fetch_optional(&mut transaction)
+            .traced_query("UPDATE", "tasks")
+            .await
+            .map_err(internal_error)?;
+
+        insert_task_event(WebhookEvent::TaskUpdated, result.as_ref(), outbox, &mut transaction).await?;
+
+        transaction.commit().await.map_err(internal_error)?;
+
+        Ok(result)
+    }).await
+}
+
+pub async fn move_task(todo_list_id: Uuid, task_id: Uuid, new_order: i32, outbox: &mut Outbox, db_pool: &PgPool) -> Result<Option<FullTaskInfo>, ServiceError> {
+    traced("db.move_task", async move {
+        let mut transaction = db_pool.begin().await.map_err(internal_error)?;
+        lock_todo_list(todo_list_id, &mut transaction).await?;
+
+        let previous_order = match select_task_in(todo_list_id, task_id, &mut transaction).await? {
+            Some(task) => task.order,
+            None => return Ok(None),
+        };
+
+        let task = match move_task_in(todo_list_id, task_id, new_order, Utc::now(), false, &mut transaction).await? {
+            ConditionalWrite::Applied(task) | ConditionalWrite::Outdated(task) => task,
+            ConditionalWrite::NotFound => return Ok(None),
+        };
+
+        if task.order != previous_order {
+            outbox.add(WebhookEvent::TaskMoved, &MovedTask { task: &task, previous_order })?;
+            insert_outbox(outbox, &mut transaction).await?;
+        }
+
+        transaction.commit().await.map_err(internal_error)?;
+
+        Ok(Some(task))
+    }).await
+}
+
+pub async fn select_task_changes(todo_list_id: Uuid, since: i64, db_pool: &PgPool) -> Result<Option<TaskChanges>, ServiceError> {
+    traced("db.select_task_changes", async move {
+        // sequence is read first, changes committed later are returned again by the next sync
+        let list = sqlx::query!(
+                "SELECT change_seq, name_seq
+                FROM todo_lists
+                WHERE id = $1",
+                todo_list_id
+            )
+            .fetch_optional(db_pool)
+            .traced_query("SELECT", "todo_lists")
+            .await
+            .map_err(internal_error)?;
+
+        let list = match list {
+            Some(list) => list,
+            None => return Ok(None),
+        };
+
+        let tasks = sqlx::query!(
+                "SELECT id, todo_list_id, description, \"order\", due_at, completed_at, priority, rrule, created_seq
+                FROM tasks
+                WHERE todo_list_id = $1 AND change_seq > $2
+                ORDER BY \"order\"",
+                todo_list_id,
+                since
+            )
+            .fetch_all(db_pool)
+            .traced_query("SELECT", "tasks")
+            .await
+            .map_err(internal_error)?;
+
+        let deleted = sqlx::query_as!(
+                TaskTombstone,
+                "SELECT task_id as id, deleted_at
+                FROM task_tombstones
+                WHERE todo_list_id = $1 AND change_seq > $2
+                ORDER BY change_seq",
+                todo_list_id,
+                since
+            )
+            .fetch_all(db_pool)
+            .traced_query("SELECT", "task_tombstones")
+            .await
+            .map_err(internal_error)?;
+
+        let mut changes = TaskChanges { seq: list.change_seq, name_seq: list.name_seq, created: Vec::new(), updated: Vec::new(), deleted };
+        for task in tasks {
+            let created = task.created_seq > since;
+            let task = FullTaskInfo {
+                id: task.id,
+                todo_list_id: task.todo_list_id,
+                description: task.description,
+                order: task.order,
+                due_at: task.due_at,
+                completed_at: task.completed_at,
+                priority: task.priority,
+                rrule: task.rrule
+            };
+
+            if created {
+                changes.created.push(task);
+            } else {
+                changes.updated.push(task);
+            }
+        }
+
+        Ok(Some(changes))
+    }).await
+}
+
+/// Sync writes to the list in one transaction, the list is locked until its end
+pub struct PgSyncTransaction {
+    todo_list_id: Uuid,
+    transaction: Transaction<'static, Postgres>,
+}
+
+pub async fn begin_sync_transaction(todo_list_id: Uuid, db_pool: &PgPool) -> Result<PgSyncTransaction, ServiceError> {
+    traced("db.begin_sync_transaction", async move {
+        let mut transaction = db_pool.begin().await.map_err(internal_error)?;
+        lock_todo_list(todo_list_id, &mut transaction).await?;
+
+        Ok(PgSyncTransaction { todo_list_id, transaction })
+    }).await
+}
+
+async fn is_task_deleted_in(todo_list_id: Uuid, task_id: Uuid, connection: &mut PgConnection) -> Result<bool, ServiceError> {
+    let result = sqlx::query!(
+            "SELECT task_id
+            FROM task_tombstones
+            WHERE todo_list_id = $1 AND task_id = $2",
+            todo_list_id,
+            task_id
+        )
+        .fetch_optional(connection)
+        .traced_query("SELECT", "task_tombstones")
+        .await
+        .map_err(internal_error)?;
+
+    Ok(result.is_some())
+}
+
+async fn insert_task_at_in(todo_list_id: Uuid, task_id: Uuid, description: String, order: i32, changed_at: DateTime<Utc>, connection: &mut PgConnection) -> Result<bool, ServiceError> {
+    let exists = sqlx::query!(
+            "SELECT id
+            FROM tasks
+            WHERE id = $1",
+            task_id
+        )
+        .fetch_optional(&mut *connection)
+        .traced_query("SELECT", "tasks")
+        .await
+        .map_err(internal_error)?;
+
+    if exists.is_some() {
+        return Ok(false);
+    }
+
+    // shift of orders is rolled back to the savepoint, if the id is taken
+    let mut savepoint = sqlx::Connection::begin(&mut *connection).await.map_err(internal_error)?;
+
+    let task_count = task_count(todo_list_id, &mut savepoint).await?;
+    let order = order.clamp(1, task_count as i32 + 1);
+
+    offset_add_or_remove_space(todo_list_id, order, 1, &mut savepoint).await?;
+
+    // task with the id could be inserted concurrently to list of another user
+    let inserted = sqlx::query!(
+            "INSERT INTO tasks (id, todo_list_id, description, \"order\", updated_at, moved_at)
+            VALUES ($1, $2, $3, $4, $5, $5)
+            ON CONFLICT (id) DO NOTHING",
+            task_id,
+            todo_list_id,
+            description,
+            order,
+            changed_at
+        ).execute(&mut savepoint)
+        .traced_query("INSERT", "tasks")
+        .await
+        .map_err(internal_error)?
+        .rows_affected();
+
+    if inserted == 0 {
+        return Ok(false);
+    }
+
+    savepoint.commit().await.map_err(internal_error)?;
+
+    Ok(true)
+}
+
+async fn update_task_at_in(todo_list_id: Uuid, task_id: Uuid, description: String, changed_at: DateTime<Utc>, connection: &mut PgConnection) -> Result<ConditionalWrite, ServiceError> {
+    // the same time is not older, the later written change wins
+    let result = sqlx::query_as!(
+            FullTaskInfo,
+            "UPDATE tasks
+            SET description = $1, updated_at = $4
+            WHERE todo_list_id = $2 AND id = $3 AND updated_at <= $4
+            RETURNING id, todo_list_id, description, \"order\", due_at, completed_at, priority, rrule",
+            description,
+            todo_list_id,
+            task_id,
+            changed_at
+        ).fetch_optional(&mut *connection)
+        .traced_query("UPDATE", "tasks")
+        .await
+        .map_err(internal_error)?;
+
+    if let Some(task) = result {
+        return Ok(ConditionalWrite::Applied(task));
+    }
+
+    Ok(match select_task_in(todo_list_id, task_id, connection).await? {
+        Some(task) => ConditionalWrite::Outdated(task),
+        None => ConditionalWrite::NotFound,
+    })
+}
+
+/// Moves task to `new_order` (clamped to the list bounds) and saves `moved_at`.
+/// If `conditional`, task moved after `moved_at` is not moved and is `Outdated`
+async fn move_task_in(todo_list_id: Uuid, task_id: Uuid, new_order: i32, moved_at: DateTime<Utc>, conditional: bool, connection: &mut PgConnection) -> Result<ConditionalWrite, ServiceError> {
+    lock_todo_list(todo_list_id, &mut *connection).await?;
+
+    // order is read again under lock, task could be moved by concurrent request
+    let task = sqlx::query!(
+            "SELECT id, todo_list_id, description, \"order\", due_at, completed_at, priority, rrule, moved_at
+            FROM tasks
+            WHERE todo_list_id = $1 AND id = $2",
+            todo_list_id,
+            task_id
+        )
+        .fetch_optional(&mut *connection)
+        .traced_query("SELECT", "tasks")
+        .await
+        .map_err(internal_error)?;
+
+    let task = match task {
+        Some(task) => task,
+        None => return Ok(ConditionalWrite::NotFound),
+    };
+
+    let last_moved_at = task.moved_at;
+    let task = FullTaskInfo {
+        id: task.id,
+        todo_list_id: task.todo_list_id,
+        description: task.description,
+        order: task.order,
+        due_at: task.due_at,
+        completed_at: task.completed_at,
+        priority: task.priority,
+        rrule: task.rrule
+    };
+
+    if conditional && last_moved_at > moved_at {
+        return Ok(ConditionalWrite::Outdated(task));
+    }
+
+    let task_count = task_count(todo_list_id, &mut *connection).await?;
+    let old_order = task.order;
+    let new_order = new_order.clamp(1, task_count as i32);
+
+    if old_order == new_order {
+        return Ok(ConditionalWrite::Applied(task));
+    }
+
+    // if move item from right to left, then move range from left to right
+    let range_move_left_to_right = old_order > new_order;
+
+    let mut offset_bottom = std::cmp::min(old_order, new_order);
+    let mut offset_top = std::cmp::max(old_order, new_order);
+    let offset = if range_move_left_to_right { 1 } else { -1 };
+
+    if range_move_left_to_right {
+        offset_top -= 1;
+    } else {
+        offset_bottom += 1;
+    }
+
+    offset_range(todo_list_id, offset_bottom, offset_top, offset, &mut *connection).await?;
+
+    let result = sqlx::query_as!(
+            FullTaskInfo,
+            "WITH update AS (UPDATE tasks
+            SET \"order\" = $1, moved_at = $4
+            WHERE todo_list_id = $2 AND id = $3 RETURNING id, todo_list_id, description, \"order\", due_at, completed_at, priority, rrule)
+            SELECT * FROM update",
+            new_order,
+            todo_list_id,
+            task_id,
+            moved_at
+        ).fetch_one(&mut *connection)
+        .traced_query("UPDATE", "tasks")
+        .await
+        .map_err(internal_error)?;
+
+    Ok(ConditionalWrite::Applied(result))
+}
+/// Adds event of the written task to outbox, nothing is added if the task is not found
+async fn insert_task_event(event: WebhookEvent, task: Option<&FullTaskInfo>, outbox: &mut Outbox, connection: &mut PgConnection) -> Result<(), ServiceError> {
+    if let Some(task) = task {
+        outbox.add(event, task)?;
+        insert_outbox(outbox, connection).await?;
+    }
+
+    Ok(())
+}
+
+/// Row lock of the list, serializes order changes of its tasks until the end of transaction
+async fn lock_todo_list(todo_list_id: Uuid, connection: &mut PgConnection) -> Result<(), ServiceError> {
+    sqlx::query!(
+            "SELECT id
+            FROM todo_lists
+            WHERE id = $1
+            FOR UPDATE",
+            todo_list_id
+        )
+        .fetch_optional(connection)
+        .traced_query("SELECT", "todo_lists")
+        .await
+        .map_err(internal_error)?;
+
+    Ok(())
+}
+
+async fn task_count(todo_list_id: Uuid, connection: &mut PgConnection) -> Result<i64, ServiceError> {
+    let result = sqlx::query!(
+            "SELECT COUNT(*) as count
+            FROM tasks
+            WHERE todo_list_id = $1",
+            todo_list_id
+        )
+        .fetch_one(connection)
+        .traced_query("SELECT", "tasks")
+        .await
+        .map_err(internal_error)?;
+
+    Ok(result.count.unwrap())
+}
+
+pub async fn offset_add_or_remove_space(todo_list_id: Uuid, order: i32, offset: i32, connection: &mut PgConnection) -> Result<(), ServiceError> {
+    traced("db.offset_add_or_remove_space", async move {
+        sqlx::query!(
+                "UPDATE tasks
+                SET \"order\" = \"order\" + $1
+                WHERE todo_list_id = $2 AND \"order\" >= $3;",
+                offset,
+                todo_list_id,
+                order
+            )
+            .execute(connection)
+            .traced_query("UPDATE", "tasks")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(())
+    }).await
+}
+
+pub async fn offset_range(todo_list_id: Uuid, bottom: i32, top: i32, offset: i32, connection: &mut PgConnection) -> Result<(), ServiceError> {
+    traced("db.offset_range", async move {
+        sqlx::query!(
+                "UPDATE tasks
+                SET \"order\" = \"order\" + $1
+                WHERE todo_list_id = $2 AND \"order\" >= $3 AND \"order\" <= $4;",
+                offset,
+                todo_list_id,
+                bottom,
+                top
+            )
+            .execute(connection)
+            .traced_query("UPDATE", "tasks")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(())
+    }).await
+}
+
+#[async_trait]
+impl TaskRepository for PgStorage {
+    async fn select_task_count(&self, todo_list_id: Uuid) -> Result<i64, ServiceError> {
+        select_task_count(todo_list_id, &self.pool).await
+    }
+
+    async fn insert_task_to_end(&self, todo_list_id: Uuid, description: String, outbox: &mut Outbox) -> Result<Uuid, ServiceError> {
+        insert_task_to_end(todo_list_id, description, outbox, &self.pool).await
+    }
+
+    async fn insert_tasks_to_end(&self, todo_list_id: Uuid, tasks: Vec<TaskContent>, outbox: &mut Outbox) -> Result<Vec<FullTaskInfo>, ServiceError> {
+        insert_tasks_to_end(todo_list_id, tasks, outbox, &self.pool).await
+    }
+
+    async fn insert_task_with_id(&self, todo_list_id: Uuid, task_id: Uuid, description: String, details: TaskDetails, outbox: &mut Outbox) -> Result<Option<FullTaskInfo>, ServiceError> {
+        insert_task_with_id(todo_list_id, task_id, description, details, outbox, &self.pool).await
+    }
+
+    async fn select_task(&self, todo_list_id: Uuid, task_id: Uuid) -> Result<Option<FullTaskInfo>, ServiceError> {
+        select_task(todo_list_id, task_id, &self.pool).await
+    }
+
+    async fn insert_task(&self, todo_list_id: Uuid, description: String, order: i32, outbox: &mut Outbox) -> Result<Uuid, ServiceError> {
+        insert_task(todo_list_id, description, order, outbox, &self.pool).await
+    }
+
+    async fn select_tasks(&self, todo_list_id: Uuid) -> Result<Vec<FullTaskInfo>, ServiceError> {
+        select_tasks(todo_list_id, &self.pool).await
+    }
+
+    async fn select_tasks_by_ids(&self, todo_list_id: Uuid, ids: &[Uuid]) -> Result<Vec<FullTaskInfo>, ServiceError> {
+        select_tasks_by_ids(todo_list_id, ids, &self.pool).await
+    }
+
+    async fn select_tasks_range(&self, todo_list_id: Uuid, range: TaskRange) -> Result<Vec<FullTaskInfo>, ServiceError> {
+        select_tasks_range(todo_list_id, range, &self.pool).await
+    }
+
+    async fn delete_task(&self, todo_list_id: Uuid, task_id: Uuid, outbox: &mut Outbox) -> Result<Option<FullTaskInfo>, ServiceError> {
+        delete_task(todo_list_id, task_id, outbox, &self.pool).await
+    }
+
+    async fn update_task(&self, todo_list_id: Uuid, task_id: Uuid, description: String, outbox: &mut Outbox) -> Result<Option<FullTaskInfo>, ServiceError> {
+        update_task(todo_list_id, task_id, description, outbox, &self.pool).await
+    }
+
+    async fn update_task_details(&self, todo_list_id: Uuid, task_id: Uuid, details: TaskDetails, outbox: &mut Outbox) -> Result<Option<FullTaskInfo>, ServiceError> {
+        update_task_details(todo_list_id, task_id, details, outbox, &self.pool).await
+    }
+
+    async fn replace_task(&self, todo_list_id: Uuid, task_id: Uuid, description: String, details: TaskDetails, outbox: &mut Outbox) -> Result<Option<FullTaskInfo>, ServiceError> {
+        replace_task(todo_list_id, task_id, description, details, outbox, &self.pool).await
+    }
+
+    async fn move_task(&self, todo_list_id: Uuid, task_id: Uuid, new_order: i32, outbox: &mut Outbox) -> Result<Option<FullTaskInfo>, ServiceError> {
+        move_task(todo_list_id, task_id, new_order, outbox, &self.pool).await
+    }
+
+    async fn select_task_changes(&self, todo_list_id: Uuid, since: i64) -> Result<Option<TaskChanges>, ServiceError> {
+        select_task_changes(todo_list_id, since, &self.pool).await
+    }
+
+    async fn begin_sync_transaction(&self, todo_list_id: Uuid) -> Result<Box<dyn SyncTransaction + '_>, ServiceError> {
+        Ok(Box::new(begin_sync_transaction(todo_list_id, &self.pool).await?))
+    }
+}
+
+#[async_trait]
+impl SyncTransaction for PgSyncTransaction {
+    async fn select_task_count(&mut self) -> Result<i64, ServiceError> {
+        traced("db.select_task_count", task_count(self.todo_list_id, &mut self.transaction)).await
+    }
+
+    async fn select_task(&mut self, task_id: Uuid) -> Result<Option<FullTaskInfo>, ServiceError> {
+        traced("db.select_task", select_task_in(self.todo_list_id, task_id, &mut self.transaction)).await
+    }
+
+    async fn is_task_deleted(&mut self, task_id: Uuid) -> Result<bool, ServiceError> {
+        traced("db.is_task_deleted", is_task_deleted_in(self.todo_list_id, task_id, &mut self.transaction)).await
+    }
+
+    async fn insert_task_at(&mut self, task_id: Uuid, description: String, order: i32, changed_at: DateTime<Utc>) -> Result<bool, ServiceError> {
+        traced("db.insert_task_at", insert_task_at_in(self.todo_list_id, task_id, description, order, changed_at, &mut self.transaction)).await
+    }
+
+    async fn update_task_at(&mut self, task_id: Uuid, description: String, changed_at: DateTime<Utc>) -> Result<ConditionalWrite, ServiceError> {
+        traced("db.update_task_at", update_task_at_in(self.todo_list_id, task_id, description, changed_at, &mut self.transaction)).await
+    }
+
+    async fn delete_task(&mut self, task_id: Uuid) -> Result<Option<FullTaskInfo>, ServiceError> {
+        traced("db.delete_task", delete_task_in(self.todo_list_id, task_id, &mut self.transaction)).await
+    }
+
+    async fn move_task_at(&mut self, task_id: Uuid, new_order: i32, changed_at: DateTime<Utc>) -> Result<ConditionalWrite, ServiceError> {
+        traced("db.move_task_at", move_task_in(self.todo_list_id, task_id, new_order, changed_at, true, &mut self.transaction)).await
+    }
+
+    async fn commit(mut self: Box<Self>, outbox: &Outbox) -> Result<(), ServiceError> {
+        insert_outbox(outbox, &mut self.transaction).await?;
+        self.transaction.commit().await.map_err(internal_error)
+    }
+}
\ No newline at end of file
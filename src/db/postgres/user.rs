@@ -0,0 +1,327 @@
+use async_trait::async_trait;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::PgStorage;
+use crate::db::{
+    internal_error,
+    is_unique_violation,
+    UserRepository
+};
+use crate::utils::telemetry::{
+    traced,
+    TracedQuery
+};
+use crate::models::{
+    ServiceError,
+    NewUser,
+    UserEmail
+};
+
+pub async fn insert_user(user: &NewUser, db_pool: &PgPool) -> Result<Uuid, ServiceError> {
+    traced("db.insert_user", async move {
+        let id = uuid::Uuid::new_v4();
+
+        sqlx::query!(
+                "INSERT INTO users (id, login, password)
+                VALUES ($1, $2, $3)",
+                id,
+                user.login,
+                user.password
+            ).execute(db_pool)
+            .traced_query("INSERT", "users")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(id)
+    }).await
+}
+
+pub async fn select_user_id(login: &str, password: &str, db_pool: &PgPool) -> Result<Option<Uuid>, ServiceError> {
+    traced("db.select_user_id", async move {
+        let result = sqlx::query!(
+                "SELECT id
+                FROM users
+                WHERE login = $1 AND password = $2
+                LIMIT 1",
+                login,
+                password
+            )
+            .fetch_all(db_pool)
+            .traced_query("SELECT", "users")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(result.first().map(|r| r.id))
+    }).await
+}
+
+pub async fn is_user_exist(login: &str, db_pool: &PgPool) -> std::result::Result<bool, ServiceError> {
+    traced("db.is_user_exist", async move {
+        let result = sqlx::query!(
+                "SELECT COUNT (*) as count
+                FROM users
+                WHERE login = $1 ",
+                login
+            )
+            .fetch_one(db_pool)
+            .traced_query("SELECT", "users")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(result.count.unwrap() > 0)
+    }).await
+}
+
+pub async fn select_user_login(user_id: Uuid, db_pool: &PgPool) -> Result<Option<String>, ServiceError> {
+    traced("db.select_user_login", async move {
+        let result = sqlx::query!(
+                "SELECT login
+                FROM users
+                WHERE id = $1",
+                user_id
+            )
+            .fetch_optional(db_pool)
+            .traced_query("SELECT", "users")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(result.and_then(|r| r.login))
+    }).await
+}
+
+pub async fn select_session_version(user_id: Uuid, db_pool: &PgPool) -> Result<Option<i32>, ServiceError> {
+    traced("db.select_session_version", async move {
+        let result = sqlx::query!(
+                "SELECT session_version
+                FROM users
+                WHERE id = $1",
+                user_id
+            )
+            .fetch_optional(db_pool)
+            .traced_query("SELECT", "users")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(result.map(|r| r.session_version))
+    }).await
+}
+
+pub async fn update_user_password(user_id: Uuid, old_password: &str, new_password: &str, db_pool: &PgPool) -> Result<Option<i32>, ServiceError> {
+    traced("db.update_user_password", async move {
+        let result = sqlx::query!(
+                "UPDATE users
+                SET password = $3, session_version = session_version + 1
+                WHERE id = $1 AND password = $2
+                RETURNING session_version",
+                user_id,
+                old_password,
+                new_password
+            )
+            .fetch_optional(db_pool)
+            .traced_query("UPDATE", "users")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(result.map(|r| r.session_version))
+    }).await
+}
+
+pub async fn update_user_login(user_id: Uuid, login: &str, db_pool: &PgPool) -> Result<bool, ServiceError> {
+    traced("db.update_user_login", async move {
+        let result = sqlx::query!(
+                "UPDATE users
+                SET login = $2
+                WHERE id = $1",
+                user_id,
+                login
+            ).execute(db_pool)
+            .traced_query("UPDATE", "users")
+            .await;
+
+        match result {
+            Ok(_) => Ok(true),
+            Err(e) if is_unique_violation(&e) => Ok(false),
+            Err(e) => Err(internal_error(e)),
+        }
+    }).await
+}
+
+pub async fn delete_user(user_id: Uuid, password: &str, db_pool: &PgPool) -> Result<bool, ServiceError> {
+    traced("db.delete_user", async move {
+        let mut transaction = db_pool.begin().await.map_err(internal_error)?;
+
+        // lists, tasks, tokens and 2FA are deleted by ON DELETE CASCADE
+        let deleted = sqlx::query!(
+                "DELETE FROM users
+                WHERE id = $1 AND password = $2",
+                user_id,
+                password
+            ).execute(&mut transaction)
+            .traced_query("DELETE", "users")
+            .await
+            .map_err(internal_error)?
+            .rows_affected();
+
+        if deleted == 0 {
+            return Ok(false);
+        }
+
+        // scope of idempotency keys is user id
+        sqlx::query!(
+                "DELETE FROM idempotency_keys
+                WHERE scope = $1",
+                user_id.to_string()
+            ).execute(&mut transaction)
+            .traced_query("DELETE", "idempotency_keys")
+            .await
+            .map_err(internal_error)?;
+
+        transaction.commit().await.map_err(internal_error)?;
+
+        Ok(true)
+    }).await
+}
+
+pub async fn select_user_email(user_id: Uuid, db_pool: &PgPool) -> Result<Option<UserEmail>, ServiceError> {
+    traced("db.select_user_email", async move {
+        let result = sqlx::query!(
+                "SELECT email, email_verified
+                FROM users
+                WHERE id = $1",
+                user_id
+            )
+            .fetch_optional(db_pool)
+            .traced_query("SELECT", "users")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(result.and_then(|r| r.email.map(|email| UserEmail { email, verified: r.email_verified })))
+    }).await
+}
+
+pub async fn update_user_email(user_id: Uuid, email: &str, db_pool: &PgPool) -> Result<(), ServiceError> {
+    traced("db.update_user_email", async move {
+        sqlx::query!(
+                "UPDATE users
+                SET email = $2, email_verified = false
+                WHERE id = $1",
+                user_id,
+                email
+            ).execute(db_pool)
+            .traced_query("UPDATE", "users")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(())
+    }).await
+}
+
+pub async fn verify_user_email(user_id: Uuid, email: &str, db_pool: &PgPool) -> Result<bool, ServiceError> {
+    traced("db.verify_user_email", async move {
+        let result = sqlx::query!(
+                "UPDATE users
+                SET email_verified = true
+                WHERE id = $1 AND email = $2",
+                user_id,
+                email
+            ).execute(db_pool)
+            .traced_query("UPDATE", "users")
+            .await;
+
+        match result {
+            Ok(result) => Ok(result.rows_affected() > 0),
+            Err(e) if is_unique_violation(&e) => Ok(false),
+            Err(e) => Err(internal_error(e)),
+        }
+    }).await
+}
+
+pub async fn select_user_id_by_email(email: &str, db_pool: &PgPool) -> Result<Option<Uuid>, ServiceError> {
+    traced("db.select_user_id_by_email", async move {
+        let result = sqlx::query!(
+                "SELECT id
+                FROM users
+                WHERE email = $1 AND email_verified",
+                email
+            )
+            .fetch_optional(db_pool)
+            .traced_query("SELECT", "users")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(result.map(|r| r.id))
+    }).await
+}
+
+pub async fn reset_user_password(user_id: Uuid, new_password: &str, db_pool: &PgPool) -> Result<(), ServiceError> {
+    traced("db.reset_user_password", async move {
+        sqlx::query!(
+                "UPDATE users
+                SET password = $2, session_version = session_version + 1
+                WHERE id = $1",
+                user_id,
+                new_password
+            ).execute(db_pool)
+            .traced_query("UPDATE", "users")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(())
+    }).await
+}
+
+#[async_trait]
+impl UserRepository for PgStorage {
+    async fn insert_user(&self, user: &NewUser) -> Result<Uuid, ServiceError> {
+        insert_user(user, &self.pool).await
+    }
+
+    async fn select_user_id(&self, login: &str, password: &str) -> Result<Option<Uuid>, ServiceError> {
+        select_user_id(login, password, &self.pool).await
+    }
+
+    async fn is_user_exist(&self, login: &str) -> Result<bool, ServiceError> {
+        is_user_exist(login, &self.pool).await
+    }
+
+    async fn select_user_login(&self, user_id: Uuid) -> Result<Option<String>, ServiceError> {
+        select_user_login(user_id, &self.pool).await
+    }
+
+    async fn select_session_version(&self, user_id: Uuid) -> Result<Option<i32>, ServiceError> {
+        select_session_version(user_id, &self.pool).await
+    }
+
+    async fn update_user_password(&self, user_id: Uuid, old_password: &str, new_password: &str) -> Result<Option<i32>, ServiceError> {
+        update_user_password(user_id, old_password, new_password, &self.pool).await
+    }
+
+    async fn update_user_login(&self, user_id: Uuid, login: &str) -> Result<bool, ServiceError> {
+        update_user_login(user_id, login, &self.pool).await
+    }
+
+    async fn delete_user(&self, user_id: Uuid, password: &str) -> Result<bool, ServiceError> {
+        delete_user(user_id, password, &self.pool).await
+    }
+
+    async fn select_user_email(&self, user_id: Uuid) -> Result<Option<UserEmail>, ServiceError> {
+        select_user_email(user_id, &self.pool).await
+    }
+
+    async fn update_user_email(&self, user_id: Uuid, email: &str) -> Result<(), ServiceError> {
+        update_user_email(user_id, email, &self.pool).await
+    }
+
+    async fn verify_user_email(&self, user_id: Uuid, email: &str) -> Result<bool, ServiceError> {
+        verify_user_email(user_id, email, &self.pool).await
+    }
+
+    async fn select_user_id_by_email(&self, email: &str) -> Result<Option<Uuid>, ServiceError> {
+        select_user_id_by_email(email, &self.pool).await
+    }
+
+    async fn reset_user_password(&self, user_id: Uuid, new_password: &str) -> Result<(), ServiceError> {
+        reset_user_password(user_id, new_password, &self.pool).await
+    }
+}
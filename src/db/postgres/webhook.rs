@@ -0,0 +1,391 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{
+    DateTime,
+    Utc
+};
+use sqlx::{
+    PgConnection,
+    PgPool
+};
+use uuid::Uuid;
+
+use super::PgStorage;
+use crate::db::{
+    internal_error,
+    WebhookRepository
+};
+use crate::utils::telemetry::{
+    traced,
+    TracedQuery
+};
+use crate::models::{
+    ServiceError,
+    NewWebhook,
+    UpdateWebhook,
+    WebhookInfo,
+    WebhookEvent,
+    WebhookDelivery,
+    PendingDelivery,
+    DeliveryAttempt,
+    DeliveryStatus,
+    Outbox,
+    events_from_string,
+    events_to_string
+};
+
+pub async fn insert_webhook(user_id: Uuid, webhook: &NewWebhook, secret: &str, db_pool: &PgPool) -> Result<WebhookInfo, ServiceError> {
+    traced("db.insert_webhook", async move {
+        let id = Uuid::new_v4();
+
+        let created_at = sqlx::query_scalar!(
+                "INSERT INTO webhooks (id, user_id, url, secret, events)
+                VALUES ($1, $2, $3, $4, $5)
+                RETURNING created_at",
+                id,
+                user_id,
+                webhook.url,
+                secret,
+                events_to_string(&webhook.events)
+            )
+            .fetch_one(db_pool)
+            .traced_query("INSERT", "webhooks")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(WebhookInfo {
+            id,
+            url: webhook.url.clone(),
+            events: webhook.events.clone(),
+            enabled: true,
+            consecutive_failures: 0,
+            created_at,
+        })
+    }).await
+}
+
+pub async fn select_webhooks(user_id: Uuid, db_pool: &PgPool) -> Result<Vec<WebhookInfo>, ServiceError> {
+    traced("db.select_webhooks", async move {
+        let result = sqlx::query!(
+                "SELECT id, url, events, enabled, consecutive_failures, created_at
+                FROM webhooks
+                WHERE user_id = $1
+                ORDER BY created_at",
+                user_id
+            )
+            .fetch_all(db_pool)
+            .traced_query("SELECT", "webhooks")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(result.into_iter()
+            .map(|r| WebhookInfo {
+                id: r.id,
+                url: r.url,
+                events: events_from_string(&r.events),
+                enabled: r.enabled,
+                consecutive_failures: r.consecutive_failures,
+                created_at: r.created_at,
+            })
+            .collect())
+    }).await
+}
+
+pub async fn update_webhook(user_id: Uuid, webhook_id: Uuid, update: &UpdateWebhook, db_pool: &PgPool) -> Result<Option<WebhookInfo>, ServiceError> {
+    traced("db.update_webhook", async move {
+        let result = sqlx::query!(
+                "UPDATE webhooks
+                SET url = COALESCE($3, url),
+                    secret = COALESCE($4, secret),
+                    events = COALESCE($5, events),
+                    enabled = COALESCE($6, enabled),
+                    consecutive_failures = CASE WHEN $6 THEN 0 ELSE consecutive_failures END
+                WHERE id = $1 AND user_id = $2
+                RETURNING id, url, events, enabled, consecutive_failures, created_at",
+                webhook_id,
+                user_id,
+                update.url,
+                update.secret,
+                update.events.as_deref().map(events_to_string),
+                update.enabled
+            )
+            .fetch_optional(db_pool)
+            .traced_query("UPDATE", "webhooks")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(result.map(|r| WebhookInfo {
+            id: r.id,
+            url: r.url,
+            events: events_from_string(&r.events),
+            enabled: r.enabled,
+            consecutive_failures: r.consecutive_failures,
+            created_at: r.created_at,
+        }))
+    }).await
+}
+
+pub async fn delete_webhook(user_id: Uuid, webhook_id: Uuid, db_pool: &PgPool) -> Result<bool, ServiceError> {
+    traced("db.delete_webhook", async move {
+        let deleted = sqlx::query!(
+                "DELETE FROM webhooks
+                WHERE id = $1 AND user_id = $2",
+                webhook_id,
+                user_id
+            )
+            .execute(db_pool)
+            .traced_query("DELETE", "webhooks")
+            .await
+            .map_err(internal_error)?
+            .rows_affected();
+
+        Ok(deleted > 0)
+    }).await
+}
+
+/// Adds pending delivery of every event for every enabled webhook of the user subscribed to it,
+/// in transaction of the write, which made the events
+pub async fn insert_outbox(outbox: &Outbox, connection: &mut PgConnection) -> Result<(), ServiceError> {
+    for (event, payloads) in outbox.payloads() {
+        insert_webhook_deliveries(outbox.user_id(), event, &payloads, &mut *connection).await?;
+    }
+
+    Ok(())
+}
+
+async fn insert_webhook_deliveries(user_id: Uuid, event: WebhookEvent, payloads: &[String], connection: &mut PgConnection) -> Result<u64, ServiceError> {
+    traced("db.insert_webhook_deliveries", async move {
+        let inserted = sqlx::query!(
+                "INSERT INTO webhook_deliveries (id, webhook_id, event, payload, status, next_attempt_at)
+                SELECT gen_random_uuid(), webhooks.id, $2::VARCHAR, payloads.payload, $4, now()
+                FROM webhooks
+                CROSS JOIN UNNEST($3::TEXT[]) WITH ORDINALITY AS payloads(payload, position)
+                WHERE user_id = $1 AND enabled AND (' ' || events || ' ') LIKE ('% ' || $2::TEXT || ' %')
+                ORDER BY payloads.position",
+                user_id,
+                event.as_str(),
+                payloads,
+                DeliveryStatus::Pending.as_str()
+            )
+            .execute(connection)
+            .traced_query("INSERT", "webhook_deliveries")
+            .await
+            .map_err(internal_error)?
+            .rows_affected();
+
+        Ok(inserted)
+    }).await
+}
+
+pub async fn select_webhook_deliveries(user_id: Uuid, webhook_id: Uuid, limit: i64, db_pool: &PgPool) -> Result<Option<Vec<WebhookDelivery>>, ServiceError> {
+    traced("db.select_webhook_deliveries", async move {
+        let exists = sqlx::query_scalar!(
+                r#"SELECT EXISTS(SELECT 1 FROM webhooks WHERE id = $1 AND user_id = $2) as "exists!""#,
+                webhook_id,
+                user_id
+            )
+            .fetch_one(db_pool)
+            .traced_query("SELECT", "webhooks")
+            .await
+            .map_err(internal_error)?;
+
+        if !exists {
+            return Ok(None);
+        }
+
+        let result = sqlx::query!(
+                "SELECT id, event, status, attempts, last_status_code, last_error, created_at, last_attempt_at, next_attempt_at
+                FROM webhook_deliveries
+                WHERE webhook_id = $1
+                ORDER BY created_at DESC
+                LIMIT $2",
+                webhook_id,
+                limit
+            )
+            .fetch_all(db_pool)
+            .traced_query("SELECT", "webhook_deliveries")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(Some(result.into_iter()
+            .filter_map(|r| Some(WebhookDelivery {
+                id: r.id,
+                event: WebhookEvent::parse(&r.event)?,
+                status: DeliveryStatus::parse(&r.status)?,
+                attempts: r.attempts,
+                last_status_code: r.last_status_code,
+                last_error: r.last_error,
+                created_at: r.created_at,
+                last_attempt_at: r.last_attempt_at,
+                next_attempt_at: r.next_attempt_at,
+            }))
+            .collect()))
+    }).await
+}
+
+pub async fn claim_webhook_deliveries(lease: Duration, limit: i64, db_pool: &PgPool) -> Result<Vec<PendingDelivery>, ServiceError> {
+    traced("db.claim_webhook_deliveries", async move {
+        let lease_until = Utc::now() + chrono::Duration::from_std(lease).map_err(internal_error)?;
+
+        // deliveries locked by other dispatcher are skipped
+        let result = sqlx::query!(
+                "UPDATE webhook_deliveries
+                SET next_attempt_at = $1
+                FROM webhooks
+                WHERE webhooks.id = webhook_deliveries.webhook_id
+                    AND webhook_deliveries.id IN (
+                        SELECT webhook_deliveries.id
+                        FROM webhook_deliveries
+                        JOIN webhooks ON webhooks.id = webhook_deliveries.webhook_id
+                        WHERE webhook_deliveries.status = $2 AND webhook_deliveries.next_attempt_at <= now() AND webhooks.enabled
+                        ORDER BY webhook_deliveries.next_attempt_at
+                        LIMIT $3
+                        FOR UPDATE OF webhook_deliveries SKIP LOCKED
+                    )
+                RETURNING webhook_deliveries.id, webhook_deliveries.webhook_id, webhooks.url, webhooks.secret,
+                    webhook_deliveries.event, webhook_deliveries.payload, webhook_deliveries.attempts",
+                lease_until,
+                DeliveryStatus::Pending.as_str(),
+                limit
+            )
+            .fetch_all(db_pool)
+            .traced_query("UPDATE", "webhook_deliveries")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(result.into_iter()
+            .filter_map(|r| Some(PendingDelivery {
+                id: r.id,
+                webhook_id: r.webhook_id,
+                url: r.url,
+                secret: r.secret,
+                event: WebhookEvent::parse(&r.event)?,
+                payload: r.payload,
+                attempts: r.attempts,
+            }))
+            .collect())
+    }).await
+}
+
+pub async fn record_webhook_attempt(delivery_id: Uuid, attempt: &DeliveryAttempt, disable_after: i32, db_pool: &PgPool) -> Result<bool, ServiceError> {
+    traced("db.record_webhook_attempt", async move {
+        let status = match (attempt.succeeded, attempt.retry_at) {
+            (true, _) => DeliveryStatus::Succeeded,
+            (false, Some(_)) => DeliveryStatus::Pending,
+            (false, None) => DeliveryStatus::Failed,
+        };
+        let next_attempt_at = attempt.retry_at.filter(|_| !attempt.succeeded);
+
+        let mut transaction = db_pool.begin().await.map_err(internal_error)?;
+
+        let webhook_id = sqlx::query_scalar!(
+                "UPDATE webhook_deliveries
+                SET status = $2, attempts = attempts + 1, last_status_code = $3, last_error = $4, last_attempt_at = now(), next_attempt_at = $5
+                WHERE id = $1
+                RETURNING webhook_id",
+                delivery_id,
+                status.as_str(),
+                attempt.status_code,
+                attempt.error,
+                next_attempt_at
+            )
+            .fetch_optional(&mut transaction)
+            .traced_query("UPDATE", "webhook_deliveries")
+            .await
+            .map_err(internal_error)?;
+
+        // webhook was deleted meanwhile
+        let webhook_id = match webhook_id {
+            Some(webhook_id) => webhook_id,
+            None => return Ok(false),
+        };
+
+        let disabled = if attempt.succeeded {
+            sqlx::query!(
+                    "UPDATE webhooks
+                    SET consecutive_failures = 0
+                    WHERE id = $1",
+                    webhook_id
+                )
+                .execute(&mut transaction)
+                .traced_query("UPDATE", "webhooks")
+                .await
+                .map_err(internal_error)?;
+
+            false
+        } else {
+            let result = sqlx::query!(
+                    "UPDATE webhooks
+                    SET consecutive_failures = consecutive_failures + 1, enabled = enabled AND consecutive_failures + 1 < $2
+                    WHERE id = $1
+                    RETURNING enabled, consecutive_failures",
+                    webhook_id,
+                    disable_after
+                )
+                .fetch_one(&mut transaction)
+                .traced_query("UPDATE", "webhooks")
+                .await
+                .map_err(internal_error)?;
+
+            !result.enabled && result.consecutive_failures == disable_after
+        };
+
+        transaction.commit().await.map_err(internal_error)?;
+
+        Ok(disabled)
+    }).await
+}
+
+pub async fn delete_webhook_deliveries_before(before: DateTime<Utc>, db_pool: &PgPool) -> Result<u64, ServiceError> {
+    traced("db.delete_webhook_deliveries_before", async move {
+        let deleted = sqlx::query!(
+                "DELETE FROM webhook_deliveries
+                WHERE status <> $1 AND created_at < $2",
+                DeliveryStatus::Pending.as_str(),
+                before
+            )
+            .execute(db_pool)
+            .traced_query("DELETE", "webhook_deliveries")
+            .await
+            .map_err(internal_error)?
+            .rows_affected();
+
+        Ok(deleted)
+    }).await
+}
+
+#[async_trait]
+impl WebhookRepository for PgStorage {
+    async fn insert_webhook(&self, user_id: Uuid, webhook: &NewWebhook, secret: &str) -> Result<WebhookInfo, ServiceError> {
+        insert_webhook(user_id, webhook, secret, &self.pool).await
+    }
+
+    async fn select_webhooks(&self, user_id: Uuid) -> Result<Vec<WebhookInfo>, ServiceError> {
+        select_webhooks(user_id, &self.pool).await
+    }
+
+    async fn update_webhook(&self, user_id: Uuid, webhook_id: Uuid, update: &UpdateWebhook) -> Result<Option<WebhookInfo>, ServiceError> {
+        update_webhook(user_id, webhook_id, update, &self.pool).await
+    }
+
+    async fn delete_webhook(&self, user_id: Uuid, webhook_id: Uuid) -> Result<bool, ServiceError> {
+        delete_webhook(user_id, webhook_id, &self.pool).await
+    }
+
+    async fn select_webhook_deliveries(&self, user_id: Uuid, webhook_id: Uuid, limit: i64) -> Result<Option<Vec<WebhookDelivery>>, ServiceError> {
+        select_webhook_deliveries(user_id, webhook_id, limit, &self.pool).await
+    }
+
+    async fn claim_webhook_deliveries(&self, lease: Duration, limit: i64) -> Result<Vec<PendingDelivery>, ServiceError> {
+        claim_webhook_deliveries(lease, limit, &self.pool).await
+    }
+
+    async fn record_webhook_attempt(&self, delivery_id: Uuid, attempt: &DeliveryAttempt, disable_after: i32) -> Result<bool, ServiceError> {
+        record_webhook_attempt(delivery_id, attempt, disable_after, &self.pool).await
+    }
+
+    async fn delete_webhook_deliveries_before(&self, before: DateTime<Utc>) -> Result<u64, ServiceError> {
+        delete_webhook_deliveries_before(before, &self.pool).await
+    }
+}
@@ -0,0 +1,155 @@
+use async_trait::async_trait;
+use chrono::{
+    DateTime,
+    TimeZone,
+    Utc
+};
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+use super::{
+    SqliteStorage,
+    DB_SYSTEM
+};
+use crate::db::{
+    internal_error,
+    AccessTokenRepository
+};
+use crate::utils::telemetry::{
+    traced,
+    TracedQuery
+};
+use crate::models::{
+    ServiceError,
+    NewAccessToken,
+    AccessTokenInfo,
+    AccessTokenOwner,
+    scopes_from_string,
+    scopes_to_string
+};
+
+/// Timestamps are stored as unix seconds
+fn from_timestamp(timestamp: i64) -> DateTime<Utc> {
+    Utc.timestamp_opt(timestamp, 0).single().unwrap_or_default()
+}
+
+pub async fn insert_access_token(user_id: Uuid, token_hash: &str, token: &NewAccessToken, expires_at: Option<DateTime<Utc>>, db_pool: &SqlitePool) -> Result<AccessTokenInfo, ServiceError> {
+    traced("db.insert_access_token", async move {
+        let id = Uuid::new_v4();
+        let created_at = from_timestamp(Utc::now().timestamp());
+        let expires_at = expires_at.map(|expires_at| from_timestamp(expires_at.timestamp()));
+
+        sqlx::query(
+                "INSERT INTO access_tokens (id, user_id, name, token_hash, scopes, created_at, expires_at)
+                VALUES (?, ?, ?, ?, ?, ?, ?)"
+            )
+            .bind(id)
+            .bind(user_id)
+            .bind(&token.name)
+            .bind(token_hash)
+            .bind(scopes_to_string(&token.scopes))
+            .bind(created_at.timestamp())
+            .bind(expires_at.map(|expires_at| expires_at.timestamp()))
+            .execute(db_pool)
+            .traced_query_on(DB_SYSTEM, "INSERT", "access_tokens")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(AccessTokenInfo {
+            id,
+            name: token.name.clone(),
+            scopes: token.scopes.clone(),
+            created_at,
+            expires_at,
+            last_used_at: None,
+        })
+    }).await
+}
+
+type AccessTokenRow = (Uuid, String, String, i64, Option<i64>, Option<i64>);
+
+pub async fn select_access_tokens(user_id: Uuid, db_pool: &SqlitePool) -> Result<Vec<AccessTokenInfo>, ServiceError> {
+    traced("db.select_access_tokens", async move {
+        let result: Vec<AccessTokenRow> = sqlx::query_as(
+                "SELECT id, name, scopes, created_at, expires_at, last_used_at
+                FROM access_tokens
+                WHERE user_id = ?
+                ORDER BY created_at, rowid"
+            )
+            .bind(user_id)
+            .fetch_all(db_pool)
+            .traced_query_on(DB_SYSTEM, "SELECT", "access_tokens")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(result.into_iter()
+            .map(|(id, name, scopes, created_at, expires_at, last_used_at)| AccessTokenInfo {
+                id,
+                name,
+                scopes: scopes_from_string(&scopes),
+                created_at: from_timestamp(created_at),
+                expires_at: expires_at.map(from_timestamp),
+                last_used_at: last_used_at.map(from_timestamp),
+            })
+            .collect())
+    }).await
+}
+
+pub async fn delete_access_token(user_id: Uuid, token_id: Uuid, db_pool: &SqlitePool) -> Result<bool, ServiceError> {
+    traced("db.delete_access_token", async move {
+        let deleted = sqlx::query(
+                "DELETE FROM access_tokens
+                WHERE id = ? AND user_id = ?"
+            )
+            .bind(token_id)
+            .bind(user_id)
+            .execute(db_pool)
+            .traced_query_on(DB_SYSTEM, "DELETE", "access_tokens")
+            .await
+            .map_err(internal_error)?
+            .rows_affected();
+
+        Ok(deleted > 0)
+    }).await
+}
+
+pub async fn use_access_token(token_hash: &str, db_pool: &SqlitePool) -> Result<Option<AccessTokenOwner>, ServiceError> {
+    traced("db.use_access_token", async move {
+        let now = Utc::now().timestamp();
+
+        let result: Option<(Uuid, String)> = sqlx::query_as(
+                "UPDATE access_tokens
+                SET last_used_at = ?
+                WHERE token_hash = ? AND (expires_at IS NULL OR expires_at > ?)
+                RETURNING user_id, scopes"
+            )
+            .bind(now)
+            .bind(token_hash)
+            .bind(now)
+            .fetch_optional(db_pool)
+            .traced_query_on(DB_SYSTEM, "UPDATE", "access_tokens")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(result.map(|(user_id, scopes)| AccessTokenOwner { user_id, scopes: scopes_from_string(&scopes) }))
+    }).await
+}
+
+#[async_trait]
+impl AccessTokenRepository for SqliteStorage {
+    async fn insert_access_token(&self, user_id: Uuid, token_hash: &str, token: &NewAccessToken, expires_at: Option<DateTime<Utc>>) -> Result<AccessTokenInfo, ServiceError> {
+        insert_access_token(user_id, token_hash, token, expires_at, &self.pool).await
+    }
+
+    async fn select_access_tokens(&self, user_id: Uuid) -> Result<Vec<AccessTokenInfo>, ServiceError> {
+        select_access_tokens(user_id, &self.pool).await
+    }
+
+    async fn delete_access_token(&self, user_id: Uuid, token_id: Uuid) -> Result<bool, ServiceError> {
+        delete_access_token(user_id, token_id, &self.pool).await
+    }
+
+    async fn use_access_token(&self, token_hash: &str) -> Result<Option<AccessTokenOwner>, ServiceError> {
+        use_access_token(token_hash, &self.pool).await
+    }
+}
@@ -0,0 +1,125 @@
+use async_trait::async_trait;
+use chrono::{
+    DateTime,
+    TimeZone,
+    Utc
+};
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+use super::{
+    SqliteStorage,
+    DB_SYSTEM
+};
+use crate::db::{
+    internal_error,
+    CalendarFeedRepository
+};
+use crate::utils::telemetry::{
+    traced,
+    TracedQuery
+};
+use crate::models::{
+    ServiceError,
+    CalendarFeedInfo
+};
+
+/// Timestamps are stored as unix seconds
+fn from_timestamp(timestamp: i64) -> DateTime<Utc> {
+    Utc.timestamp_opt(timestamp, 0).single().unwrap_or_default()
+}
+
+pub async fn upsert_calendar_feed(user_id: Uuid, token_hash: &str, db_pool: &SqlitePool) -> Result<CalendarFeedInfo, ServiceError> {
+    traced("db.upsert_calendar_feed", async move {
+        let created_at = from_timestamp(Utc::now().timestamp());
+
+        sqlx::query(
+                "INSERT INTO calendar_feeds (user_id, token_hash, created_at)
+                VALUES (?, ?, ?)
+                ON CONFLICT (user_id) DO UPDATE
+                SET token_hash = excluded.token_hash, created_at = excluded.created_at, last_used_at = NULL"
+            )
+            .bind(user_id)
+            .bind(token_hash)
+            .bind(created_at.timestamp())
+            .execute(db_pool)
+            .traced_query_on(DB_SYSTEM, "INSERT", "calendar_feeds")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(CalendarFeedInfo { created_at, last_used_at: None })
+    }).await
+}
+
+pub async fn select_calendar_feed(user_id: Uuid, db_pool: &SqlitePool) -> Result<Option<CalendarFeedInfo>, ServiceError> {
+    traced("db.select_calendar_feed", async move {
+        let result: Option<(i64, Option<i64>)> = sqlx::query_as(
+                "SELECT created_at, last_used_at
+                FROM calendar_feeds
+                WHERE user_id = ?"
+            )
+            .bind(user_id)
+            .fetch_optional(db_pool)
+            .traced_query_on(DB_SYSTEM, "SELECT", "calendar_feeds")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(result.map(|(created_at, last_used_at)| CalendarFeedInfo {
+            created_at: from_timestamp(created_at),
+            last_used_at: last_used_at.map(from_timestamp),
+        }))
+    }).await
+}
+
+pub async fn delete_calendar_feed(user_id: Uuid, db_pool: &SqlitePool) -> Result<bool, ServiceError> {
+    traced("db.delete_calendar_feed", async move {
+        let deleted = sqlx::query(
+                "DELETE FROM calendar_feeds
+                WHERE user_id = ?"
+            )
+            .bind(user_id)
+            .execute(db_pool)
+            .traced_query_on(DB_SYSTEM, "DELETE", "calendar_feeds")
+            .await
+            .map_err(internal_error)?
+            .rows_affected();
+
+        Ok(deleted > 0)
+    }).await
+}
+
+pub async fn use_calendar_feed(token_hash: &str, db_pool: &SqlitePool) -> Result<Option<Uuid>, ServiceError> {
+    traced("db.use_calendar_feed", async move {
+        sqlx::query_scalar(
+                "UPDATE calendar_feeds
+                SET last_used_at = ?
+                WHERE token_hash = ?
+                RETURNING user_id"
+            )
+            .bind(Utc::now().timestamp())
+            .bind(token_hash)
+            .fetch_optional(db_pool)
+            .traced_query_on(DB_SYSTEM, "UPDATE", "calendar_feeds")
+            .await
+            .map_err(internal_error)
+    }).await
+}
+
+#[async_trait]
+impl CalendarFeedRepository for SqliteStorage {
+    async fn upsert_calendar_feed(&self, user_id: Uuid, token_hash: &str) -> Result<CalendarFeedInfo, ServiceError> {
+        upsert_calendar_feed(user_id, token_hash, &self.pool).await
+    }
+
+    async fn select_calendar_feed(&self, user_id: Uuid) -> Result<Option<CalendarFeedInfo>, ServiceError> {
+        select_calendar_feed(user_id, &self.pool).await
+    }
+
+    async fn delete_calendar_feed(&self, user_id: Uuid) -> Result<bool, ServiceError> {
+        delete_calendar_feed(user_id, &self.pool).await
+    }
+
+    async fn use_calendar_feed(&self, token_hash: &str) -> Result<Option<Uuid>, ServiceError> {
+        use_calendar_feed(token_hash, &self.pool).await
+    }
+}
@@ -0,0 +1,103 @@
+use async_trait::async_trait;
+use chrono::{
+    DateTime,
+    Utc
+};
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+use super::{
+    SqliteStorage,
+    DB_SYSTEM
+};
+use crate::db::{
+    internal_error,
+    EmailTokenRepository
+};
+use crate::utils::telemetry::{
+    traced,
+    TracedQuery
+};
+use crate::models::{
+    ServiceError,
+    EmailTokenPurpose,
+    EmailTokenOwner
+};
+
+pub async fn insert_email_token(user_id: Uuid, token_hash: &str, purpose: EmailTokenPurpose, email: &str, expires_at: DateTime<Utc>, db_pool: &SqlitePool) -> Result<(), ServiceError> {
+    traced("db.insert_email_token", async move {
+        sqlx::query(
+                "INSERT INTO email_tokens (token_hash, user_id, purpose, email, expires_at)
+                VALUES (?, ?, ?, ?, ?)"
+            )
+            .bind(token_hash)
+            .bind(user_id)
+            .bind(purpose.as_str())
+            .bind(email)
+            .bind(expires_at.timestamp())
+            .execute(db_pool)
+            .traced_query_on(DB_SYSTEM, "INSERT", "email_tokens")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(())
+    }).await
+}
+
+pub async fn use_email_token(token_hash: &str, purpose: EmailTokenPurpose, db_pool: &SqlitePool) -> Result<Option<EmailTokenOwner>, ServiceError> {
+    traced("db.use_email_token", async move {
+        let mut transaction = db_pool.begin().await.map_err(internal_error)?;
+
+        sqlx::query(
+                "DELETE FROM email_tokens
+                WHERE expires_at <= ?"
+            )
+            .bind(Utc::now().timestamp())
+            .execute(&mut transaction)
+            .traced_query_on(DB_SYSTEM, "DELETE", "email_tokens")
+            .await
+            .map_err(internal_error)?;
+
+        let owner: Option<(Uuid, String)> = sqlx::query_as(
+                "DELETE FROM email_tokens
+                WHERE token_hash = ? AND purpose = ?
+                RETURNING user_id, email"
+            )
+            .bind(token_hash)
+            .bind(purpose.as_str())
+            .fetch_optional(&mut transaction)
+            .traced_query_on(DB_SYSTEM, "DELETE", "email_tokens")
+            .await
+            .map_err(internal_error)?;
+
+        let owner = owner.map(|(user_id, email)| EmailTokenOwner { user_id, email });
+
+        if let Some(owner) = &owner {
+            sqlx::query(
+                    "DELETE FROM email_tokens
+                    WHERE user_id = ? AND purpose = ?"
+                )
+                .bind(owner.user_id)
+                .bind(purpose.as_str())
+                .execute(&mut transaction)
+                .traced_query_on(DB_SYSTEM, "DELETE", "email_tokens")
+                .await
+                .map_err(internal_error)?;
+        }
+
+        transaction.commit().await.map_err(internal_error)?;
+
+        Ok(owner)
+    }).await
+}
+
+#[async_trait]
+impl EmailTokenRepository for SqliteStorage {
+    async fn insert_email_token(&self, user_id: Uuid, token_hash: &str, purpose: EmailTokenPurpose, email: &str, expires_at: DateTime<Utc>) -> Result<(), ServiceError> {
+        insert_email_token(user_id, token_hash, purpose, email, expires_at, &self.pool).await
+    }
+
+    async fn use_email_token(&self, token_hash: &str, purpose: EmailTokenPurpose) -> Result<Option<EmailTokenOwner>, ServiceError> {
+        use_email_token(token_hash, purpose, &self.pool).await
+    }
+}
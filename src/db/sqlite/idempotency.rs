@@ -0,0 +1,136 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use sqlx::SqlitePool;
+
+use super::{
+    SqliteStorage,
+    DB_SYSTEM
+};
+use crate::db::{
+    internal_error,
+    IdempotencyRepository
+};
+use crate::utils::telemetry::{
+    traced,
+    TracedQuery
+};
+use crate::models::{
+    ServiceError,
+    IdempotencyState,
+    IdempotentResponse
+};
+
+/// Fingerprint of the request and its stored response, if it is completed
+type IdempotencyRow = (String, Option<i32>, Option<String>, Option<Vec<u8>>);
+
+pub async fn start_idempotent_request(scope: &str, key: &str, fingerprint: &str, ttl: Duration, db_pool: &SqlitePool) -> Result<IdempotencyState, ServiceError> {
+    traced("db.start_idempotent_request", async move {
+        let now = Utc::now().timestamp();
+
+        sqlx::query(
+                "DELETE FROM idempotency_keys
+                WHERE expires_at <= ?"
+            )
+            .bind(now)
+            .execute(db_pool)
+            .traced_query_on(DB_SYSTEM, "DELETE", "idempotency_keys")
+            .await
+            .map_err(internal_error)?;
+
+        let inserted = sqlx::query(
+                "INSERT INTO idempotency_keys (scope, key, fingerprint, expires_at)
+                VALUES (?, ?, ?, ?)
+                ON CONFLICT (scope, key) DO NOTHING"
+            )
+            .bind(scope)
+            .bind(key)
+            .bind(fingerprint)
+            .bind(now + ttl.as_secs() as i64)
+            .execute(db_pool)
+            .traced_query_on(DB_SYSTEM, "INSERT", "idempotency_keys")
+            .await
+            .map_err(internal_error)?
+            .rows_affected();
+
+        if inserted > 0 {
+            return Ok(IdempotencyState::Started);
+        }
+
+        let record: Option<IdempotencyRow> = sqlx::query_as(
+                "SELECT fingerprint, status_code, content_type, body
+                FROM idempotency_keys
+                WHERE scope = ? AND key = ?"
+            )
+            .bind(scope)
+            .bind(key)
+            .fetch_optional(db_pool)
+            .traced_query_on(DB_SYSTEM, "SELECT", "idempotency_keys")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(match record {
+            Some((record_fingerprint, _, _, _)) if record_fingerprint != fingerprint => IdempotencyState::Mismatch,
+            Some((_, Some(status_code), content_type, body)) => IdempotencyState::Completed(IdempotentResponse {
+                status_code: status_code as u16,
+                content_type,
+                body: body.unwrap_or_default(),
+            }),
+            _ => IdempotencyState::InProgress,
+        })
+    }).await
+}
+
+pub async fn complete_idempotent_request(scope: &str, key: &str, response: &IdempotentResponse, db_pool: &SqlitePool) -> Result<(), ServiceError> {
+    traced("db.complete_idempotent_request", async move {
+        sqlx::query(
+                "UPDATE idempotency_keys
+                SET status_code = ?, content_type = ?, body = ?
+                WHERE scope = ? AND key = ?"
+            )
+            .bind(response.status_code as i32)
+            .bind(&response.content_type)
+            .bind(&response.body)
+            .bind(scope)
+            .bind(key)
+            .execute(db_pool)
+            .traced_query_on(DB_SYSTEM, "UPDATE", "idempotency_keys")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(())
+    }).await
+}
+
+pub async fn cancel_idempotent_request(scope: &str, key: &str, db_pool: &SqlitePool) -> Result<(), ServiceError> {
+    traced("db.cancel_idempotent_request", async move {
+        sqlx::query(
+                "DELETE FROM idempotency_keys
+                WHERE scope = ? AND key = ?"
+            )
+            .bind(scope)
+            .bind(key)
+            .execute(db_pool)
+            .traced_query_on(DB_SYSTEM, "DELETE", "idempotency_keys")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(())
+    }).await
+}
+
+#[async_trait]
+impl IdempotencyRepository for SqliteStorage {
+    async fn start_idempotent_request(&self, scope: &str, key: &str, fingerprint: &str, ttl: Duration) -> Result<IdempotencyState, ServiceError> {
+        start_idempotent_request(scope, key, fingerprint, ttl, &self.pool).await
+    }
+
+    async fn complete_idempotent_request(&self, scope: &str, key: &str, response: &IdempotentResponse) -> Result<(), ServiceError> {
+        complete_idempotent_request(scope, key, response, &self.pool).await
+    }
+
+    async fn cancel_idempotent_request(&self, scope: &str, key: &str) -> Result<(), ServiceError> {
+        cancel_idempotent_request(scope, key, &self.pool).await
+    }
+}
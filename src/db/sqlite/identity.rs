@@ -0,0 +1,161 @@
+use async_trait::async_trait;
+use chrono::{
+    DateTime,
+    TimeZone,
+    Utc
+};
+use sqlx::{
+    Sqlite,
+    SqlitePool,
+    Transaction
+};
+use uuid::Uuid;
+
+use super::{
+    SqliteStorage,
+    DB_SYSTEM
+};
+use crate::db::{
+    internal_error,
+    is_unique_violation,
+    IdentityRepository
+};
+use crate::utils::telemetry::{
+    traced,
+    TracedQuery
+};
+use crate::models::{
+    ServiceError,
+    NewUser,
+    NewIdentity,
+    UserIdentity
+};
+
+/// Timestamps are stored as unix seconds
+fn from_timestamp(timestamp: i64) -> DateTime<Utc> {
+    Utc.timestamp_opt(timestamp, 0).single().unwrap_or_default()
+}
+
+pub async fn select_identity_user_id(issuer: &str, subject: &str, db_pool: &SqlitePool) -> Result<Option<Uuid>, ServiceError> {
+    traced("db.select_identity_user_id", async move {
+        sqlx::query_scalar(
+                "SELECT user_id
+                FROM user_identities
+                WHERE issuer = ? AND subject = ?"
+            )
+            .bind(issuer)
+            .bind(subject)
+            .fetch_optional(db_pool)
+            .traced_query_on(DB_SYSTEM, "SELECT", "user_identities")
+            .await
+            .map_err(internal_error)
+    }).await
+}
+
+pub async fn insert_identity_user(user: &NewUser, identity: &NewIdentity, db_pool: &SqlitePool) -> Result<Option<Uuid>, ServiceError> {
+    traced("db.insert_identity_user", async move {
+        let id = Uuid::new_v4();
+        let mut transaction = db_pool.begin().await.map_err(internal_error)?;
+
+        let result = sqlx::query(
+                "INSERT INTO users (id, login, password)
+                VALUES (?, ?, ?)"
+            )
+            .bind(id)
+            .bind(&user.login)
+            .bind(&user.password)
+            .execute(&mut transaction)
+            .traced_query_on(DB_SYSTEM, "INSERT", "users")
+            .await;
+
+        match result {
+            Ok(_) => (),
+            Err(e) if is_unique_violation(&e) => return Ok(None),
+            Err(e) => return Err(internal_error(e)),
+        }
+
+        if !insert_identity_on(id, identity, &mut transaction).await? {
+            return Ok(None);
+        }
+
+        transaction.commit().await.map_err(internal_error)?;
+
+        Ok(Some(id))
+    }).await
+}
+
+pub async fn insert_identity(user_id: Uuid, identity: &NewIdentity, db_pool: &SqlitePool) -> Result<bool, ServiceError> {
+    traced("db.insert_identity", async move {
+        let mut transaction = db_pool.begin().await.map_err(internal_error)?;
+
+        if !insert_identity_on(user_id, identity, &mut transaction).await? {
+            return Ok(false);
+        }
+
+        transaction.commit().await.map_err(internal_error)?;
+
+        Ok(true)
+    }).await
+}
+
+/// `false` on unique violation
+async fn insert_identity_on(user_id: Uuid, identity: &NewIdentity, transaction: &mut Transaction<'_, Sqlite>) -> Result<bool, ServiceError> {
+    let result = sqlx::query(
+            "INSERT INTO user_identities (id, user_id, issuer, subject, email, created_at)
+            VALUES (?, ?, ?, ?, ?, ?)"
+        )
+        .bind(Uuid::new_v4())
+        .bind(user_id)
+        .bind(&identity.issuer)
+        .bind(&identity.subject)
+        .bind(&identity.email)
+        .bind(Utc::now().timestamp())
+        .execute(transaction)
+        .traced_query_on(DB_SYSTEM, "INSERT", "user_identities")
+        .await;
+
+    match result {
+        Ok(_) => Ok(true),
+        Err(e) if is_unique_violation(&e) => Ok(false),
+        Err(e) => Err(internal_error(e)),
+    }
+}
+
+pub async fn select_identities(user_id: Uuid, db_pool: &SqlitePool) -> Result<Vec<UserIdentity>, ServiceError> {
+    traced("db.select_identities", async move {
+        let result: Vec<(Uuid, String, String, Option<String>, i64)> = sqlx::query_as(
+                "SELECT id, issuer, subject, email, created_at
+                FROM user_identities
+                WHERE user_id = ?
+                ORDER BY created_at, rowid"
+            )
+            .bind(user_id)
+            .fetch_all(db_pool)
+            .traced_query_on(DB_SYSTEM, "SELECT", "user_identities")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(result.into_iter()
+            .map(|(id, issuer, subject, email, created_at)| UserIdentity { id, issuer, subject, email, created_at: from_timestamp(created_at) })
+            .collect())
+    }).await
+}
+
+#[async_trait]
+impl IdentityRepository for SqliteStorage {
+    async fn select_identity_user_id(&self, issuer: &str, subject: &str) -> Result<Option<Uuid>, ServiceError> {
+        select_identity_user_id(issuer, subject, &self.pool).await
+    }
+
+    async fn insert_identity_user(&self, user: &NewUser, identity: &NewIdentity) -> Result<Option<Uuid>, ServiceError> {
+        insert_identity_user(user, identity, &self.pool).await
+    }
+
+    async fn insert_identity(&self, user_id: Uuid, identity: &NewIdentity) -> Result<bool, ServiceError> {
+        insert_identity(user_id, identity, &self.pool).await
+    }
+
+    async fn select_identities(&self, user_id: Uuid) -> Result<Vec<UserIdentity>, ServiceError> {
+        select_identities(user_id, &self.pool).await
+    }
+}
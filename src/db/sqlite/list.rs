@@ -0,0 +1,271 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use sqlx::{
+    SqliteConnection,
+    SqlitePool
+};
+use uuid::Uuid;
+
+use super::{
+    task::insert_task_row,
+    webhook::insert_outbox,
+    SqliteStorage,
+    DB_SYSTEM
+};
+use crate::db::{
+    internal_error,
+    TodoListRepository
+};
+use crate::utils::telemetry::{
+    traced,
+    TracedQuery
+};
+use crate::models::{
+    ServiceError,
+    UpdateTodoList,
+    NewTodoList,
+    FullTaskInfo,
+    FullTodoListInfo,
+    DeletedTodoList,
+    RestoredTodoList,
+    TaskContent,
+    TaskDetails,
+    WebhookEvent,
+    Outbox
+};
+
+pub async fn insert_todo_list(user_id: Uuid, todo_list: &NewTodoList, outbox: &mut Outbox, db_pool: &SqlitePool) -> Result<Uuid, ServiceError> {
+    traced("db.insert_todo_list", async move {
+        let id = uuid::Uuid::new_v4();
+
+        let mut transaction = db_pool.begin().await.map_err(internal_error)?;
+
+        sqlx::query(
+                "INSERT INTO todo_lists (id, user_id, name)
+                VALUES (?, ?, ?)"
+            )
+            .bind(id)
+            .bind(user_id)
+            .bind(&todo_list.name)
+            .execute(&mut transaction)
+            .traced_query_on(DB_SYSTEM, "INSERT", "todo_lists")
+            .await
+            .map_err(internal_error)?;
+
+        outbox.add(WebhookEvent::ListCreated, &FullTodoListInfo { id, user_id, name: todo_list.name.clone() })?;
+        insert_outbox(outbox, &mut transaction).await?;
+
+        transaction.commit().await.map_err(internal_error)?;
+
+        Ok(id)
+    }).await
+}
+
+pub async fn select_todo_list_id(user_id: Uuid, db_pool: &SqlitePool) -> Result<Option<Uuid>, ServiceError> {
+    traced("db.select_todo_list_id", async move {
+        let result = sqlx::query_scalar(
+                "SELECT id
+                FROM todo_lists
+                WHERE user_id = ?
+                LIMIT 1"
+            )
+            .bind(user_id)
+            .fetch_optional(db_pool)
+            .traced_query_on(DB_SYSTEM, "SELECT", "todo_lists")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(result)
+    }).await
+}
+
+pub async fn delete_todo_list(user_id: Uuid, outbox: &mut Outbox, db_pool: &SqlitePool) -> Result<Option<DeletedTodoList>, ServiceError> {
+    traced("db.delete_todo_list", async move {
+        let mut transaction = db_pool.begin().await.map_err(internal_error)?;
+
+        let todo_list_id = match select_user_todo_list(user_id, &mut transaction).await? {
+            Some(todo_list_id) => todo_list_id,
+            None => return Ok(None),
+        };
+
+        let deleted = delete_todo_list_in(todo_list_id, &mut transaction).await?;
+
+        outbox.add(WebhookEvent::ListDeleted, &deleted)?;
+        insert_outbox(outbox, &mut transaction).await?;
+
+        transaction.commit().await.map_err(internal_error)?;
+
+        Ok(Some(deleted))
+    }).await
+}
+
+pub async fn update_todo_list(todo_list_id: Uuid, update_list: &UpdateTodoList, outbox: &mut Outbox, db_pool: &SqlitePool) -> Result<(), ServiceError> {
+    traced("db.update_todo_list", async move {
+        let mut transaction = db_pool.begin().await.map_err(internal_error)?;
+
+        let updated: Option<FullTodoListInfo> = sqlx::query_as(
+                "UPDATE todo_lists
+                SET name = ?
+                WHERE id = ?
+                RETURNING id, user_id, name"
+            )
+            .bind(&update_list.name)
+            .bind(todo_list_id)
+            .fetch_optional(&mut transaction)
+            .traced_query_on(DB_SYSTEM, "UPDATE", "todo_lists")
+            .await
+            .map_err(internal_error)?;
+
+        if let Some(updated) = updated {
+            outbox.add(WebhookEvent::ListUpdated, &updated)?;
+            insert_outbox(outbox, &mut transaction).await?;
+        }
+
+        transaction.commit().await.map_err(internal_error)?;
+
+        Ok(())
+    }).await
+}
+
+pub async fn select_todo_list(user_id: Uuid, db_pool: &SqlitePool) -> Result<Option<FullTodoListInfo>, ServiceError> {
+    traced("db.select_todo_list", async move {
+        let result = sqlx::query_as(
+                "SELECT id, user_id, name
+                FROM todo_lists
+                WHERE user_id = ?"
+            )
+            .bind(user_id)
+            .fetch_optional(db_pool)
+            .traced_query_on(DB_SYSTEM, "SELECT", "todo_lists")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(result)
+    }).await
+}
+
+pub async fn restore_todo_list(user_id: Uuid, name: String, tasks: Vec<TaskContent>, replace: bool, outbox: &mut Outbox, db_pool: &SqlitePool) -> Result<Option<RestoredTodoList>, ServiceError> {
+    traced("db.restore_todo_list", async move {
+        let mut transaction = db_pool.begin().await.map_err(internal_error)?;
+
+        let mut replaced = None;
+        if let Some(current) = select_user_todo_list(user_id, &mut transaction).await? {
+            if !replace {
+                return Ok(None);
+            }
+
+            replaced = Some(delete_todo_list_in(current, &mut transaction).await?);
+        }
+
+        let id = uuid::Uuid::new_v4();
+
+        // list created concurrently is the same as list, which existed before
+        let inserted = sqlx::query(
+                "INSERT INTO todo_lists (id, user_id, name)
+                VALUES (?, ?, ?)
+                ON CONFLICT (user_id) DO NOTHING"
+            )
+            .bind(id)
+            .bind(user_id)
+            .bind(&name)
+            .execute(&mut transaction)
+            .traced_query_on(DB_SYSTEM, "INSERT", "todo_lists")
+            .await
+            .map_err(internal_error)?
+            .rows_affected();
+
+        if inserted == 0 {
+            return Ok(None);
+        }
+
+        let mut restored = Vec::with_capacity(tasks.len());
+        for (index, TaskContent { description, details }) in tasks.into_iter().enumerate() {
+            let TaskDetails { due_at, completed_at, priority, rrule } = details;
+            let task = FullTaskInfo { id: uuid::Uuid::new_v4(), todo_list_id: id, description, order: index as i32 + 1, due_at, completed_at, priority, rrule };
+
+            insert_task_row(&task, Utc::now(), &mut transaction).await?;
+
+            restored.push(task);
+        }
+
+        let list = FullTodoListInfo { id, user_id, name };
+
+        if let Some(replaced) = &replaced {
+            outbox.add(WebhookEvent::ListDeleted, replaced)?;
+        }
+        outbox.add(WebhookEvent::ListCreated, &list)?;
+        for task in &restored {
+            outbox.add(WebhookEvent::TaskCreated, task)?;
+        }
+        insert_outbox(outbox, &mut transaction).await?;
+
+        transaction.commit().await.map_err(internal_error)?;
+
+        Ok(Some(RestoredTodoList { list, tasks: restored, replaced }))
+    }).await
+}
+
+async fn select_user_todo_list(user_id: Uuid, connection: &mut SqliteConnection) -> Result<Option<Uuid>, ServiceError> {
+    sqlx::query_scalar(
+            "SELECT id
+            FROM todo_lists
+            WHERE user_id = ?"
+        )
+        .bind(user_id)
+        .fetch_optional(connection)
+        .traced_query_on(DB_SYSTEM, "SELECT", "todo_lists")
+        .await
+        .map_err(internal_error)
+}
+
+async fn delete_todo_list_in(todo_list_id: Uuid, connection: &mut SqliteConnection) -> Result<DeletedTodoList, ServiceError> {
+    let deleted_tasks = sqlx::query(
+            "DELETE FROM tasks
+            WHERE todo_list_id = ?"
+        )
+        .bind(todo_list_id)
+        .execute(&mut *connection)
+        .traced_query_on(DB_SYSTEM, "DELETE", "tasks")
+        .await
+        .map_err(internal_error)?
+        .rows_affected();
+
+    sqlx::query(
+            "DELETE FROM todo_lists
+            WHERE id = ?"
+        )
+        .bind(todo_list_id)
+        .execute(&mut *connection)
+        .traced_query_on(DB_SYSTEM, "DELETE", "todo_lists")
+        .await
+        .map_err(internal_error)?;
+
+    Ok(DeletedTodoList { id: todo_list_id, deleted_tasks: deleted_tasks as i64 })
+}
+
+#[async_trait]
+impl TodoListRepository for SqliteStorage {
+    async fn insert_todo_list(&self, user_id: Uuid, todo_list: &NewTodoList, outbox: &mut Outbox) -> Result<Uuid, ServiceError> {
+        insert_todo_list(user_id, todo_list, outbox, &self.pool).await
+    }
+
+    async fn select_todo_list_id(&self, user_id: Uuid) -> Result<Option<Uuid>, ServiceError> {
+        select_todo_list_id(user_id, &self.pool).await
+    }
+
+    async fn delete_todo_list(&self, user_id: Uuid, outbox: &mut Outbox) -> Result<Option<DeletedTodoList>, ServiceError> {
+        delete_todo_list(user_id, outbox, &self.pool).await
+    }
+
+    async fn update_todo_list(&self, todo_list_id: Uuid, update_list: &UpdateTodoList, outbox: &mut Outbox) -> Result<(), ServiceError> {
+        update_todo_list(todo_list_id, update_list, outbox, &self.pool).await
+    }
+
+    async fn select_todo_list(&self, user_id: Uuid) -> Result<Option<FullTodoListInfo>, ServiceError> {
+        select_todo_list(user_id, &self.pool).await
+    }
+
+    async fn restore_todo_list(&self, user_id: Uuid, name: String, tasks: Vec<TaskContent>, replace: bool, outbox: &mut Outbox) -> Result<Option<RestoredTodoList>, ServiceError> {
+        restore_todo_list(user_id, name, tasks, replace, outbox, &self.pool).await
+    }
+}
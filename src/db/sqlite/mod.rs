@@ -0,0 +1,132 @@
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use sqlx::{
+    migrate::Migrator,
+    sqlite::{
+        SqliteConnectOptions,
+        SqlitePoolOptions
+    },
+    SqlitePool
+};
+
+use super::Repositories;
+
+pub mod user;
+pub mod list;
+pub mod task;
+pub mod idempotency;
+pub mod access_token;
+pub mod two_factor;
+pub mod email_token;
+pub mod identity;
+pub mod webhook;
+pub mod calendar_feed;
+
+/// OpenTelemetry `db.system.name` of sqlite query spans
+const DB_SYSTEM: &str = "sqlite";
+
+/// SQLite storage for self-hosted deployments. Queries are not checked at compile time,
+/// because `sqlx-data.json` describes postgres queries only
+#[derive(Clone)]
+pub struct SqliteStorage {
+    pool: SqlitePool,
+}
+
+impl SqliteStorage {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// Opens database file (created if missing) and applies `migrations_sqlite`
+    pub async fn connect(db_address: &str) -> anyhow::Result<Self> {
+        let options = SqliteConnectOptions::from_str(db_address)?
+            .create_if_missing(true)
+            .foreign_keys(true);
+
+        // single connection: sqlite has one writer anyway, and `sqlite::memory:` database exists per connection
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(options)
+            .await?;
+        let migrator = Migrator::new(std::path::Path::new("./migrations_sqlite")).await?;
+        migrator.run(&pool).await?;
+
+        Ok(Self::new(pool))
+    }
+}
+
+#[async_trait]
+impl Repositories for SqliteStorage {
+    async fn close(&self) {
+        self.pool.close().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::db::{
+        TaskRepository,
+        TodoListRepository,
+        UserRepository
+    };
+    use crate::models::{
+        NewTodoList,
+        NewUser,
+        Outbox
+    };
+
+    async fn storage_with_list() -> (SqliteStorage, Uuid) {
+        let storage = SqliteStorage::connect("sqlite::memory:").await.unwrap();
+        let user_id = storage.insert_user(&NewUser { login: "user".to_string(), password: "password1".to_string() }).await.unwrap();
+        let todo_list_id = storage.insert_todo_list(user_id, &NewTodoList { name: "test".to_string() }, &mut Outbox::new(user_id)).await.unwrap();
+        (storage, todo_list_id)
+    }
+
+    async fn descriptions(storage: &SqliteStorage, todo_list_id: Uuid) -> Vec<String> {
+        let mut tasks = storage.select_tasks(todo_list_id).await.unwrap();
+        tasks.sort_by_key(|task| task.order);
+        let orders: Vec<i32> = tasks.iter().map(|task| task.order).collect();
+        assert_eq!(orders, (1..=tasks.len() as i32).collect::<Vec<_>>(), "orders must be continuous");
+        tasks.into_iter().map(|task| task.description).collect()
+    }
+
+    #[tokio::test]
+    async fn insert_move_and_delete_keep_orders() {
+        let (storage, todo_list_id) = storage_with_list().await;
+        let user_id = storage.select_user_id("user", "password1").await.unwrap().unwrap();
+
+        let a = storage.insert_task_to_end(todo_list_id, "a".to_string(), &mut Outbox::new(user_id)).await.unwrap();
+        storage.insert_task_to_end(todo_list_id, "b".to_string(), &mut Outbox::new(user_id)).await.unwrap();
+        let c = storage.insert_task_to_end(todo_list_id, "c".to_string(), &mut Outbox::new(user_id)).await.unwrap();
+        storage.insert_task(todo_list_id, "x".to_string(), 2, &mut Outbox::new(user_id)).await.unwrap();
+        assert_eq!(descriptions(&storage, todo_list_id).await, vec!["a", "x", "b", "c"]);
+
+        let moved = storage.move_task(todo_list_id, c, 1, &mut Outbox::new(user_id)).await.unwrap().unwrap();
+        assert_eq!(moved.order, 1);
+        storage.move_task(todo_list_id, a, 4, &mut Outbox::new(user_id)).await.unwrap();
+        assert_eq!(descriptions(&storage, todo_list_id).await, vec!["c", "x", "b", "a"]);
+
+        let deleted = storage.delete_task(todo_list_id, c, &mut Outbox::new(user_id)).await.unwrap().unwrap();
+        assert_eq!(deleted.description, "c");
+        assert_eq!(descriptions(&storage, todo_list_id).await, vec!["x", "b", "a"]);
+        assert!(storage.delete_task(todo_list_id, c, &mut Outbox::new(user_id)).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn delete_list_deletes_tasks() {
+        let (storage, todo_list_id) = storage_with_list().await;
+        let user_id = storage.select_user_id("user", "password1").await.unwrap().unwrap();
+        storage.insert_task_to_end(todo_list_id, "a".to_string(), &mut Outbox::new(user_id)).await.unwrap();
+        assert!(storage.is_user_exist("user").await.unwrap());
+
+        let deleted = storage.delete_todo_list(user_id, &mut Outbox::new(user_id)).await.unwrap().unwrap();
+        assert_eq!(deleted.deleted_tasks, 1);
+
+        assert!(storage.select_todo_list(user_id).await.unwrap().is_none());
+        assert_eq!(storage.select_task_count(todo_list_id).await.unwrap(), 0);
+    }
+}
@@ -0,0 +1,794 @@
+use async_trait::async_trait;
+use chrono::{
+    DateTime,
+    TimeZone,
+    Utc
+};
+use sqlx::{
+    QueryBuilder,
+    Sqlite,
+    SqliteConnection,
+    SqlitePool,
+    Transaction
+};
+use uuid::Uuid;
+
+use super::{
+    webhook::insert_outbox,
+    SqliteStorage,
+    DB_SYSTEM
+};
+use crate::db::{
+    internal_error,
+    SyncTransaction,
+    TaskRepository
+};
+use crate::utils::telemetry::{
+    traced,
+    TracedQuery
+};
+use crate::models::{
+    ServiceError,
+    FullTaskInfo,
+    TaskRange,
+    TaskDetails,
+    TaskContent,
+    TaskChanges,
+    TaskTombstone,
+    ConditionalWrite,
+    WebhookEvent,
+    MovedTask,
+    Outbox
+};
+
+// Order changing functions run in transaction, so failed query does not leave a gap in orders
+
+#[derive(sqlx::FromRow)]
+struct ChangedTaskRow {
+    #[sqlx(flatten)]
+    task: FullTaskInfo,
+    created_seq: i64,
+}
+
+#[derive(sqlx::FromRow)]
+struct MovedTaskRow {
+    #[sqlx(flatten)]
+    task: FullTaskInfo,
+    moved_at: i64,
+}
+
+pub async fn select_task_count(todo_list_id: Uuid, db_pool: &SqlitePool) -> Result<i64, ServiceError> {
+    traced("db.select_task_count", async move {
+        let mut connection = db_pool.acquire().await.map_err(internal_error)?;
+        task_count(todo_list_id, &mut connection).await
+    }).await
+}
+
+pub async fn insert_task_to_end(todo_list_id: Uuid, description: String, outbox: &mut Outbox, db_pool: &SqlitePool) -> Result<Uuid, ServiceError> {
+    traced("db.insert_task_to_end", async move {
+        let mut transaction = db_pool.begin().await.map_err(internal_error)?;
+
+        let task_count = task_count(todo_list_id, &mut transaction).await?;
+        let task = FullTaskInfo { id: Uuid::new_v4(), todo_list_id, description, order: (task_count + 1) as i32, ..Default::default() };
+
+        insert_task_row(&task, Utc::now(), &mut transaction).await?;
+        insert_task_event(WebhookEvent::TaskCreated, Some(&task), outbox, &mut transaction).await?;
+
+        transaction.commit().await.map_err(internal_error)?;
+
+        Ok(task.id)
+    }).await
+}
+
+pub async fn insert_tasks_to_end(todo_list_id: Uuid, tasks: Vec<TaskContent>, outbox: &mut Outbox, db_pool: &SqlitePool) -> Result<Vec<FullTaskInfo>, ServiceError> {
+    traced("db.insert_tasks_to_end", async move {
+        let mut inserted = Vec::with_capacity(tasks.len());
+
+        let mut transaction = db_pool.begin().await.map_err(internal_error)?;
+
+        let task_count = task_count(todo_list_id, &mut transaction).await?;
+
+        for (index, TaskContent { description, details }) in tasks.into_iter().enumerate() {
+            let task_order = (task_count + 1) as i32 + index as i32;
+            let TaskDetails { due_at, completed_at, priority, rrule } = details;
+            let task = FullTaskInfo { id: Uuid::new_v4(), todo_list_id, description, order: task_order, due_at, completed_at, priority, rrule };
+
+            insert_task_row(&task, Utc::now(), &mut transaction).await?;
+
+            outbox.add(WebhookEvent::TaskCreated, &task)?;
+            inserted.push(task);
+        }
+
+        insert_outbox(outbox, &mut transaction).await?;
+
+        transaction.commit().await.map_err(internal_error)?;
+
+        Ok(inserted)
+    }).await
+}
+
+pub async fn insert_task_with_id(todo_list_id: Uuid, task_id: Uuid, description: String, details: TaskDetails, outbox: &mut Outbox, db_pool: &SqlitePool) -> Result<Option<FullTaskInfo>, ServiceError> {
+    traced("db.insert_task_with_id", async move {
+        let mut transaction = db_pool.begin().await.map_err(internal_error)?;
+
+        let task_count = task_count(todo_list_id, &mut transaction).await?;
+        let TaskDetails { due_at, completed_at, priority, rrule } = details;
+        let task = FullTaskInfo { id: task_id, todo_list_id, description, order: (task_count + 1) as i32, due_at, completed_at, priority, rrule };
+
+        if !insert_task_row(&task, Utc::now(), &mut transaction).await? {
+            return Ok(None);
+        }
+
+        insert_task_event(WebhookEvent::TaskCreated, Some(&task), outbox, &mut transaction).await?;
+
+        transaction.commit().await.map_err(internal_error)?;
+
+        Ok(Some(task))
+    }).await
+}
+
+pub async fn select_task(todo_list_id: Uuid, task_id: Uuid, db_pool: &SqlitePool) -> Result<Option<FullTaskInfo>, ServiceError> {
+    traced("db.select_task", async move {
+        let mut connection = db_pool.acquire().await.map_err(internal_error)?;
+        select_task_in(todo_list_id, task_id, &mut connection).await
+    }).await
+}
+
+async fn select_task_in(todo_list_id: Uuid, task_id: Uuid, connection: &mut SqliteConnection) -> Result<Option<FullTaskInfo>, ServiceError> {
+    sqlx::query_as(
+            "SELECT id, todo_list_id, description, \"order\", due_at, completed_at, priority, rrule
+            FROM tasks
+            WHERE todo_list_id = ? AND id = ?"
+        )
+        .bind(todo_list_id)
+        .bind(task_id)
+        .fetch_optional(connection)
+        .traced_query_on(DB_SYSTEM, "SELECT", "tasks")
+        .await
+        .map_err(internal_error)
+}
+
+pub async fn insert_task(todo_list_id: Uuid, description: String, order: i32, outbox: &mut Outbox, db_pool: &SqlitePool) -> Result<Uuid, ServiceError> {
+    traced("db.insert_task", async move {
+        let mut transaction = db_pool.begin().await.map_err(internal_error)?;
+
+        // order could be computed from outdated list
+        let task_count = task_count(todo_list_id, &mut transaction).await?;
+        let order = order.clamp(1, task_count as i32 + 1);
+
+        offset_add_or_remove_space(todo_list_id, order, 1, &mut transaction).await?;
+
+        let task = FullTaskInfo { id: Uuid::new_v4(), todo_list_id, description, order, ..Default::default() };
+        insert_task_row(&task, Utc::now(), &mut transaction).await?;
+        insert_task_event(WebhookEvent::TaskCreated, Some(&task), outbox, &mut transaction).await?;
+
+        transaction.commit().await.map_err(internal_error)?;
+
+        Ok(task.id)
+    }).await
+}
+
+pub async fn select_tasks(todo_list_id: Uuid, db_pool: &SqlitePool) -> Result<Vec<FullTaskInfo>, ServiceError> {
+    traced("db.select_tasks", async move {
+        let result = sqlx::query_as(
+                "SELECT id, todo_list_id, description, \"order\", due_at, completed_at, priority, rrule
+                FROM tasks
+                WHERE todo_list_id = ?"
+            )
+            .bind(todo_list_id)
+            .fetch_all(db_pool)
+            .traced_query_on(DB_SYSTEM, "SELECT", "tasks")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(result)
+    }).await
+}
+
+pub async fn select_tasks_by_ids(todo_list_id: Uuid, ids: &[Uuid], db_pool: &SqlitePool) -> Result<Vec<FullTaskInfo>, ServiceError> {
+    if ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    traced("db.select_tasks_by_ids", async move {
+        // sqlite has no arrays, every id is a parameter
+        let mut query = QueryBuilder::new(
+            "SELECT id, todo_list_id, description, \"order\", due_at, completed_at, priority, rrule
+            FROM tasks
+            WHERE todo_list_id = "
+        );
+        query.push_bind(todo_list_id).push(" AND id IN (");
+
+        let mut separated = query.separated(", ");
+        for id in ids {
+            separated.push_bind(*id);
+        }
+        separated.push_unseparated(")");
+
+        let result = query.build_query_as()
+            .fetch_all(db_pool)
+            .traced_query_on(DB_SYSTEM, "SELECT", "tasks")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(result)
+    }).await
+}
+
+pub async fn select_tasks_range(todo_list_id: Uuid, range: TaskRange, db_pool: &SqlitePool) -> Result<Vec<FullTaskInfo>, ServiceError> {
+    traced("db.select_tasks_range", async move {
+        let result = sqlx::query_as(
+                "SELECT id, todo_list_id, description, \"order\", due_at, completed_at, priority, rrule
+                FROM tasks
+                WHERE todo_list_id = ?
+                ORDER BY \"order\"
+                LIMIT ? OFFSET ?"
+            )
+            .bind(todo_list_id)
+            .bind(range.count as i64)
+            .bind(range.offset as i64)
+            .fetch_all(db_pool)
+            .traced_query_on(DB_SYSTEM, "SELECT", "tasks")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(result)
+    }).await
+}
+
+pub async fn delete_task(todo_list_id: Uuid, task_id: Uuid, outbox: &mut Outbox, db_pool: &SqlitePool) -> Result<Option<FullTaskInfo>, ServiceError> {
+    traced("db.delete_task", async move {
+        let mut transaction = db_pool.begin().await.map_err(internal_error)?;
+
+        let result = delete_task_in(todo_list_id, task_id, &mut transaction).await?;
+        insert_task_event(WebhookEvent::TaskDeleted, result.as_ref(), outbox, &mut transaction).await?;
+
+        transaction.commit().await.map_err(internal_error)?;
+
+        Ok(result)
+    }).await
+}
+
+async fn delete_task_in(todo_list_id: Uuid, task_id: Uuid, connection: &mut SqliteConnection) -> Result<Option<FullTaskInfo>, ServiceError> {
+    let result: Option<FullTaskInfo> = sqlx::query_as(
+            "DELETE FROM tasks
+            WHERE todo_list_id = ? AND id = ?
+            RETURNING id, todo_list_id, description, \"order\", due_at, completed_at, priority, rrule"
+        )
+        .bind(todo_list_id)
+        .bind(task_id)
+        .fetch_optional(&mut *connection)
+        .traced_query_on(DB_SYSTEM, "DELETE", "tasks")
+        .await
+        .map_err(internal_error)?;
+
+    if let Some(task) = &result {
+        offset_add_or_remove_space(todo_list_id, task.order, -1, &mut *connection).await?;
+    }
+
+    Ok(result)
+}
+
+pub async fn update_task(todo_list_id: Uuid, task_id: Uuid, description: String, outbox: &mut Outbox, db_pool: &SqlitePool) -> Result<Option<FullTaskInfo>, ServiceError> {
+    traced("db.update_task", async move {
+        let mut transaction = db_pool.begin().await.map_err(internal_error)?;
+
+        let result: Option<FullTaskInfo> = sqlx::query_as(
+                "UPDATE tasks
+                SET description = ?, updated_at = ?
+                WHERE todo_list_id = ? AND id = ?
+                RETURNING id, todo_list_id, description, \"order\", due_at, completed_at, priority, rrule"
+            )
+            .bind(description)
+            .bind(Utc::now().timestamp_micros())
+            .bind(todo_list_id)
+            .bind(task_id)
+            .fetch_optional(&mut transaction)
+            .traced_query_on(DB_SYSTEM, "UPDATE", "tasks")
+            .await
+            .map_err(internal_error)?;
+
+        insert_task_event(WebhookEvent::TaskUpdated, result.as_ref(), outbox, &mut transaction).await?;
+
+        transaction.commit().await.map_err(internal_error)?;
+
+        Ok(result)
+    }).await
+}
+
+pub async fn update_task_details(todo_list_id: Uuid, task_id: Uuid, details: TaskDetails, outbox: &mut Outbox, db_pool: &SqlitePool) -> Result<Option<FullTaskInfo>, ServiceError> {
+    traced("db.update_task_details", async move {
+        let mut transaction = db_pool.begin().await.map_err(internal_error)?;
+
+        let result: Option<FullTaskInfo> = sqlx::query_as(
+                "UPDATE tasks
+                SET due_at = ?, completed_at = ?, priority = ?, rrule = ?, updated_at = ?
+                WHERE todo_list_id = ? AND id = ?
+                RETURNING id, todo_list_id, description, \"order\", due_at, completed_at, priority, rrule"
+            )
+            .bind(details.due_at.map(|due_at| due_at.timestamp()))
+            .bind(details.completed_at.map(|completed_at| completed_at.timestamp()))
+            .bind(details.priority)
+            .bind(details.rrule)
+            .bind(Utc::now().timestamp_micros())
+            .bind(todo_list_id)
+            .bind(task_id)
+            .fetch_optional(&mut transaction)
+            .traced_query_on(DB_SYSTEM, "UPDATE", "tasks")
+            .await
+            .map_err(internal_error)?;
+
+        insert_task_event(WebhookEvent::TaskUpdated, result.as_ref(), outbox, &mut transaction).await?;
+
+        transaction.commit().await.map_err(internal_error)?;
+
+        Ok(result)
+    }).await
+}
+
+pub async fn replace_task(todo_list_id: Uuid, task_id: Uuid, description: String, details: TaskDetails, outbox: &mut Outbox, db_pool: &SqlitePool) -> Result<Option<FullTaskInfo>, ServiceError> {
+    traced("db.replace_task", async move {
+        let mut transaction = db_pool.begin().await.map_err(internal_error)?;
+
+        let result: Option<FullTaskInfo> = sqlx::query_as(
+                "UPDATE tasks
+                SET description = ?, due_at = ?, completed_at = ?, priority = ?, rrule = ?, updated_at = ?
+                WHERE todo_list_id = ? AND id = ?
+                RETURNING id, todo_list_id, description, \"order\", due_at, completed_at, priority, rrule"
+            )
+            .bind(description)
+            .bind(details.due_at.map(|due_at| due_at.timestamp()))
+            .bind(details.completed_at.map(|completed_at| completed_at.timestamp()))
+            .bind(details.priority)
+            .bind(details.rrule)
+            .bind(Utc::now().timestamp_micros())
+            .bind(todo_list_id)
+            .bind(task_id)
+            .fetch_optional(&mut transaction)
+            .traced_query_on(DB_SYSTEM, "UPDATE", "tasks")
+            .await
+            .map_err(internal_error)?;
+
+        insert_task_event(WebhookEvent::TaskUpdated, result.as_ref(), outbox, &mut transaction).await?;
+
+        transaction.commit().await.map_err(internal_error)?;
+
+        Ok(result)
+    }).await
+}
+
+pub async fn move_task(todo_list_id: Uuid, task_id: Uuid, new_order: i32, outbox: &mut Outbox, db_pool: &SqlitePool) -> Result<Option<FullTaskInfo>, ServiceError> {
+    traced("db.move_task", async move {
+        let mut transaction = db_pool.begin().await.map_err(internal_error)?;
+
+        let previous_order = match select_task_in(todo_list_id, task_id, &mut transaction).await? {
+            Some(task) => task.order,
+            None => return Ok(None),
+        };
+
+        let task = match move_task_in(todo_list_id, task_id, new_order, Utc::now(), false, &mut transaction).await? {
+            ConditionalWrite::Applied(task) | ConditionalWrite::Outdated(task) => task,
+            ConditionalWrite::NotFound => return Ok(None),
+        };
+
+        if task.order != previous_order {
+            outbox.add(WebhookEvent::TaskMoved, &MovedTask { task: &task, previous_order })?;
+            insert_outbox(outbox, &mut transaction).await?;
+        }
+
+        transaction.commit().await.map_err(internal_error)?;
+
+        Ok(Some(task))
+    }).await
+}
+
+pub async fn select_task_changes(todo_list_id: Uuid, since: i64, db_pool: &SqlitePool) -> Result<Option<TaskChanges>, ServiceError> {
+    traced("db.select_task_changes", async move {
+        let mut transaction = db_pool.begin().await.map_err(internal_error)?;
+
+        let list: Option<(i64, i64)> = sqlx::query_as(
+                "SELECT change_seq, name_seq
+                FROM todo_lists
+                WHERE id = ?"
+            )
+            .bind(todo_list_id)
+            .fetch_optional(&mut transaction)
+            .traced_query_on(DB_SYSTEM, "SELECT", "todo_lists")
+            .await
+            .map_err(internal_error)?;
+
+        let (seq, name_seq) = match list {
+            Some(list) => list,
+            None => return Ok(None),
+        };
+
+        let tasks: Vec<ChangedTaskRow> = sqlx::query_as(
+                "SELECT id, todo_list_id, description, \"order\", due_at, completed_at, priority, rrule, created_seq
+                FROM tasks
+                WHERE todo_list_id = ? AND change_seq > ?
+                ORDER BY \"order\""
+            )
+            .bind(todo_list_id)
+            .bind(since)
+            .fetch_all(&mut transaction)
+            .traced_query_on(DB_SYSTEM, "SELECT", "tasks")
+            .await
+            .map_err(internal_error)?;
+
+        let deleted: Vec<(Uuid, i64)> = sqlx::query_as(
+                "SELECT task_id, deleted_at
+                FROM task_tombstones
+                WHERE todo_list_id = ? AND change_seq > ?
+                ORDER BY change_seq"
+            )
+            .bind(todo_list_id)
+            .bind(since)
+            .fetch_all(&mut transaction)
+            .traced_query_on(DB_SYSTEM, "SELECT", "task_tombstones")
+            .await
+            .map_err(internal_error)?;
+
+        let deleted = deleted.into_iter()
+            .map(|(id, deleted_at)| TaskTombstone { id, deleted_at: from_timestamp(deleted_at) })
+            .collect();
+
+        let mut changes = TaskChanges { seq, name_seq, created: Vec::new(), updated: Vec::new(), deleted };
+        for ChangedTaskRow { task, created_seq } in tasks {
+            if created_seq > since {
+                changes.created.push(task);
+            } else {
+                changes.updated.push(task);
+            }
+        }
+
+        Ok(Some(changes))
+    }).await
+}
+
+/// Sync writes to the list in one transaction, sqlite allows one writer, so orders can't be changed concurrently
+pub struct SqliteSyncTransaction {
+    todo_list_id: Uuid,
+    transaction: Transaction<'static, Sqlite>,
+}
+
+pub async fn begin_sync_transaction(todo_list_id: Uuid, db_pool: &SqlitePool) -> Result<SqliteSyncTransaction, ServiceError> {
+    traced("db.begin_sync_transaction", async move {
+        let transaction = db_pool.begin().await.map_err(internal_error)?;
+
+        Ok(SqliteSyncTransaction { todo_list_id, transaction })
+    }).await
+}
+
+async fn is_task_deleted_in(todo_list_id: Uuid, task_id: Uuid, connection: &mut SqliteConnection) -> Result<bool, ServiceError> {
+    let result: Option<Uuid> = sqlx::query_scalar(
+            "SELECT task_id
+            FROM task_tombstones
+            WHERE todo_list_id = ? AND task_id = ?"
+        )
+        .bind(todo_list_id)
+        .bind(task_id)
+        .fetch_optional(connection)
+        .traced_query_on(DB_SYSTEM, "SELECT", "task_tombstones")
+        .await
+        .map_err(internal_error)?;
+
+    Ok(result.is_some())
+}
+
+async fn insert_task_at_in(todo_list_id: Uuid, task_id: Uuid, description: String, order: i32, changed_at: DateTime<Utc>, connection: &mut SqliteConnection) -> Result<bool, ServiceError> {
+    let exists: Option<Uuid> = sqlx::query_scalar(
+            "SELECT id
+            FROM tasks
+            WHERE id = ?"
+        )
+        .bind(task_id)
+        .fetch_optional(&mut *connection)
+        .traced_query_on(DB_SYSTEM, "SELECT", "tasks")
+        .await
+        .map_err(internal_error)?;
+
+    if exists.is_some() {
+        return Ok(false);
+    }
+
+    let task_count = task_count(todo_list_id, &mut *connection).await?;
+    let order = order.clamp(1, task_count as i32 + 1);
+
+    offset_add_or_remove_space(todo_list_id, order, 1, &mut *connection).await?;
+
+    let task = FullTaskInfo { id: task_id, todo_list_id, description, order, ..Default::default() };
+    insert_task_row(&task, changed_at, connection).await?;
+
+    Ok(true)
+}
+
+async fn update_task_at_in(todo_list_id: Uuid, task_id: Uuid, description: String, changed_at: DateTime<Utc>, connection: &mut SqliteConnection) -> Result<ConditionalWrite, ServiceError> {
+    // the same time is not older, the later written change wins
+    let result: Option<FullTaskInfo> = sqlx::query_as(
+            "UPDATE tasks
+            SET description = ?, updated_at = ?
+            WHERE todo_list_id = ? AND id = ? AND updated_at <= ?
+            RETURNING id, todo_list_id, description, \"order\", due_at, completed_at, priority, rrule"
+        )
+        .bind(description)
+        .bind(changed_at.timestamp_micros())
+        .bind(todo_list_id)
+        .bind(task_id)
+        .bind(changed_at.timestamp_micros())
+        .fetch_optional(&mut *connection)
+        .traced_query_on(DB_SYSTEM, "UPDATE", "tasks")
+        .await
+        .map_err(internal_error)?;
+
+    if let Some(task) = result {
+        return Ok(ConditionalWrite::Applied(task));
+    }
+
+    Ok(match select_task_in(todo_list_id, task_id, connection).await? {
+        Some(task) => ConditionalWrite::Outdated(task),
+        None => ConditionalWrite::NotFound,
+    })
+}
+
+/// Moves task to `new_order` (clamped to the list bounds) and saves `moved_at`.
+/// If `conditional`, task moved after `moved_at` is not moved and is `Outdated`
+async fn move_task_in(todo_list_id: Uuid, task_id: Uuid, new_order: i32, moved_at: DateTime<Utc>, conditional: bool, connection: &mut SqliteConnection) -> Result<ConditionalWrite, ServiceError> {
+    // order is read again in transaction, task could be moved by concurrent request
+    let task: Option<MovedTaskRow> = sqlx::query_as(
+            "SELECT id, todo_list_id, description, \"order\", due_at, completed_at, priority, rrule, moved_at
+            FROM tasks
+            WHERE todo_list_id = ? AND id = ?"
+        )
+        .bind(todo_list_id)
+        .bind(task_id)
+        .fetch_optional(&mut *connection)
+        .traced_query_on(DB_SYSTEM, "SELECT", "tasks")
+        .await
+        .map_err(internal_error)?;
+
+    let MovedTaskRow { task, moved_at: last_moved_at } = match task {
+        Some(task) => task,
+        None => return Ok(ConditionalWrite::NotFound),
+    };
+
+    if conditional && last_moved_at > moved_at.timestamp_micros() {
+        return Ok(ConditionalWrite::Outdated(task));
+    }
+
+    let task_count = task_count(todo_list_id, &mut *connection).await?;
+    let old_order = task.order;
+    let new_order = new_order.clamp(1, task_count as i32);
+
+    if old_order == new_order {
+        return Ok(ConditionalWrite::Applied(task));
+    }
+
+    // if move item from right to left, then move range from left to right
+    let range_move_left_to_right = old_order > new_order;
+
+    let mut offset_bottom = std::cmp::min(old_order, new_order);
+    let mut offset_top = std::cmp::max(old_order, new_order);
+    let offset = if range_move_left_to_right { 1 } else { -1 };
+
+    if range_move_left_to_right {
+        offset_top -= 1;
+    } else {
+        offset_bottom += 1;
+    }
+
+    offset_range(todo_list_id, offset_bottom, offset_top, offset, &mut *connection).await?;
+
+    let result = sqlx::query_as(
+            "UPDATE tasks
+            SET \"order\" = ?, moved_at = ?
+            WHERE todo_list_id = ? AND id = ?
+            RETURNING id, todo_list_id, description, \"order\", due_at, completed_at, priority, rrule"
+        )
+        .bind(new_order)
+        .bind(moved_at.timestamp_micros())
+        .bind(todo_list_id)
+        .bind(task_id)
+        .fetch_one(&mut *connection)
+        .traced_query_on(DB_SYSTEM, "UPDATE", "tasks")
+        .await
+        .map_err(internal_error)?;
+
+    Ok(ConditionalWrite::Applied(result))
+}
+
+/// Inserts task changed and moved at `changed_at`, times of changes are in microseconds.
+/// Columns are named, because sequence columns are set by triggers.
+/// Returns `false` if task with the id already exists
+pub(super) async fn insert_task_row(task: &FullTaskInfo, changed_at: DateTime<Utc>, connection: &mut SqliteConnection) -> Result<bool, ServiceError> {
+    let inserted = sqlx::query(
+            "INSERT INTO tasks (id, todo_list_id, description, \"order\", due_at, completed_at, priority, rrule, updated_at, moved_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT (id) DO NOTHING"
+        )
+        .bind(task.id)
+        .bind(task.todo_list_id)
+        .bind(&task.description)
+        .bind(task.order)
+        .bind(task.due_at.map(|due_at| due_at.timestamp()))
+        .bind(task.completed_at.map(|completed_at| completed_at.timestamp()))
+        .bind(task.priority)
+        .bind(&task.rrule)
+        .bind(changed_at.timestamp_micros())
+        .bind(changed_at.timestamp_micros())
+        .execute(connection)
+        .traced_query_on(DB_SYSTEM, "INSERT", "tasks")
+        .await
+        .map_err(internal_error)?
+        .rows_affected();
+
+    Ok(inserted > 0)
+}
+
+/// Adds event of the written task to outbox, nothing is added if the task is not found
+async fn insert_task_event(event: WebhookEvent, task: Option<&FullTaskInfo>, outbox: &mut Outbox, connection: &mut SqliteConnection) -> Result<(), ServiceError> {
+    if let Some(task) = task {
+        outbox.add(event, task)?;
+        insert_outbox(outbox, connection).await?;
+    }
+
+    Ok(())
+}
+
+fn from_timestamp(timestamp: i64) -> DateTime<Utc> {
+    Utc.timestamp_opt(timestamp, 0).single().unwrap_or_default()
+}
+
+async fn task_count(todo_list_id: Uuid, connection: &mut SqliteConnection) -> Result<i64, ServiceError> {
+    sqlx::query_scalar(
+            "SELECT COUNT(*)
+            FROM tasks
+            WHERE todo_list_id = ?"
+        )
+        .bind(todo_list_id)
+        .fetch_one(connection)
+        .traced_query_on(DB_SYSTEM, "SELECT", "tasks")
+        .await
+        .map_err(internal_error)
+}
+
+pub async fn offset_add_or_remove_space(todo_list_id: Uuid, order: i32, offset: i32, connection: &mut SqliteConnection) -> Result<(), ServiceError> {
+    traced("db.offset_add_or_remove_space", async move {
+        sqlx::query(
+                "UPDATE tasks
+                SET \"order\" = \"order\" + ?
+                WHERE todo_list_id = ? AND \"order\" >= ?"
+            )
+            .bind(offset)
+            .bind(todo_list_id)
+            .bind(order)
+            .execute(connection)
+            .traced_query_on(DB_SYSTEM, "UPDATE", "tasks")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(())
+    }).await
+}
+
+pub async fn offset_range(todo_list_id: Uuid, bottom: i32, top: i32, offset: i32, connection: &mut SqliteConnection) -> Result<(), ServiceError> {
+    traced("db.offset_range", async move {
+        sqlx::query(
+                "UPDATE tasks
+                SET \"order\" = \"order\" + ?
+                WHERE todo_list_id = ? AND \"order\" >= ? AND \"order\" <= ?"
+            )
+            .bind(offset)
+            .bind(todo_list_id)
+            .bind(bottom)
+            .bind(top)
+            .execute(connection)
+            .traced_query_on(DB_SYSTEM, "UPDATE", "tasks")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(())
+    }).await
+}
+
+#[async_trait]
+impl TaskRepository for SqliteStorage {
+    async fn select_task_count(&self, todo_list_id: Uuid) -> Result<i64, ServiceError> {
+        select_task_count(todo_list_id, &self.pool).await
+    }
+
+    async fn insert_task_to_end(&self, todo_list_id: Uuid, description: String, outbox: &mut Outbox) -> Result<Uuid, ServiceError> {
+        insert_task_to_end(todo_list_id, description, outbox, &self.pool).await
+    }
+
+    async fn insert_tasks_to_end(&self, todo_list_id: Uuid, tasks: Vec<TaskContent>, outbox: &mut Outbox) -> Result<Vec<FullTaskInfo>, ServiceError> {
+        insert_tasks_to_end(todo_list_id, tasks, outbox, &self.pool).await
+    }
+
+    async fn insert_task_with_id(&self, todo_list_id: Uuid, task_id: Uuid, description: String, details: TaskDetails, outbox: &mut Outbox) -> Result<Option<FullTaskInfo>, ServiceError> {
+        insert_task_with_id(todo_list_id, task_id, description, details, outbox, &self.pool).await
+    }
+
+    async fn select_task(&self, todo_list_id: Uuid, task_id: Uuid) -> Result<Option<FullTaskInfo>, ServiceError> {
+        select_task(todo_list_id, task_id, &self.pool).await
+    }
+
+    async fn insert_task(&self, todo_list_id: Uuid, description: String, order: i32, outbox: &mut Outbox) -> Result<Uuid, ServiceError> {
+        insert_task(todo_list_id, description, order, outbox, &self.pool).await
+    }
+
+    async fn select_tasks(&self, todo_list_id: Uuid) -> Result<Vec<FullTaskInfo>, ServiceError> {
+        select_tasks(todo_list_id, &self.pool).await
+    }
+
+    async fn select_tasks_by_ids(&self, todo_list_id: Uuid, ids: &[Uuid]) -> Result<Vec<FullTaskInfo>, ServiceError> {
+        select_tasks_by_ids(todo_list_id, ids, &self.pool).await
+    }
+
+    async fn select_tasks_range(&self, todo_list_id: Uuid, range: TaskRange) -> Result<Vec<FullTaskInfo>, ServiceError> {
+        select_tasks_range(todo_list_id, range, &self.pool).await
+    }
+
+    async fn delete_task(&self, todo_list_id: Uuid, task_id: Uuid, outbox: &mut Outbox) -> Result<Option<FullTaskInfo>, ServiceError> {
+        delete_task(todo_list_id, task_id, outbox, &self.pool).await
+    }
+
+    async fn update_task(&self, todo_list_id: Uuid, task_id: Uuid, description: String, outbox: &mut Outbox) -> Result<Option<FullTaskInfo>, ServiceError> {
+        update_task(todo_list_id, task_id, description, outbox, &self.pool).await
+    }
+
+    async fn update_task_details(&self, todo_list_id: Uuid, task_id: Uuid, details: TaskDetails, outbox: &mut Outbox) -> Result<Option<FullTaskInfo>, ServiceError> {
+        update_task_details(todo_list_id, task_id, details, outbox, &self.pool).await
+    }
+
+    async fn replace_task(&self, todo_list_id: Uuid, task_id: Uuid, description: String, details: TaskDetails, outbox: &mut Outbox) -> Result<Option<FullTaskInfo>, ServiceError> {
+        replace_task(todo_list_id, task_id, description, details, outbox, &self.pool).await
+    }
+
+    async fn move_task(&self, todo_list_id: Uuid, task_id: Uuid, new_order: i32, outbox: &mut Outbox) -> Result<Option<FullTaskInfo>, ServiceError> {
+        move_task(todo_list_id, task_id, new_order, outbox, &self.pool).await
+    }
+
+    async fn select_task_changes(&self, todo_list_id: Uuid, since: i64) -> Result<Option<TaskChanges>, ServiceError> {
+        select_task_changes(todo_list_id, since, &self.pool).await
+    }
+
+    async fn begin_sync_transaction(&self, todo_list_id: Uuid) -> Result<Box<dyn SyncTransaction + '_>, ServiceError> {
+        Ok(Box::new(begin_sync_transaction(todo_list_id, &self.pool).await?))
+    }
+}
+
+#[async_trait]
+impl SyncTransaction for SqliteSyncTransaction {
+    async fn select_task_count(&mut self) -> Result<i64, ServiceError> {
+        traced("db.select_task_count", task_count(self.todo_list_id, &mut self.transaction)).await
+    }
+
+    async fn select_task(&mut self, task_id: Uuid) -> Result<Option<FullTaskInfo>, ServiceError> {
+        traced("db.select_task", select_task_in(self.todo_list_id, task_id, &mut self.transaction)).await
+    }
+
+    async fn is_task_deleted(&mut self, task_id: Uuid) -> Result<bool, ServiceError> {
+        traced("db.is_task_deleted", is_task_deleted_in(self.todo_list_id, task_id, &mut self.transaction)).await
+    }
+
+    async fn insert_task_at(&mut self, task_id: Uuid, description: String, order: i32, changed_at: DateTime<Utc>) -> Result<bool, ServiceError> {
+        traced("db.insert_task_at", insert_task_at_in(self.todo_list_id, task_id, description, order, changed_at, &mut self.transaction)).await
+    }
+
+    async fn update_task_at(&mut self, task_id: Uuid, description: String, changed_at: DateTime<Utc>) -> Result<ConditionalWrite, ServiceError> {
+        traced("db.update_task_at", update_task_at_in(self.todo_list_id, task_id, description, changed_at, &mut self.transaction)).await
+    }
+
+    async fn delete_task(&mut self, task_id: Uuid) -> Result<Option<FullTaskInfo>, ServiceError> {
+        traced("db.delete_task", delete_task_in(self.todo_list_id, task_id, &mut self.transaction)).await
+    }
+
+    async fn move_task_at(&mut self, task_id: Uuid, new_order: i32, changed_at: DateTime<Utc>) -> Result<ConditionalWrite, ServiceError> {
+        traced("db.move_task_at", move_task_in(self.todo_list_id, task_id, new_order, changed_at, true, &mut self.transaction)).await
+    }
+
+    async fn commit(mut self: Box<Self>, outbox: &Outbox) -> Result<(), ServiceError> {
+        insert_outbox(outbox, &mut self.transaction).await?;
+        self.transaction.commit().await.map_err(internal_error)
+    }
+}
@@ -0,0 +1,209 @@
+use async_trait::async_trait;
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+use super::{
+    SqliteStorage,
+    DB_SYSTEM
+};
+use crate::db::{
+    internal_error,
+    TwoFactorRepository
+};
+use crate::utils::telemetry::{
+    traced,
+    TracedQuery
+};
+use crate::models::{
+    ServiceError,
+    TotpSecret
+};
+
+pub async fn upsert_totp_secret(user_id: Uuid, secret: &[u8], db_pool: &SqlitePool) -> Result<(), ServiceError> {
+    traced("db.upsert_totp_secret", async move {
+        let mut transaction = db_pool.begin().await.map_err(internal_error)?;
+
+        sqlx::query(
+                "DELETE FROM recovery_codes
+                WHERE user_id = ?"
+            )
+            .bind(user_id)
+            .execute(&mut transaction)
+            .traced_query_on(DB_SYSTEM, "DELETE", "recovery_codes")
+            .await
+            .map_err(internal_error)?;
+
+        sqlx::query(
+                "INSERT INTO totp_secrets (user_id, secret)
+                VALUES (?, ?)
+                ON CONFLICT (user_id) DO UPDATE
+                SET secret = excluded.secret, enabled = 0, last_used_step = NULL"
+            )
+            .bind(user_id)
+            .bind(secret)
+            .execute(&mut transaction)
+            .traced_query_on(DB_SYSTEM, "INSERT", "totp_secrets")
+            .await
+            .map_err(internal_error)?;
+
+        transaction.commit().await.map_err(internal_error)?;
+
+        Ok(())
+    }).await
+}
+
+pub async fn select_totp_secret(user_id: Uuid, db_pool: &SqlitePool) -> Result<Option<TotpSecret>, ServiceError> {
+    traced("db.select_totp_secret", async move {
+        let result: Option<(Vec<u8>, bool)> = sqlx::query_as(
+                "SELECT secret, enabled
+                FROM totp_secrets
+                WHERE user_id = ?"
+            )
+            .bind(user_id)
+            .fetch_optional(db_pool)
+            .traced_query_on(DB_SYSTEM, "SELECT", "totp_secrets")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(result.map(|(secret, enabled)| TotpSecret { secret, enabled }))
+    }).await
+}
+
+pub async fn enable_totp(user_id: Uuid, recovery_code_hashes: &[String], db_pool: &SqlitePool) -> Result<(), ServiceError> {
+    traced("db.enable_totp", async move {
+        let mut transaction = db_pool.begin().await.map_err(internal_error)?;
+
+        sqlx::query(
+                "UPDATE totp_secrets
+                SET enabled = 1
+                WHERE user_id = ?"
+            )
+            .bind(user_id)
+            .execute(&mut transaction)
+            .traced_query_on(DB_SYSTEM, "UPDATE", "totp_secrets")
+            .await
+            .map_err(internal_error)?;
+
+        sqlx::query(
+                "DELETE FROM recovery_codes
+                WHERE user_id = ?"
+            )
+            .bind(user_id)
+            .execute(&mut transaction)
+            .traced_query_on(DB_SYSTEM, "DELETE", "recovery_codes")
+            .await
+            .map_err(internal_error)?;
+
+        for code_hash in recovery_code_hashes {
+            sqlx::query(
+                    "INSERT INTO recovery_codes (user_id, code_hash)
+                    VALUES (?, ?)"
+                )
+                .bind(user_id)
+                .bind(code_hash)
+                .execute(&mut transaction)
+                .traced_query_on(DB_SYSTEM, "INSERT", "recovery_codes")
+                .await
+                .map_err(internal_error)?;
+        }
+
+        transaction.commit().await.map_err(internal_error)?;
+
+        Ok(())
+    }).await
+}
+
+pub async fn use_totp_step(user_id: Uuid, step: i64, db_pool: &SqlitePool) -> Result<bool, ServiceError> {
+    traced("db.use_totp_step", async move {
+        let updated = sqlx::query(
+                "UPDATE totp_secrets
+                SET last_used_step = ?
+                WHERE user_id = ? AND (last_used_step IS NULL OR last_used_step < ?)"
+            )
+            .bind(step)
+            .bind(user_id)
+            .bind(step)
+            .execute(db_pool)
+            .traced_query_on(DB_SYSTEM, "UPDATE", "totp_secrets")
+            .await
+            .map_err(internal_error)?
+            .rows_affected();
+
+        Ok(updated > 0)
+    }).await
+}
+
+pub async fn use_recovery_code(user_id: Uuid, code_hash: &str, db_pool: &SqlitePool) -> Result<bool, ServiceError> {
+    traced("db.use_recovery_code", async move {
+        let deleted = sqlx::query(
+                "DELETE FROM recovery_codes
+                WHERE user_id = ? AND code_hash = ?"
+            )
+            .bind(user_id)
+            .bind(code_hash)
+            .execute(db_pool)
+            .traced_query_on(DB_SYSTEM, "DELETE", "recovery_codes")
+            .await
+            .map_err(internal_error)?
+            .rows_affected();
+
+        Ok(deleted > 0)
+    }).await
+}
+
+pub async fn delete_totp(user_id: Uuid, db_pool: &SqlitePool) -> Result<(), ServiceError> {
+    traced("db.delete_totp", async move {
+        let mut transaction = db_pool.begin().await.map_err(internal_error)?;
+
+        sqlx::query(
+                "DELETE FROM recovery_codes
+                WHERE user_id = ?"
+            )
+            .bind(user_id)
+            .execute(&mut transaction)
+            .traced_query_on(DB_SYSTEM, "DELETE", "recovery_codes")
+            .await
+            .map_err(internal_error)?;
+
+        sqlx::query(
+                "DELETE FROM totp_secrets
+                WHERE user_id = ?"
+            )
+            .bind(user_id)
+            .execute(&mut transaction)
+            .traced_query_on(DB_SYSTEM, "DELETE", "totp_secrets")
+            .await
+            .map_err(internal_error)?;
+
+        transaction.commit().await.map_err(internal_error)?;
+
+        Ok(())
+    }).await
+}
+
+#[async_trait]
+impl TwoFactorRepository for SqliteStorage {
+    async fn upsert_totp_secret(&self, user_id: Uuid, secret: &[u8]) -> Result<(), ServiceError> {
+        upsert_totp_secret(user_id, secret, &self.pool).await
+    }
+
+    async fn select_totp_secret(&self, user_id: Uuid) -> Result<Option<TotpSecret>, ServiceError> {
+        select_totp_secret(user_id, &self.pool).await
+    }
+
+    async fn enable_totp(&self, user_id: Uuid, recovery_code_hashes: &[String]) -> Result<(), ServiceError> {
+        enable_totp(user_id, recovery_code_hashes, &self.pool).await
+    }
+
+    async fn use_totp_step(&self, user_id: Uuid, step: i64) -> Result<bool, ServiceError> {
+        use_totp_step(user_id, step, &self.pool).await
+    }
+
+    async fn use_recovery_code(&self, user_id: Uuid, code_hash: &str) -> Result<bool, ServiceError> {
+        use_recovery_code(user_id, code_hash, &self.pool).await
+    }
+
+    async fn delete_totp(&self, user_id: Uuid) -> Result<(), ServiceError> {
+        delete_totp(user_id, &self.pool).await
+    }
+}
@@ -0,0 +1,337 @@
+use async_trait::async_trait;
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+use super::{
+    SqliteStorage,
+    DB_SYSTEM
+};
+use crate::db::{
+    internal_error,
+    is_unique_violation,
+    UserRepository
+};
+use crate::utils::telemetry::{
+    traced,
+    TracedQuery
+};
+use crate::models::{
+    ServiceError,
+    NewUser,
+    UserEmail
+};
+
+pub async fn insert_user(user: &NewUser, db_pool: &SqlitePool) -> Result<Uuid, ServiceError> {
+    traced("db.insert_user", async move {
+        let id = uuid::Uuid::new_v4();
+
+        sqlx::query(
+                "INSERT INTO users (id, login, password)
+                VALUES (?, ?, ?)"
+            )
+            .bind(id)
+            .bind(&user.login)
+            .bind(&user.password)
+            .execute(db_pool)
+            .traced_query_on(DB_SYSTEM, "INSERT", "users")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(id)
+    }).await
+}
+
+pub async fn select_user_id(login: &str, password: &str, db_pool: &SqlitePool) -> Result<Option<Uuid>, ServiceError> {
+    traced("db.select_user_id", async move {
+        let result = sqlx::query_scalar(
+                "SELECT id
+                FROM users
+                WHERE login = ? AND password = ?
+                LIMIT 1"
+            )
+            .bind(login)
+            .bind(password)
+            .fetch_optional(db_pool)
+            .traced_query_on(DB_SYSTEM, "SELECT", "users")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(result)
+    }).await
+}
+
+pub async fn is_user_exist(login: &str, db_pool: &SqlitePool) -> Result<bool, ServiceError> {
+    traced("db.is_user_exist", async move {
+        let count: i64 = sqlx::query_scalar(
+                "SELECT COUNT(*)
+                FROM users
+                WHERE login = ?"
+            )
+            .bind(login)
+            .fetch_one(db_pool)
+            .traced_query_on(DB_SYSTEM, "SELECT", "users")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(count > 0)
+    }).await
+}
+
+pub async fn select_user_login(user_id: Uuid, db_pool: &SqlitePool) -> Result<Option<String>, ServiceError> {
+    traced("db.select_user_login", async move {
+        let result: Option<Option<String>> = sqlx::query_scalar(
+                "SELECT login
+                FROM users
+                WHERE id = ?"
+            )
+            .bind(user_id)
+            .fetch_optional(db_pool)
+            .traced_query_on(DB_SYSTEM, "SELECT", "users")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(result.flatten())
+    }).await
+}
+
+pub async fn select_session_version(user_id: Uuid, db_pool: &SqlitePool) -> Result<Option<i32>, ServiceError> {
+    traced("db.select_session_version", async move {
+        let result = sqlx::query_scalar(
+                "SELECT session_version
+                FROM users
+                WHERE id = ?"
+            )
+            .bind(user_id)
+            .fetch_optional(db_pool)
+            .traced_query_on(DB_SYSTEM, "SELECT", "users")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(result)
+    }).await
+}
+
+pub async fn update_user_password(user_id: Uuid, old_password: &str, new_password: &str, db_pool: &SqlitePool) -> Result<Option<i32>, ServiceError> {
+    traced("db.update_user_password", async move {
+        let result = sqlx::query_scalar(
+                "UPDATE users
+                SET password = ?, session_version = session_version + 1
+                WHERE id = ? AND password = ?
+                RETURNING session_version"
+            )
+            .bind(new_password)
+            .bind(user_id)
+            .bind(old_password)
+            .fetch_optional(db_pool)
+            .traced_query_on(DB_SYSTEM, "UPDATE", "users")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(result)
+    }).await
+}
+
+pub async fn update_user_login(user_id: Uuid, login: &str, db_pool: &SqlitePool) -> Result<bool, ServiceError> {
+    traced("db.update_user_login", async move {
+        let result = sqlx::query(
+                "UPDATE users
+                SET login = ?
+                WHERE id = ?"
+            )
+            .bind(login)
+            .bind(user_id)
+            .execute(db_pool)
+            .traced_query_on(DB_SYSTEM, "UPDATE", "users")
+            .await;
+
+        match result {
+            Ok(_) => Ok(true),
+            Err(e) if is_unique_violation(&e) => Ok(false),
+            Err(e) => Err(internal_error(e)),
+        }
+    }).await
+}
+
+pub async fn delete_user(user_id: Uuid, password: &str, db_pool: &SqlitePool) -> Result<bool, ServiceError> {
+    traced("db.delete_user", async move {
+        let mut transaction = db_pool.begin().await.map_err(internal_error)?;
+
+        // lists, tasks, tokens and 2FA are deleted by ON DELETE CASCADE
+        let deleted = sqlx::query(
+                "DELETE FROM users
+                WHERE id = ? AND password = ?"
+            )
+            .bind(user_id)
+            .bind(password)
+            .execute(&mut transaction)
+            .traced_query_on(DB_SYSTEM, "DELETE", "users")
+            .await
+            .map_err(internal_error)?
+            .rows_affected();
+
+        if deleted == 0 {
+            return Ok(false);
+        }
+
+        // scope of idempotency keys is user id
+        sqlx::query(
+                "DELETE FROM idempotency_keys
+                WHERE scope = ?"
+            )
+            .bind(user_id.to_string())
+            .execute(&mut transaction)
+            .traced_query_on(DB_SYSTEM, "DELETE", "idempotency_keys")
+            .await
+            .map_err(internal_error)?;
+
+        transaction.commit().await.map_err(internal_error)?;
+
+        Ok(true)
+    }).await
+}
+
+pub async fn select_user_email(user_id: Uuid, db_pool: &SqlitePool) -> Result<Option<UserEmail>, ServiceError> {
+    traced("db.select_user_email", async move {
+        let result: Option<(Option<String>, bool)> = sqlx::query_as(
+                "SELECT email, email_verified
+                FROM users
+                WHERE id = ?"
+            )
+            .bind(user_id)
+            .fetch_optional(db_pool)
+            .traced_query_on(DB_SYSTEM, "SELECT", "users")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(result.and_then(|(email, verified)| email.map(|email| UserEmail { email, verified })))
+    }).await
+}
+
+pub async fn update_user_email(user_id: Uuid, email: &str, db_pool: &SqlitePool) -> Result<(), ServiceError> {
+    traced("db.update_user_email", async move {
+        sqlx::query(
+                "UPDATE users
+                SET email = ?, email_verified = 0
+                WHERE id = ?"
+            )
+            .bind(email)
+            .bind(user_id)
+            .execute(db_pool)
+            .traced_query_on(DB_SYSTEM, "UPDATE", "users")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(())
+    }).await
+}
+
+pub async fn verify_user_email(user_id: Uuid, email: &str, db_pool: &SqlitePool) -> Result<bool, ServiceError> {
+    traced("db.verify_user_email", async move {
+        let result = sqlx::query(
+                "UPDATE users
+                SET email_verified = 1
+                WHERE id = ? AND email = ?"
+            )
+            .bind(user_id)
+            .bind(email)
+            .execute(db_pool)
+            .traced_query_on(DB_SYSTEM, "UPDATE", "users")
+            .await;
+
+        match result {
+            Ok(result) => Ok(result.rows_affected() > 0),
+            Err(e) if is_unique_violation(&e) => Ok(false),
+            Err(e) => Err(internal_error(e)),
+        }
+    }).await
+}
+
+pub async fn select_user_id_by_email(email: &str, db_pool: &SqlitePool) -> Result<Option<Uuid>, ServiceError> {
+    traced("db.select_user_id_by_email", async move {
+        let result = sqlx::query_scalar(
+                "SELECT id
+                FROM users
+                WHERE email = ? AND email_verified"
+            )
+            .bind(email)
+            .fetch_optional(db_pool)
+            .traced_query_on(DB_SYSTEM, "SELECT", "users")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(result)
+    }).await
+}
+
+pub async fn reset_user_password(user_id: Uuid, new_password: &str, db_pool: &SqlitePool) -> Result<(), ServiceError> {
+    traced("db.reset_user_password", async move {
+        sqlx::query(
+                "UPDATE users
+                SET password = ?, session_version = session_version + 1
+                WHERE id = ?"
+            )
+            .bind(new_password)
+            .bind(user_id)
+            .execute(db_pool)
+            .traced_query_on(DB_SYSTEM, "UPDATE", "users")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(())
+    }).await
+}
+
+#[async_trait]
+impl UserRepository for SqliteStorage {
+    async fn insert_user(&self, user: &NewUser) -> Result<Uuid, ServiceError> {
+        insert_user(user, &self.pool).await
+    }
+
+    async fn select_user_id(&self, login: &str, password: &str) -> Result<Option<Uuid>, ServiceError> {
+        select_user_id(login, password, &self.pool).await
+    }
+
+    async fn is_user_exist(&self, login: &str) -> Result<bool, ServiceError> {
+        is_user_exist(login, &self.pool).await
+    }
+
+    async fn select_user_login(&self, user_id: Uuid) -> Result<Option<String>, ServiceError> {
+        select_user_login(user_id, &self.pool).await
+    }
+
+    async fn select_session_version(&self, user_id: Uuid) -> Result<Option<i32>, ServiceError> {
+        select_session_version(user_id, &self.pool).await
+    }
+
+    async fn update_user_password(&self, user_id: Uuid, old_password: &str, new_password: &str) -> Result<Option<i32>, ServiceError> {
+        update_user_password(user_id, old_password, new_password, &self.pool).await
+    }
+
+    async fn update_user_login(&self, user_id: Uuid, login: &str) -> Result<bool, ServiceError> {
+        update_user_login(user_id, login, &self.pool).await
+    }
+
+    async fn delete_user(&self, user_id: Uuid, password: &str) -> Result<bool, ServiceError> {
+        delete_user(user_id, password, &self.pool).await
+    }
+
+    async fn select_user_email(&self, user_id: Uuid) -> Result<Option<UserEmail>, ServiceError> {
+        select_user_email(user_id, &self.pool).await
+    }
+
+    async fn update_user_email(&self, user_id: Uuid, email: &str) -> Result<(), ServiceError> {
+        update_user_email(user_id, email, &self.pool).await
+    }
+
+    async fn verify_user_email(&self, user_id: Uuid, email: &str) -> Result<bool, ServiceError> {
+        verify_user_email(user_id, email, &self.pool).await
+    }
+
+    async fn select_user_id_by_email(&self, email: &str) -> Result<Option<Uuid>, ServiceError> {
+        select_user_id_by_email(email, &self.pool).await
+    }
+
+    async fn reset_user_password(&self, user_id: Uuid, new_password: &str) -> Result<(), ServiceError> {
+        reset_user_password(user_id, new_password, &self.pool).await
+    }
+}
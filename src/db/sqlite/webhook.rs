@@ -0,0 +1,435 @@
+use std::{
+    collections::HashMap,
+    time::Duration
+};
+
+use async_trait::async_trait;
+use chrono::{
+    DateTime,
+    TimeZone,
+    Utc
+};
+use sqlx::{
+    SqliteConnection,
+    SqlitePool
+};
+use uuid::Uuid;
+
+use super::{
+    SqliteStorage,
+    DB_SYSTEM
+};
+use crate::db::{
+    internal_error,
+    WebhookRepository
+};
+use crate::utils::telemetry::{
+    traced,
+    TracedQuery
+};
+use crate::models::{
+    ServiceError,
+    NewWebhook,
+    UpdateWebhook,
+    WebhookInfo,
+    WebhookEvent,
+    WebhookDelivery,
+    PendingDelivery,
+    DeliveryAttempt,
+    DeliveryStatus,
+    Outbox,
+    events_from_string,
+    events_to_string
+};
+
+/// Timestamps are stored as unix seconds
+fn from_timestamp(timestamp: i64) -> DateTime<Utc> {
+    Utc.timestamp_opt(timestamp, 0).single().unwrap_or_default()
+}
+
+type WebhookRow = (Uuid, String, String, bool, i32, i64);
+
+fn webhook_info((id, url, events, enabled, consecutive_failures, created_at): WebhookRow) -> WebhookInfo {
+    WebhookInfo {
+        id,
+        url,
+        events: events_from_string(&events),
+        enabled,
+        consecutive_failures,
+        created_at: from_timestamp(created_at),
+    }
+}
+
+type DeliveryRow = (Uuid, String, String, i32, Option<i32>, Option<String>, i64, Option<i64>, Option<i64>);
+
+pub async fn insert_webhook(user_id: Uuid, webhook: &NewWebhook, secret: &str, db_pool: &SqlitePool) -> Result<WebhookInfo, ServiceError> {
+    traced("db.insert_webhook", async move {
+        let id = Uuid::new_v4();
+        let created_at = from_timestamp(Utc::now().timestamp());
+
+        sqlx::query(
+                "INSERT INTO webhooks (id, user_id, url, secret, events, created_at)
+                VALUES (?, ?, ?, ?, ?, ?)"
+            )
+            .bind(id)
+            .bind(user_id)
+            .bind(&webhook.url)
+            .bind(secret)
+            .bind(events_to_string(&webhook.events))
+            .bind(created_at.timestamp())
+            .execute(db_pool)
+            .traced_query_on(DB_SYSTEM, "INSERT", "webhooks")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(WebhookInfo {
+            id,
+            url: webhook.url.clone(),
+            events: webhook.events.clone(),
+            enabled: true,
+            consecutive_failures: 0,
+            created_at,
+        })
+    }).await
+}
+
+pub async fn select_webhooks(user_id: Uuid, db_pool: &SqlitePool) -> Result<Vec<WebhookInfo>, ServiceError> {
+    traced("db.select_webhooks", async move {
+        let result: Vec<WebhookRow> = sqlx::query_as(
+                "SELECT id, url, events, enabled, consecutive_failures, created_at
+                FROM webhooks
+                WHERE user_id = ?
+                ORDER BY created_at, rowid"
+            )
+            .bind(user_id)
+            .fetch_all(db_pool)
+            .traced_query_on(DB_SYSTEM, "SELECT", "webhooks")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(result.into_iter().map(webhook_info).collect())
+    }).await
+}
+
+pub async fn update_webhook(user_id: Uuid, webhook_id: Uuid, update: &UpdateWebhook, db_pool: &SqlitePool) -> Result<Option<WebhookInfo>, ServiceError> {
+    traced("db.update_webhook", async move {
+        let result: Option<WebhookRow> = sqlx::query_as(
+                "UPDATE webhooks
+                SET url = COALESCE(?, url),
+                    secret = COALESCE(?, secret),
+                    events = COALESCE(?, events),
+                    enabled = COALESCE(?, enabled),
+                    consecutive_failures = CASE WHEN ? THEN 0 ELSE consecutive_failures END
+                WHERE id = ? AND user_id = ?
+                RETURNING id, url, events, enabled, consecutive_failures, created_at"
+            )
+            .bind(&update.url)
+            .bind(&update.secret)
+            .bind(update.events.as_deref().map(events_to_string))
+            .bind(update.enabled)
+            .bind(update.enabled)
+            .bind(webhook_id)
+            .bind(user_id)
+            .fetch_optional(db_pool)
+            .traced_query_on(DB_SYSTEM, "UPDATE", "webhooks")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(result.map(webhook_info))
+    }).await
+}
+
+pub async fn delete_webhook(user_id: Uuid, webhook_id: Uuid, db_pool: &SqlitePool) -> Result<bool, ServiceError> {
+    traced("db.delete_webhook", async move {
+        let deleted = sqlx::query(
+                "DELETE FROM webhooks
+                WHERE id = ? AND user_id = ?"
+            )
+            .bind(webhook_id)
+            .bind(user_id)
+            .execute(db_pool)
+            .traced_query_on(DB_SYSTEM, "DELETE", "webhooks")
+            .await
+            .map_err(internal_error)?
+            .rows_affected();
+
+        Ok(deleted > 0)
+    }).await
+}
+
+/// Adds pending delivery of every event for every enabled webhook of the user subscribed to it,
+/// in transaction of the write, which made the events
+pub async fn insert_outbox(outbox: &Outbox, connection: &mut SqliteConnection) -> Result<(), ServiceError> {
+    for (event, payloads) in outbox.payloads() {
+        insert_webhook_deliveries(outbox.user_id(), event, &payloads, &mut *connection).await?;
+    }
+
+    Ok(())
+}
+
+async fn insert_webhook_deliveries(user_id: Uuid, event: WebhookEvent, payloads: &[String], connection: &mut SqliteConnection) -> Result<u64, ServiceError> {
+    traced("db.insert_webhook_deliveries", async move {
+        let now = Utc::now().timestamp();
+
+        let webhook_ids: Vec<Uuid> = sqlx::query_scalar(
+                "SELECT id
+                FROM webhooks
+                WHERE user_id = ? AND enabled AND (' ' || events || ' ') LIKE ('% ' || ? || ' %')"
+            )
+            .bind(user_id)
+            .bind(event.as_str())
+            .fetch_all(&mut *connection)
+            .traced_query_on(DB_SYSTEM, "SELECT", "webhooks")
+            .await
+            .map_err(internal_error)?;
+
+        // sqlite has no uuid function, ids are generated here
+        for payload in payloads {
+            for webhook_id in &webhook_ids {
+                sqlx::query(
+                        "INSERT INTO webhook_deliveries (id, webhook_id, event, payload, status, created_at, next_attempt_at)
+                        VALUES (?, ?, ?, ?, ?, ?, ?)"
+                    )
+                    .bind(Uuid::new_v4())
+                    .bind(webhook_id)
+                    .bind(event.as_str())
+                    .bind(payload)
+                    .bind(DeliveryStatus::Pending.as_str())
+                    .bind(now)
+                    .bind(now)
+                    .execute(&mut *connection)
+                    .traced_query_on(DB_SYSTEM, "INSERT", "webhook_deliveries")
+                    .await
+                    .map_err(internal_error)?;
+            }
+        }
+
+        Ok((webhook_ids.len() * payloads.len()) as u64)
+    }).await
+}
+
+pub async fn select_webhook_deliveries(user_id: Uuid, webhook_id: Uuid, limit: i64, db_pool: &SqlitePool) -> Result<Option<Vec<WebhookDelivery>>, ServiceError> {
+    traced("db.select_webhook_deliveries", async move {
+        let exists: bool = sqlx::query_scalar(
+                "SELECT EXISTS(SELECT 1 FROM webhooks WHERE id = ? AND user_id = ?)"
+            )
+            .bind(webhook_id)
+            .bind(user_id)
+            .fetch_one(db_pool)
+            .traced_query_on(DB_SYSTEM, "SELECT", "webhooks")
+            .await
+            .map_err(internal_error)?;
+
+        if !exists {
+            return Ok(None);
+        }
+
+        let result: Vec<DeliveryRow> = sqlx::query_as(
+                "SELECT id, event, status, attempts, last_status_code, last_error, created_at, last_attempt_at, next_attempt_at
+                FROM webhook_deliveries
+                WHERE webhook_id = ?
+                ORDER BY created_at DESC, rowid DESC
+                LIMIT ?"
+            )
+            .bind(webhook_id)
+            .bind(limit)
+            .fetch_all(db_pool)
+            .traced_query_on(DB_SYSTEM, "SELECT", "webhook_deliveries")
+            .await
+            .map_err(internal_error)?;
+
+        Ok(Some(result.into_iter()
+            .filter_map(|(id, event, status, attempts, last_status_code, last_error, created_at, last_attempt_at, next_attempt_at)| Some(WebhookDelivery {
+                id,
+                event: WebhookEvent::parse(&event)?,
+                status: DeliveryStatus::parse(&status)?,
+                attempts,
+                last_status_code,
+                last_error,
+                created_at: from_timestamp(created_at),
+                last_attempt_at: last_attempt_at.map(from_timestamp),
+                next_attempt_at: next_attempt_at.map(from_timestamp),
+            }))
+            .collect()))
+    }).await
+}
+
+pub async fn claim_webhook_deliveries(lease: Duration, limit: i64, db_pool: &SqlitePool) -> Result<Vec<PendingDelivery>, ServiceError> {
+    traced("db.claim_webhook_deliveries", async move {
+        let now = Utc::now().timestamp();
+
+        // one statement, so deliveries are claimed once
+        let claimed: Vec<(Uuid, Uuid, String, String, i32)> = sqlx::query_as(
+                "UPDATE webhook_deliveries
+                SET next_attempt_at = ?
+                WHERE id IN (
+                    SELECT webhook_deliveries.id
+                    FROM webhook_deliveries
+                    JOIN webhooks ON webhooks.id = webhook_deliveries.webhook_id
+                    WHERE webhook_deliveries.status = ? AND webhook_deliveries.next_attempt_at <= ? AND webhooks.enabled
+                    ORDER BY webhook_deliveries.next_attempt_at
+                    LIMIT ?
+                )
+                RETURNING id, webhook_id, event, payload, attempts"
+            )
+            .bind(now + lease.as_secs() as i64)
+            .bind(DeliveryStatus::Pending.as_str())
+            .bind(now)
+            .bind(limit)
+            .fetch_all(db_pool)
+            .traced_query_on(DB_SYSTEM, "UPDATE", "webhook_deliveries")
+            .await
+            .map_err(internal_error)?;
+
+        // url and secret of webhooks, RETURNING has no joins
+        let mut webhooks: HashMap<Uuid, Option<(String, String)>> = HashMap::new();
+        for (_, webhook_id, ..) in &claimed {
+            if webhooks.contains_key(webhook_id) {
+                continue;
+            }
+
+            let webhook: Option<(String, String)> = sqlx::query_as(
+                    "SELECT url, secret
+                    FROM webhooks
+                    WHERE id = ?"
+                )
+                .bind(webhook_id)
+                .fetch_optional(db_pool)
+                .traced_query_on(DB_SYSTEM, "SELECT", "webhooks")
+                .await
+                .map_err(internal_error)?;
+
+            webhooks.insert(*webhook_id, webhook);
+        }
+
+        Ok(claimed.into_iter()
+            .filter_map(|(id, webhook_id, event, payload, attempts)| {
+                let (url, secret) = webhooks.get(&webhook_id)?.clone()?;
+                Some(PendingDelivery { id, webhook_id, url, secret, event: WebhookEvent::parse(&event)?, payload, attempts })
+            })
+            .collect())
+    }).await
+}
+
+pub async fn record_webhook_attempt(delivery_id: Uuid, attempt: &DeliveryAttempt, disable_after: i32, db_pool: &SqlitePool) -> Result<bool, ServiceError> {
+    traced("db.record_webhook_attempt", async move {
+        let status = match (attempt.succeeded, attempt.retry_at) {
+            (true, _) => DeliveryStatus::Succeeded,
+            (false, Some(_)) => DeliveryStatus::Pending,
+            (false, None) => DeliveryStatus::Failed,
+        };
+        let next_attempt_at = attempt.retry_at.filter(|_| !attempt.succeeded);
+
+        let mut transaction = db_pool.begin().await.map_err(internal_error)?;
+
+        let webhook_id: Option<Uuid> = sqlx::query_scalar(
+                "UPDATE webhook_deliveries
+                SET status = ?, attempts = attempts + 1, last_status_code = ?, last_error = ?, last_attempt_at = ?, next_attempt_at = ?
+                WHERE id = ?
+                RETURNING webhook_id"
+            )
+            .bind(status.as_str())
+            .bind(attempt.status_code)
+            .bind(&attempt.error)
+            .bind(Utc::now().timestamp())
+            .bind(next_attempt_at.map(|next_attempt_at| next_attempt_at.timestamp()))
+            .bind(delivery_id)
+            .fetch_optional(&mut transaction)
+            .traced_query_on(DB_SYSTEM, "UPDATE", "webhook_deliveries")
+            .await
+            .map_err(internal_error)?;
+
+        // webhook was deleted meanwhile
+        let webhook_id = match webhook_id {
+            Some(webhook_id) => webhook_id,
+            None => return Ok(false),
+        };
+
+        let disabled = if attempt.succeeded {
+            sqlx::query(
+                    "UPDATE webhooks
+                    SET consecutive_failures = 0
+                    WHERE id = ?"
+                )
+                .bind(webhook_id)
+                .execute(&mut transaction)
+                .traced_query_on(DB_SYSTEM, "UPDATE", "webhooks")
+                .await
+                .map_err(internal_error)?;
+
+            false
+        } else {
+            let (enabled, consecutive_failures): (bool, i32) = sqlx::query_as(
+                    "UPDATE webhooks
+                    SET consecutive_failures = consecutive_failures + 1, enabled = enabled AND consecutive_failures + 1 < ?
+                    WHERE id = ?
+                    RETURNING enabled, consecutive_failures"
+                )
+                .bind(disable_after)
+                .bind(webhook_id)
+                .fetch_one(&mut transaction)
+                .traced_query_on(DB_SYSTEM, "UPDATE", "webhooks")
+                .await
+                .map_err(internal_error)?;
+
+            !enabled && consecutive_failures == disable_after
+        };
+
+        transaction.commit().await.map_err(internal_error)?;
+
+        Ok(disabled)
+    }).await
+}
+
+pub async fn delete_webhook_deliveries_before(before: DateTime<Utc>, db_pool: &SqlitePool) -> Result<u64, ServiceError> {
+    traced("db.delete_webhook_deliveries_before", async move {
+        let deleted = sqlx::query(
+                "DELETE FROM webhook_deliveries
+                WHERE status <> ? AND created_at < ?"
+            )
+            .bind(DeliveryStatus::Pending.as_str())
+            .bind(before.timestamp())
+            .execute(db_pool)
+            .traced_query_on(DB_SYSTEM, "DELETE", "webhook_deliveries")
+            .await
+            .map_err(internal_error)?
+            .rows_affected();
+
+        Ok(deleted)
+    }).await
+}
+
+#[async_trait]
+impl WebhookRepository for SqliteStorage {
+    async fn insert_webhook(&self, user_id: Uuid, webhook: &NewWebhook, secret: &str) -> Result<WebhookInfo, ServiceError> {
+        insert_webhook(user_id, webhook, secret, &self.pool).await
+    }
+
+    async fn select_webhooks(&self, user_id: Uuid) -> Result<Vec<WebhookInfo>, ServiceError> {
+        select_webhooks(user_id, &self.pool).await
+    }
+
+    async fn update_webhook(&self, user_id: Uuid, webhook_id: Uuid, update: &UpdateWebhook) -> Result<Option<WebhookInfo>, ServiceError> {
+        update_webhook(user_id, webhook_id, update, &self.pool).await
+    }
+
+    async fn delete_webhook(&self, user_id: Uuid, webhook_id: Uuid) -> Result<bool, ServiceError> {
+        delete_webhook(user_id, webhook_id, &self.pool).await
+    }
+
+    async fn select_webhook_deliveries(&self, user_id: Uuid, webhook_id: Uuid, limit: i64) -> Result<Option<Vec<WebhookDelivery>>, ServiceError> {
+        select_webhook_deliveries(user_id, webhook_id, limit, &self.pool).await
+    }
+
+    async fn claim_webhook_deliveries(&self, lease: Duration, limit: i64) -> Result<Vec<PendingDelivery>, ServiceError> {
+        claim_webhook_deliveries(lease, limit, &self.pool).await
+    }
+
+    async fn record_webhook_attempt(&self, delivery_id: Uuid, attempt: &DeliveryAttempt, disable_after: i32) -> Result<bool, ServiceError> {
+        record_webhook_attempt(delivery_id, attempt, disable_after, &self.pool).await
+    }
+
+    async fn delete_webhook_deliveries_before(&self, before: DateTime<Utc>) -> Result<u64, ServiceError> {
+        delete_webhook_deliveries_before(before, &self.pool).await
+    }
+}
@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+
+use actix_web::web;
+use async_graphql::dataloader::Loader;
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        TaskRepository,
+        TodoListRepository
+    },
+    models::{
+        FullTaskInfo,
+        FullTodoListInfo
+    }
+};
+
+use super::service_error;
+
+/// Tasks of the user list by task id, ids of one level are selected by one query
+pub struct TaskLoader {
+    pub lists: web::Data<dyn TodoListRepository>,
+    pub tasks: web::Data<dyn TaskRepository>,
+    pub user_id: Uuid,
+}
+
+impl Loader<Uuid> for TaskLoader {
+    type Value = FullTaskInfo;
+    type Error = async_graphql::Error;
+
+    async fn load(&self, keys: &[Uuid]) -> Result<HashMap<Uuid, FullTaskInfo>, async_graphql::Error> {
+        let todo_list_id = match self.lists.select_todo_list_id(self.user_id).await.map_err(service_error)? {
+            Some(todo_list_id) => todo_list_id,
+            None => return Ok(HashMap::new()),
+        };
+
+        let tasks = self.tasks.select_tasks_by_ids(todo_list_id, keys).await.map_err(service_error)?;
+
+        Ok(tasks.into_iter().map(|task| (task.id, task)).collect())
+    }
+}
+
+/// List by id of its user, every task of the list resolves `list` by the same key
+pub struct TodoListLoader {
+    pub lists: web::Data<dyn TodoListRepository>,
+}
+
+impl Loader<Uuid> for TodoListLoader {
+    type Value = FullTodoListInfo;
+    type Error = async_graphql::Error;
+
+    async fn load(&self, keys: &[Uuid]) -> Result<HashMap<Uuid, FullTodoListInfo>, async_graphql::Error> {
+        let mut lists = HashMap::new();
+
+        // user has one list, keys are users of the request
+        for user_id in keys {
+            if let Some(todo_list) = self.lists.select_todo_list(*user_id).await.map_err(service_error)? {
+                lists.insert(*user_id, todo_list);
+            }
+        }
+
+        Ok(lists)
+    }
+}
+
+/// Tasks of the list in order, by list id
+pub struct ListTasksLoader {
+    pub tasks: web::Data<dyn TaskRepository>,
+}
+
+impl Loader<Uuid> for ListTasksLoader {
+    type Value = Vec<FullTaskInfo>;
+    type Error = async_graphql::Error;
+
+    async fn load(&self, keys: &[Uuid]) -> Result<HashMap<Uuid, Vec<FullTaskInfo>>, async_graphql::Error> {
+        let mut lists = HashMap::new();
+
+        for todo_list_id in keys {
+            let mut tasks = self.tasks.select_tasks(*todo_list_id).await.map_err(service_error)?;
+            tasks.sort_by_key(|task| task.order);
+            lists.insert(*todo_list_id, tasks);
+        }
+
+        Ok(lists)
+    }
+}
@@ -0,0 +1,207 @@
+//! GraphQL api over users, lists and tasks. Mutations run the REST handlers, so validation,
+//! scopes and change events are the same, subscriptions get the events of webhooks.
+//! Nested fields are loaded by dataloaders of the request, which batch queries of one level
+mod loader;
+mod mutation;
+mod query;
+mod subscription;
+mod types;
+
+use actix_web::{
+    dev,
+    web,
+    FromRequest,
+    HttpRequest
+};
+use async_graphql::{
+    dataloader::DataLoader,
+    Data,
+    ErrorExtensions,
+    Schema
+};
+use futures::future::LocalBoxFuture;
+use validator::ValidationErrors;
+
+use crate::{
+    db::{
+        TaskRepository,
+        TodoListRepository,
+        UserRepository
+    },
+    middlewares::{
+        BearerAuth,
+        RequestLogger
+    },
+    models::{
+        RequestValidationError,
+        ServiceError,
+        StatusCode
+    },
+    utils::events::ChangeEvents
+};
+
+pub use loader::*;
+pub use mutation::Mutation;
+pub use query::Query;
+pub use subscription::Subscription;
+pub use types::*;
+
+/// Deeper queries are rejected, introspection of GraphiQL is about 13 levels deep
+const GRAPHQL_MAX_DEPTH: usize = 16;
+const GRAPHQL_MAX_COMPLEXITY: usize = 1000;
+
+pub type TodoSchema = Schema<Query, Mutation, Subscription>;
+
+pub fn schema() -> TodoSchema {
+    Schema::build(Query, Mutation, Subscription)
+        .limit_depth(GRAPHQL_MAX_DEPTH)
+        .limit_complexity(GRAPHQL_MAX_COMPLEXITY)
+        .finish()
+}
+
+/// Repositories and user of the request, the same as handlers get
+pub struct GraphqlContext {
+    pub users: web::Data<dyn UserRepository>,
+    pub lists: web::Data<dyn TodoListRepository>,
+    pub tasks: web::Data<dyn TaskRepository>,
+    pub events: web::Data<ChangeEvents>,
+    pub auth: BearerAuth,
+    pub logger: slog::Logger,
+}
+
+impl GraphqlContext {
+    pub fn logger(&self) -> RequestLogger {
+        RequestLogger(self.logger.clone())
+    }
+
+    /// Data of the request (or of WebSocket connection) with dataloaders
+    pub fn into_data(self) -> Data {
+        let mut data = Data::default();
+
+        data.insert(DataLoader::new(TaskLoader { lists: self.lists.clone(), tasks: self.tasks.clone(), user_id: self.auth.user_id }, tokio::spawn));
+        data.insert(DataLoader::new(TodoListLoader { lists: self.lists.clone() }, tokio::spawn));
+        data.insert(DataLoader::new(ListTasksLoader { tasks: self.tasks.clone() }, tokio::spawn));
+        data.insert(self);
+
+        data
+    }
+}
+
+impl FromRequest for GraphqlContext {
+    type Error = ServiceError;
+    type Future = LocalBoxFuture<'static, Result<GraphqlContext, ServiceError>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut dev::Payload) -> Self::Future {
+        let auth = BearerAuth::from_request(req, payload);
+        let logger = RequestLogger::from_request(req, payload);
+        let req = req.clone();
+
+        Box::pin(async move {
+            Ok(GraphqlContext {
+                users: app_data(&req, "User storage not found")?,
+                lists: app_data(&req, "TO-DO list storage not found")?,
+                tasks: app_data(&req, "Task storage not found")?,
+                events: app_data(&req, "Change events not found")?,
+                auth: auth.await?,
+                logger: logger.await.map(|logger| logger.0).unwrap_or_else(|_| slog_scope::logger()),
+            })
+        })
+    }
+}
+
+fn app_data<T: ?Sized + 'static>(req: &HttpRequest, detail: &str) -> Result<web::Data<T>, ServiceError> {
+    req.app_data::<web::Data<T>>()
+        .cloned()
+        .ok_or(ServiceError { status_code: StatusCode::InternalError, detail: Some(detail.to_string()) })
+}
+
+/// Error of REST api as GraphQL error, status code is in `extensions.status_code`
+pub(crate) fn service_error(e: ServiceError) -> async_graphql::Error {
+    let message = e.detail.clone().unwrap_or_else(|| e.status_code.to_string());
+
+    async_graphql::Error::new(message).extend_with(|_, extensions| {
+        if let Ok(status_code) = serde_json::to_value(&e.status_code) {
+            extensions.set("status_code", async_graphql::Value::from_json(status_code).unwrap_or_default());
+        }
+    })
+}
+
+/// Validation errors of the input, messages by field are in `extensions.fields`
+pub(crate) fn validation_error(errors: ValidationErrors) -> async_graphql::Error {
+    let error = RequestValidationError::from(errors);
+    let message = error.detail.clone().unwrap_or_else(|| error.status_code.to_string());
+
+    async_graphql::Error::new(message).extend_with(|_, extensions| {
+        if let Ok(status_code) = serde_json::to_value(&error.status_code) {
+            extensions.set("status_code", async_graphql::Value::from_json(status_code).unwrap_or_default());
+        }
+        if let Ok(fields) = serde_json::to_value(&error.fields) {
+            extensions.set("fields", async_graphql::Value::from_json(fields).unwrap_or_default());
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use async_graphql::Request;
+
+    use super::*;
+    use crate::{
+        db::{
+            memory::MemoryStorage,
+            Storage
+        },
+        models::{
+            NewTodoList,
+            Outbox
+        }
+    };
+
+    fn context(storage: &Storage, user_id: uuid::Uuid) -> GraphqlContext {
+        GraphqlContext {
+            users: web::Data::from(storage.users.clone()),
+            lists: web::Data::from(storage.lists.clone()),
+            tasks: web::Data::from(storage.tasks.clone()),
+            events: web::Data::new(storage.events.clone()),
+            auth: BearerAuth { user_id, scopes: None },
+            logger: slog::Logger::root(slog::Discard, slog::o!()),
+        }
+    }
+
+    #[tokio::test]
+    async fn tasks_are_loaded_by_id() {
+        let storage = Storage::new(MemoryStorage::new());
+        let user_id = uuid::Uuid::new_v4();
+        let todo_list_id = storage.lists.insert_todo_list(user_id, &NewTodoList { name: "test".to_string() }, &mut Outbox::new(user_id)).await.unwrap();
+        let first = storage.tasks.insert_task_to_end(todo_list_id, "first".to_string(), &mut Outbox::new(user_id)).await.unwrap();
+        let second = storage.tasks.insert_task_to_end(todo_list_id, "second".to_string(), &mut Outbox::new(user_id)).await.unwrap();
+
+        let query = format!(r#"{{
+            a: task(id: "{first}") {{ description list {{ name }} }}
+            b: task(id: "{second}") {{ description list {{ name }} }}
+            c: task(id: "{}") {{ description }}
+        }}"#, uuid::Uuid::new_v4());
+
+        let mut request = Request::new(query);
+        request.data = context(&storage, user_id).into_data();
+        let response = schema().execute(request).await;
+
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        let data = response.data.into_json().unwrap();
+        assert_eq!(data["a"]["description"], "first");
+        assert_eq!(data["b"]["list"]["name"], "test");
+        assert_eq!(data["c"], serde_json::Value::Null);
+    }
+
+    #[tokio::test]
+    async fn introspection_is_allowed() {
+        let storage = Storage::new(MemoryStorage::new());
+        let query = r#"{ __schema { types { name fields { name type { name ofType { name ofType { name ofType { name ofType { name ofType { name ofType { name } } } } } } } } } } }"#;
+
+        let mut request = Request::new(query);
+        request.data = context(&storage, uuid::Uuid::new_v4()).into_data();
+        let response = schema().execute(request).await;
+
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+    }
+}
@@ -0,0 +1,140 @@
+use actix_web::web;
+use async_graphql::{
+    dataloader::DataLoader,
+    Context,
+    Object,
+    Result
+};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::{
+    handlers,
+    middlewares::ValidatedJson,
+    models::{
+        MoveTask,
+        NewTask,
+        NewTodoList,
+        ServiceError,
+        StatusCode,
+        TaskDetails,
+        TaskPosition,
+        UpdateTask,
+        UpdateTodoList
+    }
+};
+
+use super::{
+    service_error,
+    validation_error,
+    GraphqlContext,
+    Task,
+    TaskDetailsInput,
+    TaskLoader,
+    TaskPositionInput,
+    TodoList
+};
+
+/// Mutations run the REST handlers with the input validated the same way
+pub struct Mutation;
+
+#[Object]
+impl Mutation {
+    async fn create_list(&self, ctx: &Context<'_>, name: String) -> Result<TodoList> {
+        let context = ctx.data::<GraphqlContext>()?;
+        let input = NewTodoList { name };
+        input.validate().map_err(validation_error)?;
+
+        handlers::new_list(context.lists.clone(), context.events.clone(), ValidatedJson(input), context.auth.clone(), context.logger()).await
+            .map_err(service_error)?;
+
+        user_list(context).await
+    }
+
+    async fn update_list(&self, ctx: &Context<'_>, name: String) -> Result<TodoList> {
+        let context = ctx.data::<GraphqlContext>()?;
+        let input = UpdateTodoList { name };
+        input.validate().map_err(validation_error)?;
+
+        handlers::update_list(context.lists.clone(), context.events.clone(), ValidatedJson(input), context.auth.clone(), context.logger()).await
+            .map_err(service_error)?;
+
+        user_list(context).await
+    }
+
+    /// Deletes list with its tasks, count of deleted tasks is returned
+    async fn delete_list(&self, ctx: &Context<'_>) -> Result<i64> {
+        let context = ctx.data::<GraphqlContext>()?;
+
+        handlers::delete_user_list(context.lists.as_ref(), &context.events, &context.auth, &context.logger()).await
+            .map_err(service_error)
+    }
+
+    /// Adds task, to the end if position is not set
+    async fn add_task(&self, ctx: &Context<'_>, description: String, position: Option<TaskPositionInput>) -> Result<Task> {
+        let context = ctx.data::<GraphqlContext>()?;
+        let input = NewTask { description, position: position.map_or(TaskPosition::End, TaskPosition::from) };
+        input.validate().map_err(validation_error)?;
+
+        let id = handlers::new_task(ValidatedJson(input), context.lists.clone(), context.tasks.clone(), context.events.clone(), context.auth.clone(), context.logger()).await
+            .map_err(service_error)?;
+
+        let task = ctx.data::<DataLoader<TaskLoader>>()?.load_one(id.parse::<Uuid>()?).await?
+            .ok_or_else(|| service_error(task_not_found()))?;
+
+        Ok(Task(task))
+    }
+
+    async fn update_task(&self, ctx: &Context<'_>, id: Uuid, description: String) -> Result<Task> {
+        let context = ctx.data::<GraphqlContext>()?;
+        let input = UpdateTask { description };
+        input.validate().map_err(validation_error)?;
+
+        let task = handlers::update_task(web::Path::from(id), ValidatedJson(input), context.lists.clone(), context.tasks.clone(), context.events.clone(), context.auth.clone(), context.logger()).await
+            .map_err(service_error)?;
+
+        Ok(Task(task.into_inner()))
+    }
+
+    async fn update_task_details(&self, ctx: &Context<'_>, id: Uuid, details: TaskDetailsInput) -> Result<Task> {
+        let context = ctx.data::<GraphqlContext>()?;
+        let input = TaskDetails::from(details);
+        input.validate().map_err(validation_error)?;
+
+        let task = handlers::update_task_details(web::Path::from(id), ValidatedJson(input), context.lists.clone(), context.tasks.clone(), context.events.clone(), context.auth.clone(), context.logger()).await
+            .map_err(service_error)?;
+
+        Ok(Task(task.into_inner()))
+    }
+
+    async fn move_task(&self, ctx: &Context<'_>, id: Uuid, position: TaskPositionInput) -> Result<Task> {
+        let context = ctx.data::<GraphqlContext>()?;
+        let input = MoveTask { position: position.into() };
+
+        let task = handlers::move_task(web::Path::from(id), ValidatedJson(input), context.lists.clone(), context.tasks.clone(), context.events.clone(), context.auth.clone(), context.logger()).await
+            .map_err(service_error)?;
+
+        Ok(Task(task.into_inner()))
+    }
+
+    /// Deletes task, the deleted task is returned
+    async fn delete_task(&self, ctx: &Context<'_>, id: Uuid) -> Result<Task> {
+        let context = ctx.data::<GraphqlContext>()?;
+
+        let task = handlers::delete_tasks(web::Path::from(id), context.lists.clone(), context.tasks.clone(), context.events.clone(), context.auth.clone(), context.logger()).await
+            .map_err(service_error)?;
+
+        Ok(Task(task.into_inner()))
+    }
+}
+
+async fn user_list(context: &GraphqlContext) -> Result<TodoList> {
+    let list = context.lists.select_todo_list(context.auth.user_id).await.map_err(service_error)?
+        .ok_or_else(|| service_error(ServiceError { status_code: StatusCode::NotFound, detail: Some("TO-DO list not found".to_string()) }))?;
+
+    Ok(TodoList(list))
+}
+
+fn task_not_found() -> ServiceError {
+    ServiceError { status_code: StatusCode::NotFound, detail: Some("Task not found".to_string()) }
+}
@@ -0,0 +1,48 @@
+use async_graphql::{
+    dataloader::DataLoader,
+    Context,
+    Object,
+    Result
+};
+use uuid::Uuid;
+
+use super::{
+    GraphqlContext,
+    Task,
+    TaskLoader,
+    TodoList,
+    TodoListLoader,
+    User
+};
+
+pub struct Query;
+
+#[Object]
+impl Query {
+    /// User of the token
+    async fn me(&self, ctx: &Context<'_>) -> Result<User> {
+        Ok(User { id: ctx.data::<GraphqlContext>()?.auth.user_id })
+    }
+
+    /// List of the user, `null` if it's not created
+    async fn list(&self, ctx: &Context<'_>) -> Result<Option<TodoList>> {
+        let user_id = ctx.data::<GraphqlContext>()?.auth.user_id;
+        let list = ctx.data::<DataLoader<TodoListLoader>>()?.load_one(user_id).await?;
+
+        Ok(list.map(TodoList))
+    }
+
+    /// Task of the user list, `null` if it's not found. Tasks of aliases are selected by one query
+    async fn task(&self, ctx: &Context<'_>, id: Uuid) -> Result<Option<Task>> {
+        let task = ctx.data::<DataLoader<TaskLoader>>()?.load_one(id).await?;
+
+        Ok(task.map(Task))
+    }
+
+    /// Tasks of the user list with the ids, not found ids are skipped
+    async fn tasks(&self, ctx: &Context<'_>, #[graphql(validator(max_items = 100))] ids: Vec<Uuid>) -> Result<Vec<Task>> {
+        let tasks = ctx.data::<DataLoader<TaskLoader>>()?.load_many(ids.iter().copied()).await?;
+
+        Ok(ids.iter().filter_map(|id| tasks.get(id).cloned()).map(Task).collect())
+    }
+}
@@ -0,0 +1,34 @@
+use async_graphql::{
+    Context,
+    Result,
+    Subscription as GraphqlSubscription
+};
+use futures::{
+    future,
+    Stream,
+    StreamExt
+};
+
+use crate::models::WebhookEvent;
+
+use super::{
+    Change,
+    ChangeEventType,
+    GraphqlContext
+};
+
+pub struct Subscription;
+
+#[GraphqlSubscription]
+impl Subscription {
+    /// Changes of the user lists and tasks made after subscription, the same events as of webhooks.
+    /// All events, if `events` is not set
+    async fn changes(&self, ctx: &Context<'_>, events: Option<Vec<ChangeEventType>>) -> Result<impl Stream<Item = Change>> {
+        let context = ctx.data::<GraphqlContext>()?;
+        let events: Option<Vec<WebhookEvent>> = events.map(|events| events.into_iter().map(WebhookEvent::from).collect());
+
+        Ok(context.events.subscribe(context.auth.user_id)
+            .filter(move |change| future::ready(events.as_ref().is_none_or(|events| events.contains(&change.event))))
+            .map(Change))
+    }
+}
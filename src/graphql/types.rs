@@ -0,0 +1,203 @@
+use std::sync::Arc;
+
+use async_graphql::{
+    dataloader::DataLoader,
+    Context,
+    Enum,
+    InputObject,
+    Json,
+    Object,
+    OneofObject,
+    Result
+};
+use chrono::{
+    DateTime,
+    Utc
+};
+use uuid::Uuid;
+
+use crate::models::{
+    ChangeEvent,
+    FullTaskInfo,
+    FullTodoListInfo,
+    TaskDetails,
+    TaskPosition,
+    WebhookEvent
+};
+
+use super::{
+    service_error,
+    GraphqlContext,
+    ListTasksLoader,
+    TodoListLoader
+};
+
+/// User of the token
+pub struct User {
+    pub id: Uuid,
+}
+
+#[Object]
+impl User {
+    async fn id(&self) -> Uuid {
+        self.id
+    }
+
+    async fn login(&self, ctx: &Context<'_>) -> Result<Option<String>> {
+        let context = ctx.data::<GraphqlContext>()?;
+
+        context.users.select_user_login(self.id).await.map_err(service_error)
+    }
+
+    /// Email, `null` if it's not set
+    async fn email(&self, ctx: &Context<'_>) -> Result<Option<String>> {
+        let context = ctx.data::<GraphqlContext>()?;
+        let email = context.users.select_user_email(self.id).await.map_err(service_error)?;
+
+        Ok(email.map(|email| email.email))
+    }
+
+    /// List of the user, `null` if it's not created
+    async fn list(&self, ctx: &Context<'_>) -> Result<Option<TodoList>> {
+        let list = ctx.data::<DataLoader<TodoListLoader>>()?.load_one(self.id).await?;
+
+        Ok(list.map(TodoList))
+    }
+}
+
+pub struct TodoList(pub FullTodoListInfo);
+
+#[Object]
+impl TodoList {
+    async fn id(&self) -> Uuid {
+        self.0.id
+    }
+
+    async fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    /// Tasks in order
+    async fn tasks(&self, ctx: &Context<'_>) -> Result<Vec<Task>> {
+        let tasks = ctx.data::<DataLoader<ListTasksLoader>>()?.load_one(self.0.id).await?;
+
+        Ok(tasks.unwrap_or_default().into_iter().map(Task).collect())
+    }
+}
+
+pub struct Task(pub FullTaskInfo);
+
+#[Object]
+impl Task {
+    async fn id(&self) -> Uuid {
+        self.0.id
+    }
+
+    async fn todo_list_id(&self) -> Uuid {
+        self.0.todo_list_id
+    }
+
+    async fn description(&self) -> &str {
+        &self.0.description
+    }
+
+    /// Position in the list, starts from 1
+    async fn order(&self) -> i32 {
+        self.0.order
+    }
+
+    async fn due_at(&self) -> Option<DateTime<Utc>> {
+        self.0.due_at
+    }
+
+    /// Task is open until it is completed
+    async fn completed_at(&self) -> Option<DateTime<Utc>> {
+        self.0.completed_at
+    }
+
+    /// 1 is the highest, 9 is the lowest
+    async fn priority(&self) -> Option<i32> {
+        self.0.priority
+    }
+
+    /// Recurrence rule of iCalendar, e.g. `FREQ=WEEKLY;BYDAY=MO`
+    async fn rrule(&self) -> Option<&str> {
+        self.0.rrule.as_deref()
+    }
+
+    async fn list(&self, ctx: &Context<'_>) -> Result<Option<TodoList>> {
+        let user_id = ctx.data::<GraphqlContext>()?.auth.user_id;
+        let list = ctx.data::<DataLoader<TodoListLoader>>()?.load_one(user_id).await?;
+
+        Ok(list.map(TodoList))
+    }
+}
+
+/// Position of the task in the list: `{ end: true }`, `{ after: <task id> }` or `{ before: <task id> }`
+#[derive(OneofObject)]
+pub enum TaskPositionInput {
+    /// The end of the list, value is ignored
+    End(bool),
+    After(Uuid),
+    Before(Uuid),
+}
+
+impl From<TaskPositionInput> for TaskPosition {
+    fn from(position: TaskPositionInput) -> Self {
+        match position {
+            TaskPositionInput::End(_) => TaskPosition::End,
+            TaskPositionInput::After(task_id) => TaskPosition::After { task_id },
+            TaskPositionInput::Before(task_id) => TaskPosition::Before { task_id },
+        }
+    }
+}
+
+/// Planning details of the task, missing values are cleared
+#[derive(InputObject)]
+pub struct TaskDetailsInput {
+    pub due_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub priority: Option<i32>,
+    pub rrule: Option<String>,
+}
+
+impl From<TaskDetailsInput> for TaskDetails {
+    fn from(details: TaskDetailsInput) -> Self {
+        TaskDetails { due_at: details.due_at, completed_at: details.completed_at, priority: details.priority, rrule: details.rrule }
+    }
+}
+
+/// Event of webhooks, `TASK_CREATED` is `task.created`
+#[derive(Enum, Clone, Copy, PartialEq, Eq)]
+#[graphql(remote = "WebhookEvent")]
+pub enum ChangeEventType {
+    ListCreated,
+    ListUpdated,
+    ListDeleted,
+    TaskCreated,
+    TaskUpdated,
+    TaskMoved,
+    TaskDeleted,
+}
+
+/// Change of list or task, `data` is the same as in webhook payload
+pub struct Change(pub Arc<ChangeEvent>);
+
+#[Object(name = "ChangeEvent")]
+impl Change {
+    async fn id(&self) -> Uuid {
+        self.0.id
+    }
+
+    async fn event(&self) -> ChangeEventType {
+        self.0.event.into()
+    }
+
+    async fn created_at(&self) -> DateTime<Utc> {
+        self.0.created_at
+    }
+
+    async fn data(&self) -> Json<&serde_json::Value> {
+        Json(&self.0.data)
+    }
+}
@@ -0,0 +1,160 @@
+use std::pin::Pin;
+
+use futures::{
+    future,
+    stream,
+    Stream,
+    StreamExt
+};
+use tonic::{
+    Request,
+    Response,
+    Status
+};
+use validator::Validate;
+
+use crate::{
+    handlers,
+    middlewares::{
+        BearerAuth,
+        ValidatedJson
+    },
+    models::{
+        ChangeEvent,
+        FullTodoListInfo,
+        NewTodoList,
+        UpdateTodoList,
+        WebhookEvent
+    }
+};
+
+use super::{
+    proto::{
+        self,
+        todo_list_service_server::TodoListService
+    },
+    validation_status,
+    GrpcState
+};
+
+pub struct TodoListGrpc {
+    state: GrpcState,
+}
+
+impl TodoListGrpc {
+    pub fn new(state: GrpcState) -> Self {
+        Self { state }
+    }
+
+    async fn todo_list(&self, auth: BearerAuth) -> Result<Response<proto::TodoList>, Status> {
+        let todo_list = handlers::get_list(self.state.lists.clone(), auth).await?.into_inner();
+
+        Ok(Response::new(todo_list.into()))
+    }
+}
+
+#[tonic::async_trait]
+impl TodoListService for TodoListGrpc {
+    async fn create_list(&self, request: Request<proto::CreateListRequest>) -> Result<Response<proto::TodoList>, Status> {
+        let auth = self.state.authorize(&request).await?;
+        let logger = self.state.logger("TodoListService/CreateList", Some(auth.user_id));
+        let new_list = NewTodoList { name: request.into_inner().name };
+        new_list.validate().map_err(validation_status)?;
+
+        handlers::new_list(self.state.lists.clone(), self.state.events.clone(), ValidatedJson(new_list), auth.clone(), logger).await?;
+
+        self.todo_list(auth).await
+    }
+
+    async fn get_list(&self, request: Request<proto::GetListRequest>) -> Result<Response<proto::TodoList>, Status> {
+        let auth = self.state.authorize(&request).await?;
+
+        self.todo_list(auth).await
+    }
+
+    async fn update_list(&self, request: Request<proto::UpdateListRequest>) -> Result<Response<proto::TodoList>, Status> {
+        let auth = self.state.authorize(&request).await?;
+        let logger = self.state.logger("TodoListService/UpdateList", Some(auth.user_id));
+        let list_info = UpdateTodoList { name: request.into_inner().name };
+        list_info.validate().map_err(validation_status)?;
+
+        handlers::update_list(self.state.lists.clone(), self.state.events.clone(), ValidatedJson(list_info), auth.clone(), logger).await?;
+
+        self.todo_list(auth).await
+    }
+
+    async fn delete_list(&self, request: Request<proto::DeleteListRequest>) -> Result<Response<proto::DeleteListResponse>, Status> {
+        let auth = self.state.authorize(&request).await?;
+        let logger = self.state.logger("TodoListService/DeleteList", Some(auth.user_id));
+
+        let deleted_tasks = handlers::delete_user_list(self.state.lists.as_ref(), &self.state.events, &auth, &logger).await?;
+
+        Ok(Response::new(proto::DeleteListResponse { deleted_tasks }))
+    }
+
+    type WatchChangesStream = Pin<Box<dyn Stream<Item = Result<proto::ChangeEvent, Status>> + Send>>;
+
+    /// Token is checked again on interval, stream ends with `Unauthenticated` when it is revoked
+    async fn watch_changes(&self, request: Request<proto::WatchChangesRequest>) -> Result<Response<Self::WatchChangesStream>, Status> {
+        let auth = self.state.authorize(&request).await?;
+        let token = GrpcState::token(&request)?.to_string();
+        let events: Vec<proto::ChangeEventType> = request.into_inner().events().collect();
+
+        let changes = self.state.events.subscribe(auth.user_id)
+            .filter(move |change| future::ready(events.is_empty() || events.contains(&change.event.into())))
+            .map(|change| Ok(proto::ChangeEvent::from(change.as_ref())));
+
+        let state = self.state.clone();
+        let revoked = stream::once(async move {
+            let e = state.token_check.until_invalid(&token, state.access_tokens.as_ref(), state.users.as_ref(), &state.jwt_keys).await;
+            Err(Status::from(e))
+        });
+
+        // nothing is sent after the error
+        let changes = stream::select(changes, revoked)
+            .scan(false, |ended, change| future::ready(match *ended {
+                true => None,
+                false => {
+                    *ended = change.is_err();
+                    Some(change)
+                },
+            }));
+
+        Ok(Response::new(Box::pin(changes)))
+    }
+}
+
+impl From<FullTodoListInfo> for proto::TodoList {
+    fn from(todo_list: FullTodoListInfo) -> Self {
+        Self {
+            id: todo_list.id.to_string(),
+            user_id: todo_list.user_id.to_string(),
+            name: todo_list.name,
+        }
+    }
+}
+
+impl From<WebhookEvent> for proto::ChangeEventType {
+    fn from(event: WebhookEvent) -> Self {
+        match event {
+            WebhookEvent::ListCreated => proto::ChangeEventType::ListCreated,
+            WebhookEvent::ListUpdated => proto::ChangeEventType::ListUpdated,
+            WebhookEvent::ListDeleted => proto::ChangeEventType::ListDeleted,
+            WebhookEvent::TaskCreated => proto::ChangeEventType::TaskCreated,
+            WebhookEvent::TaskUpdated => proto::ChangeEventType::TaskUpdated,
+            WebhookEvent::TaskMoved => proto::ChangeEventType::TaskMoved,
+            WebhookEvent::TaskDeleted => proto::ChangeEventType::TaskDeleted,
+        }
+    }
+}
+
+impl From<&ChangeEvent> for proto::ChangeEvent {
+    fn from(change: &ChangeEvent) -> Self {
+        Self {
+            id: change.id.to_string(),
+            event: proto::ChangeEventType::from(change.event).into(),
+            created_at: change.created_at.to_rfc3339(),
+            data: change.data.to_string(),
+        }
+    }
+}
@@ -0,0 +1,211 @@
+//! gRPC api over users, lists and tasks, served on own port. Calls run the REST handlers,
+//! so validation, scopes and change events are the same. Token is taken from `authorization`
+//! metadata, errors of handlers are mapped to gRPC status codes
+mod list;
+mod task;
+mod user;
+
+use actix_web::web;
+use chrono::{
+    DateTime,
+    Utc
+};
+use tonic::{
+    transport::{
+        server::{
+            Router,
+            TcpIncoming
+        },
+        Server
+    },
+    Code,
+    Request,
+    Status
+};
+use uuid::Uuid;
+use validator::ValidationErrors;
+
+use crate::{
+    db::{
+        AccessTokenRepository,
+        Storage,
+        TaskRepository,
+        TodoListRepository,
+        TwoFactorRepository,
+        UserRepository
+    },
+    middlewares::{
+        bearer_token,
+        BearerAuth,
+        RequestLogger,
+        TokenCheck
+    },
+    models::{
+        RequestValidationError,
+        ServiceError,
+        StatusCode,
+        TooManyRequestsError
+    },
+    utils::{
+        events::ChangeEvents,
+        jwt::JwtKeys,
+        rate_limit::RateLimits
+    }
+};
+
+pub use list::TodoListGrpc;
+pub use task::TaskGrpc;
+pub use user::UserGrpc;
+
+/// Messages and services generated from `proto/todo.proto`
+pub mod proto {
+    tonic::include_proto!("todo.v1");
+}
+
+use proto::{
+    task_service_server::TaskServiceServer,
+    todo_list_service_server::TodoListServiceServer,
+    user_service_server::UserServiceServer
+};
+
+/// Repositories and settings shared with REST api
+#[derive(Clone)]
+pub struct GrpcState {
+    pub users: web::Data<dyn UserRepository>,
+    pub two_factor: web::Data<dyn TwoFactorRepository>,
+    pub lists: web::Data<dyn TodoListRepository>,
+    pub tasks: web::Data<dyn TaskRepository>,
+    pub access_tokens: web::Data<dyn AccessTokenRepository>,
+    pub events: web::Data<ChangeEvents>,
+    pub jwt_keys: web::Data<JwtKeys>,
+    pub rate_limits: web::Data<RateLimits>,
+    pub token_check: TokenCheck,
+    pub logger: slog::Logger,
+}
+
+impl GrpcState {
+    pub fn new(storage: &Storage, jwt_keys: web::Data<JwtKeys>, rate_limits: web::Data<RateLimits>, token_check: TokenCheck, logger: slog::Logger) -> Self {
+        Self {
+            users: web::Data::from(storage.users.clone()),
+            two_factor: web::Data::from(storage.two_factor.clone()),
+            lists: web::Data::from(storage.lists.clone()),
+            tasks: web::Data::from(storage.tasks.clone()),
+            access_tokens: web::Data::from(storage.access_tokens.clone()),
+            events: web::Data::new(storage.events.clone()),
+            jwt_keys,
+            rate_limits,
+            token_check,
+            logger,
+        }
+    }
+
+    /// User of `authorization: Bearer <token>` metadata, calls are limited per user as requests of REST api
+    pub async fn authorize<T>(&self, request: &Request<T>) -> Result<BearerAuth, Status> {
+        let token = Self::token(request)?;
+
+        let auth = BearerAuth::from_token(token, self.access_tokens.as_ref(), self.users.as_ref(), &self.jwt_keys).await?;
+
+        if let Some(limiter) = &self.rate_limits.api {
+            if let Err(retry_after) = limiter.check(&auth.user_id.to_string()) {
+                return Err(Status::resource_exhausted(format!("Too many requests, try again in {} seconds", retry_after.as_secs().max(1))));
+            }
+        }
+
+        Ok(auth)
+    }
+
+    /// Token of `authorization: Bearer <token>` metadata
+    pub fn token<T>(request: &Request<T>) -> Result<&str, Status> {
+        let auth = request.metadata().get("authorization")
+            .ok_or_else(|| Status::unauthenticated("Authorization metadata not found"))?;
+
+        auth.to_str().ok()
+            .and_then(bearer_token)
+            .ok_or_else(|| Status::unauthenticated("invalid token!"))
+    }
+
+    /// Logger of the call, with the same keys as logger of REST request
+    pub fn logger(&self, method: &'static str, user_id: Option<Uuid>) -> RequestLogger {
+        RequestLogger(self.logger.new(slog::o!(
+            "request_id" => Uuid::new_v4().to_string(),
+            "method" => "gRPC",
+            "route" => method,
+            "user_id" => user_id.map(|id| id.to_string()),
+        )))
+    }
+}
+
+/// All services of gRPC api
+pub fn router(state: GrpcState) -> Router {
+    Server::builder()
+        .add_service(UserServiceServer::new(UserGrpc::new(state.clone())))
+        .add_service(TodoListServiceServer::new(TodoListGrpc::new(state.clone())))
+        .add_service(TaskServiceServer::new(TaskGrpc::new(state)))
+}
+
+/// Binds the address and serves gRPC api in background task
+pub fn spawn(state: GrpcState, addr: std::net::SocketAddr) -> std::io::Result<tokio::task::JoinHandle<()>> {
+    let incoming = TcpIncoming::bind(addr)?;
+    let logger = state.logger.clone();
+
+    Ok(tokio::spawn(async move {
+        if let Err(e) = router(state).serve_with_incoming(incoming).await {
+            slog::error!(logger, "gRPC server failed"; "error" => %e);
+        }
+    }))
+}
+
+impl From<ServiceError> for Status {
+    fn from(e: ServiceError) -> Self {
+        Status::from(&e)
+    }
+}
+
+impl From<&ServiceError> for Status {
+    fn from(e: &ServiceError) -> Self {
+        let code = match e.status_code {
+            StatusCode::BadRequest | StatusCode::UnprocessableEntity => Code::InvalidArgument,
+            StatusCode::Unauthorized => Code::Unauthenticated,
+            StatusCode::Forbidden => Code::PermissionDenied,
+            StatusCode::NotFound => Code::NotFound,
+            StatusCode::Conflict => Code::AlreadyExists,
+            StatusCode::PreconditionFailed => Code::FailedPrecondition,
+            StatusCode::PayloadTooLarge | StatusCode::TooManyRequests => Code::ResourceExhausted,
+            StatusCode::InternalError => Code::Internal,
+        };
+        let message = e.detail.clone().unwrap_or_else(|| e.status_code.to_string());
+
+        Status::new(code, message)
+    }
+}
+
+/// Error of handlers, which return `actix_web::Error`, e.g. throttled login
+pub(crate) fn handler_status(e: actix_web::Error) -> Status {
+    if let Some(e) = e.as_error::<TooManyRequestsError>() {
+        return Status::from(&e.error);
+    }
+
+    e.as_error::<ServiceError>().map_or_else(|| Status::internal(e.to_string()), Status::from)
+}
+
+/// Validation errors of the request, messages by field are joined into status message
+pub(crate) fn validation_status(errors: ValidationErrors) -> Status {
+    let error = RequestValidationError::from(errors);
+
+    let mut fields: Vec<String> = error.fields.iter()
+        .map(|(field, messages)| format!("{field}: {}", messages.join(", ")))
+        .collect();
+    fields.sort();
+
+    Status::invalid_argument(format!("{}: {}", error.detail.unwrap_or_default(), fields.join("; ")))
+}
+
+pub(crate) fn parse_id(field: &str, id: &str) -> Result<Uuid, Status> {
+    id.parse().map_err(|_| Status::invalid_argument(format!("{field} must be uuid")))
+}
+
+pub(crate) fn parse_time(field: &str, time: Option<String>) -> Result<Option<DateTime<Utc>>, Status> {
+    time.map(|time| DateTime::parse_from_rfc3339(&time).map(|time| time.with_timezone(&Utc)))
+        .transpose()
+        .map_err(|_| Status::invalid_argument(format!("{field} must be RFC 3339 time")))
+}
@@ -0,0 +1,167 @@
+use actix_web::web;
+use tonic::{
+    Request,
+    Response,
+    Status
+};
+use validator::Validate;
+
+use crate::{
+    handlers,
+    middlewares::{
+        ValidatedJson,
+        ValidatedQuery
+    },
+    models::{
+        FullTaskInfo,
+        MoveTask,
+        NewTask,
+        TaskDetails,
+        TaskPosition,
+        TaskRange,
+        UpdateTask
+    }
+};
+
+use super::{
+    parse_id,
+    parse_time,
+    proto::{
+        self,
+        task_position::Position,
+        task_service_server::TaskService
+    },
+    validation_status,
+    GrpcState
+};
+
+pub struct TaskGrpc {
+    state: GrpcState,
+}
+
+impl TaskGrpc {
+    pub fn new(state: GrpcState) -> Self {
+        Self { state }
+    }
+}
+
+#[tonic::async_trait]
+impl TaskService for TaskGrpc {
+    async fn add_task(&self, request: Request<proto::AddTaskRequest>) -> Result<Response<proto::Task>, Status> {
+        let auth = self.state.authorize(&request).await?;
+        let logger = self.state.logger("TaskService/AddTask", Some(auth.user_id));
+        let request = request.into_inner();
+        let new_task = NewTask { description: request.description, position: task_position(request.position)? };
+        new_task.validate().map_err(validation_status)?;
+
+        let id = handlers::new_task(ValidatedJson(new_task), self.state.lists.clone(), self.state.tasks.clone(), self.state.events.clone(), auth.clone(), logger).await?;
+        let id = parse_id("id", &id)?;
+
+        let todo_list_id = self.state.lists.select_todo_list_id(auth.user_id).await?
+            .ok_or_else(|| Status::not_found("TO-DO list not found"))?;
+        let task = self.state.tasks.select_task(todo_list_id, id).await?
+            .ok_or_else(|| Status::not_found("Task not found"))?;
+
+        Ok(Response::new(task.into()))
+    }
+
+    async fn get_tasks(&self, request: Request<proto::GetTasksRequest>) -> Result<Response<proto::TaskList>, Status> {
+        let auth = self.state.authorize(&request).await?;
+
+        let tasks = handlers::get_tasks(self.state.lists.clone(), self.state.tasks.clone(), auth).await?.into_inner();
+
+        Ok(Response::new(task_list(tasks)))
+    }
+
+    async fn get_task_range(&self, request: Request<proto::GetTaskRangeRequest>) -> Result<Response<proto::TaskList>, Status> {
+        let auth = self.state.authorize(&request).await?;
+        let request = request.into_inner();
+        let range = TaskRange { offset: request.offset, count: request.count };
+        range.validate().map_err(validation_status)?;
+
+        let tasks = handlers::get_tasks_range(ValidatedQuery(range), self.state.lists.clone(), self.state.tasks.clone(), auth).await?.into_inner();
+
+        Ok(Response::new(task_list(tasks)))
+    }
+
+    async fn update_task(&self, request: Request<proto::UpdateTaskRequest>) -> Result<Response<proto::Task>, Status> {
+        let auth = self.state.authorize(&request).await?;
+        let logger = self.state.logger("TaskService/UpdateTask", Some(auth.user_id));
+        let request = request.into_inner();
+        let id = parse_id("id", &request.id)?;
+        let task_info = UpdateTask { description: request.description };
+        task_info.validate().map_err(validation_status)?;
+
+        let task = handlers::update_task(web::Path::from(id), ValidatedJson(task_info), self.state.lists.clone(), self.state.tasks.clone(), self.state.events.clone(), auth, logger).await?;
+
+        Ok(Response::new(task.into_inner().into()))
+    }
+
+    async fn update_task_details(&self, request: Request<proto::UpdateTaskDetailsRequest>) -> Result<Response<proto::Task>, Status> {
+        let auth = self.state.authorize(&request).await?;
+        let logger = self.state.logger("TaskService/UpdateTaskDetails", Some(auth.user_id));
+        let request = request.into_inner();
+        let id = parse_id("id", &request.id)?;
+        let details = TaskDetails {
+            due_at: parse_time("due_at", request.due_at)?,
+            completed_at: parse_time("completed_at", request.completed_at)?,
+            priority: request.priority,
+            rrule: request.rrule,
+        };
+        details.validate().map_err(validation_status)?;
+
+        let task = handlers::update_task_details(web::Path::from(id), ValidatedJson(details), self.state.lists.clone(), self.state.tasks.clone(), self.state.events.clone(), auth, logger).await?;
+
+        Ok(Response::new(task.into_inner().into()))
+    }
+
+    async fn move_task(&self, request: Request<proto::MoveTaskRequest>) -> Result<Response<proto::Task>, Status> {
+        let auth = self.state.authorize(&request).await?;
+        let logger = self.state.logger("TaskService/MoveTask", Some(auth.user_id));
+        let request = request.into_inner();
+        let id = parse_id("id", &request.id)?;
+        let move_info = MoveTask { position: task_position(request.position)? };
+
+        let task = handlers::move_task(web::Path::from(id), ValidatedJson(move_info), self.state.lists.clone(), self.state.tasks.clone(), self.state.events.clone(), auth, logger).await?;
+
+        Ok(Response::new(task.into_inner().into()))
+    }
+
+    async fn delete_task(&self, request: Request<proto::DeleteTaskRequest>) -> Result<Response<proto::Task>, Status> {
+        let auth = self.state.authorize(&request).await?;
+        let logger = self.state.logger("TaskService/DeleteTask", Some(auth.user_id));
+        let id = parse_id("id", &request.into_inner().id)?;
+
+        let task = handlers::delete_tasks(web::Path::from(id), self.state.lists.clone(), self.state.tasks.clone(), self.state.events.clone(), auth, logger).await?;
+
+        Ok(Response::new(task.into_inner().into()))
+    }
+}
+
+/// Position of the request, the end if it's not set
+fn task_position(position: Option<proto::TaskPosition>) -> Result<TaskPosition, Status> {
+    Ok(match position.and_then(|position| position.position) {
+        None | Some(Position::End(_)) => TaskPosition::End,
+        Some(Position::After(task_id)) => TaskPosition::After { task_id: parse_id("position.after", &task_id)? },
+        Some(Position::Before(task_id)) => TaskPosition::Before { task_id: parse_id("position.before", &task_id)? },
+    })
+}
+
+fn task_list(tasks: Vec<FullTaskInfo>) -> proto::TaskList {
+    proto::TaskList { tasks: tasks.into_iter().map(proto::Task::from).collect() }
+}
+
+impl From<FullTaskInfo> for proto::Task {
+    fn from(task: FullTaskInfo) -> Self {
+        Self {
+            id: task.id.to_string(),
+            todo_list_id: task.todo_list_id.to_string(),
+            description: task.description,
+            order: task.order,
+            due_at: task.due_at.map(|time| time.to_rfc3339()),
+            completed_at: task.completed_at.map(|time| time.to_rfc3339()),
+            priority: task.priority,
+            rrule: task.rrule,
+        }
+    }
+}
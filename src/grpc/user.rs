@@ -0,0 +1,112 @@
+use tonic::{
+    Request,
+    Response,
+    Status
+};
+use validator::Validate;
+
+use crate::{
+    handlers::{
+        self,
+        LoginResult
+    },
+    middlewares::ValidatedJson,
+    models::{
+        Login,
+        NewUser,
+        TwoFactorLogin
+    }
+};
+
+use super::{
+    proto::{
+        self,
+        login_response,
+        user_service_server::UserService
+    },
+    handler_status,
+    validation_status,
+    GrpcState
+};
+
+pub struct UserGrpc {
+    state: GrpcState,
+}
+
+impl UserGrpc {
+    pub fn new(state: GrpcState) -> Self {
+        Self { state }
+    }
+}
+
+#[tonic::async_trait]
+impl UserService for UserGrpc {
+    async fn register(&self, request: Request<proto::RegisterRequest>) -> Result<Response<proto::RegisterResponse>, Status> {
+        let logger = self.state.logger("UserService/Register", None);
+        let request = request.into_inner();
+        let new_user = NewUser { login: request.login, password: request.password };
+        new_user.validate().map_err(validation_status)?;
+
+        let user_id = handlers::register(self.state.users.clone(), ValidatedJson(new_user), logger).await?;
+
+        Ok(Response::new(proto::RegisterResponse { user_id }))
+    }
+
+    /// Login is throttled by ip of the peer, as login of REST api
+    async fn login(&self, request: Request<proto::LoginRequest>) -> Result<Response<proto::LoginResponse>, Status> {
+        let logger = self.state.logger("UserService/Login", None);
+        let ip = peer_ip(&request)?;
+        let request = request.into_inner();
+        let login_info = Login { login: request.login, password: request.password };
+        login_info.validate().map_err(validation_status)?;
+
+        let user_id = handlers::authenticate(self.state.users.as_ref(), &self.state.rate_limits.login, &ip, &login_info, &logger).await
+            .map_err(handler_status)?;
+
+        let result = match handlers::login_result(self.state.users.as_ref(), self.state.two_factor.as_ref(), &self.state.jwt_keys, user_id, &logger).await? {
+            LoginResult::Token(token) => login_response::Result::Token(token),
+            LoginResult::TwoFactor(challenge) => login_response::Result::TwoFactor(proto::TwoFactorChallenge {
+                challenge_token: challenge.challenge_token,
+                expires_in: challenge.expires_in,
+            }),
+        };
+
+        Ok(Response::new(proto::LoginResponse { result: Some(result) }))
+    }
+
+    /// Code failures are throttled by ip of the peer, as 2FA login of REST api
+    async fn login_two_factor(&self, request: Request<proto::LoginTwoFactorRequest>) -> Result<Response<proto::LoginTwoFactorResponse>, Status> {
+        let logger = self.state.logger("UserService/LoginTwoFactor", None);
+        let ip = peer_ip(&request)?;
+        let request = request.into_inner();
+        let login_info = TwoFactorLogin { challenge_token: request.challenge_token, code: request.code };
+        login_info.validate().map_err(validation_status)?;
+
+        let token = handlers::two_factor_login_token(self.state.users.as_ref(), self.state.two_factor.as_ref(), &self.state.rate_limits, &self.state.jwt_keys, &login_info, &ip, &logger).await
+            .map_err(handler_status)?;
+
+        Ok(Response::new(proto::LoginTwoFactorResponse { token }))
+    }
+
+    async fn get_profile(&self, request: Request<proto::GetProfileRequest>) -> Result<Response<proto::UserProfile>, Status> {
+        let auth = self.state.authorize(&request).await?;
+
+        let profile = handlers::get_user_profile(self.state.users.clone(), self.state.two_factor.clone(), auth).await?.into_inner();
+
+        Ok(Response::new(proto::UserProfile {
+            id: profile.id.to_string(),
+            login: profile.login,
+            email: profile.email,
+            email_verified: profile.email_verified,
+            two_factor_enabled: profile.two_factor_enabled,
+        }))
+    }
+}
+
+/// Ip of the peer for login throttle. Calls without peer address are rejected,
+/// otherwise all of them would share one ip bucket
+fn peer_ip<T>(request: &Request<T>) -> Result<String, Status> {
+    request.remote_addr()
+        .map(|addr| addr.ip().to_string())
+        .ok_or_else(|| Status::failed_precondition("Address of the peer is unknown"))
+}
@@ -0,0 +1,34 @@
+use actix_web::{
+    web,
+    Result
+};
+
+use crate::{
+    models::*,
+    middlewares::{
+        AdminAuth,
+        RequestLogger
+    },
+    utils::log_level::{
+        LevelConfig,
+        LogDrain,
+        LogLevelControl
+    }
+};
+
+pub async fn get_log_levels(level_control: web::Data<LogLevelControl>, _admin_auth: AdminAuth) -> Result<web::Json<LogLevels>, ServiceError> {
+    Ok(web::Json(LogLevels {
+        console: level_control.get(LogDrain::Console),
+        file: level_control.get(LogDrain::File),
+    }))
+}
+
+pub async fn set_log_level(drain: web::Path<LogDrain>, config: web::Json<LevelConfig>, level_control: web::Data<LogLevelControl>, _admin_auth: AdminAuth, logger: RequestLogger) -> Result<web::Json<LevelConfig>, ServiceError> {
+    let drain = drain.into_inner();
+    let config = config.into_inner();
+
+    level_control.set(drain, config.clone());
+    slog::warn!(logger, "Log level changed"; "drain" => ?drain, "config" => ?config);
+
+    Ok(web::Json(config))
+}
@@ -0,0 +1,144 @@
+use actix_multipart::Multipart;
+use actix_web::{
+    http::header,
+    web,
+    HttpResponse,
+    Result
+};
+use chrono::Utc;
+
+use crate::{
+    models::*,
+    middlewares::{
+        BearerAuth,
+        RequestLogger,
+        ValidatedQuery
+    },
+    db::{
+        AccessTokenRepository,
+        CalendarFeedRepository,
+        IdentityRepository,
+        TaskRepository,
+        TodoListRepository,
+        TwoFactorRepository,
+        UserRepository,
+        WebhookRepository
+    },
+    utils::{
+        backup::{
+            read_backup,
+            write_backup,
+            BACKUP_MAX_SIZE
+        },
+        events::ChangeEvents,
+        upload::{
+            read_form,
+            required_field
+        }
+    }
+};
+
+use super::user_not_found;
+
+/// Archive of everything user owns. Secrets (password, 2FA, tokens, webhook secrets) are not included
+#[allow(clippy::too_many_arguments)]
+pub async fn get_backup(
+    users: web::Data<dyn UserRepository>,
+    two_factor: web::Data<dyn TwoFactorRepository>,
+    lists: web::Data<dyn TodoListRepository>,
+    tasks: web::Data<dyn TaskRepository>,
+    access_tokens: web::Data<dyn AccessTokenRepository>,
+    webhooks: web::Data<dyn WebhookRepository>,
+    identities: web::Data<dyn IdentityRepository>,
+    feeds: web::Data<dyn CalendarFeedRepository>,
+    bearer_auth: BearerAuth
+) -> Result<HttpResponse, ServiceError> {
+    bearer_auth.require_login_token()?;
+    let user_id = bearer_auth.user_id;
+
+    let login = users.select_user_login(user_id).await?
+        .ok_or(user_not_found())?;
+    let email = users.select_user_email(user_id).await?;
+
+    let account = BackupAccount {
+        login: login.clone(),
+        email_verified: email.as_ref().is_some_and(|email| email.verified),
+        email: email.map(|email| email.email),
+        two_factor_enabled: two_factor.select_totp_secret(user_id).await?.is_some_and(|totp| totp.enabled),
+        calendar_feed: feeds.select_calendar_feed(user_id).await?,
+        access_tokens: access_tokens.select_access_tokens(user_id).await?,
+        webhooks: webhooks.select_webhooks(user_id).await?,
+        identities: identities.select_identities(user_id).await?,
+    };
+
+    let todo_list = lists.select_todo_list(user_id).await?;
+    let list_tasks = match &todo_list {
+        Some(todo_list) => tasks.select_tasks(todo_list.id).await?,
+        None => Vec::new(),
+    };
+
+    let backup_lists: Vec<BackupList> = todo_list.into_iter()
+        .map(|list| BackupList { id: list.id, name: list.name })
+        .collect();
+    let mut backup_tasks: Vec<BackupTask> = list_tasks.into_iter()
+        .map(|task| BackupTask {
+            id: task.id,
+            list_id: task.todo_list_id,
+            order: task.order,
+            description: task.description,
+            due_at: task.due_at,
+            completed_at: task.completed_at,
+            priority: task.priority,
+            rrule: task.rrule,
+        })
+        .collect();
+    backup_tasks.sort_by_key(|task| task.order);
+
+    let created_at = Utc::now();
+    let user = BackupUser { id: user_id, login: login.clone() };
+    let archive = write_backup(user, &account, &backup_lists, &backup_tasks, created_at);
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/gzip")
+        .insert_header((header::CONTENT_DISPOSITION, format!("attachment; filename=\"todo-list-backup-{login}-{}.tar.gz\"", created_at.format("%Y%m%d"))))
+        .body(archive))
+}
+
+/// Creates list and tasks of the archive with new ids, in the same or another service.
+/// Other data of the archive is informational and is not restored
+pub async fn restore_backup(
+    query: ValidatedQuery<RestoreQuery>,
+    upload: Multipart,
+    lists: web::Data<dyn TodoListRepository>,
+    events: web::Data<ChangeEvents>,
+    bearer_auth: BearerAuth,
+    logger: RequestLogger
+) -> Result<web::Json<RestoreReport>, ServiceError> {
+    bearer_auth.require_login_token()?;
+    let user_id = bearer_auth.user_id;
+
+    let mut fields = read_form(upload, &["file"], BACKUP_MAX_SIZE as usize).await?;
+    let backup = read_backup(&required_field(&mut fields, "file")?)?;
+
+    let list = match backup.list {
+        Some(list) => list,
+        None => return Ok(web::Json(RestoreReport { version: backup.version, list: None, tasks: Vec::new(), deleted_tasks: 0 })),
+    };
+
+    let contents = backup.tasks.iter().map(|task| TaskContent { description: task.description.clone(), details: task.details() }).collect();
+    let mut outbox = Outbox::new(user_id);
+
+    let restored = lists.restore_todo_list(user_id, list.name, contents, query.replace, &mut outbox).await?
+        .ok_or(ServiceError { status_code: StatusCode::Conflict, detail: Some("You have already TO-DO list, restore with replace=true to replace it".to_string()) })?;
+
+    slog::info!(logger, "Backup restored"; "todo_list_id" => %restored.list.id, "tasks" => restored.tasks.len(), "version" => backup.version);
+
+    events.publish(outbox);
+
+    Ok(web::Json(RestoreReport {
+        version: backup.version,
+        list: Some(RestoredId { old: list.id, new: restored.list.id }),
+        tasks: backup.tasks.iter().zip(&restored.tasks).map(|(task, restored)| RestoredId { old: task.id, new: restored.id }).collect(),
+        deleted_tasks: restored.replaced.map_or(0, |replaced| replaced.deleted_tasks),
+    }))
+}
@@ -0,0 +1,500 @@
+use actix_web::{
+    http::header,
+    web,
+    HttpRequest,
+    HttpResponse,
+    Result
+};
+use chrono::Utc;
+use sha2::{
+    Digest,
+    Sha256
+};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::{
+    models::*,
+    middlewares::{
+        DavAuth,
+        RequestLogger
+    },
+    db::{
+        Storage,
+        TaskRepository,
+        TodoListRepository,
+        UserRepository
+    },
+    utils::{
+        dav::*,
+        events::ChangeEvents,
+        ical::{
+            self,
+            TodoError
+        }
+    }
+};
+
+/// Paths of the DAV tree, every user sees own principal and the only calendar of own list
+const DAV_ROOT: &str = "/dav/";
+const PRINCIPAL: &str = "/dav/principal/";
+const CALENDAR_HOME: &str = "/dav/calendars/";
+
+const DAV_COMPLIANCE: &str = "1, 3, calendar-access";
+const DAV_METHODS: &str = "OPTIONS, GET, HEAD, PUT, DELETE, PROPFIND, REPORT";
+const TASK_CONTENT_TYPE: &str = "text/calendar; charset=utf-8; component=VTODO";
+
+/// Properties returned for `allprop`, the others have to be asked by name
+const ALL_PROPS: [(&str, &str); 10] = [
+    (DAV_NS, "resourcetype"),
+    (DAV_NS, "displayname"),
+    (DAV_NS, "current-user-principal"),
+    (DAV_NS, "principal-URL"),
+    (CALDAV_NS, "calendar-home-set"),
+    (CALDAV_NS, "supported-calendar-component-set"),
+    (DAV_NS, "supported-report-set"),
+    (CALENDARSERVER_NS, "getctag"),
+    (DAV_NS, "getetag"),
+    (DAV_NS, "getcontenttype"),
+];
+
+/// Task as calendar object resource
+struct TaskResource {
+    task: FullTaskInfo,
+    ics: String,
+    etag: String,
+}
+
+impl TaskResource {
+    fn new(task: FullTaskInfo) -> Self {
+        let ics = ical::task_calendar(&task);
+        let etag = format!("\"{}\"", content_hash(&[&ics]));
+
+        Self { task, ics, etag }
+    }
+}
+
+enum Resource<'a> {
+    Root,
+    Principal,
+    CalendarHome,
+    Calendar { list: &'a FullTodoListInfo, ctag: String },
+    Task(&'a TaskResource),
+}
+
+/// Values of properties depend on the user: principal name and write access
+struct PropContext {
+    login: String,
+    writable: bool,
+}
+
+impl PropContext {
+    async fn new(users: &dyn UserRepository, auth: &DavAuth) -> Result<Self, ServiceError> {
+        let login = users.select_user_login(auth.user_id).await?.unwrap_or_default();
+
+        Ok(Self { login, writable: auth.require_scope(TokenScope::TasksWrite).is_ok() })
+    }
+
+    fn property(&self, resource: &Resource, prop: &PropName) -> Option<String> {
+        let value = match (prop.namespace.as_str(), prop.name.as_str(), resource) {
+            (DAV_NS, "resourcetype", Resource::Principal) => "<d:collection/><d:principal/>".to_string(),
+            (DAV_NS, "resourcetype", Resource::Calendar { .. }) => "<d:collection/><c:calendar/>".to_string(),
+            (DAV_NS, "resourcetype", Resource::Task(_)) => String::new(),
+            (DAV_NS, "resourcetype", _) => "<d:collection/>".to_string(),
+            (DAV_NS, "displayname", Resource::Principal) => escape(&self.login),
+            (DAV_NS, "displayname", Resource::Calendar { list, .. }) => escape(&list.name),
+            (DAV_NS, "current-user-principal", _) => href(PRINCIPAL),
+            (DAV_NS, "principal-URL", Resource::Principal) => href(PRINCIPAL),
+            (CALDAV_NS, "calendar-home-set", Resource::Principal) => href(CALENDAR_HOME),
+            (CALDAV_NS, "supported-calendar-component-set", Resource::Calendar { .. }) => "<c:comp name=\"VTODO\"/>".to_string(),
+            (DAV_NS, "supported-report-set", Resource::Calendar { .. }) => ["calendar-query", "calendar-multiget"].iter()
+                .map(|report| format!("<d:supported-report><d:report><c:{report}/></d:report></d:supported-report>"))
+                .collect(),
+            (DAV_NS, "current-user-privilege-set", Resource::Calendar { .. } | Resource::Task(_)) => self.privileges(),
+            (CALENDARSERVER_NS, "getctag", Resource::Calendar { ctag, .. }) => escape(ctag),
+            (DAV_NS, "getetag", Resource::Task(resource)) => escape(&resource.etag),
+            (DAV_NS, "getcontenttype", Resource::Task(_)) => TASK_CONTENT_TYPE.to_string(),
+            (CALDAV_NS, "calendar-data", Resource::Task(resource)) => escape(&resource.ics),
+            _ => return None,
+        };
+
+        Some(value)
+    }
+
+    fn privileges(&self) -> String {
+        let privileges: &[&str] = match self.writable {
+            true => &["read", "write-content", "bind", "unbind"],
+            false => &["read"],
+        };
+
+        privileges.iter().map(|privilege| format!("<d:privilege><d:{privilege}/></d:privilege>")).collect()
+    }
+
+    /// `207` with asked properties of the resources
+    fn multistatus(&self, resources: &[(String, Resource)], props: &PropRequest) -> HttpResponse {
+        let mut multistatus = MultiStatus::default();
+        self.write_responses(&mut multistatus, resources, props);
+
+        multistatus_response(multistatus)
+    }
+
+    fn write_responses(&self, multistatus: &mut MultiStatus, resources: &[(String, Resource)], props: &PropRequest) {
+        for (href, resource) in resources {
+            let (found, not_found) = match props {
+                PropRequest::All => {
+                    let found = ALL_PROPS.iter()
+                        .map(|(namespace, name)| PropName::new(namespace, name))
+                        .filter_map(|prop| self.property(resource, &prop).map(|value| (prop, value)))
+                        .collect();
+                    (found, Vec::new())
+                },
+                PropRequest::Props(props) => {
+                    let mut found = Vec::new();
+                    let mut not_found = Vec::new();
+
+                    for prop in props {
+                        match self.property(resource, prop) {
+                            Some(value) => found.push((prop.clone(), value)),
+                            None => not_found.push(prop.clone()),
+                        }
+                    }
+                    (found, not_found)
+                },
+            };
+
+            multistatus.response(href, &found, &not_found);
+        }
+    }
+}
+
+fn multistatus_response(multistatus: MultiStatus) -> HttpResponse {
+    HttpResponse::MultiStatus()
+        .content_type(CONTENT_TYPE)
+        .body(multistatus.finish())
+}
+
+/// Service discovery (RFC 6764), clients are sent to the root of the DAV tree
+pub async fn caldav_well_known() -> HttpResponse {
+    HttpResponse::MovedPermanently()
+        .insert_header((header::LOCATION, DAV_ROOT))
+        .finish()
+}
+
+/// Capabilities of the server, allowed without credentials
+pub async fn dav_options() -> HttpResponse {
+    HttpResponse::Ok()
+        .insert_header(("DAV", DAV_COMPLIANCE))
+        .insert_header((header::ALLOW, DAV_METHODS))
+        .finish()
+}
+
+pub async fn propfind_root(req: HttpRequest, body: String, users: web::Data<dyn UserRepository>, auth: DavAuth) -> Result<HttpResponse, ServiceError> {
+    let props = parse_propfind(&body)?;
+    let context = PropContext::new(users.as_ref(), &auth).await?;
+
+    let mut resources = vec![(DAV_ROOT.to_string(), Resource::Root)];
+    if depth(&req) > 0 {
+        resources.push((PRINCIPAL.to_string(), Resource::Principal));
+        resources.push((CALENDAR_HOME.to_string(), Resource::CalendarHome));
+    }
+
+    Ok(context.multistatus(&resources, &props))
+}
+
+pub async fn propfind_principal(body: String, users: web::Data<dyn UserRepository>, auth: DavAuth) -> Result<HttpResponse, ServiceError> {
+    let props = parse_propfind(&body)?;
+    let context = PropContext::new(users.as_ref(), &auth).await?;
+
+    Ok(context.multistatus(&[(PRINCIPAL.to_string(), Resource::Principal)], &props))
+}
+
+pub async fn propfind_calendar_home(req: HttpRequest, body: String, users: web::Data<dyn UserRepository>, lists: web::Data<dyn TodoListRepository>, tasks: web::Data<dyn TaskRepository>, auth: DavAuth) -> Result<HttpResponse, ServiceError> {
+    let props = parse_propfind(&body)?;
+    let context = PropContext::new(users.as_ref(), &auth).await?;
+
+    let list = match depth(&req) {
+        0 => None,
+        _ => lists.select_todo_list(auth.user_id).await?,
+    };
+    let task_resources = match &list {
+        Some(list) => task_resources(tasks.as_ref(), list.id).await?,
+        None => Vec::new(),
+    };
+
+    let mut resources = vec![(CALENDAR_HOME.to_string(), Resource::CalendarHome)];
+    if let Some(list) = &list {
+        resources.push((calendar_href(list.id), Resource::Calendar { list, ctag: ctag(list, &task_resources) }));
+    }
+
+    Ok(context.multistatus(&resources, &props))
+}
+
+pub async fn propfind_calendar(req: HttpRequest, list_id: web::Path<Uuid>, body: String, users: web::Data<dyn UserRepository>, lists: web::Data<dyn TodoListRepository>, tasks: web::Data<dyn TaskRepository>, auth: DavAuth) -> Result<HttpResponse, ServiceError> {
+    let props = parse_propfind(&body)?;
+    let list = user_calendar(lists.as_ref(), &auth, *list_id).await?;
+    let context = PropContext::new(users.as_ref(), &auth).await?;
+
+    let task_resources = task_resources(tasks.as_ref(), list.id).await?;
+
+    let mut resources = vec![(calendar_href(list.id), Resource::Calendar { list: &list, ctag: ctag(&list, &task_resources) })];
+    if depth(&req) > 0 {
+        resources.extend(task_resources.iter().map(|resource| (task_href(list.id, resource.task.id), Resource::Task(resource))));
+    }
+
+    Ok(context.multistatus(&resources, &props))
+}
+
+pub async fn propfind_task(path: web::Path<(Uuid, String)>, body: String, users: web::Data<dyn UserRepository>, lists: web::Data<dyn TodoListRepository>, tasks: web::Data<dyn TaskRepository>, auth: DavAuth) -> Result<HttpResponse, ServiceError> {
+    let props = parse_propfind(&body)?;
+    let (list_id, name) = path.into_inner();
+    let resource = task_resource(lists.as_ref(), tasks.as_ref(), &auth, list_id, &name).await?
+        .ok_or(task_not_found())?;
+    let context = PropContext::new(users.as_ref(), &auth).await?;
+
+    Ok(context.multistatus(&[(task_href(list_id, resource.task.id), Resource::Task(&resource))], &props))
+}
+
+/// `calendar-query` returns all tasks, `calendar-multiget` the asked ones
+pub async fn report_calendar(list_id: web::Path<Uuid>, body: String, users: web::Data<dyn UserRepository>, lists: web::Data<dyn TodoListRepository>, tasks: web::Data<dyn TaskRepository>, auth: DavAuth) -> Result<HttpResponse, ServiceError> {
+    let report = parse_report(&body)?;
+    let list = user_calendar(lists.as_ref(), &auth, *list_id).await?;
+    let context = PropContext::new(users.as_ref(), &auth).await?;
+
+    let task_resources = task_resources(tasks.as_ref(), list.id).await?;
+
+    match report {
+        Report::CalendarQuery { props, todos } => {
+            let resources: Vec<_> = task_resources.iter()
+                .filter(|_| todos)
+                .map(|resource| (task_href(list.id, resource.task.id), Resource::Task(resource)))
+                .collect();
+
+            Ok(context.multistatus(&resources, &props))
+        },
+        Report::CalendarMultiget { props, hrefs } => {
+            let mut multistatus = MultiStatus::default();
+
+            for href in hrefs {
+                let resource = href_task_id(&href, list.id)
+                    .and_then(|task_id| task_resources.iter().find(|resource| resource.task.id == task_id));
+
+                match resource {
+                    Some(resource) => context.write_responses(&mut multistatus, &[(href, Resource::Task(resource))], &props),
+                    None => { multistatus.missing(&href); },
+                }
+            }
+
+            Ok(multistatus_response(multistatus))
+        },
+    }
+}
+
+pub async fn get_task_resource(path: web::Path<(Uuid, String)>, lists: web::Data<dyn TodoListRepository>, tasks: web::Data<dyn TaskRepository>, auth: DavAuth) -> Result<HttpResponse, ServiceError> {
+    let (list_id, name) = path.into_inner();
+    let resource = task_resource(lists.as_ref(), tasks.as_ref(), &auth, list_id, &name).await?
+        .ok_or(task_not_found())?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(TASK_CONTENT_TYPE)
+        .insert_header((header::ETAG, resource.etag))
+        .body(resource.ics))
+}
+
+/// Creates task with the id from resource name or changes its text and details. Stored resource differs
+/// from the sent one (metadata of the client is dropped), so response has no ETag and client reads the task again
+pub async fn put_task_resource(
+    req: HttpRequest,
+    path: web::Path<(Uuid, String)>,
+    body: String,
+    storage: web::Data<Storage>,
+    auth: DavAuth,
+    logger: RequestLogger
+) -> Result<HttpResponse> {
+    auth.require_scope(TokenScope::TasksWrite)?;
+
+    let Storage { lists, tasks, events, .. } = storage.as_ref();
+    let (list_id, name) = path.into_inner();
+    let list = user_calendar(lists.as_ref(), &auth, list_id).await?;
+
+    let task_id = resource_task_id(&name)
+        .ok_or(ServiceError { status_code: StatusCode::Forbidden, detail: Some("Resource name must be UUID of the task with .ics extension".to_string()) })?;
+
+    let todo = match ical::parse_todo(&body, Utc::now()) {
+        Ok(todo) => todo,
+        Err(TodoError::Invalid(detail)) => return Err(ServiceError { status_code: StatusCode::BadRequest, detail: Some(detail) }.into()),
+        Err(TodoError::Unsupported { precondition, detail }) => {
+            slog::info!(logger, "Calendar object of CalDAV client is not supported"; "task_id" => %task_id, "detail" => &detail);
+
+            return Ok(HttpResponse::Forbidden()
+                .content_type(CONTENT_TYPE)
+                .body(precondition_error(precondition, &detail)));
+        },
+    };
+
+    let update = UpdateTask { description: todo.description };
+    update.validate().map_err(RequestValidationError::from)?;
+
+    let current = tasks.select_task(list.id, task_id).await?.map(TaskResource::new);
+    check_preconditions(&req, current.as_ref())?;
+
+    let mut outbox = Outbox::new(auth.user_id);
+
+    // description and details of the resource are one change
+    if current.is_some() {
+        tasks.replace_task(list.id, task_id, update.description, todo.details, &mut outbox).await?
+            .ok_or(task_not_found())?;
+
+        slog::info!(logger, "Task updated by CalDAV client"; "task_id" => %task_id);
+        events.publish(outbox);
+
+        return Ok(HttpResponse::NoContent().finish());
+    }
+
+    tasks.insert_task_with_id(list.id, task_id, update.description, todo.details, &mut outbox).await?
+        .ok_or(ServiceError { status_code: StatusCode::Conflict, detail: Some("Task with the id already exists".to_string()) })?;
+
+    slog::info!(logger, "Task created by CalDAV client"; "task_id" => %task_id);
+    events.publish(outbox);
+
+    Ok(HttpResponse::Created().finish())
+}
+
+pub async fn delete_task_resource(
+    req: HttpRequest,
+    path: web::Path<(Uuid, String)>,
+    lists: web::Data<dyn TodoListRepository>,
+    tasks: web::Data<dyn TaskRepository>,
+    events: web::Data<ChangeEvents>,
+    auth: DavAuth,
+    logger: RequestLogger
+) -> Result<HttpResponse, ServiceError> {
+    auth.require_scope(TokenScope::TasksWrite)?;
+
+    let (list_id, name) = path.into_inner();
+    let current = task_resource(lists.as_ref(), tasks.as_ref(), &auth, list_id, &name).await?
+        .ok_or(task_not_found())?;
+    check_preconditions(&req, Some(&current))?;
+
+    let mut outbox = Outbox::new(auth.user_id);
+
+    let task = tasks.delete_task(current.task.todo_list_id, current.task.id, &mut outbox).await?
+        .ok_or(task_not_found())?;
+
+    slog::info!(logger, "Task deleted by CalDAV client"; "task_id" => %task.id);
+    events.publish(outbox);
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// List of the user, the only calendar in the home
+async fn user_calendar(lists: &dyn TodoListRepository, auth: &DavAuth, list_id: Uuid) -> Result<FullTodoListInfo, ServiceError> {
+    lists.select_todo_list(auth.user_id).await?
+        .filter(|list| list.id == list_id)
+        .ok_or(ServiceError { status_code: StatusCode::NotFound, detail: Some("Calendar not found".to_string()) })
+}
+
+async fn task_resource(lists: &dyn TodoListRepository, tasks: &dyn TaskRepository, auth: &DavAuth, list_id: Uuid, name: &str) -> Result<Option<TaskResource>, ServiceError> {
+    let list = user_calendar(lists, auth, list_id).await?;
+
+    let task = match resource_task_id(name) {
+        Some(task_id) => tasks.select_task(list.id, task_id).await?,
+        None => None,
+    };
+
+    Ok(task.map(TaskResource::new))
+}
+
+async fn task_resources(tasks: &dyn TaskRepository, list_id: Uuid) -> Result<Vec<TaskResource>, ServiceError> {
+    let mut list_tasks = tasks.select_tasks(list_id).await?;
+    list_tasks.sort_by_key(|task| task.order);
+
+    Ok(list_tasks.into_iter().map(TaskResource::new).collect())
+}
+
+/// `If-Match` and `If-None-Match` of the request, so client doesn't overwrite changes it hasn't seen
+fn check_preconditions(req: &HttpRequest, current: Option<&TaskResource>) -> Result<(), ServiceError> {
+    let matches = |name: header::HeaderName| req.headers().get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.split(',').map(str::trim).any(|etag| current.is_some_and(|current| etag == "*" || etag == current.etag)));
+
+    if matches(header::IF_MATCH) == Some(false) || matches(header::IF_NONE_MATCH) == Some(true) {
+        return Err(ServiceError { status_code: StatusCode::PreconditionFailed, detail: Some("Resource was changed".to_string()) });
+    }
+
+    Ok(())
+}
+
+/// Collection tag changes with any task or the list name
+fn ctag(list: &FullTodoListInfo, resources: &[TaskResource]) -> String {
+    let mut parts = vec![list.name.as_str()];
+    parts.extend(resources.iter().map(|resource| resource.etag.as_str()));
+
+    content_hash(&parts)
+}
+
+fn content_hash(parts: &[&str]) -> String {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+
+    hasher.finalize()[..16].iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// `0` or `1`, `infinity` isn't supported and is handled as `1`
+fn depth(req: &HttpRequest) -> u8 {
+    match req.headers().get("Depth").and_then(|depth| depth.to_str().ok()) {
+        Some("0") => 0,
+        _ => 1,
+    }
+}
+
+fn href(path: &str) -> String {
+    format!("<d:href>{}</d:href>", escape(path))
+}
+
+fn calendar_href(list_id: Uuid) -> String {
+    format!("{CALENDAR_HOME}{list_id}/")
+}
+
+fn task_href(list_id: Uuid, task_id: Uuid) -> String {
+    format!("{}{task_id}.ics", calendar_href(list_id))
+}
+
+/// Task of the resource name `<task id>.ics`
+fn resource_task_id(name: &str) -> Option<Uuid> {
+    name.strip_suffix(".ics").and_then(|id| Uuid::parse_str(id).ok())
+}
+
+/// Task of the href in the calendar, href can be absolute url
+fn href_task_id(href: &str, list_id: Uuid) -> Option<Uuid> {
+    let path = match url::Url::parse(href) {
+        Ok(url) => url.path().to_string(),
+        Err(_) => href.to_string(),
+    };
+
+    path.strip_prefix(&calendar_href(list_id)).and_then(resource_task_id)
+}
+
+fn task_not_found() -> ServiceError {
+    ServiceError { status_code: StatusCode::NotFound, detail: Some("Task not found".to_string()) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn task_of_href() {
+        let list_id = Uuid::new_v4();
+        let task_id = Uuid::new_v4();
+        let href = task_href(list_id, task_id);
+
+        assert_eq!(href_task_id(&href, list_id), Some(task_id));
+        assert_eq!(href_task_id(&format!("https://example.com{href}"), list_id), Some(task_id));
+        assert_eq!(href_task_id(&href, Uuid::new_v4()), None);
+        assert_eq!(href_task_id(&calendar_href(list_id), list_id), None);
+        assert_eq!(resource_task_id(&format!("{}.ics", task_id.to_string().to_uppercase())), Some(task_id));
+    }
+}
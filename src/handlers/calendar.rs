@@ -0,0 +1,78 @@
+use actix_web::{
+    http::header,
+    web,
+    HttpResponse,
+    Result
+};
+use chrono::Utc;
+
+use crate::{
+    models::*,
+    middlewares::{
+        BearerAuth,
+        RequestLogger
+    },
+    db::{
+        CalendarFeedRepository,
+        TaskRepository,
+        TodoListRepository
+    },
+    utils::ical
+};
+
+/// Creates calendar feed or replaces its token, the old feed url stops working
+pub async fn new_calendar_feed(feeds: web::Data<dyn CalendarFeedRepository>, bearer_auth: BearerAuth, logger: RequestLogger) -> Result<web::Json<CreatedCalendarFeed>, ServiceError> {
+    bearer_auth.require_login_token()?;
+
+    let token = generate_calendar_feed_token();
+
+    let info = feeds.upsert_calendar_feed(bearer_auth.user_id, &calendar_feed_token_hash(&token)).await?;
+    slog::info!(logger, "Calendar feed created");
+
+    Ok(web::Json(CreatedCalendarFeed { info, path: calendar_feed_path(&token), token }))
+}
+
+pub async fn get_calendar_feed(feeds: web::Data<dyn CalendarFeedRepository>, bearer_auth: BearerAuth) -> Result<web::Json<CalendarFeedInfo>, ServiceError> {
+    bearer_auth.require_login_token()?;
+
+    let info = feeds.select_calendar_feed(bearer_auth.user_id).await?
+        .ok_or(calendar_feed_not_found())?;
+
+    Ok(web::Json(info))
+}
+
+pub async fn delete_calendar_feed(feeds: web::Data<dyn CalendarFeedRepository>, bearer_auth: BearerAuth, logger: RequestLogger) -> Result<String, ServiceError> {
+    bearer_auth.require_login_token()?;
+
+    if !feeds.delete_calendar_feed(bearer_auth.user_id).await? {
+        return Err(calendar_feed_not_found());
+    }
+
+    slog::info!(logger, "Calendar feed revoked");
+
+    Ok(bearer_auth.user_id.to_string())
+}
+
+/// Feed itself, calendar clients can't send headers, so the token in url is the only credential
+pub async fn get_calendar(token: web::Path<String>, feeds: web::Data<dyn CalendarFeedRepository>, lists: web::Data<dyn TodoListRepository>, tasks: web::Data<dyn TaskRepository>) -> Result<HttpResponse, ServiceError> {
+    let user_id = feeds.use_calendar_feed(&calendar_feed_token_hash(&token)).await?
+        .ok_or(calendar_feed_not_found())?;
+
+    let list = lists.select_todo_list(user_id).await?;
+
+    let mut list_tasks = match &list {
+        Some(list) => tasks.select_tasks(list.id).await?,
+        None => Vec::new(),
+    };
+    list_tasks.sort_by_key(|task| task.order);
+
+    Ok(HttpResponse::Ok()
+        .content_type(ical::CONTENT_TYPE)
+        // feed url is a credential, it must not be kept by shared caches
+        .insert_header((header::CACHE_CONTROL, "private, no-cache"))
+        .body(ical::tasks_calendar(list.as_ref(), &list_tasks, Utc::now())))
+}
+
+fn calendar_feed_not_found() -> ServiceError {
+    ServiceError { status_code: StatusCode::NotFound, detail: Some("Calendar feed not found".to_string()) }
+}
@@ -0,0 +1,163 @@
+use actix_web::{
+    web,
+    HttpRequest,
+    HttpResponse,
+    Result
+};
+use chrono::{
+    Duration,
+    Utc
+};
+use uuid::Uuid;
+
+use super::user::TOO_MANY_ATTEMPTS;
+use crate::{
+    models::*,
+    middlewares::{
+        BearerAuth,
+        RequestLogger,
+        ValidatedJson
+    },
+    db::{
+        EmailTokenRepository,
+        UserRepository
+    },
+    utils::{
+        mail::{
+            Mail,
+            Mailer
+        },
+        rate_limit::RateLimits
+    }
+};
+
+const VERIFY_EMAIL_TOKEN_LIFETIME_HOURS: i64 = 24;
+const RESET_PASSWORD_TOKEN_LIFETIME_MINUTES: i64 = 60;
+
+/// Sets not verified email and sends verification token to it
+pub async fn set_email(
+    users: web::Data<dyn UserRepository>,
+    email_tokens: web::Data<dyn EmailTokenRepository>,
+    mailer: web::Data<dyn Mailer>,
+    bearer_auth: BearerAuth,
+    new_email: ValidatedJson<NewEmail>,
+    logger: RequestLogger
+) -> Result<HttpResponse, ServiceError> {
+    bearer_auth.require_login_token()?;
+
+    let email = normalize_email(&new_email.email);
+    users.update_user_email(bearer_auth.user_id, &email).await?;
+
+    let token = issue_email_token(email_tokens.as_ref(), bearer_auth.user_id, EmailTokenPurpose::VerifyEmail, &email, Duration::hours(VERIFY_EMAIL_TOKEN_LIFETIME_HOURS)).await?;
+
+    let mail = Mail {
+        to: email,
+        subject: "Confirm your email".to_string(),
+        body: format!("Your email verification token: {token}\r\n\r\nThe token expires in {VERIFY_EMAIL_TOKEN_LIFETIME_HOURS} hours."),
+    };
+
+    if let Err(e) = mailer.send(&mail).await {
+        slog::error!(logger, "Verification mail not sent"; "error" => %e);
+        return Err(ServiceError { status_code: StatusCode::InternalError, detail: Some("Mail could not be sent".to_string()) });
+    }
+
+    slog::info!(logger, "Email set, verification mail sent");
+
+    Ok(HttpResponse::Accepted().finish())
+}
+
+/// Token from the verification mail, email must not be changed after the mail was sent
+pub async fn verify_email(
+    users: web::Data<dyn UserRepository>,
+    email_tokens: web::Data<dyn EmailTokenRepository>,
+    token: ValidatedJson<EmailToken>,
+    logger: RequestLogger
+) -> Result<String, ServiceError> {
+    let owner = email_tokens.use_email_token(&email_token_hash(&token.token), EmailTokenPurpose::VerifyEmail).await?
+        .ok_or(invalid_token())?;
+
+    if !users.verify_user_email(owner.user_id, &owner.email).await? {
+        return Err(ServiceError { status_code: StatusCode::Conflict, detail: Some("Email was changed or is verified by other user".to_string()) });
+    }
+
+    slog::info!(logger, "Email verified"; "email_user_id" => %owner.user_id);
+
+    Ok(owner.user_id.to_string())
+}
+
+/// Sends reset token to the verified email. Response is `202` whether user with the email exists or not,
+/// lookup and mail are done after response, so response time doesn't reveal it either
+pub async fn forgot_password(
+    users: web::Data<dyn UserRepository>,
+    email_tokens: web::Data<dyn EmailTokenRepository>,
+    mailer: web::Data<dyn Mailer>,
+    rate_limits: web::Data<RateLimits>,
+    forgot: ValidatedJson<ForgotPassword>,
+    req: HttpRequest,
+    logger: RequestLogger
+) -> Result<HttpResponse> {
+    let email = normalize_email(&forgot.email);
+
+    // every request is an attempt, mails to one address are limited like logins of one account
+    let throttle = &rate_limits.login;
+    let ip = throttle.client_ip(&req);
+    if let Err(retry_after) = throttle.check(&ip, &format!("forgot:{email}")) {
+        slog::warn!(logger, "Password reset request rejected"; "ip" => &ip, "retry_after" => retry_after.as_secs());
+        return Err(TooManyRequestsError::new(TOO_MANY_ATTEMPTS, retry_after).into());
+    }
+
+    let logger = logger.0.clone();
+    actix_web::rt::spawn(async move {
+        if let Err(e) = send_reset_token(users.as_ref(), email_tokens.as_ref(), mailer.as_ref(), email).await {
+            slog::error!(logger, "Password reset mail not sent"; "error" => %e);
+        }
+    });
+
+    Ok(HttpResponse::Accepted().finish())
+}
+
+async fn send_reset_token(users: &dyn UserRepository, email_tokens: &dyn EmailTokenRepository, mailer: &dyn Mailer, email: String) -> Result<(), ServiceError> {
+    let user_id = match users.select_user_id_by_email(&email).await? {
+        Some(user_id) => user_id,
+        None => return Ok(()),
+    };
+
+    let token = issue_email_token(email_tokens, user_id, EmailTokenPurpose::ResetPassword, &email, Duration::minutes(RESET_PASSWORD_TOKEN_LIFETIME_MINUTES)).await?;
+
+    mailer.send(&Mail {
+        to: email,
+        subject: "Password reset".to_string(),
+        body: format!("Your password reset token: {token}\r\n\r\nThe token expires in {RESET_PASSWORD_TOKEN_LIFETIME_MINUTES} minutes. \
+            If you didn't request password reset, ignore this mail."),
+    }).await
+        .map_err(|e| ServiceError { status_code: StatusCode::InternalError, detail: Some(e.to_string()) })
+}
+
+/// Sets new password with the token from reset mail, all sessions of the user are revoked
+pub async fn reset_password(
+    users: web::Data<dyn UserRepository>,
+    email_tokens: web::Data<dyn EmailTokenRepository>,
+    reset: ValidatedJson<ResetPassword>,
+    logger: RequestLogger
+) -> Result<String, ServiceError> {
+    let owner = email_tokens.use_email_token(&email_token_hash(&reset.token), EmailTokenPurpose::ResetPassword).await?
+        .ok_or(invalid_token())?;
+
+    users.reset_user_password(owner.user_id, &reset.new_password).await?;
+
+    slog::info!(logger, "Password reset"; "reset_user_id" => %owner.user_id);
+
+    Ok(owner.user_id.to_string())
+}
+
+/// Returns plaintext token, only its hash is stored
+async fn issue_email_token(email_tokens: &dyn EmailTokenRepository, user_id: Uuid, purpose: EmailTokenPurpose, email: &str, lifetime: Duration) -> Result<String, ServiceError> {
+    let token = generate_email_token();
+    email_tokens.insert_email_token(user_id, &email_token_hash(&token), purpose, email, Utc::now() + lifetime).await?;
+
+    Ok(token)
+}
+
+fn invalid_token() -> ServiceError {
+    ServiceError { status_code: StatusCode::BadRequest, detail: Some("Invalid or expired token".to_string()) }
+}
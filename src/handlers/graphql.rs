@@ -0,0 +1,206 @@
+use std::{
+    env,
+    str::FromStr
+};
+
+use actix_web::{
+    http::header,
+    web,
+    FromRequest,
+    HttpRequest,
+    HttpResponse,
+    Result
+};
+use actix_ws::{
+    CloseCode,
+    CloseReason,
+    Message
+};
+use async_graphql::http::{
+    GraphiQLSource,
+    WebSocket,
+    WebSocketProtocols,
+    WsMessage
+};
+use futures::{
+    future,
+    StreamExt
+};
+use tokio::sync::oneshot;
+
+use crate::{
+    db::{
+        AccessTokenRepository,
+        TaskRepository,
+        TodoListRepository,
+        UserRepository
+    },
+    graphql::{
+        service_error,
+        GraphqlContext,
+        TodoSchema
+    },
+    middlewares::{
+        bearer_token,
+        BearerAuth,
+        RequestLogger,
+        TokenCheck
+    },
+    models::*,
+    utils::{
+        events::ChangeEvents,
+        jwt::JwtKeys
+    }
+};
+
+const TODO_SERVICE_GRAPHIQL_ENV: &str = "TODO_SERVICE_GRAPHIQL";
+
+/// Query or mutation, errors of the request are in `errors` of the response
+pub async fn graphql(schema: web::Data<TodoSchema>, request: web::Json<async_graphql::Request>, context: GraphqlContext) -> web::Json<async_graphql::Response> {
+    let mut request = request.into_inner();
+    request.data = context.into_data();
+
+    web::Json(schema.execute(request).await)
+}
+
+/// GraphiQL page for development, enabled by `TODO_SERVICE_GRAPHIQL=true`
+pub async fn graphiql() -> Result<HttpResponse, ServiceError> {
+    if env::var(TODO_SERVICE_GRAPHIQL_ENV).ok().as_deref() != Some("true") {
+        return Err(ServiceError { status_code: StatusCode::NotFound, detail: Some("GraphiQL is disabled".to_string()) });
+    }
+
+    let html = GraphiQLSource::build()
+        .endpoint("/graphql")
+        .subscription_endpoint("/graphql/ws")
+        .finish();
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(html))
+}
+
+/// Subscriptions over WebSocket (`graphql-transport-ws` or `graphql-ws` protocol). The user is authorized
+/// by `Authorization` header of the upgrade request, or by `Authorization` of `connection_init` payload
+/// for clients which can't set headers (browsers). The token is checked again every `TokenCheck::interval`,
+/// the socket is closed with 4401 when it becomes invalid and with 4408 without `connection_init` in the interval
+#[allow(clippy::too_many_arguments)]
+pub async fn graphql_ws(
+    req: HttpRequest,
+    payload: web::Payload,
+    schema: web::Data<TodoSchema>,
+    users: web::Data<dyn UserRepository>,
+    lists: web::Data<dyn TodoListRepository>,
+    tasks: web::Data<dyn TaskRepository>,
+    events: web::Data<ChangeEvents>,
+    access_tokens: web::Data<dyn AccessTokenRepository>,
+    jwt_keys: web::Data<JwtKeys>,
+    token_check: web::Data<TokenCheck>,
+    logger: RequestLogger
+) -> Result<HttpResponse> {
+    let protocol = req.headers()
+        .get(header::SEC_WEBSOCKET_PROTOCOL)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(',').find_map(|protocol| WebSocketProtocols::from_str(protocol.trim()).ok()))
+        .ok_or(ServiceError { status_code: StatusCode::BadRequest, detail: Some("Unsupported Sec-WebSocket-Protocol".to_string()) })?;
+
+    // token of the header is rejected before upgrade
+    let header_auth = match req.headers().get(header::AUTHORIZATION) {
+        Some(value) => {
+            let auth = BearerAuth::extract(&req).await?;
+            let token = value.to_str().ok().and_then(bearer_token).unwrap_or_default().to_string();
+            Some((token, auth))
+        },
+        None => None,
+    };
+
+    let (mut response, mut session, messages) = actix_ws::handle(&req, payload)?;
+    response.headers_mut().insert(header::SEC_WEBSOCKET_PROTOCOL, header::HeaderValue::from_static(protocol.sec_websocket_protocol()));
+
+    let pong_session = session.clone();
+    // pings are answered here, the stream ends on close of the client
+    let input = messages
+        .take_while(|message| future::ready(matches!(message, Ok(message) if !matches!(message, Message::Close(_)))))
+        .filter_map(move |message| {
+            let mut session = pong_session.clone();
+            async move {
+                match message {
+                    Ok(Message::Text(text)) => Some(text.into_bytes()),
+                    Ok(Message::Binary(bytes)) => Some(bytes),
+                    Ok(Message::Ping(bytes)) => {
+                        let _ = session.pong(&bytes).await;
+                        None
+                    },
+                    _ => None,
+                }
+            }
+        });
+
+    // token of the connection is checked again after `connection_init`
+    let (token_sender, token_receiver) = oneshot::channel::<String>();
+    let init_users = users.clone();
+    let init_access_tokens = access_tokens.clone();
+    let init_jwt_keys = jwt_keys.clone();
+
+    let output = WebSocket::new(schema.as_ref().clone(), input, protocol)
+        .on_connection_init(move |payload| async move {
+            let (token, auth) = match header_auth {
+                Some(header_auth) => header_auth,
+                None => {
+                    let token = payload.get("Authorization")
+                        .and_then(serde_json::Value::as_str)
+                        .and_then(bearer_token)
+                        .ok_or_else(|| async_graphql::Error::new("Authorization is required"))?
+                        .to_string();
+                    let auth = BearerAuth::from_token(&token, init_access_tokens.as_ref(), init_users.as_ref(), &init_jwt_keys).await
+                        .map_err(service_error)?;
+                    (token, auth)
+                },
+            };
+
+            let _ = token_sender.send(token);
+            Ok(GraphqlContext { users: init_users, lists, tasks, events, auth, logger: logger.0 }.into_data())
+        });
+
+    let close_reason = async move {
+        let token = match tokio::time::timeout(token_check.interval, token_receiver).await {
+            Ok(Ok(token)) => token,
+            // rejected `connection_init` is closed by protocol
+            Ok(Err(_)) => return future::pending().await,
+            Err(_) => return CloseReason { code: CloseCode::Other(4408), description: Some("Connection initialisation timeout".to_string()) },
+        };
+
+        let error = token_check.until_invalid(&token, access_tokens.as_ref(), users.as_ref(), &jwt_keys).await;
+        CloseReason { code: CloseCode::Other(4401), description: error.detail }
+    };
+
+    actix_web::rt::spawn(async move {
+        let forward = async {
+            futures::pin_mut!(output);
+
+            while let Some(message) = output.next().await {
+                match message {
+                    WsMessage::Text(text) => {
+                        if session.text(text).await.is_err() {
+                            return;
+                        }
+                    },
+                    WsMessage::Close(code, description) => {
+                        let _ = session.clone().close(Some(CloseReason { code: CloseCode::from(code), description: Some(description) })).await;
+                        return;
+                    },
+                }
+            }
+
+            let _ = session.clone().close(None).await;
+        };
+
+        let reason = tokio::select! {
+            _ = forward => return,
+            reason = close_reason => reason,
+        };
+
+        let _ = session.close(Some(reason)).await;
+    });
+
+    Ok(response)
+}
@@ -0,0 +1,11 @@
+use actix_web::web;
+
+use crate::{
+    models::*,
+    utils::jwt::JwtKeys
+};
+
+/// Public keys verifying tokens of the service, for other services
+pub async fn get_jwks(jwt_keys: web::Data<JwtKeys>) -> web::Json<JwkSet> {
+    web::Json(jwt_keys.jwks())
+}
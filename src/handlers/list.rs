@@ -1,5 +1,20 @@
-use actix_web::{web, Result};
-use sqlx::PgPool;
+use actix_multipart::Multipart;
+use actix_web::{
+    http::header,
+    web,
+    HttpResponse,
+    Result
+};
+use chrono::{
+    SubsecRound,
+    Utc
+};
+use futures::{
+    future,
+    stream,
+    StreamExt,
+    TryStreamExt
+};
 
 use crate::{
     models::{
@@ -7,53 +22,216 @@ use crate::{
         StatusCode,
         NewTodoList,
         UpdateTodoList,
-        FullTodoListInfo
+        FullTodoListInfo,
+        TaskRange,
+        TokenScope,
+        TaskContent,
+        TaskDetails,
+        Outbox,
+        ExportQuery,
+        ImportQuery,
+        ImportMapping,
+        ImportReport,
+        ImportRejectedError
+    },
+    middlewares::{
+        BearerAuth,
+        RequestLogger,
+        ValidatedJson,
+        ValidatedQuery
     },
-    middlewares::BearerAuth,
     db::{
-        list::{
-            select_todo_list_id,
-            delete_todo_list,
-            update_todo_list,
-            insert_todo_list,
-            select_todo_list
+        TaskRepository,
+        TodoListRepository
+    },
+    utils::{
+        events::ChangeEvents,
+        export::Exporter,
+        import::{
+            parse_items,
+            plan_import
         },
-        task::delete_tasks_by_list_id
+        upload::{
+            read_form,
+            required_field,
+            text
+        }
     }
 };
 
-pub async fn new_list(db_pool: web::Data<PgPool>, new_list_info: web::Json<NewTodoList>, bearer_auth: BearerAuth) -> Result<String, ServiceError> {
-    if let Some(todo_list_id) = select_todo_list_id(bearer_auth.user_id, &**db_pool).await? {
+/// Number of tasks read by one query of export
+const EXPORT_PAGE_SIZE: u32 = 500;
+
+/// Size of the fields of import: the file and mapping
+const IMPORT_FILE_MAX_SIZE: usize = 2 * 1024 * 1024;
+/// Body of import request: the file, mapping and multipart headers
+pub const IMPORT_FORM_MAX_SIZE: usize = IMPORT_FILE_MAX_SIZE + 64 * 1024;
+
+pub async fn new_list(lists: web::Data<dyn TodoListRepository>, events: web::Data<ChangeEvents>, new_list_info: ValidatedJson<NewTodoList>, bearer_auth: BearerAuth, logger: RequestLogger) -> Result<String, ServiceError> {
+    bearer_auth.require_scope(TokenScope::ListsAdmin)?;
+
+    if let Some(todo_list_id) = lists.select_todo_list_id(bearer_auth.user_id).await? {
         return Err(ServiceError { status_code: StatusCode::BadRequest, detail: Some(format!("You have already TO-DO list with id = {todo_list_id}")) })
     }
 
-    let id = insert_todo_list(bearer_auth.user_id, &*new_list_info, &**db_pool).await?;
+    let mut outbox = Outbox::new(bearer_auth.user_id);
+
+    let id = lists.insert_todo_list(bearer_auth.user_id, &*new_list_info, &mut outbox).await?;
+    slog::info!(logger, "TO-DO list created"; "todo_list_id" => %id);
+
+    events.publish(outbox);
         
     Ok(id.to_string())
 }
 
-pub async fn delete_list(db_pool: web::Data<PgPool>, bearer_auth: BearerAuth) -> Result<String, ServiceError> {
-    let todo_list_id = select_todo_list_id(bearer_auth.user_id, &**db_pool).await?
-        .ok_or(ServiceError { status_code: StatusCode::NotFound, detail: Some(format!("TO-DO list not found")) })?;
+pub async fn delete_list(lists: web::Data<dyn TodoListRepository>, events: web::Data<ChangeEvents>, bearer_auth: BearerAuth, logger: RequestLogger) -> Result<String, ServiceError> {
+    let count = delete_user_list(lists.as_ref(), &events, &bearer_auth, &logger).await?;
 
-    let count = delete_tasks_by_list_id(todo_list_id, &*db_pool).await?;
-    delete_todo_list(bearer_auth.user_id, &*db_pool).await?;
-        
     Ok(count.to_string())
 }
 
-pub async fn update_list(db_pool: web::Data<PgPool>, list_info: web::Json<UpdateTodoList>, bearer_auth: BearerAuth) -> Result<String, ServiceError> {
-    let todo_list_id = select_todo_list_id(bearer_auth.user_id, &**db_pool).await?
+/// Deletes list of the user with its tasks, count of deleted tasks is returned
+pub(crate) async fn delete_user_list(lists: &dyn TodoListRepository, events: &ChangeEvents, bearer_auth: &BearerAuth, logger: &RequestLogger) -> Result<i64, ServiceError> {
+    bearer_auth.require_scope(TokenScope::ListsAdmin)?;
+
+    let mut outbox = Outbox::new(bearer_auth.user_id);
+
+    let deleted = lists.delete_todo_list(bearer_auth.user_id, &mut outbox).await?
+        .ok_or(ServiceError { status_code: StatusCode::NotFound, detail: Some(format!("TO-DO list not found")) })?;
+    slog::info!(logger, "TO-DO list deleted"; "todo_list_id" => %deleted.id, "deleted_tasks" => deleted.deleted_tasks);
+
+    events.publish(outbox);
+
+    Ok(deleted.deleted_tasks)
+}
+
+pub async fn update_list(lists: web::Data<dyn TodoListRepository>, events: web::Data<ChangeEvents>, list_info: ValidatedJson<UpdateTodoList>, bearer_auth: BearerAuth, logger: RequestLogger) -> Result<String, ServiceError> {
+    bearer_auth.require_scope(TokenScope::ListsAdmin)?;
+
+    let todo_list_id = lists.select_todo_list_id(bearer_auth.user_id).await?
         .ok_or(ServiceError { status_code: StatusCode::NotFound, detail: Some(format!("TO-DO list not found")) })?;
 
-    update_todo_list(todo_list_id, &*list_info, &**db_pool).await?;
+    let mut outbox = Outbox::new(bearer_auth.user_id);
+
+    lists.update_todo_list(todo_list_id, &*list_info, &mut outbox).await?;
+    slog::info!(logger, "TO-DO list updated"; "todo_list_id" => %todo_list_id);
+
+    events.publish(outbox);
         
     Ok(todo_list_id.to_string())
 }
 
-pub async fn get_list(db_pool: web::Data<PgPool>, bearer_auth: BearerAuth) -> Result<web::Json<FullTodoListInfo>, ServiceError> {
-    let todo_list = select_todo_list(bearer_auth.user_id, &**db_pool).await?
+pub async fn get_list(lists: web::Data<dyn TodoListRepository>, bearer_auth: BearerAuth) -> Result<web::Json<FullTodoListInfo>, ServiceError> {
+    let todo_list = lists.select_todo_list(bearer_auth.user_id).await?
         .ok_or(ServiceError { status_code: StatusCode::NotFound, detail: Some(format!("TO-DO list not found")) })?;
         
     Ok(web::Json(todo_list))
-}
\ No newline at end of file
+}
+
+/// List with tasks as file, which is streamed by task. User without list gets empty export
+pub async fn export_list(query: ValidatedQuery<ExportQuery>, lists: web::Data<dyn TodoListRepository>, tasks: web::Data<dyn TaskRepository>, bearer_auth: BearerAuth) -> Result<HttpResponse, ServiceError> {
+    let format = query.format;
+    let todo_list = lists.select_todo_list(bearer_auth.user_id).await?;
+
+    let todo_list_id = todo_list.as_ref().map(|todo_list| todo_list.id);
+
+    let exporter = Exporter::new(format, todo_list);
+    let head = exporter.head(Utc::now());
+    let tail = exporter.tail();
+
+    // tasks are read by pages while the response is sent, page is one chunk
+    let pages = stream::try_unfold(todo_list_id.map(|_| 0), move |offset: Option<u32>| {
+        let tasks = tasks.clone();
+        async move {
+            let (todo_list_id, offset) = match (todo_list_id, offset) {
+                (Some(todo_list_id), Some(offset)) => (todo_list_id, offset),
+                _ => return Ok(None),
+            };
+            let page = tasks.select_tasks_range(todo_list_id, TaskRange { offset, count: EXPORT_PAGE_SIZE }).await?;
+            let next = (page.len() as u32 == EXPORT_PAGE_SIZE).then_some(offset + EXPORT_PAGE_SIZE);
+            Ok::<_, ServiceError>(Some(((offset, page), next)))
+        }
+    });
+    let chunks = stream::once(future::ok(head))
+        .chain(pages.map_ok(move |(offset, page)| {
+            page.iter()
+                .enumerate()
+                .map(|(index, task)| exporter.task(offset as usize + index, task))
+                .collect::<String>()
+        }))
+        .chain(stream::once(future::ok(tail)))
+        .try_filter(|chunk| future::ready(!chunk.is_empty()))
+        .map_ok(web::Bytes::from)
+        .map_err(actix_web::Error::from);
+
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header((header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", format.file_name())))
+        .streaming(chunks))
+}
+
+/// Adds tasks of the uploaded file to the end of the list in one transaction. Nothing is imported,
+/// if the file has invalid tasks, dry run only reports what would be imported
+pub async fn import_list(
+    query: ValidatedQuery<ImportQuery>,
+    upload: Multipart,
+    lists: web::Data<dyn TodoListRepository>,
+    tasks: web::Data<dyn TaskRepository>,
+    events: web::Data<ChangeEvents>,
+    bearer_auth: BearerAuth,
+    logger: RequestLogger
+) -> Result<web::Json<ImportReport>> {
+    bearer_auth.require_scope(TokenScope::TasksWrite)?;
+
+    let todo_list_id = lists.select_todo_list_id(bearer_auth.user_id).await?
+        .ok_or(ServiceError { status_code: StatusCode::NotFound, detail: Some("TO-DO list not found".to_string()) })?;
+
+    let (file, mapping) = read_upload(upload).await?;
+    // completion at import is stored in seconds as times of the file
+    let items = parse_items(query.format, &file, &mapping, Utc::now().trunc_subsecs(0))?;
+
+    let existing = tasks.select_tasks(todo_list_id).await?;
+    let mut report = plan_import(query.format, query.dry_run, items, &existing, query.duplicates);
+
+    if !report.errors.is_empty() {
+        return Err(ImportRejectedError::new(report).into());
+    }
+
+    if query.dry_run || report.tasks.is_empty() {
+        return Ok(web::Json(report));
+    }
+
+    let contents = report.tasks.iter()
+        .map(|task| TaskContent {
+            description: task.description.clone(),
+            details: TaskDetails { completed_at: task.completed_at, due_at: task.due_at, priority: task.priority, rrule: None },
+        })
+        .collect();
+    let mut outbox = Outbox::new(bearer_auth.user_id);
+
+    let created = tasks.insert_tasks_to_end(todo_list_id, contents, &mut outbox).await?;
+    slog::info!(logger, "Tasks imported"; "todo_list_id" => %todo_list_id, "count" => created.len());
+
+    // order is known only after insert, list could change since the plan
+    for (task, created) in report.tasks.iter_mut().zip(&created) {
+        task.id = Some(created.id);
+        task.order = created.order;
+    }
+    events.publish(outbox);
+
+    Ok(web::Json(report))
+}
+
+/// `file` and optional json `mapping` fields of the form, other fields are ignored
+async fn read_upload(upload: Multipart) -> Result<(String, ImportMapping), ServiceError> {
+    let mut fields = read_form(upload, &["file", "mapping"], IMPORT_FILE_MAX_SIZE).await?;
+    let file = text(required_field(&mut fields, "file")?)?;
+
+    let mapping = match fields.remove("mapping").map(text).transpose()? {
+        Some(mapping) if !mapping.trim().is_empty() => serde_json::from_str(&mapping)
+            .map_err(|e| ServiceError { status_code: StatusCode::BadRequest, detail: Some(format!("Invalid mapping: {e}")) })?,
+        _ => ImportMapping::default(),
+    };
+
+    Ok((file, mapping))
+}
@@ -5,4 +5,39 @@ mod list;
 pub use list::*;
 
 mod task;
-pub use task::*;
\ No newline at end of file
+pub use task::*;
+
+mod token;
+pub use token::*;
+
+mod two_factor;
+pub use two_factor::*;
+
+mod email;
+pub use email::*;
+
+mod oidc;
+pub use oidc::*;
+
+mod webhook;
+pub use webhook::*;
+
+mod calendar;
+pub use calendar::*;
+
+mod caldav;
+pub use caldav::*;
+
+mod backup;
+pub use backup::*;
+
+mod sync;
+pub use sync::*;
+
+mod jwks;
+pub use jwks::*;
+mod graphql;
+pub use graphql::*;
+
+mod admin;
+pub use admin::*;
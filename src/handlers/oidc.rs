@@ -0,0 +1,208 @@
+use actix_web::{
+    cookie::{
+        time,
+        Cookie,
+        SameSite
+    },
+    http::header,
+    web,
+    HttpRequest,
+    HttpResponse,
+    Result
+};
+use rand::{
+    distributions::Alphanumeric,
+    Rng
+};
+use uuid::Uuid;
+
+use super::user::login_response;
+use crate::{
+    models::*,
+    middlewares::{
+        BearerAuth,
+        RequestLogger
+    },
+    db::{
+        IdentityRepository,
+        TwoFactorRepository,
+        UserRepository
+    },
+    utils::{
+        jwt::JwtKeys,
+        oidc::{
+            OidcIdentity,
+            OidcProvider
+        }
+    }
+};
+
+/// State of started login, binds callback to the browser which started it
+const OIDC_STATE_COOKIE: &str = "oidc_state";
+const OIDC_STATE_COOKIE_PATH: &str = "/api/user/oidc";
+const OIDC_STATE_COOKIE_MINUTES: i64 = 10;
+/// Attempts to find free login for provisioned user
+const PROVISION_ATTEMPTS: usize = 5;
+const PROVISIONED_PASSWORD_LEN: usize = 64;
+
+/// Redirects to provider, callback returns token like `login`
+pub async fn oidc_login(oidc: Option<web::Data<OidcProvider>>, logger: RequestLogger) -> Result<HttpResponse, ServiceError> {
+    let oidc = provider(oidc)?;
+    let (state, authorization_url) = start_login(&oidc, None, &logger).await?;
+
+    Ok(HttpResponse::Found()
+        .insert_header((header::LOCATION, authorization_url))
+        .cookie(state_cookie(&oidc, state))
+        .finish())
+}
+
+/// Starts linking of external identity to the current user, client opens returned url in browser
+pub async fn oidc_link(oidc: Option<web::Data<OidcProvider>>, bearer_auth: BearerAuth, logger: RequestLogger) -> Result<HttpResponse, ServiceError> {
+    bearer_auth.require_login_token()?;
+
+    let oidc = provider(oidc)?;
+    let (state, authorization_url) = start_login(&oidc, Some(bearer_auth.user_id), &logger).await?;
+
+    Ok(HttpResponse::Ok()
+        .cookie(state_cookie(&oidc, state))
+        .json(OidcAuthorization { authorization_url }))
+}
+
+/// Redirect from provider. Login returns token of the user with the identity, user is created on first login.
+/// Linking returns id of the user
+#[allow(clippy::too_many_arguments)]
+pub async fn oidc_callback(
+    oidc: Option<web::Data<OidcProvider>>,
+    users: web::Data<dyn UserRepository>,
+    two_factor: web::Data<dyn TwoFactorRepository>,
+    identities: web::Data<dyn IdentityRepository>,
+    jwt_keys: web::Data<JwtKeys>,
+    callback: web::Query<OidcCallback>,
+    req: HttpRequest,
+    logger: RequestLogger
+) -> Result<HttpResponse, ServiceError> {
+    let oidc = provider(oidc)?;
+
+    if req.cookie(OIDC_STATE_COOKIE).is_none_or(|cookie| cookie.value() != callback.state) {
+        return Err(invalid_state());
+    }
+
+    let login = oidc.take_pending(&callback.state).ok_or(invalid_state())?;
+
+    if let Some(error) = &callback.error {
+        slog::info!(logger, "OIDC login denied by provider"; "error" => error);
+        return Err(login_failed());
+    }
+
+    let code = callback.code.as_deref()
+        .ok_or(ServiceError { status_code: StatusCode::BadRequest, detail: Some("Authorization code is missing".to_string()) })?;
+
+    let identity = oidc.exchange_code(&login, code).await
+        .map_err(|e| {
+            slog::warn!(logger, "OIDC login failed"; "error" => %format!("{e:#}"));
+            login_failed()
+        })?;
+
+    let mut response = match login.link_user_id {
+        Some(user_id) => link_identity(identities.as_ref(), user_id, &identity, &logger).await?,
+        None => {
+            let user_id = match identities.select_identity_user_id(&identity.issuer, &identity.subject).await? {
+                Some(user_id) => user_id,
+                None => provision_user(identities.as_ref(), &identity, &logger).await?,
+            };
+
+            login_response(users.as_ref(), two_factor.as_ref(), &jwt_keys, user_id, &logger).await?
+        },
+    };
+
+    response.add_removal_cookie(&state_cookie(&oidc, String::new()))
+        .map_err(|e| ServiceError { status_code: StatusCode::InternalError, detail: Some(e.to_string()) })?;
+
+    Ok(response)
+}
+
+pub async fn get_identities(identities: web::Data<dyn IdentityRepository>, bearer_auth: BearerAuth) -> Result<web::Json<Vec<UserIdentity>>, ServiceError> {
+    Ok(web::Json(identities.select_identities(bearer_auth.user_id).await?))
+}
+
+async fn start_login(oidc: &OidcProvider, link_user_id: Option<Uuid>, logger: &RequestLogger) -> Result<(String, String), ServiceError> {
+    oidc.authorization_url(link_user_id).await
+        .map_err(|e| {
+            slog::error!(logger, "OIDC login not started"; "error" => %format!("{e:#}"));
+            ServiceError { status_code: StatusCode::InternalError, detail: Some("Identity provider is not available".to_string()) }
+        })
+}
+
+async fn link_identity(identities: &dyn IdentityRepository, user_id: Uuid, identity: &OidcIdentity, logger: &RequestLogger) -> Result<HttpResponse, ServiceError> {
+    if !identities.insert_identity(user_id, &new_identity(identity)).await?
+        && identities.select_identity_user_id(&identity.issuer, &identity.subject).await? != Some(user_id)
+    {
+        return Err(ServiceError { status_code: StatusCode::Conflict, detail: Some("Identity is linked to other user".to_string()) });
+    }
+
+    slog::info!(logger, "Identity linked"; "link_user_id" => %user_id, "issuer" => &identity.issuer);
+
+    Ok(HttpResponse::Ok().body(user_id.to_string()))
+}
+
+/// User with login from the identity claims and random password, which isn't shown to anyone.
+/// Password can be set by password reset after email is added
+async fn provision_user(identities: &dyn IdentityRepository, identity: &OidcIdentity, logger: &RequestLogger) -> Result<Uuid, ServiceError> {
+    for attempt in 0..PROVISION_ATTEMPTS {
+        let user = NewUser {
+            login: provisioned_login(identity.preferred_username.as_deref(), identity.email.as_deref(), attempt > 0),
+            password: random_password(),
+        };
+
+        if let Some(user_id) = identities.insert_identity_user(&user, &new_identity(identity)).await? {
+            slog::info!(logger, "User provisioned"; "new_user_id" => %user_id, "issuer" => &identity.issuer);
+            return Ok(user_id);
+        }
+
+        // concurrent first login with the same identity
+        if let Some(user_id) = identities.select_identity_user_id(&identity.issuer, &identity.subject).await? {
+            return Ok(user_id);
+        }
+    }
+
+    Err(ServiceError { status_code: StatusCode::Conflict, detail: Some("User could not be created".to_string()) })
+}
+
+fn random_password() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(PROVISIONED_PASSWORD_LEN)
+        .map(char::from)
+        .collect()
+}
+
+fn new_identity(identity: &OidcIdentity) -> NewIdentity {
+    NewIdentity {
+        issuer: identity.issuer.clone(),
+        subject: identity.subject.clone(),
+        email: identity.email.clone(),
+    }
+}
+
+fn provider(oidc: Option<web::Data<OidcProvider>>) -> Result<web::Data<OidcProvider>, ServiceError> {
+    oidc.ok_or(ServiceError { status_code: StatusCode::NotFound, detail: Some("OpenID Connect login is not configured".to_string()) })
+}
+
+fn state_cookie(oidc: &OidcProvider, state: String) -> Cookie<'static> {
+    Cookie::build(OIDC_STATE_COOKIE, state)
+        .path(OIDC_STATE_COOKIE_PATH)
+        .http_only(true)
+        // cookie is sent with top-level redirect from provider
+        .same_site(SameSite::Lax)
+        .secure(oidc.config().redirect_uri.starts_with("https://"))
+        .max_age(time::Duration::minutes(OIDC_STATE_COOKIE_MINUTES))
+        .finish()
+}
+
+fn invalid_state() -> ServiceError {
+    ServiceError { status_code: StatusCode::BadRequest, detail: Some("Unknown or expired login state".to_string()) }
+}
+
+fn login_failed() -> ServiceError {
+    ServiceError { status_code: StatusCode::Unauthorized, detail: Some("OpenID Connect login failed".to_string()) }
+}
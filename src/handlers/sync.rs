@@ -0,0 +1,229 @@
+use actix_web::{
+    web,
+    Result
+};
+use chrono::{
+    DateTime,
+    Utc
+};
+use uuid::Uuid;
+
+use crate::{
+    models::*,
+    middlewares::{
+        BearerAuth,
+        RequestLogger,
+        ValidatedJson,
+        ValidatedQuery
+    },
+    db::{
+        SyncTransaction,
+        TaskRepository,
+        TodoListRepository
+    },
+    utils::events::ChangeEvents
+};
+
+use super::destination_order;
+
+/// Result of mutation, webhook event is added for applied one
+enum Outcome {
+    Created(FullTaskInfo),
+    Updated(FullTaskInfo),
+    Deleted(FullTaskInfo),
+    /// Task and its previous order
+    Moved(FullTaskInfo, i32),
+    Duplicate(Option<FullTaskInfo>, &'static str),
+    Conflict(Option<FullTaskInfo>, &'static str),
+}
+
+/// Changes of the list after the cursor. Without cursor, or with cursor of another list, the whole list is returned
+pub async fn get_sync_changes(query: ValidatedQuery<SyncQuery>, lists: web::Data<dyn TodoListRepository>, tasks: web::Data<dyn TaskRepository>, bearer_auth: BearerAuth) -> Result<web::Json<SyncChanges>, ServiceError> {
+    let todo_list = lists.select_todo_list(bearer_auth.user_id).await?
+        .ok_or_else(list_not_found)?;
+
+    let cursor = parse_cursor(query.since.as_deref())?;
+
+    Ok(web::Json(changes_since(&**tasks, todo_list, cursor).await?))
+}
+
+/// Applies mutations made offline in order of their client time, the same time is ordered as in request.
+/// All mutations are applied in one transaction, which also stores webhook events of the applied ones.
+/// Conflicts are resolved by time of the last change, the later written change wins for the same time:
+/// - update is applied, if description was not changed after the client time, otherwise server wins;
+/// - move is applied, if task was not moved after the client time; deleted anchor means the end of list;
+/// - delete always wins, update and move of deleted task are conflicts;
+/// - create of existing or deleted task is duplicate, e.g. retried request.
+///
+/// Results are returned with changes after the cursor, which include the applied mutations
+pub async fn apply_sync_mutations(request: ValidatedJson<SyncRequest>, lists: web::Data<dyn TodoListRepository>, tasks: web::Data<dyn TaskRepository>, events: web::Data<ChangeEvents>, bearer_auth: BearerAuth, logger: RequestLogger) -> Result<web::Json<SyncResponse>, ServiceError> {
+    bearer_auth.require_scope(TokenScope::TasksWrite)?;
+
+    let todo_list = lists.select_todo_list(bearer_auth.user_id).await?
+        .ok_or_else(list_not_found)?;
+
+    let request = request.into_inner();
+    let cursor = parse_cursor(request.cursor.as_deref())?;
+
+    // stable sort keeps order of request for the same time
+    let mut mutations: Vec<(usize, SyncMutation)> = request.mutations.into_iter().enumerate().collect();
+    mutations.sort_by_key(|(_, mutation)| mutation.client_time);
+
+    let now = Utc::now();
+    let mut outcomes = Vec::with_capacity(mutations.len());
+
+    let mut transaction = tasks.begin_sync_transaction(todo_list.id).await?;
+    for (index, SyncMutation { client_time, mutation }) in mutations {
+        let outcome = apply_mutation(&mut *transaction, &mutation, client_time.min(now)).await?;
+        outcomes.push((index, mutation.task_id(), outcome));
+    }
+
+    let mut outbox = Outbox::new(bearer_auth.user_id);
+    let mut results = Vec::with_capacity(outcomes.len());
+    for (index, task_id, outcome) in outcomes {
+        let (status, detail, task) = match outcome {
+            Outcome::Created(task) => {
+                outbox.add(WebhookEvent::TaskCreated, &task)?;
+                (MutationStatus::Applied, None, Some(task))
+            },
+            Outcome::Updated(task) => {
+                outbox.add(WebhookEvent::TaskUpdated, &task)?;
+                (MutationStatus::Applied, None, Some(task))
+            },
+            Outcome::Deleted(task) => {
+                outbox.add(WebhookEvent::TaskDeleted, &task)?;
+                (MutationStatus::Applied, None, None)
+            },
+            Outcome::Moved(task, previous_order) => {
+                if task.order != previous_order {
+                    outbox.add(WebhookEvent::TaskMoved, &MovedTask { task: &task, previous_order })?;
+                }
+                (MutationStatus::Applied, None, Some(task))
+            },
+            Outcome::Duplicate(task, detail) => (MutationStatus::Duplicate, Some(detail.to_string()), task),
+            Outcome::Conflict(task, detail) => (MutationStatus::Conflict, Some(detail.to_string()), task),
+        };
+
+        results.push(MutationResult { index, task_id, status, detail, task });
+    }
+
+    transaction.commit(&outbox).await?;
+    events.publish(outbox);
+
+    results.sort_by_key(|result| result.index);
+
+    let conflicts = results.iter().filter(|result| result.status == MutationStatus::Conflict).count();
+    slog::info!(logger, "Sync mutations applied"; "todo_list_id" => %todo_list.id, "mutations" => results.len(), "conflicts" => conflicts);
+
+    let changes = changes_since(&**tasks, todo_list, cursor).await?;
+
+    Ok(web::Json(SyncResponse { results, changes }))
+}
+
+async fn apply_mutation(transaction: &mut dyn SyncTransaction, mutation: &TaskMutation, changed_at: DateTime<Utc>) -> Result<Outcome, ServiceError> {
+    let outcome = match mutation {
+        TaskMutation::Create { task_id, description, position } => {
+            if let Some(task) = transaction.select_task(*task_id).await? {
+                return Ok(Outcome::Duplicate(Some(task), "Task already exists"));
+            }
+            if transaction.is_task_deleted(*task_id).await? {
+                return Ok(Outcome::Duplicate(None, "Task is deleted"));
+            }
+
+            // order is clamped, so deleted anchor means the end
+            let order = match (position, anchor_task(transaction, position).await?) {
+                (TaskPosition::After { .. }, Some(anchor)) => anchor.order + 1,
+                (_, Some(anchor)) => anchor.order,
+                (_, None) => i32::MAX,
+            };
+
+            if !transaction.insert_task_at(*task_id, description.clone(), order, changed_at).await? {
+                return Ok(Outcome::Conflict(None, "Task id is taken"));
+            }
+
+            match transaction.select_task(*task_id).await? {
+                Some(task) => Outcome::Created(task),
+                None => Outcome::Conflict(None, "Task is deleted"),
+            }
+        },
+        TaskMutation::Update { task_id, description } => {
+            match transaction.update_task_at(*task_id, description.clone(), changed_at).await? {
+                ConditionalWrite::Applied(task) => Outcome::Updated(task),
+                ConditionalWrite::Outdated(task) => Outcome::Conflict(Some(task), "Task is changed later"),
+                ConditionalWrite::NotFound => Outcome::Conflict(None, missing_task_detail(transaction, *task_id).await?),
+            }
+        },
+        TaskMutation::Delete { task_id } => {
+            match transaction.delete_task(*task_id).await? {
+                Some(task) => Outcome::Deleted(task),
+                None if transaction.is_task_deleted(*task_id).await? => Outcome::Duplicate(None, "Task is deleted"),
+                None => Outcome::Conflict(None, "Task not found"),
+            }
+        },
+        TaskMutation::Move { task_id, position } => {
+            let task = match transaction.select_task(*task_id).await? {
+                Some(task) => task,
+                None => return Ok(Outcome::Conflict(None, missing_task_detail(transaction, *task_id).await?)),
+            };
+
+            // anchor is not the task itself, it's validated with request
+            let order = match anchor_task(transaction, position).await? {
+                Some(anchor) => destination_order(task.order, position, anchor.order),
+                None => transaction.select_task_count().await? as i32,
+            };
+
+            match transaction.move_task_at(*task_id, order, changed_at).await? {
+                ConditionalWrite::Applied(moved) => Outcome::Moved(moved, task.order),
+                ConditionalWrite::Outdated(task) => Outcome::Conflict(Some(task), "Task is moved later"),
+                ConditionalWrite::NotFound => Outcome::Conflict(None, "Task is deleted"),
+            }
+        },
+    };
+
+    Ok(outcome)
+}
+
+/// Task of `after` or `before` position, `None` for the end or missing task
+async fn anchor_task(transaction: &mut dyn SyncTransaction, position: &TaskPosition) -> Result<Option<FullTaskInfo>, ServiceError> {
+    match position.anchor() {
+        Some(anchor) => transaction.select_task(anchor).await,
+        None => Ok(None),
+    }
+}
+
+async fn missing_task_detail(transaction: &mut dyn SyncTransaction, task_id: Uuid) -> Result<&'static str, ServiceError> {
+    Ok(if transaction.is_task_deleted(task_id).await? { "Task is deleted" } else { "Task not found" })
+}
+
+async fn changes_since(tasks: &dyn TaskRepository, todo_list: FullTodoListInfo, cursor: Option<SyncCursor>) -> Result<SyncChanges, ServiceError> {
+    let mut since = cursor.filter(|cursor| cursor.todo_list_id == todo_list.id).map(|cursor| cursor.seq);
+
+    let mut changes = tasks.select_task_changes(todo_list.id, since.unwrap_or(0)).await?
+        .ok_or_else(list_not_found)?;
+
+    // cursor is ahead of the list, e.g. database was restored from backup
+    if since.is_some_and(|since| since > changes.seq) {
+        since = None;
+        changes = tasks.select_task_changes(todo_list.id, 0).await?
+            .ok_or_else(list_not_found)?;
+    }
+
+    Ok(SyncChanges {
+        cursor: SyncCursor { todo_list_id: todo_list.id, seq: changes.seq }.to_string(),
+        reset: cursor.is_some() && since.is_none(),
+        list: since.is_none_or(|since| changes.name_seq > since).then_some(todo_list),
+        created: changes.created,
+        updated: changes.updated,
+        // whole list replaces data of client
+        deleted: if since.is_some() { changes.deleted } else { Vec::new() },
+    })
+}
+
+fn parse_cursor(cursor: Option<&str>) -> Result<Option<SyncCursor>, ServiceError> {
+    cursor.map(|cursor| SyncCursor::parse(cursor).ok_or(ServiceError { status_code: StatusCode::BadRequest, detail: Some("Invalid sync cursor".to_string()) }))
+        .transpose()
+}
+
+fn list_not_found() -> ServiceError {
+    ServiceError { status_code: StatusCode::NotFound, detail: Some("TO-DO list not found".to_string()) }
+}
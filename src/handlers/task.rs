@@ -2,137 +2,386 @@ use actix_web::{
     web,
     Result
 };
-use sqlx::PgPool;
 use uuid::Uuid;
 
 use crate::{
     models::*,
-    middlewares::BearerAuth,
+    middlewares::{
+        BearerAuth,
+        RequestLogger,
+        ValidatedJson,
+        ValidatedQuery
+    },
     db::{
-        list,
-        task
-    }
+        TaskRepository,
+        TodoListRepository
+    },
+    utils::events::ChangeEvents
 };
 
-pub async fn new_task(new_task_info: web::Json<NewTask>, db_pool: web::Data<PgPool>, bearer_auth: BearerAuth) -> Result<String, ServiceError> {
-    let todo_list_id = list::select_todo_list_id(bearer_auth.user_id, &**db_pool).await?
+pub async fn new_task(new_task_info: ValidatedJson<NewTask>, lists: web::Data<dyn TodoListRepository>, tasks: web::Data<dyn TaskRepository>, events: web::Data<ChangeEvents>, bearer_auth: BearerAuth, logger: RequestLogger) -> Result<String, ServiceError> {
+    bearer_auth.require_scope(TokenScope::TasksWrite)?;
+
+    let todo_list_id = lists.select_todo_list_id(bearer_auth.user_id).await?
         .ok_or(ServiceError { status_code: StatusCode::NotFound, detail: Some(format!("TO-DO list not found")) })?;
 
+    let mut outbox = Outbox::new(bearer_auth.user_id);
+
     let id = match new_task_info.position {
         crate::models::TaskPosition::End => {
-            task::insert_task_to_end(todo_list_id, new_task_info.description.clone(), &**db_pool).await?
+            tasks.insert_task_to_end(todo_list_id, new_task_info.description.clone(), &mut outbox).await?
         },
         crate::models::TaskPosition::After { task_id } => {
-            let task = task::select_task(todo_list_id, task_id, &**db_pool).await?
+            let task = tasks.select_task(todo_list_id, task_id).await?
                 .ok_or(ServiceError { status_code: StatusCode::NotFound, detail: Some(format!("Task not found")) })?;
 
-                task::insert_task(todo_list_id, new_task_info.description.clone(), task.order + 1, &**db_pool).await?
+                tasks.insert_task(todo_list_id, new_task_info.description.clone(), task.order + 1, &mut outbox).await?
         },
         crate::models::TaskPosition::Before { task_id } => {
-            let task = task::select_task(todo_list_id, task_id, &**db_pool).await?
+            let task = tasks.select_task(todo_list_id, task_id).await?
                 .ok_or(ServiceError { status_code: StatusCode::NotFound, detail: Some(format!("Task not found")) })?;
 
-            // if task.order is min, then min - 1 less then min
-            let order = std::cmp::max(task.order - 1, 1);
-            
-            task::insert_task(todo_list_id, new_task_info.description.clone(), order, &**db_pool).await?
+            // new task takes place of the task, task and all after it move down
+            tasks.insert_task(todo_list_id, new_task_info.description.clone(), task.order, &mut outbox).await?
         },
     };
+
+    slog::info!(logger, "Task created"; "task_id" => %id);
+
+    events.publish(outbox);
         
     Ok(id.to_string())
 }
 
-pub async fn get_tasks(db_pool: web::Data<PgPool>, bearer_auth: BearerAuth) -> Result<web::Json<Vec<FullTaskInfo>>, ServiceError> {
-    let todo_list_id = list::select_todo_list_id(bearer_auth.user_id, &**db_pool).await?
+pub async fn get_tasks(lists: web::Data<dyn TodoListRepository>, tasks: web::Data<dyn TaskRepository>, bearer_auth: BearerAuth) -> Result<web::Json<Vec<FullTaskInfo>>, ServiceError> {
+    let todo_list_id = lists.select_todo_list_id(bearer_auth.user_id).await?
         .ok_or(ServiceError { status_code: StatusCode::NotFound, detail: Some(format!("TO-DO list not found")) })?;
 
-    let mut tasks = task::select_tasks(todo_list_id, &**db_pool).await?;
+    let mut tasks = tasks.select_tasks(todo_list_id).await?;
     tasks.sort_by_key(|x| x.order);
         
     Ok(web::Json(tasks))
 }
 
-pub async fn get_tasks_range(range: web::Query<TaskRange>, db_pool: web::Data<PgPool>, bearer_auth: BearerAuth) -> Result<web::Json<Vec<FullTaskInfo>>, ServiceError> {
-    let todo_list_id = list::select_todo_list_id(bearer_auth.user_id, &**db_pool).await?
+pub async fn get_tasks_range(range: ValidatedQuery<TaskRange>, lists: web::Data<dyn TodoListRepository>, tasks: web::Data<dyn TaskRepository>, bearer_auth: BearerAuth) -> Result<web::Json<Vec<FullTaskInfo>>, ServiceError> {
+    let todo_list_id = lists.select_todo_list_id(bearer_auth.user_id).await?
         .ok_or(ServiceError { status_code: StatusCode::NotFound, detail: Some(format!("TO-DO list not found")) })?;
 
-    let mut tasks = task::select_tasks_range(todo_list_id, range.into_inner(), &**db_pool).await?;
+    let mut tasks = tasks.select_tasks_range(todo_list_id, range.into_inner()).await?;
     tasks.sort_by_key(|x| x.order);
         
     Ok(web::Json(tasks))
 }
 
-pub async fn delete_tasks(task_id: web::Path<Uuid>, db_pool: web::Data<PgPool>, bearer_auth: BearerAuth) -> Result<web::Json<FullTaskInfo>, ServiceError> {
-    let todo_list_id = list::select_todo_list_id(bearer_auth.user_id, &**db_pool).await?
+pub async fn delete_tasks(task_id: web::Path<Uuid>, lists: web::Data<dyn TodoListRepository>, tasks: web::Data<dyn TaskRepository>, events: web::Data<ChangeEvents>, bearer_auth: BearerAuth, logger: RequestLogger) -> Result<web::Json<FullTaskInfo>, ServiceError> {
+    bearer_auth.require_scope(TokenScope::TasksWrite)?;
+
+    let todo_list_id = lists.select_todo_list_id(bearer_auth.user_id).await?
         .ok_or(ServiceError { status_code: StatusCode::NotFound, detail: Some(format!("TO-DO list not found")) })?;
 
     let task_id = task_id.into_inner();
 
-    let task = task::delete_task(todo_list_id, task_id, &**db_pool).await?
+    let mut outbox = Outbox::new(bearer_auth.user_id);
+
+    let task = tasks.delete_task(todo_list_id, task_id, &mut outbox).await?
         .ok_or(ServiceError { status_code: StatusCode::NotFound, detail: Some(format!("Task not found")) })?;
 
+    slog::info!(logger, "Task deleted"; "task_id" => %task_id);
+
+    events.publish(outbox);
+
     Ok(web::Json(task))
 }
 
-pub async fn update_task(task_id: web::Path<Uuid>, new_task_info: web::Json<UpdateTask>, db_pool: web::Data<PgPool>, bearer_auth: BearerAuth) -> Result<web::Json<FullTaskInfo>, ServiceError> {
-    let todo_list_id = list::select_todo_list_id(bearer_auth.user_id, &**db_pool).await?
+pub async fn update_task(task_id: web::Path<Uuid>, new_task_info: ValidatedJson<UpdateTask>, lists: web::Data<dyn TodoListRepository>, tasks: web::Data<dyn TaskRepository>, events: web::Data<ChangeEvents>, bearer_auth: BearerAuth, logger: RequestLogger) -> Result<web::Json<FullTaskInfo>, ServiceError> {
+    bearer_auth.require_scope(TokenScope::TasksWrite)?;
+
+    let todo_list_id = lists.select_todo_list_id(bearer_auth.user_id).await?
         .ok_or(ServiceError { status_code: StatusCode::NotFound, detail: Some(format!("TO-DO list not found")) })?;
 
     let task_id = task_id.into_inner();
 
-    let task = task::update_task(todo_list_id, task_id, new_task_info.description.clone(), &**db_pool).await?
+    let mut outbox = Outbox::new(bearer_auth.user_id);
+
+    let task = tasks.update_task(todo_list_id, task_id, new_task_info.description.clone(), &mut outbox).await?
         .ok_or(ServiceError { status_code: StatusCode::NotFound, detail: Some(format!("Task not found")) })?;
 
+    slog::info!(logger, "Task updated"; "task_id" => %task_id);
+
+    events.publish(outbox);
+
     Ok(web::Json(task))
 }
 
-pub async fn move_task(task_id: web::Path<Uuid>, new_task_info: web::Json<MoveTask>, db_pool: web::Data<PgPool>, bearer_auth: BearerAuth) -> Result<web::Json<FullTaskInfo>, ServiceError> {
-    let todo_list_id = list::select_todo_list_id(bearer_auth.user_id, &**db_pool).await?
+pub async fn update_task_details(task_id: web::Path<Uuid>, details: ValidatedJson<TaskDetails>, lists: web::Data<dyn TodoListRepository>, tasks: web::Data<dyn TaskRepository>, events: web::Data<ChangeEvents>, bearer_auth: BearerAuth, logger: RequestLogger) -> Result<web::Json<FullTaskInfo>, ServiceError> {
+    bearer_auth.require_scope(TokenScope::TasksWrite)?;
+
+    let todo_list_id = lists.select_todo_list_id(bearer_auth.user_id).await?
+        .ok_or(ServiceError { status_code: StatusCode::NotFound, detail: Some("TO-DO list not found".to_string()) })?;
+
+    let mut outbox = Outbox::new(bearer_auth.user_id);
+
+    let task = tasks.update_task_details(todo_list_id, task_id.into_inner(), details.into_inner(), &mut outbox).await?
+        .ok_or(ServiceError { status_code: StatusCode::NotFound, detail: Some("Task not found".to_string()) })?;
+
+    slog::info!(logger, "Task details updated"; "task_id" => %task.id);
+
+    events.publish(outbox);
+
+    Ok(web::Json(task))
+}
+
+pub async fn move_task(task_id: web::Path<Uuid>, new_task_info: ValidatedJson<MoveTask>, lists: web::Data<dyn TodoListRepository>, tasks: web::Data<dyn TaskRepository>, events: web::Data<ChangeEvents>, bearer_auth: BearerAuth, logger: RequestLogger) -> Result<web::Json<FullTaskInfo>, ServiceError> {
+    bearer_auth.require_scope(TokenScope::TasksWrite)?;
+
+    let todo_list_id = lists.select_todo_list_id(bearer_auth.user_id).await?
         .ok_or(ServiceError { status_code: StatusCode::NotFound, detail: Some(format!("TO-DO list not found")) })?;
 
     let id = task_id.into_inner();
 
-    let source_position_order = task::select_task(todo_list_id, id, &**db_pool).await?
+    let source_position_order = tasks.select_task(todo_list_id, id).await?
         .ok_or(ServiceError { status_code: StatusCode::NotFound, detail: Some(format!("Task not found")) })?
         .order;
 
-    let task = match new_task_info.position {
-        crate::models::TaskPosition::End => {
-            let task_count = task::select_task_count(todo_list_id, &**db_pool).await?;
-            let destination_position_order = task_count as i32;
+    let destination_position_order = move_destination_order(&**tasks, todo_list_id, id, source_position_order, &new_task_info.position).await?;
 
-            task::move_task(todo_list_id, id, source_position_order, destination_position_order, &**db_pool).await?
-        },
-        crate::models::TaskPosition::After { task_id } => {
-            if id == task_id {
-                return Err(ServiceError { status_code: StatusCode::BadRequest, detail: Some(format!("Source and destination task id is the same")) });
-            }
+    let mut outbox = Outbox::new(bearer_auth.user_id);
 
-            let task = task::select_task(todo_list_id, task_id, &**db_pool).await?
-                .ok_or(ServiceError { status_code: StatusCode::NotFound, detail: Some(format!("Task not found")) })?;
+    // storage returns task unchanged and adds no event, if it already stands on the destination
+    let task = tasks.move_task(todo_list_id, id, destination_position_order, &mut outbox).await?
+        .ok_or(ServiceError { status_code: StatusCode::NotFound, detail: Some(format!("Task not found")) })?;
 
-            // 1, 2, 3, 4, 5 , 6 if move 6 after 2, order is 3, if move 2 after 6 order is 6, because range 3-6 move -1
-            let destination_position_order = if source_position_order < task.order { task.order } else { task.order + 1};
+    slog::info!(logger, "Task moved"; "task_id" => %id, "from" => source_position_order, "to" => task.order);
 
-            task::move_task(todo_list_id, id, source_position_order, destination_position_order, &**db_pool).await?
-        },
-        crate::models::TaskPosition::Before { task_id } => {
+    events.publish(outbox);
+
+    Ok(web::Json(task))
+}
+
+/// Order, which task with `source_order` gets, when it's moved to the position
+pub(crate) async fn move_destination_order(tasks: &dyn TaskRepository, todo_list_id: Uuid, id: Uuid, source_order: i32, position: &TaskPosition) -> Result<i32, ServiceError> {
+    let target_order = match position.anchor() {
+        None => tasks.select_task_count(todo_list_id).await? as i32,
+        Some(task_id) => {
             if id == task_id {
                 return Err(ServiceError { status_code: StatusCode::BadRequest, detail: Some(format!("Source and destination task id is the same")) });
             }
 
-            let task = task::select_task(todo_list_id, task_id, &**db_pool).await?
+            let task = tasks.select_task(todo_list_id, task_id).await?
                 .ok_or(ServiceError { status_code: StatusCode::NotFound, detail: Some(format!("Task not found")) })?;
 
+            task.order
+        },
+    };
+
+    Ok(destination_order(source_order, position, target_order))
+}
+
+/// Order of task moved from `source_order` to `position`, `target_order` is order of the anchor task
+/// or count of tasks for the end
+pub(crate) fn destination_order(source_order: i32, position: &TaskPosition, target_order: i32) -> i32 {
+    match position {
+        TaskPosition::End => target_order,
+        // 1, 2, 3, 4, 5 , 6 if move 6 after 2, order is 3, if move 2 after 6 order is 6, because range 3-6 move -1
+        TaskPosition::After { .. } => if source_order < target_order { target_order } else { target_order + 1 },
+        TaskPosition::Before { .. } => {
             // 1, 2, 3, 4, 5 , 6 if move 6 before 2, order is 2, if move 2 before 6 order is 6, because range 3-6 move -1
-            let destination_position_order = if source_position_order < task.order { task.order - 1 } else { task.order };
+            let destination_position_order = if source_order < target_order { target_order - 1 } else { target_order };
 
             // if task.order is min, then min - 1 less then min
-            let destination_position_order = std::cmp::max(destination_position_order, 1);
-            
-            task::move_task(todo_list_id, id, source_position_order, destination_position_order, &**db_pool).await?
+            std::cmp::max(destination_position_order, 1)
         },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        error::ResponseError,
+        http,
+        web
     };
+    use uuid::Uuid;
 
-    Ok(web::Json(task))
+    use super::*;
+    use crate::db::{
+        memory::MemoryStorage,
+        Storage
+    };
+
+    struct TestList {
+        lists: web::Data<dyn TodoListRepository>,
+        tasks: web::Data<dyn TaskRepository>,
+        events: web::Data<ChangeEvents>,
+        user_id: Uuid,
+    }
+
+    impl TestList {
+        async fn new(descriptions: &[&str]) -> Self {
+            let storage = Storage::new(MemoryStorage::new());
+            let user_id = Uuid::new_v4();
+            storage.lists.insert_todo_list(user_id, &NewTodoList { name: "test".to_string() }, &mut Outbox::new(user_id)).await.unwrap();
+
+            let list = Self {
+                lists: web::Data::from(storage.lists.clone()),
+                tasks: web::Data::from(storage.tasks.clone()),
+                events: web::Data::new(storage.events.clone()),
+                user_id,
+            };
+
+            for description in descriptions {
+                list.add(description, TaskPosition::End).await.unwrap();
+            }
+
+            list
+        }
+
+        fn logger() -> RequestLogger {
+            RequestLogger(slog::Logger::root(slog::Discard, slog::o!()))
+        }
+
+        async fn add(&self, description: &str, position: TaskPosition) -> Result<String, ServiceError> {
+            let new_task_info = NewTask { description: description.to_string(), position };
+            new_task(ValidatedJson(new_task_info), self.lists.clone(), self.tasks.clone(), self.events.clone(), BearerAuth { user_id: self.user_id, scopes: None }, Self::logger()).await
+        }
+
+        async fn move_to(&self, description: &str, position: TaskPosition) -> Result<FullTaskInfo, ServiceError> {
+            self.move_to_id(self.id(description).await, position).await
+        }
+
+        async fn move_to_id(&self, task_id: Uuid, position: TaskPosition) -> Result<FullTaskInfo, ServiceError> {
+            move_task(web::Path::from(task_id), ValidatedJson(MoveTask { position }), self.lists.clone(), self.tasks.clone(), self.events.clone(), BearerAuth { user_id: self.user_id, scopes: None }, Self::logger()).await
+                .map(|task| task.into_inner())
+        }
+
+        async fn delete(&self, description: &str) -> FullTaskInfo {
+            let task_id = self.id(description).await;
+            delete_tasks(web::Path::from(task_id), self.lists.clone(), self.tasks.clone(), self.events.clone(), BearerAuth { user_id: self.user_id, scopes: None }, Self::logger()).await
+                .unwrap()
+                .into_inner()
+        }
+
+        async fn tasks(&self) -> Vec<FullTaskInfo> {
+            get_tasks(self.lists.clone(), self.tasks.clone(), BearerAuth { user_id: self.user_id, scopes: None }).await.unwrap().into_inner()
+        }
+
+        async fn descriptions(&self) -> Vec<String> {
+            let tasks = self.tasks().await;
+            let orders: Vec<i32> = tasks.iter().map(|task| task.order).collect();
+            assert_eq!(orders, (1..=tasks.len() as i32).collect::<Vec<_>>(), "orders must be continuous");
+            tasks.into_iter().map(|task| task.description).collect()
+        }
+
+        async fn id(&self, description: &str) -> Uuid {
+            self.tasks().await.into_iter().find(|task| task.description == description).unwrap().id
+        }
+
+        async fn after(&self, description: &str) -> TaskPosition {
+            TaskPosition::After { task_id: self.id(description).await }
+        }
+
+        async fn before(&self, description: &str) -> TaskPosition {
+            TaskPosition::Before { task_id: self.id(description).await }
+        }
+    }
+
+    #[tokio::test]
+    async fn new_task_positions() {
+        let list = TestList::new(&["a", "b", "c"]).await;
+
+        list.add("after a", list.after("a").await).await.unwrap();
+        list.add("before c", list.before("c").await).await.unwrap();
+        list.add("before a", list.before("a").await).await.unwrap();
+        list.add("after c", list.after("c").await).await.unwrap();
+
+        assert_eq!(list.descriptions().await, vec!["before a", "a", "after a", "b", "before c", "c", "after c"]);
+    }
+
+    #[tokio::test]
+    async fn new_task_after_unknown_task() {
+        let list = TestList::new(&["a"]).await;
+
+        let error = list.add("x", TaskPosition::After { task_id: Uuid::new_v4() }).await.unwrap_err();
+
+        assert_eq!(error.status_code(), http::StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn move_task_after() {
+        let list = TestList::new(&["1", "2", "3", "4", "5"]).await;
+
+        let task = list.move_to("2", list.after("4").await).await.unwrap();
+        assert_eq!(task.order, 4);
+        assert_eq!(list.descriptions().await, vec!["1", "3", "4", "2", "5"]);
+
+        list.move_to("5", list.after("1").await).await.unwrap();
+        assert_eq!(list.descriptions().await, vec!["1", "5", "3", "4", "2"]);
+
+        list.move_to("1", list.after("2").await).await.unwrap();
+        assert_eq!(list.descriptions().await, vec!["5", "3", "4", "2", "1"]);
+    }
+
+    #[tokio::test]
+    async fn move_task_before() {
+        let list = TestList::new(&["1", "2", "3", "4", "5"]).await;
+
+        let task = list.move_to("2", list.before("5").await).await.unwrap();
+        assert_eq!(task.order, 4);
+        assert_eq!(list.descriptions().await, vec!["1", "3", "4", "2", "5"]);
+
+        list.move_to("5", list.before("1").await).await.unwrap();
+        assert_eq!(list.descriptions().await, vec!["5", "1", "3", "4", "2"]);
+    }
+
+    #[tokio::test]
+    async fn move_task_to_end() {
+        let list = TestList::new(&["1", "2", "3"]).await;
+
+        let task = list.move_to("1", TaskPosition::End).await.unwrap();
+        assert_eq!(task.order, 3);
+        assert_eq!(list.descriptions().await, vec!["2", "3", "1"]);
+    }
+
+    #[tokio::test]
+    async fn move_task_to_current_position() {
+        let list = TestList::new(&["1", "2", "3"]).await;
+
+        assert_eq!(list.move_to("3", TaskPosition::End).await.unwrap().order, 3);
+        assert_eq!(list.move_to("2", list.before("3").await).await.unwrap().order, 2);
+        assert_eq!(list.move_to("2", list.after("1").await).await.unwrap().order, 2);
+
+        assert_eq!(list.descriptions().await, vec!["1", "2", "3"]);
+    }
+
+    #[tokio::test]
+    async fn move_task_relative_to_itself() {
+        let list = TestList::new(&["1", "2"]).await;
+
+        let error = list.move_to("1", list.before("1").await).await.unwrap_err();
+        assert_eq!(error.status_code(), http::StatusCode::BAD_REQUEST);
+
+        let error = list.move_to("1", list.after("1").await).await.unwrap_err();
+        assert_eq!(error.status_code(), http::StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn delete_task_closes_gap() {
+        let list = TestList::new(&["1", "2", "3"]).await;
+
+        assert_eq!(list.delete("2").await.order, 2);
+
+        assert_eq!(list.descriptions().await, vec!["1", "3"]);
+    }
+
+    #[tokio::test]
+    async fn tasks_of_other_user_are_not_visible() {
+        let list = TestList::new(&["1"]).await;
+        let other = TestList { user_id: Uuid::new_v4(), lists: list.lists.clone(), tasks: list.tasks.clone(), events: list.events.clone() };
+        list.lists.insert_todo_list(other.user_id, &NewTodoList { name: "other".to_string() }, &mut Outbox::new(other.user_id)).await.unwrap();
+
+        let error = other.move_to_id(list.id("1").await, TaskPosition::End).await.unwrap_err();
+
+        assert_eq!(error.status_code(), http::StatusCode::NOT_FOUND);
+        assert!(other.tasks().await.is_empty());
+    }
 }
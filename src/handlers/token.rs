@@ -0,0 +1,49 @@
+use actix_web::{
+    web,
+    Result
+};
+use chrono::{
+    Duration,
+    Utc
+};
+use uuid::Uuid;
+
+use crate::{
+    models::*,
+    middlewares::{
+        BearerAuth,
+        RequestLogger,
+        ValidatedJson
+    },
+    db::AccessTokenRepository
+};
+
+pub async fn new_access_token(tokens: web::Data<dyn AccessTokenRepository>, new_token_info: ValidatedJson<NewAccessToken>, bearer_auth: BearerAuth, logger: RequestLogger) -> Result<web::Json<CreatedAccessToken>, ServiceError> {
+    bearer_auth.require_login_token()?;
+
+    let token = generate_access_token();
+    let expires_at = new_token_info.expires_in_days.map(|days| Utc::now() + Duration::days(days as i64));
+
+    let info = tokens.insert_access_token(bearer_auth.user_id, &access_token_hash(&token), &new_token_info, expires_at).await?;
+    slog::info!(logger, "Access token created"; "access_token_id" => %info.id);
+
+    Ok(web::Json(CreatedAccessToken { info, token }))
+}
+
+pub async fn get_access_tokens(tokens: web::Data<dyn AccessTokenRepository>, bearer_auth: BearerAuth) -> Result<web::Json<Vec<AccessTokenInfo>>, ServiceError> {
+    bearer_auth.require_login_token()?;
+
+    Ok(web::Json(tokens.select_access_tokens(bearer_auth.user_id).await?))
+}
+
+pub async fn delete_access_token(token_id: web::Path<Uuid>, tokens: web::Data<dyn AccessTokenRepository>, bearer_auth: BearerAuth, logger: RequestLogger) -> Result<String, ServiceError> {
+    bearer_auth.require_login_token()?;
+
+    if !tokens.delete_access_token(bearer_auth.user_id, *token_id).await? {
+        return Err(ServiceError { status_code: StatusCode::NotFound, detail: Some("Access token not found".to_string()) });
+    }
+
+    slog::info!(logger, "Access token revoked"; "access_token_id" => %token_id);
+
+    Ok(token_id.to_string())
+}
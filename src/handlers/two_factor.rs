@@ -0,0 +1,161 @@
+use std::env;
+
+use actix_web::{
+    web,
+    HttpRequest,
+    Result
+};
+use chrono::Utc;
+use uuid::Uuid;
+
+use super::user::{
+    login_token,
+    TOO_MANY_ATTEMPTS
+};
+use crate::{
+    models::*,
+    middlewares::{
+        BearerAuth,
+        RequestLogger,
+        ValidatedJson
+    },
+    db::{
+        TwoFactorRepository,
+        UserRepository
+    },
+    utils::{
+        jwt::JwtKeys,
+        rate_limit::RateLimits,
+        totp
+    }
+};
+
+const TODO_SERVICE_TOTP_ISSUER_ENV: &str = "TODO_SERVICE_TOTP_ISSUER";
+const DEFAULT_TOTP_ISSUER: &str = "todo-list-rs";
+
+/// New not confirmed secret, replaces previous not confirmed one
+pub async fn enroll_two_factor(users: web::Data<dyn UserRepository>, two_factor: web::Data<dyn TwoFactorRepository>, bearer_auth: BearerAuth) -> Result<web::Json<TotpEnrollment>, ServiceError> {
+    bearer_auth.require_login_token()?;
+
+    if two_factor.select_totp_secret(bearer_auth.user_id).await?.is_some_and(|totp| totp.enabled) {
+        return Err(ServiceError { status_code: StatusCode::BadRequest, detail: Some("2FA is already enabled".to_string()) });
+    }
+
+    let login = users.select_user_login(bearer_auth.user_id).await?
+        .ok_or(ServiceError { status_code: StatusCode::NotFound, detail: Some("User not found".to_string()) })?;
+
+    let secret = totp::generate_secret();
+    two_factor.upsert_totp_secret(bearer_auth.user_id, &secret).await?;
+
+    let issuer = env::var(TODO_SERVICE_TOTP_ISSUER_ENV).unwrap_or_else(|_| DEFAULT_TOTP_ISSUER.to_string());
+
+    Ok(web::Json(TotpEnrollment {
+        secret: totp::encode_secret(&secret),
+        otpauth_uri: totp::otpauth_uri(&issuer, &login, &secret),
+    }))
+}
+
+/// Enables 2FA with the first code from authenticator app, returns recovery codes once
+pub async fn confirm_two_factor(two_factor: web::Data<dyn TwoFactorRepository>, code: ValidatedJson<TwoFactorCode>, bearer_auth: BearerAuth, logger: RequestLogger) -> Result<web::Json<RecoveryCodes>, ServiceError> {
+    bearer_auth.require_login_token()?;
+
+    let totp_secret = match two_factor.select_totp_secret(bearer_auth.user_id).await? {
+        Some(totp_secret) if !totp_secret.enabled => totp_secret,
+        Some(_) => return Err(ServiceError { status_code: StatusCode::BadRequest, detail: Some("2FA is already enabled".to_string()) }),
+        None => return Err(ServiceError { status_code: StatusCode::BadRequest, detail: Some("2FA enrollment not found".to_string()) }),
+    };
+
+    let step = totp::verify(&totp_secret.secret, &code.code, Utc::now().timestamp() as u64);
+
+    // confirmation code can't be reused for login
+    let accepted = match step {
+        Some(step) => two_factor.use_totp_step(bearer_auth.user_id, step as i64).await?,
+        None => false,
+    };
+
+    if !accepted {
+        return Err(ServiceError { status_code: StatusCode::BadRequest, detail: Some("Invalid code".to_string()) });
+    }
+
+    let recovery_codes: Vec<String> = (0..RECOVERY_CODE_COUNT).map(|_| generate_recovery_code()).collect();
+    let hashes: Vec<String> = recovery_codes.iter().map(|code| recovery_code_hash(code)).collect();
+    two_factor.enable_totp(bearer_auth.user_id, &hashes).await?;
+
+    slog::info!(logger, "2FA enabled");
+
+    Ok(web::Json(RecoveryCodes { recovery_codes }))
+}
+
+/// Disables 2FA, requires current code or recovery code
+pub async fn disable_two_factor(two_factor: web::Data<dyn TwoFactorRepository>, rate_limits: web::Data<RateLimits>, code: ValidatedJson<TwoFactorCode>, bearer_auth: BearerAuth, req: HttpRequest, logger: RequestLogger) -> Result<String> {
+    bearer_auth.require_login_token()?;
+
+    match two_factor.select_totp_secret(bearer_auth.user_id).await? {
+        Some(totp_secret) if totp_secret.enabled => {
+            let ip = rate_limits.login.client_ip(&req);
+            check_code(two_factor.as_ref(), &rate_limits, &ip, bearer_auth.user_id, &totp_secret, &code.code).await?;
+        },
+        _ => return Err(ServiceError { status_code: StatusCode::BadRequest, detail: Some("2FA is not enabled".to_string()) }.into()),
+    }
+
+    two_factor.delete_totp(bearer_auth.user_id).await?;
+
+    slog::info!(logger, "2FA disabled");
+
+    Ok(bearer_auth.user_id.to_string())
+}
+
+/// Second step of login: challenge token from `login` and TOTP or recovery code are exchanged for bearer token
+pub async fn login_two_factor(users: web::Data<dyn UserRepository>, two_factor: web::Data<dyn TwoFactorRepository>, rate_limits: web::Data<RateLimits>, jwt_keys: web::Data<JwtKeys>, login_info: ValidatedJson<TwoFactorLogin>, req: HttpRequest, logger: RequestLogger) -> Result<String> {
+    let ip = rate_limits.login.client_ip(&req);
+
+    two_factor_login_token(users.as_ref(), two_factor.as_ref(), &rate_limits, &jwt_keys, &login_info, &ip, &logger).await
+}
+
+/// Bearer token of the challenge, code failures are throttled by `ip`. Shared by REST and gRPC api
+pub(crate) async fn two_factor_login_token(users: &dyn UserRepository, two_factor: &dyn TwoFactorRepository, rate_limits: &RateLimits, jwt_keys: &JwtKeys, login_info: &TwoFactorLogin, ip: &str, logger: &RequestLogger) -> Result<String> {
+    let user_id = jwt_keys.verify::<TwoFactorChallengeClaim>(&login_info.challenge_token)
+        .map(|claims| claims.challenge_user_id)
+        .ok_or(ServiceError { status_code: StatusCode::Unauthorized, detail: Some("Invalid or expired challenge token".to_string()) })?;
+
+    let totp_secret = two_factor.select_totp_secret(user_id).await?
+        .filter(|totp_secret| totp_secret.enabled)
+        .ok_or(ServiceError { status_code: StatusCode::Unauthorized, detail: Some("Invalid or expired challenge token".to_string()) })?;
+
+    check_code(two_factor, rate_limits, ip, user_id, &totp_secret, &login_info.code).await?;
+
+    let token = login_token(jwt_keys, users, user_id).await?;
+
+    slog::info!(logger, "User logged in with 2FA"; "login_user_id" => %user_id);
+
+    Ok(token)
+}
+
+/// Accepts TOTP code of not used step or removes matched recovery code.
+/// Failures are throttled like password failures, with user id as the account
+async fn check_code(two_factor: &dyn TwoFactorRepository, rate_limits: &RateLimits, ip: &str, user_id: Uuid, totp_secret: &TotpSecret, code: &str) -> Result<()> {
+    let throttle = &rate_limits.login;
+    let account = format!("2fa:{user_id}");
+
+    if let Err(retry_after) = throttle.check(ip, &account) {
+        return Err(TooManyRequestsError::new(TOO_MANY_ATTEMPTS, retry_after).into());
+    }
+
+    let accepted = match totp::verify(&totp_secret.secret, code, Utc::now().timestamp() as u64) {
+        Some(step) => two_factor.use_totp_step(user_id, step as i64).await?,
+        None => two_factor.use_recovery_code(user_id, &recovery_code_hash(code)).await?,
+    };
+
+    if !accepted {
+        throttle.failed(ip, &account);
+        return Err(invalid_code().into());
+    }
+
+    throttle.succeeded(&account);
+
+    Ok(())
+}
+
+fn invalid_code() -> ServiceError {
+    ServiceError { status_code: StatusCode::Unauthorized, detail: Some("Invalid code".to_string()) }
+}
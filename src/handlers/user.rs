@@ -1,56 +1,264 @@
-use std::env;
-
 use actix_web::{
+    http::header::ContentType,
     web,
+    HttpRequest,
+    HttpResponse,
     Result
 };
 use chrono::{
     Utc,
     Duration
 };
-use jsonwebtoken::{
-    EncodingKey,
-    encode
-};
-use sqlx::PgPool;
+use uuid::Uuid;
 
 use crate::{
     models::*,
-    db::user
+    middlewares::{
+        BearerAuth,
+        ValidatedJson,
+        RequestLogger
+    },
+    db::{
+        TwoFactorRepository,
+        UserRepository
+    },
+    utils::{
+        jwt::JwtKeys,
+        rate_limit::{
+            LoginThrottle,
+            RateLimits
+        }
+    }
 };
 
-pub async fn register(db_pool: web::Data<PgPool>, new_user_info: web::Json<NewUser>) -> Result<String, ServiceError> {
-    if user::is_user_exist(&new_user_info.login, &**db_pool).await? {
+pub async fn register(users: web::Data<dyn UserRepository>, new_user_info: ValidatedJson<NewUser>, logger: RequestLogger) -> Result<String, ServiceError> {
+    if users.is_user_exist(&new_user_info.login).await? {
         return Err(ServiceError { status_code: StatusCode::BadRequest, detail: Some(format!("User with login name \"{}\" already exists", new_user_info.login)) })
     }
 
-    let id = user::insert_user(&*new_user_info, &**db_pool).await?;
+    let id = users.insert_user(&*new_user_info).await?;
+    slog::info!(logger, "User registered"; "new_user_id" => %id);
         
     Ok(id.to_string())
 }
 
-const BEARER_KEY_ENV: &'static str = "BEARER_KEY";
+const LOGIN_TOKEN_LIFETIME_HOURS: i64 = 1;
+const TWO_FACTOR_CHALLENGE_LIFETIME_SECONDS: i64 = 5 * 60;
+
+/// Unknown login name and wrong password are not distinguished
+pub(crate) const INVALID_CREDENTIALS: &str = "Invalid login or password";
+pub(crate) const TOO_MANY_ATTEMPTS: &str = "Too many login attempts, try again later";
+
+/// Returns bearer token, or `202` with 2FA challenge if user has enabled 2FA
+pub async fn login(
+    users: web::Data<dyn UserRepository>,
+    two_factor: web::Data<dyn TwoFactorRepository>,
+    rate_limits: web::Data<RateLimits>,
+    jwt_keys: web::Data<JwtKeys>,
+    login_info: ValidatedJson<Login>,
+    req: HttpRequest,
+    logger: RequestLogger
+) -> Result<HttpResponse> {
+    let ip = rate_limits.login.client_ip(&req);
+    let user_id = authenticate(users.as_ref(), &rate_limits.login, &ip, &login_info, &logger).await?;
+
+    Ok(login_response(users.as_ref(), two_factor.as_ref(), &jwt_keys, user_id, &logger).await?)
+}
+
+/// User of the login name and password, attempts are throttled by ip and login name.
+/// Shared by login of REST and gRPC api
+pub(crate) async fn authenticate(users: &dyn UserRepository, throttle: &LoginThrottle, ip: &str, login_info: &Login, logger: &RequestLogger) -> Result<Uuid> {
+    if let Err(retry_after) = throttle.check(ip, &login_info.login) {
+        slog::warn!(logger, "Login attempt rejected"; "ip" => ip, "retry_after" => retry_after.as_secs());
+        return Err(TooManyRequestsError::new(TOO_MANY_ATTEMPTS, retry_after).into());
+    }
 
-pub async fn login(db_pool: web::Data<PgPool>, login_info: web::Json<Login>) -> Result<String, ServiceError> {
-    let user_id = user::select_user_id(&login_info.login, &login_info.password, &**db_pool).await?
-        .ok_or(ServiceError { status_code: StatusCode::BadRequest, detail: Some(format!("User with login name \"{}\" not found", login_info.login)) })?;
-    
-    let date = Utc::now() + Duration::hours(1);
+    let user_id = match users.select_user_id(&login_info.login, &login_info.password).await? {
+        Some(user_id) => user_id,
+        None => {
+            throttle.failed(ip, &login_info.login);
+            slog::info!(logger, "Login failed"; "ip" => ip);
+            return Err(ServiceError { status_code: StatusCode::Unauthorized, detail: Some(INVALID_CREDENTIALS.to_string()) }.into());
+        },
+    };
+
+    throttle.succeeded(&login_info.login);
+
+    Ok(user_id)
+}
+
+/// Result of authentication by password or by identity provider
+pub(crate) enum LoginResult {
+    Token(String),
+    TwoFactor(TwoFactorChallenge),
+}
+
+/// Bearer token of authenticated user, or 2FA challenge if user has enabled 2FA
+pub(crate) async fn login_result(
+    users: &dyn UserRepository,
+    two_factor: &dyn TwoFactorRepository,
+    jwt_keys: &JwtKeys,
+    user_id: Uuid,
+    logger: &RequestLogger
+) -> Result<LoginResult, ServiceError> {
+    if two_factor.select_totp_secret(user_id).await?.is_some_and(|totp| totp.enabled) {
+        slog::info!(logger, "2FA challenge issued"; "login_user_id" => %user_id);
+        return Ok(LoginResult::TwoFactor(two_factor_challenge(jwt_keys, user_id)?));
+    }
 
-    let user_claim = UserClaim {
+    let token = login_token(jwt_keys, users, user_id).await?;
+
+    slog::info!(logger, "User logged in"; "login_user_id" => %user_id);
+
+    Ok(LoginResult::Token(token))
+}
+
+/// Bearer token of authenticated user, or `202` with 2FA challenge if user has enabled 2FA
+pub(crate) async fn login_response(
+    users: &dyn UserRepository,
+    two_factor: &dyn TwoFactorRepository,
+    jwt_keys: &JwtKeys,
+    user_id: Uuid,
+    logger: &RequestLogger
+) -> Result<HttpResponse, ServiceError> {
+    Ok(match login_result(users, two_factor, jwt_keys, user_id, logger).await? {
+        LoginResult::Token(token) => HttpResponse::Ok().content_type(ContentType::plaintext()).body(token),
+        LoginResult::TwoFactor(challenge) => HttpResponse::Accepted().json(challenge),
+    })
+}
+
+/// Login token of the current session version of the user
+pub(crate) async fn login_token(jwt_keys: &JwtKeys, users: &dyn UserRepository, user_id: Uuid) -> Result<String, ServiceError> {
+    let session_version = users.select_session_version(user_id).await?
+        .ok_or(user_not_found())?;
+
+    sign_login_token(jwt_keys, user_id, session_version)
+}
+
+fn sign_login_token(jwt_keys: &JwtKeys, user_id: Uuid, session_version: i32) -> Result<String, ServiceError> {
+    let date = Utc::now() + Duration::hours(LOGIN_TOKEN_LIFETIME_HOURS);
+
+    jwt_keys.sign(&UserClaim {
+        exp: date.timestamp() as usize,
+        user_id,
+        session_version,
+    })
+}
+
+fn two_factor_challenge(jwt_keys: &JwtKeys, user_id: Uuid) -> Result<TwoFactorChallenge, ServiceError> {
+    let date = Utc::now() + Duration::seconds(TWO_FACTOR_CHALLENGE_LIFETIME_SECONDS);
+
+    let challenge_token = jwt_keys.sign(&TwoFactorChallengeClaim {
         exp: date.timestamp() as usize,
-        user_id: user_id,
+        challenge_user_id: user_id,
+    })?;
+
+    Ok(TwoFactorChallenge { challenge_token, expires_in: TWO_FACTOR_CHALLENGE_LIFETIME_SECONDS })
+}
+
+pub async fn get_user_profile(users: web::Data<dyn UserRepository>, two_factor: web::Data<dyn TwoFactorRepository>, bearer_auth: BearerAuth) -> Result<web::Json<UserProfile>, ServiceError> {
+    let login = users.select_user_login(bearer_auth.user_id).await?
+        .ok_or(user_not_found())?;
+
+    let email = users.select_user_email(bearer_auth.user_id).await?;
+
+    let two_factor_enabled = two_factor.select_totp_secret(bearer_auth.user_id).await?
+        .is_some_and(|totp| totp.enabled);
+
+    Ok(web::Json(UserProfile {
+        id: bearer_auth.user_id,
+        login,
+        email_verified: email.as_ref().is_some_and(|email| email.verified),
+        email: email.map(|email| email.email),
+        two_factor_enabled,
+    }))
+}
+
+/// Login tokens issued before are revoked, new token for the current client is returned
+pub async fn change_password(
+    users: web::Data<dyn UserRepository>,
+    rate_limits: web::Data<RateLimits>,
+    jwt_keys: web::Data<JwtKeys>,
+    bearer_auth: BearerAuth,
+    new_password: ValidatedJson<NewPassword>,
+    req: HttpRequest,
+    logger: RequestLogger
+) -> Result<HttpResponse> {
+    bearer_auth.require_login_token()?;
+
+    let (ip, account) = password_attempt(&rate_limits.login, &req, bearer_auth.user_id)?;
+
+    let session_version = match users.update_user_password(bearer_auth.user_id, &new_password.old_password, &new_password.new_password).await? {
+        Some(session_version) => session_version,
+        None => {
+            rate_limits.login.failed(&ip, &account);
+            return Err(invalid_password().into());
+        },
     };
 
-    let bearer_key = env::var(BEARER_KEY_ENV)
-        .expect(&*format!("Env {BEARER_KEY_ENV} not found"));
+    rate_limits.login.succeeded(&account);
+
+    let token = sign_login_token(&jwt_keys, bearer_auth.user_id, session_version)?;
 
-    let token = encode(
-            &jsonwebtoken::Header::default(),
-            &user_claim,
-            &EncodingKey::from_secret(bearer_key.as_bytes()),
-        )
-        .map_err(|e| ServiceError { status_code: StatusCode::InternalError, detail: Some(e.to_string()) })?;
+    slog::info!(logger, "Password changed");
+
+    Ok(HttpResponse::Ok().content_type(ContentType::plaintext()).body(token))
+}
+
+pub async fn change_login(users: web::Data<dyn UserRepository>, bearer_auth: BearerAuth, new_login: ValidatedJson<NewLogin>, logger: RequestLogger) -> Result<String, ServiceError> {
+    bearer_auth.require_login_token()?;
+
+    if !users.update_user_login(bearer_auth.user_id, &new_login.login).await? {
+        return Err(ServiceError { status_code: StatusCode::BadRequest, detail: Some(format!("User with login name \"{}\" already exists", new_login.login)) });
+    }
+
+    slog::info!(logger, "Login changed");
+
+    Ok(bearer_auth.user_id.to_string())
+}
 
-    Ok(token)
-}
\ No newline at end of file
+/// Deletes account with lists, tasks and tokens
+pub async fn delete_user(
+    users: web::Data<dyn UserRepository>,
+    rate_limits: web::Data<RateLimits>,
+    bearer_auth: BearerAuth,
+    delete_info: ValidatedJson<DeleteUser>,
+    req: HttpRequest,
+    logger: RequestLogger
+) -> Result<String> {
+    bearer_auth.require_login_token()?;
+
+    let (ip, account) = password_attempt(&rate_limits.login, &req, bearer_auth.user_id)?;
+
+    if !users.delete_user(bearer_auth.user_id, &delete_info.password).await? {
+        rate_limits.login.failed(&ip, &account);
+        return Err(invalid_password().into());
+    }
+
+    rate_limits.login.succeeded(&account);
+
+    slog::info!(logger, "User deleted");
+
+    Ok(bearer_auth.user_id.to_string())
+}
+
+/// Password confirmations of account changes are throttled like login, with user id as the account.
+/// Returns ip and account keys of the throttle
+fn password_attempt(throttle: &LoginThrottle, req: &HttpRequest, user_id: Uuid) -> Result<(String, String)> {
+    let ip = throttle.client_ip(req);
+    let account = format!("password:{user_id}");
+
+    if let Err(retry_after) = throttle.check(&ip, &account) {
+        return Err(TooManyRequestsError::new(TOO_MANY_ATTEMPTS, retry_after).into());
+    }
+
+    Ok((ip, account))
+}
+
+fn invalid_password() -> ServiceError {
+    ServiceError { status_code: StatusCode::Forbidden, detail: Some("Invalid password".to_string()) }
+}
+
+pub(crate) fn user_not_found() -> ServiceError {
+    ServiceError { status_code: StatusCode::NotFound, detail: Some("User not found".to_string()) }
+}
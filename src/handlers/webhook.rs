@@ -0,0 +1,104 @@
+use actix_web::{
+    web,
+    Result
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    models::*,
+    middlewares::{
+        BearerAuth,
+        RequestLogger,
+        ValidatedJson
+    },
+    db::WebhookRepository,
+    utils::webhook::{
+        is_private_target,
+        WebhookConfig,
+        PRIVATE_TARGET
+    }
+};
+
+const MAX_WEBHOOKS: usize = 10;
+const DELIVERY_LOG_DEFAULT_LIMIT: i64 = 50;
+const DELIVERY_LOG_MAX_LIMIT: i64 = 500;
+
+#[derive(Deserialize)]
+pub struct DeliveryLogQuery {
+    pub limit: Option<i64>,
+}
+
+pub async fn new_webhook(webhooks: web::Data<dyn WebhookRepository>, config: web::Data<WebhookConfig>, new_webhook_info: ValidatedJson<NewWebhook>, bearer_auth: BearerAuth, logger: RequestLogger) -> Result<web::Json<CreatedWebhook>, ServiceError> {
+    bearer_auth.require_login_token()?;
+    check_target(&new_webhook_info.url, &config)?;
+
+    if webhooks.select_webhooks(bearer_auth.user_id).await?.len() >= MAX_WEBHOOKS {
+        return Err(ServiceError { status_code: StatusCode::Conflict, detail: Some(format!("User can have at most {MAX_WEBHOOKS} webhooks")) });
+    }
+
+    let secret = new_webhook_info.secret.clone().unwrap_or_else(generate_webhook_secret);
+
+    let info = webhooks.insert_webhook(bearer_auth.user_id, &new_webhook_info, &secret).await?;
+    slog::info!(logger, "Webhook created"; "webhook_id" => %info.id);
+
+    Ok(web::Json(CreatedWebhook { info, secret }))
+}
+
+pub async fn get_webhooks(webhooks: web::Data<dyn WebhookRepository>, bearer_auth: BearerAuth) -> Result<web::Json<Vec<WebhookInfo>>, ServiceError> {
+    bearer_auth.require_login_token()?;
+
+    Ok(web::Json(webhooks.select_webhooks(bearer_auth.user_id).await?))
+}
+
+pub async fn update_webhook(webhook_id: web::Path<Uuid>, webhooks: web::Data<dyn WebhookRepository>, config: web::Data<WebhookConfig>, update: ValidatedJson<UpdateWebhook>, bearer_auth: BearerAuth, logger: RequestLogger) -> Result<web::Json<WebhookInfo>, ServiceError> {
+    bearer_auth.require_login_token()?;
+
+    if let Some(url) = &update.url {
+        check_target(url, &config)?;
+    }
+
+    let info = webhooks.update_webhook(bearer_auth.user_id, *webhook_id, &update).await?
+        .ok_or(webhook_not_found())?;
+
+    slog::info!(logger, "Webhook updated"; "webhook_id" => %info.id, "enabled" => info.enabled);
+
+    Ok(web::Json(info))
+}
+
+pub async fn delete_webhook(webhook_id: web::Path<Uuid>, webhooks: web::Data<dyn WebhookRepository>, bearer_auth: BearerAuth, logger: RequestLogger) -> Result<String, ServiceError> {
+    bearer_auth.require_login_token()?;
+
+    if !webhooks.delete_webhook(bearer_auth.user_id, *webhook_id).await? {
+        return Err(webhook_not_found());
+    }
+
+    slog::info!(logger, "Webhook deleted"; "webhook_id" => %webhook_id);
+
+    Ok(webhook_id.to_string())
+}
+
+/// Latest deliveries of the webhook, newest first
+pub async fn get_webhook_deliveries(webhook_id: web::Path<Uuid>, query: web::Query<DeliveryLogQuery>, webhooks: web::Data<dyn WebhookRepository>, bearer_auth: BearerAuth) -> Result<web::Json<Vec<WebhookDelivery>>, ServiceError> {
+    bearer_auth.require_login_token()?;
+
+    let limit = query.limit.unwrap_or(DELIVERY_LOG_DEFAULT_LIMIT).clamp(1, DELIVERY_LOG_MAX_LIMIT);
+
+    let deliveries = webhooks.select_webhook_deliveries(bearer_auth.user_id, *webhook_id, limit).await?
+        .ok_or(webhook_not_found())?;
+
+    Ok(web::Json(deliveries))
+}
+
+/// Webhooks must not be sent to internal services, names are checked again on delivery
+fn check_target(url: &str, config: &WebhookConfig) -> Result<(), ServiceError> {
+    if !config.allow_private_targets && is_private_target(url) {
+        return Err(ServiceError { status_code: StatusCode::UnprocessableEntity, detail: Some(PRIVATE_TARGET.to_string()) });
+    }
+
+    Ok(())
+}
+
+fn webhook_not_found() -> ServiceError {
+    ServiceError { status_code: StatusCode::NotFound, detail: Some("Webhook not found".to_string()) }
+}
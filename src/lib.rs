@@ -0,0 +1,8 @@
+pub mod utils;
+pub mod handlers;
+pub mod models;
+pub mod middlewares;
+pub mod db;
+pub mod routes;
+pub mod graphql;
+pub mod grpc;
@@ -1,113 +1,145 @@
-mod utils;
-mod handlers;
-mod models;
-mod middlewares;
-mod db;
-
 use actix_web::{
     middleware::Logger,
-    get,
     web,
     App,
-    HttpServer,
-    Responder,
+    HttpServer
 };
 use std::{
     env,
-    net::Ipv4Addr
+    net::{
+        Ipv4Addr,
+        SocketAddr
+    }
 };
 use slog;
 
-use crate::handlers::*;
-
-#[get("/ping")]
-async fn ping(logger: web::Data<slog::Logger>) -> impl Responder {
-    slog::info!(logger, "pong");
-    format!("pong")
-}
+use todo_list_rs::{
+    grpc,
+    middlewares::{
+        AdminToken,
+        RequestId,
+        TokenCheck,
+        Tracing
+    },
+    routes,
+    utils
+};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()>{
     dotenv::dotenv().ok();
 
-    let (logger, _scope_guard) = utils::logging::create_logger();
+    let utils::logging::Logging {
+        logger,
+        scope_guard: _scope_guard,
+        reopen_handle: log_reopen_handle,
+        level_control: log_level_control
+    } = utils::logging::create_logger();
 
     slog::info!(logger, "Logger created");
 
-    let db_pool = utils::db::prepare_db(&logger).await?;
+    spawn_log_reopen_on_sighup(log_reopen_handle, logger.clone())?;
+
+    let tracer_provider = utils::telemetry::init_tracing(&logger)?;
+
+    let storage = utils::db::prepare_storage(&logger).await?;
+
+    let webhook_config = utils::webhook::WebhookConfig::from_env();
+    let webhook_dispatcher = utils::webhook::WebhookDispatcher::new(storage.webhooks.clone(), webhook_config.clone(), logger.clone())?
+        .spawn();
 
     let (ip,port) = get_address();
     slog::info!(logger, "Starting server on:[{ip}:{port}] ...");
     
     let actix_logger = logger.clone();
-    let actix_db_pool = db_pool.clone();
+    let actix_storage = storage.clone();
+    let actix_log_level_control = log_level_control.clone();
+    // shared by workers, otherwise every worker would have own limits
+    let actix_rate_limits = web::Data::new(utils::rate_limit::RateLimits::from_env());
+    let actix_jwt_keys = web::Data::new(utils::jwt::JwtKeys::from_env()?);
+    let actix_mailer = web::Data::from(utils::mail::from_env(&logger)?);
+    // OpenID Connect login is registered only if provider is configured
+    let actix_oidc = utils::oidc::OidcProvider::from_env().map(web::Data::new);
+    let actix_webhook_config = web::Data::new(webhook_config);
+    let actix_token_check = web::Data::new(TokenCheck::from_env());
+    let actix_admin_token = web::Data::new(AdminToken::from_env());
+
+    // gRPC api is served only if its port is configured
+    let grpc_server = match get_grpc_port() {
+        Some(grpc_port) => {
+            slog::info!(logger, "Starting gRPC server on:[{ip}:{grpc_port}] ...");
+            let state = grpc::GrpcState::new(&storage, actix_jwt_keys.clone(), actix_rate_limits.clone(), TokenCheck::from_env(), logger.clone());
+            Some(grpc::spawn(state, SocketAddr::from((ip, grpc_port)))?)
+        },
+        None => None,
+    };
     
     HttpServer::new(move || {
         App::new()
             .app_data(web::Data::new(actix_logger.clone()))
-            .app_data(web::Data::new(actix_db_pool.clone()))
-            .wrap(Logger::default())
-            .service(ping)
-            .service(
-                web::scope("/api")
-                    .service(
-                        web::scope("/user")
-                            .service(
-                                web::resource("/register")
-                                    .route(web::post().to(register))
-                            )
-                            .service(
-                                web::resource("/login")
-                                    .route(web::post().to(login))
-                            )
-                    )
-                    .service(
-                        web::scope("/list")
-                            .service(
-                                web::resource("")
-                                    .route(web::get().to(get_list))
-                                    .route(web::post().to(new_list))
-                                    .route(web::delete().to(delete_list))
-                                    .route(web::patch().to(update_list))
-                            )
-                    )
-                    .service(
-                        web::scope("/task")
-                            .service(
-                                web::resource("")
-                                    .route(web::get().to(get_tasks))
-                                    .route(web::post().to(new_task))
-                            )
-                            .service(
-                                web::resource("range")
-                                    .route(web::get().to(get_tasks_range))
-                            )
-                            .service(
-                                web::scope("/{task_id}")
-                                    .service(
-                                        web::resource("")
-                                            .route(web::delete().to(delete_tasks))
-                                            .route(web::patch().to(update_task))
-                                    )
-                                    .service(
-                                        web::resource("/move")
-                                            .route(web::post().to(move_task))
-                                    )
-                            )
-                    )
-            )
+            .configure(routes::storage_data(actix_storage.clone()))
+            .app_data(web::Data::new(actix_log_level_control.clone()))
+            .app_data(actix_rate_limits.clone())
+            .app_data(actix_jwt_keys.clone())
+            .app_data(actix_mailer.clone())
+            .app_data(actix_webhook_config.clone())
+            .app_data(actix_token_check.clone())
+            .app_data(actix_admin_token.clone())
+            .configure(|cfg| if let Some(oidc) = &actix_oidc {
+                cfg.app_data(oidc.clone());
+            })
+            .wrap(RequestId::new(actix_logger.clone()))
+            .wrap(Tracing)
+            .wrap(Logger::new("%a \"%r\" %s %b \"%{Referer}i\" \"%{User-Agent}i\" %T %{x-request-id}o"))
+            .configure(routes::configure)
     })
     .bind((ip.to_string(), port))?
     .run()
     .await?;
 
-    db_pool.close().await;
+    webhook_dispatcher.abort();
+    if let Some(grpc_server) = grpc_server {
+        // connections are dropped before the global logger, which they log to
+        grpc_server.abort();
+        let _ = grpc_server.await;
+    }
+    storage.close().await;
+
+    if let Some(tracer_provider) = tracer_provider {
+        tracer_provider.shutdown()?;
+    }
 
     Ok(())
 }
 
+/// Reopen log file on SIGHUP, for external logrotate
+#[cfg(unix)]
+fn spawn_log_reopen_on_sighup(reopen_handle: utils::log_file::ReopenHandle, logger: slog::Logger) -> anyhow::Result<()> {
+    use tokio::signal::unix::{
+        signal,
+        SignalKind
+    };
+
+    let mut hangup = signal(SignalKind::hangup())?;
+
+    tokio::spawn(async move {
+        while hangup.recv().await.is_some() {
+            slog::info!(logger, "SIGHUP received, reopening log file");
+            reopen_handle.reopen();
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn spawn_log_reopen_on_sighup(_reopen_handle: utils::log_file::ReopenHandle, _logger: slog::Logger) -> anyhow::Result<()> {
+    Ok(())
+}
+
 const TODO_SERVICE_PORT_ENV: &str = "TODO_SERVICE_PORT";
 const TODO_SERVICE_IP_ENV: &str = "TODO_SERVICE_IP";
+const TODO_SERVICE_GRPC_PORT_ENV: &str = "TODO_SERVICE_GRPC_PORT";
 
 fn get_address() -> (Ipv4Addr, u16) {
     let ip = env::var(TODO_SERVICE_IP_ENV)
@@ -121,4 +153,11 @@ fn get_address() -> (Ipv4Addr, u16) {
         .expect(&*format!("Env {TODO_SERVICE_PORT_ENV} must be valid u32"));
 
     (ip, port)
-}
\ No newline at end of file
+}
+
+fn get_grpc_port() -> Option<u16> {
+    env::var(TODO_SERVICE_GRPC_PORT_ENV)
+        .ok()
+        .filter(|port| !port.is_empty())
+        .map(|port| port.parse::<u16>().unwrap_or_else(|_| panic!("Env {TODO_SERVICE_GRPC_PORT_ENV} must be valid u16")))
+}
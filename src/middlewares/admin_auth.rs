@@ -0,0 +1,111 @@
+use std::env;
+
+use actix_web::{
+    dev,
+    web,
+    FromRequest,
+    HttpRequest
+};
+use futures::future::{
+    err,
+    ok,
+    Ready
+};
+
+use crate::models::*;
+
+/// Access to admin api, request must have `Authorization: Bearer <TODO_SERVICE_ADMIN_TOKEN>` header.
+/// Admin api is disabled if env isn't set
+pub struct AdminAuth;
+
+const TODO_SERVICE_ADMIN_TOKEN_ENV: &str = "TODO_SERVICE_ADMIN_TOKEN";
+
+/// Token of admin api, read once on start and shared by workers as `web::Data`
+#[derive(Clone, Default)]
+pub struct AdminToken(Option<String>);
+
+impl AdminToken {
+    pub fn new(token: &str) -> Self {
+        Self(Some(token.to_string()).filter(|token| !token.is_empty()))
+    }
+
+    pub fn from_env() -> Self {
+        Self::new(&env::var(TODO_SERVICE_ADMIN_TOKEN_ENV).unwrap_or_default())
+    }
+}
+
+impl FromRequest for AdminAuth {
+    type Error = ServiceError;
+    type Future = Ready<Result<AdminAuth, ServiceError>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut dev::Payload) -> Self::Future {
+        let admin_token = match req.app_data::<web::Data<AdminToken>>().and_then(|token| token.0.clone()) {
+            Some(token) => token,
+            None => return err(ServiceError { status_code: StatusCode::NotFound, detail: Some("Admin api is disabled".to_string()) }),
+        };
+
+        let token = req.headers()
+            .get("Authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer"))
+            .map(|token| token.trim());
+
+        match token {
+            Some(token) if constant_time_eq(token.as_bytes(), admin_token.as_bytes()) => ok(AdminAuth),
+            Some(_) => err(ServiceError { status_code: StatusCode::Unauthorized, detail: Some("invalid admin token!".to_string()) }),
+            None => err(ServiceError { status_code: StatusCode::BadRequest, detail: Some("Authorization header not found".to_string()) }),
+        }
+    }
+}
+
+/// Every byte of the sent token is compared, so time depends only on its length,
+/// not on length of the admin token or length of the matched prefix
+fn constant_time_eq(sent: &[u8], expected: &[u8]) -> bool {
+    let mut diff = (sent.len() != expected.len()) as u8;
+
+    for (i, byte) in sent.iter().enumerate() {
+        diff |= byte ^ expected[i % expected.len()];
+    }
+
+    diff == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::TestRequest;
+
+    use super::*;
+
+    fn authorize(admin_token: AdminToken, header: Option<&str>) -> Result<AdminAuth, ServiceError> {
+        let mut req = TestRequest::default().app_data(web::Data::new(admin_token));
+        if let Some(header) = header {
+            req = req.insert_header(("Authorization", header));
+        }
+
+        AdminAuth::extract(&req.to_http_request()).into_inner()
+    }
+
+    #[test]
+    fn admin_token_is_checked() {
+        let admin_token = AdminToken::new("secret");
+
+        assert!(authorize(admin_token.clone(), Some("Bearer secret")).is_ok());
+        assert!(matches!(authorize(admin_token.clone(), Some("Bearer secret2")), Err(e) if matches!(e.status_code, StatusCode::Unauthorized)));
+        assert!(matches!(authorize(admin_token.clone(), Some("Bearer secreT")), Err(e) if matches!(e.status_code, StatusCode::Unauthorized)));
+        assert!(matches!(authorize(admin_token, None), Err(e) if matches!(e.status_code, StatusCode::BadRequest)));
+    }
+
+    #[test]
+    fn admin_api_is_disabled_without_token() {
+        assert!(matches!(authorize(AdminToken::new(""), Some("Bearer ")), Err(e) if matches!(e.status_code, StatusCode::NotFound)));
+        assert!(matches!(authorize(AdminToken::default(), Some("Bearer secret")), Err(e) if matches!(e.status_code, StatusCode::NotFound)));
+    }
+
+    #[test]
+    fn tokens_of_other_length_differ() {
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(!constant_time_eq(b"secretsecret", b"secret"));
+        assert!(!constant_time_eq(b"sec", b"secret"));
+        assert!(!constant_time_eq(b"", b"secret"));
+    }
+}
@@ -1,56 +1,175 @@
+use std::{
+    env,
+    time::Duration
+};
 
-use std::env;
-
-use crate::models::*;
+use crate::{
+    db::{
+        AccessTokenRepository,
+        UserRepository
+    },
+    models::*,
+    utils::jwt::JwtKeys
+};
 
 use actix_web::{
     dev,
+    web,
     FromRequest,
+    HttpMessage,
     HttpRequest
 };
-use futures::future::{
-    err,
-    ok,
-    Ready
-};
-use jsonwebtoken::{
-    decode,
-    Algorithm,
-    DecodingKey,
-    Validation
-};
+use futures::future::LocalBoxFuture;
 use uuid::Uuid;
 
+use super::RequestContext;
+
+pub const TODO_SERVICE_TOKEN_CHECK_INTERVAL_ENV: &str = "TODO_SERVICE_TOKEN_CHECK_INTERVAL";
+
+/// User of the request, authorized by login token (JWT) or personal access token
+#[derive(Debug, Clone)]
 pub struct BearerAuth {
     pub user_id: Uuid,
+    /// Scopes of personal access token, `None` for login token, which has full access
+    pub scopes: Option<Vec<TokenScope>>,
 }
 
-const BEARER_KEY_ENV: &'static str = "BEARER_KEY";
+impl BearerAuth {
+    pub fn require_scope(&self, scope: TokenScope) -> Result<(), ServiceError> {
+        match &self.scopes {
+            Some(scopes) if !scopes.contains(&scope) => Err(ServiceError { status_code: StatusCode::Forbidden, detail: Some(format!("Token has no scope \"{}\"", scope.as_str())) }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Access tokens can't be used to manage access tokens
+    pub fn require_login_token(&self) -> Result<(), ServiceError> {
+        match self.scopes {
+            Some(_) => Err(ServiceError { status_code: StatusCode::Forbidden, detail: Some("Login token is required".to_string()) }),
+            None => Ok(()),
+        }
+    }
+}
 
 impl FromRequest for BearerAuth {
     type Error = ServiceError;
-    type Future = Ready<Result<BearerAuth, ServiceError>>;
+    type Future = LocalBoxFuture<'static, Result<BearerAuth, ServiceError>>;
 
     fn from_request(req: &HttpRequest, _payload: &mut dev::Payload) -> Self::Future {
-        let auth = req.headers().get("Authorization");
-        match auth {
-            Some(_) => {
-                let _split: Vec<&str> = auth.unwrap().to_str().unwrap().split("Bearer").collect();
-                let token = _split[1].trim();
-
-                let bearer_key = env::var(BEARER_KEY_ENV)
-                    .expect(&*format!("Env {BEARER_KEY_ENV} not found"));
-
-                match decode::<UserClaim>(
-                    token,
-                    &DecodingKey::from_secret(bearer_key.as_bytes()),
-                    &Validation::new(Algorithm::HS256),
-                ) {
-                    Ok(claims) => ok(BearerAuth { user_id: claims.claims.user_id }),
-                    Err(_e) => err(ServiceError { status_code: StatusCode::Unauthorized, detail: Some("invalid token!".to_string())}),
-                }
+        // middlewares and handler share result, so access token is looked up once per request
+        if let Some(auth) = req.extensions().get::<BearerAuth>() {
+            let auth = auth.clone();
+            return Box::pin(async move { Ok(auth) });
+        }
+
+        let req = req.clone();
+
+        Box::pin(async move {
+            let auth = authorize(&req).await?;
+
+            if let Some(context) = req.extensions().get::<RequestContext>() {
+                context.set_user_id(auth.user_id);
             }
-            None => err(ServiceError { status_code: StatusCode::BadRequest, detail: Some("Authorization header not found".to_string())}),
+            req.extensions_mut().insert(auth.clone());
+
+            Ok(auth)
+        })
+    }
+}
+
+async fn authorize(req: &HttpRequest) -> Result<BearerAuth, ServiceError> {
+    let auth = req.headers().get("Authorization")
+        .ok_or(ServiceError { status_code: StatusCode::BadRequest, detail: Some("Authorization header not found".to_string())})?;
+
+    let token = auth.to_str().ok()
+        .and_then(bearer_token)
+        .ok_or(invalid_token())?;
+
+    authorize_token(req, token).await
+}
+
+/// Personal access token or login token (JWT)
+pub(super) async fn authorize_token(req: &HttpRequest, token: &str) -> Result<BearerAuth, ServiceError> {
+    let tokens = req.app_data::<web::Data<dyn AccessTokenRepository>>()
+        .ok_or(ServiceError { status_code: StatusCode::InternalError, detail: Some("Access token storage not found".to_string()) })?;
+
+    let jwt_keys = req.app_data::<web::Data<JwtKeys>>()
+        .ok_or(ServiceError { status_code: StatusCode::InternalError, detail: Some("Jwt keys not found".to_string()) })?;
+
+    let users = req.app_data::<web::Data<dyn UserRepository>>()
+        .ok_or(ServiceError { status_code: StatusCode::InternalError, detail: Some("User storage not found".to_string()) })?;
+
+    BearerAuth::from_token(token, tokens.as_ref(), users.as_ref(), jwt_keys).await
+}
+
+impl BearerAuth {
+    /// Checks personal access token or login token (JWT), also for tokens not sent in request header (GraphQL WebSocket, gRPC)
+    pub async fn from_token(token: &str, tokens: &dyn AccessTokenRepository, users: &dyn UserRepository, jwt_keys: &JwtKeys) -> Result<BearerAuth, ServiceError> {
+        if token.starts_with(ACCESS_TOKEN_PREFIX) {
+            let owner = tokens.use_access_token(&access_token_hash(token)).await?
+                .ok_or(invalid_token())?;
+
+            return Ok(BearerAuth { user_id: owner.user_id, scopes: Some(owner.scopes) });
         }
+
+        let claims = jwt_keys.verify::<UserClaim>(token)
+            .ok_or(invalid_token())?;
+
+        // password change and account deletion revoke login tokens
+        if users.select_session_version(claims.user_id).await? != Some(claims.session_version) {
+            return Err(invalid_token());
+        }
+
+        Ok(BearerAuth { user_id: claims.user_id, scopes: None })
     }
-}
\ No newline at end of file
+}
+
+/// Token of long-lived connection (GraphQL subscriptions, gRPC watch) is checked again every `interval`,
+/// so revoked token, changed password or deleted account ends the connection
+#[derive(Debug, Clone, Copy)]
+pub struct TokenCheck {
+    pub interval: Duration,
+}
+
+impl Default for TokenCheck {
+    fn default() -> Self {
+        Self { interval: Duration::from_secs(60) }
+    }
+}
+
+impl TokenCheck {
+    /// Interval in seconds from `TODO_SERVICE_TOKEN_CHECK_INTERVAL`
+    pub fn from_env() -> Self {
+        match env::var(TODO_SERVICE_TOKEN_CHECK_INTERVAL_ENV) {
+            Ok(value) => {
+                let seconds: u64 = value.parse().unwrap_or_else(|_| panic!("Env {TODO_SERVICE_TOKEN_CHECK_INTERVAL_ENV} has invalid value \"{value}\""));
+                Self { interval: Duration::from_secs(seconds.max(1)) }
+            },
+            Err(_) => Self::default(),
+        }
+    }
+
+    /// Completes with error, when the token is no longer valid. Storage errors are not a reason to end the connection,
+    /// the token is checked again after next interval
+    pub async fn until_invalid(&self, token: &str, tokens: &dyn AccessTokenRepository, users: &dyn UserRepository, jwt_keys: &JwtKeys) -> ServiceError {
+        let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + self.interval, self.interval);
+
+        loop {
+            interval.tick().await;
+
+            match BearerAuth::from_token(token, tokens, users, jwt_keys).await {
+                Err(e) if matches!(e.status_code, StatusCode::Unauthorized) => return e,
+                _ => continue,
+            }
+        }
+    }
+}
+
+/// Token of `Bearer <token>` value
+pub fn bearer_token(value: &str) -> Option<&str> {
+    value.trim().strip_prefix("Bearer").map(|token| token.trim())
+}
+
+fn invalid_token() -> ServiceError {
+    ServiceError { status_code: StatusCode::Unauthorized, detail: Some("invalid token!".to_string())}
+}
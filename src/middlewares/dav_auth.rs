@@ -0,0 +1,162 @@
+use std::ops::Deref;
+
+use actix_web::{
+    dev,
+    http::header,
+    web,
+    Error,
+    FromRequest,
+    HttpMessage,
+    HttpRequest
+};
+use futures::future::LocalBoxFuture;
+
+use crate::{
+    db::{
+        TwoFactorRepository,
+        UserRepository
+    },
+    handlers::{
+        INVALID_CREDENTIALS,
+        TOO_MANY_ATTEMPTS
+    },
+    models::*,
+    utils::rate_limit::RateLimits
+};
+
+use super::{
+    bearer_auth::authorize_token,
+    BearerAuth,
+    RequestContext
+};
+
+/// Challenge of `401` responses, native clients show login form for it
+const DAV_CHALLENGE: &str = "Basic realm=\"todo-list-rs\", charset=\"UTF-8\"";
+
+/// User of CalDAV request. Native clients know only Basic auth, so besides bearer token
+/// login with password or with personal access token as password is accepted.
+/// Password of user with enabled 2FA is rejected, such user needs access token.
+/// Requests are counted by the same per-user limit as api requests
+#[derive(Debug, Clone)]
+pub struct DavAuth(pub BearerAuth);
+
+impl Deref for DavAuth {
+    type Target = BearerAuth;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl FromRequest for DavAuth {
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<DavAuth, Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut dev::Payload) -> Self::Future {
+        let req = req.clone();
+
+        Box::pin(async move {
+            let auth = authorize(&req).await?;
+
+            if let Some(context) = req.extensions().get::<RequestContext>() {
+                context.set_user_id(auth.user_id);
+            }
+
+            let rate_limits = req.app_data::<web::Data<RateLimits>>();
+            if let Some(limiter) = rate_limits.and_then(|rate_limits| rate_limits.api.as_ref()) {
+                if let Err(retry_after) = limiter.check(&auth.user_id.to_string()) {
+                    return Err(TooManyRequestsError::new("Too many requests, try again later", retry_after).into());
+                }
+            }
+
+            req.extensions_mut().insert(auth.clone());
+
+            Ok(DavAuth(auth))
+        })
+    }
+}
+
+async fn authorize(req: &HttpRequest) -> Result<BearerAuth, Error> {
+    let auth = req.headers().get(header::AUTHORIZATION)
+        .and_then(|auth| auth.to_str().ok())
+        .map(|auth| auth.trim())
+        .ok_or_else(|| AuthenticateError::new("Authorization header not found", DAV_CHALLENGE))?;
+
+    if let Some(token) = auth.strip_prefix("Bearer") {
+        return authorize_token(req, token.trim()).await.map_err(challenge);
+    }
+
+    let (login, password) = auth.strip_prefix("Basic")
+        .and_then(|credentials| basic_credentials(credentials.trim()))
+        .ok_or_else(|| AuthenticateError::new(INVALID_CREDENTIALS, DAV_CHALLENGE))?;
+
+    let users = req.app_data::<web::Data<dyn UserRepository>>()
+        .ok_or(ServiceError { status_code: StatusCode::InternalError, detail: Some("User storage not found".to_string()) })?;
+
+    if password.starts_with(ACCESS_TOKEN_PREFIX) {
+        let auth = authorize_token(req, &password).await.map_err(challenge)?;
+
+        // token of another user must not be accepted with the login
+        if users.select_user_login(auth.user_id).await?.as_deref() != Some(login.as_str()) {
+            return Err(AuthenticateError::new(INVALID_CREDENTIALS, DAV_CHALLENGE).into());
+        }
+
+        return Ok(auth);
+    }
+
+    // password is sent with every request, so only failed attempts are throttled
+    let rate_limits = req.app_data::<web::Data<RateLimits>>()
+        .ok_or(ServiceError { status_code: StatusCode::InternalError, detail: Some("Rate limits not found".to_string()) })?;
+    let throttle = &rate_limits.login;
+    let ip = throttle.client_ip(req);
+
+    if let Err(retry_after) = throttle.check_lockout(&ip, &login) {
+        return Err(TooManyRequestsError::new(TOO_MANY_ATTEMPTS, retry_after).into());
+    }
+
+    let user_id = match users.select_user_id(&login, &password).await? {
+        Some(user_id) => user_id,
+        None => {
+            throttle.failed(&ip, &login);
+            return Err(AuthenticateError::new(INVALID_CREDENTIALS, DAV_CHALLENGE).into());
+        },
+    };
+
+    throttle.succeeded(&login);
+
+    let two_factor = req.app_data::<web::Data<dyn TwoFactorRepository>>()
+        .ok_or(ServiceError { status_code: StatusCode::InternalError, detail: Some("2FA storage not found".to_string()) })?;
+
+    if two_factor.select_totp_secret(user_id).await?.is_some_and(|totp| totp.enabled) {
+        return Err(AuthenticateError::new("2FA is enabled, use personal access token as password", DAV_CHALLENGE).into());
+    }
+
+    Ok(BearerAuth { user_id, scopes: None })
+}
+
+/// Login and password of `Basic` credentials, login can't contain colon
+fn basic_credentials(credentials: &str) -> Option<(String, String)> {
+    let decoded = String::from_utf8(base64::decode(credentials).ok()?).ok()?;
+    let (login, password) = decoded.split_once(':')?;
+
+    Some((login.to_string(), password.to_string()))
+}
+
+fn challenge(error: ServiceError) -> Error {
+    match error.status_code {
+        StatusCode::Unauthorized => AuthenticateError { error, challenge: DAV_CHALLENGE.to_string() }.into(),
+        _ => error.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basic_credentials_are_decoded() {
+        assert_eq!(basic_credentials(&base64::encode("user:pass:word")), Some(("user".to_string(), "pass:word".to_string())));
+        assert_eq!(basic_credentials(&base64::encode("user")), None);
+        assert_eq!(basic_credentials("not base64!"), None);
+    }
+}
@@ -0,0 +1,278 @@
+use std::{
+    env,
+    future::{
+        ready,
+        Ready
+    },
+    pin::Pin,
+    rc::Rc,
+    time::Duration
+};
+
+use actix_web::{
+    body::{
+        self,
+        BoxBody,
+        MessageBody
+    },
+    dev::{
+        self,
+        Service,
+        ServiceRequest,
+        ServiceResponse,
+        Transform
+    },
+    error::PayloadError,
+    http::{
+        header,
+        Method
+    },
+    web,
+    Error,
+    FromRequest,
+    HttpResponse
+};
+use futures::{
+    future::LocalBoxFuture,
+    Stream,
+    StreamExt
+};
+use sha2::{
+    Digest,
+    Sha256
+};
+
+use super::BearerAuth;
+use crate::{
+    db::IdempotencyRepository,
+    models::*
+};
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+/// Set on responses which are replayed from storage
+pub const IDEMPOTENT_REPLAYED_HEADER: &str = "idempotent-replayed";
+
+const TODO_SERVICE_IDEMPOTENCY_TTL_ENV: &str = "TODO_SERVICE_IDEMPOTENCY_TTL";
+
+const DEFAULT_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+const KEY_MAX_LEN: usize = 255;
+/// Request body is read before handler to compute fingerprint, so it is limited here
+const DEFAULT_BODY_MAX_SIZE: usize = 256 * 1024;
+
+/// Scope of keys of requests without `Authorization` header (register)
+const ANONYMOUS_SCOPE: &str = "anonymous";
+
+/// `Idempotency-Key` support for POST requests. Key is unique per user, response for the key is stored
+/// for `TODO_SERVICE_IDEMPOTENCY_TTL` seconds and replayed on retries with the same method, path and body.
+/// Responses with 5xx status are not stored, so such requests can be retried
+pub struct Idempotency {
+    ttl: Duration,
+    body_max_size: usize,
+}
+
+impl Idempotency {
+    pub fn from_env() -> Self {
+        let ttl = env::var(TODO_SERVICE_IDEMPOTENCY_TTL_ENV)
+            .ok()
+            .map(|value| value.parse::<u64>().unwrap_or_else(|_| panic!("Env {TODO_SERVICE_IDEMPOTENCY_TTL_ENV} must be valid u64")))
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_TTL);
+
+        Self { ttl, body_max_size: DEFAULT_BODY_MAX_SIZE }
+    }
+
+    /// Limit of the body for resources accepting files (import)
+    pub fn body_max_size(mut self, body_max_size: usize) -> Self {
+        self.body_max_size = body_max_size;
+        self
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for Idempotency
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Transform = IdempotencyMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(IdempotencyMiddleware { service: Rc::new(service), ttl: self.ttl, body_max_size: self.body_max_size }))
+    }
+}
+
+pub struct IdempotencyMiddleware<S> {
+    service: Rc<S>,
+    ttl: Duration,
+    body_max_size: usize,
+}
+
+impl<S, B> Service<ServiceRequest> for IdempotencyMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    dev::forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let ttl = self.ttl;
+        let body_max_size = self.body_max_size;
+
+        Box::pin(async move {
+            let key = match req.headers().get(IDEMPOTENCY_KEY_HEADER) {
+                Some(key) if req.method() == Method::POST => key.to_str().ok().map(|key| key.to_string()),
+                _ => return service.call(req).await.map(|res| res.map_into_boxed_body()),
+            };
+
+            let key = match key.filter(|key| !key.is_empty() && key.len() <= KEY_MAX_LEN) {
+                Some(key) => key,
+                None => return Ok(req.error_response(ServiceError {
+                    status_code: StatusCode::BadRequest,
+                    detail: Some(format!("Idempotency-Key must be from 1 to {KEY_MAX_LEN} visible ASCII characters")),
+                })),
+            };
+
+            let repository = match req.app_data::<web::Data<dyn IdempotencyRepository>>() {
+                Some(repository) => repository.clone(),
+                None => return Ok(req.error_response(ServiceError { status_code: StatusCode::InternalError, detail: Some("Idempotency storage not found".to_string()) })),
+            };
+
+            // invalid token is rejected by handler
+            let scope = match scope(&mut req).await {
+                Some(scope) => scope,
+                None => return service.call(req).await.map(|res| res.map_into_boxed_body()),
+            };
+
+            let body = match read_body(&mut req, body_max_size).await {
+                Ok(body) => body,
+                Err(e) => return Ok(req.error_response(e)),
+            };
+
+            let fingerprint = fingerprint(&req, &body);
+            req.set_payload(payload(body));
+
+            match repository.start_idempotent_request(&scope, &key, &fingerprint, ttl).await {
+                Ok(IdempotencyState::Started) => {},
+                Ok(IdempotencyState::Completed(response)) => return Ok(req.into_response(replay(response))),
+                Ok(IdempotencyState::InProgress) => return Ok(req.error_response(ServiceError {
+                    status_code: StatusCode::Conflict,
+                    detail: Some("Request with this Idempotency-Key is in progress".to_string()),
+                })),
+                Ok(IdempotencyState::Mismatch) => return Ok(req.error_response(ServiceError {
+                    status_code: StatusCode::UnprocessableEntity,
+                    detail: Some("Idempotency-Key was used for another request".to_string()),
+                })),
+                Err(e) => return Ok(req.error_response(e)),
+            }
+
+            let res = match service.call(req).await {
+                Ok(res) if !res.status().is_server_error() => res,
+                result => {
+                    cancel(repository.as_ref(), &scope, &key).await;
+                    return result.map(|res| res.map_into_boxed_body());
+                },
+            };
+
+            let (req, res) = res.into_parts();
+            let (res, body) = res.into_parts();
+
+            let body = match body::to_bytes(body).await {
+                Ok(body) => body,
+                Err(e) => {
+                    cancel(repository.as_ref(), &scope, &key).await;
+                    let e = ServiceError { status_code: StatusCode::InternalError, detail: Some(e.into().to_string()) };
+                    return Ok(ServiceResponse::from_err(e, req));
+                },
+            };
+
+            let response = IdempotentResponse {
+                status_code: res.status().as_u16(),
+                content_type: res.headers().get(header::CONTENT_TYPE).and_then(|value| value.to_str().ok()).map(|value| value.to_string()),
+                body: body.to_vec(),
+            };
+
+            // response is already produced, client retry will be conflict or new request after cancel
+            if let Err(e) = repository.complete_idempotent_request(&scope, &key, &response).await {
+                slog_scope::warn!("Idempotent response is not stored: {e}");
+                cancel(repository.as_ref(), &scope, &key).await;
+            }
+
+            Ok(ServiceResponse::new(req, res.set_body(BoxBody::new(body))))
+        })
+    }
+}
+
+/// User id from token, `None` if token is invalid
+async fn scope(req: &mut ServiceRequest) -> Option<String> {
+    if !req.headers().contains_key(header::AUTHORIZATION) {
+        return Some(ANONYMOUS_SCOPE.to_string());
+    }
+
+    let (http_req, payload) = req.parts_mut();
+    BearerAuth::from_request(http_req, payload).await
+        .ok()
+        .map(|auth| auth.user_id.to_string())
+}
+
+async fn read_body(req: &mut ServiceRequest, max_size: usize) -> Result<web::Bytes, ServiceError> {
+    let (_, payload) = req.parts_mut();
+    let mut payload = payload.take();
+    let mut body = web::BytesMut::new();
+
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk.map_err(|e| ServiceError { status_code: StatusCode::BadRequest, detail: Some(e.to_string()) })?;
+
+        if body.len() + chunk.len() > max_size {
+            return Err(ServiceError { status_code: StatusCode::PayloadTooLarge, detail: Some(format!("Request body is larger than {max_size} bytes")) });
+        }
+
+        body.extend_from_slice(&chunk);
+    }
+
+    Ok(body.freeze())
+}
+
+fn payload(body: web::Bytes) -> dev::Payload {
+    let stream: Pin<Box<dyn Stream<Item = Result<web::Bytes, PayloadError>>>> = Box::pin(futures::stream::once(async move { Ok(body) }));
+    dev::Payload::from(stream)
+}
+
+/// Hash of method, path with query and body
+fn fingerprint(req: &ServiceRequest, body: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(req.method().as_str());
+    hasher.update(b" ");
+    hasher.update(req.uri().path_and_query().map(|path| path.as_str()).unwrap_or_else(|| req.path()));
+    hasher.update(b"\n");
+    hasher.update(body);
+    format!("{:x}", hasher.finalize())
+}
+
+fn replay(response: IdempotentResponse) -> HttpResponse {
+    let status_code = actix_web::http::StatusCode::from_u16(response.status_code)
+        .unwrap_or(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR);
+
+    let mut builder = HttpResponse::build(status_code);
+
+    if let Some(content_type) = response.content_type {
+        builder.insert_header((header::CONTENT_TYPE, content_type));
+    }
+
+    builder
+        .insert_header((IDEMPOTENT_REPLAYED_HEADER, "true"))
+        .body(response.body)
+}
+
+async fn cancel(repository: &dyn IdempotencyRepository, scope: &str, key: &str) {
+    if let Err(e) = repository.cancel_idempotent_request(scope, key).await {
+        slog_scope::warn!("Idempotency key is not released: {e}");
+    }
+}
@@ -1,2 +1,23 @@
 mod bearer_auth;
-pub use bearer_auth::*;
\ No newline at end of file
+pub use bearer_auth::*;
+
+mod validation;
+pub use validation::*;
+
+mod request_id;
+pub use request_id::*;
+
+mod admin_auth;
+pub use admin_auth::*;
+
+mod tracing;
+pub use self::tracing::*;
+
+mod idempotency;
+pub use idempotency::*;
+
+mod rate_limit;
+pub use rate_limit::*;
+
+mod dav_auth;
+pub use dav_auth::*;
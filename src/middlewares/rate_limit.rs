@@ -0,0 +1,88 @@
+use std::{
+    future::{
+        ready,
+        Ready
+    },
+    rc::Rc
+};
+
+use actix_web::{
+    body::{
+        BoxBody,
+        MessageBody
+    },
+    dev::{
+        self,
+        Service,
+        ServiceRequest,
+        ServiceResponse,
+        Transform
+    },
+    web,
+    Error,
+    FromRequest
+};
+use futures::future::LocalBoxFuture;
+
+use super::BearerAuth;
+use crate::{
+    models::*,
+    utils::rate_limit::RateLimits
+};
+
+/// Limit of requests per user, set by `TODO_SERVICE_API_RATE_LIMIT`.
+/// Requests without valid token are passed to handler, which rejects them
+pub struct RateLimit;
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimit
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Transform = RateLimitMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimitMiddleware { service: Rc::new(service) }))
+    }
+}
+
+pub struct RateLimitMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimitMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    dev::forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+
+        Box::pin(async move {
+            let rate_limits = req.app_data::<web::Data<RateLimits>>().cloned();
+
+            if let Some(limiter) = rate_limits.as_ref().and_then(|rate_limits| rate_limits.api.as_ref()) {
+                let (http_req, payload) = req.parts_mut();
+
+                if let Ok(auth) = BearerAuth::from_request(http_req, payload).await {
+                    if let Err(retry_after) = limiter.check(&auth.user_id.to_string()) {
+                        let error = TooManyRequestsError::new("Too many requests, try again later", retry_after);
+                        return Ok(req.error_response(error));
+                    }
+                }
+            }
+
+            service.call(req).await.map(|res| res.map_into_boxed_body())
+        })
+    }
+}
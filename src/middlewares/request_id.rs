@@ -0,0 +1,183 @@
+use std::{
+    future::{
+        ready,
+        Ready
+    },
+    rc::Rc,
+    sync::{
+        Arc,
+        OnceLock
+    }
+};
+
+use actix_web::{
+    dev::{
+        self,
+        Service,
+        ServiceRequest,
+        ServiceResponse,
+        Transform
+    },
+    http::header::{
+        HeaderName,
+        HeaderValue
+    },
+    web,
+    Error,
+    FromRequest,
+    HttpMessage,
+    HttpRequest
+};
+use futures::future::LocalBoxFuture;
+use opentelemetry::{
+    trace::TraceContextExt,
+    Context
+};
+use slog::{
+    o,
+    FnValue,
+    Logger
+};
+use uuid::Uuid;
+
+use crate::utils::logging::with_request_scope;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+const REQUEST_ID_MAX_LEN: usize = 128;
+
+/// Per request data, created by `RequestId` middleware
+#[derive(Clone)]
+pub struct RequestContext {
+    pub logger: Logger,
+    user_id: Arc<OnceLock<Uuid>>,
+}
+
+impl RequestContext {
+    /// Bind authorized user to request, logger records will contain `user_id` after this call
+    pub fn set_user_id(&self, user_id: Uuid) {
+        let _ = self.user_id.set(user_id);
+    }
+}
+
+/// Accepts `X-Request-Id` header or generates new id, creates child logger for the request
+/// and echoes id in response headers
+pub struct RequestId {
+    logger: Logger,
+}
+
+impl RequestId {
+    pub fn new(logger: Logger) -> Self {
+        Self { logger }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RequestId
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RequestIdMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestIdMiddleware { service: Rc::new(service), logger: self.logger.clone() }))
+    }
+}
+
+pub struct RequestIdMiddleware<S> {
+    service: Rc<S>,
+    logger: Logger,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestIdMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    dev::forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let request_id = req.headers()
+            .get(REQUEST_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .filter(|value| is_valid_request_id(value))
+            .map(|value| value.to_string())
+            .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+        let user_id = Arc::new(OnceLock::<Uuid>::new());
+        let logger_user_id = user_id.clone();
+
+        let span_context = Context::current().span().span_context().clone();
+        let (trace_id, span_id) = if span_context.is_valid() {
+            (Some(span_context.trace_id().to_string()), Some(span_context.span_id().to_string()))
+        } else {
+            (None, None)
+        };
+
+        let logger = self.logger.new(o!(
+            "request_id" => request_id.clone(),
+            "trace_id" => trace_id,
+            "span_id" => span_id,
+            "method" => req.method().to_string(),
+            "route" => req.match_pattern().unwrap_or_else(|| req.path().to_string()),
+            "user_id" => FnValue(move |_| logger_user_id.get().map(|id| id.to_string())),
+        ));
+
+        req.extensions_mut().insert(RequestContext {
+            logger: logger.clone(),
+            user_id,
+        });
+
+        let service = self.service.clone();
+
+        Box::pin(with_request_scope(request_id.clone(), logger, async move {
+            let mut res = service.call(req).await?;
+
+            if let Ok(value) = HeaderValue::from_str(&request_id) {
+                res.headers_mut().insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+            }
+
+            Ok(res)
+        }))
+    }
+}
+
+fn is_valid_request_id(value: &str) -> bool {
+    !value.is_empty()
+        && value.len() <= REQUEST_ID_MAX_LEN
+        && value.chars().all(|c| c.is_ascii_graphic())
+}
+
+/// Per request logger, falls back to root logger if `RequestId` middleware isn't registered
+pub struct RequestLogger(pub Logger);
+
+impl std::ops::Deref for RequestLogger {
+    type Target = Logger;
+
+    fn deref(&self) -> &Logger {
+        &self.0
+    }
+}
+
+impl FromRequest for RequestLogger {
+    type Error = Error;
+    type Future = Ready<Result<Self, Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut dev::Payload) -> Self::Future {
+        let logger = req.extensions()
+            .get::<RequestContext>()
+            .map(|context| context.logger.clone())
+            .or_else(|| req.app_data::<web::Data<Logger>>().map(|logger| logger.get_ref().clone()))
+            .unwrap_or_else(slog_scope::logger);
+
+        ready(Ok(RequestLogger(logger)))
+    }
+}
@@ -0,0 +1,115 @@
+use std::{
+    future::{
+        ready,
+        Ready
+    },
+    rc::Rc
+};
+
+use actix_web::{
+    dev::{
+        self,
+        Service,
+        ServiceRequest,
+        ServiceResponse,
+        Transform
+    },
+    Error
+};
+use futures::future::LocalBoxFuture;
+use opentelemetry::{
+    global,
+    trace::{
+        FutureExt,
+        SpanKind,
+        Status,
+        TraceContextExt,
+        Tracer
+    },
+    KeyValue
+};
+
+use crate::utils::telemetry::{
+    tracer,
+    HeaderExtractor
+};
+
+/// Creates server span for every request, parent context is taken from W3C `traceparent` header
+#[derive(Default)]
+pub struct Tracing;
+
+impl<S, B> Transform<S, ServiceRequest> for Tracing
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = TracingMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(TracingMiddleware { service: Rc::new(service) }))
+    }
+}
+
+pub struct TracingMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for TracingMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    dev::forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let parent_cx = global::get_text_map_propagator(|propagator| propagator.extract(&HeaderExtractor(req.headers())));
+
+        let method = req.method().to_string();
+        let route = req.match_pattern();
+
+        let mut attributes = vec![
+            KeyValue::new("http.request.method", method.clone()),
+            KeyValue::new("url.path", req.path().to_string()),
+        ];
+        if let Some(route) = &route {
+            attributes.push(KeyValue::new("http.route", route.clone()));
+        }
+
+        let tracer = tracer();
+        let span = tracer
+            .span_builder(format!("{method} {}", route.unwrap_or_default()).trim().to_string())
+            .with_kind(SpanKind::Server)
+            .with_attributes(attributes)
+            .start_with_context(&tracer, &parent_cx);
+
+        let cx = parent_cx.with_span(span);
+        let service = self.service.clone();
+        let request_cx = cx.clone();
+
+        Box::pin(async move {
+            let result = service.call(req).await;
+
+            let span = request_cx.span();
+            match &result {
+                Ok(res) => {
+                    span.set_attribute(KeyValue::new("http.response.status_code", res.status().as_u16() as i64));
+                    if res.status().is_server_error() {
+                        span.set_status(Status::error(res.status().to_string()));
+                    }
+                },
+                Err(e) => span.set_status(Status::error(e.to_string())),
+            }
+            span.end();
+
+            result
+        }.with_context(cx))
+    }
+}
@@ -0,0 +1,87 @@
+use std::ops::Deref;
+
+use actix_web::{
+    dev,
+    web,
+    Error,
+    FromRequest,
+    HttpRequest
+};
+use futures::future::{
+    FutureExt,
+    LocalBoxFuture
+};
+use serde::de::DeserializeOwned;
+use validator::Validate;
+
+use crate::models::*;
+
+/// Json body extractor, which runs `Validate` before handler
+pub struct ValidatedJson<T>(pub T);
+
+impl<T> ValidatedJson<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for ValidatedJson<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: DeserializeOwned + Validate + 'static> FromRequest for ValidatedJson<T> {
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut dev::Payload) -> Self::Future {
+        web::Json::<T>::from_request(req, payload)
+            .map(|result| {
+                let value = result
+                    .map_err(|e| ServiceError { status_code: StatusCode::BadRequest, detail: Some(e.to_string()) })?
+                    .into_inner();
+
+                value.validate().map_err(RequestValidationError::from)?;
+
+                Ok(ValidatedJson(value))
+            })
+            .boxed_local()
+    }
+}
+
+/// Query string extractor, which runs `Validate` before handler
+pub struct ValidatedQuery<T>(pub T);
+
+impl<T> ValidatedQuery<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for ValidatedQuery<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: DeserializeOwned + Validate + 'static> FromRequest for ValidatedQuery<T> {
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut dev::Payload) -> Self::Future {
+        let result = web::Query::<T>::from_query(req.query_string())
+            .map_err(|e| ServiceError { status_code: StatusCode::BadRequest, detail: Some(e.to_string()) }.into())
+            .and_then(|query| {
+                let value = query.into_inner();
+                value.validate().map_err(RequestValidationError::from)?;
+                Ok(ValidatedQuery(value))
+            });
+
+        futures::future::ready(result).boxed_local()
+    }
+}
@@ -0,0 +1,9 @@
+use serde::Serialize;
+
+use crate::utils::log_level::LevelConfig;
+
+#[derive(Serialize)]
+pub struct LogLevels {
+    pub console: LevelConfig,
+    pub file: LevelConfig,
+}
@@ -0,0 +1,116 @@
+use chrono::{
+    DateTime,
+    Utc
+};
+use serde::{
+    Deserialize,
+    Serialize
+};
+use uuid::Uuid;
+use validator::Validate;
+
+use super::{
+    AccessTokenInfo,
+    CalendarFeedInfo,
+    DeletedTodoList,
+    FullTaskInfo,
+    FullTodoListInfo,
+    TaskDetails,
+    UserIdentity,
+    WebhookInfo
+};
+
+/// `manifest.json` of the archive, it lists data files with their checksums
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BackupManifest {
+    pub format: String,
+    /// Version of the archive structure, older archives are upgraded on restore
+    pub version: u32,
+    pub created_at: DateTime<Utc>,
+    pub user: BackupUser,
+    pub files: Vec<BackupFile>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BackupUser {
+    pub id: Uuid,
+    pub login: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BackupFile {
+    pub name: String,
+    pub size: u64,
+    /// Hex sha256 of the file
+    pub sha256: String,
+}
+
+/// `account.json`, profile and settings, which are not restored
+#[derive(Serialize)]
+pub struct BackupAccount {
+    pub login: String,
+    pub email: Option<String>,
+    pub email_verified: bool,
+    pub two_factor_enabled: bool,
+    pub calendar_feed: Option<CalendarFeedInfo>,
+    pub access_tokens: Vec<AccessTokenInfo>,
+    pub webhooks: Vec<WebhookInfo>,
+    pub identities: Vec<UserIdentity>,
+}
+
+/// Item of `lists.json`
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BackupList {
+    pub id: Uuid,
+    pub name: String,
+}
+
+/// Item of `tasks.json`
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BackupTask {
+    pub id: Uuid,
+    pub list_id: Uuid,
+    pub order: i32,
+    pub description: String,
+    pub due_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub priority: Option<i32>,
+    pub rrule: Option<String>,
+}
+
+impl BackupTask {
+    pub fn details(&self) -> TaskDetails {
+        TaskDetails { due_at: self.due_at, completed_at: self.completed_at, priority: self.priority, rrule: self.rrule.clone() }
+    }
+}
+
+#[derive(Deserialize, Validate)]
+pub struct RestoreQuery {
+    /// Delete list of the user with its tasks, otherwise user with list gets `409`
+    #[serde(default)]
+    pub replace: bool,
+}
+
+/// Result of restore in storage, tasks are in the restored order
+pub struct RestoredTodoList {
+    pub list: FullTodoListInfo,
+    pub tasks: Vec<FullTaskInfo>,
+    pub replaced: Option<DeletedTodoList>,
+}
+
+/// Id in the archive and id of the restored entity
+#[derive(Serialize, Debug)]
+pub struct RestoredId {
+    pub old: Uuid,
+    pub new: Uuid,
+}
+
+#[derive(Serialize, Debug)]
+pub struct RestoreReport {
+    /// Version of the archive, before upgrade
+    pub version: u32,
+    pub list: Option<RestoredId>,
+    pub tasks: Vec<RestoredId>,
+    /// Tasks of the replaced list
+    pub deleted_tasks: i64,
+}
@@ -0,0 +1,53 @@
+use chrono::{
+    DateTime,
+    Utc
+};
+use rand::{
+    distributions::Alphanumeric,
+    Rng
+};
+use serde::Serialize;
+use sha2::{
+    Digest,
+    Sha256
+};
+
+/// Feed tokens have this prefix, so leaked token is recognizable
+pub const CALENDAR_FEED_TOKEN_PREFIX: &str = "tdl_cal_";
+const CALENDAR_FEED_TOKEN_RANDOM_LEN: usize = 40;
+
+/// Calendar feed of the user without token
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CalendarFeedInfo {
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+/// Response of feed creation, token is shown only once
+#[derive(Serialize)]
+pub struct CreatedCalendarFeed {
+    #[serde(flatten)]
+    pub info: CalendarFeedInfo,
+    pub token: String,
+    /// Path of the feed, relative to the service address
+    pub path: String,
+}
+
+pub fn generate_calendar_feed_token() -> String {
+    let random: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(CALENDAR_FEED_TOKEN_RANDOM_LEN)
+        .map(char::from)
+        .collect();
+
+    format!("{CALENDAR_FEED_TOKEN_PREFIX}{random}")
+}
+
+/// Only hash of the token is stored
+pub fn calendar_feed_token_hash(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+pub fn calendar_feed_path(token: &str) -> String {
+    format!("/api/calendar/{token}.ics")
+}
@@ -5,4 +5,15 @@ use uuid::Uuid;
 pub struct UserClaim {
     pub exp: usize,
     pub user_id: Uuid,
-}
\ No newline at end of file
+    /// Session version of the user when token was issued, password change increments it.
+    /// Tokens issued before the field was added have version 0
+    #[serde(default)]
+    pub session_version: i32,
+}
+
+/// Claim of login challenge when 2FA is enabled. Has no `user_id` field, so it can't be used as `UserClaim`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TwoFactorChallengeClaim {
+    pub exp: usize,
+    pub challenge_user_id: Uuid,
+}
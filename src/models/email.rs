@@ -0,0 +1,96 @@
+use rand::{
+    distributions::Alphanumeric,
+    Rng
+};
+use serde::Deserialize;
+use sha2::{
+    Digest,
+    Sha256
+};
+use uuid::Uuid;
+use validator::Validate;
+
+use super::validation::*;
+
+const EMAIL_TOKEN_LEN: usize = 40;
+
+/// Email is set unverified, verification token is sent to it
+#[derive(Deserialize, Validate)]
+pub struct NewEmail {
+    #[validate(
+        email(message = "Invalid email"),
+        length(max = "EMAIL_MAX_LEN", message = "Email length must be at most 254")
+    )]
+    pub email: String,
+}
+
+/// Token from the mail
+#[derive(Deserialize, Validate)]
+pub struct EmailToken {
+    #[validate(length(min = 1, max = 128, message = "Token length must be between 1 and 128"))]
+    pub token: String,
+}
+
+#[derive(Deserialize, Validate)]
+pub struct ForgotPassword {
+    #[validate(length(min = 1, max = "EMAIL_MAX_LEN", message = "Email length must be between 1 and 254"))]
+    pub email: String,
+}
+
+#[derive(Deserialize, Validate)]
+pub struct ResetPassword {
+    #[validate(length(min = 1, max = 128, message = "Token length must be between 1 and 128"))]
+    pub token: String,
+    #[validate(
+        length(min = "PASSWORD_MIN_LEN", max = "PASSWORD_MAX_LEN", message = "Password length must be between 8 and 128"),
+        custom = "validate_password_strength"
+    )]
+    pub new_password: String,
+}
+
+/// Email of user, only verified email can be used for password reset
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserEmail {
+    pub email: String,
+    pub verified: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EmailTokenPurpose {
+    VerifyEmail,
+    ResetPassword,
+}
+
+impl EmailTokenPurpose {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EmailTokenPurpose::VerifyEmail => "verify_email",
+            EmailTokenPurpose::ResetPassword => "reset_password",
+        }
+    }
+}
+
+/// User and email, for which the token was sent
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmailTokenOwner {
+    pub user_id: Uuid,
+    pub email: String,
+}
+
+/// Emails are compared case insensitive
+pub fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+pub fn generate_email_token() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(EMAIL_TOKEN_LEN)
+        .map(char::from)
+        .collect()
+}
+
+/// Only hash is stored, like hash of access token
+pub fn email_token_hash(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.trim().as_bytes()))
+}
@@ -1,3 +1,8 @@
+use std::{
+    collections::HashMap,
+    time::Duration
+};
+
 use actix_web::{
     HttpResponse,
     http::{
@@ -9,14 +14,30 @@ use actix_web::{
 use derive_more::Display;
 use serde::Serialize;
 
+use crate::utils::logging::current_request_id;
+
+use super::ImportReport;
+
 #[derive(Serialize, Debug, Display)]
 pub enum StatusCode {
     #[serde(rename(serialize = "400 Bad Request"))] 
     BadRequest,
     #[serde(rename(serialize = "401 Unauthorized"))] 
     Unauthorized,
+    #[serde(rename(serialize = "403 Forbidden"))] 
+    Forbidden,
     #[serde(rename(serialize = "404 Not Found"))] 
     NotFound,
+    #[serde(rename(serialize = "409 Conflict"))] 
+    Conflict,
+    #[serde(rename(serialize = "412 Precondition Failed"))] 
+    PreconditionFailed,
+    #[serde(rename(serialize = "413 Payload Too Large"))] 
+    PayloadTooLarge,
+    #[serde(rename(serialize = "422 Unprocessable Entity"))] 
+    UnprocessableEntity,
+    #[serde(rename(serialize = "429 Too Many Requests"))] 
+    TooManyRequests,
     #[serde(rename(serialize = "500 Internal Error"))] 
     InternalError,
 }
@@ -32,19 +53,173 @@ impl error::ResponseError for ServiceError {
     fn error_response(&self) -> HttpResponse {
         HttpResponse::build(self.status_code())
             .insert_header(ContentType::json())
-            .body(serde_json::to_string(self).unwrap())
+            .body(error_body(self))
     }
 
     fn status_code(&self) -> http::StatusCode {
         match self.status_code {
             StatusCode::BadRequest => http::StatusCode::BAD_REQUEST,
             StatusCode::Unauthorized => http::StatusCode::UNAUTHORIZED,
+            StatusCode::Forbidden => http::StatusCode::FORBIDDEN,
             StatusCode::NotFound => http::StatusCode::NOT_FOUND,
+            StatusCode::Conflict => http::StatusCode::CONFLICT,
+            StatusCode::PreconditionFailed => http::StatusCode::PRECONDITION_FAILED,
+            StatusCode::PayloadTooLarge => http::StatusCode::PAYLOAD_TOO_LARGE,
+            StatusCode::UnprocessableEntity => http::StatusCode::UNPROCESSABLE_ENTITY,
+            StatusCode::TooManyRequests => http::StatusCode::TOO_MANY_REQUESTS,
             StatusCode::InternalError => http::StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
 }
 
+#[derive(Serialize, Debug, Display)]
+#[display(fmt = "{}", "serde_json::to_string(self).unwrap()")]
+pub struct RequestValidationError {
+    pub status_code: StatusCode,
+    pub detail: Option<String>,
+    pub fields: HashMap<String, Vec<String>>,
+}
+
+impl From<validator::ValidationErrors> for RequestValidationError {
+    fn from(errors: validator::ValidationErrors) -> Self {
+        let fields = errors.field_errors()
+            .into_iter()
+            .map(|(field, errors)| {
+                let messages = errors.iter()
+                    .map(|e| e.message.as_ref().map(|m| m.to_string()).unwrap_or_else(|| e.code.to_string()))
+                    .collect();
+
+                (field.to_string(), messages)
+            })
+            .collect();
+
+        Self {
+            status_code: StatusCode::UnprocessableEntity,
+            detail: Some("Request validation failed".to_string()),
+            fields,
+        }
+    }
+}
+
+impl error::ResponseError for RequestValidationError {
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code())
+            .insert_header(ContentType::json())
+            .body(error_body(self))
+    }
+
+    fn status_code(&self) -> http::StatusCode {
+        http::StatusCode::UNPROCESSABLE_ENTITY
+    }
+}
+
+/// `429` with `Retry-After` header in seconds
+#[derive(Debug, Display)]
+#[display(fmt = "{}", error)]
+pub struct TooManyRequestsError {
+    pub error: ServiceError,
+    pub retry_after: Duration,
+}
+
+impl TooManyRequestsError {
+    pub fn new(detail: &str, retry_after: Duration) -> Self {
+        Self {
+            error: ServiceError { status_code: StatusCode::TooManyRequests, detail: Some(detail.to_string()) },
+            retry_after,
+        }
+    }
+}
+
+impl error::ResponseError for TooManyRequestsError {
+    fn error_response(&self) -> HttpResponse {
+        // client must not retry earlier than allowed
+        let retry_after = self.retry_after.as_secs() + u64::from(self.retry_after.subsec_nanos() > 0);
+
+        HttpResponse::build(self.status_code())
+            .insert_header(ContentType::json())
+            .insert_header((http::header::RETRY_AFTER, retry_after.max(1)))
+            .body(error_body(&self.error))
+    }
+
+    fn status_code(&self) -> http::StatusCode {
+        http::StatusCode::TOO_MANY_REQUESTS
+    }
+}
+
+/// `401` with `WWW-Authenticate` challenge, so clients without own login form (CalDAV) ask user for credentials
+#[derive(Debug, Display)]
+#[display(fmt = "{}", error)]
+pub struct AuthenticateError {
+    pub error: ServiceError,
+    pub challenge: String,
+}
+
+impl AuthenticateError {
+    pub fn new(detail: &str, challenge: &str) -> Self {
+        Self {
+            error: ServiceError { status_code: StatusCode::Unauthorized, detail: Some(detail.to_string()) },
+            challenge: challenge.to_string(),
+        }
+    }
+}
+
+impl error::ResponseError for AuthenticateError {
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code())
+            .insert_header(ContentType::json())
+            .insert_header((http::header::WWW_AUTHENTICATE, self.challenge.as_str()))
+            .body(error_body(&self.error))
+    }
+
+    fn status_code(&self) -> http::StatusCode {
+        http::StatusCode::UNAUTHORIZED
+    }
+}
+
+/// `422` of import with invalid items, nothing is imported and report lists the items
+#[derive(Serialize, Debug, Display)]
+#[display(fmt = "{}", "serde_json::to_string(self).unwrap()")]
+pub struct ImportRejectedError {
+    pub status_code: StatusCode,
+    pub detail: Option<String>,
+    pub report: ImportReport,
+}
+
+impl ImportRejectedError {
+    pub fn new(report: ImportReport) -> Self {
+        Self {
+            status_code: StatusCode::UnprocessableEntity,
+            detail: Some("File has invalid tasks, nothing is imported".to_string()),
+            report,
+        }
+    }
+}
+
+impl error::ResponseError for ImportRejectedError {
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code())
+            .insert_header(ContentType::json())
+            .body(error_body(self))
+    }
+
+    fn status_code(&self) -> http::StatusCode {
+        http::StatusCode::UNPROCESSABLE_ENTITY
+    }
+}
+
+/// Error with id of the current request, so client can report it
+#[derive(Serialize)]
+struct ErrorBody<'a, T: Serialize> {
+    #[serde(flatten)]
+    error: &'a T,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
+}
+
+fn error_body<T: Serialize>(error: &T) -> String {
+    serde_json::to_string(&ErrorBody { error, request_id: current_request_id() }).unwrap()
+}
+
 // impl ServiceError {
 //     fn from_status_code(status_code: StatusCode) -> Self {
 //         Self {
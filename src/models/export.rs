@@ -0,0 +1,63 @@
+use chrono::{
+    DateTime,
+    Utc
+};
+use serde::{
+    Deserialize,
+    Serialize
+};
+use uuid::Uuid;
+use validator::Validate;
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum ExportFormat {
+    #[default]
+    #[serde(rename = "json")]
+    Json,
+    #[serde(rename = "csv")]
+    Csv,
+    /// GitHub-style checklist
+    #[serde(rename = "markdown")]
+    Markdown,
+    /// One task per line, http://todotxt.org
+    #[serde(rename = "todotxt")]
+    TodoTxt,
+}
+
+impl ExportFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Json => "application/json",
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Markdown => "text/markdown; charset=utf-8",
+            ExportFormat::TodoTxt => "text/plain; charset=utf-8",
+        }
+    }
+
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            ExportFormat::Json => "todo-list.json",
+            ExportFormat::Csv => "todo-list.csv",
+            ExportFormat::Markdown => "todo-list.md",
+            ExportFormat::TodoTxt => "todo.txt",
+        }
+    }
+}
+
+#[derive(Deserialize, Validate)]
+pub struct ExportQuery {
+    #[serde(default)]
+    pub format: ExportFormat,
+}
+
+/// Task in json export, list is the enclosing object
+#[derive(Serialize)]
+pub struct ExportedTask<'a> {
+    pub id: Uuid,
+    pub order: i32,
+    pub description: &'a str,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub due_at: Option<DateTime<Utc>>,
+    pub priority: Option<i32>,
+    pub rrule: Option<&'a str>,
+}
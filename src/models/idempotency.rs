@@ -0,0 +1,18 @@
+/// Response of the request with `Idempotency-Key`, replayed on retries
+#[derive(Debug, Clone, PartialEq)]
+pub struct IdempotentResponse {
+    pub status_code: u16,
+    pub content_type: Option<String>,
+    pub body: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum IdempotencyState {
+    /// Key is reserved for this request, response must be completed or cancelled
+    Started,
+    /// Request with the key is being processed
+    InProgress,
+    Completed(IdempotentResponse),
+    /// Key was used for request with other fingerprint
+    Mismatch,
+}
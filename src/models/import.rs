@@ -0,0 +1,105 @@
+use chrono::{
+    DateTime,
+    Utc
+};
+use serde::{
+    Deserialize,
+    Serialize
+};
+use uuid::Uuid;
+use validator::Validate;
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+pub enum ImportFormat {
+    /// One task per line, http://todotxt.org
+    #[serde(rename = "todotxt")]
+    TodoTxt,
+    /// File with header row, task text is taken from the mapped column
+    #[serde(rename = "csv")]
+    Csv,
+    /// Items of GitHub-style checklist
+    #[serde(rename = "markdown")]
+    Markdown,
+    /// Tasks of Todoist backup or REST API: array of tasks or object with `items`
+    #[serde(rename = "todoist")]
+    Todoist,
+    /// Cards of Trello board export
+    #[serde(rename = "trello")]
+    Trello,
+}
+
+/// What to do with item, which has the same text as existing task or earlier item of the file
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum DuplicatePolicy {
+    #[default]
+    #[serde(rename = "skip")]
+    Skip,
+    #[serde(rename = "allow")]
+    Allow,
+}
+
+#[derive(Deserialize, Validate)]
+pub struct ImportQuery {
+    pub format: ImportFormat,
+    /// Report what would be imported without creating tasks
+    #[serde(default)]
+    pub dry_run: bool,
+    #[serde(default)]
+    pub duplicates: DuplicatePolicy,
+}
+
+/// Columns of csv or fields of Todoist/Trello items, which are imported.
+/// Not set fields use defaults of the format
+#[derive(Deserialize, Default, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ImportMapping {
+    /// Text of the task
+    pub description: Option<String>,
+    /// Added to the text after empty line
+    pub notes: Option<String>,
+    /// Number, by which items are sorted, otherwise order of the file is kept
+    pub order: Option<String>,
+    /// Completion flag or time, completed item without time is completed at import
+    pub completed: Option<String>,
+    /// Due date or time
+    pub due: Option<String>,
+    /// Priority from 1 (the highest) to 9
+    pub priority: Option<String>,
+    /// Only cards of the Trello list with the name
+    pub list: Option<String>,
+}
+
+/// Task, which is created (or would be created by dry run)
+#[derive(Serialize, Debug)]
+pub struct ImportedTask {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<Uuid>,
+    pub order: i32,
+    pub description: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub completed_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub due_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<i32>,
+}
+
+/// Item of the file, which is not imported. `item` is number of the item in the file starting from 1
+#[derive(Serialize, Debug)]
+pub struct ImportIssue {
+    pub item: usize,
+    pub detail: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ImportReport {
+    pub format: ImportFormat,
+    pub dry_run: bool,
+    /// Items found in the file
+    pub total: usize,
+    pub created: usize,
+    pub duplicates: Vec<ImportIssue>,
+    /// Invalid items, import with them is rejected as a whole
+    pub errors: Vec<ImportIssue>,
+    pub tasks: Vec<ImportedTask>,
+}
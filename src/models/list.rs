@@ -3,18 +3,29 @@ use serde::{
     Serialize
 };
 use uuid::Uuid;
+use validator::Validate;
 
-#[derive(Deserialize)]
+use super::validation::*;
+
+#[derive(Deserialize, Validate)]
 pub struct NewTodoList {
+    #[validate(
+        length(min = 1, max = "LIST_NAME_MAX_LEN", message = "Name length must be between 1 and 1024"),
+        custom = "validate_not_blank"
+    )]
     pub name: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Validate)]
 pub struct UpdateTodoList {
+    #[validate(
+        length(min = 1, max = "LIST_NAME_MAX_LEN", message = "Name length must be between 1 and 1024"),
+        custom = "validate_not_blank"
+    )]
     pub name: String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Clone, sqlx::FromRow)]
 pub struct FullTodoListInfo {
     pub id: Uuid,
     pub user_id: Uuid,
@@ -11,4 +11,44 @@ mod error;
 pub use error::*;
 
 mod claims;
-pub use claims::*;
\ No newline at end of file
+pub use claims::*;
+
+mod idempotency;
+pub use idempotency::*;
+
+mod token;
+pub use token::*;
+
+mod two_factor;
+pub use two_factor::*;
+
+mod jwk;
+pub use jwk::*;
+
+mod email;
+pub use email::*;
+
+mod oidc;
+pub use oidc::*;
+
+mod webhook;
+pub use webhook::*;
+
+mod calendar;
+pub use calendar::*;
+
+mod export;
+pub use export::*;
+
+mod import;
+pub use import::*;
+
+mod backup;
+pub use backup::*;
+
+mod sync;
+pub use sync::*;
+
+mod validation;
+mod admin;
+pub use admin::*;
@@ -0,0 +1,94 @@
+use chrono::{
+    DateTime,
+    Utc
+};
+use rand::{
+    distributions::Alphanumeric,
+    Rng
+};
+use serde::{
+    Deserialize,
+    Serialize
+};
+use uuid::Uuid;
+
+use super::validation::LOGIN_MAX_LEN;
+
+const LOGIN_SUFFIX_LEN: usize = 6;
+
+/// Query of the redirect from provider, `error` is set if user denied access
+#[derive(Deserialize)]
+pub struct OidcCallback {
+    pub state: String,
+    pub code: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Url of provider, where user confirms linking of the identity
+#[derive(Serialize, Deserialize)]
+pub struct OidcAuthorization {
+    pub authorization_url: String,
+}
+
+/// Identity verified by provider, identified by issuer and subject
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewIdentity {
+    pub issuer: String,
+    pub subject: String,
+    pub email: Option<String>,
+}
+
+/// External identity linked to user
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct UserIdentity {
+    pub id: Uuid,
+    pub issuer: String,
+    pub subject: String,
+    pub email: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Login of auto-provisioned user from preferred username or email of the provider,
+/// invalid characters are dropped. With `suffix` random part is added for taken logins
+pub fn provisioned_login(preferred_username: Option<&str>, email: Option<&str>, suffix: bool) -> String {
+    let name = preferred_username
+        .or_else(|| email.and_then(|email| email.split('@').next()))
+        .unwrap_or_default();
+
+    let mut login: String = name.chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '-' || *c == '.')
+        .take(LOGIN_MAX_LEN as usize - LOGIN_SUFFIX_LEN - 1)
+        .collect();
+
+    if login.is_empty() {
+        login = "user".to_string();
+    }
+
+    if suffix {
+        let random: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(LOGIN_SUFFIX_LEN)
+            .map(|c| char::from(c).to_ascii_lowercase())
+            .collect();
+        login = format!("{login}_{random}");
+    }
+
+    login
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn provisioned_login_from_claims() {
+        assert_eq!(provisioned_login(Some("Alice Smith"), Some("alice@example.com"), false), "AliceSmith");
+        assert_eq!(provisioned_login(None, Some("alice.smith@example.com"), false), "alice.smith");
+        assert_eq!(provisioned_login(Some("Алиса"), None, false), "user");
+        assert_eq!(provisioned_login(None, None, false), "user");
+
+        let login = provisioned_login(Some(&"a".repeat(200)), None, true);
+        assert_eq!(login.len(), LOGIN_MAX_LEN as usize);
+        assert!(login.starts_with(&"a".repeat(121)));
+    }
+}
@@ -0,0 +1,221 @@
+use std::fmt;
+
+use chrono::{
+    DateTime,
+    Utc
+};
+use serde::{
+    Deserialize,
+    Serialize
+};
+use uuid::Uuid;
+use validator::{
+    Validate,
+    ValidationError
+};
+
+use super::validation::*;
+use super::{
+    FullTaskInfo,
+    FullTodoListInfo,
+    TaskPosition,
+    UpdateTask
+};
+
+pub const SYNC_MUTATIONS_MAX_COUNT: u64 = 500;
+
+/// Position in change feed of the list: `{list_id}.{seq}`. Client keeps it opaque
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SyncCursor {
+    pub todo_list_id: Uuid,
+    pub seq: i64,
+}
+
+impl SyncCursor {
+    pub fn parse(cursor: &str) -> Option<Self> {
+        let (todo_list_id, seq) = cursor.split_once('.')?;
+
+        Some(Self {
+            todo_list_id: Uuid::parse_str(todo_list_id).ok()?,
+            seq: seq.parse().ok().filter(|seq| *seq >= 0)?,
+        })
+    }
+}
+
+impl fmt::Display for SyncCursor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}", self.todo_list_id, self.seq)
+    }
+}
+
+#[derive(Deserialize, Validate)]
+pub struct SyncQuery {
+    /// Cursor of the previous sync, everything is returned without it
+    pub since: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct TaskTombstone {
+    pub id: Uuid,
+    pub deleted_at: DateTime<Utc>,
+}
+
+/// Changes of the list tasks after the sequence number, tasks created and then changed are `created`
+pub struct TaskChanges {
+    /// Sequence number of the last change
+    pub seq: i64,
+    /// Sequence number of the last rename of the list
+    pub name_seq: i64,
+    pub created: Vec<FullTaskInfo>,
+    pub updated: Vec<FullTaskInfo>,
+    pub deleted: Vec<TaskTombstone>,
+}
+
+/// Write of sync, which is applied only if the task wasn't changed after the client time
+#[derive(Debug)]
+pub enum ConditionalWrite {
+    Applied(FullTaskInfo),
+    /// Task is changed later, current task is returned
+    Outdated(FullTaskInfo),
+    NotFound,
+}
+
+#[derive(Serialize)]
+pub struct SyncChanges {
+    pub cursor: String,
+    /// Cursor is of another list or unknown, client must replace its data with the returned
+    pub reset: bool,
+    /// List if renamed after the cursor
+    pub list: Option<FullTodoListInfo>,
+    pub created: Vec<FullTaskInfo>,
+    pub updated: Vec<FullTaskInfo>,
+    pub deleted: Vec<TaskTombstone>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TaskMutation {
+    /// Id is chosen by client, so repeated create is recognized
+    Create { task_id: Uuid, description: String, position: TaskPosition },
+    Update { task_id: Uuid, description: String },
+    Delete { task_id: Uuid },
+    Move { task_id: Uuid, position: TaskPosition },
+}
+
+impl TaskMutation {
+    pub fn task_id(&self) -> Uuid {
+        match self {
+            TaskMutation::Create { task_id, .. }
+            | TaskMutation::Update { task_id, .. }
+            | TaskMutation::Delete { task_id }
+            | TaskMutation::Move { task_id, .. } => *task_id,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SyncMutation {
+    /// Time of the change on client, time in future is treated as the time of the request
+    pub client_time: DateTime<Utc>,
+    #[serde(flatten)]
+    pub mutation: TaskMutation,
+}
+
+#[derive(Deserialize, Validate)]
+pub struct SyncRequest {
+    /// Cursor of the previous sync, changes after it are returned with the results
+    pub cursor: Option<String>,
+    #[validate(
+        length(max = "SYNC_MUTATIONS_MAX_COUNT", message = "Count of mutations must be less than 500"),
+        custom = "validate_mutations"
+    )]
+    pub mutations: Vec<SyncMutation>,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum MutationStatus {
+    Applied,
+    /// Mutation was applied before, e.g. by retried request, or task is already deleted
+    Duplicate,
+    /// Server state wins, it is in `task`
+    Conflict,
+}
+
+#[derive(Serialize)]
+pub struct MutationResult {
+    /// Index of the mutation in request
+    pub index: usize,
+    pub task_id: Uuid,
+    pub status: MutationStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    /// Task after the mutation, `None` if it is deleted
+    pub task: Option<FullTaskInfo>,
+}
+
+#[derive(Serialize)]
+pub struct SyncResponse {
+    /// Results in order of mutations in request
+    pub results: Vec<MutationResult>,
+    #[serde(flatten)]
+    pub changes: SyncChanges,
+}
+
+fn validate_mutations(mutations: &Vec<SyncMutation>) -> Result<(), ValidationError> {
+    for sync_mutation in mutations {
+        let (description, position) = match &sync_mutation.mutation {
+            TaskMutation::Create { description, position, .. } => (Some(description), Some(position)),
+            TaskMutation::Update { description, .. } => (Some(description), None),
+            TaskMutation::Delete { .. } => (None, None),
+            TaskMutation::Move { position, .. } => (None, Some(position)),
+        };
+
+        if description.is_some_and(|description| UpdateTask { description: description.clone() }.validate().is_err()) {
+            return Err(error("mutation_description", "Description length must be between 1 and 4096 and not blank"));
+        }
+
+        if position.and_then(TaskPosition::anchor) == Some(sync_mutation.mutation.task_id()) {
+            return Err(error("mutation_position", "Source and destination task id is the same"));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursor_roundtrip() {
+        let cursor = SyncCursor { todo_list_id: Uuid::new_v4(), seq: 42 };
+
+        assert_eq!(SyncCursor::parse(&cursor.to_string()), Some(cursor));
+        assert_eq!(SyncCursor::parse("42"), None);
+        assert_eq!(SyncCursor::parse(&format!("{}.-1", cursor.todo_list_id)), None);
+    }
+
+    #[test]
+    fn mutations_are_validated() {
+        let task_id = Uuid::new_v4();
+        let request: SyncRequest = serde_json::from_value(serde_json::json!({
+            "mutations": [
+                { "type": "create", "task_id": task_id, "description": "buy milk", "position": "end", "client_time": "2026-10-19T10:00:00Z" },
+                { "type": "move", "task_id": task_id, "position": { "after": { "task_id": Uuid::new_v4() } }, "client_time": "2026-10-19T10:01:00Z" },
+                { "type": "delete", "task_id": task_id, "client_time": "2026-10-19T10:02:00Z" }
+            ]
+        })).unwrap();
+        assert!(request.validate().is_ok());
+
+        let request: SyncRequest = serde_json::from_value(serde_json::json!({
+            "mutations": [{ "type": "update", "task_id": task_id, "description": " ", "client_time": "2026-10-19T10:00:00Z" }]
+        })).unwrap();
+        assert!(request.validate().is_err());
+
+        let request: SyncRequest = serde_json::from_value(serde_json::json!({
+            "mutations": [{ "type": "move", "task_id": task_id, "position": { "before": { "task_id": task_id } }, "client_time": "2026-10-19T10:00:00Z" }]
+        })).unwrap();
+        assert!(request.validate().is_err());
+    }
+}
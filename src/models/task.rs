@@ -1,8 +1,18 @@
+use chrono::{
+    DateTime,
+    Utc
+};
 use serde::{
     Deserialize,
     Serialize
 };
 use uuid::Uuid;
+use validator::{
+    Validate,
+    ValidationError
+};
+
+use super::validation::*;
 
 #[derive(Serialize, Deserialize)]
 pub enum TaskPosition {
@@ -14,32 +24,95 @@ pub enum TaskPosition {
     Before { task_id: Uuid },
 }
 
-#[derive(Deserialize)]
+impl TaskPosition {
+    /// Task, next to which the task is placed
+    pub fn anchor(&self) -> Option<Uuid> {
+        match self {
+            TaskPosition::End => None,
+            TaskPosition::After { task_id } | TaskPosition::Before { task_id } => Some(*task_id),
+        }
+    }
+}
+
+#[derive(Deserialize, Validate)]
 pub struct NewTask {
+    #[validate(
+        length(min = 1, max = "TASK_DESCRIPTION_MAX_LEN", message = "Description length must be between 1 and 4096"),
+        custom = "validate_not_blank"
+    )]
     pub description: String,
     pub position: TaskPosition,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Debug, Clone, Default, sqlx::FromRow)]
 pub struct FullTaskInfo {
     pub id: Uuid,
     pub todo_list_id: Uuid,
     pub description: String,
     pub order: i32,
+    pub due_at: Option<DateTime<Utc>>,
+    /// Task is open until it is completed
+    pub completed_at: Option<DateTime<Utc>>,
+    /// 1 is the highest, 9 is the lowest
+    pub priority: Option<i32>,
+    /// Recurrence rule of iCalendar, e.g. `FREQ=WEEKLY;BYDAY=MO`
+    pub rrule: Option<String>,
+}
+
+impl FullTaskInfo {
+    pub fn details(&self) -> TaskDetails {
+        TaskDetails { due_at: self.due_at, completed_at: self.completed_at, priority: self.priority, rrule: self.rrule.clone() }
+    }
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Validate)]
 pub struct UpdateTask {
+    #[validate(
+        length(min = 1, max = "TASK_DESCRIPTION_MAX_LEN", message = "Description length must be between 1 and 4096"),
+        custom = "validate_not_blank"
+    )]
     pub description: String,
 }
 
-#[derive(Deserialize)]
+/// Planning details of the task, missing values are cleared
+#[derive(Deserialize, Validate, Debug, Clone, Default, PartialEq)]
+#[validate(schema(function = "validate_recurrence_start"))]
+pub struct TaskDetails {
+    pub due_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>,
+    #[validate(range(min = 1, max = 9, message = "Priority must be between 1 and 9"))]
+    pub priority: Option<i32>,
+    #[validate(
+        length(min = 1, max = "TASK_RRULE_MAX_LEN", message = "Recurrence rule length must be between 1 and 512"),
+        custom = "validate_rrule"
+    )]
+    pub rrule: Option<String>,
+}
+
+/// Text and details of the task added without position, e.g. by import
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TaskContent {
+    pub description: String,
+    pub details: TaskDetails,
+}
+
+/// Recurrence set starts at due time of the task
+fn validate_recurrence_start(details: &TaskDetails) -> Result<(), ValidationError> {
+    if details.rrule.is_none() || details.due_at.is_some() {
+        return Ok(());
+    }
+
+    Err(error("recurrence_start", "Recurring task must have due time"))
+}
+
+#[derive(Deserialize, Validate)]
 pub struct MoveTask {
     pub position: TaskPosition,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Validate)]
 pub struct TaskRange {
     pub offset: u32,
+    #[validate(range(min = 1, max = "TASK_RANGE_MAX_COUNT", message = "Count must be between 1 and 100"))]
     pub count: u32
 }
\ No newline at end of file
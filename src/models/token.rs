@@ -0,0 +1,117 @@
+use chrono::{
+    DateTime,
+    Utc
+};
+use rand::{
+    distributions::Alphanumeric,
+    Rng
+};
+use serde::{
+    Deserialize,
+    Serialize
+};
+use sha2::{
+    Digest,
+    Sha256
+};
+use uuid::Uuid;
+use validator::Validate;
+
+use super::validation::*;
+
+/// Personal access tokens have this prefix, so `BearerAuth` can tell them from JWT
+pub const ACCESS_TOKEN_PREFIX: &str = "tdl_pat_";
+const ACCESS_TOKEN_RANDOM_LEN: usize = 40;
+
+/// Access of personal access token. Every scope allows reading of own list and tasks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TokenScope {
+    #[serde(rename = "read-only")]
+    ReadOnly,
+    /// Create, update, move and delete tasks
+    #[serde(rename = "tasks:write")]
+    TasksWrite,
+    /// Create, rename and delete list
+    #[serde(rename = "lists:admin")]
+    ListsAdmin,
+}
+
+impl TokenScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenScope::ReadOnly => "read-only",
+            TokenScope::TasksWrite => "tasks:write",
+            TokenScope::ListsAdmin => "lists:admin",
+        }
+    }
+
+    pub fn parse(scope: &str) -> Option<Self> {
+        [TokenScope::ReadOnly, TokenScope::TasksWrite, TokenScope::ListsAdmin]
+            .into_iter()
+            .find(|known| known.as_str() == scope)
+    }
+}
+
+/// Scopes are stored as space separated string
+pub fn scopes_to_string(scopes: &[TokenScope]) -> String {
+    scopes.iter().map(|scope| scope.as_str()).collect::<Vec<_>>().join(" ")
+}
+
+pub fn scopes_from_string(scopes: &str) -> Vec<TokenScope> {
+    scopes.split_whitespace().filter_map(TokenScope::parse).collect()
+}
+
+#[derive(Deserialize, Validate)]
+pub struct NewAccessToken {
+    #[validate(
+        length(min = 1, max = "ACCESS_TOKEN_NAME_MAX_LEN", message = "Name length must be between 1 and 128"),
+        custom = "validate_not_blank"
+    )]
+    pub name: String,
+    #[validate(length(min = 1, message = "At least one scope is required"))]
+    pub scopes: Vec<TokenScope>,
+    /// Token never expires if not set
+    #[validate(range(min = 1, max = "ACCESS_TOKEN_MAX_DAYS", message = "Token lifetime must be between 1 and 3650 days"))]
+    pub expires_in_days: Option<u32>,
+}
+
+/// Token without secret, returned by list of tokens
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct AccessTokenInfo {
+    pub id: Uuid,
+    pub name: String,
+    pub scopes: Vec<TokenScope>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+/// Response of token creation, secret is shown only once
+#[derive(Serialize)]
+pub struct CreatedAccessToken {
+    #[serde(flatten)]
+    pub info: AccessTokenInfo,
+    pub token: String,
+}
+
+/// User and scopes of valid access token
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccessTokenOwner {
+    pub user_id: Uuid,
+    pub scopes: Vec<TokenScope>,
+}
+
+pub fn generate_access_token() -> String {
+    let random: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(ACCESS_TOKEN_RANDOM_LEN)
+        .map(char::from)
+        .collect();
+
+    format!("{ACCESS_TOKEN_PREFIX}{random}")
+}
+
+/// Only hash is stored, token has enough entropy for unsalted hash
+pub fn access_token_hash(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
@@ -0,0 +1,79 @@
+use rand::{
+    distributions::Alphanumeric,
+    Rng
+};
+use serde::{
+    Deserialize,
+    Serialize
+};
+use sha2::{
+    Digest,
+    Sha256
+};
+use validator::Validate;
+
+pub const RECOVERY_CODE_COUNT: usize = 10;
+const RECOVERY_CODE_PART_LEN: usize = 5;
+
+/// TOTP secret of user, 2FA is enabled after confirmation with the first code
+pub struct TotpSecret {
+    pub secret: Vec<u8>,
+    pub enabled: bool,
+}
+
+#[derive(Serialize)]
+pub struct TotpEnrollment {
+    /// Base32 secret for manual input
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+/// TOTP code or one of recovery codes
+#[derive(Deserialize, Validate)]
+pub struct TwoFactorCode {
+    #[validate(length(min = 1, max = 32, message = "Code length must be between 1 and 32"))]
+    pub code: String,
+}
+
+#[derive(Serialize)]
+pub struct RecoveryCodes {
+    pub recovery_codes: Vec<String>,
+}
+
+/// Response of login when 2FA is enabled, token is exchanged for bearer token with a code
+#[derive(Serialize)]
+pub struct TwoFactorChallenge {
+    pub challenge_token: String,
+    pub expires_in: i64,
+}
+
+#[derive(Deserialize, Validate)]
+pub struct TwoFactorLogin {
+    #[validate(length(min = 1, max = 4096, message = "Challenge token length must be between 1 and 4096"))]
+    pub challenge_token: String,
+    #[validate(length(min = 1, max = 32, message = "Code length must be between 1 and 32"))]
+    pub code: String,
+}
+
+/// Code like `k3j9a-0qm2x`
+pub fn generate_recovery_code() -> String {
+    let mut rng = rand::thread_rng();
+    let mut part = || -> String {
+        (&mut rng).sample_iter(&Alphanumeric)
+            .take(RECOVERY_CODE_PART_LEN)
+            .map(|c| (c as char).to_ascii_lowercase())
+            .collect()
+    };
+
+    format!("{}-{}", part(), part())
+}
+
+/// Hash of code normalized to lowercase without spaces and dashes
+pub fn recovery_code_hash(code: &str) -> String {
+    let normalized: String = code.chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+
+    format!("{:x}", Sha256::digest(normalized.as_bytes()))
+}
@@ -1,13 +1,67 @@
-use serde::Deserialize;
+use serde::{
+    Deserialize,
+    Serialize
+};
+use uuid::Uuid;
+use validator::Validate;
 
-#[derive(Deserialize)]
+use super::validation::*;
+
+#[derive(Deserialize, Validate)]
 pub struct NewUser {
+    #[validate(
+        length(min = 1, max = "LOGIN_MAX_LEN", message = "Login length must be between 1 and 128"),
+        custom = "validate_login_charset"
+    )]
     pub login: String,
+    #[validate(
+        length(min = "PASSWORD_MIN_LEN", max = "PASSWORD_MAX_LEN", message = "Password length must be between 8 and 128"),
+        custom = "validate_password_strength"
+    )]
     pub password: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Validate)]
 pub struct Login {
+    #[validate(length(min = 1, max = "LOGIN_MAX_LEN", message = "Login length must be between 1 and 128"))]
     pub login: String,
+    #[validate(length(min = 1, max = "PASSWORD_MAX_LEN", message = "Password length must be between 1 and 128"))]
     pub password: String,
-}
\ No newline at end of file
+}
+
+/// Changes password, login tokens issued before are revoked
+#[derive(Deserialize, Validate)]
+pub struct NewPassword {
+    #[validate(length(min = 1, max = "PASSWORD_MAX_LEN", message = "Password length must be between 1 and 128"))]
+    pub old_password: String,
+    #[validate(
+        length(min = "PASSWORD_MIN_LEN", max = "PASSWORD_MAX_LEN", message = "Password length must be between 8 and 128"),
+        custom = "validate_password_strength"
+    )]
+    pub new_password: String,
+}
+
+#[derive(Deserialize, Validate)]
+pub struct NewLogin {
+    #[validate(
+        length(min = 1, max = "LOGIN_MAX_LEN", message = "Login length must be between 1 and 128"),
+        custom = "validate_login_charset"
+    )]
+    pub login: String,
+}
+
+/// Account is deleted with lists, tasks and tokens, password is required as confirmation
+#[derive(Deserialize, Validate)]
+pub struct DeleteUser {
+    #[validate(length(min = 1, max = "PASSWORD_MAX_LEN", message = "Password length must be between 1 and 128"))]
+    pub password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserProfile {
+    pub id: Uuid,
+    pub login: String,
+    pub email: Option<String>,
+    pub email_verified: bool,
+    pub two_factor_enabled: bool,
+}
@@ -0,0 +1,74 @@
+use std::borrow::Cow;
+
+use validator::ValidationError;
+
+pub const LOGIN_MAX_LEN: u64 = 128;
+pub const PASSWORD_MIN_LEN: u64 = 8;
+pub const PASSWORD_MAX_LEN: u64 = 128;
+pub const LIST_NAME_MAX_LEN: u64 = 1024;
+pub const TASK_DESCRIPTION_MAX_LEN: u64 = 4096;
+pub const TASK_RRULE_MAX_LEN: u64 = 512;
+pub const TASK_RANGE_MAX_COUNT: u32 = 100;
+pub const ACCESS_TOKEN_NAME_MAX_LEN: u64 = 128;
+pub const ACCESS_TOKEN_MAX_DAYS: u32 = 3650;
+pub const EMAIL_MAX_LEN: u64 = 254;
+pub const WEBHOOK_URL_MAX_LEN: u64 = 2048;
+pub const WEBHOOK_SECRET_MIN_LEN: u64 = 16;
+pub const WEBHOOK_SECRET_MAX_LEN: u64 = 128;
+
+/// Login may contain only latin letters, digits and `_`, `-`, `.`
+pub fn validate_login_charset(login: &str) -> Result<(), ValidationError> {
+    if login.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.') {
+        return Ok(());
+    }
+
+    Err(error("login_charset", "Login may contain only latin letters, digits, '_', '-' and '.'"))
+}
+
+/// Password must contain at least one letter and one digit
+pub fn validate_password_strength(password: &str) -> Result<(), ValidationError> {
+    let has_letter = password.chars().any(|c| c.is_alphabetic());
+    let has_digit = password.chars().any(|c| c.is_ascii_digit());
+
+    if has_letter && has_digit {
+        return Ok(());
+    }
+
+    Err(error("password_strength", "Password must contain at least one letter and one digit"))
+}
+
+pub fn validate_not_blank(value: &str) -> Result<(), ValidationError> {
+    if !value.trim().is_empty() {
+        return Ok(());
+    }
+
+    Err(error("not_blank", "Value must not be blank"))
+}
+
+/// Absolute http or https url without credentials
+pub fn validate_webhook_url(url: &str) -> Result<(), ValidationError> {
+    match url::Url::parse(url) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") && url.has_host() && url.username().is_empty() && url.password().is_none() => Ok(()),
+        _ => Err(error("webhook_url", "Url must be absolute http or https url without credentials")),
+    }
+}
+
+/// Recurrence rule is a list of `NAME=VALUE` parts with `FREQ` (RFC 5545 3.3.10).
+/// Rule is written to iCalendar as is, so line breaks and other characters of content lines are rejected
+pub fn validate_rrule(rrule: &str) -> Result<(), ValidationError> {
+    let valid_chars = rrule.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || matches!(c, '=' | ';' | ',' | '+' | '-'));
+    let valid_parts = rrule.split(';').all(|part| matches!(part.split_once('='), Some((name, value)) if !name.is_empty() && !value.is_empty()));
+    let has_frequency = rrule.split(';').any(|part| part.starts_with("FREQ="));
+
+    if valid_chars && valid_parts && has_frequency {
+        return Ok(());
+    }
+
+    Err(error("rrule", "Recurrence rule must be like FREQ=WEEKLY;BYDAY=MO"))
+}
+
+pub(super) fn error(code: &'static str, message: &'static str) -> ValidationError {
+    let mut error = ValidationError::new(code);
+    error.message = Some(Cow::Borrowed(message));
+    error
+}
@@ -0,0 +1,309 @@
+use actix_web::{
+    get,
+    http::Method,
+    web,
+    Responder,
+    Route
+};
+
+use crate::{
+    db::Storage,
+    graphql,
+    handlers::*,
+    middlewares::{
+        Idempotency,
+        RateLimit,
+        RequestLogger
+    }
+};
+
+#[get("/ping")]
+async fn ping(logger: RequestLogger) -> impl Responder {
+    slog::info!(logger, "pong");
+    format!("pong")
+}
+
+/// Methods of WebDAV, actix has no guards for them
+fn propfind() -> Route {
+    web::method(Method::from_bytes(b"PROPFIND").unwrap())
+}
+
+fn report() -> Route {
+    web::method(Method::from_bytes(b"REPORT").unwrap())
+}
+
+/// Repositories of the storage as app data for handlers, handlers writing to several of them get the whole storage
+pub fn storage_data(storage: Storage) -> impl FnOnce(&mut web::ServiceConfig) {
+    move |cfg| {
+        cfg.app_data(web::Data::new(storage.clone()))
+            .app_data(web::Data::from(storage.users))
+            .app_data(web::Data::from(storage.lists))
+            .app_data(web::Data::from(storage.tasks))
+            .app_data(web::Data::from(storage.idempotency))
+            .app_data(web::Data::from(storage.access_tokens))
+            .app_data(web::Data::from(storage.two_factor))
+            .app_data(web::Data::from(storage.email_tokens))
+            .app_data(web::Data::from(storage.identities))
+            .app_data(web::Data::from(storage.webhooks))
+            .app_data(web::Data::from(storage.calendar_feeds))
+            .app_data(web::Data::new(storage.events));
+    }
+}
+
+/// Route tree of the service, shared by server and integration tests.
+/// Resources with POST routes creating data accept `Idempotency-Key`,
+/// requests to lists and tasks are limited per user by `RateLimit`.
+/// CalDAV tree is under `/dav`, its requests are limited by `DavAuth`.
+/// GraphQL is under `/graphql`, subscriptions are served on `/graphql/ws`
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.app_data(web::Data::new(graphql::schema()))
+        .service(ping)
+        .service(
+            web::resource("/.well-known/jwks.json")
+                .route(web::get().to(get_jwks))
+        )
+        .service(
+            web::resource("/.well-known/caldav")
+                .to(caldav_well_known)
+        )
+        .service(
+            web::scope("/graphql")
+                .service(
+                    web::resource("")
+                        .route(web::post().to(graphql))
+                        .route(web::get().to(graphiql))
+                )
+                .service(
+                    web::resource("/ws")
+                        .route(web::get().to(graphql_ws))
+                )
+                .wrap(RateLimit)
+        )
+        .service(
+            web::scope("/dav")
+                .service(
+                    web::resource("/")
+                        .route(propfind().to(propfind_root))
+                        .route(web::method(Method::OPTIONS).to(dav_options))
+                )
+                .service(
+                    web::resource("/principal/")
+                        .route(propfind().to(propfind_principal))
+                        .route(web::method(Method::OPTIONS).to(dav_options))
+                )
+                .service(
+                    web::resource("/calendars/")
+                        .route(propfind().to(propfind_calendar_home))
+                        .route(web::method(Method::OPTIONS).to(dav_options))
+                )
+                .service(
+                    web::resource("/calendars/{list_id}/")
+                        .route(propfind().to(propfind_calendar))
+                        .route(report().to(report_calendar))
+                        .route(web::method(Method::OPTIONS).to(dav_options))
+                )
+                .service(
+                    web::resource("/calendars/{list_id}/{name}")
+                        .route(propfind().to(propfind_task))
+                        .route(web::get().to(get_task_resource))
+                        .route(web::head().to(get_task_resource))
+                        .route(web::put().to(put_task_resource))
+                        .route(web::delete().to(delete_task_resource))
+                        .route(web::method(Method::OPTIONS).to(dav_options))
+                )
+        )
+        .service(
+            web::scope("/api")
+                .service(
+                    web::scope("/admin")
+                        .service(
+                            web::resource("/log-level")
+                                .route(web::get().to(get_log_levels))
+                        )
+                        .service(
+                            web::resource("/log-level/{drain}")
+                                .route(web::put().to(set_log_level))
+                        )
+                )
+                .service(
+                    web::scope("/user")
+                        .service(
+                            web::resource("")
+                                .route(web::delete().to(delete_user))
+                        )
+                        .service(
+                            web::resource("/me")
+                                .route(web::get().to(get_user_profile))
+                        )
+                        .service(
+                            web::resource("/password")
+                                .route(web::patch().to(change_password))
+                        )
+                        .service(
+                            web::resource("/password/forgot")
+                                .route(web::post().to(forgot_password))
+                        )
+                        .service(
+                            web::resource("/password/reset")
+                                .route(web::post().to(reset_password))
+                        )
+                        .service(
+                            web::resource("/email")
+                                .route(web::put().to(set_email))
+                        )
+                        .service(
+                            web::resource("/email/verify")
+                                .route(web::post().to(verify_email))
+                        )
+                        .service(
+                            web::resource("/register")
+                                .route(web::post().to(register))
+                                .wrap(Idempotency::from_env())
+                        )
+                        .service(
+                            web::resource("/login")
+                                .route(web::post().to(login))
+                                .route(web::patch().to(change_login))
+                        )
+                        .service(
+                            web::resource("/login/2fa")
+                                .route(web::post().to(login_two_factor))
+                        )
+                        .service(
+                            web::scope("/oidc")
+                                .service(
+                                    web::resource("/login")
+                                        .route(web::get().to(oidc_login))
+                                )
+                                .service(
+                                    web::resource("/link")
+                                        .route(web::post().to(oidc_link))
+                                )
+                                .service(
+                                    web::resource("/callback")
+                                        .route(web::get().to(oidc_callback))
+                                )
+                        )
+                        .service(
+                            web::resource("/identities")
+                                .route(web::get().to(get_identities))
+                        )
+                        .service(
+                            web::resource("/webhooks")
+                                .route(web::get().to(get_webhooks))
+                                .route(web::post().to(new_webhook))
+                                .wrap(Idempotency::from_env())
+                        )
+                        .service(
+                            web::resource("/webhooks/{webhook_id}")
+                                .route(web::patch().to(update_webhook))
+                                .route(web::delete().to(delete_webhook))
+                        )
+                        .service(
+                            web::resource("/webhooks/{webhook_id}/deliveries")
+                                .route(web::get().to(get_webhook_deliveries))
+                        )
+                        .service(
+                            web::resource("/backup")
+                                .route(web::get().to(get_backup))
+                        )
+                        .service(
+                            web::resource("/restore")
+                                .route(web::post().to(restore_backup))
+                        )
+                        .service(
+                            web::resource("/calendar-feed")
+                                .route(web::get().to(get_calendar_feed))
+                                .route(web::post().to(new_calendar_feed))
+                                .route(web::delete().to(delete_calendar_feed))
+                        )
+                        .service(
+                            web::scope("/2fa")
+                                .service(
+                                    web::resource("/enroll")
+                                        .route(web::post().to(enroll_two_factor))
+                                )
+                                .service(
+                                    web::resource("/confirm")
+                                        .route(web::post().to(confirm_two_factor))
+                                )
+                                .service(
+                                    web::resource("/disable")
+                                        .route(web::post().to(disable_two_factor))
+                                )
+                        )
+                        .service(
+                            web::resource("/tokens")
+                                .route(web::get().to(get_access_tokens))
+                                .route(web::post().to(new_access_token))
+                        )
+                        .service(
+                            web::resource("/tokens/{token_id}")
+                                .route(web::delete().to(delete_access_token))
+                        )
+                )
+                .service(
+                    web::resource("/calendar/{token}.ics")
+                        .route(web::get().to(get_calendar))
+                )
+                .service(
+                    web::scope("/list")
+                        .service(
+                            web::resource("")
+                                .route(web::get().to(get_list))
+                                .route(web::post().to(new_list))
+                                .route(web::delete().to(delete_list))
+                                .route(web::patch().to(update_list))
+                                .wrap(Idempotency::from_env())
+                        )
+                        .service(
+                            web::resource("/export")
+                                .route(web::get().to(export_list))
+                        )
+                        .service(
+                            web::resource("/import")
+                                .route(web::post().to(import_list))
+                                .wrap(Idempotency::from_env().body_max_size(IMPORT_FORM_MAX_SIZE))
+                        )
+                        .wrap(RateLimit)
+                )
+                .service(
+                    web::scope("/task")
+                        .service(
+                            web::resource("")
+                                .route(web::get().to(get_tasks))
+                                .route(web::post().to(new_task))
+                                .wrap(Idempotency::from_env())
+                        )
+                        .service(
+                            web::resource("range")
+                                .route(web::get().to(get_tasks_range))
+                        )
+                        .service(
+                            web::scope("/{task_id}")
+                                .service(
+                                    web::resource("")
+                                        .route(web::delete().to(delete_tasks))
+                                        .route(web::patch().to(update_task))
+                                )
+                                .service(
+                                    web::resource("/details")
+                                        .route(web::put().to(update_task_details))
+                                )
+                                .service(
+                                    web::resource("/move")
+                                        .route(web::post().to(move_task))
+                                        .wrap(Idempotency::from_env())
+                                )
+                        )
+                        .wrap(RateLimit)
+                )
+                .service(
+                    web::resource("/sync")
+                        .route(web::get().to(get_sync_changes))
+                        .route(web::post().to(apply_sync_mutations))
+                        .wrap(Idempotency::from_env())
+                        .wrap(RateLimit)
+                )
+        );
+}
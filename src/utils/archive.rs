@@ -0,0 +1,123 @@
+//! Gzipped tar (ustar) archive of small files, which is built and read in memory
+
+use std::io::Read;
+
+use flate2::{
+    read::GzDecoder,
+    write::GzEncoder,
+    Compression
+};
+use tar::{
+    Archive,
+    Builder,
+    EntryType,
+    Header
+};
+
+use crate::models::{
+    ServiceError,
+    StatusCode
+};
+
+/// Archive of the files in the given order, names must be shorter than 100 bytes
+pub fn write_tar_gz(files: &[(&str, &[u8])], mtime: i64) -> Vec<u8> {
+    let mut builder = Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+
+    for (name, data) in files {
+        let mut header = Header::new_ustar();
+        header.set_entry_type(EntryType::Regular);
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(mtime.max(0) as u64);
+
+        // path and checksum are set by builder
+        if builder.append_data(&mut header, name, *data).is_err() {
+            return Vec::new();
+        }
+    }
+
+    builder.into_inner().and_then(|encoder| encoder.finish()).unwrap_or_default()
+}
+
+/// Regular files of the archive with their names. Archive larger than `max_size`
+/// after decompression is rejected
+pub fn read_tar_gz(data: &[u8], max_size: u64) -> Result<Vec<(String, Vec<u8>)>, ServiceError> {
+    let mut tar = Vec::new();
+    GzDecoder::new(data).take(max_size + 1).read_to_end(&mut tar)
+        .map_err(|e| invalid_archive(&e.to_string()))?;
+
+    if tar.len() as u64 > max_size {
+        return Err(ServiceError { status_code: StatusCode::PayloadTooLarge, detail: Some(format!("Archive is larger than {max_size} bytes")) });
+    }
+
+    let mut archive = Archive::new(tar.as_slice());
+    let mut files = Vec::new();
+
+    for entry in archive.entries().map_err(|e| invalid_archive(&e.to_string()))? {
+        let mut entry = entry.map_err(|e| invalid_archive(&e.to_string()))?;
+
+        // directories and links are skipped, extended headers are applied by reader
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+
+        let name = entry.path().map_err(|e| invalid_archive(&e.to_string()))?.to_string_lossy().into_owned();
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data).map_err(|e| invalid_archive(&e.to_string()))?;
+
+        files.push((name, data));
+    }
+
+    Ok(files)
+}
+
+fn invalid_archive(detail: &str) -> ServiceError {
+    ServiceError { status_code: StatusCode::BadRequest, detail: Some(format!("Invalid archive: {detail}")) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn archive_roundtrip() {
+        let big = vec![b'x'; 1300];
+        let archive = write_tar_gz(&[("manifest.json", b"{}"), ("empty", b""), ("big", &big)], 1792432800);
+
+        let files = read_tar_gz(&archive, 1 << 20).unwrap();
+        assert_eq!(files.len(), 3);
+        assert_eq!(files[0], ("manifest.json".to_string(), b"{}".to_vec()));
+        assert_eq!(files[1], ("empty".to_string(), Vec::new()));
+        assert_eq!(files[2].1, big);
+    }
+
+    #[test]
+    fn header_is_ustar() {
+        let archive = write_tar_gz(&[("tasks.json", b"0123456789")], 1792432800);
+
+        let mut tar = Archive::new(GzDecoder::new(archive.as_slice()));
+        let entry = tar.entries().unwrap().next().unwrap().unwrap();
+        let header = entry.header();
+
+        assert!(header.as_ustar().is_some());
+        assert_eq!(header.size().unwrap(), 10);
+        assert_eq!(header.mtime().unwrap(), 1792432800);
+        assert_eq!(header.mode().unwrap(), 0o644);
+    }
+
+    #[test]
+    fn invalid_archives_are_rejected() {
+        assert!(matches!(read_tar_gz(b"not gzip", 1024).unwrap_err().status_code, StatusCode::BadRequest));
+
+        let archive = write_tar_gz(&[("big", &[0; 2048])], 0);
+        assert!(matches!(read_tar_gz(&archive, 1024).unwrap_err().status_code, StatusCode::PayloadTooLarge));
+
+        // data of the file is cut
+        let mut tar = Vec::new();
+        GzDecoder::new(archive.as_slice()).read_to_end(&mut tar).unwrap();
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        std::io::Write::write_all(&mut encoder, &tar[..1024]).unwrap();
+        let truncated = encoder.finish().unwrap();
+        assert!(matches!(read_tar_gz(&truncated, 1 << 20).unwrap_err().status_code, StatusCode::BadRequest));
+    }
+}
@@ -0,0 +1,381 @@
+//! Backup archive of the account: `manifest.json` with checksums of data files, `account.json`
+//! (not restored), `lists.json` and `tasks.json`. Archive of older version is upgraded on restore
+
+use std::collections::HashMap;
+
+use chrono::{
+    DateTime,
+    Utc
+};
+use serde::{
+    de::DeserializeOwned,
+    Serialize
+};
+use serde_json::Value;
+use sha2::{
+    Digest,
+    Sha256
+};
+use validator::Validate;
+
+use crate::models::{
+    BackupAccount,
+    BackupFile,
+    BackupList,
+    BackupManifest,
+    BackupTask,
+    BackupUser,
+    NewTodoList,
+    RequestValidationError,
+    ServiceError,
+    StatusCode,
+    UpdateTask
+};
+
+use super::archive::{
+    read_tar_gz,
+    write_tar_gz
+};
+
+pub const BACKUP_FORMAT: &str = "todo-list-rs-backup";
+pub const BACKUP_VERSION: u32 = 2;
+
+/// Size of unpacked archive
+pub const BACKUP_MAX_SIZE: u64 = 32 * 1024 * 1024;
+/// Tasks of the restored list, they are added in one transaction
+pub const BACKUP_MAX_TASKS: usize = 10_000;
+
+const MANIFEST: &str = "manifest.json";
+
+/// Data files of archive by name
+type DataFiles = HashMap<String, Value>;
+
+/// Step, which changes data files of a version to the next version
+type Upgrade = fn(&mut DataFiles) -> Result<(), ServiceError>;
+
+/// Step with index `n` upgrades version `n + 1`, step is added with every new version
+const UPGRADES: [Upgrade; BACKUP_VERSION as usize - 1] = [add_task_details];
+
+/// List with tasks sorted by order, which are restored
+#[derive(Debug)]
+pub struct Backup {
+    pub version: u32,
+    pub list: Option<BackupList>,
+    pub tasks: Vec<BackupTask>,
+}
+
+pub fn write_backup(user: BackupUser, account: &BackupAccount, lists: &[BackupList], tasks: &[BackupTask], created_at: DateTime<Utc>) -> Vec<u8> {
+    let files = [
+        ("account.json", to_json(account)),
+        ("lists.json", to_json(lists)),
+        ("tasks.json", to_json(tasks)),
+    ];
+
+    let manifest = BackupManifest {
+        format: BACKUP_FORMAT.to_string(),
+        version: BACKUP_VERSION,
+        created_at,
+        user,
+        files: files.iter()
+            .map(|(name, data)| BackupFile { name: name.to_string(), size: data.len() as u64, sha256: sha256(data) })
+            .collect(),
+    };
+    let manifest = to_json(&manifest);
+
+    let entries: Vec<(&str, &[u8])> = std::iter::once((MANIFEST, manifest.as_slice()))
+        .chain(files.iter().map(|(name, data)| (*name, data.as_slice())))
+        .collect();
+
+    write_tar_gz(&entries, created_at.timestamp())
+}
+
+/// Checks and upgrades the archive. Damaged archive, archive of newer version or
+/// with data, which user can't have, is rejected with `422`
+pub fn read_backup(archive: &[u8]) -> Result<Backup, ServiceError> {
+    read_backup_of_version(archive, BACKUP_VERSION, &UPGRADES)
+}
+
+/// Backup, which is upgraded to `supported` version by `upgrades` steps
+fn read_backup_of_version(archive: &[u8], supported: u32, upgrades: &[Upgrade]) -> Result<Backup, ServiceError> {
+    let mut entries: HashMap<String, Vec<u8>> = read_tar_gz(archive, BACKUP_MAX_SIZE)?.into_iter().collect();
+
+    let manifest: Value = entries.remove(MANIFEST)
+        .ok_or_else(|| unprocessable(format!("Archive has no {MANIFEST}, it's not a backup")))
+        .and_then(|manifest| parse(MANIFEST, &manifest))?;
+
+    if manifest["format"] != BACKUP_FORMAT {
+        return Err(unprocessable("Archive is not a backup of the service".to_string()));
+    }
+
+    let version = manifest["version"].as_u64()
+        .and_then(|version| u32::try_from(version).ok())
+        .filter(|version| *version >= 1)
+        .ok_or_else(|| unprocessable("Invalid version of backup".to_string()))?;
+
+    if version > supported {
+        return Err(unprocessable(format!("Backup version {version} is newer than supported version {supported}")));
+    }
+
+    let listed: Vec<BackupFile> = serde_json::from_value(manifest["files"].clone())
+        .map_err(|e| unprocessable(format!("Invalid {MANIFEST}: {e}")))?;
+
+    let mut files = DataFiles::new();
+    for file in listed {
+        let data = entries.remove(&file.name)
+            .ok_or_else(|| unprocessable(format!("File {} of backup not found", file.name)))?;
+
+        if data.len() as u64 != file.size || sha256(&data) != file.sha256 {
+            return Err(unprocessable(format!("File {} of backup is damaged", file.name)));
+        }
+
+        files.insert(file.name.clone(), parse(&file.name, &data)?);
+    }
+
+    for upgrade in &upgrades[version as usize - 1..] {
+        upgrade(&mut files)?;
+    }
+
+    let mut lists: Vec<BackupList> = data_file(&mut files, "lists.json")?;
+    let mut tasks: Vec<BackupTask> = data_file(&mut files, "tasks.json")?;
+
+    if tasks.len() > BACKUP_MAX_TASKS {
+        return Err(unprocessable(format!("Backup can have at most {BACKUP_MAX_TASKS} tasks")));
+    }
+
+    if lists.len() > 1 {
+        return Err(unprocessable(format!("Backup has {} lists, user can have one list", lists.len())));
+    }
+    let list = lists.pop();
+
+    if let Some(task) = tasks.iter().find(|task| list.as_ref().map(|list| list.id) != Some(task.list_id)) {
+        return Err(unprocessable(format!("Task {} belongs to list, which is not in backup", task.id)));
+    }
+
+    if let Some(list) = &list {
+        NewTodoList { name: list.name.clone() }.validate().map_err(invalid_data)?;
+    }
+    for task in &tasks {
+        UpdateTask { description: task.description.clone() }.validate().map_err(invalid_data)?;
+        task.details().validate().map_err(invalid_data)?;
+    }
+
+    tasks.sort_by_key(|task| task.order);
+
+    Ok(Backup { version, list, tasks })
+}
+
+/// Version 2: tasks have due date, completion, priority and recurrence, tasks of version 1 have none of them
+fn add_task_details(files: &mut DataFiles) -> Result<(), ServiceError> {
+    if let Some(Value::Array(tasks)) = files.get_mut("tasks.json") {
+        for task in tasks.iter_mut().filter_map(Value::as_object_mut) {
+            for field in ["due_at", "completed_at", "priority", "rrule"] {
+                task.insert(field.to_string(), Value::Null);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn to_json<T: Serialize + ?Sized>(value: &T) -> Vec<u8> {
+    serde_json::to_vec_pretty(value).unwrap_or_default()
+}
+
+fn sha256(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+fn parse(name: &str, data: &[u8]) -> Result<Value, ServiceError> {
+    serde_json::from_slice(data).map_err(|e| unprocessable(format!("Invalid {name}: {e}")))
+}
+
+/// Data file, which is empty if archive doesn't have it
+fn data_file<T: DeserializeOwned>(files: &mut DataFiles, name: &str) -> Result<Vec<T>, ServiceError> {
+    match files.remove(name) {
+        Some(value) => serde_json::from_value(value).map_err(|e| unprocessable(format!("Invalid {name}: {e}"))),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn invalid_data(errors: validator::ValidationErrors) -> ServiceError {
+    let messages: Vec<String> = RequestValidationError::from(errors).fields.into_values().flatten().collect();
+    unprocessable(format!("Invalid data of backup: {}", messages.join(", ")))
+}
+
+fn unprocessable(detail: String) -> ServiceError {
+    ServiceError { status_code: StatusCode::UnprocessableEntity, detail: Some(detail) }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::models::TaskDetails;
+
+    fn account() -> BackupAccount {
+        BackupAccount {
+            login: "user".to_string(),
+            email: None,
+            email_verified: false,
+            two_factor_enabled: false,
+            calendar_feed: None,
+            access_tokens: Vec::new(),
+            webhooks: Vec::new(),
+            identities: Vec::new(),
+        }
+    }
+
+    fn backup(lists: &[BackupList], tasks: &[BackupTask]) -> Vec<u8> {
+        let user = BackupUser { id: Uuid::new_v4(), login: "user".to_string() };
+        write_backup(user, &account(), lists, tasks, Utc.timestamp_opt(1792432800, 0).unwrap())
+    }
+
+    fn task(list_id: Uuid, order: i32, description: &str) -> BackupTask {
+        BackupTask { id: Uuid::new_v4(), list_id, order, description: description.to_string(), due_at: None, completed_at: None, priority: None, rrule: None }
+    }
+
+    /// Archive with changed files, manifest is kept unless it's changed too
+    fn repack(archive: &[u8], change: impl Fn(&str, &mut Vec<u8>)) -> Vec<u8> {
+        let mut files = read_tar_gz(archive, BACKUP_MAX_SIZE).unwrap();
+        for (name, data) in &mut files {
+            change(name, data);
+        }
+
+        let entries: Vec<(&str, &[u8])> = files.iter().map(|(name, data)| (name.as_str(), data.as_slice())).collect();
+        write_tar_gz(&entries, 0)
+    }
+
+    #[test]
+    fn backup_roundtrip() {
+        let list = BackupList { id: Uuid::new_v4(), name: "Home".to_string() };
+        let tasks = [task(list.id, 2, "second"), task(list.id, 1, "first")];
+
+        let restored = read_backup(&backup(&[list], &tasks)).unwrap();
+
+        assert_eq!(restored.version, BACKUP_VERSION);
+        assert_eq!(restored.list.unwrap().name, "Home");
+        assert_eq!(restored.tasks.iter().map(|task| task.description.as_str()).collect::<Vec<_>>(), ["first", "second"]);
+
+        let empty = read_backup(&backup(&[], &[])).unwrap();
+        assert!(empty.list.is_none() && empty.tasks.is_empty());
+    }
+
+    #[test]
+    fn task_details_are_kept() {
+        let list = BackupList { id: Uuid::new_v4(), name: "Home".to_string() };
+        let due_at = Utc.timestamp_opt(1792519200, 0).unwrap();
+        let tasks = [BackupTask { due_at: Some(due_at), completed_at: Some(due_at), priority: Some(1), rrule: Some("FREQ=DAILY".to_string()), ..task(list.id, 1, "first") }];
+
+        let restored = read_backup(&backup(&[list], &tasks)).unwrap();
+        assert_eq!(restored.tasks[0].details(), tasks[0].details());
+
+        let list = BackupList { id: Uuid::new_v4(), name: "Home".to_string() };
+        let invalid = [BackupTask { priority: Some(10), ..task(list.id, 1, "first") }];
+        assert_eq!(read_backup(&backup(&[list], &invalid)).unwrap_err().detail.unwrap(), "Invalid data of backup: Priority must be between 1 and 9");
+    }
+
+    #[test]
+    fn tasks_of_first_version_get_no_details() {
+        let list_id = Uuid::new_v4();
+        let old = archive_of_version(1, serde_json::json!([{ "id": list_id, "name": "Home" }]), serde_json::json!([
+            { "id": Uuid::new_v4(), "list_id": list_id, "order": 1, "description": "first" },
+        ]));
+
+        let restored = read_backup(&old).unwrap();
+        assert_eq!(restored.version, 1);
+        assert_eq!(restored.tasks[0].description, "first");
+        assert_eq!(restored.tasks[0].details(), TaskDetails::default());
+    }
+
+    #[test]
+    fn damaged_and_newer_backups_are_rejected() {
+        let list = BackupList { id: Uuid::new_v4(), name: "Home".to_string() };
+        let archive = backup(&[list], &[]);
+
+        let damaged = repack(&archive, |name, data| if name == "lists.json" { data.push(b' ') });
+        assert_eq!(read_backup(&damaged).unwrap_err().detail.unwrap(), "File lists.json of backup is damaged");
+
+        let newer = repack(&archive, |name, data| if name == MANIFEST {
+            let mut manifest: Value = serde_json::from_slice(data).unwrap();
+            manifest["version"] = (BACKUP_VERSION + 1).into();
+            *data = serde_json::to_vec(&manifest).unwrap();
+        });
+        assert!(read_backup(&newer).unwrap_err().detail.unwrap().starts_with(&format!("Backup version {} is newer", BACKUP_VERSION + 1)));
+
+        let foreign = repack(&archive, |name, data| if name == MANIFEST { *data = b"{\"format\": \"other\"}".to_vec() });
+        assert!(read_backup(&foreign).is_err());
+    }
+
+    /// Archive with data files of older or newer structure
+    fn archive_of_version(version: u32, lists: Value, tasks: Value) -> Vec<u8> {
+        let files = [("lists.json", to_json(&lists)), ("tasks.json", to_json(&tasks))];
+        let manifest = BackupManifest {
+            format: BACKUP_FORMAT.to_string(),
+            version,
+            created_at: Utc.timestamp_opt(1792432800, 0).unwrap(),
+            user: BackupUser { id: Uuid::new_v4(), login: "user".to_string() },
+            files: files.iter()
+                .map(|(name, data)| BackupFile { name: name.to_string(), size: data.len() as u64, sha256: sha256(data) })
+                .collect(),
+        };
+        let manifest = to_json(&manifest);
+
+        let entries: Vec<(&str, &[u8])> = std::iter::once((MANIFEST, manifest.as_slice()))
+            .chain(files.iter().map(|(name, data)| (*name, data.as_slice())))
+            .collect();
+        write_tar_gz(&entries, 0)
+    }
+
+    /// Step of the test version 2, where `text` of task is renamed to `description`
+    fn rename_text(files: &mut DataFiles) -> Result<(), ServiceError> {
+        if let Some(Value::Array(tasks)) = files.get_mut("tasks.json") {
+            for task in tasks.iter_mut().filter_map(Value::as_object_mut) {
+                let text = task.remove("text").ok_or_else(|| unprocessable("Task has no text".to_string()))?;
+                task.insert("description".to_string(), text);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn older_backup_is_upgraded() {
+        let list_id = Uuid::new_v4();
+        let lists = serde_json::json!([{ "id": list_id, "name": "Home" }]);
+        let upgrades: [Upgrade; 1] = [rename_text];
+
+        let old = archive_of_version(1, lists.clone(), serde_json::json!([
+            { "id": Uuid::new_v4(), "list_id": list_id, "order": 2, "text": "second" },
+            { "id": Uuid::new_v4(), "list_id": list_id, "order": 1, "text": "first" },
+        ]));
+        let restored = read_backup_of_version(&old, 2, &upgrades).unwrap();
+        assert_eq!(restored.version, 1);
+        assert_eq!(restored.tasks.iter().map(|task| task.description.as_str()).collect::<Vec<_>>(), ["first", "second"]);
+
+        // archive of the current version is not upgraded
+        let current = archive_of_version(2, lists.clone(), serde_json::json!([
+            { "id": Uuid::new_v4(), "list_id": list_id, "order": 1, "description": "first" },
+        ]));
+        assert_eq!(read_backup_of_version(&current, 2, &upgrades).unwrap().tasks[0].description, "first");
+
+        let broken = archive_of_version(1, lists, serde_json::json!([{ "id": Uuid::new_v4(), "list_id": list_id, "order": 1 }]));
+        assert_eq!(read_backup_of_version(&broken, 2, &upgrades).unwrap_err().detail.unwrap(), "Task has no text");
+    }
+
+    #[test]
+    fn invalid_data_is_rejected() {
+        let list = BackupList { id: Uuid::new_v4(), name: "Home".to_string() };
+        let other = BackupList { id: Uuid::new_v4(), name: "Work".to_string() };
+
+        assert!(read_backup(&backup(&[list, other], &[])).is_err());
+
+        let list = BackupList { id: Uuid::new_v4(), name: "Home".to_string() };
+        assert!(read_backup(&backup(&[], &[task(list.id, 1, "orphan")])).is_err());
+        assert!(read_backup(&backup(&[BackupList { id: list.id, name: " ".to_string() }], &[])).is_err());
+        assert!(read_backup(&backup(&[list], &[])).is_ok());
+
+        let list = BackupList { id: Uuid::new_v4(), name: "Home".to_string() };
+        let tasks: Vec<BackupTask> = (1..=BACKUP_MAX_TASKS as i32 + 1).map(|order| task(list.id, order, "task")).collect();
+        assert_eq!(read_backup(&backup(&[list], &tasks)).unwrap_err().detail.unwrap(), format!("Backup can have at most {BACKUP_MAX_TASKS} tasks"));
+    }
+}
@@ -0,0 +1,279 @@
+//! WebDAV (RFC 4918) and CalDAV (RFC 4791) xml: properties of requests and multi-status responses
+
+use roxmltree::{
+    Document,
+    Node
+};
+
+use crate::models::{
+    ServiceError,
+    StatusCode
+};
+
+pub const DAV_NS: &str = "DAV:";
+pub const CALDAV_NS: &str = "urn:ietf:params:xml:ns:caldav";
+/// Namespace of `getctag`, clients compare it to skip sync of unchanged collection
+pub const CALENDARSERVER_NS: &str = "http://calendarserver.org/ns/";
+
+pub const CONTENT_TYPE: &str = "application/xml; charset=utf-8";
+
+/// Prefixes of the namespaces declared on `multistatus`
+const PREFIXES: [(&str, &str); 3] = [(DAV_NS, "d"), (CALDAV_NS, "c"), (CALENDARSERVER_NS, "cs")];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PropName {
+    pub namespace: String,
+    pub name: String,
+}
+
+impl PropName {
+    pub fn new(namespace: &str, name: &str) -> Self {
+        Self { namespace: namespace.to_string(), name: name.to_string() }
+    }
+
+    /// Element of the property with xml `value`, property of unknown namespace declares it
+    fn element(&self, value: &str) -> String {
+        let (name, declaration) = match PREFIXES.iter().find(|(namespace, _)| *namespace == self.namespace) {
+            Some((_, prefix)) => (format!("{prefix}:{}", self.name), String::new()),
+            None => (format!("x:{}", self.name), format!(" xmlns:x=\"{}\"", escape(&self.namespace))),
+        };
+
+        match value.is_empty() {
+            true => format!("<{name}{declaration}/>"),
+            false => format!("<{name}{declaration}>{value}</{name}>"),
+        }
+    }
+}
+
+/// Properties asked by PROPFIND or REPORT
+#[derive(Debug, PartialEq)]
+pub enum PropRequest {
+    /// `allprop` or `propname`, also request without body
+    All,
+    Props(Vec<PropName>),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Report {
+    /// Only component filter is applied, `time-range` is not and all tasks match it: `todos` is `false`
+    /// if the query asks for other components than VTODO
+    CalendarQuery { props: PropRequest, todos: bool },
+    CalendarMultiget { props: PropRequest, hrefs: Vec<String> },
+}
+
+pub fn parse_propfind(body: &str) -> Result<PropRequest, ServiceError> {
+    if body.trim().is_empty() {
+        return Ok(PropRequest::All);
+    }
+
+    let document = parse(body)?;
+    let root = document.root_element();
+
+    if !is(root, DAV_NS, "propfind") {
+        return Err(invalid_xml("propfind element not found"));
+    }
+
+    Ok(prop_request(root))
+}
+
+pub fn parse_report(body: &str) -> Result<Report, ServiceError> {
+    let document = parse(body)?;
+    let root = document.root_element();
+
+    if is(root, CALDAV_NS, "calendar-query") {
+        let calendar_filter = child(root, CALDAV_NS, "filter")
+            .and_then(|filter| child(filter, CALDAV_NS, "comp-filter"));
+
+        // filter of VCALENDAR without nested component filter matches all objects
+        let todos = match calendar_filter.and_then(|filter| child(filter, CALDAV_NS, "comp-filter")) {
+            Some(component_filter) => component_filter.attribute("name").is_some_and(|name| name.eq_ignore_ascii_case("VTODO")),
+            None => true,
+        };
+
+        return Ok(Report::CalendarQuery { props: prop_request(root), todos });
+    }
+
+    if is(root, CALDAV_NS, "calendar-multiget") {
+        let hrefs = root.children()
+            .filter(|node| is(*node, DAV_NS, "href"))
+            .filter_map(|node| node.text())
+            .map(|href| href.trim().to_string())
+            .collect();
+
+        return Ok(Report::CalendarMultiget { props: prop_request(root), hrefs });
+    }
+
+    Err(ServiceError { status_code: StatusCode::Forbidden, detail: Some(format!("Report \"{}\" is not supported", root.tag_name().name())) })
+}
+
+/// Body of `207 Multi-Status` response
+pub struct MultiStatus {
+    out: String,
+}
+
+impl Default for MultiStatus {
+    fn default() -> Self {
+        let mut out = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<d:multistatus");
+        for (namespace, prefix) in PREFIXES {
+            out.push_str(&format!(" xmlns:{prefix}=\"{namespace}\""));
+        }
+        out.push('>');
+
+        Self { out }
+    }
+}
+
+impl MultiStatus {
+    /// Resource with found properties and their xml values, and with properties it doesn't have
+    pub fn response(&mut self, href: &str, found: &[(PropName, String)], not_found: &[PropName]) -> &mut Self {
+        self.out.push_str(&format!("<d:response><d:href>{}</d:href>", escape(href)));
+
+        if !found.is_empty() {
+            self.propstat(found.iter().map(|(prop, value)| prop.element(value)), "200 OK");
+        }
+        if !not_found.is_empty() {
+            self.propstat(not_found.iter().map(|prop| prop.element("")), "404 Not Found");
+        }
+
+        self.out.push_str("</d:response>");
+        self
+    }
+
+    /// Resource asked by href, which doesn't exist
+    pub fn missing(&mut self, href: &str) -> &mut Self {
+        self.out.push_str(&format!("<d:response><d:href>{}</d:href><d:status>HTTP/1.1 404 Not Found</d:status></d:response>", escape(href)));
+        self
+    }
+
+    pub fn finish(mut self) -> String {
+        self.out.push_str("</d:multistatus>");
+        self.out
+    }
+
+    fn propstat(&mut self, props: impl Iterator<Item = String>, status: &str) {
+        self.out.push_str("<d:propstat><d:prop>");
+        self.out.extend(props);
+        self.out.push_str(&format!("</d:prop><d:status>HTTP/1.1 {status}</d:status></d:propstat>"));
+    }
+}
+
+/// Body of `403 Forbidden` response to request, which doesn't meet CalDAV precondition (RFC 4791 1.3),
+/// e.g. `supported-calendar-component`
+pub fn precondition_error(precondition: &str, detail: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<d:error xmlns:d=\"{DAV_NS}\" xmlns:c=\"{CALDAV_NS}\"><c:{precondition}/><d:responsedescription>{}</d:responsedescription></d:error>",
+        escape(detail)
+    )
+}
+
+/// Escapes text of element or attribute
+pub fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+fn parse(body: &str) -> Result<Document<'_>, ServiceError> {
+    Document::parse(body).map_err(|e| invalid_xml(&e.to_string()))
+}
+
+fn prop_request(root: Node) -> PropRequest {
+    match child(root, DAV_NS, "prop") {
+        Some(prop) => PropRequest::Props(prop.children()
+            .filter(|node| node.is_element())
+            .map(|node| PropName::new(node.tag_name().namespace().unwrap_or_default(), node.tag_name().name()))
+            .collect()),
+        None => PropRequest::All,
+    }
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, namespace: &str, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|child| is(*child, namespace, name))
+}
+
+fn is(node: Node, namespace: &str, name: &str) -> bool {
+    node.is_element() && node.tag_name().namespace() == Some(namespace) && node.tag_name().name() == name
+}
+
+fn invalid_xml(detail: &str) -> ServiceError {
+    ServiceError { status_code: StatusCode::BadRequest, detail: Some(format!("Invalid xml: {detail}")) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn propfind_props() {
+        let body = r#"<?xml version="1.0"?>
+            <D:propfind xmlns:D="DAV:" xmlns:CS="http://calendarserver.org/ns/" xmlns:X="urn:unknown">
+              <D:prop><D:resourcetype/><CS:getctag/><X:color/></D:prop>
+            </D:propfind>"#;
+
+        assert_eq!(parse_propfind(body).unwrap(), PropRequest::Props(vec![
+            PropName::new(DAV_NS, "resourcetype"),
+            PropName::new(CALENDARSERVER_NS, "getctag"),
+            PropName::new("urn:unknown", "color"),
+        ]));
+        assert_eq!(parse_propfind("").unwrap(), PropRequest::All);
+        assert_eq!(parse_propfind(r#"<propfind xmlns="DAV:"><allprop/></propfind>"#).unwrap(), PropRequest::All);
+        assert!(parse_propfind("<propfind").is_err());
+    }
+
+    #[test]
+    fn calendar_query_component_filter() {
+        let query = |filter: &str| parse_report(&format!(
+            r#"<c:calendar-query xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav"><d:prop><d:getetag/></d:prop><c:filter>{filter}</c:filter></c:calendar-query>"#
+        )).unwrap();
+
+        let todos = |report: Report| match report {
+            Report::CalendarQuery { todos, .. } => todos,
+            _ => panic!("not calendar-query"),
+        };
+
+        assert!(todos(query(r#"<c:comp-filter name="VCALENDAR"><c:comp-filter name="VTODO"><c:prop-filter name="COMPLETED"><c:is-not-defined/></c:prop-filter></c:comp-filter></c:comp-filter>"#)));
+        assert!(todos(query(r#"<c:comp-filter name="VCALENDAR"/>"#)));
+        assert!(!todos(query(r#"<c:comp-filter name="VCALENDAR"><c:comp-filter name="VEVENT"/></c:comp-filter>"#)));
+    }
+
+    #[test]
+    fn multiget_hrefs() {
+        let body = r#"<c:calendar-multiget xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+            <d:prop><d:getetag/><c:calendar-data/></d:prop>
+            <d:href>/dav/calendars/1/a.ics</d:href>
+            <d:href> /dav/calendars/1/b.ics </d:href>
+        </c:calendar-multiget>"#;
+
+        let Report::CalendarMultiget { props, hrefs } = parse_report(body).unwrap() else { panic!("not multiget") };
+
+        assert_eq!(props, PropRequest::Props(vec![PropName::new(DAV_NS, "getetag"), PropName::new(CALDAV_NS, "calendar-data")]));
+        assert_eq!(hrefs, ["/dav/calendars/1/a.ics", "/dav/calendars/1/b.ics"]);
+        assert!(parse_report(r#"<d:sync-collection xmlns:d="DAV:"/>"#).is_err());
+    }
+
+    #[test]
+    fn multistatus_xml() {
+        let mut multistatus = MultiStatus::default();
+        multistatus.response("/dav/a&b/", &[(PropName::new(DAV_NS, "displayname"), escape("Home & work"))], &[PropName::new("urn:unknown", "color")])
+            .missing("/dav/missing.ics");
+        let xml = multistatus.finish();
+
+        let document = Document::parse(&xml).unwrap();
+        let responses: Vec<_> = document.root_element().children().filter(|node| is(*node, DAV_NS, "response")).collect();
+        assert_eq!(responses.len(), 2);
+
+        assert!(xml.contains("<d:href>/dav/a&amp;b/</d:href>"));
+        assert!(xml.contains("<d:prop><d:displayname>Home &amp; work</d:displayname></d:prop><d:status>HTTP/1.1 200 OK</d:status>"));
+        assert!(xml.contains("<d:prop><x:color xmlns:x=\"urn:unknown\"/></d:prop><d:status>HTTP/1.1 404 Not Found</d:status>"));
+    }
+}
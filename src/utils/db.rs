@@ -6,19 +6,56 @@ use sqlx::{
     migrate::Migrator
 };
 
+use crate::db::{
+    memory::MemoryStorage,
+    postgres::PgStorage,
+    Storage
+};
+
 const DATABASE_URL_ENV: &str = "DATABASE_URL";
 
-pub async fn prepare_db(logger: &Logger) -> anyhow::Result<PgPool> {
-    let db_address = get_db_string();
+pub async fn prepare_storage(logger: &Logger) -> anyhow::Result<Storage> {
+    connect_storage(logger, &get_db_string()).await
+}
+
+/// Storage backend is selected by database url scheme: `postgres://`, `sqlite://` or `memory://`
+pub async fn connect_storage(logger: &Logger, db_address: &str) -> anyhow::Result<Storage> {
+    if db_address.starts_with("memory:") {
+        slog::warn!(logger, "{DATABASE_URL_ENV}:[{db_address}]. Using in-memory storage, data will be lost on restart");
+        return Ok(Storage::new(MemoryStorage::new()));
+    }
+
+    if db_address.starts_with("sqlite:") {
+        return prepare_sqlite(logger, db_address).await;
+    }
+
+    let db_pool = prepare_db(logger, db_address).await?;
+    Ok(Storage::new(PgStorage::new(db_pool)))
+}
+
+pub async fn prepare_db(logger: &Logger, db_address: &str) -> anyhow::Result<PgPool> {
     slog::info!(logger, "{DATABASE_URL_ENV}:[{db_address}]. Creating connection pool ...");
 
-    let db_pool = PgPool::connect(&db_address).await?;
+    let db_pool = PgPool::connect(db_address).await?;
     let migrator = Migrator::new(std::path::Path::new("./migrations")).await?;
     migrator.run(&db_pool).await?;
     Ok(db_pool)
 }
 
+#[cfg(feature = "sqlite")]
+async fn prepare_sqlite(logger: &Logger, db_address: &str) -> anyhow::Result<Storage> {
+    slog::info!(logger, "{DATABASE_URL_ENV}:[{db_address}]. Opening sqlite database ...");
+
+    let storage = crate::db::sqlite::SqliteStorage::connect(db_address).await?;
+    Ok(Storage::new(storage))
+}
+
+#[cfg(not(feature = "sqlite"))]
+async fn prepare_sqlite(_logger: &Logger, db_address: &str) -> anyhow::Result<Storage> {
+    anyhow::bail!("{DATABASE_URL_ENV}:[{db_address}]. Service is built without sqlite support, rebuild with `--features sqlite`")
+}
+
 fn get_db_string() -> String {
     env::var(DATABASE_URL_ENV)
         .expect(&*format!("Env {DATABASE_URL_ENV} not found"))
-}
\ No newline at end of file
+}
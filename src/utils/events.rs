@@ -0,0 +1,90 @@
+use std::sync::Arc;
+
+use futures::{
+    stream,
+    Stream
+};
+use tokio::sync::broadcast::{
+    self,
+    error::RecvError
+};
+use uuid::Uuid;
+
+use crate::models::{
+    ChangeEvent,
+    Outbox
+};
+
+/// Events kept for slow subscribers, older events are skipped by them
+const SUBSCRIBER_BUFFER_SIZE: usize = 1024;
+
+/// Change notifications of lists and tasks for subscribers of this process (GraphQL subscriptions),
+/// which get events published after they subscribed. Webhooks get the events from outbox, which is
+/// stored with the change. Clones share subscribers
+#[derive(Clone)]
+pub struct ChangeEvents {
+    sender: broadcast::Sender<Arc<ChangeEvent>>,
+}
+
+impl ChangeEvents {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(SUBSCRIBER_BUFFER_SIZE);
+
+        Self { sender }
+    }
+
+    /// Sends events of the committed write
+    pub fn publish(&self, outbox: Outbox) {
+        for event in outbox.into_changes() {
+            // no subscribers is not an error
+            let _ = self.sender.send(Arc::new(event));
+        }
+    }
+
+    /// Events of the user, stream ends when all senders are dropped
+    pub fn subscribe(&self, user_id: Uuid) -> impl Stream<Item = Arc<ChangeEvent>> + Send + 'static {
+        stream::unfold(self.sender.subscribe(), move |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) if event.user_id == user_id => return Some((event, receiver)),
+                    Ok(_) | Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        })
+    }
+}
+
+impl Default for ChangeEvents {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+
+    use super::*;
+    use crate::models::WebhookEvent;
+
+    fn event(user_id: Uuid, event: WebhookEvent) -> Outbox {
+        let mut outbox = Outbox::new(user_id);
+        outbox.add(event, &serde_json::json!({})).unwrap();
+        outbox
+    }
+
+    #[tokio::test]
+    async fn subscriber_gets_events_of_its_user() {
+        let events = ChangeEvents::new();
+        let user_id = Uuid::new_v4();
+        let mut subscription = Box::pin(events.subscribe(user_id));
+
+        events.publish(event(Uuid::new_v4(), WebhookEvent::TaskCreated));
+        events.publish(event(user_id, WebhookEvent::TaskDeleted));
+
+        let received = subscription.next().await.unwrap();
+        assert_eq!(received.user_id, user_id);
+        assert_eq!(received.event, WebhookEvent::TaskDeleted);
+    }
+}
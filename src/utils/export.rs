@@ -0,0 +1,340 @@
+//! Export of the list and its tasks. Output is produced by chunks: head, one chunk per task
+//! and tail, so response is streamed without building the whole file
+
+use chrono::{
+    DateTime,
+    SecondsFormat,
+    Utc
+};
+
+use crate::{
+    models::{
+        ExportFormat,
+        ExportedTask,
+        FullTaskInfo,
+        FullTodoListInfo
+    },
+    utils::import::todo_txt_needs_escape
+};
+
+/// Version of json export, changed with incompatible changes of its structure
+pub const JSON_EXPORT_VERSION: u32 = 1;
+
+const CSV_HEADER: &str = "list_id,list_name,task_id,order,description,completed_at,due_at,priority,rrule\r\n";
+
+/// Export of the tasks sorted by order, export is empty if user has no list. Chunks may be empty
+pub struct Exporter {
+    format: ExportFormat,
+    list: Option<FullTodoListInfo>,
+}
+
+impl Exporter {
+    pub fn new(format: ExportFormat, list: Option<FullTodoListInfo>) -> Self {
+        Self { format, list }
+    }
+
+    pub fn head(&self, exported_at: DateTime<Utc>) -> String {
+        match (self.format, &self.list) {
+            (ExportFormat::Json, list) => {
+                let mut head = format!(
+                    "{{\"version\":{JSON_EXPORT_VERSION},\"exported_at\":\"{}\",\"lists\":[",
+                    exported_at.to_rfc3339_opts(SecondsFormat::Secs, true)
+                );
+                if let Some(list) = list {
+                    head.push_str(&format!("{{\"id\":\"{}\",\"name\":{},\"tasks\":[", list.id, json_string(&list.name)));
+                }
+                head
+            },
+            (ExportFormat::Csv, _) => CSV_HEADER.to_string(),
+            (ExportFormat::Markdown, Some(list)) => format!("# {}\n\n", single_line(&list.name)),
+            _ => String::new(),
+        }
+    }
+
+    /// Index is the position of the task in the whole export
+    pub fn task(&self, index: usize, task: &FullTaskInfo) -> String {
+        let list = match &self.list {
+            Some(list) => list,
+            None => return String::new(),
+        };
+
+        match self.format {
+            ExportFormat::Json => {
+                let exported = ExportedTask {
+                    id: task.id,
+                    order: task.order,
+                    description: &task.description,
+                    completed_at: task.completed_at,
+                    due_at: task.due_at,
+                    priority: task.priority,
+                    rrule: task.rrule.as_deref(),
+                };
+                let separator = if index > 0 { "," } else { "" };
+                format!("{separator}{}", serde_json::to_string(&exported).unwrap_or_default())
+            },
+            ExportFormat::Csv => {
+                let fields = [
+                    list.id.to_string(),
+                    list.name.clone(),
+                    task.id.to_string(),
+                    task.order.to_string(),
+                    task.description.clone(),
+                    task.completed_at.map(csv_time).unwrap_or_default(),
+                    task.due_at.map(csv_time).unwrap_or_default(),
+                    task.priority.map(|priority| priority.to_string()).unwrap_or_default(),
+                    task.rrule.clone().unwrap_or_default(),
+                ];
+                let row: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+                format!("{}\r\n", row.join(","))
+            },
+            ExportFormat::Markdown => {
+                // lines after the first are indented, so they stay in the item
+                let mut lines = task.description.lines();
+                let check = if task.completed_at.is_some() { 'x' } else { ' ' };
+                let mut item = format!("- [{check}] {}\n", lines.next().unwrap_or_default());
+                for line in lines {
+                    item.push_str(&format!("  {line}\n"));
+                }
+                item
+            },
+            ExportFormat::TodoTxt => {
+                let project: Vec<&str> = list.name.split_whitespace().collect();
+                let mut line = String::new();
+
+                // completed task has no priority at the start, it is kept as `pri:` tag
+                let priority = task.priority.map(todo_txt_priority);
+                match (task.completed_at, priority) {
+                    (Some(completed_at), _) => line.push_str(&format!("x {} ", completed_at.format("%Y-%m-%d"))),
+                    (None, Some(priority)) => line.push_str(&format!("({priority}) ")),
+                    (None, None) => (),
+                }
+
+                line.push_str(&format!("{} +{}", todo_txt_description(&task.description), project.join("_")));
+
+                if let Some(due_at) = task.due_at {
+                    line.push_str(&format!(" due:{}", due_at.format("%Y-%m-%d")));
+                }
+                if let (Some(_), Some(priority)) = (task.completed_at, priority) {
+                    line.push_str(&format!(" pri:{priority}"));
+                }
+
+                line.push('\n');
+                line
+            },
+        }
+    }
+
+    pub fn tail(&self) -> String {
+        match (self.format, &self.list) {
+            (ExportFormat::Json, Some(_)) => "]}]}".to_string(),
+            (ExportFormat::Json, None) => "]}".to_string(),
+            _ => String::new(),
+        }
+    }
+}
+
+/// Field is quoted if it has separator, quote or line break (RFC 4180)
+fn csv_field(value: &str) -> String {
+    match value.contains([',', '"', '\r', '\n']) {
+        true => format!("\"{}\"", value.replace('"', "\"\"")),
+        false => value.to_string(),
+    }
+}
+
+fn csv_time(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn json_string(value: &str) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
+
+/// Formats without multi-line values get lines joined by space
+fn single_line(value: &str) -> String {
+    value.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Priority 1 is `A`, 9 is `I`
+fn todo_txt_priority(priority: i32) -> char {
+    (b'A' + priority.clamp(1, 9) as u8 - 1) as char
+}
+
+/// Tokens, which todo.txt reads as completion, priority, date, project, context or tag, are
+/// prefixed by backslash, so they stay in the text. Import removes the backslash
+fn todo_txt_description(value: &str) -> String {
+    let tokens: Vec<String> = value.split_whitespace()
+        .enumerate()
+        .map(|(index, token)| match todo_txt_needs_escape(token, index == 0) {
+            true => format!("\\{token}"),
+            false => token.to_string(),
+        })
+        .collect();
+    tokens.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use uuid::Uuid;
+
+    use crate::{
+        models::{
+            ImportFormat,
+            ImportMapping
+        },
+        utils::import::parse_items
+    };
+
+    use super::*;
+
+    fn export_tasks(format: ExportFormat, descriptions: &[&str]) -> String {
+        let tasks: Vec<FullTaskInfo> = descriptions.iter()
+            .enumerate()
+            .map(|(index, description)| FullTaskInfo {
+                id: Uuid::nil(),
+                todo_list_id: Uuid::nil(),
+                description: description.to_string(),
+                order: index as i32 + 1,
+                ..Default::default()
+            })
+            .collect();
+        export_full_tasks(format, &tasks)
+    }
+
+    fn export_full_tasks(format: ExportFormat, tasks: &[FullTaskInfo]) -> String {
+        let list = FullTodoListInfo { id: Uuid::nil(), user_id: Uuid::new_v4(), name: "Home work".to_string() };
+        let exporter = Exporter::new(format, Some(list));
+
+        let mut export = exporter.head(Utc.timestamp_opt(1792432800, 0).unwrap());
+        for (index, task) in tasks.iter().enumerate() {
+            export.push_str(&exporter.task(index, task));
+        }
+        export.push_str(&exporter.tail());
+        export
+    }
+
+    fn export(format: ExportFormat, with_list: bool) -> String {
+        match with_list {
+            true => export_tasks(format, &["milk, \"fresh\"", "plan trip\nbook hotel"]),
+            false => {
+                let exporter = Exporter::new(format, None);
+                exporter.head(Utc.timestamp_opt(1792432800, 0).unwrap()) + &exporter.tail()
+            },
+        }
+    }
+
+    /// Completed task with priority and open task with due date and recurrence
+    fn export_dated(format: ExportFormat) -> String {
+        let time = |day: u32| Utc.with_ymd_and_hms(2026, 10, day, 18, 30, 0).unwrap();
+        let task = |order: i32, description: &str| FullTaskInfo { id: Uuid::nil(), todo_list_id: Uuid::nil(), description: description.to_string(), order, ..Default::default() };
+
+        let tasks = [
+            FullTaskInfo { completed_at: Some(time(19)), priority: Some(1), ..task(1, "pay bills") },
+            FullTaskInfo { due_at: Some(time(20)), priority: Some(3), rrule: Some("FREQ=WEEKLY".to_string()), ..task(2, "call mom") },
+        ];
+        export_full_tasks(format, &tasks)
+    }
+
+    #[test]
+    fn json_export() {
+        let json: serde_json::Value = serde_json::from_str(&export(ExportFormat::Json, true)).unwrap();
+
+        assert_eq!(json["version"], 1);
+        assert_eq!(json["exported_at"], "2026-10-19T18:00:00Z");
+        assert_eq!(json["lists"][0]["name"], "Home work");
+        assert_eq!(json["lists"][0]["tasks"][1]["description"], "plan trip\nbook hotel");
+        assert_eq!(json["lists"][0]["tasks"][1]["order"], 2);
+        assert_eq!(json["lists"][0]["tasks"][1]["due_at"], serde_json::Value::Null);
+
+        let empty: serde_json::Value = serde_json::from_str(&export(ExportFormat::Json, false)).unwrap();
+        assert_eq!(empty["lists"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn json_export_of_details() {
+        let json: serde_json::Value = serde_json::from_str(&export_dated(ExportFormat::Json)).unwrap();
+        let tasks = &json["lists"][0]["tasks"];
+
+        assert_eq!(tasks[0]["completed_at"], "2026-10-19T18:30:00Z");
+        assert_eq!(tasks[0]["priority"], 1);
+        assert_eq!(tasks[1]["completed_at"], serde_json::Value::Null);
+        assert_eq!(tasks[1]["due_at"], "2026-10-20T18:30:00Z");
+        assert_eq!(tasks[1]["rrule"], "FREQ=WEEKLY");
+    }
+
+    #[test]
+    fn csv_export() {
+        let nil = Uuid::nil();
+
+        assert_eq!(export(ExportFormat::Csv, true), format!(
+            "{CSV_HEADER}{nil},Home work,{nil},1,\"milk, \"\"fresh\"\"\",,,,\r\n{nil},Home work,{nil},2,\"plan trip\nbook hotel\",,,,\r\n"
+        ));
+        assert_eq!(export(ExportFormat::Csv, false), CSV_HEADER);
+    }
+
+    #[test]
+    fn csv_export_of_details() {
+        let nil = Uuid::nil();
+
+        assert_eq!(export_dated(ExportFormat::Csv), format!(
+            "{CSV_HEADER}{nil},Home work,{nil},1,pay bills,2026-10-19T18:30:00Z,,1,\r\n{nil},Home work,{nil},2,call mom,,2026-10-20T18:30:00Z,3,FREQ=WEEKLY\r\n"
+        ));
+    }
+
+    #[test]
+    fn markdown_export() {
+        assert_eq!(export(ExportFormat::Markdown, true), "# Home work\n\n- [ ] milk, \"fresh\"\n- [ ] plan trip\n  book hotel\n");
+        assert_eq!(export(ExportFormat::Markdown, false), "");
+    }
+
+    #[test]
+    fn markdown_export_of_completed() {
+        assert_eq!(export_dated(ExportFormat::Markdown), "# Home work\n\n- [x] pay bills\n- [ ] call mom\n");
+    }
+
+    #[test]
+    fn todo_txt_export() {
+        assert_eq!(export(ExportFormat::TodoTxt, true), "milk, \"fresh\" +Home_work\nplan trip book hotel +Home_work\n");
+    }
+
+    #[test]
+    fn todo_txt_export_of_details() {
+        assert_eq!(export_dated(ExportFormat::TodoTxt), "x 2026-10-19 pay bills +Home_work pri:A\n(C) call mom +Home_work due:2026-10-20\n");
+    }
+
+    #[test]
+    fn todo_txt_metadata_is_escaped() {
+        assert_eq!(
+            export_tasks(ExportFormat::TodoTxt, &["x ray", "(A) grade", "2026-10-19 report", "call @mom +1 re: sale at 10:30", "\\+not escape"]),
+            "\\x ray +Home_work\n\\(A) grade +Home_work\n\\2026-10-19 report +Home_work\n\
+             call \\@mom \\+1 re: sale at \\10:30 +Home_work\n\\\\+not escape +Home_work\n"
+        );
+    }
+
+    #[test]
+    fn todo_txt_round_trip() {
+        let descriptions = [
+            "x ray",
+            "(B) grade papers",
+            "2026-10-19 report due:friday",
+            "buy milk +groceries @store",
+            "look at http://example.com",
+            "\\\\server\\share \\+1",
+            "plain task",
+        ];
+        let export = export_tasks(ExportFormat::TodoTxt, &descriptions);
+
+        let imported = parse_items(ImportFormat::TodoTxt, &export, &ImportMapping::default(), Utc::now()).unwrap();
+        let imported: Vec<&str> = imported.iter().map(|item| item.description.as_str()).collect();
+        assert_eq!(imported, descriptions);
+    }
+
+    #[test]
+    fn todo_txt_details_round_trip() {
+        let imported = parse_items(ImportFormat::TodoTxt, &export_dated(ExportFormat::TodoTxt), &ImportMapping::default(), Utc::now()).unwrap();
+        let day = |day: u32| Some(Utc.with_ymd_and_hms(2026, 10, day, 0, 0, 0).unwrap());
+
+        assert_eq!((imported[0].description.as_str(), imported[0].details.completed_at, imported[0].details.priority), ("pay bills", day(19), Some(1)));
+        assert_eq!((imported[1].description.as_str(), imported[1].details.due_at, imported[1].details.priority), ("call mom", day(20), Some(3)));
+    }
+}
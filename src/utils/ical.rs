@@ -0,0 +1,518 @@
+//! iCalendar (RFC 5545) writer for the calendar feed of tasks
+
+use chrono::{
+    DateTime,
+    Duration,
+    NaiveDate,
+    NaiveDateTime,
+    TimeZone,
+    Utc
+};
+use chrono_tz::Tz;
+use ical::{
+    parser::Component,
+    property::Property
+};
+use validator::Validate;
+
+use crate::models::{
+    FullTaskInfo,
+    FullTodoListInfo,
+    TaskDetails
+};
+
+pub const CONTENT_TYPE: &str = "text/calendar; charset=utf-8";
+
+const PRODID: &str = "-//todo-list-rs//Tasks//EN";
+/// Lines longer than 75 octets are folded (RFC 5545 3.1)
+const MAX_LINE_LEN: usize = 75;
+/// Hint for clients how often the feed is refreshed (RFC 7986 5.7)
+const REFRESH_INTERVAL: &str = "PT1H";
+
+/// Content lines of iCalendar object
+#[derive(Default)]
+struct IcsWriter {
+    out: String,
+}
+
+impl IcsWriter {
+    fn begin(&mut self, component: &str) -> &mut Self {
+        self.line(&format!("BEGIN:{component}"))
+    }
+
+    fn end(&mut self, component: &str) -> &mut Self {
+        self.line(&format!("END:{component}"))
+    }
+
+    /// Property with value, which is written as is
+    fn property(&mut self, name: &str, value: &str) -> &mut Self {
+        self.line(&format!("{name}:{value}"))
+    }
+
+    fn text(&mut self, name: &str, value: &str) -> &mut Self {
+        self.property(name, &escape_text(value))
+    }
+
+    fn date_time(&mut self, name: &str, value: DateTime<Utc>) -> &mut Self {
+        self.property(name, &value.format("%Y%m%dT%H%M%SZ").to_string())
+    }
+
+    fn todo(&mut self, task: &FullTaskInfo, stamp: DateTime<Utc>) -> &mut Self {
+        // summary is one line, multi-line description is kept whole in DESCRIPTION
+        let summary = task.description.lines().next().unwrap_or_default();
+
+        self.begin("VTODO")
+            .property("UID", &task.id.to_string())
+            .date_time("DTSTAMP", stamp)
+            .text("SUMMARY", summary);
+
+        if summary.len() < task.description.len() {
+            self.text("DESCRIPTION", &task.description);
+        }
+
+        if let Some(due_at) = task.due_at {
+            // recurrence set starts at DTSTART, the first occurrence is due at the due time
+            if let Some(rrule) = &task.rrule {
+                self.date_time("DTSTART", due_at)
+                    .property("RRULE", rrule);
+            }
+
+            self.date_time("DUE", due_at);
+        }
+
+        match task.completed_at {
+            Some(completed_at) => self.property("STATUS", "COMPLETED").date_time("COMPLETED", completed_at),
+            None => self.property("STATUS", "NEEDS-ACTION"),
+        };
+
+        if let Some(priority) = task.priority {
+            self.property("PRIORITY", &priority.to_string());
+        }
+
+        // position of the task in the list, used by Apple Reminders for sorting
+        self.property("X-APPLE-SORT-ORDER", &task.order.to_string())
+            .end("VTODO")
+    }
+
+    fn line(&mut self, line: &str) -> &mut Self {
+        self.out.push_str(&fold_line(line));
+        self.out.push_str("\r\n");
+        self
+    }
+}
+
+/// Escapes TEXT value (RFC 5545 3.3.11)
+pub fn escape_text(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+
+    for c in value.replace("\r\n", "\n").chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            // other control characters are not allowed in TEXT
+            c if c.is_control() && c != '\t' => (),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// Reverts escaping of TEXT value, parser returns values as they are written
+pub fn unescape_text(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') | Some('N') => unescaped.push('\n'),
+            Some(escaped) => unescaped.push(escaped),
+            None => (),
+        }
+    }
+
+    unescaped
+}
+
+/// Folds line to parts of at most 75 octets, continuation lines start with space.
+/// Line is split on character boundary, so multi-byte characters are not broken.
+/// Folded part doesn't end with whitespace, some clients trim it before unfolding
+pub fn fold_line(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + line.len() / MAX_LINE_LEN * 3);
+    let mut line_start = 0;
+
+    for c in line.chars() {
+        if folded.len() - line_start + c.len_utf8() > MAX_LINE_LEN {
+            let part = &folded[line_start..];
+            let content_len = part.trim_end_matches([' ', '\t']).len();
+            // whitespace moves to continuation line, if it fits there and part isn't whitespace only
+            let split = if content_len > 1 && part.len() - content_len + c.len_utf8() < MAX_LINE_LEN {
+                line_start + content_len
+            } else {
+                folded.len()
+            };
+
+            let whitespace = folded.split_off(split);
+            folded.push_str("\r\n ");
+            line_start = folded.len() - 1;
+            folded.push_str(&whitespace);
+        }
+
+        folded.push(c);
+    }
+
+    folded
+}
+
+/// Calendar with VTODO for every task of the list. Calendar is empty, if user has no list
+pub fn tasks_calendar(list: Option<&FullTodoListInfo>, tasks: &[FullTaskInfo], now: DateTime<Utc>) -> String {
+    let mut ics = IcsWriter::default();
+
+    ics.begin("VCALENDAR")
+        .property("VERSION", "2.0")
+        .property("PRODID", PRODID)
+        .property("CALSCALE", "GREGORIAN")
+        .property("METHOD", "PUBLISH");
+
+    if let Some(list) = list {
+        ics.text("NAME", &list.name)
+            .text("X-WR-CALNAME", &list.name);
+    }
+
+    ics.property("REFRESH-INTERVAL;VALUE=DURATION", REFRESH_INTERVAL)
+        .property("X-PUBLISHED-TTL", REFRESH_INTERVAL);
+
+    for task in tasks {
+        ics.todo(task, now);
+    }
+
+    ics.end("VCALENDAR");
+
+    ics.out
+}
+
+/// Calendar object resource of CalDAV collection with the only VTODO of the task.
+/// Resource must be the same between requests for strong ETag, so DTSTAMP is fixed:
+/// tasks don't keep time of change
+pub fn task_calendar(task: &FullTaskInfo) -> String {
+    let mut ics = IcsWriter::default();
+
+    // CalDAV resources must not have METHOD (RFC 4791 4.1)
+    ics.begin("VCALENDAR")
+        .property("VERSION", "2.0")
+        .property("PRODID", PRODID)
+        .todo(task, DateTime::<Utc>::default())
+        .end("VCALENDAR");
+
+    ics.out
+}
+
+/// Task of VTODO, sent by CalDAV client
+#[derive(Debug, PartialEq)]
+pub struct Todo {
+    pub uid: Option<String>,
+    pub description: String,
+    pub details: TaskDetails,
+}
+
+/// Calendar object which can't be stored as task
+#[derive(Debug, PartialEq)]
+pub enum TodoError {
+    /// Not a valid calendar object, `400 Bad Request`
+    Invalid(String),
+    /// Valid object with content tasks don't have, it would be lost. `precondition` of CalDAV PUT
+    /// (RFC 4791 5.3.2.1) which isn't met
+    Unsupported { precondition: &'static str, detail: String },
+}
+
+pub const SUPPORTED_COMPONENT: &str = "supported-calendar-component";
+pub const VALID_RESOURCE: &str = "valid-calendar-object-resource";
+
+/// Properties, which are regenerated by the server or don't change the task, they are dropped.
+/// `X-` properties of clients are dropped too
+const IGNORED_PROPERTIES: [&str; 7] = ["UID", "DTSTAMP", "CREATED", "LAST-MODIFIED", "SEQUENCE", "PERCENT-COMPLETE", "CLASS"];
+
+/// Parses calendar object with one VTODO. Summary and description are joined to the text of the task,
+/// status, due time, priority and recurrence are its details. Calendar object with other content,
+/// e.g. alarms or categories, is rejected, so the client doesn't lose it silently.
+/// Completed todo without completion time is completed at `now`
+pub fn parse_todo(body: &str, now: DateTime<Utc>) -> Result<Todo, TodoError> {
+    let unsupported = |precondition: &'static str, detail: String| TodoError::Unsupported { precondition, detail };
+
+    let mut parser = ical::IcalParser::new(body.as_bytes());
+    let calendar = parser.next()
+        .ok_or_else(|| TodoError::Invalid("Calendar object not found".to_string()))?
+        .map_err(|e| TodoError::Invalid(format!("Invalid calendar object: {e}")))?;
+
+    // time zones of the calendar are referenced by TZID and are resolved by name
+    let other_components = !calendar.events.is_empty() || !calendar.journals.is_empty() || !calendar.free_busys.is_empty() || !calendar.alarms.is_empty();
+    if parser.next().is_some() || calendar.todos.len() != 1 || other_components {
+        return Err(unsupported(SUPPORTED_COMPONENT, "Calendar object must have exactly one VTODO".to_string()));
+    }
+
+    let todo = &calendar.todos[0];
+    if !todo.alarms.is_empty() {
+        return Err(unsupported(SUPPORTED_COMPONENT, "Alarms of tasks are not supported".to_string()));
+    }
+
+    if let Some(property) = todo.properties.iter().find(|property| !is_supported(&property.name)) {
+        return Err(unsupported(VALID_RESOURCE, format!("Property {} is not supported", property.name.to_uppercase())));
+    }
+
+    let property = |name: &str| todo.get_property(name)
+        .and_then(|property| property.value.as_deref())
+        .map(unescape_text)
+        .unwrap_or_default();
+
+    let summary = property("SUMMARY");
+    let details = property("DESCRIPTION");
+
+    // description of the task written by `todo` starts with its summary
+    let description = if details.trim().is_empty() || summary.trim() == details.trim() {
+        summary
+    } else if summary.trim().is_empty() || details.starts_with(&summary) {
+        details
+    } else {
+        format!("{summary}\n{details}")
+    };
+
+    let date_time = |name: &str| todo.get_property(name).map(parse_date_time).transpose();
+    let due_at = date_time("DUE")?;
+    let completed_at = date_time("COMPLETED")?;
+
+    let completed_at = match property("STATUS").to_uppercase().as_str() {
+        "" => completed_at,
+        "NEEDS-ACTION" | "IN-PROCESS" => None,
+        "COMPLETED" => Some(completed_at.unwrap_or(now)),
+        status => return Err(unsupported(VALID_RESOURCE, format!("Status {status} is not supported"))),
+    };
+
+    // 0 is undefined priority
+    let priority = match property("PRIORITY").trim() {
+        "" | "0" => None,
+        priority => Some(priority.parse::<i32>().map_err(|_| TodoError::Invalid(format!("Invalid priority \"{priority}\"")))?),
+    };
+
+    // recurrence of the task starts at its due time, other start is not kept
+    if date_time("DTSTART")?.is_some_and(|start| Some(start) != due_at) {
+        return Err(unsupported(VALID_RESOURCE, "Start time of the task must be equal to its due time".to_string()));
+    }
+
+    let rrule = Some(property("RRULE")).filter(|rrule| !rrule.is_empty());
+    let details = TaskDetails { due_at, completed_at, priority, rrule };
+    details.validate().map_err(|e| unsupported(VALID_RESOURCE, e.to_string()))?;
+
+    Ok(Todo { uid: todo.get_property("UID").and_then(|uid| uid.value.clone()), description, details })
+}
+
+fn is_supported(name: &str) -> bool {
+    let name = name.to_uppercase();
+
+    name.starts_with("X-")
+        || IGNORED_PROPERTIES.contains(&name.as_str())
+        || ["SUMMARY", "DESCRIPTION", "STATUS", "COMPLETED", "DUE", "DTSTART", "PRIORITY", "RRULE"].contains(&name.as_str())
+}
+
+/// DATE-TIME in UTC, in time zone of TZID or floating time, which is taken as UTC.
+/// DATE is midnight of the day in UTC
+fn parse_date_time(property: &Property) -> Result<DateTime<Utc>, TodoError> {
+    let value = property.value.as_deref().unwrap_or_default().trim();
+    let param = |name: &str| property.params.iter().flatten()
+        .find(|(param, _)| param.eq_ignore_ascii_case(name))
+        .and_then(|(_, values)| values.first());
+
+    let invalid = || TodoError::Invalid(format!("Invalid time \"{value}\" of {}", property.name.to_uppercase()));
+
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y%m%d") {
+        return Ok(Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).ok_or_else(invalid)?));
+    }
+
+    if let Some(utc) = value.strip_suffix('Z') {
+        let time = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").map_err(|_| invalid())?;
+        return Ok(Utc.from_utc_datetime(&time));
+    }
+
+    let time = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").map_err(|_| invalid())?;
+
+    let time_zone = match param("TZID") {
+        Some(tzid) => tzid.trim_start_matches('/').parse::<Tz>()
+            .map_err(|_| TodoError::Unsupported { precondition: VALID_RESOURCE, detail: format!("Time zone \"{tzid}\" is not supported") })?,
+        None => return Ok(Utc.from_utc_datetime(&time)),
+    };
+
+    // time skipped by transition to summer time has offset before the transition
+    time_zone.from_local_datetime(&time).earliest()
+        .or_else(|| time_zone.from_local_datetime(&(time - Duration::hours(1))).earliest().map(|time| time + Duration::hours(1)))
+        .map(|time| time.with_timezone(&Utc))
+        .ok_or_else(invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use uuid::Uuid;
+
+    use super::*;
+
+    #[test]
+    fn text_escaping() {
+        assert_eq!(escape_text("milk, bread; eggs\\cheese"), "milk\\, bread\\; eggs\\\\cheese");
+        assert_eq!(escape_text("first\r\nsecond\nthird\u{7}"), "first\\nsecond\\nthird");
+    }
+
+    #[test]
+    fn long_lines_are_folded() {
+        let line = format!("SUMMARY:{}", "ж".repeat(60));
+
+        let folded = fold_line(&line);
+
+        assert!(folded.split("\r\n").all(|part| part.len() <= MAX_LINE_LEN));
+        assert_eq!(folded.replace("\r\n ", ""), line);
+        assert_eq!(fold_line("SUMMARY:short"), "SUMMARY:short");
+    }
+
+    #[test]
+    fn folded_parts_do_not_end_with_whitespace() {
+        for line in [format!("SUMMARY:{}", "word ".repeat(40)), format!("SUMMARY:a{}b", " ".repeat(200))] {
+            let folded = fold_line(&line);
+
+            assert!(folded.split("\r\n").all(|part| part.len() <= MAX_LINE_LEN), "{folded}");
+            assert_eq!(folded.replace("\r\n ", ""), line);
+        }
+
+        let folded = fold_line(&format!("SUMMARY:{}end", "word ".repeat(40)));
+        assert!(folded.split("\r\n ").all(|part| !part.ends_with(' ')), "{folded}");
+    }
+
+    #[test]
+    fn calendar_of_tasks() {
+        let list = FullTodoListInfo { id: Uuid::new_v4(), user_id: Uuid::new_v4(), name: "Home".to_string() };
+        let task = FullTaskInfo { id: Uuid::new_v4(), todo_list_id: list.id, description: "Buy milk\nand bread".to_string(), order: 1, ..Default::default() };
+        let now = Utc.timestamp_opt(1792432800, 0).unwrap();
+
+        let ics = tasks_calendar(Some(&list), &[task], now);
+
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(ics.ends_with("END:VTODO\r\nEND:VCALENDAR\r\n"));
+        assert!(ics.contains("\r\nX-WR-CALNAME:Home\r\n"));
+        assert!(ics.contains("\r\nDTSTAMP:20261019T180000Z\r\n"));
+        assert!(ics.contains("\r\nSUMMARY:Buy milk\r\nDESCRIPTION:Buy milk\\nand bread\r\n"));
+    }
+
+    #[test]
+    fn todo_has_task_details() {
+        let due_at = Utc.timestamp_opt(1792486800, 0).unwrap();
+        let task = FullTaskInfo {
+            description: "Water plants".to_string(),
+            order: 1,
+            due_at: Some(due_at),
+            completed_at: Some(due_at),
+            priority: Some(5),
+            rrule: Some("FREQ=WEEKLY;BYDAY=TU".to_string()),
+            ..Default::default()
+        };
+
+        let ics = task_calendar(&task);
+
+        assert!(ics.contains("\r\nDTSTART:20261020T090000Z\r\nRRULE:FREQ=WEEKLY;BYDAY=TU\r\nDUE:20261020T090000Z\r\n"), "{ics}");
+        assert!(ics.contains("\r\nSTATUS:COMPLETED\r\nCOMPLETED:20261020T090000Z\r\nPRIORITY:5\r\n"), "{ics}");
+        assert!(!ics.contains("NEEDS-ACTION"));
+    }
+
+    #[test]
+    fn task_resource_round_trip() {
+        let due_at = Utc.timestamp_opt(1792486800, 0).unwrap();
+        let task = FullTaskInfo {
+            id: Uuid::new_v4(),
+            todo_list_id: Uuid::new_v4(),
+            description: "plan trip, book hotel\nand car; cheap".to_string(),
+            order: 3,
+            due_at: Some(due_at),
+            completed_at: Some(due_at),
+            priority: Some(2),
+            rrule: Some("FREQ=YEARLY".to_string())
+        };
+
+        let ics = task_calendar(&task);
+
+        assert_eq!(ics, task_calendar(&task));
+        assert!(!ics.contains("METHOD"));
+        assert_eq!(parse_todo(&ics, Utc::now()).unwrap(), Todo { uid: Some(task.id.to_string()), description: task.description.clone(), details: task.details() });
+    }
+
+    fn todo(properties: &str) -> Result<Todo, TodoError> {
+        parse_todo(&format!("BEGIN:VCALENDAR\r\nVERSION:2.0\r\nBEGIN:VTODO\r\nUID:1\r\n{properties}END:VTODO\r\nEND:VCALENDAR\r\n"), Utc.timestamp_opt(1792432800, 0).unwrap())
+    }
+
+    fn unsupported(error: TodoError) -> &'static str {
+        match error {
+            TodoError::Unsupported { precondition, .. } => precondition,
+            error => panic!("unexpected error: {error:?}"),
+        }
+    }
+
+    #[test]
+    fn todo_summary_and_description_are_joined() {
+        let description = |properties: &str| todo(properties).unwrap().description;
+
+        assert_eq!(description("SUMMARY:Buy milk\r\nSTATUS:NEEDS-ACTION\r\n"), "Buy milk");
+        assert_eq!(description("SUMMARY:Buy milk\r\nDESCRIPTION:2 bottles\\, fresh\r\n"), "Buy milk\n2 bottles, fresh");
+        assert_eq!(description("SUMMARY:Buy milk\r\nDESCRIPTION:Buy milk\r\n"), "Buy milk");
+        assert_eq!(description("DESCRIPTION:only notes\r\n"), "only notes");
+    }
+
+    #[test]
+    fn todo_details() {
+        let details = |properties: &str| todo(properties).unwrap().details;
+        let time = |timestamp: i64| Some(Utc.timestamp_opt(timestamp, 0).unwrap());
+
+        assert_eq!(details("SUMMARY:Buy milk\r\nDTSTAMP:20261019T180000Z\r\nX-APPLE-SORT-ORDER:1\r\n"), TaskDetails::default());
+
+        let completed = details("STATUS:COMPLETED\r\nCOMPLETED:20261019T170000Z\r\nPERCENT-COMPLETE:100\r\nPRIORITY:1\r\n");
+        assert_eq!(completed, TaskDetails { completed_at: time(1792429200), priority: Some(1), ..Default::default() });
+
+        // completion time is set by the server, if client doesn't send it
+        assert_eq!(details("STATUS:COMPLETED\r\n").completed_at, time(1792432800));
+        assert_eq!(details("STATUS:NEEDS-ACTION\r\nCOMPLETED:20261019T170000Z\r\nPRIORITY:0\r\n"), TaskDetails::default());
+
+        let recurring = details("DTSTART;TZID=Europe/Moscow:20261020T120000\r\nDUE;TZID=Europe/Moscow:20261020T120000\r\nRRULE:FREQ=WEEKLY;BYDAY=TU\r\n");
+        assert_eq!(recurring, TaskDetails { due_at: time(1792486800), rrule: Some("FREQ=WEEKLY;BYDAY=TU".to_string()), ..Default::default() });
+
+        assert_eq!(details("DUE;VALUE=DATE:20261020\r\n").due_at, time(1792454400));
+        assert_eq!(details("DUE:20261020T090000\r\n").due_at, time(1792486800));
+    }
+
+    #[test]
+    fn unsupported_content_is_rejected() {
+        let alarm = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nBEGIN:VTODO\r\nUID:1\r\nSUMMARY:call\r\nBEGIN:VALARM\r\nACTION:DISPLAY\r\nTRIGGER:-PT15M\r\nEND:VALARM\r\nEND:VTODO\r\nEND:VCALENDAR\r\n";
+        assert_eq!(unsupported(parse_todo(alarm, Utc::now()).unwrap_err()), SUPPORTED_COMPONENT);
+
+        for properties in [
+            "SUMMARY:call\r\nCATEGORIES:work\r\n",
+            "SUMMARY:call\r\nSTATUS:CANCELLED\r\n",
+            "SUMMARY:call\r\nDTSTART:20261019T090000Z\r\nDUE:20261020T090000Z\r\n",
+            "SUMMARY:call\r\nRRULE:FREQ=DAILY\r\n",
+            "SUMMARY:call\r\nPRIORITY:10\r\n",
+            "SUMMARY:call\r\nDUE;TZID=Mars/Olympus:20261020T090000\r\n",
+        ] {
+            assert_eq!(unsupported(todo(properties).unwrap_err()), VALID_RESOURCE, "{properties}");
+        }
+
+        assert!(matches!(todo("SUMMARY:call\r\nDUE:tomorrow\r\n"), Err(TodoError::Invalid(_))));
+    }
+
+    #[test]
+    fn only_one_todo_is_accepted() {
+        let event = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nBEGIN:VEVENT\r\nUID:1\r\nSUMMARY:meeting\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+
+        assert_eq!(unsupported(parse_todo(event, Utc::now()).unwrap_err()), SUPPORTED_COMPONENT);
+        assert!(matches!(parse_todo("not a calendar", Utc::now()), Err(TodoError::Invalid(_))));
+    }
+}
@@ -0,0 +1,735 @@
+//! Import of tasks from files of other apps. File is parsed to items in the order, in which
+//! they are added to the end of the list, then items are checked against existing tasks
+
+use std::{
+    cmp::Ordering,
+    collections::HashSet
+};
+
+use chrono::{
+    DateTime,
+    NaiveDate,
+    NaiveDateTime,
+    Utc
+};
+use serde_json::Value;
+use validator::Validate;
+
+use crate::models::{
+    DuplicatePolicy,
+    FullTaskInfo,
+    ImportFormat,
+    ImportIssue,
+    ImportMapping,
+    ImportReport,
+    ImportedTask,
+    RequestValidationError,
+    ServiceError,
+    StatusCode,
+    TaskDetails,
+    UpdateTask
+};
+
+pub const MAX_IMPORT_ITEMS: usize = 5000;
+
+/// Task found in the file, `item` is its number in the file starting from 1
+#[derive(Debug, Default, PartialEq)]
+pub struct ParsedItem {
+    pub item: usize,
+    pub description: String,
+    pub details: TaskDetails,
+}
+
+/// Completed items without completion time in the file are completed at `now`
+pub fn parse_items(format: ImportFormat, data: &str, mapping: &ImportMapping, now: DateTime<Utc>) -> Result<Vec<ParsedItem>, ServiceError> {
+    let data = data.strip_prefix('\u{feff}').unwrap_or(data);
+
+    let mapped = mapping.description.is_some() || mapping.notes.is_some() || mapping.order.is_some()
+        || mapping.completed.is_some() || mapping.due.is_some() || mapping.priority.is_some();
+    match format {
+        ImportFormat::TodoTxt | ImportFormat::Markdown if mapped || mapping.list.is_some() => {
+            return Err(bad_request("Mapping is supported only for csv, todoist and trello formats".to_string()));
+        },
+        ImportFormat::Csv | ImportFormat::Todoist if mapping.list.is_some() => {
+            return Err(bad_request("Mapping of list is supported only for trello format".to_string()));
+        },
+        _ => (),
+    }
+
+    let items = match format {
+        ImportFormat::TodoTxt => parse_todo_txt(data, now),
+        ImportFormat::Markdown => parse_markdown(data, now),
+        ImportFormat::Csv => parse_csv(data, mapping, now)?,
+        ImportFormat::Todoist => parse_todoist(&parse_json(data)?, mapping, now)?,
+        ImportFormat::Trello => parse_trello(&parse_json(data)?, mapping, now)?,
+    };
+
+    if items.len() > MAX_IMPORT_ITEMS {
+        return Err(ServiceError { status_code: StatusCode::UnprocessableEntity, detail: Some(format!("File can have at most {MAX_IMPORT_ITEMS} tasks")) });
+    }
+
+    Ok(items)
+}
+
+/// Report of import of the items to the end of the list with `existing` tasks.
+/// Tasks of the report have no ids yet
+pub fn plan_import(format: ImportFormat, dry_run: bool, items: Vec<ParsedItem>, existing: &[FullTaskInfo], duplicates: DuplicatePolicy) -> ImportReport {
+    let mut report = ImportReport {
+        format,
+        dry_run,
+        total: items.len(),
+        created: 0,
+        duplicates: Vec::new(),
+        errors: Vec::new(),
+        tasks: Vec::new(),
+    };
+
+    let mut known: HashSet<String> = existing.iter().map(|task| duplicate_key(&task.description)).collect();
+
+    for item in items {
+        let task = UpdateTask { description: item.description };
+        let errors: Vec<String> = [task.validate(), item.details.validate()].into_iter()
+            .filter_map(Result::err)
+            .flat_map(|errors| RequestValidationError::from(errors).fields.into_values().flatten())
+            .collect();
+        if !errors.is_empty() {
+            report.errors.push(ImportIssue { item: item.item, detail: errors.join(", ") });
+            continue;
+        }
+
+        if !known.insert(duplicate_key(&task.description)) && duplicates == DuplicatePolicy::Skip {
+            report.duplicates.push(ImportIssue { item: item.item, detail: task.description });
+            continue;
+        }
+
+        let order = (existing.len() + report.tasks.len() + 1) as i32;
+        let TaskDetails { completed_at, due_at, priority, .. } = item.details;
+        report.tasks.push(ImportedTask { id: None, order, description: task.description, completed_at, due_at, priority });
+    }
+
+    report.created = report.tasks.len();
+    report
+}
+
+/// Tasks are the same, if they differ only by case and whitespace
+fn duplicate_key(description: &str) -> String {
+    description.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+/// Completion, priority and `due:` date are read, `pri:` tag is priority of completed task.
+/// Creation date and `+project` tags are dropped, user has one list. Contexts and other `key:value` tags are kept
+fn parse_todo_txt(data: &str, now: DateTime<Utc>) -> Vec<ParsedItem> {
+    data.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            let mut tokens: Vec<&str> = line.split_whitespace().collect();
+            let mut details = TaskDetails::default();
+
+            // completed task has no priority at the start
+            let completed = tokens.first() == Some(&"x");
+            details.priority = tokens.first()
+                .filter(|token| !completed && is_priority(token))
+                .and_then(|token| letter_priority(&token[1..2]));
+            if completed || details.priority.is_some() {
+                tokens.remove(0);
+            }
+
+            // completed task has completion date before creation date, completion without date is kept
+            if completed {
+                let completed_at = tokens.first().and_then(|token| parse_date(token));
+                if completed_at.is_some() {
+                    tokens.remove(0);
+                }
+                details.completed_at = completed_at.or(Some(now));
+            }
+            if tokens.first().is_some_and(|token| is_date(token)) {
+                tokens.remove(0);
+            }
+
+            let mut description = Vec::with_capacity(tokens.len());
+            for token in tokens {
+                let tag = token.split_once(':');
+
+                match tag {
+                    _ if token.len() > 1 && token.starts_with('+') => (),
+                    Some(("due", value)) if is_date(value) => details.due_at = parse_date(value),
+                    Some(("pri", value)) if completed && letter_priority(value).is_some() => details.priority = letter_priority(value),
+                    _ => description.push(match token.strip_prefix('\\') {
+                        Some(escaped) if todo_txt_needs_escape(escaped, description.is_empty()) => escaped,
+                        _ => token,
+                    }),
+                }
+            }
+
+            ParsedItem { item: index + 1, description: description.join(" "), details }
+        })
+        .collect()
+}
+
+/// Token of the text, which is read as metadata: completion, priority or date at the start,
+/// project, context or `key:value` tag anywhere. Token starting with backslash is escaped too,
+/// so the escape is not lost on import
+pub(crate) fn todo_txt_needs_escape(token: &str, first: bool) -> bool {
+    let tag = token.len() > 1 && token.starts_with(['+', '@'])
+        || token.split_once(':').is_some_and(|(key, value)| !key.is_empty() && !value.is_empty());
+
+    token.starts_with('\\') || tag || first && (token == "x" || is_priority(token) || is_date(token))
+}
+
+fn is_priority(token: &str) -> bool {
+    let bytes = token.as_bytes();
+    bytes.len() == 3 && bytes[0] == b'(' && bytes[1].is_ascii_uppercase() && bytes[2] == b')'
+}
+
+/// `A` is priority 1, letters after `I` are the lowest priority 9
+fn letter_priority(letter: &str) -> Option<i32> {
+    match letter.as_bytes() {
+        [letter] if letter.is_ascii_uppercase() => Some(((letter - b'A') as i32 + 1).min(9)),
+        _ => None,
+    }
+}
+
+fn is_date(token: &str) -> bool {
+    parse_date(token).is_some()
+}
+
+/// Date is the start of the day in UTC
+fn parse_date(value: &str) -> Option<DateTime<Utc>> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|time| time.and_utc())
+}
+
+/// RFC 3339 time, time without offset is in UTC, date is the start of the day
+fn parse_time(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value).map(|time| time.with_timezone(&Utc)).ok()
+        .or_else(|| NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S%.f").ok().map(|time| time.and_utc()))
+        .or_else(|| parse_date(value))
+}
+
+/// Checklist items with their indented continuation lines, other lines (headings, text) are skipped
+fn parse_markdown(data: &str, now: DateTime<Utc>) -> Vec<ParsedItem> {
+    let mut items: Vec<ParsedItem> = Vec::new();
+    let mut in_item = false;
+
+    for line in data.lines() {
+        if let Some((completed, text)) = checklist_item(line) {
+            let details = TaskDetails { completed_at: completed.then_some(now), ..Default::default() };
+            items.push(ParsedItem { item: items.len() + 1, description: text.trim().to_string(), details });
+            in_item = true;
+            continue;
+        }
+
+        match items.last_mut() {
+            Some(item) if in_item && (line.starts_with("  ") || line.starts_with('\t')) => {
+                item.description.push('\n');
+                item.description.push_str(line.trim());
+            },
+            _ => in_item = false,
+        }
+    }
+
+    for item in &mut items {
+        item.description = item.description.trim_end().to_string();
+    }
+
+    items
+}
+
+/// `- [ ] text`, `* [x] text` or `1. [ ] text`
+fn checklist_item(line: &str) -> Option<(bool, &str)> {
+    let line = line.trim_start();
+
+    let rest = match line.strip_prefix(['-', '*', '+']) {
+        Some(rest) => rest,
+        None => {
+            let digits = line.find(|c: char| !c.is_ascii_digit()).unwrap_or(line.len());
+            match digits {
+                0 => return None,
+                _ => line[digits..].strip_prefix(['.', ')'])?,
+            }
+        },
+    };
+
+    let rest = rest.strip_prefix(' ')?.trim_start();
+    let completed = match rest.get(..3)? {
+        "[ ]" => false,
+        "[x]" | "[X]" => true,
+        _ => return None,
+    };
+
+    let text = &rest[3..];
+    match text.is_empty() || text.starts_with([' ', '\t']) {
+        true => Some((completed, text)),
+        false => None,
+    }
+}
+
+/// Columns of details are optional, columns of export are read if the file has them
+fn parse_csv(data: &str, mapping: &ImportMapping, now: DateTime<Utc>) -> Result<Vec<ParsedItem>, ServiceError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(data.as_bytes());
+
+    let headers = reader.headers()
+        .map_err(|e| bad_request(format!("Invalid csv: {e}")))?
+        .clone();
+
+    let column = |name: &str| headers.iter()
+        .position(|header| header.trim().eq_ignore_ascii_case(name.trim()))
+        .ok_or_else(|| bad_request(format!("Column \"{name}\" not found, columns of the file: {}", headers.iter().collect::<Vec<_>>().join(", "))));
+
+    let description = column(mapping.description.as_deref().unwrap_or("description"))?;
+    let notes = mapping.notes.as_deref().map(column).transpose()?;
+    let order = mapping.order.as_deref().map(column).transpose()?;
+
+    let optional = |mapped: Option<&str>, default: &str| match mapped {
+        Some(name) => column(name).map(Some),
+        None => Ok(headers.iter().position(|header| header.trim().eq_ignore_ascii_case(default))),
+    };
+    let completed = optional(mapping.completed.as_deref(), "completed_at")?;
+    let due = optional(mapping.due.as_deref(), "due_at")?;
+    let priority = optional(mapping.priority.as_deref(), "priority")?;
+
+    let mut items = Vec::new();
+    for (index, record) in reader.records().enumerate() {
+        let record = record.map_err(|e| bad_request(format!("Invalid csv: {e}")))?;
+        let field = |column: usize| record.get(column).unwrap_or_default();
+
+        if record.iter().all(|field| field.trim().is_empty()) {
+            continue;
+        }
+
+        let order = match order {
+            Some(column) => order_key(field(column).trim(), index + 1)?,
+            None => None,
+        };
+        let description = with_notes(field(description), notes.map(field).unwrap_or_default());
+        let details = TaskDetails {
+            completed_at: completed.map(|column| completion(field(column), index + 1, now)).transpose()?.flatten(),
+            due_at: due.map(|column| due_time(field(column), index + 1)).transpose()?.flatten(),
+            priority: priority.map(|column| priority_number(field(column), index + 1)).transpose()?.flatten(),
+            rrule: None,
+        };
+
+        items.push((ParsedItem { item: index + 1, description, details }, order));
+    }
+
+    Ok(sorted(items))
+}
+
+fn parse_json(data: &str) -> Result<Value, ServiceError> {
+    serde_json::from_str(data).map_err(|e| bad_request(format!("Invalid json: {e}")))
+}
+
+/// Deleted items are skipped, `checked` or `is_completed` ones are completed at `completed_at`.
+/// Priority 4 (p1) of Todoist is 1, 3 is 5, 2 is 9, tasks with 1 have no priority
+fn parse_todoist(json: &Value, mapping: &ImportMapping, now: DateTime<Utc>) -> Result<Vec<ParsedItem>, ServiceError> {
+    let tasks = match json {
+        Value::Array(tasks) => tasks,
+        Value::Object(object) => object.get("items").or_else(|| object.get("tasks"))
+            .and_then(Value::as_array)
+            .ok_or_else(|| bad_request("Todoist file must be array of tasks or object with \"items\"".to_string()))?,
+        _ => return Err(bad_request("Todoist file must be array of tasks or object with \"items\"".to_string())),
+    };
+
+    let description = mapping.description.as_deref().unwrap_or("content");
+    let notes = mapping.notes.as_deref().unwrap_or("description");
+
+    let mut items = Vec::new();
+    for (index, task) in tasks.iter().enumerate() {
+        if is_true(&task["is_deleted"]) {
+            continue;
+        }
+
+        let order = match mapping.order.as_deref() {
+            Some(field) => json_order_key(&task[field], index + 1)?,
+            None => json_order_key(&task["child_order"], index + 1)?.or(json_order_key(&task["order"], index + 1)?),
+        };
+        let completed_at = match mapping.completed.as_deref() {
+            Some(field) => json_completion(&task[field], index + 1, now)?,
+            None => (is_true(&task["checked"]) || is_true(&task["is_completed"]))
+                .then(|| task["completed_at"].as_str().and_then(parse_time).unwrap_or(now)),
+        };
+        let details = TaskDetails {
+            completed_at,
+            due_at: json_due(&task[mapping.due.as_deref().unwrap_or("due")], index + 1)?,
+            priority: json_priority(&task[mapping.priority.as_deref().unwrap_or("priority")], index + 1)?.and_then(todoist_priority),
+            rrule: None,
+        };
+        let description = with_notes(&json_text(&task[description]), &json_text(&task[notes]));
+
+        items.push((ParsedItem { item: index + 1, description, details }, order));
+    }
+
+    Ok(sorted(items))
+}
+
+/// Cards are sorted by position of their list and then by own position. Archived cards,
+/// cards of archived lists and cards with completed due date are completed at import.
+/// Cards have no priority, it is read only from mapped field
+fn parse_trello(json: &Value, mapping: &ImportMapping, now: DateTime<Utc>) -> Result<Vec<ParsedItem>, ServiceError> {
+    let cards = json["cards"].as_array()
+        .ok_or_else(|| bad_request("Trello file must be board export with \"cards\"".to_string()))?;
+    let lists = json["lists"].as_array().map(Vec::as_slice).unwrap_or_default();
+
+    let list_of = |card: &Value| lists.iter().find(|list| list["id"] == card["idList"]);
+
+    if let Some(name) = mapping.list.as_deref() {
+        if !lists.iter().any(|list| list["name"].as_str() == Some(name)) {
+            return Err(bad_request(format!("Trello list \"{name}\" not found")));
+        }
+    }
+
+    let description = mapping.description.as_deref().unwrap_or("name");
+    let notes = mapping.notes.as_deref().unwrap_or("desc");
+
+    let mut items = Vec::new();
+    for (index, card) in cards.iter().enumerate() {
+        let list = list_of(card);
+
+        if let Some(name) = mapping.list.as_deref() {
+            if list.and_then(|list| list["name"].as_str()) != Some(name) {
+                continue;
+            }
+        }
+
+        let order = match mapping.order.as_deref() {
+            Some(field) => json_order_key(&card[field], index + 1)?,
+            None => json_order_key(&card["pos"], index + 1)?,
+        };
+        let list_order = list.and_then(|list| list["pos"].as_f64());
+
+        let completed_at = match mapping.completed.as_deref() {
+            Some(field) => json_completion(&card[field], index + 1, now)?,
+            None => (is_true(&card["closed"]) || is_true(&card["dueComplete"]) || list.is_some_and(|list| is_true(&list["closed"])))
+                .then_some(now),
+        };
+        let details = TaskDetails {
+            completed_at,
+            due_at: json_due(&card[mapping.due.as_deref().unwrap_or("due")], index + 1)?,
+            priority: mapping.priority.as_deref().map(|field| json_priority(&card[field], index + 1)).transpose()?.flatten(),
+            rrule: None,
+        };
+        let description = with_notes(&json_text(&card[description]), &json_text(&card[notes]));
+
+        items.push(((ParsedItem { item: index + 1, description, details }, order), list_order));
+    }
+
+    // mapped order replaces both positions
+    match mapping.order {
+        Some(_) => Ok(sorted(items.into_iter().map(|(item, _)| item).collect())),
+        None => {
+            items.sort_by(|((_, a), a_list), ((_, b), b_list)| compare_keys(*a_list, *b_list).then(compare_keys(*a, *b)));
+            Ok(items.into_iter().map(|((item, _), _)| item).collect())
+        },
+    }
+}
+
+fn with_notes(description: &str, notes: &str) -> String {
+    match (description.trim(), notes.trim()) {
+        (description, "") => description.to_string(),
+        (description, notes) => format!("{description}\n\n{notes}"),
+    }
+}
+
+fn json_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Null => String::new(),
+        value => value.to_string(),
+    }
+}
+
+fn is_true(value: &Value) -> bool {
+    value.as_bool() == Some(true) || value.as_i64() == Some(1)
+}
+
+/// Empty, `false`, `0` or `no` is open task, `true`, `1`, `yes` or `x` is completed at `now`, otherwise time of completion
+fn completion(value: &str, item: usize, now: DateTime<Utc>) -> Result<Option<DateTime<Utc>>, ServiceError> {
+    match value.trim().to_lowercase().as_str() {
+        "" | "false" | "0" | "no" => Ok(None),
+        "true" | "1" | "yes" | "x" => Ok(Some(now)),
+        _ => parse_time(value.trim()).map(Some).ok_or_else(|| bad_request(format!("Completion of item {item} is not a flag or time: {value}"))),
+    }
+}
+
+fn due_time(value: &str, item: usize) -> Result<Option<DateTime<Utc>>, ServiceError> {
+    match value.trim() {
+        "" => Ok(None),
+        value => parse_time(value).map(Some).ok_or_else(|| bad_request(format!("Due date of item {item} is not a date: {value}"))),
+    }
+}
+
+/// Range of priority is checked with other fields of the task
+fn priority_number(value: &str, item: usize) -> Result<Option<i32>, ServiceError> {
+    match value.trim() {
+        "" => Ok(None),
+        value => value.parse().map(Some).map_err(|_| bad_request(format!("Priority of item {item} is not a number: {value}"))),
+    }
+}
+
+fn json_completion(value: &Value, item: usize, now: DateTime<Utc>) -> Result<Option<DateTime<Utc>>, ServiceError> {
+    match value {
+        Value::String(text) => completion(text, item, now),
+        value => Ok(is_true(value).then_some(now)),
+    }
+}
+
+/// Due of Todoist is object with `datetime` or `date`
+fn json_due(value: &Value, item: usize) -> Result<Option<DateTime<Utc>>, ServiceError> {
+    match value {
+        Value::Null => Ok(None),
+        Value::String(text) => due_time(text, item),
+        Value::Object(due) => match due.get("datetime").filter(|datetime| !datetime.is_null()).or(due.get("date")) {
+            Some(due) => json_due(due, item),
+            None => Ok(None),
+        },
+        value => Err(bad_request(format!("Due date of item {item} is not a date: {value}"))),
+    }
+}
+
+fn json_priority(value: &Value, item: usize) -> Result<Option<i32>, ServiceError> {
+    match value {
+        Value::Null => Ok(None),
+        Value::Number(number) => number.as_i64()
+            .map(|number| Some(number.clamp(i32::MIN as i64, i32::MAX as i64) as i32))
+            .ok_or_else(|| bad_request(format!("Priority of item {item} is not a number: {number}"))),
+        Value::String(text) => priority_number(text, item),
+        value => Err(bad_request(format!("Priority of item {item} is not a number: {value}"))),
+    }
+}
+
+/// High, medium and low priorities of iCalendar are 1, 5 and 9
+fn todoist_priority(priority: i32) -> Option<i32> {
+    match priority {
+        4 => Some(1),
+        3 => Some(5),
+        2 => Some(9),
+        _ => None,
+    }
+}
+
+fn order_key(value: &str, item: usize) -> Result<Option<f64>, ServiceError> {
+    match value {
+        "" => Ok(None),
+        value => value.parse().map(Some).map_err(|_| bad_request(format!("Order of item {item} is not a number: {value}"))),
+    }
+}
+
+fn json_order_key(value: &Value, item: usize) -> Result<Option<f64>, ServiceError> {
+    match value {
+        Value::Null => Ok(None),
+        Value::Number(number) => Ok(number.as_f64()),
+        Value::String(text) => order_key(text.trim(), item),
+        value => Err(bad_request(format!("Order of item {item} is not a number: {value}"))),
+    }
+}
+
+/// Stable sort by order, items without order keep their place after ordered ones
+fn sorted(mut items: Vec<(ParsedItem, Option<f64>)>) -> Vec<ParsedItem> {
+    items.sort_by(|(_, a), (_, b)| compare_keys(*a, *b));
+    items.into_iter().map(|(item, _)| item).collect()
+}
+
+fn compare_keys(a: Option<f64>, b: Option<f64>) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+fn bad_request(detail: String) -> ServiceError {
+    ServiceError { status_code: StatusCode::BadRequest, detail: Some(detail) }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 10, 19, 18, 0, 0).unwrap()
+    }
+
+    fn date(day: u32) -> Option<DateTime<Utc>> {
+        Some(Utc.with_ymd_and_hms(2026, 10, day, 0, 0, 0).unwrap())
+    }
+
+    fn parse(format: ImportFormat, data: &str, mapping: &ImportMapping) -> Result<Vec<ParsedItem>, ServiceError> {
+        parse_items(format, data, mapping, now())
+    }
+
+    fn descriptions(items: &[ParsedItem]) -> Vec<(&str, bool)> {
+        items.iter().map(|item| (item.description.as_str(), item.details.completed_at.is_some())).collect()
+    }
+
+    #[test]
+    fn todo_txt_items() {
+        let data = "(A) 2026-10-01 call mom @phone +Family\n\nx 2026-10-02 2026-10-01 pay bills +Home due:2026-10-05\nC++ talk\n";
+        let items = parse(ImportFormat::TodoTxt, data, &ImportMapping::default()).unwrap();
+
+        assert_eq!(descriptions(&items), [("call mom @phone", false), ("pay bills", true), ("C++ talk", false)]);
+        assert_eq!(items[2].item, 4);
+        assert_eq!(items[0].details, TaskDetails { priority: Some(1), ..Default::default() });
+        assert_eq!(items[1].details, TaskDetails { completed_at: date(2), due_at: date(5), ..Default::default() });
+    }
+
+    #[test]
+    fn todo_txt_details() {
+        let data = "x pay bills pri:B
+(J) later due:soon
+(C) call mom due:2026-10-20 \\due:2026-10-21
+";
+        let items = parse(ImportFormat::TodoTxt, data, &ImportMapping::default()).unwrap();
+
+        assert_eq!(descriptions(&items), [("pay bills", true), ("later due:soon", false), ("call mom due:2026-10-21", false)]);
+        assert_eq!(items[0].details, TaskDetails { completed_at: Some(now()), priority: Some(2), ..Default::default() });
+        assert_eq!(items[1].details.priority, Some(9));
+        assert_eq!(items[2].details, TaskDetails { due_at: date(20), priority: Some(3), ..Default::default() });
+    }
+
+    #[test]
+    fn markdown_items() {
+        let data = "# Home work\n\n- [ ] plan trip\n  book hotel\n* [x] done\n1. [ ] numbered\ntext after\n- [link](url)\n  - [ ] nested\n";
+        let items = parse(ImportFormat::Markdown, data, &ImportMapping::default()).unwrap();
+
+        assert_eq!(descriptions(&items), [("plan trip\nbook hotel", false), ("done", true), ("numbered", false), ("nested", false)]);
+        assert_eq!(items[1].details.completed_at, Some(now()));
+    }
+
+    #[test]
+    fn csv_mapping_and_order() {
+        let data = "Title,Notes,Position\r\nsecond,,2\r\n\"first, with comma\",\"note\nline\",1\r\nlast,,\r\n";
+        let mapping = ImportMapping { description: Some("title".to_string()), notes: Some("Notes".to_string()), order: Some("Position".to_string()), ..Default::default() };
+        let items = parse(ImportFormat::Csv, data, &mapping).unwrap();
+
+        assert_eq!(descriptions(&items), [("first, with comma\n\nnote\nline", false), ("second", false), ("last", false)]);
+
+        let error = parse(ImportFormat::Csv, data, &ImportMapping::default()).unwrap_err();
+        assert_eq!(error.detail.unwrap(), "Column \"description\" not found, columns of the file: Title, Notes, Position");
+    }
+
+    #[test]
+    fn csv_details() {
+        let data = "description,completed_at,due_at,priority\r\nexported,2026-10-19T18:30:00Z,,1\r\nopen,,2026-10-20,\r\n";
+        let items = parse(ImportFormat::Csv, data, &ImportMapping::default()).unwrap();
+
+        assert_eq!(items[0].details, TaskDetails { completed_at: Some(Utc.with_ymd_and_hms(2026, 10, 19, 18, 30, 0).unwrap()), priority: Some(1), ..Default::default() });
+        assert_eq!(items[1].details, TaskDetails { due_at: date(20), ..Default::default() });
+
+        let data = "Title,Done,Deadline,Importance\r\na,yes,2026-10-20T12:00:00,5\r\nb,no,,\r\n";
+        let mapping = ImportMapping {
+            description: Some("Title".to_string()),
+            completed: Some("Done".to_string()),
+            due: Some("Deadline".to_string()),
+            priority: Some("Importance".to_string()),
+            ..Default::default()
+        };
+        let items = parse(ImportFormat::Csv, data, &mapping).unwrap();
+        assert_eq!(items[0].details, TaskDetails { completed_at: Some(now()), due_at: Some(Utc.with_ymd_and_hms(2026, 10, 20, 12, 0, 0).unwrap()), priority: Some(5), rrule: None });
+        assert_eq!(items[1].details, TaskDetails::default());
+
+        let error = parse(ImportFormat::Csv, "description,due_at\r\na,tomorrow\r\n", &ImportMapping::default()).unwrap_err();
+        assert_eq!(error.detail.unwrap(), "Due date of item 1 is not a date: tomorrow");
+
+        let mapping = ImportMapping { due: Some("Deadline".to_string()), ..Default::default() };
+        assert!(parse(ImportFormat::Csv, "description\r\na\r\n", &mapping).is_err());
+    }
+
+    #[test]
+    fn todoist_items() {
+        let data = r#"{"items": [
+            {"content": "b", "child_order": 2, "checked": false},
+            {"content": "a", "description": "details", "child_order": 1},
+            {"content": "deleted", "child_order": 0, "is_deleted": true},
+            {"content": "done", "child_order": 3, "checked": true, "completed_at": "2026-10-18T10:00:00Z"}
+        ]}"#;
+        let items = parse(ImportFormat::Todoist, data, &ImportMapping::default()).unwrap();
+
+        assert_eq!(descriptions(&items), [("a\n\ndetails", false), ("b", false), ("done", true)]);
+        assert_eq!(items[2].details.completed_at, Some(Utc.with_ymd_and_hms(2026, 10, 18, 10, 0, 0).unwrap()));
+
+        let rest = r#"[{"content": "x", "order": 1, "is_completed": false}]"#;
+        assert_eq!(descriptions(&parse(ImportFormat::Todoist, rest, &ImportMapping::default()).unwrap()), [("x", false)]);
+    }
+
+    #[test]
+    fn todoist_details() {
+        let data = r#"[
+            {"content": "urgent", "priority": 4, "due": {"date": "2026-10-20", "datetime": null}},
+            {"content": "timed", "priority": 1, "due": {"date": "2026-10-20T12:00:00", "datetime": "2026-10-20T10:00:00Z"}},
+            {"content": "done", "is_completed": true, "priority": 3}
+        ]"#;
+        let items = parse(ImportFormat::Todoist, data, &ImportMapping::default()).unwrap();
+
+        assert_eq!(items[0].details, TaskDetails { due_at: date(20), priority: Some(1), ..Default::default() });
+        assert_eq!(items[1].details, TaskDetails { due_at: Some(Utc.with_ymd_and_hms(2026, 10, 20, 10, 0, 0).unwrap()), ..Default::default() });
+        assert_eq!(items[2].details, TaskDetails { completed_at: Some(now()), priority: Some(5), ..Default::default() });
+    }
+
+    #[test]
+    fn trello_items() {
+        let data = r#"{
+            "lists": [{"id": "l2", "name": "Doing", "pos": 2}, {"id": "l1", "name": "To do", "pos": 1}],
+            "cards": [
+                {"name": "doing", "idList": "l2", "pos": 1, "due": "2026-10-20T09:00:00.000Z"},
+                {"name": "second", "idList": "l1", "pos": 20, "desc": "notes"},
+                {"name": "first", "idList": "l1", "pos": 10},
+                {"name": "archived", "idList": "l1", "pos": 5, "closed": true}
+            ]
+        }"#;
+        let items = parse(ImportFormat::Trello, data, &ImportMapping::default()).unwrap();
+
+        assert_eq!(descriptions(&items), [("archived", true), ("first", false), ("second\n\nnotes", false), ("doing", false)]);
+        assert_eq!(items[3].details.due_at, Some(Utc.with_ymd_and_hms(2026, 10, 20, 9, 0, 0).unwrap()));
+
+        let mapping = ImportMapping { list: Some("Doing".to_string()), ..Default::default() };
+        assert_eq!(descriptions(&parse(ImportFormat::Trello, data, &mapping).unwrap()), [("doing", false)]);
+
+        let mapping = ImportMapping { list: Some("Done".to_string()), ..Default::default() };
+        assert!(parse(ImportFormat::Trello, data, &mapping).is_err());
+    }
+
+    #[test]
+    fn mapping_of_line_formats_is_rejected() {
+        let mapping = ImportMapping { description: Some("title".to_string()), ..Default::default() };
+        assert!(parse(ImportFormat::TodoTxt, "task", &mapping).is_err());
+        let mapping = ImportMapping { priority: Some("priority".to_string()), ..Default::default() };
+        assert!(parse(ImportFormat::Markdown, "- [ ] task", &mapping).is_err());
+        assert!(parse(ImportFormat::Todoist, "not json", &ImportMapping::default()).is_err());
+    }
+
+    #[test]
+    fn plan_skips_duplicates_and_reports_errors() {
+        let existing = vec![FullTaskInfo { id: Uuid::new_v4(), todo_list_id: Uuid::nil(), description: "Buy  Milk".to_string(), order: 1, ..Default::default() }];
+        let item = |item: usize, description: &str, details: TaskDetails| ParsedItem { item, description: description.to_string(), details };
+        let done = TaskDetails { completed_at: Some(now()), ..Default::default() };
+        let invalid = TaskDetails { priority: Some(10), ..Default::default() };
+        let items = || vec![
+            item(1, "buy milk", TaskDetails::default()),
+            item(2, "call mom", TaskDetails::default()),
+            item(3, " ", TaskDetails::default()),
+            item(4, "Call mom", TaskDetails::default()),
+            item(5, "done", done.clone()),
+            item(6, "urgent", invalid.clone()),
+        ];
+
+        let report = plan_import(ImportFormat::Csv, true, items(), &existing, DuplicatePolicy::Skip);
+        assert_eq!((report.total, report.created), (6, 2));
+        assert_eq!(report.duplicates.iter().map(|issue| issue.item).collect::<Vec<_>>(), [1, 4]);
+        assert_eq!(report.errors.iter().map(|issue| issue.item).collect::<Vec<_>>(), [3, 6]);
+        assert_eq!(report.errors[1].detail, "Priority must be between 1 and 9");
+        assert_eq!((report.tasks[0].order, report.tasks[0].description.as_str()), (2, "call mom"));
+        assert_eq!((report.tasks[1].description.as_str(), report.tasks[1].completed_at), ("done", Some(now())));
+
+        let report = plan_import(ImportFormat::Csv, true, items(), &existing, DuplicatePolicy::Allow);
+        assert_eq!(report.tasks.iter().map(|task| task.order).collect::<Vec<_>>(), [2, 3, 4, 5]);
+        assert!(report.duplicates.is_empty());
+    }
+}
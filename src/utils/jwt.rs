@@ -0,0 +1,336 @@
+use std::{
+    env,
+    fs,
+    path::Path
+};
+
+use anyhow::Context;
+use jsonwebtoken::{
+    decode,
+    decode_header,
+    encode,
+    Algorithm,
+    DecodingKey,
+    EncodingKey,
+    Header,
+    Validation
+};
+use ring::signature::{
+    Ed25519KeyPair,
+    KeyPair,
+    RsaKeyPair
+};
+use serde::{
+    de::DeserializeOwned,
+    Serialize
+};
+use simple_asn1::{
+    oid,
+    ASN1Block
+};
+
+use crate::models::*;
+
+const BEARER_KEY_ENV: &str = "BEARER_KEY";
+const TODO_SERVICE_JWT_KEYS_DIR_ENV: &str = "TODO_SERVICE_JWT_KEYS_DIR";
+const TODO_SERVICE_JWT_SIGNING_KEY_ENV: &str = "TODO_SERVICE_JWT_SIGNING_KEY";
+
+/// Keys of the tokens, loaded once on start and shared by workers.
+/// Tokens are signed by one key and verified by the key with `kid` of the token header,
+/// so tokens of retired keys stay valid while their files are in the keys directory
+pub struct JwtKeys {
+    signing: SigningKey,
+    verifying: Vec<VerifyingKey>,
+}
+
+struct SigningKey {
+    kid: Option<String>,
+    algorithm: Algorithm,
+    key: EncodingKey,
+}
+
+struct VerifyingKey {
+    /// `None` for shared secret, tokens signed by it have no `kid`
+    kid: Option<String>,
+    algorithm: Algorithm,
+    key: DecodingKey,
+    /// Public key for jwks, `None` for shared secret
+    jwk: Option<Jwk>,
+}
+
+impl JwtKeys {
+    /// HS256 with shared secret, other services can't verify tokens without it
+    pub fn hmac(secret: &[u8]) -> Self {
+        Self {
+            signing: SigningKey { kid: None, algorithm: Algorithm::HS256, key: EncodingKey::from_secret(secret) },
+            verifying: vec![hmac_verifying_key(secret)],
+        }
+    }
+
+    /// Loads `<kid>.pem` files of the directory. Private keys are RSA (PKCS#1 or PKCS#8) or Ed25519 (PKCS#8),
+    /// public keys (SPKI) of retired keys only verify tokens. `signing_kid` must be a private key
+    pub fn load(dir: &Path, signing_kid: &str) -> anyhow::Result<Self> {
+        let mut paths = fs::read_dir(dir)
+            .with_context(|| format!("Can't read jwt keys directory {}", dir.display()))?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        paths.sort();
+
+        let mut signing = None;
+        let mut verifying = Vec::new();
+
+        for path in paths {
+            let kid = match path.file_name().and_then(|name| name.to_str()).and_then(|name| name.strip_suffix(".pem")) {
+                Some(kid) => kid.to_string(),
+                None => continue,
+            };
+
+            let pem = fs::read(&path).with_context(|| format!("Can't read jwt key {}", path.display()))?;
+            let (signing_key, verifying_key) = parse_key(&kid, &pem)
+                .with_context(|| format!("Invalid jwt key {}", path.display()))?;
+
+            if kid == signing_kid {
+                signing = Some(signing_key.with_context(|| format!("Signing jwt key \"{kid}\" has no private key"))?);
+            }
+            verifying.push(verifying_key);
+        }
+
+        let signing = signing.with_context(|| format!("Signing jwt key \"{signing_kid}\" not found in {}", dir.display()))?;
+
+        Ok(Self { signing, verifying })
+    }
+
+    /// Asymmetric keys are used if `TODO_SERVICE_JWT_KEYS_DIR` is set, otherwise HS256 with `BEARER_KEY`.
+    /// With keys directory `BEARER_KEY` is optional and only verifies tokens issued before the switch
+    pub fn from_env() -> anyhow::Result<Self> {
+        let bearer_key = env::var(BEARER_KEY_ENV).ok();
+
+        let dir = match env::var(TODO_SERVICE_JWT_KEYS_DIR_ENV) {
+            Ok(dir) => dir,
+            Err(_) => {
+                let bearer_key = bearer_key.unwrap_or_else(|| panic!("Env {BEARER_KEY_ENV} not found"));
+                return Ok(Self::hmac(bearer_key.as_bytes()));
+            },
+        };
+
+        let signing_kid = env::var(TODO_SERVICE_JWT_SIGNING_KEY_ENV)
+            .unwrap_or_else(|_| panic!("Env {TODO_SERVICE_JWT_SIGNING_KEY_ENV} not found"));
+
+        let mut keys = Self::load(Path::new(&dir), &signing_kid)?;
+        if let Some(bearer_key) = bearer_key {
+            keys.verifying.push(hmac_verifying_key(bearer_key.as_bytes()));
+        }
+
+        Ok(keys)
+    }
+
+    pub fn sign<T: Serialize>(&self, claims: &T) -> Result<String, ServiceError> {
+        let header = Header {
+            kid: self.signing.kid.clone(),
+            ..Header::new(self.signing.algorithm)
+        };
+
+        encode(&header, claims, &self.signing.key)
+            .map_err(|e| ServiceError { status_code: StatusCode::InternalError, detail: Some(e.to_string()) })
+    }
+
+    /// Claims of the valid token, key is selected by `kid` and must have the algorithm of the token
+    pub fn verify<T: DeserializeOwned>(&self, token: &str) -> Option<T> {
+        let header = decode_header(token).ok()?;
+        let key = self.verifying.iter().find(|key| key.kid == header.kid)?;
+
+        decode::<T>(token, &key.key, &Validation::new(key.algorithm))
+            .map(|data| data.claims)
+            .ok()
+    }
+
+    /// Public keys, shared secret isn't published
+    pub fn jwks(&self) -> JwkSet {
+        JwkSet { keys: self.verifying.iter().filter_map(|key| key.jwk.clone()).collect() }
+    }
+}
+
+fn hmac_verifying_key(secret: &[u8]) -> VerifyingKey {
+    VerifyingKey { kid: None, algorithm: Algorithm::HS256, key: DecodingKey::from_secret(secret), jwk: None }
+}
+
+/// Signing key is `None` for public key
+fn parse_key(kid: &str, pem: &[u8]) -> anyhow::Result<(Option<SigningKey>, VerifyingKey)> {
+    let parsed = pem::parse(pem)?;
+
+    let (algorithm, public) = match parsed.tag.as_str() {
+        "RSA PRIVATE KEY" => rsa_private(RsaKeyPair::from_der(&parsed.contents))?,
+        "PRIVATE KEY" => match Ed25519KeyPair::from_pkcs8_maybe_unchecked(&parsed.contents) {
+            Ok(key_pair) => (Algorithm::EdDSA, PublicKey::Ed25519(key_pair.public_key().as_ref().to_vec())),
+            Err(_) => rsa_private(RsaKeyPair::from_pkcs8(&parsed.contents))?,
+        },
+        "PUBLIC KEY" => {
+            let public = spki_public_key(&parsed.contents)?;
+            let algorithm = match public {
+                PublicKey::Rsa { .. } => Algorithm::RS256,
+                PublicKey::Ed25519(_) => Algorithm::EdDSA,
+            };
+            return Ok((None, public.into_verifying_key(kid, algorithm)));
+        },
+        tag => anyhow::bail!("Unsupported pem \"{tag}\", expected private or public key"),
+    };
+
+    let key = match algorithm {
+        Algorithm::EdDSA => EncodingKey::from_ed_pem(pem)?,
+        _ => EncodingKey::from_rsa_pem(pem)?,
+    };
+
+    Ok((
+        Some(SigningKey { kid: Some(kid.to_string()), algorithm, key }),
+        public.into_verifying_key(kid, algorithm),
+    ))
+}
+
+fn rsa_private(key_pair: Result<RsaKeyPair, ring::error::KeyRejected>) -> anyhow::Result<(Algorithm, PublicKey)> {
+    let key_pair = key_pair.map_err(anyhow::Error::msg)?;
+    let public = key_pair.public_key();
+
+    Ok((Algorithm::RS256, PublicKey::Rsa {
+        n: public.modulus().big_endian_without_leading_zero().to_vec(),
+        e: public.exponent().big_endian_without_leading_zero().to_vec(),
+    }))
+}
+
+enum PublicKey {
+    Rsa { n: Vec<u8>, e: Vec<u8> },
+    Ed25519(Vec<u8>),
+}
+
+impl PublicKey {
+    fn into_verifying_key(self, kid: &str, algorithm: Algorithm) -> VerifyingKey {
+        let (key, jwk) = match self {
+            PublicKey::Rsa { n, e } => (
+                DecodingKey::from_rsa_raw_components(&n, &e),
+                Jwk { kty: "RSA".to_string(), kid: kid.to_string(), key_use: "sig".to_string(), alg: "RS256".to_string(), n: Some(base64url(&n)), e: Some(base64url(&e)), crv: None, x: None },
+            ),
+            PublicKey::Ed25519(x) => (
+                DecodingKey::from_ed_der(&x),
+                Jwk { kty: "OKP".to_string(), kid: kid.to_string(), key_use: "sig".to_string(), alg: "EdDSA".to_string(), n: None, e: None, crv: Some("Ed25519".to_string()), x: Some(base64url(&x)) },
+            ),
+        };
+
+        VerifyingKey { kid: Some(kid.to_string()), algorithm, key, jwk: Some(jwk) }
+    }
+}
+
+/// SubjectPublicKeyInfo: sequence of algorithm identifier and bit string with the key,
+/// which is PKCS#1 RSAPublicKey for RSA and raw 32 bytes for Ed25519
+fn spki_public_key(der: &[u8]) -> anyhow::Result<PublicKey> {
+    let (algorithm, key) = match simple_asn1::from_der(der)?.as_slice() {
+        [ASN1Block::Sequence(_, info)] => match info.as_slice() {
+            [ASN1Block::Sequence(_, algorithm), ASN1Block::BitString(_, _, key)] => match algorithm.first() {
+                Some(ASN1Block::ObjectIdentifier(_, algorithm)) => (algorithm.clone(), key.clone()),
+                _ => anyhow::bail!("Invalid public key algorithm"),
+            },
+            _ => anyhow::bail!("Invalid public key"),
+        },
+        _ => anyhow::bail!("Invalid public key"),
+    };
+
+    if algorithm == oid!(1, 3, 101, 112) {
+        anyhow::ensure!(key.len() == 32, "Invalid Ed25519 public key");
+        return Ok(PublicKey::Ed25519(key));
+    }
+
+    if algorithm == oid!(1, 2, 840, 113_549, 1, 1, 1) {
+        return match simple_asn1::from_der(&key)?.as_slice() {
+            [ASN1Block::Sequence(_, components)] => match components.as_slice() {
+                [ASN1Block::Integer(_, n), ASN1Block::Integer(_, e)] => Ok(PublicKey::Rsa { n: n.to_bytes_be().1, e: e.to_bytes_be().1 }),
+                _ => anyhow::bail!("Invalid RSA public key"),
+            },
+            _ => anyhow::bail!("Invalid RSA public key"),
+        };
+    }
+
+    anyhow::bail!("Unsupported public key algorithm, expected RSA or Ed25519")
+}
+
+fn base64url(bytes: &[u8]) -> String {
+    base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use chrono::{
+        Duration,
+        Utc
+    };
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn keys_dir() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/keys/jwks")
+    }
+
+    fn claim() -> UserClaim {
+        UserClaim { exp: (Utc::now() + Duration::hours(1)).timestamp() as usize, user_id: Uuid::new_v4(), session_version: 0 }
+    }
+
+    #[test]
+    fn hmac_round_trip() {
+        let keys = JwtKeys::hmac(b"secret");
+        let claim = claim();
+
+        let token = keys.sign(&claim).unwrap();
+        assert_eq!(decode_header(&token).unwrap().kid, None);
+        assert_eq!(keys.verify::<UserClaim>(&token).unwrap().user_id, claim.user_id);
+
+        assert!(JwtKeys::hmac(b"other").verify::<UserClaim>(&token).is_none());
+        assert!(keys.jwks().keys.is_empty());
+    }
+
+    #[test]
+    fn every_key_of_directory_verifies() {
+        let claim = claim();
+
+        for (kid, alg) in [("ed-2", Algorithm::EdDSA), ("rsa-1", Algorithm::RS256)] {
+            let keys = JwtKeys::load(&keys_dir(), kid).unwrap();
+            let token = keys.sign(&claim).unwrap();
+
+            let header = decode_header(&token).unwrap();
+            assert_eq!((header.kid.as_deref(), header.alg), (Some(kid), alg));
+
+            // other signing key, same set of verifying keys
+            let other = JwtKeys::load(&keys_dir(), if kid == "ed-2" { "rsa-1" } else { "ed-2" }).unwrap();
+            assert_eq!(other.verify::<UserClaim>(&token).unwrap().user_id, claim.user_id);
+        }
+    }
+
+    #[test]
+    fn jwks_has_public_keys() {
+        let jwks = JwtKeys::load(&keys_dir(), "ed-2").unwrap().jwks();
+        let keys: Vec<_> = jwks.keys.iter().map(|key| (key.kid.as_str(), key.kty.as_str(), key.alg.as_str())).collect();
+
+        assert_eq!(keys, vec![("ed-2", "OKP", "EdDSA"), ("rsa-0", "RSA", "RS256"), ("rsa-1", "RSA", "RS256")]);
+        assert_eq!(jwks.keys[1].e.as_deref(), Some("AQAB"));
+        assert_eq!(jwks.keys[0].x.as_ref().map(|x| x.len()), Some(43));
+    }
+
+    #[test]
+    fn public_key_can_not_sign() {
+        assert!(JwtKeys::load(&keys_dir(), "rsa-0").is_err());
+        assert!(JwtKeys::load(&keys_dir(), "unknown").is_err());
+    }
+
+    #[test]
+    fn token_must_match_key_algorithm() {
+        let keys = JwtKeys::load(&keys_dir(), "rsa-1").unwrap();
+
+        // HS256 token with kid of the RSA key
+        let header = Header { kid: Some("rsa-1".to_string()), ..Header::new(Algorithm::HS256) };
+        let token = encode(&header, &claim(), &EncodingKey::from_secret(b"secret")).unwrap();
+        assert!(keys.verify::<UserClaim>(&token).is_none());
+
+        // token without kid, there is no shared secret
+        let token = JwtKeys::hmac(b"secret").sign(&claim()).unwrap();
+        assert!(keys.verify::<UserClaim>(&token).is_none());
+    }
+}
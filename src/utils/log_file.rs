@@ -0,0 +1,388 @@
+use std::{
+    fs::{
+        self,
+        File,
+        OpenOptions
+    },
+    io::{
+        self,
+        Write
+    },
+    path::{
+        Path,
+        PathBuf
+    },
+    sync::{
+        atomic::{
+            AtomicBool,
+            Ordering
+        },
+        Arc
+    },
+    time::{
+        SystemTime,
+        UNIX_EPOCH
+    }
+};
+
+use flate2::{
+    write::GzEncoder,
+    Compression
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RotationPeriod {
+    Never,
+    Hourly,
+    Daily,
+}
+
+impl RotationPeriod {
+    fn seconds(&self) -> Option<u64> {
+        match self {
+            RotationPeriod::Never => None,
+            RotationPeriod::Hourly => Some(60 * 60),
+            RotationPeriod::Daily => Some(24 * 60 * 60),
+        }
+    }
+
+    /// Number of the period since unix epoch, file is rotated when it changes
+    fn key(&self, time: SystemTime) -> Option<u64> {
+        let seconds = time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        self.seconds().map(|period| seconds / period)
+    }
+}
+
+impl std::str::FromStr for RotationPeriod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "never" => Ok(RotationPeriod::Never),
+            "hourly" => Ok(RotationPeriod::Hourly),
+            "daily" => Ok(RotationPeriod::Daily),
+            _ => Err(format!("Unknown rotation period \"{s}\", expected never, hourly or daily")),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RotationConfig {
+    /// Rotate when file size exceeds this value, `None` disables size based rotation
+    pub max_size: Option<u64>,
+    pub period: RotationPeriod,
+    /// Count of rotated files which are kept, older files are removed
+    pub retained_files: usize,
+    /// Gzip rotated files
+    pub compress: bool,
+}
+
+/// Handle for reopening log file after it was moved by external tool (logrotate)
+#[derive(Clone)]
+pub struct ReopenHandle {
+    reopen: Arc<AtomicBool>,
+}
+
+impl ReopenHandle {
+    /// File is reopened before the next write
+    pub fn reopen(&self) {
+        self.reopen.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Source of the current time, time based rotation is checked against it
+type Clock = Box<dyn Fn() -> SystemTime + Send>;
+
+/// Append only log file with size and time based rotation.
+/// Every record must end with `flush`, so file is never rotated in the middle of a record
+pub struct RotatingFile {
+    path: PathBuf,
+    config: RotationConfig,
+    file: File,
+    size: u64,
+    period_key: Option<u64>,
+    reopen: Arc<AtomicBool>,
+    record_start: bool,
+    clock: Clock,
+}
+
+impl RotatingFile {
+    pub fn open(path: impl AsRef<Path>, config: RotationConfig) -> io::Result<Self> {
+        Self::open_with_clock(path, config, Box::new(SystemTime::now))
+    }
+
+    fn open_with_clock(path: impl AsRef<Path>, config: RotationConfig, clock: Clock) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let (file, size, modified) = open_append(&path)?;
+        let opened = if size == 0 { clock() } else { modified };
+
+        Ok(Self {
+            period_key: config.period.key(opened),
+            path,
+            config,
+            file,
+            size,
+            reopen: Arc::new(AtomicBool::new(false)),
+            record_start: true,
+            clock,
+        })
+    }
+
+    pub fn reopen_handle(&self) -> ReopenHandle {
+        ReopenHandle { reopen: self.reopen.clone() }
+    }
+
+    fn reopen_file(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        let (file, size, modified) = open_append(&self.path)?;
+        let opened = if size == 0 { (self.clock)() } else { modified };
+        self.file = file;
+        self.size = size;
+        self.period_key = self.config.period.key(opened);
+        Ok(())
+    }
+
+    fn need_rotation(&self, incoming: usize) -> bool {
+        let size_exceeded = self.config.max_size
+            .map(|max_size| self.size > 0 && self.size + incoming as u64 > max_size)
+            .unwrap_or(false);
+
+        let period_changed = self.period_key.is_some()
+            && self.config.period.key((self.clock)()) != self.period_key;
+
+        size_exceeded || period_changed
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+
+        let retained = self.config.retained_files;
+
+        if retained == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            remove_if_exists(&self.rotated_path(retained, false))?;
+            remove_if_exists(&self.rotated_path(retained, true))?;
+
+            for index in (1..retained).rev() {
+                for compressed in [false, true] {
+                    let from = self.rotated_path(index, compressed);
+                    if from.exists() {
+                        fs::rename(&from, self.rotated_path(index + 1, compressed))?;
+                    }
+                }
+            }
+
+            let rotated = self.rotated_path(1, false);
+            fs::rename(&self.path, &rotated)?;
+
+            if self.config.compress {
+                gzip(&rotated, &self.rotated_path(1, true))?;
+            }
+        }
+
+        self.reopen_file()
+    }
+
+    /// `log.json.1` is the newest rotated file, `log.json.{retained_files}` is the oldest
+    fn rotated_path(&self, index: usize, compressed: bool) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{index}"));
+        if compressed {
+            path.push(".gz");
+        }
+        PathBuf::from(path)
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.record_start {
+            self.record_start = false;
+
+            if self.reopen.swap(false, Ordering::SeqCst) {
+                self.reopen_file()?;
+            }
+
+            if self.need_rotation(buf.len()) {
+                self.rotate()?;
+            }
+        }
+
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.record_start = true;
+        self.file.flush()
+    }
+}
+
+fn open_append(path: &Path) -> io::Result<(File, u64, SystemTime)> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let metadata = file.metadata()?;
+    let modified = metadata.modified().unwrap_or_else(|_| SystemTime::now());
+    Ok((file, metadata.len(), modified))
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+fn gzip(source: &Path, destination: &Path) -> io::Result<()> {
+    let mut input = File::open(source)?;
+    let mut encoder = GzEncoder::new(File::create(destination)?, Compression::default());
+    io::copy(&mut input, &mut encoder)?;
+    encoder.finish()?;
+    fs::remove_file(source)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env,
+        io::Read,
+        sync::atomic::AtomicU64,
+        time::Duration
+    };
+
+    use flate2::read::GzDecoder;
+
+    use super::*;
+
+    /// Temporary directory, removed on drop
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            let path = env::temp_dir().join(format!("todo-log-{}", uuid::Uuid::new_v4().simple()));
+            fs::create_dir(&path).unwrap();
+            Self(path)
+        }
+
+        fn log(&self) -> PathBuf {
+            self.0.join("log.json")
+        }
+
+        fn files(&self) -> Vec<String> {
+            let mut files: Vec<String> = fs::read_dir(&self.0).unwrap()
+                .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+                .collect();
+            files.sort();
+            files
+        }
+
+        fn read(&self, name: &str) -> String {
+            fs::read_to_string(self.0.join(name)).unwrap()
+        }
+
+        fn read_gzip(&self, name: &str) -> String {
+            let mut content = String::new();
+            GzDecoder::new(File::open(self.0.join(name)).unwrap()).read_to_string(&mut content).unwrap();
+            content
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn config(max_size: Option<u64>, period: RotationPeriod, retained_files: usize, compress: bool) -> RotationConfig {
+        RotationConfig { max_size, period, retained_files, compress }
+    }
+
+    fn record(file: &mut RotatingFile, record: &str) {
+        file.write_all(record.as_bytes()).unwrap();
+        file.flush().unwrap();
+    }
+
+    #[test]
+    fn file_is_rotated_by_size_and_old_files_are_removed() {
+        let dir = TempDir::new();
+        let mut file = RotatingFile::open(dir.log(), config(Some(10), RotationPeriod::Never, 2, false)).unwrap();
+
+        record(&mut file, "first\n");
+        assert_eq!(dir.files(), ["log.json"]);
+
+        for content in ["second\n", "third\n", "fourth\n"] {
+            record(&mut file, content);
+        }
+
+        assert_eq!(dir.files(), ["log.json", "log.json.1", "log.json.2"]);
+        assert_eq!(dir.read("log.json"), "fourth\n");
+        assert_eq!(dir.read("log.json.1"), "third\n");
+        assert_eq!(dir.read("log.json.2"), "second\n");
+    }
+
+    #[test]
+    fn record_is_not_split_by_rotation() {
+        let dir = TempDir::new();
+        let mut file = RotatingFile::open(dir.log(), config(Some(10), RotationPeriod::Never, 1, false)).unwrap();
+
+        file.write_all(b"long ").unwrap();
+        file.write_all(b"record\n").unwrap();
+        file.flush().unwrap();
+        record(&mut file, "next\n");
+
+        assert_eq!(dir.read("log.json.1"), "long record\n");
+        assert_eq!(dir.read("log.json"), "next\n");
+    }
+
+    #[test]
+    fn file_is_rotated_when_period_changes() {
+        let dir = TempDir::new();
+        let now = Arc::new(AtomicU64::new(1_800_000_000));
+        let clock = {
+            let now = now.clone();
+            Box::new(move || UNIX_EPOCH + Duration::from_secs(now.load(Ordering::SeqCst)))
+        };
+        let mut file = RotatingFile::open_with_clock(dir.log(), config(None, RotationPeriod::Hourly, 3, false), clock).unwrap();
+
+        record(&mut file, "first\n");
+        now.fetch_add(60, Ordering::SeqCst);
+        record(&mut file, "same hour\n");
+        assert_eq!(dir.files(), ["log.json"]);
+
+        now.fetch_add(60 * 60, Ordering::SeqCst);
+        record(&mut file, "next hour\n");
+        record(&mut file, "still next hour\n");
+
+        assert_eq!(dir.files(), ["log.json", "log.json.1"]);
+        assert_eq!(dir.read("log.json.1"), "first\nsame hour\n");
+        assert_eq!(dir.read("log.json"), "next hour\nstill next hour\n");
+    }
+
+    #[test]
+    fn rotated_files_are_compressed() {
+        let dir = TempDir::new();
+        let mut file = RotatingFile::open(dir.log(), config(Some(10), RotationPeriod::Never, 2, true)).unwrap();
+
+        for content in ["first\n", "second\n", "third\n"] {
+            record(&mut file, content);
+        }
+
+        assert_eq!(dir.files(), ["log.json", "log.json.1.gz", "log.json.2.gz"]);
+        assert_eq!(dir.read_gzip("log.json.1.gz"), "second\n");
+        assert_eq!(dir.read_gzip("log.json.2.gz"), "first\n");
+        assert_eq!(dir.read("log.json"), "third\n");
+    }
+
+    #[test]
+    fn no_rotated_files_are_kept_without_retention() {
+        let dir = TempDir::new();
+        let mut file = RotatingFile::open(dir.log(), config(Some(10), RotationPeriod::Never, 0, false)).unwrap();
+
+        record(&mut file, "first\n");
+        record(&mut file, "second\n");
+
+        assert_eq!(dir.files(), ["log.json"]);
+        assert_eq!(dir.read("log.json"), "second\n");
+    }
+}
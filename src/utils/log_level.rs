@@ -0,0 +1,308 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    panic::RefUnwindSafe,
+    sync::{
+        Arc,
+        Mutex
+    }
+};
+
+use serde::{
+    Deserialize,
+    Deserializer,
+    Serialize,
+    Serializer
+};
+use slog::{
+    Drain,
+    Key,
+    Level,
+    Never,
+    OwnedKVList,
+    Record,
+    KV
+};
+use slog_atomic::{
+    AtomicSwitch,
+    AtomicSwitchCtrl
+};
+
+/// Level wrapper, (de)serialized as level name: "info", "debug" ...
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogLevel(pub Level);
+
+impl Serialize for LogLevel {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0.as_str().to_lowercase())
+    }
+}
+
+impl<'de> Deserialize<'de> for LogLevel {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        let level_name = if name.eq_ignore_ascii_case("warning") { "warn" } else { name.as_str() };
+
+        level_name.parse::<Level>()
+            .map(LogLevel)
+            .map_err(|_| serde::de::Error::custom(format!("Unknown log level \"{name}\"")))
+    }
+}
+
+/// Level for records which have key with specified value, e.g. `route` = `/api/task`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyLevel {
+    pub key: String,
+    pub value: String,
+    pub level: LogLevel,
+}
+
+/// Levels of one drain. Level for record is chosen by first matched key rule,
+/// then by the longest matched module prefix, then default level is used
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LevelConfig {
+    pub level: LogLevel,
+    #[serde(default)]
+    pub modules: BTreeMap<String, LogLevel>,
+    #[serde(default)]
+    pub keys: Vec<KeyLevel>,
+}
+
+impl LevelConfig {
+    pub fn new(level: Level) -> Self {
+        Self {
+            level: LogLevel(level),
+            modules: BTreeMap::new(),
+            keys: Vec::new(),
+        }
+    }
+
+    fn level_for(&self, record: &Record, values: &OwnedKVList) -> Level {
+        for key_level in &self.keys {
+            if find_value(&key_level.key, record, values).as_deref() == Some(key_level.value.as_str()) {
+                return key_level.level.0;
+            }
+        }
+
+        let module = record.module();
+
+        self.modules.iter()
+            .filter(|(prefix, _)| module == prefix.as_str() || module.starts_with(&format!("{prefix}::")))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, level)| level.0)
+            .unwrap_or(self.level.0)
+    }
+}
+
+/// Drain which passes records allowed by `LevelConfig`. Config is immutable,
+/// on change new filter is created and swapped in with `AtomicSwitch`
+struct LevelFilter<D> {
+    drain: Arc<D>,
+    config: LevelConfig,
+}
+
+impl<D: Drain<Ok = (), Err = Never>> Drain for LevelFilter<D> {
+    type Ok = ();
+    type Err = Never;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> Result<(), Never> {
+        if record.level().is_at_least(self.config.level_for(record, values)) {
+            self.drain.log(record, values)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogDrain {
+    Console,
+    File,
+}
+
+type SetConfig = Box<dyn Fn(LevelConfig) + Send + Sync>;
+
+/// Runtime control of drain levels
+#[derive(Clone)]
+pub struct LogLevelControl {
+    console: Arc<ControlledDrain>,
+    file: Arc<ControlledDrain>,
+}
+
+struct ControlledDrain {
+    config: Mutex<LevelConfig>,
+    set: SetConfig,
+}
+
+impl LogLevelControl {
+    pub fn get(&self, drain: LogDrain) -> LevelConfig {
+        self.drain(drain).config.lock().unwrap().clone()
+    }
+
+    pub fn set(&self, drain: LogDrain, config: LevelConfig) {
+        let controlled = self.drain(drain);
+        let mut current = controlled.config.lock().unwrap();
+        (controlled.set)(config.clone());
+        *current = config;
+    }
+
+    fn drain(&self, drain: LogDrain) -> &ControlledDrain {
+        match drain {
+            LogDrain::Console => &self.console,
+            LogDrain::File => &self.file,
+        }
+    }
+}
+
+fn controlled<D>(drain: D, config: LevelConfig) -> (AtomicSwitch, Arc<ControlledDrain>)
+where
+    D: Drain<Ok = (), Err = Never> + Send + Sync + RefUnwindSafe + 'static
+{
+    let drain = Arc::new(drain);
+    let switch = AtomicSwitch::new(LevelFilter { drain: drain.clone(), config: config.clone() });
+    let ctrl: AtomicSwitchCtrl = switch.ctrl();
+
+    let set: SetConfig = Box::new(move |config| ctrl.set(LevelFilter { drain: drain.clone(), config }));
+
+    (switch, Arc::new(ControlledDrain { config: Mutex::new(config), set }))
+}
+
+/// Combines console and file drains, each with its own runtime adjustable levels
+pub fn level_controlled<C, F>(
+    console: C,
+    console_config: LevelConfig,
+    file: F,
+    file_config: LevelConfig
+) -> (impl Drain<Ok = (), Err = Never> + Send + Sync + RefUnwindSafe + 'static, LogLevelControl)
+where
+    C: Drain<Ok = (), Err = Never> + Send + Sync + RefUnwindSafe + 'static,
+    F: Drain<Ok = (), Err = Never> + Send + Sync + RefUnwindSafe + 'static
+{
+    let (console_switch, console) = controlled(console, console_config);
+    let (file_switch, file) = controlled(file, file_config);
+
+    let drain = slog::Duplicate::new(console_switch, file_switch).fuse();
+
+    (drain, LogLevelControl { console, file })
+}
+
+fn find_value(key: &str, record: &Record, values: &OwnedKVList) -> Option<String> {
+    let mut finder = KeyFinder { key, value: None };
+    let _ = record.kv().serialize(record, &mut finder);
+    if finder.value.is_none() {
+        let _ = values.serialize(record, &mut finder);
+    }
+    finder.value
+}
+
+struct KeyFinder<'a> {
+    key: &'a str,
+    value: Option<String>,
+}
+
+impl<'a> slog::Serializer for KeyFinder<'a> {
+    fn emit_arguments(&mut self, key: Key, val: &fmt::Arguments) -> slog::Result {
+        if self.value.is_none() && key == self.key {
+            self.value = Some(val.to_string());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Arc,
+        Mutex
+    };
+
+    use slog::{
+        o,
+        Drain,
+        Level,
+        Logger,
+        Never,
+        OwnedKVList,
+        Record
+    };
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct Capture(Arc<Mutex<Vec<String>>>);
+
+    impl Capture {
+        fn messages(&self) -> Vec<String> {
+            self.0.lock().unwrap().clone()
+        }
+    }
+
+    impl Drain for Capture {
+        type Ok = ();
+        type Err = Never;
+
+        fn log(&self, record: &Record, _values: &OwnedKVList) -> Result<(), Never> {
+            self.0.lock().unwrap().push(record.msg().to_string());
+            Ok(())
+        }
+    }
+
+    fn logger(console_config: LevelConfig, file_config: LevelConfig) -> (Logger, Capture, Capture, LogLevelControl) {
+        let console = Capture::default();
+        let file = Capture::default();
+        let (drain, control) = level_controlled(console.clone(), console_config, file.clone(), file_config);
+        (Logger::root(drain, o!()), console, file, control)
+    }
+
+    #[test]
+    fn console_and_file_use_own_levels() {
+        let (logger, console, file, _) = logger(LevelConfig::new(Level::Info), LevelConfig::new(Level::Debug));
+
+        slog::debug!(logger, "debug");
+        slog::info!(logger, "info");
+
+        assert_eq!(console.messages(), vec!["info"]);
+        assert_eq!(file.messages(), vec!["debug", "info"]);
+    }
+
+    #[test]
+    fn level_is_changed_at_runtime() {
+        let (logger, console, file, control) = logger(LevelConfig::new(Level::Info), LevelConfig::new(Level::Info));
+
+        slog::debug!(logger, "before");
+        control.set(LogDrain::Console, LevelConfig::new(Level::Debug));
+        slog::debug!(logger, "after");
+
+        assert_eq!(console.messages(), vec!["after"]);
+        assert!(file.messages().is_empty());
+        assert_eq!(control.get(LogDrain::Console).level, LogLevel(Level::Debug));
+    }
+
+    #[test]
+    fn module_and_key_overrides() {
+        let mut config = LevelConfig::new(Level::Warning);
+        config.modules.insert(module_path!().to_string(), LogLevel(Level::Info));
+        config.modules.insert("todo_list_rs".to_string(), LogLevel(Level::Error));
+        config.keys.push(KeyLevel { key: "route".to_string(), value: "/api/task".to_string(), level: LogLevel(Level::Debug) });
+
+        let (logger, console, _, _) = logger(config, LevelConfig::new(Level::Info));
+
+        slog::debug!(logger, "module debug");
+        slog::info!(logger, "module info");
+        slog::debug!(logger.new(o!("route" => "/api/task")), "route debug");
+        slog::debug!(logger, "record key debug"; "route" => "/api/task");
+        slog::debug!(logger.new(o!("route" => "/api/list")), "other route debug");
+
+        assert_eq!(console.messages(), vec!["module info", "route debug", "record key debug"]);
+    }
+
+    #[test]
+    fn config_is_deserialized_from_level_names() {
+        let config: LevelConfig = serde_json::from_str(r#"{"level": "info", "modules": {"todo_list_rs::db": "debug"}}"#).unwrap();
+
+        assert_eq!(config.level, LogLevel(Level::Info));
+        assert_eq!(config.modules["todo_list_rs::db"], LogLevel(Level::Debug));
+        assert!(serde_json::from_str::<LevelConfig>(r#"{"level": "loud"}"#).is_err());
+    }
+}
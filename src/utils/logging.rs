@@ -1,35 +1,67 @@
-use std::env;
+use std::{
+    env,
+    future::Future,
+    pin::Pin,
+    task::{
+        Context,
+        Poll
+    }
+};
 
 use slog::*;
 use slog_scope::GlobalLoggerGuard;
 
+use super::{
+    log_file::{
+        ReopenHandle,
+        RotatingFile,
+        RotationConfig,
+        RotationPeriod
+    },
+    log_level::{
+        level_controlled,
+        LevelConfig,
+        LogLevelControl
+    }
+};
+
 const TODO_SERVICE_FILE_LOG_LEVEL_ENV: &str = "TODO_SERVICE_FILE_LOG_LEVEL";
 const TODO_SERVICE_CONSOLE_LOG_LEVEL_ENV: &str = "TODO_SERVICE_CONSOLE_LOG_LEVEL";
 const TODO_SERVICE_LOG_PATH_ENV: &str = "TODO_SERVICE_LOG_PATH";
+const TODO_SERVICE_LOG_PRETTY_ENV: &str = "TODO_SERVICE_LOG_PRETTY";
+const TODO_SERVICE_LOG_MAX_SIZE_ENV: &str = "TODO_SERVICE_LOG_MAX_SIZE";
+const TODO_SERVICE_LOG_ROTATION_PERIOD_ENV: &str = "TODO_SERVICE_LOG_ROTATION_PERIOD";
+const TODO_SERVICE_LOG_RETAINED_FILES_ENV: &str = "TODO_SERVICE_LOG_RETAINED_FILES";
+const TODO_SERVICE_LOG_COMPRESS_ENV: &str = "TODO_SERVICE_LOG_COMPRESS";
+
+const DEFAULT_RETAINED_FILES: usize = 5;
+
+pub struct Logging {
+    pub logger: Logger,
+    /// Global logger is reset when guard is dropped
+    pub scope_guard: GlobalLoggerGuard,
+    pub reopen_handle: ReopenHandle,
+    pub level_control: LogLevelControl,
+}
 
-pub fn create_logger() -> (Logger, GlobalLoggerGuard) {
-    let file_log_level = env::var(TODO_SERVICE_FILE_LOG_LEVEL_ENV)
-        .expect(&*format!("Env {TODO_SERVICE_FILE_LOG_LEVEL_ENV} not found"))
-        .parse::<Level>()
-        .expect(&*format!("Env {TODO_SERVICE_FILE_LOG_LEVEL_ENV} must be valid slog::Level"));
-
-
-    let console_log_level = env::var(TODO_SERVICE_CONSOLE_LOG_LEVEL_ENV)
-        .expect(&*format!("Env {TODO_SERVICE_CONSOLE_LOG_LEVEL_ENV} not found"))
-        .parse::<Level>()
-        .expect(&*format!("Env {TODO_SERVICE_CONSOLE_LOG_LEVEL_ENV} must be valid slog::Level"));
+pub fn create_logger() -> Logging {
+    let LogLevels { console: console_log_level, file: file_log_level } = log_levels(|name| env::var(name).ok());
 
     let decorator = slog_term::TermDecorator::new().build();
-    let terminal_drain = slog_term::FullFormat::new(decorator).use_custom_header_print(print_msg_header).build().filter_level(file_log_level).fuse();
+    let terminal_drain = slog_term::FullFormat::new(decorator).use_custom_header_print(print_msg_header).build().fuse();
     let terminal_drain = slog_async::Async::new(terminal_drain).build().fuse();
 
     let log_path = env::var(TODO_SERVICE_LOG_PATH_ENV)
         .expect(&*format!("Env {TODO_SERVICE_LOG_PATH_ENV} not found"));
 
-    let file = std::fs::File::create(log_path).expect("Couldn't open log file");
+    let file = RotatingFile::open(log_path, get_rotation_config()).expect("Couldn't open log file");
+    let reopen_handle = file.reopen_handle();
+
+    let pretty = get_optional_env::<bool>(TODO_SERVICE_LOG_PRETTY_ENV).unwrap_or(true);
 
     let drain = slog_json::Json::new(file)
-        .set_pretty(true)
+        .set_pretty(pretty)
+        .set_flush(true)
         .add_key_value(o!(
             "ts" => FnValue(move |_ : &Record| {
                 time::OffsetDateTime::now_utc()
@@ -48,20 +80,68 @@ pub fn create_logger() -> (Logger, GlobalLoggerGuard) {
             }),
         ))
         .build()
-        .filter_level(console_log_level)
         .fuse();
         
     let drain = slog_async::Async::new(drain).build().fuse();
 
+    let (drain, level_control) = level_controlled(
+        terminal_drain,
+        LevelConfig::new(console_log_level),
+        drain,
+        LevelConfig::new(file_log_level)
+    );
+
     let root_logger = Logger::root(
-        slog::Duplicate::new(terminal_drain, drain).fuse(),
+        drain,
         o!("version" => env!("CARGO_PKG_VERSION")),
     );
 
     let scope_guard = slog_scope::set_global_logger(root_logger.clone());
     slog_stdlog::init().unwrap();
 
-    (root_logger, scope_guard)
+    Logging {
+        logger: root_logger,
+        scope_guard,
+        reopen_handle,
+        level_control,
+    }
+}
+
+/// Initial levels of the drains
+#[derive(Debug, PartialEq, Eq)]
+struct LogLevels {
+    console: Level,
+    file: Level,
+}
+
+/// Each drain takes the level from its own env
+fn log_levels(get_env: impl Fn(&str) -> Option<String>) -> LogLevels {
+    let level = |name: &str| {
+        get_env(name)
+            .unwrap_or_else(|| panic!("Env {name} not found"))
+            .parse::<Level>()
+            .unwrap_or_else(|_| panic!("Env {name} must be valid slog::Level"))
+    };
+
+    LogLevels {
+        console: level(TODO_SERVICE_CONSOLE_LOG_LEVEL_ENV),
+        file: level(TODO_SERVICE_FILE_LOG_LEVEL_ENV),
+    }
+}
+
+fn get_rotation_config() -> RotationConfig {
+    RotationConfig {
+        max_size: get_optional_env::<u64>(TODO_SERVICE_LOG_MAX_SIZE_ENV).filter(|size| *size > 0),
+        period: get_optional_env::<RotationPeriod>(TODO_SERVICE_LOG_ROTATION_PERIOD_ENV).unwrap_or(RotationPeriod::Never),
+        retained_files: get_optional_env::<usize>(TODO_SERVICE_LOG_RETAINED_FILES_ENV).unwrap_or(DEFAULT_RETAINED_FILES),
+        compress: get_optional_env::<bool>(TODO_SERVICE_LOG_COMPRESS_ENV).unwrap_or(false),
+    }
+}
+
+fn get_optional_env<T: std::str::FromStr>(name: &str) -> Option<T> {
+    env::var(name)
+        .ok()
+        .map(|value| value.parse::<T>().unwrap_or_else(|_| panic!("Env {name} has invalid value \"{value}\"")))
 }
 
 use std::io::Write;
@@ -96,4 +176,65 @@ pub fn print_msg_header(
     let mut count_rd = slog_term::CountingWriter::new(&mut rd);
     write!(count_rd, "{}", record.msg())?;
     Ok(count_rd.count() != 0)
-}
\ No newline at end of file
+}
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Id of the request which is currently processed by the task, if any
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+/// Runs future with request id and scoped logger, so code without access to request (db functions, errors)
+/// can use `slog_scope::logger()` and `current_request_id()`
+pub async fn with_request_scope<F: Future>(request_id: String, logger: Logger, future: F) -> F::Output {
+    REQUEST_ID.scope(request_id, LoggerScope { logger, future: Box::pin(future) }).await
+}
+
+struct LoggerScope<F> {
+    logger: Logger,
+    future: Pin<Box<F>>,
+}
+
+impl<F: Future> Future for LoggerScope<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        slog_scope::scope(&this.logger, || this.future.as_mut().poll(cx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn levels_of(env: &[(&str, &str)]) -> LogLevels {
+        let env: HashMap<String, String> = env.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect();
+        log_levels(|name| env.get(name).cloned())
+    }
+
+    #[test]
+    fn drains_take_levels_from_own_env() {
+        let levels = levels_of(&[(TODO_SERVICE_CONSOLE_LOG_LEVEL_ENV, "warn"), (TODO_SERVICE_FILE_LOG_LEVEL_ENV, "trace")]);
+        assert_eq!(levels, LogLevels { console: Level::Warning, file: Level::Trace });
+
+        let levels = levels_of(&[(TODO_SERVICE_CONSOLE_LOG_LEVEL_ENV, "debug"), (TODO_SERVICE_FILE_LOG_LEVEL_ENV, "error")]);
+        assert_eq!(levels, LogLevels { console: Level::Debug, file: Level::Error });
+    }
+
+    #[test]
+    #[should_panic(expected = "TODO_SERVICE_FILE_LOG_LEVEL must be valid slog::Level")]
+    fn invalid_level_is_rejected() {
+        levels_of(&[(TODO_SERVICE_CONSOLE_LOG_LEVEL_ENV, "info"), (TODO_SERVICE_FILE_LOG_LEVEL_ENV, "loud")]);
+    }
+
+    #[test]
+    fn trace_is_not_compiled_out() {
+        assert_eq!(slog::__slog_static_max_level(), slog::FilterLevel::Trace);
+    }
+}
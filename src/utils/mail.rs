@@ -0,0 +1,306 @@
+use std::{
+    env,
+    path::PathBuf,
+    sync::Arc,
+    time::Duration
+};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use lettre::{
+    message::{
+        header::ContentType,
+        Mailbox
+    },
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport,
+    AsyncTransport,
+    Message,
+    Tokio1Executor
+};
+use tokio::io::AsyncWriteExt;
+
+const TODO_SERVICE_MAILER_ENV: &str = "TODO_SERVICE_MAILER";
+const TODO_SERVICE_MAIL_FROM_ENV: &str = "TODO_SERVICE_MAIL_FROM";
+const TODO_SERVICE_MAIL_FILE_ENV: &str = "TODO_SERVICE_MAIL_FILE";
+const TODO_SERVICE_SMTP_HOST_ENV: &str = "TODO_SERVICE_SMTP_HOST";
+const TODO_SERVICE_SMTP_PORT_ENV: &str = "TODO_SERVICE_SMTP_PORT";
+const TODO_SERVICE_SMTP_TLS_ENV: &str = "TODO_SERVICE_SMTP_TLS";
+const TODO_SERVICE_SMTP_USER_ENV: &str = "TODO_SERVICE_SMTP_USER";
+const TODO_SERVICE_SMTP_PASSWORD_ENV: &str = "TODO_SERVICE_SMTP_PASSWORD";
+
+const DEFAULT_MAIL_FROM: &str = "todo-list-rs <noreply@localhost>";
+const DEFAULT_MAIL_FILE: &str = "mail.log";
+const SMTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Plain text mail
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Sends mails of the service, selected by `TODO_SERVICE_MAILER`
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, mail: &Mail) -> anyhow::Result<()>;
+}
+
+/// `log` (default) and `file` mailers are for development, mails are not delivered
+pub fn from_env(logger: &slog::Logger) -> anyhow::Result<Arc<dyn Mailer>> {
+    let from = env::var(TODO_SERVICE_MAIL_FROM_ENV).unwrap_or_else(|_| DEFAULT_MAIL_FROM.to_string());
+
+    let mailer: Arc<dyn Mailer> = match env::var(TODO_SERVICE_MAILER_ENV).as_deref() {
+        Err(_) | Ok("log") => Arc::new(LogMailer::new(logger.clone())),
+        Ok("file") => {
+            let path = env::var(TODO_SERVICE_MAIL_FILE_ENV).unwrap_or_else(|_| DEFAULT_MAIL_FILE.to_string());
+            Arc::new(FileMailer::new(&from, path.into())?)
+        },
+        Ok("smtp") => Arc::new(SmtpMailer::from_env(&from)?),
+        Ok(other) => anyhow::bail!("Env {TODO_SERVICE_MAILER_ENV} must be log, file or smtp, got \"{other}\""),
+    };
+
+    Ok(mailer)
+}
+
+/// Writes mails to the service log
+pub struct LogMailer {
+    logger: slog::Logger,
+}
+
+impl LogMailer {
+    pub fn new(logger: slog::Logger) -> Self {
+        Self { logger }
+    }
+}
+
+#[async_trait]
+impl Mailer for LogMailer {
+    async fn send(&self, mail: &Mail) -> anyhow::Result<()> {
+        slog::info!(self.logger, "Mail"; "to" => &mail.to, "subject" => &mail.subject, "body" => &mail.body);
+        Ok(())
+    }
+}
+
+/// Appends mails in RFC 5322 format to the file
+pub struct FileMailer {
+    from: Mailbox,
+    path: PathBuf,
+    /// mails of concurrent requests are not interleaved
+    lock: tokio::sync::Mutex<()>,
+}
+
+impl FileMailer {
+    pub fn new(from: &str, path: PathBuf) -> anyhow::Result<Self> {
+        let from = from.parse().with_context(|| format!("Invalid sender address \"{from}\""))?;
+        Ok(Self { from, path, lock: tokio::sync::Mutex::new(()) })
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, mail: &Mail) -> anyhow::Result<()> {
+        let mut message = message(&self.from, mail)?.formatted();
+        message.extend_from_slice(b"\r\n\r\n");
+
+        let _lock = self.lock.lock().await;
+        let mut file = tokio::fs::OpenOptions::new().create(true).append(true).open(&self.path).await?;
+        file.write_all(&message).await?;
+        // tokio file writes in background, write is complete after flush
+        file.flush().await?;
+
+        Ok(())
+    }
+}
+
+/// Sends mails through SMTP relay
+pub struct SmtpMailer {
+    from: Mailbox,
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+/// Connection security of SMTP relay
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SmtpTls {
+    /// Plain connection, only for local relays
+    None,
+    StartTls,
+    /// Implicit TLS, usually port 465
+    Tls,
+}
+
+impl SmtpMailer {
+    /// Port `None` is default port of the tls mode
+    pub fn new(from: &str, host: &str, port: Option<u16>, tls: SmtpTls, credentials: Option<(String, String)>) -> anyhow::Result<Self> {
+        let from = from.parse().with_context(|| format!("Invalid sender address \"{from}\""))?;
+
+        let mut builder = match tls {
+            SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+            SmtpTls::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?,
+            SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host)?,
+        };
+
+        if let Some(port) = port {
+            builder = builder.port(port);
+        }
+
+        if let Some((user, password)) = credentials {
+            builder = builder.credentials(Credentials::new(user, password));
+        }
+
+        Ok(Self { from, transport: builder.timeout(Some(SMTP_TIMEOUT)).build() })
+    }
+
+    fn from_env(from: &str) -> anyhow::Result<Self> {
+        let host = env::var(TODO_SERVICE_SMTP_HOST_ENV)
+            .with_context(|| format!("Env {TODO_SERVICE_SMTP_HOST_ENV} not found"))?;
+
+        let port = env::var(TODO_SERVICE_SMTP_PORT_ENV).ok()
+            .map(|port| port.parse::<u16>())
+            .transpose()
+            .with_context(|| format!("Env {TODO_SERVICE_SMTP_PORT_ENV} must be valid port"))?;
+
+        let tls = match env::var(TODO_SERVICE_SMTP_TLS_ENV).as_deref() {
+            Err(_) | Ok("starttls") => SmtpTls::StartTls,
+            Ok("tls") => SmtpTls::Tls,
+            Ok("none") => SmtpTls::None,
+            Ok(other) => anyhow::bail!("Env {TODO_SERVICE_SMTP_TLS_ENV} must be none, starttls or tls, got \"{other}\""),
+        };
+
+        let credentials = match (env::var(TODO_SERVICE_SMTP_USER_ENV), env::var(TODO_SERVICE_SMTP_PASSWORD_ENV)) {
+            (Ok(user), Ok(password)) => Some((user, password)),
+            (Err(_), Err(_)) => None,
+            _ => anyhow::bail!("Envs {TODO_SERVICE_SMTP_USER_ENV} and {TODO_SERVICE_SMTP_PASSWORD_ENV} must be set together"),
+        };
+
+        Self::new(from, &host, port, tls, credentials)
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, mail: &Mail) -> anyhow::Result<()> {
+        self.transport.send(message(&self.from, mail)?).await?;
+        Ok(())
+    }
+}
+
+fn message(from: &Mailbox, mail: &Mail) -> anyhow::Result<Message> {
+    let to: Mailbox = mail.to.parse().with_context(|| format!("Invalid recipient address \"{}\"", mail.to))?;
+
+    Ok(Message::builder()
+        .from(from.clone())
+        .to(to)
+        .subject(&mail.subject)
+        .header(ContentType::TEXT_PLAIN)
+        .body(mail.body.clone())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        io::{
+            AsyncBufReadExt,
+            BufReader
+        },
+        net::TcpListener
+    };
+
+    use super::*;
+
+    fn mail() -> Mail {
+        Mail { to: "alice@example.com".to_string(), subject: "Password reset".to_string(), body: "token: abc".to_string() }
+    }
+
+    /// Accepts one session and returns its commands and mail data, recipients are answered with `rcpt_reply`
+    async fn smtp_stand_in(listener: TcpListener, rcpt_reply: &'static [u8]) -> (Vec<String>, String) {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+
+        writer.write_all(b"220 localhost ESMTP\r\n").await.unwrap();
+
+        let mut commands = Vec::new();
+        let mut data = String::new();
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).await.unwrap() == 0 {
+                break;
+            }
+            let command = line.trim_end().to_string();
+
+            let reply: &[u8] = match command.split(' ').next().unwrap().to_uppercase().as_str() {
+                "EHLO" => b"250-localhost\r\n250 8BITMIME\r\n",
+                "RCPT" => rcpt_reply,
+                "DATA" => {
+                    writer.write_all(b"354 End data with <CR><LF>.<CR><LF>\r\n").await.unwrap();
+                    loop {
+                        let mut line = String::new();
+                        reader.read_line(&mut line).await.unwrap();
+                        if line == ".\r\n" {
+                            break;
+                        }
+                        data.push_str(&line);
+                    }
+                    b"250 OK\r\n"
+                },
+                "QUIT" => {
+                    writer.write_all(b"221 Bye\r\n").await.unwrap();
+                    commands.push(command);
+                    break;
+                },
+                _ => b"250 OK\r\n",
+            };
+
+            commands.push(command);
+            writer.write_all(reply).await.unwrap();
+        }
+
+        (commands, data)
+    }
+
+    #[tokio::test]
+    async fn smtp_mailer_sends_mail() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(smtp_stand_in(listener, b"250 OK\r\n"));
+
+        let mailer = SmtpMailer::new("Todo <todo@example.com>", "127.0.0.1", Some(port), SmtpTls::None, None).unwrap();
+        mailer.send(&mail()).await.unwrap();
+
+        let (commands, data) = server.await.unwrap();
+        assert!(commands.contains(&"MAIL FROM:<todo@example.com>".to_string()), "{commands:?}");
+        assert!(commands.contains(&"RCPT TO:<alice@example.com>".to_string()), "{commands:?}");
+        assert!(data.contains("Subject: Password reset\r\n"), "{data}");
+        assert!(data.ends_with("\r\n\r\ntoken: abc\r\n"), "{data}");
+    }
+
+    #[tokio::test]
+    async fn smtp_mailer_reports_rejected_recipient() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(smtp_stand_in(listener, b"550 No such user\r\n"));
+
+        let mailer = SmtpMailer::new("todo@example.com", "127.0.0.1", Some(port), SmtpTls::None, None).unwrap();
+        assert!(mailer.send(&mail()).await.is_err());
+
+        let (_, data) = server.await.unwrap();
+        assert_eq!(data, "");
+    }
+
+    #[tokio::test]
+    async fn file_mailer_appends_mails() {
+        let path = env::temp_dir().join(format!("todo-mail-{}.log", uuid::Uuid::new_v4().simple()));
+        let mailer = FileMailer::new("todo@example.com", path.clone()).unwrap();
+
+        mailer.send(&mail()).await.unwrap();
+        mailer.send(&Mail { subject: "Second".to_string(), ..mail() }).await.unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(content.matches("To: alice@example.com\r\n").count(), 2, "{content}");
+        assert!(content.contains("Subject: Password reset\r\n") && content.contains("Subject: Second\r\n"), "{content}");
+    }
+}
@@ -1,2 +1,19 @@
 pub mod logging;
-pub mod db;
\ No newline at end of file
+pub mod log_file;
+pub mod log_level;
+pub mod telemetry;
+pub mod db;
+pub mod rate_limit;
+pub mod totp;
+pub mod jwt;
+pub mod mail;
+pub mod oidc;
+pub mod webhook;
+pub mod ical;
+pub mod dav;
+pub mod export;
+pub mod import;
+pub mod upload;
+pub mod archive;
+pub mod backup;
+pub mod events;
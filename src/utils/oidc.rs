@@ -0,0 +1,406 @@
+use std::{
+    collections::HashMap,
+    env,
+    str::FromStr,
+    sync::Mutex,
+    time::{
+        Duration,
+        Instant
+    }
+};
+
+use anyhow::Context;
+use jsonwebtoken::{
+    decode,
+    decode_header,
+    Algorithm,
+    DecodingKey,
+    Header,
+    Validation
+};
+use rand::{
+    distributions::Alphanumeric,
+    Rng
+};
+use serde::Deserialize;
+use sha2::{
+    Digest,
+    Sha256
+};
+use tokio::sync::{
+    OnceCell,
+    RwLock
+};
+use uuid::Uuid;
+
+const TODO_SERVICE_OIDC_ISSUER_ENV: &str = "TODO_SERVICE_OIDC_ISSUER";
+const TODO_SERVICE_OIDC_CLIENT_ID_ENV: &str = "TODO_SERVICE_OIDC_CLIENT_ID";
+const TODO_SERVICE_OIDC_CLIENT_SECRET_ENV: &str = "TODO_SERVICE_OIDC_CLIENT_SECRET";
+const TODO_SERVICE_OIDC_SCOPES_ENV: &str = "TODO_SERVICE_OIDC_SCOPES";
+const TODO_SERVICE_OIDC_REDIRECT_URI_ENV: &str = "TODO_SERVICE_OIDC_REDIRECT_URI";
+
+const DEFAULT_OIDC_SCOPES: &str = "openid email profile";
+/// Time for user to log in at provider
+const PENDING_LOGIN_LIFETIME: Duration = Duration::from_secs(10 * 60);
+const MAX_PENDING_LOGINS: usize = 10_000;
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+const STATE_LEN: usize = 32;
+const CODE_VERIFIER_LEN: usize = 64;
+
+/// OpenID Connect provider, login is disabled if `TODO_SERVICE_OIDC_ISSUER` is not set
+#[derive(Debug, Clone)]
+pub struct OidcConfig {
+    /// Issuer url, metadata is discovered at `<issuer>/.well-known/openid-configuration`
+    pub issuer: String,
+    pub client_id: String,
+    /// `None` for public client, which relies only on PKCE
+    pub client_secret: Option<String>,
+    /// Space separated, must contain `openid`
+    pub scopes: String,
+    /// Url of `/api/user/oidc/callback` of the service, registered at provider
+    pub redirect_uri: String,
+}
+
+impl OidcConfig {
+    pub fn from_env() -> Option<Self> {
+        let issuer = env::var(TODO_SERVICE_OIDC_ISSUER_ENV).ok()?;
+
+        let client_id = env::var(TODO_SERVICE_OIDC_CLIENT_ID_ENV)
+            .unwrap_or_else(|_| panic!("Env {TODO_SERVICE_OIDC_CLIENT_ID_ENV} not found"));
+
+        let redirect_uri = env::var(TODO_SERVICE_OIDC_REDIRECT_URI_ENV)
+            .unwrap_or_else(|_| panic!("Env {TODO_SERVICE_OIDC_REDIRECT_URI_ENV} not found"));
+
+        Some(Self {
+            issuer,
+            client_id,
+            client_secret: env::var(TODO_SERVICE_OIDC_CLIENT_SECRET_ENV).ok(),
+            scopes: env::var(TODO_SERVICE_OIDC_SCOPES_ENV).unwrap_or_else(|_| DEFAULT_OIDC_SCOPES.to_string()),
+            redirect_uri,
+        })
+    }
+}
+
+/// Authorization code flow with PKCE. Provider metadata and keys are fetched on first login,
+/// keys are fetched again when ID token has unknown `kid`.
+/// Started logins are kept in memory of the process until callback, like rate limits
+pub struct OidcProvider {
+    config: OidcConfig,
+    http: reqwest::Client,
+    metadata: OnceCell<ProviderMetadata>,
+    keys: RwLock<Vec<ProviderKey>>,
+    /// state -> login
+    pending: Mutex<HashMap<String, PendingLogin>>,
+}
+
+#[derive(Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Clone)]
+struct ProviderKey {
+    kid: Option<String>,
+    algorithm: Algorithm,
+    key: DecodingKey,
+}
+
+/// Key of provider jwks, all fields are optional, unsupported keys are skipped
+#[derive(Deserialize)]
+struct ProviderJwk {
+    kty: String,
+    kid: Option<String>,
+    alg: Option<String>,
+    #[serde(rename = "use")]
+    key_use: Option<String>,
+    n: Option<String>,
+    e: Option<String>,
+    crv: Option<String>,
+    x: Option<String>,
+    y: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ProviderJwks {
+    keys: Vec<ProviderJwk>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+#[derive(Deserialize)]
+struct IdTokenClaims {
+    sub: String,
+    nonce: Option<String>,
+    email: Option<String>,
+    #[serde(default)]
+    email_verified: bool,
+    preferred_username: Option<String>,
+}
+
+/// Login started by `authorization_url`
+pub struct PendingLogin {
+    code_verifier: String,
+    nonce: String,
+    /// User, to which identity is linked, `None` for login
+    pub link_user_id: Option<Uuid>,
+    expires_at: Instant,
+}
+
+/// Claims of verified ID token
+#[derive(Debug, Clone, PartialEq)]
+pub struct OidcIdentity {
+    pub issuer: String,
+    pub subject: String,
+    pub email: Option<String>,
+    pub email_verified: bool,
+    pub preferred_username: Option<String>,
+}
+
+impl OidcProvider {
+    pub fn new(config: OidcConfig) -> Self {
+        Self {
+            config,
+            http: reqwest::Client::builder()
+                .timeout(HTTP_TIMEOUT)
+                .build()
+                .expect("Http client must be created"),
+            metadata: OnceCell::new(),
+            keys: RwLock::new(Vec::new()),
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn from_env() -> Option<Self> {
+        OidcConfig::from_env().map(Self::new)
+    }
+
+    pub fn config(&self) -> &OidcConfig {
+        &self.config
+    }
+
+    /// Starts login, returns state and url of provider, where user is redirected
+    pub async fn authorization_url(&self, link_user_id: Option<Uuid>) -> anyhow::Result<(String, String)> {
+        let metadata = self.metadata().await?;
+
+        let state = random_string(STATE_LEN);
+        let nonce = random_string(STATE_LEN);
+        let code_verifier = random_string(CODE_VERIFIER_LEN);
+
+        let mut url = url::Url::parse(&metadata.authorization_endpoint)
+            .context("Invalid authorization endpoint of provider")?;
+
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.config.client_id)
+            .append_pair("redirect_uri", &self.config.redirect_uri)
+            .append_pair("scope", &self.config.scopes)
+            .append_pair("state", &state)
+            .append_pair("nonce", &nonce)
+            .append_pair("code_challenge", &code_challenge(&code_verifier))
+            .append_pair("code_challenge_method", "S256");
+
+        let mut pending = self.pending.lock().map_err(|e| anyhow::anyhow!("{e}"))?;
+
+        let now = Instant::now();
+        pending.retain(|_, login| login.expires_at > now);
+        anyhow::ensure!(pending.len() < MAX_PENDING_LOGINS, "Too many pending logins");
+
+        pending.insert(state.clone(), PendingLogin { code_verifier, nonce, link_user_id, expires_at: now + PENDING_LOGIN_LIFETIME });
+
+        Ok((state, url.into()))
+    }
+
+    /// Login of the state, every state is used once
+    pub fn take_pending(&self, state: &str) -> Option<PendingLogin> {
+        self.pending.lock().ok()?
+            .remove(state)
+            .filter(|login| login.expires_at > Instant::now())
+    }
+
+    /// Exchanges code with PKCE verifier of the login and verifies ID token
+    pub async fn exchange_code(&self, login: &PendingLogin, code: &str) -> anyhow::Result<OidcIdentity> {
+        let metadata = self.metadata().await?;
+
+        let mut request = self.http.post(&metadata.token_endpoint)
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", &self.config.redirect_uri),
+                ("client_id", &self.config.client_id),
+                ("code_verifier", &login.code_verifier),
+            ]);
+
+        if let Some(client_secret) = &self.config.client_secret {
+            request = request.basic_auth(&self.config.client_id, Some(client_secret));
+        }
+
+        let response = request.send().await.context("Token request failed")?;
+        let status = response.status();
+        anyhow::ensure!(status.is_success(), "Token endpoint returned {status}: {}", response.text().await.unwrap_or_default());
+
+        let tokens: TokenResponse = response.json().await.context("Invalid token response")?;
+        let claims = self.verify_id_token(&tokens.id_token, &metadata.issuer).await?;
+
+        anyhow::ensure!(claims.nonce.as_deref() == Some(login.nonce.as_str()), "ID token nonce doesn't match");
+
+        Ok(OidcIdentity {
+            issuer: metadata.issuer.clone(),
+            subject: claims.sub,
+            email: claims.email,
+            email_verified: claims.email_verified,
+            preferred_username: claims.preferred_username,
+        })
+    }
+
+    async fn verify_id_token(&self, id_token: &str, issuer: &str) -> anyhow::Result<IdTokenClaims> {
+        let header = decode_header(id_token).context("Invalid ID token")?;
+
+        let key = match self.find_key(&header).await {
+            Some(key) => key,
+            None => {
+                self.fetch_keys().await?;
+                self.find_key(&header).await
+                    .with_context(|| format!("No key of provider for ID token with kid {:?} and {:?}", header.kid, header.alg))?
+            },
+        };
+
+        let mut validation = Validation::new(key.algorithm);
+        validation.set_audience(&[&self.config.client_id]);
+        validation.set_issuer(&[issuer]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+
+        Ok(decode::<IdTokenClaims>(id_token, &key.key, &validation).context("Invalid ID token")?.claims)
+    }
+
+    /// Key with `kid` and algorithm of the token, token without `kid` is accepted if provider has one key of the algorithm
+    async fn find_key(&self, header: &Header) -> Option<ProviderKey> {
+        let keys = self.keys.read().await;
+        let mut candidates = keys.iter()
+            .filter(|key| key.algorithm == header.alg)
+            .filter(|key| header.kid.is_none() || key.kid == header.kid);
+
+        match (candidates.next(), candidates.next()) {
+            (Some(key), None) => Some(key.clone()),
+            _ => None,
+        }
+    }
+
+    async fn fetch_keys(&self) -> anyhow::Result<()> {
+        let metadata = self.metadata().await?;
+
+        let jwks: ProviderJwks = self.http.get(&metadata.jwks_uri)
+            .send().await
+            .and_then(|response| response.error_for_status())
+            .context("Provider keys request failed")?
+            .json().await
+            .context("Invalid provider keys")?;
+
+        *self.keys.write().await = jwks.keys.iter().filter_map(provider_key).collect();
+
+        Ok(())
+    }
+
+    async fn metadata(&self) -> anyhow::Result<&ProviderMetadata> {
+        self.metadata.get_or_try_init(|| async {
+            let url = format!("{}/.well-known/openid-configuration", self.config.issuer.trim_end_matches('/'));
+
+            let metadata: ProviderMetadata = self.http.get(&url)
+                .send().await
+                .and_then(|response| response.error_for_status())
+                .with_context(|| format!("Provider discovery request {url} failed"))?
+                .json().await
+                .context("Invalid provider metadata")?;
+
+            anyhow::ensure!(
+                metadata.issuer.trim_end_matches('/') == self.config.issuer.trim_end_matches('/'),
+                "Provider issuer {} doesn't match {}", metadata.issuer, self.config.issuer
+            );
+
+            Ok(metadata)
+        }).await
+    }
+}
+
+/// RSA, EC P-256/P-384 and Ed25519 signing keys, `None` for other keys
+fn provider_key(jwk: &ProviderJwk) -> Option<ProviderKey> {
+    if jwk.key_use.as_deref().is_some_and(|key_use| key_use != "sig") {
+        return None;
+    }
+
+    let (default_algorithm, key) = match (jwk.kty.as_str(), jwk.crv.as_deref()) {
+        ("RSA", _) => (Algorithm::RS256, DecodingKey::from_rsa_components(jwk.n.as_ref()?, jwk.e.as_ref()?).ok()?),
+        ("EC", Some(crv @ ("P-256" | "P-384"))) => {
+            // uncompressed point
+            let mut point = vec![4];
+            point.extend(base64url_decode(jwk.x.as_ref()?)?);
+            point.extend(base64url_decode(jwk.y.as_ref()?)?);
+
+            let algorithm = match crv {
+                "P-256" => Algorithm::ES256,
+                _ => Algorithm::ES384,
+            };
+            (algorithm, DecodingKey::from_ec_der(&point))
+        },
+        ("OKP", Some("Ed25519")) => (Algorithm::EdDSA, DecodingKey::from_ed_der(&base64url_decode(jwk.x.as_ref()?)?)),
+        _ => return None,
+    };
+
+    let algorithm = match &jwk.alg {
+        Some(alg) => Algorithm::from_str(alg).ok()?,
+        None => default_algorithm,
+    };
+
+    Some(ProviderKey { kid: jwk.kid.clone(), algorithm, key })
+}
+
+fn base64url_decode(value: &str) -> Option<Vec<u8>> {
+    base64::decode_config(value, base64::URL_SAFE_NO_PAD).ok()
+}
+
+/// S256 challenge of PKCE verifier
+fn code_challenge(code_verifier: &str) -> String {
+    base64::encode_config(Sha256::digest(code_verifier.as_bytes()), base64::URL_SAFE_NO_PAD)
+}
+
+fn random_string(len: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(len)
+        .map(char::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pkce_challenge() {
+        // RFC 7636, appendix B
+        assert_eq!(code_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"), "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM");
+    }
+
+    #[test]
+    fn provider_keys() {
+        let jwks: ProviderJwks = serde_json::from_value(serde_json::json!({ "keys": [
+            { "kty": "RSA", "kid": "rsa", "n": "sXchDaQebHnPiGvyDOAT4saGEUetSyo9MKLOoWFsueri23bOdgWp4Dy1WlUzewbgBHod5pcM9H95GQRV3JDXboIRROSBigeC5yjU1hGzHHyXss8UDprecbAYxknTcQkhslANGRUZmdTOQ5qTRsLAt6BTYuyvVRdhS8exSZEy_c4gs_7svlJJQ4H9_NxsiIoLwAEk7-Q3UXERGYw_75IDrGA84-lA_-Ct4eTlXHBIY2EaV7t7LjJaynVJCpkv4LKjTTAumiGUIuQhrNhZLuF_RJLqHpM2kgWFLU7-VTdL1VbC2tejvcI2BlMkEpk1BzBZI0KQB0GaDWFLN-aEAw3vRw", "e": "AQAB" },
+            { "kty": "EC", "kid": "ec", "crv": "P-256", "x": "f83OJ3D2xF1Bg8vub9tLe1gHMzV76e8Tus9uPHvRVEU", "y": "x_FEzRu9m36HLN_tue659LNpXW6pCyStikYjKIWI5a0" },
+            { "kty": "OKP", "kid": "ed", "crv": "Ed25519", "x": "11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo" },
+            { "kty": "RSA", "kid": "enc", "use": "enc", "n": "AQAB", "e": "AQAB" },
+            { "kty": "oct", "kid": "secret", "k": "c2VjcmV0" },
+        ] })).unwrap();
+
+        let keys: Vec<_> = jwks.keys.iter().filter_map(provider_key).map(|key| (key.kid.unwrap(), key.algorithm)).collect();
+        assert_eq!(keys, vec![
+            ("rsa".to_string(), Algorithm::RS256),
+            ("ec".to_string(), Algorithm::ES256),
+            ("ed".to_string(), Algorithm::EdDSA),
+        ]);
+    }
+}
@@ -0,0 +1,347 @@
+use std::{
+    collections::HashMap,
+    env,
+    str::FromStr,
+    sync::Mutex,
+    time::{
+        Duration,
+        Instant
+    }
+};
+
+use actix_web::HttpRequest;
+
+const TODO_SERVICE_LOGIN_IP_LIMIT_ENV: &str = "TODO_SERVICE_LOGIN_IP_LIMIT";
+const TODO_SERVICE_LOGIN_ACCOUNT_LIMIT_ENV: &str = "TODO_SERVICE_LOGIN_ACCOUNT_LIMIT";
+const TODO_SERVICE_LOGIN_IP_LOCKOUT_THRESHOLD_ENV: &str = "TODO_SERVICE_LOGIN_IP_LOCKOUT_THRESHOLD";
+const TODO_SERVICE_LOGIN_ACCOUNT_LOCKOUT_THRESHOLD_ENV: &str = "TODO_SERVICE_LOGIN_ACCOUNT_LOCKOUT_THRESHOLD";
+const TODO_SERVICE_LOGIN_LOCKOUT_BASE_ENV: &str = "TODO_SERVICE_LOGIN_LOCKOUT_BASE";
+const TODO_SERVICE_LOGIN_LOCKOUT_MAX_ENV: &str = "TODO_SERVICE_LOGIN_LOCKOUT_MAX";
+const TODO_SERVICE_TRUST_PROXY_ENV: &str = "TODO_SERVICE_TRUST_PROXY";
+const TODO_SERVICE_API_RATE_LIMIT_ENV: &str = "TODO_SERVICE_API_RATE_LIMIT";
+
+/// Limits are set as requests per this period
+const LIMIT_PERIOD: Duration = Duration::from_secs(60);
+/// Login failures are forgotten if there were no new failures for this time
+const FAILURES_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Key of requests without peer address (test requests, unix socket)
+const UNKNOWN_IP: &str = "unknown";
+
+/// Rate limits shared by all workers, state is kept in memory of the process
+pub struct RateLimits {
+    pub login: LoginThrottle,
+    /// Requests per user, `None` if api rate limit is disabled
+    pub api: Option<RateLimiter>,
+}
+
+impl RateLimits {
+    pub fn new(login: LoginThrottleConfig, api_limit: Option<u32>) -> Self {
+        Self {
+            login: LoginThrottle::new(login),
+            api: api_limit.map(|limit| RateLimiter::new(limit, LIMIT_PERIOD)),
+        }
+    }
+
+    pub fn from_env() -> Self {
+        let api_limit = env_value::<u32>(TODO_SERVICE_API_RATE_LIMIT_ENV).filter(|limit| *limit > 0);
+        Self::new(LoginThrottleConfig::from_env(), api_limit)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LoginThrottleConfig {
+    /// Login attempts per minute from one ip
+    pub ip_limit: u32,
+    /// Login attempts per minute for one login name
+    pub account_limit: u32,
+    /// Failures from one ip before lockout
+    pub ip_lockout_threshold: u32,
+    /// Failures for one login name before lockout
+    pub account_lockout_threshold: u32,
+    /// Lockout after threshold is reached, doubled with every next failure
+    pub lockout_base: Duration,
+    pub lockout_max: Duration,
+    /// Take client ip from `Forwarded` / `X-Forwarded-For`, service must be behind reverse proxy
+    pub trust_proxy: bool,
+}
+
+impl Default for LoginThrottleConfig {
+    fn default() -> Self {
+        Self {
+            ip_limit: 30,
+            account_limit: 10,
+            ip_lockout_threshold: 20,
+            account_lockout_threshold: 5,
+            lockout_base: Duration::from_secs(30),
+            lockout_max: Duration::from_secs(60 * 60),
+            trust_proxy: false,
+        }
+    }
+}
+
+impl LoginThrottleConfig {
+    pub fn from_env() -> Self {
+        let default = Self::default();
+
+        Self {
+            ip_limit: env_value(TODO_SERVICE_LOGIN_IP_LIMIT_ENV).unwrap_or(default.ip_limit),
+            account_limit: env_value(TODO_SERVICE_LOGIN_ACCOUNT_LIMIT_ENV).unwrap_or(default.account_limit),
+            ip_lockout_threshold: env_value(TODO_SERVICE_LOGIN_IP_LOCKOUT_THRESHOLD_ENV).unwrap_or(default.ip_lockout_threshold),
+            account_lockout_threshold: env_value(TODO_SERVICE_LOGIN_ACCOUNT_LOCKOUT_THRESHOLD_ENV).unwrap_or(default.account_lockout_threshold),
+            lockout_base: env_value(TODO_SERVICE_LOGIN_LOCKOUT_BASE_ENV).map(Duration::from_secs).unwrap_or(default.lockout_base),
+            lockout_max: env_value(TODO_SERVICE_LOGIN_LOCKOUT_MAX_ENV).map(Duration::from_secs).unwrap_or(default.lockout_max),
+            trust_proxy: env_value(TODO_SERVICE_TRUST_PROXY_ENV).unwrap_or(default.trust_proxy),
+        }
+    }
+}
+
+fn env_value<T: FromStr>(name: &str) -> Option<T> {
+    env::var(name).ok()
+        .map(|value| value.parse::<T>().unwrap_or_else(|_| panic!("Env {name} has invalid value \"{value}\"")))
+}
+
+/// Brute-force protection of login. Attempts are limited per ip and per login name,
+/// repeated failures lock ip or login name with growing lockout time.
+/// Login names are tracked whether user exists or not, so responses don't reveal it
+pub struct LoginThrottle {
+    ip_limiter: RateLimiter,
+    account_limiter: RateLimiter,
+    ip_lockout: Lockout,
+    account_lockout: Lockout,
+    trust_proxy: bool,
+}
+
+impl LoginThrottle {
+    pub fn new(config: LoginThrottleConfig) -> Self {
+        Self {
+            ip_limiter: RateLimiter::new(config.ip_limit, LIMIT_PERIOD),
+            account_limiter: RateLimiter::new(config.account_limit, LIMIT_PERIOD),
+            ip_lockout: Lockout::new(config.ip_lockout_threshold, config.lockout_base, config.lockout_max),
+            account_lockout: Lockout::new(config.account_lockout_threshold, config.lockout_base, config.lockout_max),
+            trust_proxy: config.trust_proxy,
+        }
+    }
+
+    pub fn client_ip(&self, req: &HttpRequest) -> String {
+        let address = match self.trust_proxy {
+            true => req.connection_info().realip_remote_addr().map(|address| address.to_string()),
+            false => req.peer_addr().map(|address| address.to_string()),
+        };
+
+        match address {
+            // address can be with port
+            Some(address) => address.parse::<std::net::SocketAddr>()
+                .map(|address| address.ip().to_string())
+                .unwrap_or(address),
+            None => UNKNOWN_IP.to_string(),
+        }
+    }
+
+    /// Called before password check, `Err` with time until next attempt is allowed
+    pub fn check(&self, ip: &str, login: &str) -> Result<(), Duration> {
+        let now = Instant::now();
+
+        self.ip_lockout.check(ip, now)?;
+        self.account_lockout.check(login, now)?;
+        self.ip_limiter.check_at(ip, now)?;
+        self.account_limiter.check_at(login, now)
+    }
+
+    /// Check of clients sending password with every request (CalDAV Basic auth):
+    /// only failures lock ip or login name, successful requests don't use up attempts
+    pub fn check_lockout(&self, ip: &str, login: &str) -> Result<(), Duration> {
+        let now = Instant::now();
+
+        self.ip_lockout.check(ip, now)?;
+        self.account_lockout.check(login, now)
+    }
+
+    pub fn failed(&self, ip: &str, login: &str) {
+        let now = Instant::now();
+        self.ip_lockout.failed(ip, now);
+        self.account_lockout.failed(login, now);
+    }
+
+    /// Failures of ip are kept, otherwise own account could be used to reset them
+    pub fn succeeded(&self, login: &str) {
+        self.account_lockout.reset(login);
+    }
+}
+
+/// Token bucket per key: `limit` requests, refilled evenly during `period`
+pub struct RateLimiter {
+    limit: u32,
+    period: Duration,
+    state: Mutex<LimiterState>,
+}
+
+struct LimiterState {
+    buckets: HashMap<String, Bucket>,
+    cleaned_at: Instant,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(limit: u32, period: Duration) -> Self {
+        Self {
+            limit,
+            period,
+            state: Mutex::new(LimiterState { buckets: HashMap::new(), cleaned_at: Instant::now() }),
+        }
+    }
+
+    /// Takes one request of the key, `Err` with time until next request is allowed
+    pub fn check(&self, key: &str) -> Result<(), Duration> {
+        self.check_at(key, Instant::now())
+    }
+
+    fn check_at(&self, key: &str, now: Instant) -> Result<(), Duration> {
+        let mut state = self.state.lock().unwrap();
+        let limit = self.limit as f64;
+        let refill_time = self.period.as_secs_f64() / limit;
+
+        // full buckets are the same as missing ones
+        if now.saturating_duration_since(state.cleaned_at) >= self.period {
+            state.buckets.retain(|_, bucket| now.saturating_duration_since(bucket.updated_at) < self.period);
+            state.cleaned_at = now;
+        }
+
+        let bucket = state.buckets.entry(key.to_string()).or_insert(Bucket { tokens: limit, updated_at: now });
+
+        let elapsed = now.saturating_duration_since(bucket.updated_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed / refill_time).min(limit);
+        bucket.updated_at = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) * refill_time))
+        }
+    }
+}
+
+/// Progressive lockout on failures: key is locked for `base` after `threshold` failures,
+/// every next failure doubles the time up to `max`
+struct Lockout {
+    threshold: u32,
+    base: Duration,
+    max: Duration,
+    failures: Mutex<HashMap<String, Failures>>,
+}
+
+struct Failures {
+    count: u32,
+    failed_at: Instant,
+    locked_until: Option<Instant>,
+}
+
+impl Lockout {
+    fn new(threshold: u32, base: Duration, max: Duration) -> Self {
+        Self { threshold, base, max, failures: Mutex::new(HashMap::new()) }
+    }
+
+    fn check(&self, key: &str, now: Instant) -> Result<(), Duration> {
+        let failures = self.failures.lock().unwrap();
+
+        match failures.get(key).and_then(|failures| failures.locked_until) {
+            Some(locked_until) if locked_until > now => Err(locked_until - now),
+            _ => Ok(()),
+        }
+    }
+
+    fn failed(&self, key: &str, now: Instant) {
+        let mut failures = self.failures.lock().unwrap();
+        failures.retain(|_, failures| now.saturating_duration_since(failures.failed_at) < FAILURES_TTL);
+
+        let entry = failures.entry(key.to_string()).or_insert(Failures { count: 0, failed_at: now, locked_until: None });
+        entry.count += 1;
+        entry.failed_at = now;
+
+        if entry.count >= self.threshold {
+            let exponent = (entry.count - self.threshold).min(31);
+            let lockout = self.base.checked_mul(1 << exponent).unwrap_or(self.max).min(self.max);
+            entry.locked_until = Some(now + lockout);
+        }
+    }
+
+    fn reset(&self, key: &str) {
+        self.failures.lock().unwrap().remove(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn retry_after(result: Result<(), Duration>) -> u64 {
+        result.unwrap_err().as_secs_f64().round() as u64
+    }
+
+    #[test]
+    fn limiter_refills_evenly() {
+        let limiter = RateLimiter::new(2, Duration::from_secs(60));
+        let now = Instant::now();
+
+        assert!(limiter.check_at("a", now).is_ok());
+        assert!(limiter.check_at("a", now).is_ok());
+        assert_eq!(retry_after(limiter.check_at("a", now)), 30);
+        assert!(limiter.check_at("b", now).is_ok());
+
+        assert_eq!(retry_after(limiter.check_at("a", now + Duration::from_secs(20))), 10);
+        assert!(limiter.check_at("a", now + Duration::from_secs(30)).is_ok());
+        assert!(limiter.check_at("a", now + Duration::from_secs(30)).is_err());
+    }
+
+    #[test]
+    fn limiter_forgets_full_buckets() {
+        let limiter = RateLimiter::new(1, Duration::from_secs(60));
+        let now = Instant::now();
+
+        limiter.check_at("a", now).unwrap();
+        limiter.check_at("b", now + Duration::from_secs(120)).unwrap();
+
+        assert_eq!(limiter.state.lock().unwrap().buckets.len(), 1);
+    }
+
+    #[test]
+    fn lockout_grows_with_failures() {
+        let lockout = Lockout::new(2, Duration::from_secs(10), Duration::from_secs(25));
+        let now = Instant::now();
+
+        lockout.failed("a", now);
+        assert!(lockout.check("a", now).is_ok());
+
+        lockout.failed("a", now);
+        assert_eq!(lockout.check("a", now), Err(Duration::from_secs(10)));
+        assert!(lockout.check("b", now).is_ok());
+
+        let later = now + Duration::from_secs(10);
+        assert!(lockout.check("a", later).is_ok());
+
+        lockout.failed("a", later);
+        assert_eq!(lockout.check("a", later), Err(Duration::from_secs(20)));
+
+        lockout.failed("a", later);
+        assert_eq!(lockout.check("a", later), Err(Duration::from_secs(25)));
+
+        lockout.reset("a");
+        assert!(lockout.check("a", later).is_ok());
+    }
+
+    #[test]
+    fn lockout_failures_expire() {
+        let lockout = Lockout::new(2, Duration::from_secs(10), Duration::from_secs(25));
+        let now = Instant::now();
+
+        lockout.failed("a", now);
+        lockout.failed("a", now + FAILURES_TTL);
+
+        assert!(lockout.check("a", now + FAILURES_TTL).is_ok());
+    }
+}
@@ -0,0 +1,285 @@
+use std::{
+    env,
+    fmt,
+    fs::OpenOptions,
+    future::Future,
+    io::Write,
+    sync::Mutex,
+    time::UNIX_EPOCH
+};
+
+use opentelemetry::{
+    global::{
+        self,
+        BoxedTracer
+    },
+    propagation::Extractor,
+    trace::{
+        FutureExt,
+        SpanKind,
+        Status,
+        TraceContextExt,
+        Tracer
+    },
+    Context,
+    KeyValue
+};
+use opentelemetry_sdk::{
+    error::{
+        OTelSdkError,
+        OTelSdkResult
+    },
+    propagation::TraceContextPropagator,
+    trace::{
+        SdkTracerProvider,
+        SpanData,
+        SpanExporter
+    },
+    Resource
+};
+use opentelemetry_otlp::WithExportConfig;
+use serde_json::json;
+use slog::Logger;
+
+use crate::models::ServiceError;
+
+const TODO_SERVICE_TRACING_EXPORTER_ENV: &str = "TODO_SERVICE_TRACING_EXPORTER";
+const TODO_SERVICE_TRACING_OTLP_ENDPOINT_ENV: &str = "TODO_SERVICE_TRACING_OTLP_ENDPOINT";
+const TODO_SERVICE_TRACING_FILE_ENV: &str = "TODO_SERVICE_TRACING_FILE";
+
+const DEFAULT_OTLP_ENDPOINT: &str = "http://localhost:4318/v1/traces";
+const TRACER_NAME: &str = env!("CARGO_PKG_NAME");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TracingExporter {
+    None,
+    Otlp,
+    Stdout,
+    File,
+}
+
+impl std::str::FromStr for TracingExporter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "none" => Ok(TracingExporter::None),
+            "otlp" => Ok(TracingExporter::Otlp),
+            "stdout" => Ok(TracingExporter::Stdout),
+            "file" => Ok(TracingExporter::File),
+            _ => Err(format!("Unknown tracing exporter \"{s}\", expected none, otlp, stdout or file")),
+        }
+    }
+}
+
+/// Sets W3C trace context propagator and global tracer provider.
+/// Returns `None` if tracing is disabled, otherwise provider must be shut down before exit to flush spans
+pub fn init_tracing(logger: &Logger) -> anyhow::Result<Option<SdkTracerProvider>> {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    let exporter = env::var(TODO_SERVICE_TRACING_EXPORTER_ENV)
+        .ok()
+        .map(|value| value.parse::<TracingExporter>().map_err(anyhow::Error::msg))
+        .transpose()?
+        .unwrap_or(TracingExporter::None);
+
+    slog::info!(logger, "Tracing exporter: {exporter:?}");
+
+    let builder = SdkTracerProvider::builder()
+        .with_resource(Resource::builder().with_service_name(TRACER_NAME).build());
+
+    let provider = match exporter {
+        TracingExporter::None => return Ok(None),
+        TracingExporter::Otlp => {
+            let endpoint = env::var(TODO_SERVICE_TRACING_OTLP_ENDPOINT_ENV)
+                .unwrap_or_else(|_| DEFAULT_OTLP_ENDPOINT.to_string());
+
+            slog::info!(logger, "{TODO_SERVICE_TRACING_OTLP_ENDPOINT_ENV}:[{endpoint}]");
+
+            let exporter = opentelemetry_otlp::SpanExporter::builder()
+                .with_http()
+                .with_endpoint(endpoint)
+                .build()?;
+
+            builder.with_batch_exporter(exporter).build()
+        },
+        TracingExporter::Stdout => {
+            builder.with_simple_exporter(JsonLinesExporter::new(std::io::stdout())).build()
+        },
+        TracingExporter::File => {
+            let path = env::var(TODO_SERVICE_TRACING_FILE_ENV)
+                .unwrap_or_else(|_| panic!("Env {TODO_SERVICE_TRACING_FILE_ENV} not found"));
+
+            let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+            builder.with_simple_exporter(JsonLinesExporter::new(file)).build()
+        },
+    };
+
+    global::set_tracer_provider(provider.clone());
+
+    Ok(Some(provider))
+}
+
+pub fn tracer() -> BoxedTracer {
+    global::tracer(TRACER_NAME)
+}
+
+/// Runs future in child span of the current context, span gets error status if future returns error
+pub async fn traced<T, F>(name: &'static str, future: F) -> Result<T, ServiceError>
+where
+    F: Future<Output = Result<T, ServiceError>>
+{
+    let cx = Context::current_with_span(tracer().start(name));
+
+    let result = future.with_context(cx.clone()).await;
+
+    if let Err(e) = &result {
+        cx.span().set_status(Status::error(e.to_string()));
+    }
+
+    result
+}
+
+/// Span for sqlx query future
+pub trait TracedQuery: Future + Sized {
+    /// `operation` and `table` form span name, e.g. "SELECT tasks"
+    fn traced_query(self, operation: &'static str, table: &'static str) -> opentelemetry::trace::WithContext<Self> {
+        self.traced_query_on("postgresql", operation, table)
+    }
+
+    /// Same as `traced_query` for database other than postgres, `db_system` is OpenTelemetry name, e.g. "sqlite"
+    fn traced_query_on(self, db_system: &'static str, operation: &'static str, table: &'static str) -> opentelemetry::trace::WithContext<Self> {
+        let tracer = tracer();
+        let span = tracer
+            .span_builder(format!("{operation} {table}"))
+            .with_kind(SpanKind::Client)
+            .with_attributes([
+                KeyValue::new("db.system.name", db_system),
+                KeyValue::new("db.operation.name", operation),
+                KeyValue::new("db.collection.name", table),
+            ])
+            .start(&tracer);
+
+        self.with_context(Context::current_with_span(span))
+    }
+}
+
+impl<F: Future> TracedQuery for F {}
+
+/// Reads W3C `traceparent` from actix headers
+pub struct HeaderExtractor<'a>(pub &'a actix_web::http::header::HeaderMap);
+
+impl<'a> Extractor for HeaderExtractor<'a> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|key| key.as_str()).collect()
+    }
+}
+
+/// Writes every span as one json line, used for local runs and tests
+pub struct JsonLinesExporter {
+    writer: Mutex<Box<dyn Write + Send>>,
+}
+
+impl JsonLinesExporter {
+    pub fn new(writer: impl Write + Send + 'static) -> Self {
+        Self { writer: Mutex::new(Box::new(writer)) }
+    }
+}
+
+impl fmt::Debug for JsonLinesExporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("JsonLinesExporter")
+    }
+}
+
+impl SpanExporter for JsonLinesExporter {
+    async fn export(&self, batch: Vec<SpanData>) -> OTelSdkResult {
+        let mut writer = self.writer.lock()
+            .map_err(|e| OTelSdkError::InternalFailure(e.to_string()))?;
+
+        for span in batch {
+            let attributes: serde_json::Map<String, serde_json::Value> = span.attributes.iter()
+                .map(|kv| (kv.key.to_string(), json!(kv.value.to_string())))
+                .collect();
+
+            let status = match &span.status {
+                Status::Unset => json!("unset"),
+                Status::Ok => json!("ok"),
+                Status::Error { description } => json!({ "error": description }),
+            };
+
+            let line = json!({
+                "name": span.name,
+                "kind": format!("{:?}", span.span_kind),
+                "trace_id": span.span_context.trace_id().to_string(),
+                "span_id": span.span_context.span_id().to_string(),
+                "parent_span_id": span.parent_span_id.to_string(),
+                "start_unix_nano": span.start_time.duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos() as u64,
+                "end_unix_nano": span.end_time.duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos() as u64,
+                "status": status,
+                "attributes": attributes,
+            });
+
+            writeln!(writer, "{line}").map_err(|e| OTelSdkError::InternalFailure(e.to_string()))?;
+        }
+
+        writer.flush().map_err(|e| OTelSdkError::InternalFailure(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io,
+        sync::{
+            Arc,
+            Mutex
+        }
+    };
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn query_span_is_child_of_function_span() {
+        let buffer = Buffer::default();
+        let provider = SdkTracerProvider::builder()
+            .with_simple_exporter(JsonLinesExporter::new(buffer.clone()))
+            .build();
+        global::set_tracer_provider(provider.clone());
+
+        let result = traced("db.select_tasks", async {
+            async { Ok::<_, ServiceError>(1) }.traced_query("SELECT", "tasks").await
+        }).await;
+        assert_eq!(result.unwrap(), 1);
+
+        provider.shutdown().unwrap();
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let spans: Vec<serde_json::Value> = output.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+
+        let query = spans.iter().find(|span| span["name"] == "SELECT tasks").unwrap();
+        let function = spans.iter().find(|span| span["name"] == "db.select_tasks").unwrap();
+
+        assert_eq!(query["parent_span_id"], function["span_id"]);
+        assert_eq!(query["trace_id"], function["trace_id"]);
+        assert_eq!(query["attributes"]["db.collection.name"], "tasks");
+    }
+}
@@ -0,0 +1,121 @@
+//! RFC 6238 TOTP: HMAC-SHA1, 30 seconds step, 6 digits - defaults of authenticator apps
+
+use hmac::{
+    Hmac,
+    Mac
+};
+use rand::RngCore;
+use sha1::Sha1;
+
+const STEP_SECONDS: u64 = 30;
+const DIGITS: u32 = 6;
+const SECRET_LEN: usize = 20;
+/// Codes of neighbour steps are accepted because of clock drift
+const ALLOWED_DRIFT_STEPS: u64 = 1;
+
+pub fn generate_secret() -> Vec<u8> {
+    let mut secret = vec![0; SECRET_LEN];
+    rand::thread_rng().fill_bytes(&mut secret);
+    secret
+}
+
+pub fn encode_secret(secret: &[u8]) -> String {
+    base32::encode(base32::Alphabet::RFC4648 { padding: false }, secret)
+}
+
+pub fn decode_secret(secret: &str) -> Option<Vec<u8>> {
+    base32::decode(base32::Alphabet::RFC4648 { padding: false }, secret)
+}
+
+/// Key uri for QR code of authenticator app
+pub fn otpauth_uri(issuer: &str, account: &str, secret: &[u8]) -> String {
+    let issuer = urlencoding(issuer);
+    let account = urlencoding(account);
+    let secret = encode_secret(secret);
+
+    format!("otpauth://totp/{issuer}:{account}?secret={secret}&issuer={issuer}&algorithm=SHA1&digits={DIGITS}&period={STEP_SECONDS}")
+}
+
+pub fn step(unix_time: u64) -> u64 {
+    unix_time / STEP_SECONDS
+}
+
+pub fn code(secret: &[u8], step: u64) -> String {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("HMAC accepts key of any length");
+    mac.update(&step.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+
+    // dynamic truncation
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let value = u32::from_be_bytes([hash[offset] & 0x7f, hash[offset + 1], hash[offset + 2], hash[offset + 3]]);
+
+    format!("{:0width$}", value % 10u32.pow(DIGITS), width = DIGITS as usize)
+}
+
+/// Step of the matched code, caller must reject steps which were already used
+pub fn verify(secret: &[u8], code: &str, unix_time: u64) -> Option<u64> {
+    let code = code.trim();
+    if code.len() != DIGITS as usize || !code.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let current = step(unix_time);
+
+    (current.saturating_sub(ALLOWED_DRIFT_STEPS)..=current + ALLOWED_DRIFT_STEPS)
+        .find(|step| constant_time_eq(self::code(secret, *step).as_bytes(), code.as_bytes()))
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Percent encoding of uri label and parameter
+fn urlencoding(value: &str) -> String {
+    value.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// RFC 6238 appendix B, SHA1 secret
+    const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn rfc_test_vectors() {
+        // 8 digit codes of the RFC truncated to 6 digits
+        for (time, expected) in [(59, "287082"), (1111111109, "081804"), (1234567890, "005924"), (2000000000, "279037")] {
+            assert_eq!(code(RFC_SECRET, step(time)), expected, "{time}");
+        }
+    }
+
+    #[test]
+    fn verify_accepts_neighbour_steps() {
+        let time = 1111111109;
+
+        assert_eq!(verify(RFC_SECRET, "081804", time), Some(step(time)));
+        assert_eq!(verify(RFC_SECRET, "081804", time + 30), Some(step(time)));
+        assert_eq!(verify(RFC_SECRET, "081804", time + 60), None);
+        assert_eq!(verify(RFC_SECRET, "81804", time), None);
+        assert_eq!(verify(RFC_SECRET, "08180a", time), None);
+    }
+
+    #[test]
+    fn secret_round_trip() {
+        let secret = generate_secret();
+        assert_eq!(decode_secret(&encode_secret(&secret)), Some(secret));
+    }
+
+    #[test]
+    fn uri_is_encoded() {
+        assert_eq!(
+            otpauth_uri("todo list", "bob", RFC_SECRET),
+            "otpauth://totp/todo%20list:bob?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&issuer=todo%20list&algorithm=SHA1&digits=6&period=30"
+        );
+    }
+}
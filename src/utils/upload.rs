@@ -0,0 +1,73 @@
+//! Fields of `multipart/form-data` uploads, which are read in memory
+
+use std::collections::HashMap;
+
+use actix_multipart::{
+    Field,
+    Multipart,
+    MultipartError
+};
+use futures::TryStreamExt;
+
+use crate::models::{
+    ServiceError,
+    StatusCode
+};
+
+/// Fields of the form with the given names. Other fields are skipped without keeping their data,
+/// but all fields together are limited by `max_size`
+pub async fn read_form(mut upload: Multipart, names: &[&str], max_size: usize) -> Result<HashMap<String, Vec<u8>>, ServiceError> {
+    let mut fields = HashMap::new();
+    let mut size = 0;
+
+    while let Some(mut field) = upload.try_next().await.map_err(invalid_upload)? {
+        let name = field.name().filter(|name| names.contains(name)).map(str::to_string);
+
+        match name {
+            Some(name) => {
+                let data = read_field(field, &mut size, max_size).await?;
+                fields.insert(name, data);
+            },
+            None => while let Some(chunk) = field.try_next().await.map_err(invalid_upload)? {
+                add_size(&mut size, chunk.len(), max_size)?;
+            },
+        }
+    }
+
+    Ok(fields)
+}
+
+/// Field, which the form must have
+pub fn required_field(fields: &mut HashMap<String, Vec<u8>>, name: &str) -> Result<Vec<u8>, ServiceError> {
+    fields.remove(name)
+        .ok_or(ServiceError { status_code: StatusCode::BadRequest, detail: Some(format!("Field \"{name}\" not found")) })
+}
+
+pub fn text(data: Vec<u8>) -> Result<String, ServiceError> {
+    String::from_utf8(data)
+        .map_err(|_| ServiceError { status_code: StatusCode::BadRequest, detail: Some("File must be UTF-8 text".to_string()) })
+}
+
+async fn read_field(mut field: Field, size: &mut usize, max_size: usize) -> Result<Vec<u8>, ServiceError> {
+    let mut data = Vec::new();
+
+    while let Some(chunk) = field.try_next().await.map_err(invalid_upload)? {
+        add_size(size, chunk.len(), max_size)?;
+        data.extend_from_slice(&chunk);
+    }
+
+    Ok(data)
+}
+
+fn add_size(size: &mut usize, chunk_size: usize, max_size: usize) -> Result<(), ServiceError> {
+    *size += chunk_size;
+
+    match *size > max_size {
+        true => Err(ServiceError { status_code: StatusCode::PayloadTooLarge, detail: Some(format!("Upload is larger than {max_size} bytes")) }),
+        false => Ok(()),
+    }
+}
+
+fn invalid_upload(error: MultipartError) -> ServiceError {
+    ServiceError { status_code: StatusCode::BadRequest, detail: Some(format!("Invalid multipart upload: {error}")) }
+}
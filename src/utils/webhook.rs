@@ -0,0 +1,418 @@
+//! Delivery of webhook outbox. Handlers add deliveries to storage, dispatcher claims due deliveries,
+//! sends them and records attempts. Failed deliveries are retried with exponential backoff
+
+use std::{
+    env,
+    net::{
+        IpAddr,
+        Ipv4Addr,
+        SocketAddr
+    },
+    str::FromStr,
+    sync::Arc,
+    time::{
+        Duration,
+        Instant
+    }
+};
+
+use chrono::Utc;
+use hmac::{
+    Hmac,
+    Mac
+};
+use sha2::Sha256;
+
+use crate::{
+    db::WebhookRepository,
+    models::{
+        DeliveryAttempt,
+        PendingDelivery
+    }
+};
+
+const TODO_SERVICE_WEBHOOK_MAX_ATTEMPTS_ENV: &str = "TODO_SERVICE_WEBHOOK_MAX_ATTEMPTS";
+const TODO_SERVICE_WEBHOOK_RETRY_BASE_ENV: &str = "TODO_SERVICE_WEBHOOK_RETRY_BASE";
+const TODO_SERVICE_WEBHOOK_RETRY_MAX_ENV: &str = "TODO_SERVICE_WEBHOOK_RETRY_MAX";
+const TODO_SERVICE_WEBHOOK_DISABLE_AFTER_ENV: &str = "TODO_SERVICE_WEBHOOK_DISABLE_AFTER";
+const TODO_SERVICE_WEBHOOK_TIMEOUT_ENV: &str = "TODO_SERVICE_WEBHOOK_TIMEOUT";
+const TODO_SERVICE_WEBHOOK_LOG_RETENTION_DAYS_ENV: &str = "TODO_SERVICE_WEBHOOK_LOG_RETENTION_DAYS";
+const TODO_SERVICE_WEBHOOK_ALLOW_PRIVATE_TARGETS_ENV: &str = "TODO_SERVICE_WEBHOOK_ALLOW_PRIVATE_TARGETS";
+
+pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-Webhook-Timestamp";
+pub const EVENT_HEADER: &str = "X-Webhook-Event";
+pub const DELIVERY_HEADER: &str = "X-Webhook-Delivery";
+
+const USER_AGENT: &str = concat!("todo-list-rs-webhooks/", env!("CARGO_PKG_VERSION"));
+/// Claimed delivery is retried after this time, if its attempt was not recorded, e.g. service was stopped
+const CLAIM_LEASE_MARGIN: Duration = Duration::from_secs(60);
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const MAX_ERROR_LEN: usize = 512;
+
+pub const PRIVATE_TARGET: &str = "Webhook url must not point to loopback, private, link-local or unspecified address";
+
+#[derive(Debug, Clone)]
+pub struct WebhookConfig {
+    /// Attempts of one delivery, after them delivery fails
+    pub max_attempts: i32,
+    /// Delay before the first retry, doubled with every next retry
+    pub retry_base: Duration,
+    pub retry_max: Duration,
+    /// Failed attempts of webhook in a row, after which webhook is disabled
+    pub disable_after: i32,
+    pub timeout: Duration,
+    /// Succeeded and failed deliveries are kept in the delivery log for this time
+    pub log_retention: Duration,
+    pub poll_interval: Duration,
+    /// Deliveries claimed and sent concurrently
+    pub batch_size: i64,
+    /// Receivers on loopback, private and link-local addresses are allowed, for tests and local development only
+    pub allow_private_targets: bool,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 8,
+            retry_base: Duration::from_secs(30),
+            retry_max: Duration::from_secs(60 * 60),
+            disable_after: 20,
+            timeout: Duration::from_secs(10),
+            log_retention: Duration::from_secs(30 * 24 * 60 * 60),
+            poll_interval: Duration::from_secs(1),
+            batch_size: 50,
+            allow_private_targets: false,
+        }
+    }
+}
+
+impl WebhookConfig {
+    pub fn from_env() -> Self {
+        let default = Self::default();
+
+        Self {
+            max_attempts: env_value(TODO_SERVICE_WEBHOOK_MAX_ATTEMPTS_ENV).unwrap_or(default.max_attempts).max(1),
+            retry_base: env_value(TODO_SERVICE_WEBHOOK_RETRY_BASE_ENV).map(Duration::from_secs).unwrap_or(default.retry_base),
+            retry_max: env_value(TODO_SERVICE_WEBHOOK_RETRY_MAX_ENV).map(Duration::from_secs).unwrap_or(default.retry_max),
+            disable_after: env_value(TODO_SERVICE_WEBHOOK_DISABLE_AFTER_ENV).unwrap_or(default.disable_after).max(1),
+            timeout: env_value(TODO_SERVICE_WEBHOOK_TIMEOUT_ENV).map(Duration::from_secs).unwrap_or(default.timeout),
+            log_retention: env_value(TODO_SERVICE_WEBHOOK_LOG_RETENTION_DAYS_ENV).map(|days: u64| Duration::from_secs(days * 24 * 60 * 60)).unwrap_or(default.log_retention),
+            allow_private_targets: env_value(TODO_SERVICE_WEBHOOK_ALLOW_PRIVATE_TARGETS_ENV).unwrap_or(default.allow_private_targets),
+            ..default
+        }
+    }
+
+    /// Delay after failed attempt `attempt` (1 - first attempt), `None` if attempts run out
+    pub fn retry_delay(&self, attempt: i32) -> Option<Duration> {
+        if attempt >= self.max_attempts {
+            return None;
+        }
+
+        let factor = 2u32.saturating_pow((attempt - 1).clamp(0, 31) as u32);
+        Some(self.retry_base.saturating_mul(factor).min(self.retry_max))
+    }
+}
+
+fn env_value<T: FromStr>(name: &str) -> Option<T> {
+    env::var(name).ok()
+        .map(|value| value.parse::<T>().unwrap_or_else(|_| panic!("Env {name} has invalid value \"{value}\"")))
+}
+
+/// `sha256=<hex>` of HMAC-SHA256 with the webhook secret over `<timestamp>.<body>`.
+/// Timestamp is signed, so receiver can reject replayed requests
+pub fn signature(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts key of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+
+    format!("sha256={:x}", mac.finalize().into_bytes())
+}
+
+/// Address of the internet, webhooks are not sent to loopback, private, link-local,
+/// unspecified, shared (carrier-grade NAT), broadcast and multicast addresses
+pub fn is_public_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [first, second, ..] = ip.octets();
+            let this_network = first == 0;
+            let shared = first == 100 && (second & 0b1100_0000) == 64;
+
+            !(ip.is_loopback() || ip.is_private() || ip.is_link_local() || ip.is_unspecified()
+                || ip.is_broadcast() || ip.is_multicast() || this_network || shared)
+        },
+        IpAddr::V6(ip) => {
+            let segments = ip.segments();
+            // ipv4 address embedded by NAT64 as 64:ff9b::a.b.c.d or mapped as ::ffff:a.b.c.d
+            let embedded = match segments {
+                [0x64, 0xff9b, 0, 0, 0, 0, high, low] => Some(Ipv4Addr::from((u32::from(high) << 16) | u32::from(low))),
+                _ => ip.to_ipv4_mapped(),
+            };
+
+            if let Some(ipv4) = embedded {
+                return is_public_address(IpAddr::V4(ipv4));
+            }
+
+            let unique_local = (segments[0] & 0xfe00) == 0xfc00;
+            let link_local = (segments[0] & 0xffc0) == 0xfe80;
+
+            !(ip.is_loopback() || ip.is_unspecified() || ip.is_multicast() || unique_local || link_local)
+        },
+    }
+}
+
+/// Host of the url is ip address which is not public or `localhost`. Other names are checked
+/// after resolution on delivery, because they can resolve to other addresses later
+pub fn is_private_target(url: &str) -> bool {
+    let Ok(url) = url::Url::parse(url) else {
+        return false;
+    };
+
+    match url.host() {
+        Some(url::Host::Domain(domain)) => {
+            let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+            domain == "localhost" || domain.ends_with(".localhost")
+        },
+        _ => host_ip(&url).is_some_and(|ip| !is_public_address(ip)),
+    }
+}
+
+/// Ip address of the url host, it's not resolved
+fn host_ip(url: &url::Url) -> Option<IpAddr> {
+    match url.host()? {
+        url::Host::Ipv4(ip) => Some(IpAddr::V4(ip)),
+        url::Host::Ipv6(ip) => Some(IpAddr::V6(ip)),
+        url::Host::Domain(_) => None,
+    }
+}
+
+/// Resolves host of the receiver and fails if any of its addresses is not public.
+/// Connection is made to the checked addresses, so the name can't be rebound to private address after the check
+struct PublicAddressResolver;
+
+impl reqwest::dns::Resolve for PublicAddressResolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        let host = name.as_str().to_string();
+
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0)).await?.collect();
+
+            if let Some(addr) = addrs.iter().find(|addr| !is_public_address(addr.ip())) {
+                return Err(format!("{host} resolves to not allowed address {}", addr.ip()).into());
+            }
+
+            let addrs: reqwest::dns::Addrs = Box::new(addrs.into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+/// Sends deliveries of webhook outbox, one dispatcher per service instance
+pub struct WebhookDispatcher {
+    webhooks: Arc<dyn WebhookRepository>,
+    config: WebhookConfig,
+    http: reqwest::Client,
+    logger: slog::Logger,
+}
+
+impl WebhookDispatcher {
+    pub fn new(webhooks: Arc<dyn WebhookRepository>, config: WebhookConfig, logger: slog::Logger) -> anyhow::Result<Self> {
+        let mut http = reqwest::Client::builder()
+            .timeout(config.timeout)
+            .user_agent(USER_AGENT)
+            // redirect is a failed attempt, receiver url must be updated instead
+            .redirect(reqwest::redirect::Policy::none());
+
+        if !config.allow_private_targets {
+            http = http.dns_resolver(Arc::new(PublicAddressResolver));
+        }
+
+        let http = http.build()?;
+
+        Ok(Self { webhooks, config, http, logger })
+    }
+
+    /// Sends due deliveries, returns count of attempts
+    pub async fn dispatch_due(&self) -> anyhow::Result<usize> {
+        let deliveries = self.webhooks.claim_webhook_deliveries(self.config.timeout + CLAIM_LEASE_MARGIN, self.config.batch_size).await
+            .map_err(|e| anyhow::anyhow!("Deliveries not claimed: {e}"))?;
+
+        let count = deliveries.len();
+        futures::future::join_all(deliveries.iter().map(|delivery| self.deliver(delivery))).await;
+
+        Ok(count)
+    }
+
+    /// Runs until the service stops: polls outbox and prunes delivery log
+    pub fn spawn(self) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut pruned_at: Option<Instant> = None;
+
+            loop {
+                if pruned_at.is_none_or(|pruned_at| pruned_at.elapsed() >= PRUNE_INTERVAL) {
+                    self.prune_log().await;
+                    pruned_at = Some(Instant::now());
+                }
+
+                match self.dispatch_due().await {
+                    // next batch may be due already
+                    Ok(count) if count as i64 >= self.config.batch_size => continue,
+                    Ok(_) => (),
+                    Err(e) => slog::error!(self.logger, "Webhook dispatch failed"; "error" => %e),
+                }
+
+                tokio::time::sleep(self.config.poll_interval).await;
+            }
+        })
+    }
+
+    async fn deliver(&self, delivery: &PendingDelivery) {
+        let attempt = self.attempt(delivery).await;
+
+        if !attempt.succeeded {
+            slog::warn!(self.logger, "Webhook delivery attempt failed";
+                "webhook_id" => %delivery.webhook_id,
+                "delivery_id" => %delivery.id,
+                "attempt" => delivery.attempts + 1,
+                "status_code" => attempt.status_code,
+                "error" => attempt.error.as_deref(),
+                "retry_at" => attempt.retry_at.map(|retry_at| retry_at.to_rfc3339()));
+        }
+
+        match self.webhooks.record_webhook_attempt(delivery.id, &attempt, self.config.disable_after).await {
+            Ok(true) => slog::warn!(self.logger, "Webhook disabled after repeated failures"; "webhook_id" => %delivery.webhook_id),
+            Ok(false) => (),
+            Err(e) => slog::error!(self.logger, "Webhook delivery attempt not recorded"; "delivery_id" => %delivery.id, "error" => %e),
+        }
+    }
+
+    async fn attempt(&self, delivery: &PendingDelivery) -> DeliveryAttempt {
+        // names are checked by resolver, ip addresses are not resolved
+        let private_ip = url::Url::parse(&delivery.url).ok()
+            .and_then(|url| host_ip(&url))
+            .is_some_and(|ip| !is_public_address(ip));
+
+        if !self.config.allow_private_targets && private_ip {
+            return self.failed_attempt(delivery, None, PRIVATE_TARGET.to_string());
+        }
+
+        let timestamp = Utc::now().timestamp();
+
+        let result = self.http.post(&delivery.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, delivery.event.as_str())
+            .header(DELIVERY_HEADER, delivery.id.to_string())
+            .header(TIMESTAMP_HEADER, timestamp)
+            .header(SIGNATURE_HEADER, signature(&delivery.secret, timestamp, &delivery.payload))
+            .body(delivery.payload.clone())
+            .send()
+            .await;
+
+        let (status_code, error) = match result {
+            Ok(response) if response.status().is_success() => {
+                return DeliveryAttempt { succeeded: true, status_code: Some(response.status().as_u16() as i32), error: None, retry_at: None };
+            },
+            Ok(response) => (Some(response.status().as_u16() as i32), format!("Receiver responded with {}", response.status())),
+            // source of reqwest error has the reason, e.g. connection refused
+            Err(e) => (None, error_chain(&e)),
+        };
+
+        self.failed_attempt(delivery, status_code, error)
+    }
+
+    fn failed_attempt(&self, delivery: &PendingDelivery, status_code: Option<i32>, error: String) -> DeliveryAttempt {
+        let retry_at = self.config.retry_delay(delivery.attempts + 1)
+            .and_then(|delay| chrono::Duration::from_std(delay).ok())
+            .map(|delay| Utc::now() + delay);
+
+        DeliveryAttempt {
+            succeeded: false,
+            status_code,
+            error: Some(error.chars().take(MAX_ERROR_LEN).collect()),
+            retry_at,
+        }
+    }
+
+    async fn prune_log(&self) {
+        let before = chrono::Duration::from_std(self.config.log_retention).ok()
+            .and_then(|retention| Utc::now().checked_sub_signed(retention));
+
+        if let Some(before) = before {
+            match self.webhooks.delete_webhook_deliveries_before(before).await {
+                Ok(0) => (),
+                Ok(count) => slog::info!(self.logger, "Webhook delivery log pruned"; "deleted" => count),
+                Err(e) => slog::error!(self.logger, "Webhook delivery log not pruned"; "error" => %e),
+            }
+        }
+    }
+}
+
+/// Error with its sources, reason of reqwest error (connection refused, address not allowed) is in the sources
+fn error_chain(e: &dyn std::error::Error) -> String {
+    let mut message = e.to_string();
+    let mut source = e.source();
+
+    while let Some(e) = source {
+        message.push_str(": ");
+        message.push_str(&e.to_string());
+        source = e.source();
+    }
+
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_delays() {
+        let config = WebhookConfig { max_attempts: 5, retry_base: Duration::from_secs(30), retry_max: Duration::from_secs(100), ..WebhookConfig::default() };
+
+        let delays: Vec<_> = (1..=5).map(|attempt| config.retry_delay(attempt).map(|delay| delay.as_secs())).collect();
+
+        assert_eq!(delays, vec![Some(30), Some(60), Some(100), Some(100), None]);
+    }
+
+    #[test]
+    fn signature_of_timestamp_and_body() {
+        // echo -n '1700000000.{"event":"task.created"}' | openssl dgst -sha256 -hmac secret
+        assert_eq!(
+            signature("secret", 1700000000, r#"{"event":"task.created"}"#),
+            "sha256=fc53e1d22cb0ed2216fe98c535f28e2e668e9a812f23e87d18f07b966afb540a"
+        );
+    }
+
+    #[test]
+    fn private_addresses_are_not_public() {
+        for ip in ["127.0.0.1", "10.1.2.3", "172.16.0.1", "192.168.1.1", "169.254.169.254", "0.0.0.0", "100.64.0.1", "255.255.255.255",
+            "::1", "::", "fd00::1", "fe80::1", "::ffff:127.0.0.1", "::ffff:10.0.0.1", "64:ff9b::a9fe:a9fe"] {
+            assert!(!is_public_address(ip.parse().unwrap()), "{ip}");
+        }
+
+        for ip in ["93.184.216.34", "8.8.8.8", "172.32.0.1", "100.128.0.1", "2606:2800:220:1:248:1893:25c8:1946", "::ffff:8.8.8.8"] {
+            assert!(is_public_address(ip.parse().unwrap()), "{ip}");
+        }
+    }
+
+    #[test]
+    fn private_targets() {
+        for url in ["http://127.0.0.1/hook", "http://10.0.0.5:8080/hook", "http://169.254.169.254/latest/meta-data", "http://[::1]/hook",
+            "http://[::ffff:7f00:1]/hook", "http://0.0.0.0/hook", "http://2130706433/hook", "http://localhost:8080/hook", "http://api.localhost./hook"] {
+            assert!(is_private_target(url), "{url}");
+        }
+
+        for url in ["https://example.com/hook", "http://8.8.8.8/hook", "https://[2606:2800:220:1:248:1893:25c8:1946]/hook"] {
+            assert!(!is_private_target(url), "{url}");
+        }
+    }
+
+    #[tokio::test]
+    async fn resolver_rejects_name_of_private_address() {
+        use reqwest::dns::Resolve;
+
+        let error = PublicAddressResolver.resolve("localhost".parse().unwrap()).await.err().unwrap();
+        assert!(error.to_string().contains("not allowed address"), "{error}");
+    }
+}
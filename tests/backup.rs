@@ -0,0 +1,202 @@
+mod common;
+
+use std::collections::HashMap;
+
+use actix_http::Request;
+use actix_web::{
+    body::MessageBody,
+    dev::{
+        Service,
+        ServiceResponse
+    },
+    http::{
+        Method,
+        StatusCode
+    },
+    test
+};
+use serde_json::{
+    json,
+    Value
+};
+
+use common::*;
+use todo_list_rs::utils::archive::read_tar_gz;
+
+/// Archive of the user backup
+async fn backup<S, B>(app: &S, token: &str) -> Vec<u8>
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody
+{
+    let response = test::call_service(app, test::TestRequest::get()
+        .uri("/api/user/backup")
+        .insert_header(("Authorization", format!("Bearer {token}")))
+        .to_request()).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers().get("Content-Type").unwrap(), "application/gzip");
+    assert!(response.headers().get("Content-Disposition").unwrap().to_str().unwrap().starts_with("attachment; filename=\"todo-list-backup-user_"));
+
+    test::read_body(response).await.to_vec()
+}
+
+async fn restore<S, B>(app: &S, token: &str, query: &str, archive: &[u8]) -> Response
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody
+{
+    upload(app, &format!("/api/user/restore?{query}"), token, &[("file", archive)]).await
+}
+
+#[actix_web::test]
+async fn backup_has_manifest_and_data_files() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_with_tasks(&app, &["buy milk", "plan trip"]).await;
+    let tasks = tasks(&app, &token).await;
+
+    let response = send(&app, Method::POST, "/api/user/webhooks", Some(&token), Some(json!({ "url": "https://example.com/hook", "events": ["task.created"] }))).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    let files: HashMap<String, Vec<u8>> = read_tar_gz(&backup(&app, &token).await, 1 << 20).unwrap().into_iter().collect();
+    let file = |name: &str| -> Value { serde_json::from_slice(&files[name]).unwrap() };
+
+    let manifest = file("manifest.json");
+    assert_eq!(manifest["format"], "todo-list-rs-backup");
+    assert_eq!(manifest["version"], 2);
+    assert_eq!(manifest["files"].as_array().unwrap().len(), 3);
+
+    assert_eq!(file("lists.json")[0]["name"], "test");
+    assert_eq!(file("tasks.json")[1]["id"], tasks[1]["id"]);
+    assert_eq!(file("tasks.json")[1]["order"], 2);
+
+    let account = file("account.json");
+    assert_eq!(account["webhooks"][0]["url"], "https://example.com/hook");
+    assert!(account["webhooks"][0].get("secret").is_none());
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn restore_to_another_user_remaps_ids() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_with_tasks(&app, &["buy milk", "plan trip\nbook hotel", "call mom"]).await;
+    let original = tasks(&app, &token).await;
+
+    let archive = backup(&app, &token).await;
+
+    let other = user_token(&app).await;
+    let response = restore(&app, &other, "", &archive).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    let report: Value = response.json();
+    assert_eq!(report["version"], 2);
+    assert_eq!(report["deleted_tasks"], 0);
+
+    let restored = tasks(&app, &other).await;
+    assert_eq!(descriptions(&app, &other).await, ["buy milk", "plan trip\nbook hotel", "call mom"]);
+    for (index, task) in restored.iter().enumerate() {
+        assert_ne!(task["id"], original[index]["id"]);
+        assert_eq!(report["tasks"][index]["old"], original[index]["id"]);
+        assert_eq!(report["tasks"][index]["new"], task["id"]);
+    }
+
+    let list: Value = send(&app, Method::GET, "/api/list", Some(&other), None).await.json();
+    assert_eq!(list["name"], "test");
+    assert_eq!(report["list"]["new"], list["id"]);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn restore_keeps_task_details() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_with_tasks(&app, &["water plants", "buy milk"]).await;
+    let plants = tasks(&app, &token).await[0]["id"].as_str().unwrap().to_string();
+
+    let details = json!({ "due_at": "2026-10-20T09:00:00Z", "completed_at": "2026-10-19T18:30:00Z", "priority": 1, "rrule": "FREQ=WEEKLY;BYDAY=TU" });
+    let response = send(&app, Method::PUT, &format!("/api/task/{plants}/details"), Some(&token), Some(details.clone())).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    let archive = backup(&app, &token).await;
+
+    let other = user_token(&app).await;
+    let response = restore(&app, &other, "", &archive).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    let restored = tasks(&app, &other).await;
+    for field in ["due_at", "completed_at", "priority", "rrule"] {
+        assert_eq!(restored[0][field], details[field], "{field}");
+        assert!(restored[1][field].is_null(), "{field}");
+    }
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn restore_replaces_list_only_on_request() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_with_tasks(&app, &["buy milk", "plan trip"]).await;
+
+    let archive = backup(&app, &token).await;
+
+    let response = send(&app, Method::POST, "/api/task", Some(&token), Some(json!({ "description": "after backup", "position": "end" }))).await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    let response = restore(&app, &token, "", &archive).await;
+    assert_eq!(response.status, StatusCode::CONFLICT);
+    assert_eq!(descriptions(&app, &token).await.len(), 3);
+
+    let response = restore(&app, &token, "replace=true", &archive).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(response.json::<Value>()["deleted_tasks"], 3);
+    assert_eq!(descriptions(&app, &token).await, ["buy milk", "plan trip"]);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn restore_rejects_invalid_archives() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_token(&app).await;
+
+    let response = restore(&app, &token, "", b"not an archive").await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+
+    let foreign = todo_list_rs::utils::archive::write_tar_gz(&[("manifest.json", b"{\"format\": \"other\"}")], 0);
+    let response = restore(&app, &token, "", &foreign).await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let response = upload(&app, "/api/user/restore", &token, &[("backup", b"")]).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+
+    // backup of user without list changes nothing
+    let response = restore(&app, &token, "", &backup(&app, &token).await).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.json::<Value>()["list"], Value::Null);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn backup_requires_login_token() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_with_tasks(&app, &[]).await;
+
+    let created: Value = send(&app, Method::POST, "/api/user/tokens", Some(&token), Some(json!({ "name": "ci", "scopes": ["lists:admin", "tasks:write"] }))).await.json();
+    let access_token = created["token"].as_str().unwrap();
+
+    let response = send(&app, Method::GET, "/api/user/backup", Some(access_token), None).await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+
+    let response = restore(&app, access_token, "replace=true", &backup(&app, &token).await).await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+
+    db.close().await;
+}
@@ -0,0 +1,396 @@
+mod common;
+
+use std::collections::HashMap;
+
+use actix_http::Request;
+use actix_web::{
+    body::MessageBody,
+    dev::{
+        Service,
+        ServiceResponse
+    },
+    http::{
+        header::{
+            self,
+            HeaderMap
+        },
+        Method,
+        StatusCode
+    },
+    test
+};
+use chrono::Utc;
+use serde_json::{
+    json,
+    Value
+};
+use uuid::Uuid;
+
+use common::*;
+use todo_list_rs::utils::totp;
+
+const PROPFIND_CALENDAR: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:" xmlns:cs="http://calendarserver.org/ns/">
+  <d:prop><d:resourcetype/><d:displayname/><cs:getctag/><d:getetag/><d:current-user-privilege-set/></d:prop>
+</d:propfind>"#;
+
+const QUERY_TODOS: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<c:calendar-query xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+  <d:prop><d:getetag/><c:calendar-data/></d:prop>
+  <c:filter><c:comp-filter name="VCALENDAR"><c:comp-filter name="VTODO"/></c:comp-filter></c:filter>
+</c:calendar-query>"#;
+
+struct DavUser {
+    login: String,
+    token: String,
+    list_id: String,
+}
+
+impl DavUser {
+    fn basic(&self, password: &str) -> String {
+        format!("Basic {}", base64::encode(format!("{}:{password}", self.login)))
+    }
+
+    fn password(&self) -> String {
+        self.basic("password1")
+    }
+
+    fn calendar(&self) -> String {
+        format!("/dav/calendars/{}/", self.list_id)
+    }
+}
+
+struct DavResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: String,
+}
+
+impl DavResponse {
+    /// Found properties of every resource: text of the property, or names of its child elements
+    fn props(&self) -> HashMap<String, HashMap<String, String>> {
+        assert_eq!(self.status, StatusCode::MULTI_STATUS, "{}", self.body);
+        let document = roxmltree::Document::parse(&self.body).unwrap_or_else(|e| panic!("{e}: {}", self.body));
+
+        document.root_element().children()
+            .filter(|node| node.has_tag_name(("DAV:", "response")))
+            .map(|response| {
+                let href = element(response, "href").text().unwrap().to_string();
+                let props = response.children()
+                    .filter(|node| node.has_tag_name(("DAV:", "propstat")) && element(*node, "status").text().unwrap().contains("200"))
+                    .flat_map(|propstat| element(propstat, "prop").children().filter(|node| node.is_element()))
+                    .map(|prop| (prop.tag_name().name().to_string(), prop_value(prop)))
+                    .collect();
+
+                (href, props)
+            })
+            .collect()
+    }
+}
+
+fn element<'a, 'input>(node: roxmltree::Node<'a, 'input>, name: &str) -> roxmltree::Node<'a, 'input> {
+    node.children().find(|child| child.has_tag_name(("DAV:", name))).unwrap_or_else(|| panic!("no {name} element"))
+}
+
+fn prop_value(prop: roxmltree::Node) -> String {
+    let text: String = prop.descendants().filter(|node| node.is_text()).filter_map(|node| node.text()).collect();
+
+    match text.trim().is_empty() {
+        true => prop.descendants().skip(1).filter(|node| node.is_element()).map(|node| node.tag_name().name()).collect::<Vec<_>>().join(","),
+        false => text.trim().to_string(),
+    }
+}
+
+async fn dav<S, B>(app: &S, method: &str, uri: &str, auth: Option<&str>, headers: &[(&str, &str)], body: &str) -> DavResponse
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody
+{
+    let mut request = test::TestRequest::default()
+        .method(Method::from_bytes(method.as_bytes()).unwrap())
+        .uri(uri)
+        .set_payload(body.to_string());
+
+    if let Some(auth) = auth {
+        request = request.insert_header((header::AUTHORIZATION, auth));
+    }
+    for header in headers {
+        request = request.insert_header(*header);
+    }
+
+    let response = test::call_service(app, request.to_request()).await;
+    let status = response.status();
+    let headers = response.headers().clone();
+    let body = String::from_utf8(test::read_body(response).await.to_vec()).unwrap();
+
+    DavResponse { status, headers, body }
+}
+
+/// Registers user with list and tasks
+async fn dav_user<S, B>(app: &S, descriptions: &[&str]) -> DavUser
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody
+{
+    let login_name = format!("user_{}", Uuid::new_v4().simple());
+    assert_eq!(register(app, &login_name, "password1").await.status, StatusCode::OK);
+    let token = login(app, &login_name, "password1").await.body;
+
+    let response = send(app, Method::POST, "/api/list", Some(&token), Some(json!({ "name": "Дом & работа" }))).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let list: Value = send(app, Method::GET, "/api/list", Some(&token), None).await.json();
+
+    for description in descriptions {
+        let response = send(app, Method::POST, "/api/task", Some(&token), Some(json!({ "description": description, "position": "end" }))).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    }
+
+    DavUser { login: login_name, token, list_id: list["id"].as_str().unwrap().to_string() }
+}
+
+fn todo(uid: &str, summary: &str, description: Option<&str>) -> String {
+    let description = description.map(|description| format!("DESCRIPTION:{description}\r\n")).unwrap_or_default();
+    format!("BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//test//EN\r\nBEGIN:VTODO\r\nUID:{uid}\r\nDTSTAMP:20261019T120000Z\r\nSUMMARY:{summary}\r\n{description}STATUS:NEEDS-ACTION\r\nEND:VTODO\r\nEND:VCALENDAR\r\n")
+}
+
+#[actix_web::test]
+async fn calendar_discovery() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let user = dav_user(&app, &[]).await;
+
+    let response = dav(&app, "PROPFIND", "/.well-known/caldav", None, &[], "").await;
+    assert_eq!(response.status, StatusCode::MOVED_PERMANENTLY);
+    assert_eq!(response.headers.get(header::LOCATION).unwrap(), "/dav/");
+
+    let response = dav(&app, "OPTIONS", "/dav/", None, &[], "").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.headers.get("DAV").unwrap().to_str().unwrap().contains("calendar-access"));
+
+    let body = r#"<d:propfind xmlns:d="DAV:"><d:prop><d:current-user-principal/></d:prop></d:propfind>"#;
+    let props = dav(&app, "PROPFIND", "/dav/", Some(&user.password()), &[("Depth", "0")], body).await.props();
+    assert_eq!(props["/dav/"]["current-user-principal"], "/dav/principal/");
+
+    let body = r#"<d:propfind xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav"><d:prop><c:calendar-home-set/><d:displayname/></d:prop></d:propfind>"#;
+    let props = dav(&app, "PROPFIND", "/dav/principal/", Some(&user.password()), &[("Depth", "0")], body).await.props();
+    assert_eq!(props["/dav/principal/"]["calendar-home-set"], "/dav/calendars/");
+    assert_eq!(props["/dav/principal/"]["displayname"], user.login);
+
+    let props = dav(&app, "PROPFIND", "/dav/calendars/", Some(&user.password()), &[("Depth", "1")], PROPFIND_CALENDAR).await.props();
+    assert_eq!(props.len(), 2);
+    let calendar = &props[&user.calendar()];
+    assert_eq!(calendar["resourcetype"], "collection,calendar");
+    assert_eq!(calendar["displayname"], "Дом & работа");
+    assert_eq!(calendar["current-user-privilege-set"], "privilege,read,privilege,write-content,privilege,bind,privilege,unbind");
+
+    // unknown properties are reported as not found
+    let response = dav(&app, "PROPFIND", &user.calendar(), Some(&user.password()), &[("Depth", "0")], r#"<propfind xmlns="DAV:"><prop><color xmlns="urn:x"/></prop></propfind>"#).await;
+    assert!(response.body.contains("<x:color xmlns:x=\"urn:x\"/></d:prop><d:status>HTTP/1.1 404 Not Found</d:status>"), "{}", response.body);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn tasks_are_synced_as_todos() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let user = dav_user(&app, &["buy milk", "plan trip\nbook hotel"]).await;
+    let tasks = tasks(&app, &user.token).await;
+
+    let props = dav(&app, "PROPFIND", &user.calendar(), Some(&user.password()), &[("Depth", "1")], PROPFIND_CALENDAR).await.props();
+    assert_eq!(props.len(), 3);
+    let task_href = format!("{}{}.ics", user.calendar(), tasks[1]["id"].as_str().unwrap());
+    let etag = props[&task_href]["getetag"].clone();
+
+    let response = dav(&app, "GET", &task_href, Some(&user.password()), &[], "").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.headers.get(header::ETAG).unwrap().to_str().unwrap(), etag);
+    assert!(response.body.contains("SUMMARY:plan trip\r\nDESCRIPTION:plan trip\\nbook hotel\r\n"), "{}", response.body);
+
+    let props = dav(&app, "REPORT", &user.calendar(), Some(&user.password()), &[("Depth", "1")], QUERY_TODOS).await.props();
+    assert_eq!(props.len(), 2);
+    assert_eq!(props[&task_href]["getetag"], etag);
+    assert!(props[&task_href]["calendar-data"].starts_with("BEGIN:VCALENDAR"));
+
+    let missing = format!("{}{}.ics", user.calendar(), Uuid::new_v4());
+    let body = format!(r#"<c:calendar-multiget xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav"><d:prop><d:getetag/></d:prop><d:href>{task_href}</d:href><d:href>{missing}</d:href></c:calendar-multiget>"#);
+    let response = dav(&app, "REPORT", &user.calendar(), Some(&user.password()), &[], &body).await;
+    assert_eq!(response.props()[&task_href]["getetag"], etag);
+    assert!(response.body.contains(&format!("<d:href>{missing}</d:href><d:status>HTTP/1.1 404 Not Found</d:status>")), "{}", response.body);
+
+    // events are not stored
+    let body = QUERY_TODOS.replace("VTODO", "VEVENT");
+    assert!(dav(&app, "REPORT", &user.calendar(), Some(&user.password()), &[], &body).await.props().is_empty());
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn client_edits_flow_back_to_tasks() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let user = dav_user(&app, &["first"]).await;
+    let auth = user.password();
+
+    let ctag = |props: HashMap<String, HashMap<String, String>>| props[&user.calendar()]["getctag"].clone();
+    let first_ctag = ctag(dav(&app, "PROPFIND", &user.calendar(), Some(&auth), &[("Depth", "0")], PROPFIND_CALENDAR).await.props());
+
+    // new todo is added to the end of the list with id of the resource
+    let id = Uuid::new_v4();
+    let href = format!("{}{id}.ics", user.calendar());
+    let response = dav(&app, "PUT", &href, Some(&auth), &[("If-None-Match", "*")], &todo(&id.to_string(), "Buy milk", Some("2 bottles\\, fresh"))).await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+    assert!(response.headers.get(header::ETAG).is_none());
+
+    assert_eq!(descriptions(&app, &user.token).await, ["first", "Buy milk\n2 bottles, fresh"]);
+    assert_eq!(task_id(&app, &user.token, "Buy milk\n2 bottles, fresh").await, id.to_string());
+
+    let response = dav(&app, "PUT", &href, Some(&auth), &[("If-None-Match", "*")], &todo(&id.to_string(), "again", None)).await;
+    assert_eq!(response.status, StatusCode::PRECONDITION_FAILED);
+
+    // update needs the current etag
+    let etag = dav(&app, "GET", &href, Some(&auth), &[], "").await.headers.get(header::ETAG).unwrap().to_str().unwrap().to_string();
+    let response = dav(&app, "PUT", &href, Some(&auth), &[("If-Match", "\"outdated\"")], &todo(&id.to_string(), "Buy tea", None)).await;
+    assert_eq!(response.status, StatusCode::PRECONDITION_FAILED);
+
+    let response = dav(&app, "PUT", &href, Some(&auth), &[("If-Match", &etag)], &todo(&id.to_string(), "Buy tea", None)).await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT, "{}", response.body);
+    assert_eq!(descriptions(&app, &user.token).await, ["first", "Buy tea"]);
+
+    let second_ctag = ctag(dav(&app, "PROPFIND", &user.calendar(), Some(&auth), &[("Depth", "0")], PROPFIND_CALENDAR).await.props());
+    assert_ne!(first_ctag, second_ctag);
+
+    // only todos with text are accepted
+    let response = dav(&app, "PUT", &href, Some(&auth), &[], &todo(&id.to_string(), " ", None)).await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+    let event = todo(&id.to_string(), "meeting", None).replace("VTODO", "VEVENT");
+    assert_eq!(dav(&app, "PUT", &href, Some(&auth), &[], &event).await.status, StatusCode::FORBIDDEN);
+    let response = dav(&app, "PUT", &format!("{}not-uuid.ics", user.calendar()), Some(&auth), &[], &todo("x", "x", None)).await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+
+    let response = dav(&app, "DELETE", &href, Some(&auth), &[], "").await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+    assert_eq!(descriptions(&app, &user.token).await, ["first"]);
+    assert_eq!(dav(&app, "GET", &href, Some(&auth), &[], "").await.status, StatusCode::NOT_FOUND);
+
+    // id of the task of another user can't be taken
+    let other = dav_user(&app, &["secret"]).await;
+    let other_id = task_id(&app, &other.token, "secret").await;
+    let response = dav(&app, "PUT", &format!("{}{other_id}.ics", user.calendar()), Some(&auth), &[], &todo(&other_id, "mine", None)).await;
+    assert_eq!(response.status, StatusCode::CONFLICT);
+    assert_eq!(descriptions(&app, &other.token).await, ["secret"]);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn client_details_are_stored() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let user = dav_user(&app, &["water plants"]).await;
+    let auth = user.password();
+    let id = task_id(&app, &user.token, "water plants").await;
+    let href = format!("{}{id}.ics", user.calendar());
+
+    let details = "DUE;TZID=Europe/Moscow:20261020T120000\r\nPRIORITY:1\r\nSTATUS:COMPLETED\r\nCOMPLETED:20261019T170000Z\r\nPERCENT-COMPLETE:100\r\n";
+    let body = todo(&id, "water plants", None).replace("STATUS:NEEDS-ACTION\r\n", details);
+    let response = dav(&app, "PUT", &href, Some(&auth), &[], &body).await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT, "{}", response.body);
+
+    let task = &tasks(&app, &user.token).await[0];
+    assert_eq!(task["due_at"], "2026-10-20T09:00:00Z");
+    assert_eq!(task["completed_at"], "2026-10-19T17:00:00Z");
+    assert_eq!(task["priority"], 1);
+
+    let resource = dav(&app, "GET", &href, Some(&auth), &[], "").await.body;
+    assert!(resource.contains("\r\nDUE:20261020T090000Z\r\nSTATUS:COMPLETED\r\nCOMPLETED:20261019T170000Z\r\nPRIORITY:1\r\n"), "{resource}");
+
+    // reopened in the client
+    let response = dav(&app, "PUT", &href, Some(&auth), &[], &todo(&id, "water plants", None)).await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT, "{}", response.body);
+    assert_eq!(tasks(&app, &user.token).await[0]["completed_at"], Value::Null);
+
+    let new_id = Uuid::new_v4().to_string();
+    let body = todo(&new_id, "pay rent", None).replace("STATUS:NEEDS-ACTION\r\n", "DUE;VALUE=DATE:20261101\r\nRRULE:FREQ=MONTHLY\r\nDTSTART;VALUE=DATE:20261101\r\n");
+    let response = dav(&app, "PUT", &format!("{}{new_id}.ics", user.calendar()), Some(&auth), &[("If-None-Match", "*")], &body).await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+    let task = &tasks(&app, &user.token).await[1];
+    assert_eq!(task["due_at"], "2026-11-01T00:00:00Z");
+    assert_eq!(task["rrule"], "FREQ=MONTHLY");
+
+    // content, which task can't keep, is not dropped silently
+    let alarm = todo(&id, "water plants", None).replace("END:VTODO", "BEGIN:VALARM\r\nACTION:DISPLAY\r\nTRIGGER:-PT15M\r\nEND:VALARM\r\nEND:VTODO");
+    let response = dav(&app, "PUT", &href, Some(&auth), &[], &alarm).await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+    assert!(response.body.contains("<c:supported-calendar-component/>"), "{}", response.body);
+
+    let categories = todo(&id, "water the garden", None).replace("STATUS:NEEDS-ACTION", "CATEGORIES:home");
+    let response = dav(&app, "PUT", &href, Some(&auth), &[], &categories).await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+    assert!(response.body.contains("<c:valid-calendar-object-resource/>"), "{}", response.body);
+    assert_eq!(descriptions(&app, &user.token).await, ["water plants", "pay rent"]);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn dav_credentials() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let user = dav_user(&app, &["a"]).await;
+    let body = PROPFIND_CALENDAR;
+
+    let response = dav(&app, "PROPFIND", &user.calendar(), None, &[], body).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    assert!(response.headers.get(header::WWW_AUTHENTICATE).unwrap().to_str().unwrap().starts_with("Basic realm="));
+
+    let response = dav(&app, "PROPFIND", &user.calendar(), Some(&user.basic("wrong")), &[], body).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    assert!(response.headers.get(header::WWW_AUTHENTICATE).is_some());
+
+    // login token and personal access token of the user
+    let response = dav(&app, "PROPFIND", &user.calendar(), Some(&format!("Bearer {}", user.token)), &[], body).await;
+    assert_eq!(response.status, StatusCode::MULTI_STATUS);
+
+    let created: Value = send(&app, Method::POST, "/api/user/tokens", Some(&user.token), Some(json!({ "name": "phone", "scopes": ["read-only"] }))).await.json();
+    let access_token = created["token"].as_str().unwrap();
+    let response = dav(&app, "PROPFIND", &user.calendar(), Some(&user.basic(access_token)), &[], body).await;
+    assert_eq!(response.props()[&user.calendar()]["current-user-privilege-set"], "privilege,read");
+
+    let id = Uuid::new_v4();
+    let response = dav(&app, "PUT", &format!("{}{id}.ics", user.calendar()), Some(&user.basic(access_token)), &[], &todo(&id.to_string(), "b", None)).await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+
+    // token is accepted only with login of its owner, list of other user isn't visible
+    let other = dav_user(&app, &[]).await;
+    let response = dav(&app, "PROPFIND", &user.calendar(), Some(&other.basic(access_token)), &[], body).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+
+    let response = dav(&app, "PROPFIND", &user.calendar(), Some(&other.password()), &[], body).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn two_factor_user_needs_access_token() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let user = dav_user(&app, &["a"]).await;
+
+    let enrollment: Value = send(&app, Method::POST, "/api/user/2fa/enroll", Some(&user.token), None).await.json();
+    let secret = totp::decode_secret(enrollment["secret"].as_str().unwrap()).unwrap();
+    let code = totp::code(&secret, totp::step(Utc::now().timestamp() as u64));
+    let response = send(&app, Method::POST, "/api/user/2fa/confirm", Some(&user.token), Some(json!({ "code": code }))).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    let response = dav(&app, "PROPFIND", &user.calendar(), Some(&user.password()), &[], PROPFIND_CALENDAR).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    assert!(response.body.contains("personal access token"), "{}", response.body);
+
+    let created: Value = send(&app, Method::POST, "/api/user/tokens", Some(&user.token), Some(json!({ "name": "phone", "scopes": ["tasks:write"] }))).await.json();
+    let response = dav(&app, "PROPFIND", &user.calendar(), Some(&user.basic(created["token"].as_str().unwrap())), &[], PROPFIND_CALENDAR).await;
+    assert_eq!(response.status, StatusCode::MULTI_STATUS);
+
+    db.close().await;
+}
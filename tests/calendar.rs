@@ -0,0 +1,220 @@
+mod common;
+
+use actix_web::{
+    http::{
+        header,
+        Method,
+        StatusCode
+    },
+    test
+};
+use ical::parser::{
+    ical::component::IcalCalendar,
+    Component
+};
+use serde_json::{
+    json,
+    Value
+};
+
+use common::*;
+
+/// Value of the property without TEXT escaping, parser returns raw values
+fn property(component: &impl Component, name: &str) -> Option<String> {
+    component.get_property(name)
+        .and_then(|property| property.value.as_deref())
+        .map(unescape)
+}
+
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') | Some('N') => unescaped.push('\n'),
+            Some(escaped) => unescaped.push(escaped),
+            None => (),
+        }
+    }
+
+    unescaped
+}
+
+fn parse_calendar(body: &str) -> IcalCalendar {
+    let mut parser = ical::IcalParser::new(body.as_bytes());
+    let calendar = parser.next().expect("no calendar in feed").unwrap_or_else(|e| panic!("{e}: {body}"));
+    assert!(parser.next().is_none());
+    calendar
+}
+
+#[actix_web::test]
+async fn calendar_feed_round_trip() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let descriptions = ["milk, bread; eggs", "back\\slash", "plan trip\nbook hotel", &format!("{}конец", "длинная задача ".repeat(10))];
+    let token = user_with_tasks(&app, &descriptions).await;
+    let tasks = tasks(&app, &token).await;
+
+    let response = send(&app, Method::POST, "/api/user/calendar-feed", Some(&token), None).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let feed: Value = response.json();
+    let path = feed["path"].as_str().unwrap();
+    assert!(path.ends_with(&format!("/{}.ics", feed["token"].as_str().unwrap())));
+
+    let response = test::call_service(&app, test::TestRequest::get().uri(path).to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers().get(header::CONTENT_TYPE).unwrap(), "text/calendar; charset=utf-8");
+    let body = String::from_utf8(test::read_body(response).await.to_vec()).unwrap();
+
+    // content lines end with CRLF and are folded to 75 octets
+    assert!(body.ends_with("\r\n"));
+    assert!(body.split("\r\n").all(|line| line.len() <= 75), "{body}");
+
+    let calendar = parse_calendar(&body);
+    assert_eq!(property(&calendar, "VERSION").as_deref(), Some("2.0"));
+    assert_eq!(property(&calendar, "X-WR-CALNAME").as_deref(), Some("test"));
+    assert_eq!(calendar.todos.len(), descriptions.len());
+
+    for (todo, task) in calendar.todos.iter().zip(&tasks) {
+        let description = task["description"].as_str().unwrap();
+
+        assert_eq!(property(todo, "UID").as_deref(), task["id"].as_str());
+        assert_eq!(property(todo, "SUMMARY").as_deref(), description.lines().next());
+        assert_eq!(property(todo, "STATUS").as_deref(), Some("NEEDS-ACTION"));
+        assert_eq!(property(todo, "X-APPLE-SORT-ORDER"), Some(task["order"].to_string()));
+        assert!(property(todo, "DTSTAMP").is_some());
+    }
+
+    let multiline = &calendar.todos[2];
+    assert_eq!(property(multiline, "DESCRIPTION").as_deref(), Some("plan trip\nbook hotel"));
+    assert_eq!(property(&calendar.todos[0], "DESCRIPTION"), None);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn calendar_feed_has_task_details() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_with_tasks(&app, &["water plants", "buy milk"]).await;
+    let plants = task_id(&app, &token, "water plants").await;
+    let milk = task_id(&app, &token, "buy milk").await;
+
+    let details = json!({ "due_at": "2026-10-20T09:00:00Z", "priority": 1, "rrule": "FREQ=WEEKLY;BYDAY=TU" });
+    let response = send(&app, Method::PUT, &format!("/api/task/{plants}/details"), Some(&token), Some(details)).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let task: Value = response.json();
+    assert_eq!(task["priority"], 1);
+    assert_eq!(task["rrule"], "FREQ=WEEKLY;BYDAY=TU");
+
+    let details = json!({ "completed_at": "2026-10-19T18:30:00Z" });
+    let response = send(&app, Method::PUT, &format!("/api/task/{milk}/details"), Some(&token), Some(details)).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    let feed: Value = send(&app, Method::POST, "/api/user/calendar-feed", Some(&token), None).await.json();
+    let calendar = parse_calendar(&send(&app, Method::GET, feed["path"].as_str().unwrap(), None, None).await.body);
+
+    let recurring = &calendar.todos[0];
+    assert_eq!(property(recurring, "DUE").as_deref(), Some("20261020T090000Z"));
+    assert_eq!(property(recurring, "DTSTART").as_deref(), Some("20261020T090000Z"));
+    assert_eq!(property(recurring, "RRULE").as_deref(), Some("FREQ=WEEKLY;BYDAY=TU"));
+    assert_eq!(property(recurring, "PRIORITY").as_deref(), Some("1"));
+    assert_eq!(property(recurring, "STATUS").as_deref(), Some("NEEDS-ACTION"));
+
+    let completed = &calendar.todos[1];
+    assert_eq!(property(completed, "STATUS").as_deref(), Some("COMPLETED"));
+    assert_eq!(property(completed, "COMPLETED").as_deref(), Some("20261019T183000Z"));
+    assert_eq!(property(completed, "DUE"), None);
+    assert_eq!(property(completed, "PRIORITY"), None);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn invalid_task_details_are_rejected() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_with_tasks(&app, &["water plants"]).await;
+    let plants = task_id(&app, &token, "water plants").await;
+    let path = format!("/api/task/{plants}/details");
+
+    for details in [
+        json!({ "priority": 10 }),
+        json!({ "due_at": "2026-10-20T09:00:00Z", "rrule": "FREQ=DAILY\r\nBEGIN:VEVENT" }),
+        json!({ "due_at": "2026-10-20T09:00:00Z", "rrule": "BYDAY=MO" }),
+        // recurrence starts at due time
+        json!({ "rrule": "FREQ=DAILY" }),
+    ] {
+        let response = send(&app, Method::PUT, &path, Some(&token), Some(details.clone())).await;
+        assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY, "{details}: {}", response.body);
+    }
+
+    let response = send(&app, Method::PUT, &format!("/api/task/{}/details", uuid::Uuid::new_v4()), Some(&token), Some(json!({}))).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn calendar_feed_without_list_is_empty() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_token(&app).await;
+
+    let feed: Value = send(&app, Method::POST, "/api/user/calendar-feed", Some(&token), None).await.json();
+
+    let response = send(&app, Method::GET, feed["path"].as_str().unwrap(), None, None).await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    let calendar = parse_calendar(&response.body);
+    assert!(calendar.todos.is_empty());
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn calendar_feed_token_is_revocable() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_with_tasks(&app, &["a"]).await;
+
+    let response = send(&app, Method::GET, "/api/user/calendar-feed", Some(&token), None).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+    let first: Value = send(&app, Method::POST, "/api/user/calendar-feed", Some(&token), None).await.json();
+    let first_path = first["path"].as_str().unwrap();
+    assert!(first["last_used_at"].is_null());
+
+    assert_eq!(send(&app, Method::GET, first_path, None, None).await.status, StatusCode::OK);
+
+    let info: Value = send(&app, Method::GET, "/api/user/calendar-feed", Some(&token), None).await.json();
+    assert!(info["last_used_at"].is_string());
+    assert!(info.get("token").is_none());
+
+    // new token replaces the old one
+    let second: Value = send(&app, Method::POST, "/api/user/calendar-feed", Some(&token), None).await.json();
+    let second_path = second["path"].as_str().unwrap();
+    assert_ne!(first_path, second_path);
+
+    assert_eq!(send(&app, Method::GET, first_path, None, None).await.status, StatusCode::NOT_FOUND);
+    assert_eq!(send(&app, Method::GET, second_path, None, None).await.status, StatusCode::OK);
+
+    let response = send(&app, Method::DELETE, "/api/user/calendar-feed", Some(&token), None).await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    assert_eq!(send(&app, Method::GET, second_path, None, None).await.status, StatusCode::NOT_FOUND);
+
+    let response = send(&app, Method::DELETE, "/api/user/calendar-feed", Some(&token), None).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+    let response = send(&app, Method::GET, "/api/calendar/tdl_cal_unknown.ics", None, None).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+    db.close().await;
+}
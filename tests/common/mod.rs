@@ -0,0 +1,390 @@
+//! Test app on ephemeral storage. Storage is selected by `TODO_SERVICE_TEST_DATABASE_URL`
+//! with the same schemes as `DATABASE_URL`, default is `memory://`.
+//! For `postgres://` url every test creates its own database, which is dropped with `TestDb`
+#![allow(dead_code)]
+
+pub mod oidc;
+pub mod webhook;
+
+use std::{
+    env,
+    sync::{
+        Arc,
+        Mutex
+    },
+    time::Duration
+};
+
+use actix_http::Request;
+use async_trait::async_trait;
+use actix_web::{
+    body::MessageBody,
+    dev::{
+        Service,
+        ServiceFactory,
+        ServiceRequest,
+        ServiceResponse
+    },
+    http::{
+        Method,
+        StatusCode
+    },
+    test,
+    web,
+    App
+};
+use serde::de::DeserializeOwned;
+use serde_json::{
+    json,
+    Value
+};
+use sqlx::{
+    Connection,
+    PgConnection
+};
+use uuid::Uuid;
+
+use todo_list_rs::{
+    db::Storage,
+    middlewares::{
+        RequestId,
+        TokenCheck,
+        Tracing
+    },
+    routes,
+    utils::{
+        db::connect_storage,
+        jwt::JwtKeys,
+        oidc::OidcProvider,
+        mail::{
+            Mail,
+            Mailer
+        },
+        rate_limit::{
+            LoginThrottleConfig,
+            RateLimits
+        },
+        webhook::WebhookConfig
+    }
+};
+
+const TEST_DATABASE_URL_ENV: &str = "TODO_SERVICE_TEST_DATABASE_URL";
+pub const BEARER_KEY: &str = "integration_test_key";
+
+pub struct TestDb {
+    pub storage: Storage,
+    /// Server url and name of the created postgres database
+    postgres: Option<(String, String)>,
+}
+
+impl TestDb {
+    pub async fn new() -> Self {
+        let db_address = env::var(TEST_DATABASE_URL_ENV).unwrap_or_else(|_| "memory://".to_string());
+
+        if !db_address.starts_with("postgres") {
+            let storage = connect_storage(&logger(), &db_address).await.unwrap();
+            return Self { storage, postgres: None };
+        }
+
+        let name = format!("todo_test_{}", Uuid::new_v4().simple());
+        execute(&db_address, &format!("CREATE DATABASE {name}")).await;
+
+        let storage = connect_storage(&logger(), &with_database(&db_address, &name)).await.unwrap();
+        Self { storage, postgres: Some((db_address, name)) }
+    }
+
+    pub async fn close(self) {
+        self.storage.close().await;
+    }
+}
+
+/// Drops postgres database also when test panics
+impl Drop for TestDb {
+    fn drop(&mut self) {
+        if let Some((db_address, name)) = self.postgres.take() {
+            // test runtime can't be blocked, database is dropped on own runtime.
+            // Connections of the storage can still be open, so drop is forced
+            std::thread::spawn(move || {
+                tokio::runtime::Runtime::new().unwrap()
+                    .block_on(execute(&db_address, &format!("DROP DATABASE {name} WITH (FORCE)")));
+            }).join().unwrap();
+        }
+    }
+}
+
+async fn execute(db_address: &str, sql: &str) {
+    let mut connection = PgConnection::connect(db_address).await.unwrap();
+    sqlx::query(sql).execute(&mut connection).await.unwrap();
+    connection.close().await.unwrap();
+}
+
+/// Replaces database name in postgres url
+fn with_database(db_address: &str, name: &str) -> String {
+    let (address, params) = match db_address.split_once('?') {
+        Some((address, params)) => (address, format!("?{params}")),
+        None => (db_address, String::new()),
+    };
+    let (server, _) = address.rsplit_once('/').unwrap();
+    format!("{server}/{name}{params}")
+}
+
+fn logger() -> slog::Logger {
+    slog::Logger::root(slog::Discard, slog::o!())
+}
+
+/// Same middlewares and routes as the server, without access log.
+/// Login is throttled with default limits, api isn't limited, tokens are signed by `BEARER_KEY`,
+/// webhooks may be sent to local mock receiver
+pub fn app(storage: &Storage) -> App<impl ServiceFactory<ServiceRequest, Config = (), Response = ServiceResponse<impl MessageBody>, Error = actix_web::Error, InitError = ()>> {
+    app_with_limits(storage, RateLimits::new(LoginThrottleConfig::default(), None))
+}
+
+pub fn app_with_limits(storage: &Storage, rate_limits: RateLimits) -> App<impl ServiceFactory<ServiceRequest, Config = (), Response = ServiceResponse<impl MessageBody>, Error = actix_web::Error, InitError = ()>> {
+    app_with(storage, rate_limits, JwtKeys::hmac(BEARER_KEY.as_bytes()), Arc::new(TestMailer::default()), None, local_webhooks(), TokenCheck::default())
+}
+
+pub fn app_with_keys(storage: &Storage, jwt_keys: JwtKeys) -> App<impl ServiceFactory<ServiceRequest, Config = (), Response = ServiceResponse<impl MessageBody>, Error = actix_web::Error, InitError = ()>> {
+    app_with(storage, RateLimits::new(LoginThrottleConfig::default(), None), jwt_keys, Arc::new(TestMailer::default()), None, local_webhooks(), TokenCheck::default())
+}
+
+/// Mails sent by the app are recorded by `mailer`
+pub fn app_with_mailer(storage: &Storage, mailer: Arc<TestMailer>) -> App<impl ServiceFactory<ServiceRequest, Config = (), Response = ServiceResponse<impl MessageBody>, Error = actix_web::Error, InitError = ()>> {
+    app_with(storage, RateLimits::new(LoginThrottleConfig::default(), None), JwtKeys::hmac(BEARER_KEY.as_bytes()), mailer, None, local_webhooks(), TokenCheck::default())
+}
+
+/// OpenID Connect login with the provider
+pub fn app_with_oidc(storage: &Storage, oidc: OidcProvider) -> App<impl ServiceFactory<ServiceRequest, Config = (), Response = ServiceResponse<impl MessageBody>, Error = actix_web::Error, InitError = ()>> {
+    app_with(storage, RateLimits::new(LoginThrottleConfig::default(), None), JwtKeys::hmac(BEARER_KEY.as_bytes()), Arc::new(TestMailer::default()), Some(oidc), local_webhooks(), TokenCheck::default())
+}
+
+/// Webhook urls are checked as on the server
+pub fn app_with_webhook_config(storage: &Storage, webhook_config: WebhookConfig) -> App<impl ServiceFactory<ServiceRequest, Config = (), Response = ServiceResponse<impl MessageBody>, Error = actix_web::Error, InitError = ()>> {
+    app_with(storage, RateLimits::new(LoginThrottleConfig::default(), None), JwtKeys::hmac(BEARER_KEY.as_bytes()), Arc::new(TestMailer::default()), None, webhook_config, TokenCheck::default())
+}
+
+/// App, which checks tokens of WebSocket connections with the interval
+pub fn app_with_token_check(storage: &Storage, token_check: TokenCheck) -> App<impl ServiceFactory<ServiceRequest, Config = (), Response = ServiceResponse<impl MessageBody>, Error = actix_web::Error, InitError = ()>> {
+    app_with(storage, RateLimits::new(LoginThrottleConfig::default(), None), JwtKeys::hmac(BEARER_KEY.as_bytes()), Arc::new(TestMailer::default()), None, local_webhooks(), token_check)
+}
+
+fn local_webhooks() -> WebhookConfig {
+    WebhookConfig { allow_private_targets: true, ..WebhookConfig::default() }
+}
+
+fn app_with(storage: &Storage, rate_limits: RateLimits, jwt_keys: JwtKeys, mailer: Arc<dyn Mailer>, oidc: Option<OidcProvider>, webhook_config: WebhookConfig, token_check: TokenCheck) -> App<impl ServiceFactory<ServiceRequest, Config = (), Response = ServiceResponse<impl MessageBody>, Error = actix_web::Error, InitError = ()>> {
+    App::new()
+        .configure(routes::storage_data(storage.clone()))
+        .app_data(web::Data::new(rate_limits))
+        .app_data(web::Data::new(jwt_keys))
+        .app_data(web::Data::from(mailer))
+        .app_data(web::Data::new(webhook_config))
+        .app_data(web::Data::new(token_check))
+        .configure(|cfg| if let Some(oidc) = oidc {
+            cfg.app_data(web::Data::new(oidc));
+        })
+        .wrap(RequestId::new(logger()))
+        .wrap(Tracing)
+        .configure(routes::configure)
+}
+
+/// Records sent mails instead of delivering them
+#[derive(Default)]
+pub struct TestMailer {
+    mails: Mutex<Vec<Mail>>,
+}
+
+impl TestMailer {
+    /// Mails not taken by `wait_mail`
+    pub fn mails(&self) -> Vec<Mail> {
+        self.mails.lock().unwrap().clone()
+    }
+
+    /// Takes the oldest mail to the address, waits for it because some mails are sent after response
+    pub async fn wait_mail(&self, to: &str) -> Mail {
+        for _ in 0..100 {
+            {
+                let mut mails = self.mails.lock().unwrap();
+                if let Some(position) = mails.iter().position(|mail| mail.to == to) {
+                    return mails.remove(position);
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("no mail to {to}")
+    }
+}
+
+#[async_trait]
+impl Mailer for TestMailer {
+    async fn send(&self, mail: &Mail) -> anyhow::Result<()> {
+        self.mails.lock().unwrap().push(mail.clone());
+        Ok(())
+    }
+}
+
+/// Token from the `... token: <token>` line of the mail body
+pub fn mail_token(mail: &Mail) -> String {
+    mail.body.lines()
+        .find_map(|line| line.split_once("token: ").map(|(_, token)| token.trim().to_string()))
+        .unwrap_or_else(|| panic!("no token in mail: {}", mail.body))
+}
+
+pub struct Response {
+    pub status: StatusCode,
+    pub body: String,
+}
+
+impl Response {
+    pub fn json<T: DeserializeOwned>(&self) -> T {
+        serde_json::from_str(&self.body).unwrap_or_else(|e| panic!("{e}: {}", self.body))
+    }
+}
+
+pub async fn send<S, B>(app: &S, method: Method, uri: &str, token: Option<&str>, body: Option<Value>) -> Response
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody
+{
+    let mut request = test::TestRequest::default().method(method).uri(uri);
+
+    if let Some(token) = token {
+        request = request.insert_header(("Authorization", format!("Bearer {token}")));
+    }
+
+    if let Some(body) = body {
+        request = request.set_json(body);
+    }
+
+    let response = test::call_service(app, request.to_request()).await;
+    let status = response.status();
+    let body = String::from_utf8(test::read_body(response).await.to_vec()).unwrap();
+
+    Response { status, body }
+}
+
+/// Sends `multipart/form-data` with the fields as files
+pub async fn upload<S, B>(app: &S, uri: &str, token: &str, fields: &[(&str, &[u8])]) -> Response
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody
+{
+    upload_with_headers(app, uri, token, &[], fields).await
+}
+
+pub async fn upload_with_headers<S, B>(app: &S, uri: &str, token: &str, headers: &[(&str, &str)], fields: &[(&str, &[u8])]) -> Response
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody
+{
+    const BOUNDARY: &str = "test-upload-boundary";
+
+    let mut body = Vec::new();
+    for (name, value) in fields {
+        body.extend_from_slice(format!("--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"{name}\"; filename=\"{name}\"\r\n\r\n").as_bytes());
+        body.extend_from_slice(value);
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(format!("--{BOUNDARY}--\r\n").as_bytes());
+
+    let mut request = test::TestRequest::post()
+        .uri(uri)
+        .insert_header(("Authorization", format!("Bearer {token}")))
+        .insert_header(("Content-Type", format!("multipart/form-data; boundary={BOUNDARY}")));
+    for header in headers {
+        request = request.insert_header(*header);
+    }
+    let request = request.set_payload(body).to_request();
+
+    let response = test::call_service(app, request).await;
+    let status = response.status();
+    let body = String::from_utf8(test::read_body(response).await.to_vec()).unwrap();
+
+    Response { status, body }
+}
+
+pub async fn register<S, B>(app: &S, login: &str, password: &str) -> Response
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody
+{
+    send(app, Method::POST, "/api/user/register", None, Some(json!({ "login": login, "password": password }))).await
+}
+
+pub async fn login<S, B>(app: &S, login: &str, password: &str) -> Response
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody
+{
+    send(app, Method::POST, "/api/user/login", None, Some(json!({ "login": login, "password": password }))).await
+}
+
+/// Registers user with unique login and returns token
+pub async fn user_token<S, B>(app: &S) -> String
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody
+{
+    let login_name = format!("user_{}", Uuid::new_v4().simple());
+
+    let response = register(app, &login_name, "password1").await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    let response = login(app, &login_name, "password1").await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    response.body
+}
+
+/// Registers user, creates list with tasks at the end and returns token
+pub async fn user_with_tasks<S, B>(app: &S, descriptions: &[&str]) -> String
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody
+{
+    let token = user_token(app).await;
+
+    let response = send(app, Method::POST, "/api/list", Some(&token), Some(json!({ "name": "test" }))).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    for description in descriptions {
+        let response = send(app, Method::POST, "/api/task", Some(&token), Some(json!({ "description": description, "position": "end" }))).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    }
+
+    token
+}
+
+/// Tasks sorted by order, checks that orders are continuous
+pub async fn tasks<S, B>(app: &S, token: &str) -> Vec<Value>
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody
+{
+    let response = send(app, Method::GET, "/api/task", Some(token), None).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    let tasks: Vec<Value> = response.json();
+    let orders: Vec<i64> = tasks.iter().map(|task| task["order"].as_i64().unwrap()).collect();
+    assert_eq!(orders, (1..=tasks.len() as i64).collect::<Vec<_>>(), "orders must be continuous");
+
+    tasks
+}
+
+pub async fn descriptions<S, B>(app: &S, token: &str) -> Vec<String>
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody
+{
+    tasks(app, token).await.iter().map(|task| task["description"].as_str().unwrap().to_string()).collect()
+}
+
+pub async fn task_id<S, B>(app: &S, token: &str, description: &str) -> String
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody
+{
+    tasks(app, token).await.iter()
+        .find(|task| task["description"] == description)
+        .map(|task| task["id"].as_str().unwrap().to_string())
+        .unwrap_or_else(|| panic!("task \"{description}\" not found"))
+}
@@ -0,0 +1,242 @@
+//! Mock OpenID Connect issuer on ephemeral port: discovery, jwks, authorization endpoint,
+//! which logs in the configured user without interaction, and token endpoint with PKCE check.
+//! ID tokens are signed by `tests/keys/jwks/rsa-1.pem`
+
+use std::{
+    collections::HashMap,
+    net::TcpListener,
+    path::Path,
+    sync::{
+        Arc,
+        Mutex
+    }
+};
+
+use actix_web::{
+    dev::ServerHandle,
+    http::header,
+    web,
+    App,
+    HttpRequest,
+    HttpResponse,
+    HttpServer
+};
+use chrono::{
+    Duration,
+    Utc
+};
+use serde_json::json;
+use sha2::{
+    Digest,
+    Sha256
+};
+use uuid::Uuid;
+
+use todo_list_rs::utils::{
+    jwt::JwtKeys,
+    oidc::OidcConfig
+};
+
+pub const CLIENT_ID: &str = "todo-list";
+pub const CLIENT_SECRET: &str = "client-secret";
+pub const REDIRECT_URI: &str = "http://localhost/api/user/oidc/callback";
+
+/// User, who logs in at the issuer
+#[derive(Clone)]
+pub struct MockUser {
+    pub subject: String,
+    pub preferred_username: Option<String>,
+    pub email: Option<String>,
+}
+
+impl MockUser {
+    pub fn new(subject: &str, preferred_username: &str) -> Self {
+        Self {
+            subject: subject.to_string(),
+            preferred_username: Some(preferred_username.to_string()),
+            email: Some(format!("{preferred_username}@example.com")),
+        }
+    }
+}
+
+struct Authorization {
+    redirect_uri: String,
+    nonce: Option<String>,
+    code_challenge: String,
+    user: MockUser,
+}
+
+struct Behavior {
+    /// `None` - user denies access
+    user: Option<MockUser>,
+    audience: String,
+    nonce: Option<String>,
+}
+
+struct IssuerState {
+    url: String,
+    keys: JwtKeys,
+    behavior: Mutex<Behavior>,
+    /// code -> authorization
+    codes: Mutex<HashMap<String, Authorization>>,
+}
+
+pub struct MockIssuer {
+    state: Arc<IssuerState>,
+    handle: ServerHandle,
+}
+
+impl MockIssuer {
+    pub fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        let state = Arc::new(IssuerState {
+            url,
+            keys: JwtKeys::load(&Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/keys/jwks"), "rsa-1").unwrap(),
+            behavior: Mutex::new(Behavior { user: None, audience: CLIENT_ID.to_string(), nonce: None }),
+            codes: Mutex::new(HashMap::new()),
+        });
+
+        let server_state = state.clone();
+        let server = HttpServer::new(move || {
+                App::new()
+                    .app_data(web::Data::from(server_state.clone()))
+                    .route("/.well-known/openid-configuration", web::get().to(discovery))
+                    .route("/jwks", web::get().to(jwks))
+                    .route("/authorize", web::get().to(authorize))
+                    .route("/token", web::post().to(token))
+            })
+            .workers(1)
+            .disable_signals()
+            .listen(listener)
+            .unwrap()
+            .run();
+
+        let handle = server.handle();
+        actix_web::rt::spawn(server);
+
+        Self { state, handle }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.state.url
+    }
+
+    pub fn config(&self) -> OidcConfig {
+        OidcConfig {
+            issuer: self.state.url.clone(),
+            client_id: CLIENT_ID.to_string(),
+            client_secret: Some(CLIENT_SECRET.to_string()),
+            scopes: "openid email profile".to_string(),
+            redirect_uri: REDIRECT_URI.to_string(),
+        }
+    }
+
+    /// Next authorizations log in the user
+    pub fn set_user(&self, user: MockUser) {
+        self.state.behavior.lock().unwrap().user = Some(user);
+    }
+
+    /// Next authorizations are denied by user
+    pub fn deny(&self) {
+        self.state.behavior.lock().unwrap().user = None;
+    }
+
+    /// ID tokens are issued for other client
+    pub fn set_audience(&self, audience: &str) {
+        self.state.behavior.lock().unwrap().audience = audience.to_string();
+    }
+
+    /// ID tokens have the nonce instead of nonce of the authorization request
+    pub fn set_nonce(&self, nonce: &str) {
+        self.state.behavior.lock().unwrap().nonce = Some(nonce.to_string());
+    }
+
+    pub async fn stop(self) {
+        self.handle.stop(false).await;
+    }
+}
+
+async fn discovery(state: web::Data<IssuerState>) -> HttpResponse {
+    HttpResponse::Ok().json(json!({
+        "issuer": state.url,
+        "authorization_endpoint": format!("{}/authorize", state.url),
+        "token_endpoint": format!("{}/token", state.url),
+        "jwks_uri": format!("{}/jwks", state.url),
+        "response_types_supported": ["code"],
+        "subject_types_supported": ["public"],
+        "id_token_signing_alg_values_supported": ["RS256", "EdDSA"],
+        "code_challenge_methods_supported": ["S256"],
+    }))
+}
+
+async fn jwks(state: web::Data<IssuerState>) -> HttpResponse {
+    HttpResponse::Ok().json(state.keys.jwks())
+}
+
+async fn authorize(state: web::Data<IssuerState>, query: web::Query<HashMap<String, String>>) -> HttpResponse {
+    let param = |name: &str| query.get(name).cloned().unwrap_or_default();
+
+    if param("response_type") != "code" || param("client_id") != CLIENT_ID || param("code_challenge_method") != "S256" {
+        return HttpResponse::BadRequest().body("invalid authorization request");
+    }
+
+    let redirect_uri = param("redirect_uri");
+    let location = match state.behavior.lock().unwrap().user.clone() {
+        Some(user) => {
+            let code = Uuid::new_v4().to_string();
+            state.codes.lock().unwrap().insert(code.clone(), Authorization {
+                redirect_uri: redirect_uri.clone(),
+                nonce: query.get("nonce").cloned(),
+                code_challenge: param("code_challenge"),
+                user,
+            });
+            format!("{redirect_uri}?code={code}&state={}", param("state"))
+        },
+        None => format!("{redirect_uri}?error=access_denied&state={}", param("state")),
+    };
+
+    HttpResponse::Found().insert_header((header::LOCATION, location)).finish()
+}
+
+async fn token(state: web::Data<IssuerState>, form: web::Form<HashMap<String, String>>, req: HttpRequest) -> HttpResponse {
+    let param = |name: &str| form.get(name).cloned().unwrap_or_default();
+
+    let basic = format!("Basic {}", base64::encode(format!("{CLIENT_ID}:{CLIENT_SECRET}")));
+    if req.headers().get(header::AUTHORIZATION).and_then(|value| value.to_str().ok()) != Some(basic.as_str()) {
+        return HttpResponse::Unauthorized().json(json!({ "error": "invalid_client" }));
+    }
+
+    // code is single-use
+    let authorization = match state.codes.lock().unwrap().remove(&param("code")) {
+        Some(authorization) if param("grant_type") == "authorization_code" && param("redirect_uri") == authorization.redirect_uri => authorization,
+        _ => return HttpResponse::BadRequest().json(json!({ "error": "invalid_grant" })),
+    };
+
+    let challenge = base64::encode_config(Sha256::digest(param("code_verifier").as_bytes()), base64::URL_SAFE_NO_PAD);
+    if challenge != authorization.code_challenge {
+        return HttpResponse::BadRequest().json(json!({ "error": "invalid_grant", "error_description": "PKCE verification failed" }));
+    }
+
+    let behavior = state.behavior.lock().unwrap();
+    let now = Utc::now();
+    let id_token = state.keys.sign(&json!({
+        "iss": state.url,
+        "sub": authorization.user.subject,
+        "aud": behavior.audience,
+        "exp": (now + Duration::minutes(5)).timestamp(),
+        "iat": now.timestamp(),
+        "nonce": behavior.nonce.clone().or(authorization.nonce),
+        "email": authorization.user.email,
+        "email_verified": true,
+        "preferred_username": authorization.user.preferred_username,
+    })).unwrap();
+
+    HttpResponse::Ok().json(json!({
+        "access_token": Uuid::new_v4().to_string(),
+        "token_type": "Bearer",
+        "expires_in": 300,
+        "id_token": id_token,
+    }))
+}
@@ -0,0 +1,135 @@
+//! Mock webhook receiver on ephemeral port, records requests and responds with configured statuses
+
+use std::{
+    collections::VecDeque,
+    net::TcpListener,
+    sync::{
+        Arc,
+        Mutex
+    },
+    time::Duration
+};
+
+use actix_web::{
+    dev::ServerHandle,
+    web,
+    App,
+    HttpRequest,
+    HttpResponse,
+    HttpServer
+};
+use serde_json::Value;
+
+use todo_list_rs::{
+    db::Storage,
+    utils::webhook::{
+        WebhookConfig,
+        WebhookDispatcher,
+        DELIVERY_HEADER,
+        EVENT_HEADER,
+        SIGNATURE_HEADER,
+        TIMESTAMP_HEADER
+    }
+};
+
+#[derive(Debug, Clone)]
+pub struct ReceivedRequest {
+    pub event: String,
+    pub delivery_id: String,
+    pub timestamp: i64,
+    pub signature: String,
+    pub body: String,
+}
+
+impl ReceivedRequest {
+    pub fn json(&self) -> Value {
+        serde_json::from_str(&self.body).unwrap()
+    }
+}
+
+#[derive(Default)]
+struct ReceiverState {
+    requests: Mutex<Vec<ReceivedRequest>>,
+    /// Statuses of next responses, then 200
+    statuses: Mutex<VecDeque<u16>>,
+}
+
+pub struct MockReceiver {
+    url: String,
+    state: Arc<ReceiverState>,
+    handle: ServerHandle,
+}
+
+impl MockReceiver {
+    pub fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+
+        let state = Arc::new(ReceiverState::default());
+
+        let server_state = state.clone();
+        let server = HttpServer::new(move || {
+                App::new()
+                    .app_data(web::Data::from(server_state.clone()))
+                    .route("/hook", web::post().to(receive))
+            })
+            .workers(1)
+            .disable_signals()
+            .listen(listener)
+            .unwrap()
+            .run();
+
+        let handle = server.handle();
+        actix_web::rt::spawn(server);
+
+        Self { url, state, handle }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Next requests are answered with the statuses in order
+    pub fn respond_with(&self, statuses: &[u16]) {
+        self.state.statuses.lock().unwrap().extend(statuses);
+    }
+
+    /// Takes received requests
+    pub fn take_requests(&self) -> Vec<ReceivedRequest> {
+        std::mem::take(&mut *self.state.requests.lock().unwrap())
+    }
+
+    pub async fn stop(self) {
+        self.handle.stop(false).await;
+    }
+}
+
+async fn receive(state: web::Data<ReceiverState>, req: HttpRequest, body: String) -> HttpResponse {
+    let header = |name: &str| req.headers().get(name)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+
+    state.requests.lock().unwrap().push(ReceivedRequest {
+        event: header(EVENT_HEADER),
+        delivery_id: header(DELIVERY_HEADER),
+        timestamp: header(TIMESTAMP_HEADER).parse().unwrap_or_default(),
+        signature: header(SIGNATURE_HEADER),
+        body,
+    });
+
+    let status = state.statuses.lock().unwrap().pop_front().unwrap_or(200);
+    HttpResponse::build(actix_web::http::StatusCode::from_u16(status).unwrap()).finish()
+}
+
+/// Dispatcher without retry delays, so failed deliveries are due again at once.
+/// Deliveries may be sent to local mock receiver
+pub fn dispatcher(storage: &Storage, max_attempts: i32, disable_after: i32) -> WebhookDispatcher {
+    dispatcher_with(storage, WebhookConfig { max_attempts, disable_after, allow_private_targets: true, ..WebhookConfig::default() })
+}
+
+pub fn dispatcher_with(storage: &Storage, config: WebhookConfig) -> WebhookDispatcher {
+    let config = WebhookConfig { retry_base: Duration::ZERO, timeout: Duration::from_secs(5), ..config };
+
+    WebhookDispatcher::new(storage.webhooks.clone(), config, slog::Logger::root(slog::Discard, slog::o!())).unwrap()
+}
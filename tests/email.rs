@@ -0,0 +1,220 @@
+mod common;
+
+use std::sync::Arc;
+
+use actix_web::{
+    http::{
+        Method,
+        StatusCode
+    },
+    test
+};
+use chrono::{
+    Duration,
+    Utc
+};
+use serde_json::{
+    json,
+    Value
+};
+use uuid::Uuid;
+
+use common::*;
+use todo_list_rs::models::{
+    email_token_hash,
+    EmailTokenPurpose
+};
+
+#[actix_web::test]
+async fn verify_email() {
+    let db = TestDb::new().await;
+    let mailer = Arc::new(TestMailer::default());
+    let app = test::init_service(app_with_mailer(&db.storage, mailer.clone())).await;
+
+    let user_id = register(&app, "alice", "password1").await.body;
+    let token = login(&app, "alice", "password1").await.body;
+
+    let response = send(&app, Method::PUT, "/api/user/email", Some(&token), Some(json!({ "email": "not an email" }))).await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let response = send(&app, Method::PUT, "/api/user/email", Some(&token), Some(json!({ "email": "Alice@Example.com" }))).await;
+    assert_eq!(response.status, StatusCode::ACCEPTED, "{}", response.body);
+
+    let response = send(&app, Method::GET, "/api/user/me", Some(&token), None).await;
+    assert_eq!(response.json::<Value>()["email"], "alice@example.com");
+    assert_eq!(response.json::<Value>()["email_verified"], false);
+
+    let mail_token = mail_token(&mailer.wait_mail("alice@example.com").await);
+
+    let response = send(&app, Method::POST, "/api/user/email/verify", None, Some(json!({ "token": "wrong" }))).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+
+    let response = send(&app, Method::POST, "/api/user/email/verify", None, Some(json!({ "token": mail_token }))).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(response.body, user_id);
+
+    let response = send(&app, Method::GET, "/api/user/me", Some(&token), None).await;
+    assert_eq!(response.json::<Value>()["email_verified"], true);
+
+    // token is single-use
+    let response = send(&app, Method::POST, "/api/user/email/verify", None, Some(json!({ "token": mail_token }))).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn verify_changed_or_taken_email() {
+    let db = TestDb::new().await;
+    let mailer = Arc::new(TestMailer::default());
+    let app = test::init_service(app_with_mailer(&db.storage, mailer.clone())).await;
+
+    let alice = user_token(&app).await;
+    let bob = user_token(&app).await;
+
+    // token of previous email is not accepted
+    send(&app, Method::PUT, "/api/user/email", Some(&alice), Some(json!({ "email": "first@example.com" }))).await;
+    let first_token = mail_token(&mailer.wait_mail("first@example.com").await);
+    send(&app, Method::PUT, "/api/user/email", Some(&alice), Some(json!({ "email": "second@example.com" }))).await;
+
+    let response = send(&app, Method::POST, "/api/user/email/verify", None, Some(json!({ "token": first_token }))).await;
+    assert_eq!(response.status, StatusCode::CONFLICT);
+
+    // other verification tokens of the user are removed with the used one
+    let second_token = mail_token(&mailer.wait_mail("second@example.com").await);
+    let response = send(&app, Method::POST, "/api/user/email/verify", None, Some(json!({ "token": second_token }))).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+
+    send(&app, Method::PUT, "/api/user/email", Some(&alice), Some(json!({ "email": "shared@example.com" }))).await;
+    let alice_token = mail_token(&mailer.wait_mail("shared@example.com").await);
+
+    send(&app, Method::PUT, "/api/user/email", Some(&bob), Some(json!({ "email": "shared@example.com" }))).await;
+    let bob_token = mail_token(&mailer.wait_mail("shared@example.com").await);
+
+    let response = send(&app, Method::POST, "/api/user/email/verify", None, Some(json!({ "token": bob_token }))).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    // email is verified only by one user
+    let response = send(&app, Method::POST, "/api/user/email/verify", None, Some(json!({ "token": alice_token }))).await;
+    assert_eq!(response.status, StatusCode::CONFLICT);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn reset_password() {
+    let db = TestDb::new().await;
+    let mailer = Arc::new(TestMailer::default());
+    let app = test::init_service(app_with_mailer(&db.storage, mailer.clone())).await;
+
+    let user_id = register(&app, "alice", "password1").await.body;
+    let session = login(&app, "alice", "password1").await.body;
+
+    send(&app, Method::PUT, "/api/user/email", Some(&session), Some(json!({ "email": "alice@example.com" }))).await;
+    let verify_token = mail_token(&mailer.wait_mail("alice@example.com").await);
+    send(&app, Method::POST, "/api/user/email/verify", None, Some(json!({ "token": verify_token }))).await;
+
+    let response = send(&app, Method::POST, "/api/user/password/forgot", None, Some(json!({ "email": "ALICE@example.com" }))).await;
+    assert_eq!(response.status, StatusCode::ACCEPTED);
+
+    let mail = mailer.wait_mail("alice@example.com").await;
+    assert_eq!(mail.subject, "Password reset");
+    let reset_token = mail_token(&mail);
+
+    // verification token can't reset password
+    let response = send(&app, Method::POST, "/api/user/password/reset", None, Some(json!({ "token": verify_token, "new_password": "password2" }))).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+
+    let response = send(&app, Method::POST, "/api/user/password/reset", None, Some(json!({ "token": reset_token, "new_password": "short" }))).await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let response = send(&app, Method::POST, "/api/user/password/reset", None, Some(json!({ "token": reset_token, "new_password": "password2" }))).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(response.body, user_id);
+
+    // sessions are revoked, only new password is valid
+    let response = send(&app, Method::GET, "/api/list", Some(&session), None).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(login(&app, "alice", "password1").await.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(login(&app, "alice", "password2").await.status, StatusCode::OK);
+
+    // token is single-use
+    let response = send(&app, Method::POST, "/api/user/password/reset", None, Some(json!({ "token": reset_token, "new_password": "password3" }))).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn reset_token_expires() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+
+    let user_id: Uuid = register(&app, "alice", "password1").await.body.parse().unwrap();
+
+    db.storage.email_tokens.insert_email_token(user_id, &email_token_hash("expired"), EmailTokenPurpose::ResetPassword, "alice@example.com", Utc::now() - Duration::seconds(1)).await.unwrap();
+
+    let response = send(&app, Method::POST, "/api/user/password/reset", None, Some(json!({ "token": "expired", "new_password": "password2" }))).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert_eq!(login(&app, "alice", "password1").await.status, StatusCode::OK);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn new_reset_token_replaces_previous() {
+    let db = TestDb::new().await;
+    let mailer = Arc::new(TestMailer::default());
+    let app = test::init_service(app_with_mailer(&db.storage, mailer.clone())).await;
+
+    let session = user_token(&app).await;
+    send(&app, Method::PUT, "/api/user/email", Some(&session), Some(json!({ "email": "alice@example.com" }))).await;
+    let verify_token = mail_token(&mailer.wait_mail("alice@example.com").await);
+    send(&app, Method::POST, "/api/user/email/verify", None, Some(json!({ "token": verify_token }))).await;
+
+    send(&app, Method::POST, "/api/user/password/forgot", None, Some(json!({ "email": "alice@example.com" }))).await;
+    let first_token = mail_token(&mailer.wait_mail("alice@example.com").await);
+    assert_ne!(first_token, verify_token);
+
+    send(&app, Method::POST, "/api/user/password/forgot", None, Some(json!({ "email": "alice@example.com" }))).await;
+    let second_token = mail_token(&mailer.wait_mail("alice@example.com").await);
+
+    let response = send(&app, Method::POST, "/api/user/password/reset", None, Some(json!({ "token": second_token, "new_password": "password2" }))).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    // other reset tokens of the user are removed with the used one
+    let response = send(&app, Method::POST, "/api/user/password/reset", None, Some(json!({ "token": first_token, "new_password": "password3" }))).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn forgot_password_does_not_reveal_users() {
+    let db = TestDb::new().await;
+    let mailer = Arc::new(TestMailer::default());
+    let app = test::init_service(app_with_mailer(&db.storage, mailer.clone())).await;
+
+    // not verified email can't be used for reset
+    let session = user_token(&app).await;
+    send(&app, Method::PUT, "/api/user/email", Some(&session), Some(json!({ "email": "alice@example.com" }))).await;
+    mailer.wait_mail("alice@example.com").await;
+
+    for email in ["alice@example.com", "nobody@example.com"] {
+        let response = send(&app, Method::POST, "/api/user/password/forgot", None, Some(json!({ "email": email }))).await;
+        assert_eq!(response.status, StatusCode::ACCEPTED);
+        assert_eq!(response.body, "");
+    }
+
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    assert_eq!(mailer.mails(), vec![]);
+
+    // requests for one email are limited
+    let mut statuses = Vec::new();
+    for _ in 0..10 {
+        statuses.push(send(&app, Method::POST, "/api/user/password/forgot", None, Some(json!({ "email": "nobody@example.com" }))).await.status);
+    }
+    assert!(statuses.contains(&StatusCode::TOO_MANY_REQUESTS), "{statuses:?}");
+
+    db.close().await;
+}
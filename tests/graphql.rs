@@ -0,0 +1,391 @@
+mod common;
+
+use std::{
+    net::SocketAddr,
+    time::Duration
+};
+
+use actix_codec::Framed;
+use actix_http::{
+    ws,
+    Request
+};
+use actix_web::{
+    body::MessageBody,
+    dev::{
+        Service,
+        ServiceResponse
+    },
+    http::{
+        Method,
+        StatusCode
+    },
+    test,
+    web,
+    HttpServer
+};
+use futures::{
+    SinkExt,
+    StreamExt
+};
+use serde_json::{
+    json,
+    Value
+};
+use tokio::{
+    io::{
+        AsyncReadExt,
+        AsyncWriteExt
+    },
+    net::TcpStream
+};
+use uuid::Uuid;
+
+use todo_list_rs::{
+    db::Storage,
+    graphql::{
+        self,
+        GraphqlContext
+    },
+    middlewares::{
+        BearerAuth,
+        TokenCheck
+    }
+};
+
+use common::*;
+
+/// Response of the query, asserts that HTTP status is 200
+async fn graphql<S, B>(app: &S, token: &str, query: &str, variables: Value) -> Value
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody
+{
+    let response = send(app, Method::POST, "/graphql", Some(token), Some(json!({ "query": query, "variables": variables }))).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    response.json()
+}
+
+/// Test app on ephemeral port, WebSocket needs real connection
+fn serve(storage: &Storage, token_check: TokenCheck) -> SocketAddr {
+    let storage = storage.clone();
+    let server = HttpServer::new(move || app_with_token_check(&storage, token_check))
+        .workers(1)
+        .disable_signals()
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+
+    let addr = server.addrs()[0];
+    actix_web::rt::spawn(server.run());
+    addr
+}
+
+type WsClient = Framed<TcpStream, ws::Codec>;
+
+/// Connection of `graphql-transport-ws` protocol, upgrade request has no `Authorization` header
+async fn connect(addr: SocketAddr) -> WsClient {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let request = format!(
+        "GET /graphql/ws HTTP/1.1\r\nHost: {addr}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+        Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\nSec-WebSocket-Protocol: graphql-transport-ws\r\n\r\n"
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+
+    // server sends nothing after the upgrade until the client does
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        head.push(stream.read_u8().await.unwrap());
+    }
+    let head = String::from_utf8(head).unwrap();
+    assert!(head.starts_with("HTTP/1.1 101"), "{head}");
+
+    Framed::new(stream, ws::Codec::new().client_mode())
+}
+
+async fn send_ws(client: &mut WsClient, message: Value) {
+    client.send(ws::Message::Text(message.to_string().into())).await.unwrap();
+}
+
+/// Next message, or close code if the server closed the connection
+async fn receive_ws(client: &mut WsClient) -> Result<Value, u16> {
+    loop {
+        let frame = tokio::time::timeout(Duration::from_secs(5), client.next()).await
+            .expect("no message")
+            .expect("connection is closed without close frame")
+            .unwrap();
+
+        match frame {
+            ws::Frame::Text(text) => return Ok(serde_json::from_slice(&text).unwrap()),
+            ws::Frame::Close(reason) => return Err(reason.map(|reason| u16::from(reason.code)).unwrap_or_default()),
+            _ => continue,
+        }
+    }
+}
+
+fn descriptions_of(tasks: &Value) -> Vec<&str> {
+    tasks.as_array().unwrap().iter().map(|task| task["description"].as_str().unwrap()).collect()
+}
+
+#[actix_web::test]
+async fn nested_query_returns_list_with_tasks() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_with_tasks(&app, &["buy milk", "plan trip"]).await;
+    let milk = task_id(&app, &token, "buy milk").await;
+    let trip = task_id(&app, &token, "plan trip").await;
+
+    let query = r#"query($milk: UUID!, $ids: [UUID!]!) {
+        me { login list { name tasks { id description order } } }
+        milk: task(id: $milk) { description list { name } }
+        tasks(ids: $ids) { description }
+    }"#;
+    let response = graphql(&app, &token, query, json!({ "milk": milk, "ids": [trip, Uuid::new_v4(), milk] })).await;
+    assert!(response.get("errors").is_none(), "{response}");
+
+    let data = &response["data"];
+    assert!(data["me"]["login"].as_str().unwrap().starts_with("user_"));
+    assert_eq!(data["me"]["list"]["name"], "test");
+    assert_eq!(descriptions_of(&data["me"]["list"]["tasks"]), ["buy milk", "plan trip"]);
+    assert_eq!(data["me"]["list"]["tasks"][1]["order"], 2);
+    assert_eq!(data["milk"]["list"]["name"], "test");
+    assert_eq!(descriptions_of(&data["tasks"]), ["plan trip", "buy milk"]);
+}
+
+#[actix_web::test]
+async fn other_user_tasks_are_not_found() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_with_tasks(&app, &["buy milk"]).await;
+    let milk = task_id(&app, &token, "buy milk").await;
+    let other = user_with_tasks(&app, &[]).await;
+
+    let response = graphql(&app, &other, "query($id: UUID!) { task(id: $id) { description } }", json!({ "id": milk })).await;
+    assert!(response.get("errors").is_none(), "{response}");
+    assert_eq!(response["data"]["task"], Value::Null);
+}
+
+#[actix_web::test]
+async fn mutations_change_tasks() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_token(&app).await;
+
+    let response = graphql(&app, &token, r#"mutation { createList(name: "home") { name tasks { id } } }"#, json!({})).await;
+    assert!(response.get("errors").is_none(), "{response}");
+    assert_eq!(response["data"]["createList"]["name"], "home");
+
+    let add = "mutation($description: String!, $position: TaskPositionInput) { addTask(description: $description, position: $position) { id order } }";
+    let response = graphql(&app, &token, add, json!({ "description": "buy milk" })).await;
+    let milk = response["data"]["addTask"]["id"].as_str().unwrap().to_string();
+
+    let response = graphql(&app, &token, add, json!({ "description": "plan trip", "position": { "before": milk } })).await;
+    assert_eq!(response["data"]["addTask"]["order"], 1, "{response}");
+    let trip = response["data"]["addTask"]["id"].as_str().unwrap().to_string();
+    assert_eq!(descriptions(&app, &token).await, ["plan trip", "buy milk"]);
+
+    let query = r#"mutation($id: UUID!) { updateTask(id: $id, description: "buy oat milk") { description } }"#;
+    let response = graphql(&app, &token, query, json!({ "id": milk })).await;
+    assert_eq!(response["data"]["updateTask"]["description"], "buy oat milk", "{response}");
+
+    let query = "mutation($id: UUID!, $details: TaskDetailsInput!) { updateTaskDetails(id: $id, details: $details) { dueAt completedAt priority rrule } }";
+    let details = json!({ "dueAt": "2026-10-20T09:00:00Z", "priority": 1, "rrule": "FREQ=WEEKLY;BYDAY=TU" });
+    let response = graphql(&app, &token, query, json!({ "id": milk, "details": details })).await;
+    assert_eq!(response["data"]["updateTaskDetails"], json!({ "dueAt": "2026-10-20T09:00:00+00:00", "completedAt": null, "priority": 1, "rrule": "FREQ=WEEKLY;BYDAY=TU" }), "{response}");
+
+    let response = graphql(&app, &token, query, json!({ "id": milk, "details": { "priority": 10 } })).await;
+    assert_eq!(response["errors"][0]["extensions"]["status_code"], "422 Unprocessable Entity", "{response}");
+    assert_eq!(response["data"], Value::Null);
+
+    let query = "mutation($id: UUID!, $after: UUID!) { moveTask(id: $id, position: { after: $after }) { order } }";
+    let response = graphql(&app, &token, query, json!({ "id": trip, "after": milk })).await;
+    assert_eq!(response["data"]["moveTask"]["order"], 2, "{response}");
+    assert_eq!(descriptions(&app, &token).await, ["buy oat milk", "plan trip"]);
+
+    let query = "mutation($id: UUID!) { deleteTask(id: $id) { description } }";
+    let response = graphql(&app, &token, query, json!({ "id": trip })).await;
+    assert_eq!(response["data"]["deleteTask"]["description"], "plan trip", "{response}");
+
+    let response = graphql(&app, &token, r#"mutation { updateList(name: "shop") { name tasks { description } } }"#, json!({})).await;
+    assert_eq!(response["data"]["updateList"]["name"], "shop", "{response}");
+    assert_eq!(descriptions_of(&response["data"]["updateList"]["tasks"]), ["buy oat milk"]);
+
+    let response = graphql(&app, &token, "mutation { deleteList }", json!({})).await;
+    assert_eq!(response["data"]["deleteList"], 1, "{response}");
+
+    let response = graphql(&app, &token, "{ list { name } }", json!({})).await;
+    assert_eq!(response["data"]["list"], Value::Null, "{response}");
+}
+
+#[actix_web::test]
+async fn errors_have_status_code() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_with_tasks(&app, &["buy milk"]).await;
+
+    let response = graphql(&app, &token, r#"mutation { addTask(description: "") { id } }"#, json!({})).await;
+    assert_eq!(response["data"], Value::Null);
+    assert_eq!(response["errors"][0]["extensions"]["status_code"], "422 Unprocessable Entity", "{response}");
+    assert!(response["errors"][0]["extensions"]["fields"]["description"].is_array(), "{response}");
+
+    let query = "mutation($id: UUID!) { deleteTask(id: $id) { id } }";
+    let response = graphql(&app, &token, query, json!({ "id": Uuid::new_v4() })).await;
+    assert_eq!(response["errors"][0]["extensions"]["status_code"], "404 Not Found", "{response}");
+
+    let response = graphql(&app, &token, r#"mutation { createList(name: "second") { id } }"#, json!({})).await;
+    assert_eq!(response["errors"][0]["extensions"]["status_code"], "400 Bad Request", "{response}");
+    assert_eq!(descriptions(&app, &token).await, ["buy milk"]);
+}
+
+#[actix_web::test]
+async fn mutations_require_token_scope() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_with_tasks(&app, &["buy milk"]).await;
+    let created: Value = send(&app, Method::POST, "/api/user/tokens", Some(&token), Some(json!({ "name": "phone", "scopes": ["read-only"] }))).await.json();
+    let access_token = created["token"].as_str().unwrap();
+
+    let response = graphql(&app, access_token, "{ list { tasks { description } } }", json!({})).await;
+    assert_eq!(descriptions_of(&response["data"]["list"]["tasks"]), ["buy milk"], "{response}");
+
+    let response = graphql(&app, access_token, r#"mutation { addTask(description: "plan trip") { id } }"#, json!({})).await;
+    assert_eq!(response["errors"][0]["extensions"]["status_code"], "403 Forbidden", "{response}");
+    assert_eq!(descriptions(&app, &token).await, ["buy milk"]);
+}
+
+#[actix_web::test]
+async fn request_without_token_is_rejected() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+
+    let response = send(&app, Method::POST, "/graphql", None, Some(json!({ "query": "{ me { id } }" }))).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST, "{}", response.body);
+
+    let response = send(&app, Method::POST, "/graphql", Some("invalid"), Some(json!({ "query": "{ me { id } }" }))).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED, "{}", response.body);
+}
+
+#[actix_web::test]
+async fn deep_query_is_rejected() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_with_tasks(&app, &["buy milk"]).await;
+
+    let mut query = "name".to_string();
+    for _ in 0..10 {
+        query = format!("tasks {{ list {{ {query} }} }}");
+    }
+
+    let response = graphql(&app, &token, &format!("{{ list {{ {query} }} }}"), json!({})).await;
+    assert_eq!(response["errors"][0]["message"], "Query is nested too deep.", "{response}");
+}
+
+#[actix_web::test]
+async fn graphiql_is_disabled_by_default() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+
+    let response = send(&app, Method::GET, "/graphql", None, None).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn websocket_requires_graphql_protocol() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_token(&app).await;
+
+    let response = send(&app, Method::GET, "/graphql/ws", Some(&token), None).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST, "{}", response.body);
+}
+
+#[actix_web::test]
+async fn subscription_gets_changes_of_user() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_with_tasks(&app, &["buy milk"]).await;
+    let milk = task_id(&app, &token, "buy milk").await;
+    let other = user_with_tasks(&app, &["plan trip"]).await;
+
+    let response = graphql(&app, &token, "{ me { id } }", json!({})).await;
+    let user_id: Uuid = response["data"]["me"]["id"].as_str().unwrap().parse().unwrap();
+
+    let context = GraphqlContext {
+        users: web::Data::from(db.storage.users.clone()),
+        lists: web::Data::from(db.storage.lists.clone()),
+        tasks: web::Data::from(db.storage.tasks.clone()),
+        events: web::Data::new(db.storage.events.clone()),
+        auth: BearerAuth { user_id, scopes: None },
+        logger: slog::Logger::root(slog::Discard, slog::o!()),
+    };
+
+    let schema = graphql::schema();
+    let mut request = async_graphql::Request::new("subscription { changes(events: [TASK_UPDATED]) { event data } }");
+    request.data = context.into_data();
+    let mut stream = schema.execute_stream(request);
+    // subscription starts on the first poll
+    assert!(futures::poll!(stream.next()).is_pending());
+
+    let trip = task_id(&app, &other, "plan trip").await;
+    let response = send(&app, Method::PATCH, &format!("/api/task/{trip}"), Some(&other), Some(json!({ "description": "pack bags" }))).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let response = send(&app, Method::POST, "/api/task", Some(&token), Some(json!({ "description": "plan trip", "position": "end" }))).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let response = send(&app, Method::PATCH, &format!("/api/task/{milk}"), Some(&token), Some(json!({ "description": "buy oat milk" }))).await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    let response = tokio::time::timeout(Duration::from_secs(5), stream.next()).await
+        .expect("no change event")
+        .unwrap();
+    assert!(response.errors.is_empty(), "{:?}", response.errors);
+
+    let change = response.data.into_json().unwrap();
+    assert_eq!(change["changes"]["event"], "TASK_UPDATED");
+    assert_eq!(change["changes"]["data"]["description"], "buy oat milk");
+}
+
+#[actix_web::test]
+async fn websocket_is_authorized_by_connection_init() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_with_tasks(&app, &["buy milk"]).await;
+    let milk = task_id(&app, &token, "buy milk").await;
+    let addr = serve(&db.storage, TokenCheck { interval: Duration::from_secs(1) });
+
+    let mut client = connect(addr).await;
+    send_ws(&mut client, json!({ "type": "connection_init", "payload": { "Authorization": "Bearer invalid" } })).await;
+    assert_eq!(receive_ws(&mut client).await, Err(1002));
+
+    let mut client = connect(addr).await;
+    send_ws(&mut client, json!({ "type": "connection_init", "payload": { "Authorization": format!("Bearer {token}") } })).await;
+    assert_eq!(receive_ws(&mut client).await.unwrap()["type"], "connection_ack");
+
+    send_ws(&mut client, json!({ "id": "1", "type": "subscribe", "payload": { "query": "subscription { changes(events: [TASK_UPDATED]) { data } }" } })).await;
+    // subscription starts, when server polls it
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let response = send(&app, Method::PATCH, &format!("/api/task/{milk}"), Some(&token), Some(json!({ "description": "buy oat milk" }))).await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    let message = receive_ws(&mut client).await.unwrap();
+    assert_eq!(message["type"], "next");
+    assert_eq!(message["payload"]["data"]["changes"]["data"]["description"], "buy oat milk");
+
+    // password change revokes the token, connection is closed on the next check
+    let response = send(&app, Method::PATCH, "/api/user/password", Some(&token), Some(json!({ "old_password": "password1", "new_password": "password3" }))).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(receive_ws(&mut client).await, Err(4401));
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn websocket_without_connection_init_is_closed() {
+    let db = TestDb::new().await;
+    let addr = serve(&db.storage, TokenCheck { interval: Duration::from_secs(1) });
+
+    let mut client = connect(addr).await;
+    assert_eq!(receive_ws(&mut client).await, Err(4408));
+
+    db.close().await;
+}
@@ -0,0 +1,303 @@
+mod common;
+
+use std::time::Duration;
+
+use actix_web::{
+    http::{
+        Method,
+        StatusCode
+    },
+    test,
+    web
+};
+use chrono::Utc;
+use serde_json::{
+    json,
+    Value
+};
+use tonic::{
+    transport::{
+        server::TcpIncoming,
+        Channel
+    },
+    Code,
+    Request
+};
+use uuid::Uuid;
+
+use todo_list_rs::{
+    db::Storage,
+    grpc::{
+        self,
+        proto::{
+            self,
+            login_response,
+            task_position::Position,
+            task_service_client::TaskServiceClient,
+            todo_list_service_client::TodoListServiceClient,
+            user_service_client::UserServiceClient
+        },
+        GrpcState
+    },
+    middlewares::TokenCheck,
+    utils::{
+        jwt::JwtKeys,
+        rate_limit::{
+            LoginThrottleConfig,
+            RateLimits
+        },
+        totp
+    }
+};
+
+use common::*;
+
+/// gRPC server of the storage on ephemeral port, tokens are signed by `BEARER_KEY` as tokens of test app
+async fn serve(storage: &Storage) -> Channel {
+    serve_with_token_check(storage, TokenCheck::default()).await
+}
+
+async fn serve_with_token_check(storage: &Storage, token_check: TokenCheck) -> Channel {
+    let state = GrpcState::new(
+        storage,
+        web::Data::new(JwtKeys::hmac(BEARER_KEY.as_bytes())),
+        web::Data::new(RateLimits::new(LoginThrottleConfig::default(), None)),
+        token_check,
+        slog::Logger::root(slog::Discard, slog::o!())
+    );
+
+    let incoming = TcpIncoming::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = incoming.local_addr().unwrap();
+    tokio::spawn(grpc::router(state).serve_with_incoming(incoming));
+
+    Channel::from_shared(format!("http://{addr}")).unwrap().connect().await.unwrap()
+}
+
+fn authorized<T>(message: T, token: &str) -> Request<T> {
+    let mut request = Request::new(message);
+    request.metadata_mut().insert("authorization", format!("Bearer {token}").parse().unwrap());
+    request
+}
+
+fn position(position: Position) -> Option<proto::TaskPosition> {
+    Some(proto::TaskPosition { position: Some(position) })
+}
+
+fn task_descriptions(tasks: &proto::TaskList) -> Vec<&str> {
+    tasks.tasks.iter().map(|task| task.description.as_str()).collect()
+}
+
+#[actix_web::test]
+async fn register_login_and_profile() {
+    let db = TestDb::new().await;
+    let mut users = UserServiceClient::new(serve(&db.storage).await);
+    let login_name = format!("user_{}", Uuid::new_v4().simple());
+
+    let registered = users.register(proto::RegisterRequest { login: login_name.clone(), password: "password1".to_string() }).await.unwrap().into_inner();
+
+    let status = users.login(proto::LoginRequest { login: login_name.clone(), password: "wrong".to_string() }).await.unwrap_err();
+    assert_eq!(status.code(), Code::Unauthenticated);
+
+    let response = users.login(proto::LoginRequest { login: login_name.clone(), password: "password1".to_string() }).await.unwrap().into_inner();
+    let token = match response.result {
+        Some(login_response::Result::Token(token)) => token,
+        result => panic!("unexpected login result: {result:?}"),
+    };
+
+    let profile = users.get_profile(authorized(proto::GetProfileRequest {}, &token)).await.unwrap().into_inner();
+    assert_eq!(profile.id, registered.user_id);
+    assert_eq!(profile.login, login_name);
+    assert_eq!(profile.email, None);
+
+    let status = users.register(proto::RegisterRequest { login: login_name, password: "password1".to_string() }).await.unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+}
+
+#[actix_web::test]
+async fn login_with_two_factor() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let mut users = UserServiceClient::new(serve(&db.storage).await);
+    let login_name = format!("user_{}", Uuid::new_v4().simple());
+
+    register(&app, &login_name, "password1").await;
+    let token = login(&app, &login_name, "password1").await.body;
+
+    let enrollment: Value = send(&app, Method::POST, "/api/user/2fa/enroll", Some(&token), None).await.json();
+    let secret = totp::decode_secret(enrollment["secret"].as_str().unwrap()).unwrap();
+    let code = |offset: u64| totp::code(&secret, totp::step(Utc::now().timestamp() as u64) + offset);
+    let response = send(&app, Method::POST, "/api/user/2fa/confirm", Some(&token), Some(json!({ "code": code(0) }))).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    let response = users.login(proto::LoginRequest { login: login_name.clone(), password: "password1".to_string() }).await.unwrap().into_inner();
+    let challenge_token = match response.result {
+        Some(login_response::Result::TwoFactor(challenge)) => challenge.challenge_token,
+        result => panic!("unexpected login result: {result:?}"),
+    };
+
+    // code of the confirmation is already used
+    let request = proto::LoginTwoFactorRequest { challenge_token: challenge_token.clone(), code: code(0) };
+    assert_eq!(users.login_two_factor(request).await.unwrap_err().code(), Code::Unauthenticated);
+
+    let request = proto::LoginTwoFactorRequest { challenge_token: String::new(), code: code(1) };
+    assert_eq!(users.login_two_factor(request).await.unwrap_err().code(), Code::InvalidArgument);
+
+    let request = proto::LoginTwoFactorRequest { challenge_token, code: code(1) };
+    let token = users.login_two_factor(request).await.unwrap().into_inner().token;
+
+    let profile = users.get_profile(authorized(proto::GetProfileRequest {}, &token)).await.unwrap().into_inner();
+    assert_eq!(profile.login, login_name);
+    assert!(profile.two_factor_enabled);
+}
+
+#[actix_web::test]
+async fn tasks_are_shared_with_rest_api() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let channel = serve(&db.storage).await;
+    let mut lists = TodoListServiceClient::new(channel.clone());
+    let mut tasks = TaskServiceClient::new(channel);
+    let token = user_token(&app).await;
+
+    let todo_list = lists.create_list(authorized(proto::CreateListRequest { name: "home".to_string() }, &token)).await.unwrap().into_inner();
+    assert_eq!(todo_list.name, "home");
+
+    let milk = tasks.add_task(authorized(proto::AddTaskRequest { description: "buy milk".to_string(), position: None }, &token)).await.unwrap().into_inner();
+    assert_eq!(milk.todo_list_id, todo_list.id);
+    assert_eq!(milk.order, 1);
+
+    let request = proto::AddTaskRequest { description: "plan trip".to_string(), position: position(Position::Before(milk.id.clone())) };
+    let trip = tasks.add_task(authorized(request, &token)).await.unwrap().into_inner();
+    assert_eq!(trip.order, 1);
+    assert_eq!(descriptions(&app, &token).await, ["plan trip", "buy milk"]);
+
+    let request = proto::MoveTaskRequest { id: trip.id.clone(), position: position(Position::After(milk.id.clone())) };
+    let moved = tasks.move_task(authorized(request, &token)).await.unwrap().into_inner();
+    assert_eq!(moved.order, 2);
+
+    let response = send(&app, Method::PATCH, &format!("/api/task/{}", milk.id), Some(&token), Some(json!({ "description": "buy oat milk" }))).await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    let all = tasks.get_tasks(authorized(proto::GetTasksRequest {}, &token)).await.unwrap().into_inner();
+    assert_eq!(task_descriptions(&all), ["buy oat milk", "plan trip"]);
+
+    let range = tasks.get_task_range(authorized(proto::GetTaskRangeRequest { offset: 1, count: 10 }, &token)).await.unwrap().into_inner();
+    assert_eq!(task_descriptions(&range), ["plan trip"]);
+
+    let request = proto::UpdateTaskRequest { id: trip.id.clone(), description: "plan vacation".to_string() };
+    let updated = tasks.update_task(authorized(request, &token)).await.unwrap().into_inner();
+    assert_eq!(updated.description, "plan vacation");
+
+    let request = proto::UpdateTaskDetailsRequest { id: trip.id.clone(), due_at: Some("2026-10-20T12:00:00+03:00".to_string()), priority: Some(1), ..Default::default() };
+    let updated = tasks.update_task_details(authorized(request, &token)).await.unwrap().into_inner();
+    assert_eq!((updated.due_at.as_deref(), updated.completed_at, updated.priority), (Some("2026-10-20T09:00:00+00:00"), None, Some(1)));
+    assert_eq!(common::tasks(&app, &token).await[1]["due_at"], "2026-10-20T09:00:00Z");
+
+    let request = proto::UpdateTaskDetailsRequest { id: trip.id.clone(), due_at: Some("tomorrow".to_string()), ..Default::default() };
+    assert_eq!(tasks.update_task_details(authorized(request, &token)).await.unwrap_err().code(), Code::InvalidArgument);
+    let request = proto::UpdateTaskDetailsRequest { id: trip.id.clone(), priority: Some(10), ..Default::default() };
+    assert_eq!(tasks.update_task_details(authorized(request, &token)).await.unwrap_err().code(), Code::InvalidArgument);
+
+    let deleted = tasks.delete_task(authorized(proto::DeleteTaskRequest { id: milk.id }, &token)).await.unwrap().into_inner();
+    assert_eq!(deleted.description, "buy oat milk");
+    assert_eq!(descriptions(&app, &token).await, ["plan vacation"]);
+
+    let renamed = lists.update_list(authorized(proto::UpdateListRequest { name: "trip".to_string() }, &token)).await.unwrap().into_inner();
+    assert_eq!(renamed.name, "trip");
+
+    let deleted = lists.delete_list(authorized(proto::DeleteListRequest {}, &token)).await.unwrap().into_inner();
+    assert_eq!(deleted.deleted_tasks, 1);
+
+    let status = lists.get_list(authorized(proto::GetListRequest {}, &token)).await.unwrap_err();
+    assert_eq!(status.code(), Code::NotFound);
+}
+
+#[actix_web::test]
+async fn errors_are_mapped_to_status_codes() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let mut tasks = TaskServiceClient::new(serve(&db.storage).await);
+    let token = user_with_tasks(&app, &["buy milk"]).await;
+
+    let status = tasks.get_tasks(proto::GetTasksRequest {}).await.unwrap_err();
+    assert_eq!(status.code(), Code::Unauthenticated);
+
+    let status = tasks.get_tasks(authorized(proto::GetTasksRequest {}, "invalid")).await.unwrap_err();
+    assert_eq!(status.code(), Code::Unauthenticated);
+
+    let status = tasks.add_task(authorized(proto::AddTaskRequest { description: String::new(), position: None }, &token)).await.unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+    assert!(status.message().contains("description"), "{}", status.message());
+
+    let status = tasks.delete_task(authorized(proto::DeleteTaskRequest { id: Uuid::new_v4().to_string() }, &token)).await.unwrap_err();
+    assert_eq!(status.code(), Code::NotFound);
+
+    let status = tasks.delete_task(authorized(proto::DeleteTaskRequest { id: "milk".to_string() }, &token)).await.unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    let created: Value = send(&app, Method::POST, "/api/user/tokens", Some(&token), Some(json!({ "name": "service", "scopes": ["read-only"] }))).await.json();
+    let access_token = created["token"].as_str().unwrap();
+
+    let all = tasks.get_tasks(authorized(proto::GetTasksRequest {}, access_token)).await.unwrap().into_inner();
+    assert_eq!(task_descriptions(&all), ["buy milk"]);
+
+    let status = tasks.add_task(authorized(proto::AddTaskRequest { description: "plan trip".to_string(), position: None }, access_token)).await.unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+    assert_eq!(descriptions(&app, &token).await, ["buy milk"]);
+}
+
+#[actix_web::test]
+async fn watch_changes_streams_user_events() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let mut lists = TodoListServiceClient::new(serve(&db.storage).await);
+    let token = user_with_tasks(&app, &["buy milk"]).await;
+    let milk = task_id(&app, &token, "buy milk").await;
+    let other = user_with_tasks(&app, &["plan trip"]).await;
+    let trip = task_id(&app, &other, "plan trip").await;
+
+    // subscribed, when response is received
+    let request = proto::WatchChangesRequest { events: vec![proto::ChangeEventType::TaskUpdated.into()] };
+    let mut changes = lists.watch_changes(authorized(request, &token)).await.unwrap().into_inner();
+
+    let response = send(&app, Method::PATCH, &format!("/api/task/{trip}"), Some(&other), Some(json!({ "description": "pack bags" }))).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let response = send(&app, Method::POST, "/api/task", Some(&token), Some(json!({ "description": "plan trip", "position": "end" }))).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let response = send(&app, Method::PATCH, &format!("/api/task/{milk}"), Some(&token), Some(json!({ "description": "buy oat milk" }))).await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    let change = tokio::time::timeout(Duration::from_secs(5), changes.message()).await
+        .expect("no change event")
+        .unwrap()
+        .unwrap();
+    assert_eq!(change.event(), proto::ChangeEventType::TaskUpdated);
+
+    let data: Value = serde_json::from_str(&change.data).unwrap();
+    assert_eq!(data["id"], milk.as_str());
+    assert_eq!(data["description"], "buy oat milk");
+}
+
+#[actix_web::test]
+async fn watch_changes_ends_when_token_is_revoked() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let mut lists = TodoListServiceClient::new(serve_with_token_check(&db.storage, TokenCheck { interval: Duration::from_secs(1) }).await);
+    let token = user_with_tasks(&app, &["buy milk"]).await;
+
+    let request = proto::WatchChangesRequest { events: Vec::new() };
+    let mut changes = lists.watch_changes(authorized(request, &token)).await.unwrap().into_inner();
+
+    // password change revokes the token, stream is ended on the next check
+    let response = send(&app, Method::PATCH, "/api/user/password", Some(&token), Some(json!({ "old_password": "password1", "new_password": "password3" }))).await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    let status = loop {
+        match tokio::time::timeout(Duration::from_secs(5), changes.message()).await.expect("stream is not ended") {
+            Ok(Some(_)) => continue,
+            Ok(None) => panic!("stream is ended without status"),
+            Err(status) => break status,
+        }
+    };
+    assert_eq!(status.code(), Code::Unauthenticated);
+}
@@ -0,0 +1,192 @@
+mod common;
+
+use std::time::Duration;
+
+use actix_http::Request;
+use actix_web::{
+    body::MessageBody,
+    dev::{
+        Service,
+        ServiceResponse
+    },
+    http::StatusCode,
+    test
+};
+use serde_json::{
+    json,
+    Value
+};
+
+use common::*;
+use todo_list_rs::models::{
+    IdempotencyState,
+    IdempotentResponse
+};
+
+struct Response {
+    status: StatusCode,
+    replayed: bool,
+    body: String,
+}
+
+async fn post<S, B>(app: &S, uri: &str, token: Option<&str>, key: &str, body: Value) -> Response
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody
+{
+    let mut request = test::TestRequest::post()
+        .uri(uri)
+        .insert_header(("Idempotency-Key", key))
+        .set_json(body);
+
+    if let Some(token) = token {
+        request = request.insert_header(("Authorization", format!("Bearer {token}")));
+    }
+
+    let response = test::call_service(app, request.to_request()).await;
+    let status = response.status();
+    let replayed = response.headers().contains_key("idempotent-replayed");
+    let body = String::from_utf8(test::read_body(response).await.to_vec()).unwrap();
+
+    Response { status, replayed, body }
+}
+
+#[actix_web::test]
+async fn retried_new_task_is_created_once() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_with_tasks(&app, &["a"]).await;
+
+    let body = json!({ "description": "b", "position": "end" });
+
+    let first = post(&app, "/api/task", Some(&token), "key-1", body.clone()).await;
+    assert_eq!(first.status, StatusCode::OK);
+    assert!(!first.replayed);
+
+    let retry = post(&app, "/api/task", Some(&token), "key-1", body.clone()).await;
+    assert_eq!(retry.status, StatusCode::OK);
+    assert!(retry.replayed);
+    assert_eq!(retry.body, first.body);
+
+    assert_eq!(descriptions(&app, &token).await, ["a", "b"]);
+
+    let other_key = post(&app, "/api/task", Some(&token), "key-2", body).await;
+    assert!(!other_key.replayed);
+    assert_eq!(descriptions(&app, &token).await, ["a", "b", "b"]);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn key_reused_with_other_body() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_with_tasks(&app, &[]).await;
+
+    post(&app, "/api/task", Some(&token), "key", json!({ "description": "a", "position": "end" })).await;
+
+    let response = post(&app, "/api/task", Some(&token), "key", json!({ "description": "b", "position": "end" })).await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let response = post(&app, "/api/list", Some(&token), "key", json!({ "description": "a", "position": "end" })).await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    assert_eq!(descriptions(&app, &token).await, ["a"]);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn keys_are_scoped_by_user() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let first = user_with_tasks(&app, &[]).await;
+    let second = user_with_tasks(&app, &[]).await;
+
+    let body = json!({ "description": "a", "position": "end" });
+
+    assert!(!post(&app, "/api/task", Some(&first), "key", body.clone()).await.replayed);
+    assert!(!post(&app, "/api/task", Some(&second), "key", body).await.replayed);
+
+    assert_eq!(descriptions(&app, &first).await, ["a"]);
+    assert_eq!(descriptions(&app, &second).await, ["a"]);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn retried_register_and_move() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+
+    let body = json!({ "login": "dave", "password": "password1" });
+    let first = post(&app, "/api/user/register", None, "register-key", body.clone()).await;
+    let retry = post(&app, "/api/user/register", None, "register-key", body).await;
+    assert_eq!(first.status, StatusCode::OK);
+    assert!(retry.replayed);
+    assert_eq!(retry.body, first.body);
+
+    let token = user_with_tasks(&app, &["a", "b", "c"]).await;
+    let a = task_id(&app, &token, "a").await;
+    let uri = format!("/api/task/{a}/move");
+
+    let first = post(&app, &uri, Some(&token), "move-key", json!({ "position": "end" })).await;
+    assert_eq!(first.status, StatusCode::OK);
+
+    // list is changed by other request, retry must not move task again
+    let b = task_id(&app, &token, "b").await;
+    post(&app, &format!("/api/task/{b}/move"), Some(&token), "other-move-key", json!({ "position": "end" })).await;
+
+    let retry = post(&app, &uri, Some(&token), "move-key", json!({ "position": "end" })).await;
+    assert!(retry.replayed);
+    assert_eq!(descriptions(&app, &token).await, ["c", "a", "b"]);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn error_responses() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_with_tasks(&app, &[]).await;
+
+    let response = post(&app, "/api/task", Some(&token), &"k".repeat(256), json!({ "description": "a", "position": "end" })).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+
+    // client errors are stored and replayed
+    let body = json!({ "description": "", "position": "end" });
+    assert_eq!(post(&app, "/api/task", Some(&token), "invalid", body.clone()).await.status, StatusCode::UNPROCESSABLE_ENTITY);
+    let retry = post(&app, "/api/task", Some(&token), "invalid", body).await;
+    assert_eq!(retry.status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(retry.replayed);
+
+    // invalid token is rejected by handler, key is not stored
+    let body = json!({ "description": "a", "position": "end" });
+    assert_eq!(post(&app, "/api/task", Some("invalid"), "key", body.clone()).await.status, StatusCode::UNAUTHORIZED);
+    assert!(!post(&app, "/api/task", Some(&token), "key", body).await.replayed);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn storage_states() {
+    let db = TestDb::new().await;
+    let storage = &db.storage.idempotency;
+    let ttl = Duration::from_secs(60);
+
+    assert_eq!(storage.start_idempotent_request("user", "key", "hash", ttl).await.unwrap(), IdempotencyState::Started);
+    assert_eq!(storage.start_idempotent_request("user", "key", "hash", ttl).await.unwrap(), IdempotencyState::InProgress);
+    assert_eq!(storage.start_idempotent_request("user", "key", "other", ttl).await.unwrap(), IdempotencyState::Mismatch);
+
+    let response = IdempotentResponse { status_code: 200, content_type: Some("application/json".to_string()), body: b"{}".to_vec() };
+    storage.complete_idempotent_request("user", "key", &response).await.unwrap();
+    assert_eq!(storage.start_idempotent_request("user", "key", "hash", ttl).await.unwrap(), IdempotencyState::Completed(response));
+
+    storage.cancel_idempotent_request("user", "key").await.unwrap();
+    assert_eq!(storage.start_idempotent_request("user", "key", "other", ttl).await.unwrap(), IdempotencyState::Started);
+
+    assert_eq!(storage.start_idempotent_request("user", "expired", "hash", Duration::ZERO).await.unwrap(), IdempotencyState::Started);
+    assert_eq!(storage.start_idempotent_request("user", "expired", "other", ttl).await.unwrap(), IdempotencyState::Started);
+
+    db.close().await;
+}
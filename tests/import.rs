@@ -0,0 +1,183 @@
+mod common;
+
+use actix_http::Request;
+use actix_web::{
+    body::MessageBody,
+    dev::{
+        Service,
+        ServiceResponse
+    },
+    http::{
+        Method,
+        StatusCode
+    },
+    test
+};
+use serde_json::Value;
+
+use common::*;
+
+async fn import<S, B>(app: &S, token: &str, query: &str, fields: &[(&str, &str)]) -> Response
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody
+{
+    let fields: Vec<(&str, &[u8])> = fields.iter().map(|(name, value)| (*name, value.as_bytes())).collect();
+    upload(app, &format!("/api/list/import?{query}"), token, &fields).await
+}
+
+#[actix_web::test]
+async fn import_appends_tasks_in_order() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_with_tasks(&app, &["buy milk"]).await;
+
+    let file = "# Plans\n\n- [ ] plan trip\n  book hotel\n- [x] call mom\n- [ ] Buy  Milk\n- [ ] fix bike\n";
+    let response = import(&app, &token, "format=markdown", &[("file", file)]).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    let report: Value = response.json();
+    assert_eq!(report["total"], 4);
+    assert_eq!(report["created"], 3);
+    assert_eq!(report["duplicates"][0]["item"], 3);
+    assert_eq!(report["tasks"][2]["order"], 4);
+    assert_eq!(report["tasks"][2]["description"], "fix bike");
+
+    assert_eq!(descriptions(&app, &token).await, ["buy milk", "plan trip\nbook hotel", "call mom", "fix bike"]);
+    assert_eq!(task_id(&app, &token, "fix bike").await, report["tasks"][2]["id"].as_str().unwrap());
+
+    // completed item is imported as completed task
+    assert!(report["tasks"][1]["completed_at"].is_string());
+    let tasks: Value = send(&app, Method::GET, "/api/task", Some(&token), None).await.json();
+    assert_eq!(tasks[2]["completed_at"], report["tasks"][1]["completed_at"]);
+    assert!(tasks[3]["completed_at"].is_null());
+
+    // repeated import creates nothing
+    let report: Value = import(&app, &token, "format=markdown", &[("file", file)]).await.json();
+    assert_eq!(report["created"], 0);
+    assert_eq!(descriptions(&app, &token).await.len(), 4);
+
+    let response = import(&app, &token, "format=markdown&duplicates=allow", &[("file", "- [ ] fix bike\n")]).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(descriptions(&app, &token).await.len(), 5);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn retried_import_creates_tasks_once() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_with_tasks(&app, &[]).await;
+
+    let file = "- [ ] plan trip\n- [ ] fix bike\n";
+    let headers = [("Idempotency-Key", "import-1")];
+    let first = upload_with_headers(&app, "/api/list/import?format=markdown&duplicates=allow", &token, &headers, &[("file", file.as_bytes())]).await;
+    assert_eq!(first.status, StatusCode::OK, "{}", first.body);
+
+    let retry = upload_with_headers(&app, "/api/list/import?format=markdown&duplicates=allow", &token, &headers, &[("file", file.as_bytes())]).await;
+    assert_eq!(retry.status, StatusCode::OK, "{}", retry.body);
+    assert_eq!(retry.body, first.body);
+    assert_eq!(descriptions(&app, &token).await, ["plan trip", "fix bike"]);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn dry_run_and_mapping() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_with_tasks(&app, &[]).await;
+
+    let file = "Title,Position\r\nsecond,2\r\nfirst,1\r\n";
+    let mapping = r#"{"description": "Title", "order": "Position"}"#;
+
+    let report: Value = import(&app, &token, "format=csv&dry_run=true", &[("file", file), ("mapping", mapping)]).await.json();
+    assert_eq!(report["dry_run"], true);
+    assert_eq!(report["created"], 2);
+    assert_eq!(report["tasks"][0]["description"], "first");
+    assert!(report["tasks"][0].get("id").is_none());
+    assert!(descriptions(&app, &token).await.is_empty());
+
+    let response = import(&app, &token, "format=csv", &[("file", file)]).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST, "{}", response.body);
+
+    let response = import(&app, &token, "format=csv", &[("file", file), ("mapping", r#"{"column": "Title"}"#)]).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST, "{}", response.body);
+
+    let response = import(&app, &token, "format=csv", &[("file", file), ("mapping", mapping)]).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(descriptions(&app, &token).await, ["first", "second"]);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn invalid_items_reject_whole_import() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_with_tasks(&app, &[]).await;
+
+    let long = "a".repeat(4097);
+    let file = format!(r#"{{"items": [{{"content": "ok", "child_order": 1}}, {{"content": "{long}", "child_order": 2}}]}}"#);
+
+    let response = import(&app, &token, "format=todoist", &[("file", &file)]).await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY, "{}", response.body);
+
+    let error: Value = response.json();
+    assert_eq!(error["report"]["errors"][0]["item"], 2);
+    assert!(descriptions(&app, &token).await.is_empty());
+
+    let response = import(&app, &token, "format=todoist&dry_run=true", &[("file", &file)]).await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn import_requires_list_and_file() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_token(&app).await;
+
+    let response = import(&app, &token, "format=todotxt", &[("file", "task")]).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+    let token = user_with_tasks(&app, &[]).await;
+
+    let response = import(&app, &token, "format=todotxt", &[("notes", "task")]).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+
+    let response = import(&app, &token, "format=docx", &[("file", "task")]).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+
+    let response = send(&app, Method::POST, "/api/list/import?format=todotxt", Some(&token), Some(serde_json::json!({ "file": "task" }))).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+
+    let response = import(&app, &token, "format=todotxt", &[("file", "(A) 2026-10-01 task +test @home")]).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(descriptions(&app, &token).await, ["task @home"]);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn form_size_is_limited() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_with_tasks(&app, &[]).await;
+
+    // other fields are skipped, but they are counted with the file
+    let response = import(&app, &token, "format=todotxt", &[("notes", "skipped"), ("file", "task")]).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    let large = "x".repeat(1024 * 1024 + 1);
+    let response = import(&app, &token, "format=todotxt", &[("notes", &large), ("file", &large)]).await;
+    assert_eq!(response.status, StatusCode::PAYLOAD_TOO_LARGE, "{}", response.body);
+
+    let response = import(&app, &token, "format=todotxt", &[("file", "task"), ("mapping", &large), ("notes", &large)]).await;
+    assert_eq!(response.status, StatusCode::PAYLOAD_TOO_LARGE, "{}", response.body);
+    assert_eq!(descriptions(&app, &token).await, ["task"]);
+
+    db.close().await;
+}
@@ -0,0 +1,140 @@
+mod common;
+
+use std::path::{
+    Path,
+    PathBuf
+};
+
+use actix_web::{
+    http::{
+        Method,
+        StatusCode
+    },
+    test
+};
+use chrono::{
+    Duration,
+    Utc
+};
+use jsonwebtoken::{
+    decode,
+    decode_header,
+    encode,
+    Algorithm,
+    DecodingKey,
+    EncodingKey,
+    Header,
+    Validation
+};
+use serde_json::json;
+use uuid::Uuid;
+
+use common::*;
+use todo_list_rs::{
+    models::{
+        JwkSet,
+        UserClaim
+    },
+    utils::jwt::JwtKeys
+};
+
+fn keys_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/keys")
+}
+
+fn token(user_id: Uuid, header: Header, key: &EncodingKey) -> String {
+    let claim = UserClaim { exp: (Utc::now() + Duration::hours(1)).timestamp() as usize, user_id, session_version: 0 };
+    encode(&header, &claim, key).unwrap()
+}
+
+fn header(kid: &str, algorithm: Algorithm) -> Header {
+    Header { kid: Some(kid.to_string()), ..Header::new(algorithm) }
+}
+
+#[actix_web::test]
+async fn login_token_is_verified_by_jwks() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app_with_keys(&db.storage, JwtKeys::load(&keys_dir().join("jwks"), "rsa-1").unwrap())).await;
+
+    let token = user_token(&app).await;
+    let token_header = decode_header(&token).unwrap();
+    assert_eq!((token_header.kid.as_deref(), token_header.alg), (Some("rsa-1"), Algorithm::RS256));
+
+    let response = send(&app, Method::GET, "/.well-known/jwks.json", None, None).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let jwks: JwkSet = response.json();
+
+    let kids: Vec<_> = jwks.keys.iter().map(|key| key.kid.as_str()).collect();
+    assert_eq!(kids, vec!["ed-2", "rsa-0", "rsa-1"]);
+
+    // other service verifies token with published key
+    let jwk = jwks.keys.iter().find(|key| key.kid == "rsa-1").unwrap();
+    let key = DecodingKey::from_rsa_components(jwk.n.as_ref().unwrap(), jwk.e.as_ref().unwrap()).unwrap();
+    assert!(decode::<UserClaim>(&token, &key, &Validation::new(Algorithm::RS256)).is_ok());
+
+    let response = send(&app, Method::POST, "/api/list", Some(&token), Some(json!({ "name": "list" }))).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn retired_key_token_stays_valid() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app_with_keys(&db.storage, JwtKeys::load(&keys_dir().join("jwks"), "ed-2").unwrap())).await;
+
+    let response = register(&app, "alice", "password1").await;
+    assert_eq!(response.status, StatusCode::OK);
+    let user_id: Uuid = response.body.parse().unwrap();
+
+    let login_token = login(&app, "alice", "password1").await.body;
+    assert_eq!(decode_header(&login_token).unwrap().alg, Algorithm::EdDSA);
+    let response = send(&app, Method::POST, "/api/list", Some(&login_token), Some(json!({ "name": "list" }))).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    // only public key of rsa-0 is left in the keys directory
+    let retired = EncodingKey::from_rsa_pem(&std::fs::read(keys_dir().join("rsa-0.private.pem")).unwrap()).unwrap();
+    let response = send(&app, Method::GET, "/api/list", Some(&token(user_id, header("rsa-0", Algorithm::RS256), &retired)), None).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    // signature doesn't match key of kid
+    let other = EncodingKey::from_rsa_pem(&std::fs::read(keys_dir().join("jwks/rsa-1.pem")).unwrap()).unwrap();
+    let response = send(&app, Method::GET, "/api/list", Some(&token(user_id, header("rsa-0", Algorithm::RS256), &other)), None).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+
+    let response = send(&app, Method::GET, "/api/list", Some(&token(user_id, header("rsa-9", Algorithm::RS256), &retired)), None).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn shared_secret_is_not_accepted_with_keys() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app_with_keys(&db.storage, JwtKeys::load(&keys_dir().join("jwks"), "ed-2").unwrap())).await;
+
+    let response = register(&app, "alice", "password1").await;
+    let user_id: Uuid = response.body.parse().unwrap();
+
+    let secret = EncodingKey::from_secret(BEARER_KEY.as_bytes());
+    let response = send(&app, Method::GET, "/api/list", Some(&token(user_id, Header::default(), &secret)), None).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+
+    // HS256 token with kid of the public key
+    let response = send(&app, Method::GET, "/api/list", Some(&token(user_id, header("rsa-0", Algorithm::HS256), &secret)), None).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn shared_secret_is_not_published() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+
+    let response = send(&app, Method::GET, "/.well-known/jwks.json", None, None).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.json::<JwkSet>().keys, vec![]);
+
+    db.close().await;
+}
@@ -0,0 +1,125 @@
+mod common;
+
+use actix_web::{
+    http::{
+        Method,
+        StatusCode
+    },
+    test
+};
+use serde_json::{
+    json,
+    Value
+};
+
+use common::*;
+
+#[actix_web::test]
+async fn list_lifecycle() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_token(&app).await;
+
+    let response = send(&app, Method::GET, "/api/list", Some(&token), None).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+    let response = send(&app, Method::POST, "/api/list", Some(&token), Some(json!({ "name": "first" }))).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let list_id = response.body;
+
+    let response = send(&app, Method::POST, "/api/list", Some(&token), Some(json!({ "name": "second" }))).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+
+    let response = send(&app, Method::PATCH, "/api/list", Some(&token), Some(json!({ "name": "renamed" }))).await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    let list: Value = send(&app, Method::GET, "/api/list", Some(&token), None).await.json();
+    assert_eq!(list["id"], list_id.as_str());
+    assert_eq!(list["name"], "renamed");
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn delete_list_with_tasks() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_with_tasks(&app, &["a", "b"]).await;
+
+    let response = send(&app, Method::DELETE, "/api/list", Some(&token), None).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body, "2");
+
+    let response = send(&app, Method::GET, "/api/task", Some(&token), None).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+    let response = send(&app, Method::DELETE, "/api/list", Some(&token), None).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn blank_list_name() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_token(&app).await;
+
+    let response = send(&app, Method::POST, "/api/list", Some(&token), Some(json!({ "name": "   " }))).await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn export_formats() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_with_tasks(&app, &["buy milk", "plan trip\nbook hotel"]).await;
+    let tasks = tasks(&app, &token).await;
+
+    let export: Value = send(&app, Method::GET, "/api/list/export", Some(&token), None).await.json();
+    assert_eq!(export["version"], 1);
+    assert_eq!(export["lists"][0]["name"], "test");
+    assert_eq!(export["lists"][0]["tasks"][0]["id"], tasks[0]["id"]);
+    assert_eq!(export["lists"][0]["tasks"][1]["description"], "plan trip\nbook hotel");
+    assert_eq!(export["lists"][0]["tasks"][1]["order"], 2);
+
+    let response = test::call_service(&app, test::TestRequest::get()
+        .uri("/api/list/export?format=csv")
+        .insert_header(("Authorization", format!("Bearer {token}")))
+        .to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers().get("Content-Type").unwrap(), "text/csv; charset=utf-8");
+    assert_eq!(response.headers().get("Content-Disposition").unwrap(), "attachment; filename=\"todo-list.csv\"");
+    let csv = String::from_utf8(test::read_body(response).await.to_vec()).unwrap();
+    assert!(csv.starts_with("list_id,list_name,task_id,order,description,completed_at,due_at,priority,rrule\r\n"));
+    assert!(csv.ends_with(&format!(",test,{},2,\"plan trip\nbook hotel\",,,,\r\n", tasks[1]["id"].as_str().unwrap())), "{csv}");
+
+    let response = send(&app, Method::GET, "/api/list/export?format=markdown", Some(&token), None).await;
+    assert_eq!(response.body, "# test\n\n- [ ] buy milk\n- [ ] plan trip\n  book hotel\n");
+
+    let response = send(&app, Method::GET, "/api/list/export?format=todotxt", Some(&token), None).await;
+    assert_eq!(response.body, "buy milk +test\nplan trip book hotel +test\n");
+
+    let response = send(&app, Method::GET, "/api/list/export?format=xml", Some(&token), None).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn export_without_list_is_empty() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_token(&app).await;
+
+    let export: Value = send(&app, Method::GET, "/api/list/export?format=json", Some(&token), None).await.json();
+    assert_eq!(export["lists"], json!([]));
+
+    let response = send(&app, Method::GET, "/api/list/export?format=markdown", Some(&token), None).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body, "");
+
+    db.close().await;
+}
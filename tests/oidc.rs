@@ -0,0 +1,245 @@
+mod common;
+
+use actix_web::{
+    body::MessageBody,
+    dev::{
+        Service,
+        ServiceResponse
+    },
+    http::{
+        header,
+        Method,
+        StatusCode
+    },
+    test
+};
+use actix_http::Request;
+use serde_json::Value;
+
+use common::{
+    *,
+    oidc::*
+};
+use todo_list_rs::utils::oidc::OidcProvider;
+
+struct OidcResponse {
+    status: StatusCode,
+    location: Option<String>,
+    state_cookie: Option<String>,
+    body: String,
+}
+
+async fn request<S, B>(app: &S, method: Method, uri: &str, cookie: Option<&str>, token: Option<&str>) -> OidcResponse
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody
+{
+    let mut request = test::TestRequest::default().method(method).uri(uri);
+
+    if let Some(cookie) = cookie {
+        request = request.insert_header((header::COOKIE, format!("oidc_state={cookie}")));
+    }
+
+    if let Some(token) = token {
+        request = request.insert_header((header::AUTHORIZATION, format!("Bearer {token}")));
+    }
+
+    let response = test::call_service(app, request.to_request()).await;
+    let status = response.status();
+    let location = response.headers().get(header::LOCATION).map(|value| value.to_str().unwrap().to_string());
+    let state_cookie = response.response().cookies().find(|cookie| cookie.name() == "oidc_state").map(|cookie| cookie.value().to_string());
+    let body = String::from_utf8(test::read_body(response).await.to_vec()).unwrap();
+
+    OidcResponse { status, location, state_cookie, body }
+}
+
+/// Follows authorization url at the issuer, returns path and query of the callback
+async fn authorize(authorization_url: &str) -> String {
+    let client = reqwest::Client::builder().redirect(reqwest::redirect::Policy::none()).build().unwrap();
+    let response = client.get(authorization_url).send().await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::FOUND);
+
+    let callback = url::Url::parse(response.headers()[header::LOCATION.as_str()].to_str().unwrap()).unwrap();
+    assert_eq!(callback.path(), "/api/user/oidc/callback");
+
+    format!("{}?{}", callback.path(), callback.query().unwrap())
+}
+
+/// Whole login flow in one browser, returns response of the callback
+async fn oidc_login<S, B>(app: &S) -> OidcResponse
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody
+{
+    let start = request(app, Method::GET, "/api/user/oidc/login", None, None).await;
+    assert_eq!(start.status, StatusCode::FOUND, "{}", start.body);
+
+    let callback = authorize(&start.location.unwrap()).await;
+    request(app, Method::GET, &callback, start.state_cookie.as_deref(), None).await
+}
+
+#[actix_web::test]
+async fn login_provisions_user() {
+    let issuer = MockIssuer::start();
+    let db = TestDb::new().await;
+    let app = test::init_service(app_with_oidc(&db.storage, OidcProvider::new(issuer.config()))).await;
+
+    issuer.set_user(MockUser::new("subject-1", "alice"));
+
+    let response = oidc_login(&app).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    // state cookie is removed
+    assert_eq!(response.state_cookie.as_deref(), Some(""));
+    let token = response.body;
+
+    let response = send(&app, Method::GET, "/api/user/me", Some(&token), None).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let profile = response.json::<Value>();
+    assert_eq!(profile["login"], "alice");
+
+    let response = send(&app, Method::GET, "/api/user/identities", Some(&token), None).await;
+    let identities = response.json::<Value>();
+    assert_eq!(identities.as_array().unwrap().len(), 1, "{identities}");
+    assert_eq!(identities[0]["issuer"], issuer.url());
+    assert_eq!(identities[0]["subject"], "subject-1");
+    assert_eq!(identities[0]["email"], "alice@example.com");
+
+    // next login is the same user
+    let response = oidc_login(&app).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let response = send(&app, Method::GET, "/api/user/me", Some(&response.body), None).await;
+    assert_eq!(response.json::<Value>()["id"], profile["id"]);
+
+    db.close().await;
+    issuer.stop().await;
+}
+
+#[actix_web::test]
+async fn provisioned_login_is_unique() {
+    let issuer = MockIssuer::start();
+    let db = TestDb::new().await;
+    let app = test::init_service(app_with_oidc(&db.storage, OidcProvider::new(issuer.config()))).await;
+
+    register(&app, "alice", "password1").await;
+
+    issuer.set_user(MockUser::new("subject-1", "alice"));
+    let response = oidc_login(&app).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    let response = send(&app, Method::GET, "/api/user/me", Some(&response.body), None).await;
+    let provisioned = response.json::<Value>()["login"].as_str().unwrap().to_string();
+    assert!(provisioned.starts_with("alice_"), "{provisioned}");
+
+    // existing user is not logged in by identity with the same name
+    assert_eq!(login(&app, "alice", "password1").await.status, StatusCode::OK);
+
+    db.close().await;
+    issuer.stop().await;
+}
+
+#[actix_web::test]
+async fn link_identity() {
+    let issuer = MockIssuer::start();
+    let db = TestDb::new().await;
+    let app = test::init_service(app_with_oidc(&db.storage, OidcProvider::new(issuer.config()))).await;
+
+    let alice_id = register(&app, "alice", "password1").await.body;
+    let alice = login(&app, "alice", "password1").await.body;
+    let bob = user_token(&app).await;
+
+    issuer.set_user(MockUser::new("subject-1", "external"));
+
+    let response = request(&app, Method::POST, "/api/user/oidc/link", None, None).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+
+    let start = request(&app, Method::POST, "/api/user/oidc/link", None, Some(&alice)).await;
+    assert_eq!(start.status, StatusCode::OK, "{}", start.body);
+    let authorization_url = serde_json::from_str::<Value>(&start.body).unwrap()["authorization_url"].as_str().unwrap().to_string();
+
+    let callback = authorize(&authorization_url).await;
+    let response = request(&app, Method::GET, &callback, start.state_cookie.as_deref(), None).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(response.body, alice_id);
+
+    // login with the identity is login of alice
+    let response = oidc_login(&app).await;
+    let response = send(&app, Method::GET, "/api/user/me", Some(&response.body), None).await;
+    assert_eq!(response.json::<Value>()["login"], "alice");
+
+    // identity can't be linked to other user
+    let start = request(&app, Method::POST, "/api/user/oidc/link", None, Some(&bob)).await;
+    let authorization_url = serde_json::from_str::<Value>(&start.body).unwrap()["authorization_url"].as_str().unwrap().to_string();
+    let callback = authorize(&authorization_url).await;
+    let response = request(&app, Method::GET, &callback, start.state_cookie.as_deref(), None).await;
+    assert_eq!(response.status, StatusCode::CONFLICT, "{}", response.body);
+
+    let response = send(&app, Method::GET, "/api/user/identities", Some(&bob), None).await;
+    assert_eq!(response.json::<Value>(), serde_json::json!([]));
+
+    db.close().await;
+    issuer.stop().await;
+}
+
+#[actix_web::test]
+async fn callback_requires_state_of_browser() {
+    let issuer = MockIssuer::start();
+    let db = TestDb::new().await;
+    let app = test::init_service(app_with_oidc(&db.storage, OidcProvider::new(issuer.config()))).await;
+
+    issuer.set_user(MockUser::new("subject-1", "alice"));
+
+    let start = request(&app, Method::GET, "/api/user/oidc/login", None, None).await;
+    let state = start.state_cookie.unwrap();
+    let callback = authorize(&start.location.unwrap()).await;
+
+    let response = request(&app, Method::GET, &callback, None, None).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+
+    let response = request(&app, Method::GET, &callback, Some("other"), None).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+
+    let response = request(&app, Method::GET, &callback, Some(&state), None).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    // state is single-use
+    let response = request(&app, Method::GET, &callback, Some(&state), None).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+
+    db.close().await;
+    issuer.stop().await;
+}
+
+#[actix_web::test]
+async fn failed_login() {
+    let issuer = MockIssuer::start();
+    let db = TestDb::new().await;
+    let app = test::init_service(app_with_oidc(&db.storage, OidcProvider::new(issuer.config()))).await;
+
+    issuer.deny();
+    let response = oidc_login(&app).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED, "{}", response.body);
+
+    issuer.set_user(MockUser::new("subject-1", "alice"));
+
+    issuer.set_nonce("replayed");
+    let response = oidc_login(&app).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED, "{}", response.body);
+
+    issuer.set_audience("other-client");
+    let response = oidc_login(&app).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED, "{}", response.body);
+
+    db.close().await;
+    issuer.stop().await;
+}
+
+#[actix_web::test]
+async fn not_configured() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+
+    let response = request(&app, Method::GET, "/api/user/oidc/login", None, None).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+    db.close().await;
+}
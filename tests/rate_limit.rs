@@ -0,0 +1,142 @@
+mod common;
+
+use std::time::Duration;
+
+use actix_http::Request;
+use actix_web::{
+    body::MessageBody,
+    dev::{
+        Service,
+        ServiceResponse
+    },
+    http::{
+        header,
+        Method,
+        StatusCode
+    },
+    test
+};
+use serde_json::json;
+
+use common::*;
+use todo_list_rs::utils::rate_limit::{
+    LoginThrottleConfig,
+    RateLimits
+};
+
+fn config() -> LoginThrottleConfig {
+    LoginThrottleConfig {
+        ip_limit: 100,
+        account_limit: 100,
+        ip_lockout_threshold: 5,
+        account_lockout_threshold: 3,
+        lockout_base: Duration::from_secs(60),
+        lockout_max: Duration::from_secs(600),
+        trust_proxy: false,
+    }
+}
+
+/// Status and `Retry-After` of login from the address
+async fn login_from<S, B>(app: &S, ip: &str, login: &str, password: &str) -> (StatusCode, Option<u64>)
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody
+{
+    let request = test::TestRequest::post()
+        .uri("/api/user/login")
+        .peer_addr(format!("{ip}:40000").parse().unwrap())
+        .set_json(json!({ "login": login, "password": password }))
+        .to_request();
+
+    let response = test::call_service(app, request).await;
+    let retry_after = response.headers()
+        .get(header::RETRY_AFTER)
+        .map(|value| value.to_str().unwrap().parse().unwrap());
+
+    (response.status(), retry_after)
+}
+
+#[actix_web::test]
+async fn account_is_locked_after_failures() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app_with_limits(&db.storage, RateLimits::new(config(), None))).await;
+
+    register(&app, "erin", "password1").await;
+    register(&app, "frank", "password1").await;
+
+    for ip in ["10.0.0.1", "10.0.0.2", "10.0.0.3"] {
+        assert_eq!(login_from(&app, ip, "erin", "wrong1234").await.0, StatusCode::UNAUTHORIZED);
+    }
+
+    // correct password is rejected too, from any address
+    assert_eq!(login_from(&app, "10.0.0.4", "erin", "password1").await, (StatusCode::TOO_MANY_REQUESTS, Some(60)));
+
+    // unknown login names are locked the same way
+    for _ in 0..3 {
+        assert_eq!(login_from(&app, "10.0.0.5", "nobody", "wrong1234").await.0, StatusCode::UNAUTHORIZED);
+    }
+    assert_eq!(login_from(&app, "10.0.0.5", "nobody", "wrong1234").await.0, StatusCode::TOO_MANY_REQUESTS);
+
+    assert_eq!(login_from(&app, "10.0.0.4", "frank", "password1").await.0, StatusCode::OK);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn address_is_locked_after_failures() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app_with_limits(&db.storage, RateLimits::new(config(), None))).await;
+
+    register(&app, "grace", "password1").await;
+
+    for i in 0..5 {
+        assert_eq!(login_from(&app, "10.0.0.1", &format!("user{i}"), "wrong1234").await.0, StatusCode::UNAUTHORIZED);
+    }
+
+    assert_eq!(login_from(&app, "10.0.0.1", "grace", "password1").await, (StatusCode::TOO_MANY_REQUESTS, Some(60)));
+    assert_eq!(login_from(&app, "10.0.0.2", "grace", "password1").await.0, StatusCode::OK);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn login_attempts_are_limited() {
+    let db = TestDb::new().await;
+    let config = LoginThrottleConfig { ip_limit: 2, account_limit: 3, ..config() };
+    let app = test::init_service(app_with_limits(&db.storage, RateLimits::new(config, None))).await;
+
+    register(&app, "heidi", "password1").await;
+
+    assert_eq!(login_from(&app, "10.0.0.1", "heidi", "password1").await.0, StatusCode::OK);
+    assert_eq!(login_from(&app, "10.0.0.1", "heidi", "password1").await.0, StatusCode::OK);
+    assert_eq!(login_from(&app, "10.0.0.1", "heidi", "password1").await, (StatusCode::TOO_MANY_REQUESTS, Some(30)));
+
+    assert_eq!(login_from(&app, "10.0.0.2", "heidi", "password1").await.0, StatusCode::OK);
+    assert_eq!(login_from(&app, "10.0.0.3", "heidi", "password1").await, (StatusCode::TOO_MANY_REQUESTS, Some(20)));
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn api_requests_are_limited_per_user() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app_with_limits(&db.storage, RateLimits::new(LoginThrottleConfig::default(), Some(3)))).await;
+
+    // list creation is the first request
+    let first = user_with_tasks(&app, &["a"]).await;
+    let second = user_token(&app).await;
+
+    let response = send(&app, Method::GET, "/api/task", Some(&first), None).await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    let response = send(&app, Method::GET, "/api/task", Some(&first), None).await;
+    assert_eq!(response.status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.json::<serde_json::Value>()["status_code"], "429 Too Many Requests");
+
+    assert_eq!(send(&app, Method::GET, "/api/list", Some(&second), None).await.status, StatusCode::NOT_FOUND);
+
+    // requests without token are rejected by handler
+    assert_eq!(send(&app, Method::GET, "/api/task", Some("invalid"), None).await.status, StatusCode::UNAUTHORIZED);
+
+    db.close().await;
+}
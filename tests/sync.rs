@@ -0,0 +1,286 @@
+mod common;
+
+use actix_http::Request;
+use actix_web::{
+    body::MessageBody,
+    dev::{
+        Service,
+        ServiceResponse
+    },
+    http::{
+        Method,
+        StatusCode
+    },
+    test
+};
+use chrono::{
+    Duration,
+    TimeZone,
+    Utc
+};
+use serde_json::{
+    json,
+    Value
+};
+use uuid::Uuid;
+
+use common::*;
+
+/// Client time `minutes` ago
+fn ago(minutes: i64) -> String {
+    (Utc::now() - Duration::minutes(minutes)).to_rfc3339()
+}
+
+async fn changes<S, B>(app: &S, token: &str, since: Option<&str>) -> Value
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody
+{
+    let uri = match since {
+        Some(since) => format!("/api/sync?since={since}"),
+        None => "/api/sync".to_string(),
+    };
+
+    let response = send(app, Method::GET, &uri, Some(token), None).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    response.json()
+}
+
+async fn sync<S, B>(app: &S, token: &str, cursor: Option<&str>, mutations: Value) -> Value
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody
+{
+    let response = send(app, Method::POST, "/api/sync", Some(token), Some(json!({ "cursor": cursor, "mutations": mutations }))).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    response.json()
+}
+
+fn ids(tasks: &Value) -> Vec<&str> {
+    tasks.as_array().unwrap().iter().map(|task| task["id"].as_str().unwrap()).collect()
+}
+
+#[actix_web::test]
+async fn changes_after_cursor() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_with_tasks(&app, &["buy milk", "plan trip"]).await;
+    let milk = task_id(&app, &token, "buy milk").await;
+    let trip = task_id(&app, &token, "plan trip").await;
+
+    let snapshot = changes(&app, &token, None).await;
+    assert_eq!(snapshot["reset"], false);
+    assert_eq!(snapshot["list"]["name"], "test");
+    assert_eq!(ids(&snapshot["created"]), [milk.as_str(), trip.as_str()]);
+    assert!(snapshot["deleted"].as_array().unwrap().is_empty());
+    let cursor = snapshot["cursor"].as_str().unwrap();
+
+    let response = send(&app, Method::PATCH, &format!("/api/task/{milk}"), Some(&token), Some(json!({ "description": "buy oat milk" }))).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let response = send(&app, Method::DELETE, &format!("/api/task/{trip}"), Some(&token), None).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let response = send(&app, Method::POST, "/api/task", Some(&token), Some(json!({ "description": "call mom", "position": "end" }))).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let response = send(&app, Method::PATCH, "/api/list", Some(&token), Some(json!({ "name": "home" }))).await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    let delta = changes(&app, &token, Some(cursor)).await;
+    assert_eq!(delta["reset"], false);
+    assert_eq!(delta["list"]["name"], "home");
+    assert_eq!(delta["created"][0]["description"], "call mom");
+    assert_eq!(delta["updated"][0]["description"], "buy oat milk");
+    assert_eq!(delta["updated"].as_array().unwrap().len(), 1);
+    assert_eq!(delta["deleted"][0]["id"], trip.as_str());
+    assert_ne!(delta["cursor"], cursor);
+
+    // nothing changed after the last cursor
+    let empty = changes(&app, &token, delta["cursor"].as_str()).await;
+    assert_eq!(empty["cursor"], delta["cursor"]);
+    assert_eq!(empty["list"], Value::Null);
+    assert!(empty["created"].as_array().unwrap().is_empty());
+    assert!(empty["updated"].as_array().unwrap().is_empty());
+    assert!(empty["deleted"].as_array().unwrap().is_empty());
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn unknown_cursor_resets_client() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_with_tasks(&app, &["buy milk"]).await;
+    let cursor = changes(&app, &token, None).await["cursor"].as_str().unwrap().to_string();
+
+    // list is recreated with new id
+    let response = send(&app, Method::DELETE, "/api/list", Some(&token), None).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let response = send(&app, Method::POST, "/api/list", Some(&token), Some(json!({ "name": "new" }))).await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    let reset = changes(&app, &token, Some(&cursor)).await;
+    assert_eq!(reset["reset"], true);
+    assert_eq!(reset["list"]["name"], "new");
+    assert!(reset["created"].as_array().unwrap().is_empty());
+
+    let list_id = reset["list"]["id"].as_str().unwrap();
+    let ahead = changes(&app, &token, Some(&format!("{list_id}.1000"))).await;
+    assert_eq!(ahead["reset"], true);
+
+    let response = send(&app, Method::GET, "/api/sync?since=42", Some(&token), None).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn offline_mutations_are_applied_in_client_time_order() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_with_tasks(&app, &["buy milk"]).await;
+    let milk = task_id(&app, &token, "buy milk").await;
+    let cursor = changes(&app, &token, None).await["cursor"].as_str().unwrap().to_string();
+
+    let trip = Uuid::new_v4();
+    let hotel = Uuid::new_v4();
+    let mutations = json!([
+        { "type": "update", "task_id": trip, "description": "plan trip to Rome", "client_time": ago(60) },
+        { "type": "create", "task_id": hotel, "description": "book hotel", "position": { "after": { "task_id": trip } }, "client_time": ago(90) },
+        { "type": "create", "task_id": trip, "description": "plan trip", "position": { "before": { "task_id": milk } }, "client_time": ago(120) },
+    ]);
+
+    let response = sync(&app, &token, Some(&cursor), mutations.clone()).await;
+    let statuses: Vec<&str> = response["results"].as_array().unwrap().iter().map(|result| result["status"].as_str().unwrap()).collect();
+    assert_eq!(statuses, ["applied", "applied", "applied"]);
+    assert_eq!(response["results"][0]["task"]["description"], "plan trip to Rome");
+    assert_eq!(descriptions(&app, &token).await, ["plan trip to Rome", "book hotel", "buy milk"]);
+
+    // applied mutations are in changes after the cursor
+    assert_eq!(response["created"].as_array().unwrap().len(), 2);
+
+    // retried request changes nothing, update of the same time is written again
+    let response = sync(&app, &token, None, mutations).await;
+    assert_eq!(response["results"][1]["status"], "duplicate");
+    assert_eq!(response["results"][2]["status"], "duplicate");
+    assert_eq!(response["results"][0]["status"], "applied");
+    assert_eq!(response["reset"], false);
+    assert_eq!(descriptions(&app, &token).await.len(), 3);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn concurrent_edits_are_resolved_by_time() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_with_tasks(&app, &[]).await;
+
+    let [a, b, c] = [Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4()];
+    sync(&app, &token, None, json!([
+        { "type": "create", "task_id": a, "description": "a", "position": "end", "client_time": ago(180) },
+        { "type": "create", "task_id": b, "description": "b", "position": "end", "client_time": ago(180) },
+        { "type": "create", "task_id": c, "description": "c", "position": "end", "client_time": ago(180) },
+    ])).await;
+
+    // server edits are newer than offline edits
+    let response = send(&app, Method::PATCH, &format!("/api/task/{a}"), Some(&token), Some(json!({ "description": "a from web" }))).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let response = send(&app, Method::POST, &format!("/api/task/{b}/move"), Some(&token), Some(json!({ "position": "end" }))).await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    let response = sync(&app, &token, None, json!([
+        { "type": "update", "task_id": a, "description": "a from phone", "client_time": ago(60) },
+        { "type": "move", "task_id": b, "position": { "before": { "task_id": a } }, "client_time": ago(60) },
+        { "type": "move", "task_id": c, "position": { "before": { "task_id": a } }, "client_time": ago(60) },
+        { "type": "update", "task_id": c, "description": "c from phone", "client_time": ago(60) },
+    ])).await;
+
+    let results = &response["results"];
+    assert_eq!(results[0]["status"], "conflict");
+    assert_eq!(results[0]["task"]["description"], "a from web");
+    assert_eq!(results[1]["status"], "conflict");
+    assert_eq!(results[2]["status"], "applied");
+    assert_eq!(results[3]["status"], "applied");
+    assert_eq!(descriptions(&app, &token).await, ["c from phone", "a from web", "b"]);
+
+    // delete wins over later update, missing anchor means the end
+    let response = sync(&app, &token, None, json!([
+        { "type": "delete", "task_id": a, "client_time": ago(30) },
+        { "type": "update", "task_id": a, "description": "a again", "client_time": ago(20) },
+        { "type": "move", "task_id": c, "position": { "after": { "task_id": a } }, "client_time": ago(10) },
+        { "type": "delete", "task_id": a, "client_time": ago(5) },
+    ])).await;
+
+    let statuses: Vec<&str> = response["results"].as_array().unwrap().iter().map(|result| result["status"].as_str().unwrap()).collect();
+    assert_eq!(statuses, ["applied", "conflict", "applied", "duplicate"]);
+    assert_eq!(response["results"][1]["detail"], "Task is deleted");
+    assert_eq!(descriptions(&app, &token).await, ["b", "c from phone"]);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn changes_in_the_same_second_are_ordered() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_with_tasks(&app, &[]).await;
+
+    // times of one second, which differ in milliseconds
+    let second = (Utc::now() - Duration::hours(1)).timestamp();
+    let at = |millis: i64| Utc.timestamp_millis_opt(second * 1000 + millis).unwrap().to_rfc3339();
+
+    let a = Uuid::new_v4();
+    sync(&app, &token, None, json!([
+        { "type": "create", "task_id": a, "description": "a", "position": "end", "client_time": at(100) },
+    ])).await;
+
+    let response = sync(&app, &token, None, json!([
+        { "type": "update", "task_id": a, "description": "a later", "client_time": at(600) },
+    ])).await;
+    assert_eq!(response["results"][0]["status"], "applied");
+
+    let response = sync(&app, &token, None, json!([
+        { "type": "update", "task_id": a, "description": "a earlier", "client_time": at(300) },
+    ])).await;
+    assert_eq!(response["results"][0]["status"], "conflict");
+    assert_eq!(response["results"][0]["task"]["description"], "a later");
+
+    // the later written change wins for the same time
+    let response = sync(&app, &token, None, json!([
+        { "type": "update", "task_id": a, "description": "a first", "client_time": at(800) },
+        { "type": "update", "task_id": a, "description": "a second", "client_time": at(800) },
+    ])).await;
+    let statuses: Vec<&str> = response["results"].as_array().unwrap().iter().map(|result| result["status"].as_str().unwrap()).collect();
+    assert_eq!(statuses, ["applied", "applied"]);
+    assert_eq!(descriptions(&app, &token).await, ["a second"]);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn sync_requires_list_and_write_scope() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_token(&app).await;
+
+    let response = send(&app, Method::GET, "/api/sync", Some(&token), None).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+    let token = user_with_tasks(&app, &["buy milk"]).await;
+    let created: Value = send(&app, Method::POST, "/api/user/tokens", Some(&token), Some(json!({ "name": "phone", "scopes": ["read-only"] }))).await.json();
+    let read_only = created["token"].as_str().unwrap();
+
+    let response = send(&app, Method::GET, "/api/sync", Some(read_only), None).await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    let mutations = json!([{ "type": "delete", "task_id": Uuid::new_v4(), "client_time": ago(1) }]);
+    let response = send(&app, Method::POST, "/api/sync", Some(read_only), Some(json!({ "mutations": mutations }))).await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+
+    let mutations = json!([{ "type": "update", "task_id": Uuid::new_v4(), "description": "", "client_time": ago(1) }]);
+    let response = send(&app, Method::POST, "/api/sync", Some(&token), Some(json!({ "mutations": mutations }))).await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    db.close().await;
+}
@@ -0,0 +1,232 @@
+mod common;
+
+use actix_web::{
+    http::{
+        Method,
+        StatusCode
+    },
+    test
+};
+use futures::future::join_all;
+use serde_json::{
+    json,
+    Value
+};
+use uuid::Uuid;
+
+use common::*;
+
+fn after(task_id: &str) -> Value {
+    json!({ "after": { "task_id": task_id } })
+}
+
+fn before(task_id: &str) -> Value {
+    json!({ "before": { "task_id": task_id } })
+}
+
+#[actix_web::test]
+async fn add_tasks_at_positions() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_with_tasks(&app, &["a", "b", "c"]).await;
+
+    let a = task_id(&app, &token, "a").await;
+    let c = task_id(&app, &token, "c").await;
+
+    for (description, position) in [("before a", before(&a)), ("after c", after(&c)), ("before c", before(&c)), ("after a", after(&a))] {
+        let response = send(&app, Method::POST, "/api/task", Some(&token), Some(json!({ "description": description, "position": position }))).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    }
+
+    assert_eq!(descriptions(&app, &token).await, ["before a", "a", "after a", "b", "before c", "c", "after c"]);
+
+    let response = send(&app, Method::POST, "/api/task", Some(&token), Some(json!({ "description": "x", "position": after(&Uuid::new_v4().to_string()) }))).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn move_to_first_and_last() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_with_tasks(&app, &["a", "b", "c", "d"]).await;
+
+    let a = task_id(&app, &token, "a").await;
+    let c = task_id(&app, &token, "c").await;
+    let d = task_id(&app, &token, "d").await;
+
+    let response = send(&app, Method::POST, &format!("/api/task/{d}/move"), Some(&token), Some(json!({ "position": before(&a) }))).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.json::<Value>()["order"], 1);
+    assert_eq!(descriptions(&app, &token).await, ["d", "a", "b", "c"]);
+
+    let response = send(&app, Method::POST, &format!("/api/task/{a}/move"), Some(&token), Some(json!({ "position": after(&c) }))).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.json::<Value>()["order"], 4);
+    assert_eq!(descriptions(&app, &token).await, ["d", "b", "c", "a"]);
+
+    let response = send(&app, Method::POST, &format!("/api/task/{d}/move"), Some(&token), Some(json!({ "position": "end" }))).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(descriptions(&app, &token).await, ["b", "c", "a", "d"]);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn move_to_current_position() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_with_tasks(&app, &["a", "b", "c"]).await;
+
+    let a = task_id(&app, &token, "a").await;
+    let b = task_id(&app, &token, "b").await;
+    let c = task_id(&app, &token, "c").await;
+
+    for (task, position) in [(&a, before(&b)), (&b, after(&a)), (&c, json!("end"))] {
+        let response = send(&app, Method::POST, &format!("/api/task/{task}/move"), Some(&token), Some(json!({ "position": position }))).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+        assert_eq!(descriptions(&app, &token).await, ["a", "b", "c"]);
+    }
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn move_relative_to_itself() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_with_tasks(&app, &["a", "b"]).await;
+
+    let a = task_id(&app, &token, "a").await;
+
+    for position in [before(&a), after(&a)] {
+        let response = send(&app, Method::POST, &format!("/api/task/{a}/move"), Some(&token), Some(json!({ "position": position }))).await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    }
+
+    assert_eq!(descriptions(&app, &token).await, ["a", "b"]);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn move_unknown_tasks() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_with_tasks(&app, &["a"]).await;
+
+    let a = task_id(&app, &token, "a").await;
+    let unknown = Uuid::new_v4().to_string();
+
+    let response = send(&app, Method::POST, &format!("/api/task/{unknown}/move"), Some(&token), Some(json!({ "position": "end" }))).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+    let response = send(&app, Method::POST, &format!("/api/task/{a}/move"), Some(&token), Some(json!({ "position": before(&unknown) }))).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+    let response = send(&app, Method::POST, "/api/task/not-uuid/move", Some(&token), Some(json!({ "position": "end" }))).await;
+    assert!(response.status.is_client_error());
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn tasks_of_other_user() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let owner = user_with_tasks(&app, &["a"]).await;
+    let other = user_with_tasks(&app, &["b"]).await;
+
+    let a = task_id(&app, &owner, "a").await;
+    let b = task_id(&app, &other, "b").await;
+
+    let response = send(&app, Method::PATCH, &format!("/api/task/{a}"), Some(&other), Some(json!({ "description": "stolen" }))).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+    let response = send(&app, Method::DELETE, &format!("/api/task/{a}"), Some(&other), None).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+    let response = send(&app, Method::POST, &format!("/api/task/{b}/move"), Some(&other), Some(json!({ "position": after(&a) }))).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+    assert_eq!(descriptions(&app, &owner).await, ["a"]);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn update_delete_and_range() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_with_tasks(&app, &["a", "b", "c", "d"]).await;
+
+    let b = task_id(&app, &token, "b").await;
+
+    let response = send(&app, Method::PATCH, &format!("/api/task/{b}"), Some(&token), Some(json!({ "description": "B" }))).await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    let response = send(&app, Method::DELETE, &format!("/api/task/{b}"), Some(&token), None).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.json::<Value>()["description"], "B");
+    assert_eq!(descriptions(&app, &token).await, ["a", "c", "d"]);
+
+    let response = send(&app, Method::GET, "/api/task/range?offset=1&count=5", Some(&token), None).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let range: Vec<Value> = response.json();
+    assert_eq!(range.iter().map(|task| task["description"].as_str().unwrap()).collect::<Vec<_>>(), ["c", "d"]);
+
+    let response = send(&app, Method::GET, "/api/task/range?offset=0&count=0", Some(&token), None).await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn concurrent_reorders_keep_orders_continuous() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+
+    let names: Vec<String> = (0..10).map(|index| format!("task {index}")).collect();
+    let token = user_with_tasks(&app, &names.iter().map(String::as_str).collect::<Vec<_>>()).await;
+
+    let ids: Vec<String> = tasks(&app, &token).await.iter().map(|task| task["id"].as_str().unwrap().to_string()).collect();
+
+    let (app, token, ids) = (&app, &token, &ids);
+
+    let moves = (0..40).map(|index| async move {
+        let task = &ids[index % ids.len()];
+        let target = &ids[(index * 7 + 3) % ids.len()];
+
+        let position = match index % 3 {
+            _ if task == target => json!("end"),
+            0 => before(target),
+            1 => after(target),
+            _ => json!("end"),
+        };
+
+        send(app, Method::POST, &format!("/api/task/{task}/move"), Some(token), Some(json!({ "position": position }))).await
+    });
+
+    let inserts = (0..10).map(|index| async move {
+        let target = &ids[index];
+        send(app, Method::POST, "/api/task", Some(token), Some(json!({ "description": format!("new {index}"), "position": before(target) }))).await
+    });
+
+    let deletes = ids.iter().skip(8).map(|task| async move {
+        send(app, Method::DELETE, &format!("/api/task/{task}"), Some(token), None).await
+    });
+
+    let (moves, inserts, deletes) = futures::join!(join_all(moves), join_all(inserts), join_all(deletes));
+
+    // moves and inserts relative to deleted task may fail with 404, but never with 500
+    for response in moves.iter().chain(&inserts).chain(&deletes) {
+        assert!(response.status == StatusCode::OK || response.status == StatusCode::NOT_FOUND, "{} {}", response.status, response.body);
+    }
+
+    let inserted = inserts.iter().filter(|response| response.status == StatusCode::OK).count();
+    let remaining = tasks(app, token).await;
+    assert_eq!(remaining.len(), 8 + inserted);
+
+    db.close().await;
+}
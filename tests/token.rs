@@ -0,0 +1,159 @@
+mod common;
+
+use actix_http::Request;
+use actix_web::{
+    body::MessageBody,
+    dev::{
+        Service,
+        ServiceResponse
+    },
+    http::{
+        Method,
+        StatusCode
+    },
+    test
+};
+use chrono::{
+    Duration,
+    Utc
+};
+use serde_json::{
+    json,
+    Value
+};
+use uuid::Uuid;
+
+use common::*;
+use todo_list_rs::models::{
+    access_token_hash,
+    NewAccessToken,
+    TokenScope
+};
+
+/// Creates access token with scopes and returns response
+async fn create_token<S, B>(app: &S, token: &str, scopes: &[&str]) -> Value
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody
+{
+    let response = send(app, Method::POST, "/api/user/tokens", Some(token), Some(json!({ "name": "ci", "scopes": scopes }))).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    response.json()
+}
+
+#[actix_web::test]
+async fn access_token_is_listed_and_used() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_with_tasks(&app, &["a"]).await;
+
+    let created = create_token(&app, &token, &["read-only"]).await;
+    let access_token = created["token"].as_str().unwrap();
+    assert!(access_token.starts_with("tdl_pat_"));
+    assert!(created["expires_at"].is_null());
+
+    assert_eq!(descriptions(&app, access_token).await, ["a"]);
+
+    let response = send(&app, Method::GET, "/api/user/tokens", Some(&token), None).await;
+    let tokens: Vec<Value> = response.json();
+    assert_eq!(tokens.len(), 1);
+    assert_eq!(tokens[0]["id"], created["id"]);
+    assert_eq!(tokens[0]["scopes"], json!(["read-only"]));
+    assert!(tokens[0].get("token").is_none());
+    assert!(!tokens[0]["last_used_at"].is_null());
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn access_token_scopes() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_with_tasks(&app, &["a"]).await;
+
+    let read_only = create_token(&app, &token, &["read-only"]).await["token"].as_str().unwrap().to_string();
+    let tasks_write = create_token(&app, &token, &["tasks:write"]).await["token"].as_str().unwrap().to_string();
+    let lists_admin = create_token(&app, &token, &["lists:admin"]).await["token"].as_str().unwrap().to_string();
+
+    let new_task = json!({ "description": "b", "position": "end" });
+
+    let response = send(&app, Method::POST, "/api/task", Some(&read_only), Some(new_task.clone())).await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+
+    let response = send(&app, Method::POST, "/api/task", Some(&tasks_write), Some(new_task)).await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    let response = send(&app, Method::PATCH, "/api/list", Some(&tasks_write), Some(json!({ "name": "renamed" }))).await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+
+    let response = send(&app, Method::PATCH, "/api/list", Some(&lists_admin), Some(json!({ "name": "renamed" }))).await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    assert_eq!(descriptions(&app, &lists_admin).await, ["a", "b"]);
+
+    // access token can't create other tokens
+    let response = send(&app, Method::POST, "/api/user/tokens", Some(&lists_admin), Some(json!({ "name": "ci", "scopes": ["tasks:write"] }))).await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn revoked_token_is_rejected() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_with_tasks(&app, &[]).await;
+    let other = user_token(&app).await;
+
+    let created = create_token(&app, &token, &["read-only"]).await;
+    let access_token = created["token"].as_str().unwrap();
+    let uri = format!("/api/user/tokens/{}", created["id"].as_str().unwrap());
+
+    assert_eq!(send(&app, Method::DELETE, &uri, Some(&other), None).await.status, StatusCode::NOT_FOUND);
+    assert_eq!(send(&app, Method::GET, "/api/task", Some(access_token), None).await.status, StatusCode::OK);
+
+    assert_eq!(send(&app, Method::DELETE, &uri, Some(&token), None).await.status, StatusCode::OK);
+    assert_eq!(send(&app, Method::DELETE, &uri, Some(&token), None).await.status, StatusCode::NOT_FOUND);
+
+    assert_eq!(send(&app, Method::GET, "/api/task", Some(access_token), None).await.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(send(&app, Method::GET, "/api/task", Some("tdl_pat_unknown"), None).await.status, StatusCode::UNAUTHORIZED);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn expired_token_is_rejected() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_with_tasks(&app, &[]).await;
+    let user_id = send(&app, Method::GET, "/api/list", Some(&token), None).await.json::<Value>()["user_id"].as_str().unwrap().parse::<Uuid>().unwrap();
+
+    let new_token = NewAccessToken { name: "old".to_string(), scopes: vec![TokenScope::ReadOnly], expires_in_days: Some(1) };
+    db.storage.access_tokens.insert_access_token(user_id, &access_token_hash("tdl_pat_expired"), &new_token, Some(Utc::now() - Duration::days(1))).await.unwrap();
+    db.storage.access_tokens.insert_access_token(user_id, &access_token_hash("tdl_pat_valid"), &new_token, Some(Utc::now() + Duration::days(1))).await.unwrap();
+
+    assert_eq!(send(&app, Method::GET, "/api/task", Some("tdl_pat_expired"), None).await.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(send(&app, Method::GET, "/api/task", Some("tdl_pat_valid"), None).await.status, StatusCode::OK);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn invalid_new_token() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_token(&app).await;
+
+    let response = send(&app, Method::POST, "/api/user/tokens", Some(&token), Some(json!({ "name": " ", "scopes": [], "expires_in_days": 0 }))).await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let fields = &response.json::<Value>()["fields"];
+    assert!(fields["name"].is_array());
+    assert!(fields["scopes"].is_array());
+    assert!(fields["expires_in_days"].is_array());
+
+    let response = send(&app, Method::POST, "/api/user/tokens", Some(&token), Some(json!({ "name": "ci", "scopes": ["admin"] }))).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+
+    db.close().await;
+}
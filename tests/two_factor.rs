@@ -0,0 +1,162 @@
+mod common;
+
+use actix_http::Request;
+use actix_web::{
+    body::MessageBody,
+    dev::{
+        Service,
+        ServiceResponse
+    },
+    http::{
+        Method,
+        StatusCode
+    },
+    test
+};
+use chrono::Utc;
+use serde_json::{
+    json,
+    Value
+};
+
+use common::*;
+use todo_list_rs::utils::totp;
+
+/// Code of the current time step shifted by `offset` steps
+fn code(secret: &str, offset: u64) -> String {
+    let secret = totp::decode_secret(secret).unwrap();
+    totp::code(&secret, totp::step(Utc::now().timestamp() as u64) + offset)
+}
+
+/// Enables 2FA for the user, returns secret and recovery codes
+async fn enable<S, B>(app: &S, token: &str) -> (String, Vec<String>)
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody
+{
+    let response = send(app, Method::POST, "/api/user/2fa/enroll", Some(token), None).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let secret = response.json::<Value>()["secret"].as_str().unwrap().to_string();
+
+    let response = send(app, Method::POST, "/api/user/2fa/confirm", Some(token), Some(json!({ "code": code(&secret, 0) }))).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let recovery_codes = response.json::<Value>()["recovery_codes"].as_array().unwrap()
+        .iter()
+        .map(|code| code.as_str().unwrap().to_string())
+        .collect();
+
+    (secret, recovery_codes)
+}
+
+async fn challenge<S, B>(app: &S, login_name: &str) -> String
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody
+{
+    let response = login(app, login_name, "password1").await;
+    assert_eq!(response.status, StatusCode::ACCEPTED, "{}", response.body);
+    response.json::<Value>()["challenge_token"].as_str().unwrap().to_string()
+}
+
+async fn login_with_code<S, B>(app: &S, challenge_token: &str, code: &str) -> Response
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody
+{
+    send(app, Method::POST, "/api/user/login/2fa", None, Some(json!({ "challenge_token": challenge_token, "code": code }))).await
+}
+
+#[actix_web::test]
+async fn enrollment() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+
+    register(&app, "ivan", "password1").await;
+    let token = login(&app, "ivan", "password1").await.body;
+
+    let response = send(&app, Method::POST, "/api/user/2fa/enroll", Some(&token), None).await;
+    let enrollment: Value = response.json();
+    let secret = enrollment["secret"].as_str().unwrap();
+    assert_eq!(enrollment["otpauth_uri"], format!("otpauth://totp/todo-list-rs:ivan?secret={secret}&issuer=todo-list-rs&algorithm=SHA1&digits=6&period=30"));
+
+    // not confirmed enrollment doesn't change login
+    assert_eq!(login(&app, "ivan", "password1").await.status, StatusCode::OK);
+
+    let wrong_code = if code(secret, 0) == "000000" { "111111" } else { "000000" };
+    let response = send(&app, Method::POST, "/api/user/2fa/confirm", Some(&token), Some(json!({ "code": wrong_code }))).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+
+    let response = send(&app, Method::POST, "/api/user/2fa/confirm", Some(&token), Some(json!({ "code": code(secret, 0) }))).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.json::<Value>()["recovery_codes"].as_array().unwrap().len(), 10);
+
+    assert_eq!(send(&app, Method::POST, "/api/user/2fa/enroll", Some(&token), None).await.status, StatusCode::BAD_REQUEST);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn login_is_two_step() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+
+    register(&app, "judy", "password1").await;
+    let token = login(&app, "judy", "password1").await.body;
+    let (secret, recovery_codes) = enable(&app, &token).await;
+
+    let challenge_token = challenge(&app, "judy").await;
+
+    // challenge is not a bearer token
+    assert_eq!(send(&app, Method::GET, "/api/list", Some(&challenge_token), None).await.status, StatusCode::UNAUTHORIZED);
+
+    // code of confirmation was used already
+    assert_eq!(login_with_code(&app, &challenge_token, &code(&secret, 0)).await.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(login_with_code(&app, "invalid", &code(&secret, 1)).await.status, StatusCode::UNAUTHORIZED);
+
+    let response = login_with_code(&app, &challenge_token, &code(&secret, 1)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(send(&app, Method::GET, "/api/list", Some(&response.body), None).await.status, StatusCode::NOT_FOUND);
+
+    assert_eq!(login_with_code(&app, &challenge_token, &code(&secret, 1)).await.status, StatusCode::UNAUTHORIZED);
+
+    // recovery code is accepted once, in any case
+    let recovery_code = recovery_codes[0].to_uppercase();
+    assert_eq!(login_with_code(&app, &challenge_token, &recovery_code).await.status, StatusCode::OK);
+    assert_eq!(login_with_code(&app, &challenge_token, &recovery_code).await.status, StatusCode::UNAUTHORIZED);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn disable() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+
+    register(&app, "mallory", "password1").await;
+    let token = login(&app, "mallory", "password1").await.body;
+    let (_, recovery_codes) = enable(&app, &token).await;
+
+    let response = send(&app, Method::POST, "/api/user/2fa/disable", Some(&token), Some(json!({ "code": "invalid" }))).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+
+    let response = send(&app, Method::POST, "/api/user/2fa/disable", Some(&token), Some(json!({ "code": recovery_codes[1] }))).await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    assert_eq!(login(&app, "mallory", "password1").await.status, StatusCode::OK);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn access_token_cant_enroll() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let token = user_token(&app).await;
+
+    let response = send(&app, Method::POST, "/api/user/tokens", Some(&token), Some(json!({ "name": "ci", "scopes": ["lists:admin"] }))).await;
+    let access_token = response.json::<Value>()["token"].as_str().unwrap().to_string();
+
+    assert_eq!(send(&app, Method::POST, "/api/user/2fa/enroll", Some(&access_token), None).await.status, StatusCode::FORBIDDEN);
+
+    db.close().await;
+}
@@ -0,0 +1,264 @@
+mod common;
+
+use actix_web::{
+    http::{
+        Method,
+        StatusCode
+    },
+    test
+};
+use chrono::{
+    Duration,
+    Utc
+};
+use jsonwebtoken::{
+    encode,
+    EncodingKey,
+    Header
+};
+use serde_json::{
+    json,
+    Value
+};
+use uuid::Uuid;
+
+use common::*;
+use todo_list_rs::models::UserClaim;
+
+fn token(user_id: Uuid, key: &str, expires_in: Duration) -> String {
+    let claim = UserClaim { exp: (Utc::now() + expires_in).timestamp() as usize, user_id, session_version: 0 };
+    encode(&Header::default(), &claim, &EncodingKey::from_secret(key.as_bytes())).unwrap()
+}
+
+#[actix_web::test]
+async fn register_and_login() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+
+    let response = register(&app, "alice", "password1").await;
+    assert_eq!(response.status, StatusCode::OK);
+    let user_id: Uuid = response.body.parse().unwrap();
+
+    let response = login(&app, "alice", "password1").await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    let response = send(&app, Method::POST, "/api/list", Some(&response.body), Some(json!({ "name": "alice list" }))).await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    let response = send(&app, Method::GET, "/api/list", Some(&token(user_id, BEARER_KEY, Duration::hours(1))), None).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.json::<serde_json::Value>()["name"], "alice list");
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn register_existing_login() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+
+    assert_eq!(register(&app, "bob", "password1").await.status, StatusCode::OK);
+    assert_eq!(register(&app, "bob", "password2").await.status, StatusCode::BAD_REQUEST);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn register_invalid_user() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+
+    let response = register(&app, "bad login", "short").await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let fields = &response.json::<serde_json::Value>()["fields"];
+    assert!(fields["login"].is_array());
+    assert!(fields["password"].is_array());
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn login_with_wrong_password() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+
+    register(&app, "carol", "password1").await;
+
+    let wrong_password = login(&app, "carol", "password2").await;
+    let unknown_login = login(&app, "unknown", "password1").await;
+
+    assert_eq!(wrong_password.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(unknown_login.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(wrong_password.json::<Value>()["detail"], unknown_login.json::<Value>()["detail"]);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn request_without_authorization_header() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+
+    let response = send(&app, Method::GET, "/api/task", None, None).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn request_with_invalid_token() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+    let user_id = Uuid::new_v4();
+
+    let invalid_tokens = [
+        "not a token".to_string(),
+        token(user_id, "other_key", Duration::hours(1)),
+        token(user_id, BEARER_KEY, Duration::hours(-1)),
+    ];
+
+    for invalid_token in invalid_tokens {
+        let response = send(&app, Method::GET, "/api/list", Some(&invalid_token), None).await;
+        assert_eq!(response.status, StatusCode::UNAUTHORIZED, "{invalid_token}");
+    }
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn request_with_malformed_authorization_header() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+
+    for header in ["Basic dXNlcjpwYXNzd29yZA==", "", "Bearer"] {
+        let request = test::TestRequest::get()
+            .uri("/api/list")
+            .insert_header(("Authorization", header))
+            .to_request();
+
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED, "{header}");
+    }
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn user_profile() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+
+    let user_id = register(&app, "alice", "password1").await.body;
+    let token = login(&app, "alice", "password1").await.body;
+
+    let response = send(&app, Method::GET, "/api/user/me", Some(&token), None).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.json::<Value>(), json!({ "id": user_id, "login": "alice", "email": null, "email_verified": false, "two_factor_enabled": false }));
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn change_password_revokes_sessions() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+
+    register(&app, "alice", "password1").await;
+    let first_session = login(&app, "alice", "password1").await.body;
+    let second_session = login(&app, "alice", "password1").await.body;
+
+    let response = send(&app, Method::PATCH, "/api/user/password", Some(&first_session), Some(json!({ "old_password": "password2", "new_password": "password3" }))).await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+
+    let response = send(&app, Method::PATCH, "/api/user/password", Some(&first_session), Some(json!({ "old_password": "password1", "new_password": "short" }))).await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let response = send(&app, Method::PATCH, "/api/user/password", Some(&first_session), Some(json!({ "old_password": "password1", "new_password": "password3" }))).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let new_session = response.body;
+
+    for token in [&first_session, &second_session] {
+        assert_eq!(send(&app, Method::GET, "/api/user/me", Some(token), None).await.status, StatusCode::UNAUTHORIZED);
+    }
+    assert_eq!(send(&app, Method::GET, "/api/user/me", Some(&new_session), None).await.status, StatusCode::OK);
+
+    assert_eq!(login(&app, "alice", "password1").await.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(login(&app, "alice", "password3").await.status, StatusCode::OK);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn change_login_must_be_unique() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+
+    register(&app, "alice", "password1").await;
+    register(&app, "bob", "password1").await;
+    let token = login(&app, "bob", "password1").await.body;
+
+    let response = send(&app, Method::PATCH, "/api/user/login", Some(&token), Some(json!({ "login": "alice" }))).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+
+    let response = send(&app, Method::PATCH, "/api/user/login", Some(&token), Some(json!({ "login": "bad login" }))).await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    // own login is not taken
+    let response = send(&app, Method::PATCH, "/api/user/login", Some(&token), Some(json!({ "login": "bob" }))).await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    let response = send(&app, Method::PATCH, "/api/user/login", Some(&token), Some(json!({ "login": "carol" }))).await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    // sessions are kept
+    let response = send(&app, Method::GET, "/api/user/me", Some(&token), None).await;
+    assert_eq!(response.json::<Value>()["login"], "carol");
+
+    assert_eq!(login(&app, "bob", "password1").await.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(login(&app, "carol", "password1").await.status, StatusCode::OK);
+    assert_eq!(register(&app, "bob", "password1").await.status, StatusCode::OK);
+
+    db.close().await;
+}
+
+#[actix_web::test]
+async fn delete_user_removes_data() {
+    let db = TestDb::new().await;
+    let app = test::init_service(app(&db.storage)).await;
+
+    register(&app, "alice", "password1").await;
+    let token = login(&app, "alice", "password1").await.body;
+
+    let response = send(&app, Method::POST, "/api/list", Some(&token), Some(json!({ "name": "alice list" }))).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let response = send(&app, Method::POST, "/api/task", Some(&token), Some(json!({ "description": "task", "position": "end" }))).await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    let response = send(&app, Method::POST, "/api/user/tokens", Some(&token), Some(json!({ "name": "ci", "scopes": ["read-only"] }))).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let access_token = response.json::<Value>()["token"].as_str().unwrap().to_string();
+
+    // access token can't manage account
+    let response = send(&app, Method::DELETE, "/api/user", Some(&access_token), Some(json!({ "password": "password1" }))).await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+
+    let response = send(&app, Method::DELETE, "/api/user", Some(&token), Some(json!({ "password": "password2" }))).await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+
+    let response = send(&app, Method::DELETE, "/api/user", Some(&token), Some(json!({ "password": "password1" }))).await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    for token in [&token, &access_token] {
+        assert_eq!(send(&app, Method::GET, "/api/list", Some(token), None).await.status, StatusCode::UNAUTHORIZED);
+    }
+    assert_eq!(login(&app, "alice", "password1").await.status, StatusCode::UNAUTHORIZED);
+
+    // login is free, new account has no data of the deleted one
+    assert_eq!(register(&app, "alice", "password1").await.status, StatusCode::OK);
+    let token = login(&app, "alice", "password1").await.body;
+    let response = send(&app, Method::GET, "/api/list", Some(&token), None).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+    db.close().await;
+}